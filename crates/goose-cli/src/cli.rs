@@ -1,14 +1,35 @@
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
 
+use goose::config::migrations::migrate;
+use goose::config::schema::config_schema;
 use goose::config::Config;
+use goose::usage::UsageGroupBy;
 
 use crate::commands::agent_version::AgentCommand;
+use crate::commands::audit::handle_audit;
 use crate::commands::bench::{list_selectors, run_benchmark};
+use crate::commands::code::{handle_code_index, handle_code_outline, handle_code_search};
 use crate::commands::configure::handle_configure;
+use crate::commands::doctor::handle_doctor;
 use crate::commands::info::handle_info;
+use crate::commands::knowledge::{
+    handle_knowledge_add, handle_knowledge_list, handle_knowledge_reindex, handle_knowledge_remove,
+    handle_knowledge_search,
+};
 use crate::commands::mcp::run_server;
-use crate::commands::session::handle_session_list;
+use crate::commands::memory::{
+    handle_memory_add, handle_memory_delete, handle_memory_edit, handle_memory_extract,
+    handle_memory_list, handle_memory_search,
+};
+use crate::commands::recipe::{handle_recipe_run, handle_recipe_validate};
+use crate::commands::schedule::{handle_schedule_list, handle_schedule_run, handle_schedule_start};
+use crate::commands::session::{
+    handle_session_archive, handle_session_export, handle_session_fork, handle_session_import,
+    handle_session_list, handle_session_purge, handle_session_replay, handle_session_search,
+    handle_session_stats,
+};
+use crate::commands::usage::handle_usage;
 use crate::logging::setup_logging;
 use crate::session;
 use crate::session::build_session;
@@ -22,6 +43,13 @@ struct Cli {
     command: Option<Command>,
 }
 
+fn parse_metadata(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
 #[derive(Args)]
 #[group(required = false, multiple = false)]
 struct Identifier {
@@ -69,6 +97,414 @@ enum SessionCommand {
         )]
         format: String,
     },
+
+    #[command(
+        about = "Export sessions as JSONL",
+        long_about = "Export a session, by default the most recent one, in the goose format to import it on another machine, or sessions in the OpenAI chat fine-tuning format to build a dataset from them."
+    )]
+    Export {
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+
+        #[arg(
+            long,
+            help = "Export every session, with --format openai",
+            conflicts_with_all = ["name", "path"]
+        )]
+        all: bool,
+
+        #[arg(
+            short,
+            long,
+            help = "Output format (goose, openai, markdown, html)",
+            default_value = "goose"
+        )]
+        format: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "File to write to instead of stdout"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Search past sessions",
+        long_about = "Find the sessions whose messages contain the given words, that called a tool or that touched a file, printing each matching message with the part that matched in [brackets]."
+    )]
+    Search {
+        #[arg(
+            value_name = "QUERY",
+            help = "Words to find in the messages, in any order"
+        )]
+        query: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "TOOL",
+            help = "Only messages calling this tool, e.g. shell"
+        )]
+        tool: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Only messages calling a tool on this file, or on paths containing it"
+        )]
+        path: Option<String>,
+
+        #[arg(short, long, help = "Most matches to show", default_value_t = 20)]
+        limit: usize,
+
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
+    #[command(
+        about = "Show where a session's time and money went",
+        long_about = "Show a session's turns, tool calls by tool, tokens, cost, duration and the files its tools worked on, by default for the most recent session."
+    )]
+    Stats {
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
+    #[command(
+        about = "Fork a session into a new one",
+        long_about = "Start a new session from a session's history, by default the most recent session's, to try another approach without changing the original. The fork keeps the messages before --at, or all of them."
+    )]
+    Fork {
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+
+        #[arg(
+            long,
+            value_name = "MESSAGE",
+            help = "Fork before this message, counting from 0, as shown by `session search`"
+        )]
+        at: Option<usize>,
+
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Name for the fork, by default a new session id"
+        )]
+        into: Option<String>,
+    },
+
+    #[command(
+        about = "Move sessions to the session archive",
+        long_about = "Upload a session to the bucket in GOOSE_SESSION_ARCHIVE_URL and keep only its metadata here, or without a session, every session not updated for GOOSE_SESSION_ARCHIVE_AFTER_DAYS. Archived sessions are downloaded again when they are resumed."
+    )]
+    Archive {
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+    },
+
+    #[command(
+        about = "Remove old sessions",
+        long_about = "Remove the oldest sessions past the given limits, or by default past GOOSE_SESSION_MAX_COUNT, GOOSE_SESSION_MAX_AGE_DAYS and GOOSE_SESSION_MAX_SIZE_MB. Sessions updated in the last hour are kept."
+    )]
+    Purge {
+        #[arg(
+            long,
+            value_name = "COUNT",
+            help = "Keep only the most recent sessions"
+        )]
+        keep: Option<usize>,
+
+        #[arg(
+            long,
+            value_name = "DAYS",
+            help = "Remove sessions not updated for this many days"
+        )]
+        older_than: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "MB",
+            help = "Remove the oldest sessions until the rest take at most this many megabytes"
+        )]
+        max_size: Option<u64>,
+
+        #[arg(long, help = "Only list the sessions that would be removed")]
+        dry_run: bool,
+    },
+
+    #[command(about = "Import a session exported in the goose format")]
+    Import {
+        #[arg(value_name = "FILE", help = "The exported session")]
+        file: PathBuf,
+
+        #[arg(
+            short,
+            long,
+            value_name = "NAME",
+            help = "Name for the imported session, by default the file's name"
+        )]
+        name: Option<String>,
+    },
+
+    #[command(
+        about = "Replay a recorded session",
+        long_about = "Re-drive the agent through a session recorded with GOOSE_RECORD_SESSIONS, by default the most recent one, answering completions and tool calls from the recording, without calling the provider or running any tool. Reports where the replay diverges from the recorded session."
+    )]
+    Replay {
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    #[command(
+        about = "Check the configuration for problems",
+        long_about = "Check the config files for unknown keys, the provider for missing settings, an unreachable host or an unknown model, and the enabled extensions for missing commands or servers, suggesting fixes."
+    )]
+    Doctor {},
+
+    #[command(
+        about = "Print the JSON Schema of the config file",
+        long_about = "Print the JSON Schema of the config file, including the settings of every provider, for editors to complete and check config.yaml."
+    )]
+    Schema {},
+
+    #[command(
+        about = "Trust this project's config file",
+        long_about = "Trust the .goose/config.yaml found in this directory or its parents, as it is now, so goose uses its values. It has to be trusted again after it changes."
+    )]
+    Trust {},
+}
+
+#[derive(Subcommand)]
+enum RecipeCommand {
+    #[command(
+        about = "Run a recipe",
+        long_about = "Run a recipe from a YAML file or URL: its prompt and then each of its steps, in a new session, with only the tools it allows."
+    )]
+    Run {
+        #[arg(help = "The recipe's file or http(s) URL")]
+        source: String,
+
+        #[arg(
+            long = "param",
+            value_name = "KEY=VALUE",
+            value_parser = parse_metadata,
+            help = "Set a parameter of the recipe (can be specified multiple times)",
+            action = clap::ArgAction::Append
+        )]
+        params: Vec<(String, String)>,
+
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+
+        #[arg(
+            short = 's',
+            long = "interactive",
+            help = "Continue in interactive mode after the recipe's steps"
+        )]
+        interactive: bool,
+
+        #[arg(
+            long,
+            help = "Enable debug output mode with full content and no truncation"
+        )]
+        debug: bool,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Write the result to FILE as JSON, - for stdout",
+            long_help = "Write the value the recipe's last answer gave for its response schema to FILE as JSON, or to stdout for -, which is the default. Fails if the agent didn't give a value that matches."
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Check a recipe",
+        long_about = "Check that a recipe from a YAML file or URL is valid, and list the parameters it takes."
+    )]
+    Validate {
+        #[arg(help = "The recipe's file or http(s) URL")]
+        source: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommand {
+    #[command(about = "List the scheduled jobs with their next and last runs")]
+    List {
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
+    #[command(
+        about = "Run a scheduled job now",
+        long_about = "Run a scheduled job now, whatever its schedule, saving it as a session and notifying of it as a scheduled run would."
+    )]
+    Run {
+        #[arg(help = "The name of the job")]
+        name: String,
+    },
+
+    #[command(
+        about = "Run the scheduled jobs as they come due",
+        long_about = "Keep running in the foreground, running each enabled job in the config's 'schedules' when its cron expression next matches, until stopped with Ctrl+C. Runs missed while the scheduler isn't running aren't made up for."
+    )]
+    Start {},
+}
+
+#[derive(Subcommand)]
+enum KnowledgeCommand {
+    #[command(about = "List the indexed files and directories")]
+    List {},
+
+    #[command(
+        about = "Index files or directories",
+        long_about = "Split the text files under each path into passages and embed them, skipping files that .gitignore or .gooseignore exclude. Goose draws on them when GOOSE_KNOWLEDGE is set to prompt or tool."
+    )]
+    Add {
+        #[arg(required = true, help = "The files or directories to index")]
+        paths: Vec<PathBuf>,
+    },
+
+    #[command(about = "Remove a file or directory from the index")]
+    Remove {
+        #[arg(help = "The file or directory, as it was added")]
+        path: PathBuf,
+    },
+
+    #[command(about = "Index the files that changed since they were indexed")]
+    Reindex {},
+
+    #[command(about = "Find the indexed passages most like a query")]
+    Search {
+        #[arg(help = "What to search the documents for")]
+        query: String,
+
+        #[arg(short, long, help = "The most passages to show", default_value = "5")]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum CodeCommand {
+    #[command(
+        about = "Index the definitions in the project",
+        long_about = "Parse the source files of the project of the current directory that changed since they were indexed, skipping files that .gitignore or .gooseignore exclude. Goose searches them with the platform__search_code tool when GOOSE_CODE_INDEX is true."
+    )]
+    Index {},
+
+    #[command(about = "Find the definitions named like a query or about it")]
+    Search {
+        #[arg(help = "A name, or words about what to find")]
+        query: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Only find this kind of definition, e.g. function, struct or class"
+        )]
+        kind: Option<String>,
+
+        #[arg(
+            short,
+            long,
+            help = "The most definitions to show",
+            default_value = "10"
+        )]
+        limit: usize,
+    },
+
+    #[command(about = "List the definitions in a file or directory")]
+    Outline {
+        #[arg(help = "The file or directory, the whole project if not given")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryCommand {
+    #[command(about = "List the memories, the most recently updated first")]
+    List {
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
+    #[command(about = "Find the memories most like a query")]
+    Search {
+        #[arg(help = "What to search the memories for")]
+        query: String,
+
+        #[arg(short, long, help = "The most memories to show", default_value = "10")]
+        limit: usize,
+
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
+    #[command(about = "Remember something")]
+    Add {
+        #[arg(help = "What to remember, in a sentence")]
+        content: String,
+
+        #[arg(long, help = "Remember it as a preference rather than a fact")]
+        preference: bool,
+    },
+
+    #[command(about = "Change what a memory says")]
+    Edit {
+        #[arg(help = "The memory's id, or the start of it")]
+        id: String,
+
+        #[arg(help = "What the memory should say")]
+        content: String,
+    },
+
+    #[command(about = "Forget a memory")]
+    Delete {
+        #[arg(help = "The memory's id, or the start of it")]
+        id: String,
+    },
+
+    #[command(
+        about = "Extract memories from a session",
+        long_about = "Have the configured model extract the facts and preferences worth remembering from the messages of a session, by default the most recent one, that haven't been remembered from yet. When GOOSE_MEMORY is on this happens as sessions end."
+    )]
+    Extract {
+        #[command(flatten)]
+        identifier: Option<Identifier>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -77,6 +513,13 @@ enum Command {
     #[command(about = "Configure Goose settings")]
     Configure {},
 
+    /// Inspect the Goose configuration
+    #[command(about = "Inspect the goose configuration")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
     /// Display Goose configuration information
     #[command(about = "Display Goose information")]
     Info {
@@ -137,6 +580,15 @@ enum Command {
             value_delimiter = ','
         )]
         builtin: Vec<String>,
+
+        /// The persona to work as
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Work as one of the personas in the config",
+            long_help = "Start in one of the personas configured under 'personas', with its instructions, model and tool policy, instead of the one in GOOSE_PERSONA. A resumed session keeps its persona unless given another."
+        )]
+        persona: Option<String>,
     },
 
     /// Execute commands from an instruction file
@@ -212,11 +664,177 @@ enum Command {
             value_delimiter = ','
         )]
         builtin: Vec<String>,
+
+        /// Files to attach to the input
+        #[arg(
+            long = "attach",
+            value_name = "FILE",
+            help = "Attach a file to the input (can be specified multiple times)",
+            long_help = "Attach a file, such as a CSV to analyze, to the input. Text files are included in the message, images and PDFs are sent to providers that read them. Can be specified multiple times.",
+            action = clap::ArgAction::Append
+        )]
+        attach: Vec<PathBuf>,
+
+        /// Metadata values to set on the session
+        #[arg(
+            long = "meta",
+            value_name = "KEY=VALUE",
+            value_parser = parse_metadata,
+            help = "Set a metadata value on the session (can be specified multiple times)",
+            long_help = "Set a metadata value on the session, such as a ticket ID or the system that started the run, so it can be matched up with goose's session later. Can be specified multiple times.",
+            action = clap::ArgAction::Append
+        )]
+        meta: Vec<(String, String)>,
+
+        /// The persona to work as
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Work as one of the personas in the config",
+            long_help = "Start in one of the personas configured under 'personas', with its instructions, model and tool policy, instead of the one in GOOSE_PERSONA. A resumed session keeps its persona unless given another."
+        )]
+        persona: Option<String>,
+
+        /// A JSON schema the result has to match
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Make the result a JSON value matching the schema in FILE",
+            long_help = "Have the agent end with a value matching the JSON schema in FILE, checked and repaired until it matches, and write it as JSON when the run is done. The schema can be written in JSON or YAML."
+        )]
+        response_schema: Option<PathBuf>,
+
+        /// Where to write the result
+        #[arg(
+            long,
+            value_name = "FILE",
+            requires = "response_schema",
+            help = "Write the result to FILE as JSON instead of stdout"
+        )]
+        output: Option<PathBuf>,
     },
 
     /// List available agent versions
     Agents(AgentCommand),
 
+    /// Run repeatable tasks defined in YAML
+    #[command(about = "Run repeatable tasks defined in YAML recipes")]
+    Recipe {
+        #[command(subcommand)]
+        command: RecipeCommand,
+    },
+
+    /// Run prompts unattended on a schedule
+    #[command(about = "Run prompts unattended on a schedule")]
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+
+    /// Index local documents for goose to draw on
+    #[command(about = "Index local documents for goose to draw on")]
+    Knowledge {
+        #[command(subcommand)]
+        command: KnowledgeCommand,
+    },
+
+    /// Index the definitions in the project for goose to search
+    #[command(about = "Index the definitions in the project for goose to search")]
+    Code {
+        #[command(subcommand)]
+        command: CodeCommand,
+    },
+
+    /// Manage the facts and preferences remembered across sessions
+    #[command(about = "Manage the facts and preferences remembered across sessions")]
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+
+    /// List the tool calls recorded in the audit log
+    #[command(about = "List the tool calls recorded in the audit log")]
+    Audit {
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Only include calls from this session"
+        )]
+        session: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "TOOL",
+            help = "Only include calls to this tool, or to the tools of this extension"
+        )]
+        tool: Option<String>,
+
+        #[arg(
+            short,
+            long,
+            value_name = "DAYS",
+            help = "Only include calls from the last DAYS days"
+        )]
+        days: Option<u32>,
+
+        #[arg(
+            long,
+            value_name = "OUTCOME",
+            help = "Only include calls that ended this way (success, error, not_called)"
+        )]
+        outcome: Option<String>,
+
+        #[arg(
+            short,
+            long,
+            help = "Show at most this many of the latest calls",
+            default_value = "50"
+        )]
+        limit: usize,
+
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
+    /// Report token usage and cost
+    #[command(about = "Report token usage and cost")]
+    Usage {
+        /// Number of days to report on
+        #[arg(
+            short,
+            long,
+            value_name = "DAYS",
+            help = "Only include usage from the last DAYS days",
+            long_help = "Only include usage from the last DAYS days. Includes all recorded usage if not set."
+        )]
+        days: Option<u32>,
+
+        /// Dimension to group usage by
+        #[arg(
+            short = 'b',
+            long = "by",
+            value_name = "GROUP",
+            help = "Group usage by day, provider, model, session or role",
+            value_enum,
+            ignore_case = true,
+            default_value = "day"
+        )]
+        group_by: CliUsageGroup,
+
+        #[arg(
+            short,
+            long,
+            help = "Output format (text, json)",
+            default_value = "text"
+        )]
+        format: String,
+    },
+
     /// Update the Goose CLI version
     #[command(about = "Update the goose CLI version")]
     Update {
@@ -302,14 +920,73 @@ enum CliProviderVariant {
     Ollama,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum CliUsageGroup {
+    Day,
+    Provider,
+    Model,
+    Session,
+    Role,
+}
+
+impl From<CliUsageGroup> for UsageGroupBy {
+    fn from(group: CliUsageGroup) -> Self {
+        match group {
+            CliUsageGroup::Day => UsageGroupBy::Day,
+            CliUsageGroup::Provider => UsageGroupBy::Provider,
+            CliUsageGroup::Model => UsageGroupBy::Model,
+            CliUsageGroup::Session => UsageGroupBy::Session,
+            CliUsageGroup::Role => UsageGroupBy::Role,
+        }
+    }
+}
+
 pub async fn cli() -> Result<()> {
     let cli = Cli::parse();
 
+    // Bring a config file from an older version up to date before anything reads it
+    match migrate(Config::global(), &goose::providers::providers()) {
+        Ok(Some(report)) => {
+            eprintln!("Updated the config file for this version of goose:");
+            for description in &report.applied {
+                eprintln!("  - {}", description);
+            }
+            eprintln!("The previous config file is at {}", report.backup.display());
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to update the config file: {}", e),
+    }
+
     match cli.command {
         Some(Command::Configure {}) => {
             let _ = handle_configure().await;
             return Ok(());
         }
+        Some(Command::Config {
+            command: ConfigCommand::Doctor {},
+        }) => {
+            if !handle_doctor().await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Schema {},
+        }) => {
+            let schema = config_schema(&goose::providers::providers());
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Trust {},
+        }) => {
+            let config = Config::global();
+            config.trust_project()?;
+            if let Some(path) = config.project_path() {
+                println!("Trusted {}", path);
+            }
+            return Ok(());
+        }
         Some(Command::Info { verbose }) => {
             handle_info(verbose)?;
             return Ok(());
@@ -324,12 +1001,65 @@ pub async fn cli() -> Result<()> {
             debug,
             extension,
             builtin,
+            persona,
         }) => {
             match command {
                 Some(SessionCommand::List { verbose, format }) => {
                     handle_session_list(verbose, format)?;
                     return Ok(());
                 }
+                Some(SessionCommand::Export {
+                    identifier,
+                    all,
+                    format,
+                    output,
+                }) => {
+                    handle_session_export(identifier.map(extract_identifier), all, format, output)?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Search {
+                    query,
+                    tool,
+                    path,
+                    limit,
+                    format,
+                }) => {
+                    handle_session_search(query, tool, path, limit, format)?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Stats { identifier, format }) => {
+                    handle_session_stats(identifier.map(extract_identifier), format)?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Fork {
+                    identifier,
+                    at,
+                    into,
+                }) => {
+                    handle_session_fork(identifier.map(extract_identifier), at, into)?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Archive { identifier }) => {
+                    handle_session_archive(identifier.map(extract_identifier))?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Purge {
+                    keep,
+                    older_than,
+                    max_size,
+                    dry_run,
+                }) => {
+                    handle_session_purge(keep, older_than, max_size, dry_run)?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Import { file, name }) => {
+                    handle_session_import(file, name)?;
+                    return Ok(());
+                }
+                Some(SessionCommand::Replay { identifier }) => {
+                    handle_session_replay(identifier.map(extract_identifier), debug).await?;
+                    return Ok(());
+                }
                 None => {
                     // Run session command by default
                     let mut session = build_session(
@@ -337,6 +1067,7 @@ pub async fn cli() -> Result<()> {
                         resume,
                         extension,
                         builtin,
+                        persona,
                         debug,
                     )
                     .await;
@@ -358,6 +1089,11 @@ pub async fn cli() -> Result<()> {
             debug,
             extension,
             builtin,
+            attach,
+            meta,
+            persona,
+            response_schema,
+            output,
         }) => {
             let contents = match (instructions, input_text) {
                 (Some(file), _) if file == "-" => {
@@ -386,6 +1122,7 @@ pub async fn cli() -> Result<()> {
                 resume,
                 extension,
                 builtin,
+                persona,
                 debug,
             )
             .await;
@@ -395,10 +1132,34 @@ pub async fn cli() -> Result<()> {
                 None,
             )?;
 
+            for path in attach {
+                if let Err(e) = session.attach(&path) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            if !meta.is_empty() {
+                session
+                    .set_metadata(
+                        meta.into_iter()
+                            .map(|(key, value)| (key, value.into()))
+                            .collect(),
+                    )
+                    .await?;
+            }
+
+            if let Some(path) = &response_schema {
+                session.set_response_schema(path).await?;
+            }
+
             if interactive {
                 session.interactive(Some(contents)).await?;
             } else {
                 session.headless(contents).await?;
+                session.remember().await;
+            }
+            if response_schema.is_some() {
+                session.write_final_output(output.as_deref()).await?;
             }
 
             return Ok(());
@@ -407,6 +1168,99 @@ pub async fn cli() -> Result<()> {
             cmd.run()?;
             return Ok(());
         }
+        Some(Command::Recipe { command }) => {
+            match command {
+                RecipeCommand::Run {
+                    source,
+                    params,
+                    identifier,
+                    interactive,
+                    debug,
+                    output,
+                } => {
+                    handle_recipe_run(
+                        source,
+                        params,
+                        identifier.map(extract_identifier),
+                        interactive,
+                        debug,
+                        output,
+                    )
+                    .await?
+                }
+                RecipeCommand::Validate { source } => handle_recipe_validate(source).await?,
+            }
+            return Ok(());
+        }
+        Some(Command::Schedule { command }) => {
+            match command {
+                ScheduleCommand::List { format } => handle_schedule_list(format)?,
+                ScheduleCommand::Run { name } => handle_schedule_run(name).await?,
+                ScheduleCommand::Start {} => handle_schedule_start().await?,
+            }
+            return Ok(());
+        }
+        Some(Command::Knowledge { command }) => {
+            match command {
+                KnowledgeCommand::List {} => handle_knowledge_list()?,
+                KnowledgeCommand::Add { paths } => handle_knowledge_add(paths).await?,
+                KnowledgeCommand::Remove { path } => handle_knowledge_remove(path).await?,
+                KnowledgeCommand::Reindex {} => handle_knowledge_reindex().await?,
+                KnowledgeCommand::Search { query, limit } => {
+                    handle_knowledge_search(query, limit).await?
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Code { command }) => {
+            match command {
+                CodeCommand::Index {} => handle_code_index()?,
+                CodeCommand::Search { query, kind, limit } => {
+                    handle_code_search(query, kind, limit)?
+                }
+                CodeCommand::Outline { path } => handle_code_outline(path)?,
+            }
+            return Ok(());
+        }
+        Some(Command::Memory { command }) => {
+            match command {
+                MemoryCommand::List { format } => handle_memory_list(format)?,
+                MemoryCommand::Search {
+                    query,
+                    limit,
+                    format,
+                } => handle_memory_search(query, limit, format).await?,
+                MemoryCommand::Add {
+                    content,
+                    preference,
+                } => handle_memory_add(content, preference).await?,
+                MemoryCommand::Edit { id, content } => handle_memory_edit(id, content).await?,
+                MemoryCommand::Delete { id } => handle_memory_delete(id).await?,
+                MemoryCommand::Extract { identifier } => {
+                    handle_memory_extract(identifier.map(extract_identifier)).await?
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Audit {
+            session,
+            tool,
+            days,
+            outcome,
+            limit,
+            format,
+        }) => {
+            handle_audit(session, tool, days, outcome, limit, format)?;
+            return Ok(());
+        }
+        Some(Command::Usage {
+            days,
+            group_by,
+            format,
+        }) => {
+            handle_usage(days, group_by.into(), format)?;
+            return Ok(());
+        }
         Some(Command::Update {
             canary,
             reconfigure,
@@ -468,7 +1322,7 @@ pub async fn cli() -> Result<()> {
                 return Ok(());
             } else {
                 // Run session command by default
-                let mut session = build_session(None, false, vec![], vec![], false).await;
+                let mut session = build_session(None, false, vec![], vec![], None, false).await;
                 setup_logging(
                     session.session_file().file_stem().and_then(|s| s.to_str()),
                     None,
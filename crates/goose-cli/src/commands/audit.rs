@@ -0,0 +1,91 @@
+use anyhow::Result;
+use chrono::{Duration, Local};
+use console::style;
+use goose::audit::{AuditLog, AuditQuery, AuditRecord, Outcome};
+
+/// How much of the arguments a line of the text output shows
+const MAX_ARGUMENTS_LEN: usize = 80;
+
+pub fn handle_audit(
+    session: Option<String>,
+    tool: Option<String>,
+    days: Option<u32>,
+    outcome: Option<String>,
+    limit: usize,
+    format: String,
+) -> Result<()> {
+    let query = AuditQuery {
+        session_id: session,
+        tool,
+        since: days.map(|days| chrono::Utc::now() - Duration::days(days as i64)),
+        outcome: outcome
+            .map(|outcome| outcome.parse::<Outcome>())
+            .transpose()?,
+        limit: Some(limit),
+    };
+    let log = AuditLog::open(&AuditLog::default_path())?;
+    let records = log.query(&query)?;
+
+    match format.as_str() {
+        "json" => {
+            // One call a line, like the log it comes from
+            for record in &records {
+                println!("{}", serde_json::to_string(record)?);
+            }
+        }
+        _ => {
+            if records.is_empty() {
+                println!("No tool calls recorded");
+                return Ok(());
+            }
+            for record in &records {
+                print_record(record);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_record(record: &AuditRecord) {
+    let outcome = match record.outcome {
+        Outcome::Running => style("running").yellow(),
+        Outcome::Success => style("ok").green(),
+        Outcome::Error => style("error").red(),
+        Outcome::NotCalled => style("not called").yellow(),
+    };
+    let mut duration = record
+        .duration_ms
+        .map(|ms| format!(" in {:.1}s", ms as f64 / 1000.0))
+        .unwrap_or_default();
+    if let Some(attempts) = record.attempts.filter(|attempts| *attempts > 1) {
+        duration.push_str(&format!(", {} attempts", attempts));
+    }
+    println!(
+        "{} {} {} ({}{}) {}",
+        style(
+            record
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        )
+        .dim(),
+        style(&record.tool).cyan(),
+        outcome,
+        record.approval,
+        duration,
+        style(record.session_id.as_deref().unwrap_or("")).dim(),
+    );
+
+    let mut arguments = record.arguments.to_string();
+    if arguments.chars().count() > MAX_ARGUMENTS_LEN {
+        arguments = arguments
+            .chars()
+            .take(MAX_ARGUMENTS_LEN)
+            .collect::<String>()
+            + "...";
+    }
+    println!("    {}", arguments);
+    if let Some(error) = &record.error {
+        println!("    {}", style(error).red());
+    }
+}
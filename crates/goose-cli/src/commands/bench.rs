@@ -86,6 +86,7 @@ async fn run_eval(
         false,
         requirements.external,
         requirements.builtin,
+        None,
         false,
     )
     .await;
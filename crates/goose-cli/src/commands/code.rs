@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use console::style;
+use goose::code_index::{project_root, CodeIndex, Definition, SymbolKind};
+use std::path::{Path, PathBuf};
+
+/// The index, brought up to date with the project of the current directory
+fn open_index() -> Result<(CodeIndex, PathBuf)> {
+    let root = project_root(&std::env::current_dir()?);
+    let index = CodeIndex::open(&CodeIndex::default_path())?;
+    let report = index.update(&root)?;
+    if report.indexed + report.removed > 0 {
+        println!(
+            "{}",
+            style(format!(
+                "Indexed {} changed files, removed {}",
+                report.indexed, report.removed
+            ))
+            .dim()
+        );
+    }
+    Ok((index, root))
+}
+
+pub fn handle_code_index() -> Result<()> {
+    let root = project_root(&std::env::current_dir()?);
+    let index = CodeIndex::open(&CodeIndex::default_path())?;
+    let report = index.update(&root)?;
+    let files = index.files(&root)?;
+    let symbols: usize = files.iter().map(|file| file.symbols).sum();
+    println!(
+        "{} is up to date: {} files indexed, {} unchanged, {} removed, {} skipped as they couldn't be parsed",
+        root.display(),
+        report.indexed,
+        report.unchanged,
+        report.removed,
+        report.skipped
+    );
+    println!("{} definitions in {} source files", symbols, files.len());
+    Ok(())
+}
+
+pub fn handle_code_search(query: String, kind: Option<String>, limit: usize) -> Result<()> {
+    let kind = match kind {
+        Some(kind) => Some(
+            SymbolKind::parse(&kind)
+                .ok_or_else(|| anyhow!("Unknown kind of definition {}", kind))?,
+        ),
+        None => None,
+    };
+    let (index, root) = open_index()?;
+    let found = index.search(&root, &query, kind, limit)?;
+    if found.is_empty() {
+        println!("Nothing in {} is defined like {}", root.display(), query);
+    }
+    for (definition, _) in found {
+        print_definition(&definition);
+        println!("    {}", definition.symbol.signature);
+        if let Some(doc) = &definition.symbol.doc {
+            println!("    {}", style(doc).dim());
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_code_outline(path: Option<PathBuf>) -> Result<()> {
+    let (index, root) = open_index()?;
+    let path = match path {
+        Some(path) => {
+            let path = std::env::current_dir()?.join(path);
+            path.canonicalize()
+                .unwrap_or(path)
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+                .map_err(|_| anyhow!("The path isn't in the project at {}", root.display()))?
+        }
+        None => PathBuf::new(),
+    };
+    let definitions = index.outline(&root, &path)?;
+    let files: Vec<_> = index
+        .files(&root)?
+        .into_iter()
+        .filter(|file| file.path.starts_with(&path))
+        .collect();
+    if files.is_empty() {
+        println!("No source files are indexed under {}", path.display());
+    }
+    for file in files {
+        println!(
+            "{} {}",
+            style(file.path.display()).cyan().bold(),
+            style(format!("({})", file.language)).dim()
+        );
+        if let Some(doc) = &file.doc {
+            println!("  {}", style(doc).dim());
+        }
+        for definition in definitions.iter().filter(|d| d.path == file.path) {
+            let symbol = &definition.symbol;
+            let indent = if symbol.container.is_some() {
+                "    "
+            } else {
+                "  "
+            };
+            println!(
+                "{}{} {} {}",
+                indent,
+                style(symbol.start_line).dim(),
+                symbol.kind.as_str(),
+                symbol.name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_definition(definition: &Definition) {
+    let symbol = &definition.symbol;
+    let container = symbol
+        .container
+        .as_ref()
+        .map(|container| format!(" (in {})", container))
+        .unwrap_or_default();
+    println!(
+        "{} {} {}{}",
+        style(definition.location()).cyan(),
+        symbol.kind.as_str(),
+        style(&symbol.name).bold(),
+        container
+    );
+}
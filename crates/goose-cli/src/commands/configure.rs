@@ -1,7 +1,7 @@
 use cliclack::spinner;
 use console::style;
 use goose::agents::{extension::Envs, ExtensionConfig};
-use goose::config::extensions::name_to_key;
+use goose::config::extensions::{headers_secret, name_to_key};
 use goose::config::{Config, ConfigError, ExperimentManager, ExtensionEntry, ExtensionManager};
 use goose::message::Message;
 use goose::providers::{create, providers};
@@ -315,9 +315,53 @@ pub async fn configure_provider_dialog() -> Result<bool, Box<dyn Error>> {
 
     // Select model, defaulting to the provider's recommended model UNLESS there is an env override
     let default_model = std::env::var("GOOSE_MODEL").unwrap_or(provider_meta.default_model.clone());
-    let model: String = cliclack::input("Enter a model from that provider:")
-        .default_input(&default_model)
-        .interact()?;
+
+    // Ask the provider which models it currently offers, falling back to free text entry
+    // if it has no listing API or the request fails
+    let spin = spinner();
+    spin.start("Fetching available models...");
+    let available_models = match create(
+        provider_name,
+        goose::model::ModelConfig::new(default_model.clone()),
+    ) {
+        Ok(provider) => provider.fetch_supported_models().await.unwrap_or_else(|e| {
+            tracing::debug!("Failed to fetch models for {}: {}", provider_name, e);
+            None
+        }),
+        Err(_) => None,
+    };
+    spin.stop("");
+
+    let model: String = match available_models {
+        Some(models) if !models.is_empty() => {
+            let mut items: Vec<(String, String, &str)> =
+                models.iter().map(|m| (m.clone(), m.clone(), "")).collect();
+            items.push((
+                String::new(),
+                "Other".to_string(),
+                "Enter a model that isn't listed",
+            ));
+            let initial = if models.contains(&default_model) {
+                default_model.clone()
+            } else {
+                models[0].clone()
+            };
+            let selected = cliclack::select("Select a model from that provider:")
+                .initial_value(initial)
+                .items(&items)
+                .interact()?;
+            if selected.is_empty() {
+                cliclack::input("Enter a model from that provider:")
+                    .default_input(&default_model)
+                    .interact()?
+            } else {
+                selected
+            }
+        }
+        _ => cliclack::input("Enter a model from that provider:")
+            .default_input(&default_model)
+            .interact()?,
+    };
 
     // Test the configuration
     let spin = spinner();
@@ -335,6 +379,19 @@ pub async fn configure_provider_dialog() -> Result<bool, Box<dyn Error>> {
 
     let provider = create(provider_name, model_config)?;
 
+    // Report bad keys, unreachable hosts and unknown models specifically, before the
+    // tool calling check below
+    let health = provider.health_check().await;
+    if !health.is_healthy() {
+        spin.stop(style(health.to_string()).red());
+        cliclack::outro(
+            style("Failed to configure provider: the provider health check did not pass.")
+                .on_red()
+                .white(),
+        )?;
+        return Ok(false);
+    }
+
     let messages =
         vec![Message::user().with_text("What is the weather like in San Francisco today?")];
     // Only add the sample tool if toolshim is not enabled
@@ -447,6 +504,11 @@ pub fn configure_extensions_dialog() -> Result<(), Box<dyn Error>> {
             "Remote Extension",
             "Connect to a remote extension via SSE",
         )
+        .item(
+            "streamable_http",
+            "Remote Extension (Streamable HTTP)",
+            "Connect to a remote extension at its MCP endpoint",
+        )
         .interact()?;
 
     match extension_type {
@@ -594,7 +656,7 @@ pub fn configure_extensions_dialog() -> Result<(), Box<dyn Error>> {
 
             cliclack::outro(format!("Added {} extension", style(name).green()))?;
         }
-        "sse" => {
+        "sse" | "streamable_http" => {
             let extensions = ExtensionManager::get_all_names()?;
             let name: String = cliclack::input("What would you like to call this extension?")
                 .placeholder("my-remote-extension")
@@ -609,8 +671,16 @@ pub fn configure_extensions_dialog() -> Result<(), Box<dyn Error>> {
                 })
                 .interact()?;
 
-            let uri: String = cliclack::input("What is the SSE endpoint URI?")
-                .placeholder("http://localhost:8000/events")
+            let (prompt, placeholder) = if extension_type == "sse" {
+                (
+                    "What is the SSE endpoint URI?",
+                    "http://localhost:8000/events",
+                )
+            } else {
+                ("What is the MCP endpoint URI?", "http://localhost:8000/mcp")
+            };
+            let uri: String = cliclack::input(prompt)
+                .placeholder(placeholder)
                 .validate(|input: &String| {
                     if input.is_empty() {
                         Err("Please enter a URI")
@@ -667,15 +737,57 @@ pub fn configure_extensions_dialog() -> Result<(), Box<dyn Error>> {
                 }
             }
 
-            ExtensionManager::set(ExtensionEntry {
-                enabled: true,
-                config: ExtensionConfig::Sse {
+            let add_headers =
+                cliclack::confirm("Would you like to add request headers, such as Authorization?")
+                    .initial_value(false)
+                    .interact()?;
+
+            let mut headers = HashMap::new();
+            if add_headers {
+                loop {
+                    let key: String = cliclack::input("Header name:")
+                        .placeholder("Authorization")
+                        .interact()?;
+
+                    let value: String = cliclack::password("Header value:").mask('▪').interact()?;
+
+                    headers.insert(key, value);
+
+                    if !cliclack::confirm("Add another header?").interact()? {
+                        break;
+                    }
+                }
+            }
+
+            // Headers usually carry credentials, so they go to the keyring, not the config file
+            if !headers.is_empty() {
+                Config::global().set_secret(
+                    &headers_secret(&name_to_key(&name)),
+                    serde_json::to_value(headers)?,
+                )?;
+            }
+            let config = if extension_type == "sse" {
+                ExtensionConfig::Sse {
                     name: name.clone(),
                     uri,
                     envs: Envs::new(envs),
+                    headers: HashMap::new(),
                     description,
                     timeout: Some(timeout),
-                },
+                }
+            } else {
+                ExtensionConfig::StreamableHttp {
+                    name: name.clone(),
+                    uri,
+                    envs: Envs::new(envs),
+                    headers: HashMap::new(),
+                    description,
+                    timeout: Some(timeout),
+                }
+            };
+            ExtensionManager::set(ExtensionEntry {
+                enabled: true,
+                config,
             })?;
 
             cliclack::outro(format!("Added {} extension", style(name).green()))?;
@@ -796,6 +908,11 @@ pub fn configure_goose_mode_dialog() -> Result<(), Box<dyn Error>> {
             "Chat Mode",
             "Engage with the selected provider without using tools, extensions, or file modification"
         )
+        .item(
+            "dry_run",
+            "Dry Run Mode",
+            "Work through tasks as usual, recording the tool calls instead of making them"
+        )
         .interact()?;
 
     match mode {
@@ -815,6 +932,10 @@ pub fn configure_goose_mode_dialog() -> Result<(), Box<dyn Error>> {
             config.set_param("GOOSE_MODE", Value::String("chat".to_string()))?;
             cliclack::outro("Set to Chat Mode - no tools or modifications enabled")?;
         }
+        "dry_run" => {
+            config.set_param("GOOSE_MODE", Value::String("dry_run".to_string()))?;
+            cliclack::outro("Set to Dry Run Mode - tool calls are recorded, not made")?;
+        }
         _ => unreachable!(),
     };
     Ok(())
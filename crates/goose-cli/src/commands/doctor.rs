@@ -0,0 +1,355 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use console::style;
+use goose::config::settings::{is_known_setting, SETTINGS};
+use goose::config::{Config, ExtensionConfig, ExtensionManager};
+use goose::model::ModelConfig;
+use goose::providers::base::{HealthStatus, ProviderMetadata};
+
+use crate::commands::mcp::BUILTIN_SERVERS;
+
+const EXTENSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tallies and prints the result of each check
+#[derive(Default)]
+struct Report {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn ok(&mut self, message: impl AsRef<str>) {
+        println!("  {} {}", style("✓").green(), message.as_ref());
+    }
+
+    fn warn(&mut self, message: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.warnings += 1;
+        println!("  {} {}", style("⚠").yellow(), message.as_ref());
+        println!("    {}", style(fix.as_ref()).dim());
+    }
+
+    fn error(&mut self, message: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.errors += 1;
+        println!("  {} {}", style("✗").red(), message.as_ref());
+        println!("    {}", style(fix.as_ref()).dim());
+    }
+}
+
+/// Check the whole configuration, printing what is wrong and how to fix it
+///
+/// Returns whether the configuration is free of errors; warnings don't fail it.
+pub async fn handle_doctor() -> Result<bool> {
+    let config = Config::global();
+    let providers = goose::providers::providers();
+    let mut report = Report::default();
+
+    println!("{}", style("Config files:").cyan().bold());
+    check_config_files(config, &providers, &mut report);
+
+    println!("\n{}", style("Provider:").cyan().bold());
+    check_provider(config, &providers, &mut report).await;
+
+    println!("\n{}", style("Extensions:").cyan().bold());
+    check_extensions(&mut report).await;
+
+    println!();
+    if report.errors == 0 && report.warnings == 0 {
+        println!("{}", style("No problems found").green().bold());
+    } else {
+        println!(
+            "{}",
+            style(format!(
+                "Found {} error(s) and {} warning(s)",
+                report.errors, report.warnings
+            ))
+            .bold()
+        );
+    }
+    Ok(report.errors == 0)
+}
+
+fn check_config_files(config: &Config, providers: &[ProviderMetadata], report: &mut Report) {
+    let mut files = vec![config.path()];
+    files.extend(config.project_path());
+    let values = match config.load_values() {
+        Ok(values) => {
+            report.ok(format!("Parsed {}", files.join(" and ")));
+            values
+        }
+        Err(e) => {
+            report.error(
+                format!("Couldn't read the config: {}", e),
+                format!("Fix the YAML in {}", files.join(" or ")),
+            );
+            return;
+        }
+    };
+
+    let mut keys: Vec<_> = values.keys().collect();
+    keys.sort();
+    let mut unknown = 0;
+    for key in keys {
+        if is_known_setting(key, providers) {
+            continue;
+        }
+        unknown += 1;
+        let known = SETTINGS.iter().map(|setting| setting.key).chain(
+            providers
+                .iter()
+                .flat_map(|provider| provider.config_keys.iter())
+                .map(|config_key| config_key.name.as_str()),
+        );
+        let fix = match closest(key, known) {
+            Some(suggestion) => format!("Did you mean {}?", suggestion),
+            None => "Remove it, goose doesn't read it".to_string(),
+        };
+        report.warn(format!("Unknown key {}", key), fix);
+    }
+    if unknown == 0 {
+        report.ok("All keys are known settings");
+    }
+}
+
+async fn check_provider(config: &Config, providers: &[ProviderMetadata], report: &mut Report) {
+    let Ok(name) = config.get_param::<String>("GOOSE_PROVIDER") else {
+        report.error(
+            "GOOSE_PROVIDER is not set",
+            "Run `goose configure` to choose a provider",
+        );
+        return;
+    };
+    let Some(metadata) = providers.iter().find(|provider| provider.name == name) else {
+        let fix = match closest(&name, providers.iter().map(|p| p.name.as_str())) {
+            Some(suggestion) => format!("Did you mean {}?", suggestion),
+            None => "Run `goose configure` to choose a provider".to_string(),
+        };
+        report.error(format!("Unknown provider {}", name), fix);
+        return;
+    };
+    report.ok(format!("Provider {}", metadata.display_name));
+
+    let mut missing = false;
+    for key in metadata
+        .config_keys
+        .iter()
+        .filter(|key| key.required && key.default.is_none())
+    {
+        let value = if key.secret {
+            config.get_secret::<String>(&key.name)
+        } else {
+            config.get_param::<String>(&key.name)
+        };
+        if let Err(e) = value {
+            missing = true;
+            report.error(
+                format!("{} is missing: {}", key.name, e),
+                format!("Run `goose configure` or set {}", key.name),
+            );
+        }
+    }
+    if missing {
+        return;
+    }
+    report.ok("All required settings are set");
+
+    let Ok(model) = config.get_param::<String>("GOOSE_MODEL") else {
+        report.error(
+            "GOOSE_MODEL is not set",
+            format!(
+                "Run `goose configure` or set GOOSE_MODEL, e.g. to {}",
+                metadata.default_model
+            ),
+        );
+        return;
+    };
+    let provider = match goose::providers::create(&name, ModelConfig::new(model.clone())) {
+        Ok(provider) => provider,
+        Err(e) => {
+            report.error(
+                format!("Couldn't create the provider: {}", e),
+                "Run `goose configure` to check the provider settings",
+            );
+            return;
+        }
+    };
+
+    match provider.health_check().await {
+        HealthStatus::Healthy => report.ok(format!("Model {} is available", model)),
+        HealthStatus::InvalidCredentials(message) => report.error(
+            format!("The credentials were rejected: {}", message),
+            "Run `goose configure` to update the API key",
+        ),
+        HealthStatus::Unreachable(message) => report.error(
+            format!("The provider is unreachable: {}", message),
+            "Check the host setting, the network and any proxy settings like GOOSE_PROXY",
+        ),
+        HealthStatus::ModelNotFound(message) => {
+            // The live listing where there is one, as known_models is often out of date
+            let models = provider
+                .fetch_supported_models()
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| metadata.known_models.clone());
+            let fix = match closest(&model, models.iter().map(String::as_str)) {
+                Some(suggestion) => format!("Did you mean {}? Set GOOSE_MODEL to fix", suggestion),
+                None => format!(
+                    "Set GOOSE_MODEL to a model the provider serves, see {}",
+                    metadata.model_doc_link
+                ),
+            };
+            report.error(
+                format!("Model {} is not available: {}", model, message),
+                fix,
+            );
+        }
+        HealthStatus::Unhealthy(message) => report.error(
+            format!("The provider health check failed: {}", message),
+            "Run `goose configure` to check the provider settings",
+        ),
+    }
+}
+
+async fn check_extensions(report: &mut Report) {
+    let extensions = match ExtensionManager::get_all() {
+        Ok(extensions) => extensions,
+        Err(e) => {
+            report.error(
+                format!("Couldn't read the extensions: {}", e),
+                "Run `goose configure` to set up extensions",
+            );
+            return;
+        }
+    };
+    let mut enabled: Vec<_> = extensions
+        .into_iter()
+        .filter(|extension| extension.enabled)
+        .map(|extension| extension.config)
+        .collect();
+    if enabled.is_empty() {
+        report.ok("No extensions enabled");
+        return;
+    }
+    enabled.sort_by_key(|extension| extension.name());
+
+    for extension in enabled {
+        let name = extension.name();
+        match &extension {
+            ExtensionConfig::Stdio { cmd, .. } => {
+                if command_exists(cmd, std::env::var_os("PATH").as_deref()) {
+                    report.ok(format!("{}: {} found", name, cmd));
+                } else {
+                    report.error(
+                        format!("{}: command {} not found", name, cmd),
+                        format!(
+                            "Install {} or run `goose configure` to point the extension at its \
+                             full path",
+                            cmd
+                        ),
+                    );
+                }
+            }
+            ExtensionConfig::Builtin {
+                name: builtin_name, ..
+            } => {
+                if BUILTIN_SERVERS.contains(&builtin_name.as_str()) {
+                    report.ok(format!("{}: builtin", name));
+                } else {
+                    let fix = match closest(builtin_name, BUILTIN_SERVERS.iter().copied()) {
+                        Some(suggestion) => format!("Did you mean {}?", suggestion),
+                        None => format!("Use one of {}", BUILTIN_SERVERS.join(", ")),
+                    };
+                    report.error(format!("{}: unknown builtin {}", name, builtin_name), fix);
+                }
+            }
+            ExtensionConfig::Sse { uri, .. } | ExtensionConfig::StreamableHttp { uri, .. } => {
+                let response = reqwest::Client::new()
+                    .get(uri)
+                    .timeout(EXTENSION_TIMEOUT)
+                    .send()
+                    .await;
+                match response {
+                    Ok(_) => report.ok(format!("{}: {} is reachable", name, uri)),
+                    Err(e) => report.error(
+                        format!("{}: {} is unreachable: {}", name, uri, e),
+                        "Start the server or run `goose configure` to fix its URI",
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Whether a command can be run, either as a path or from one of the directories in `paths`,
+/// which is formatted like PATH
+fn command_exists(cmd: &str, paths: Option<&OsStr>) -> bool {
+    let path = Path::new(cmd);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    let Some(paths) = paths else {
+        return false;
+    };
+    std::env::split_paths(paths).any(|dir| {
+        let candidate = dir.join(cmd);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+/// The candidate most like a misspelt name, if any is close enough to be what was meant
+///
+/// Closeness is the number of edits, counting a swap of two letters as one, and ties go to the
+/// candidate nearest in length so GOOSE_MODLE suggests GOOSE_MODEL rather than GOOSE_MODE.
+fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_edits = (name.chars().count() / 4).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            let edits = strsim::damerau_levenshtein(&name, &candidate.to_lowercase());
+            (candidate, edits, name.len().abs_diff(candidate.len()))
+        })
+        .filter(|(_, edits, _)| *edits <= max_edits)
+        .min_by_key(|(_, edits, length_difference)| (*edits, *length_difference))
+        .map(|(candidate, _, _)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest() {
+        let keys = SETTINGS.iter().map(|setting| setting.key);
+        assert_eq!(closest("GOOSE_MODLE", keys.clone()), Some("GOOSE_MODEL"));
+        assert_eq!(
+            closest("goose_provider", keys.clone()),
+            Some("GOOSE_PROVIDER")
+        );
+        assert_eq!(closest("TOTALLY_UNRELATED", keys), None);
+        assert_eq!(
+            closest("develper", BUILTIN_SERVERS.iter().copied()),
+            Some("developer")
+        );
+    }
+
+    #[test]
+    fn test_command_exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("server");
+        std::fs::write(&script, "").unwrap();
+
+        let paths = Some(dir.path().as_os_str());
+        assert!(command_exists(script.to_str().unwrap(), None));
+        assert!(!command_exists(
+            dir.path().join("missing").to_str().unwrap(),
+            None
+        ));
+        assert!(command_exists("server", paths));
+        assert!(!command_exists("missing", paths));
+        assert!(!command_exists("server", None));
+    }
+}
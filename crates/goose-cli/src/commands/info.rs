@@ -20,11 +20,20 @@ pub fn handle_info(verbose: bool) -> Result<()> {
     let config_file = config.path();
 
     // Define the labels and their corresponding path values once.
-    let paths = [
-        ("Config file:", config_file.to_string()),
-        ("Sessions dir:", sessions_dir.display().to_string()),
-        ("Logs dir:", logs_dir.display().to_string()),
-    ];
+    let mut paths = vec![("Config file:", config_file.to_string())];
+    if let Some(project_config_file) = config.project_path() {
+        let project_config_file = if config.project_trusted() {
+            project_config_file
+        } else {
+            format!(
+                "{} (not trusted, see goose config trust)",
+                project_config_file
+            )
+        };
+        paths.push(("Project config file:", project_config_file));
+    }
+    paths.push(("Sessions dir:", sessions_dir.display().to_string()));
+    paths.push(("Logs dir:", logs_dir.display().to_string()));
 
     // Calculate padding: use the max length of the label plus extra space.
     let basic_padding = paths.iter().map(|(l, _)| l.len()).max().unwrap_or(0) + 4;
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use console::style;
+use goose::config::Config;
+use goose::knowledge::{IndexReport, Knowledge, KnowledgeIndex};
+use goose::vector_store;
+use std::path::PathBuf;
+
+/// The index, for the commands that don't need the embedding model
+fn open_index() -> Result<KnowledgeIndex> {
+    let vectors = vector_store::from_config(Config::global())?;
+    KnowledgeIndex::open(&KnowledgeIndex::default_path(), vectors)
+}
+
+pub fn handle_knowledge_list() -> Result<()> {
+    let index = open_index()?;
+    let sources = index.sources()?;
+    if sources.is_empty() {
+        println!("No documents are indexed, add them with `goose knowledge add <path>`");
+        return Ok(());
+    }
+    println!("{}", style("Indexed documents").cyan().bold());
+    for source in sources {
+        let files = index.files(&source)?.len();
+        println!(
+            "  {}  {}",
+            source.display(),
+            style(format!("({} files)", files)).dim()
+        );
+    }
+    Ok(())
+}
+
+pub async fn handle_knowledge_add(paths: Vec<PathBuf>) -> Result<()> {
+    let knowledge = Knowledge::open()?;
+    for path in paths {
+        let report = knowledge.add_source(&path).await?;
+        print_report(&path.display().to_string(), &report);
+    }
+    Ok(())
+}
+
+pub async fn handle_knowledge_remove(path: PathBuf) -> Result<()> {
+    let index = open_index()?;
+    let source = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if !index.remove_source(&source).await? {
+        return Err(anyhow!("{} isn't indexed", path.display()));
+    }
+    println!("Removed {} from the index", path.display());
+    Ok(())
+}
+
+pub async fn handle_knowledge_reindex() -> Result<()> {
+    let report = Knowledge::open()?.reindex().await?;
+    print_report("The index", &report);
+    Ok(())
+}
+
+pub async fn handle_knowledge_search(query: String, limit: usize) -> Result<()> {
+    let passages = Knowledge::open()?.search(&query, limit).await?;
+    if passages.is_empty() {
+        println!("No documents are indexed, add them with `goose knowledge add <path>`");
+    }
+    for passage in passages {
+        println!(
+            "{} {}",
+            style(passage.source()).cyan(),
+            style(format!("({:.2})", passage.score)).dim()
+        );
+        println!("{}\n", passage.content);
+    }
+    Ok(())
+}
+
+fn print_report(name: &str, report: &IndexReport) {
+    println!(
+        "{} is up to date: {} files indexed, {} unchanged, {} removed, {} skipped as they aren't text",
+        name, report.indexed, report.unchanged, report.removed, report.skipped
+    );
+}
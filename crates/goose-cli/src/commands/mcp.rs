@@ -7,6 +7,17 @@ use mcp_server::router::RouterService;
 use mcp_server::{BoundedService, ByteTransport, Server};
 use tokio::io::{stdin, stdout};
 
+/// The names of the MCP servers bundled with goose, used as builtin extensions
+pub const BUILTIN_SERVERS: &[&str] = &[
+    "developer",
+    "computercontroller",
+    "jetbrains",
+    "google_drive",
+    "googledrive",
+    "memory",
+    "tutorial",
+];
+
 pub async fn run_server(name: &str) -> Result<()> {
     // Initialize logging
     crate::logging::setup_logging(Some(&format!("mcp-{name}")), None)?;
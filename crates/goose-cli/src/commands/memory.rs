@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Result};
+use console::style;
+use goose::config::Config;
+use goose::memory::{LongTermMemory, Memory, MemoryKind, MemoryStore};
+use goose::model::ModelConfig;
+use goose::session;
+use goose::vector_store;
+
+/// The memory store, for the commands that don't need the embedding model
+fn open_store() -> Result<MemoryStore> {
+    let vectors = vector_store::from_config(Config::global())?;
+    MemoryStore::open(&MemoryStore::default_path(), vectors)
+}
+
+pub fn handle_memory_list(format: String) -> Result<()> {
+    let memories = open_store()?.list()?;
+    match format.as_str() {
+        "json" => println!("{}", serde_json::to_string(&memories)?),
+        _ => {
+            if memories.is_empty() {
+                println!("Nothing is remembered yet");
+                return Ok(());
+            }
+            println!("{}", style("Memories").cyan().bold());
+            for memory in &memories {
+                print_memory(memory, None);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_memory_search(query: String, limit: usize, format: String) -> Result<()> {
+    let memory = LongTermMemory::open()?;
+    let found = memory.search(&query, limit).await?;
+    match format.as_str() {
+        "json" => {
+            let found: Vec<_> = found
+                .iter()
+                .map(|(memory, score)| serde_json::json!({"memory": memory, "score": score}))
+                .collect();
+            println!("{}", serde_json::to_string(&found)?);
+        }
+        _ => {
+            if found.is_empty() {
+                println!("Nothing is remembered yet");
+            }
+            for (memory, score) in &found {
+                print_memory(memory, Some(*score));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_memory_add(content: String, preference: bool) -> Result<()> {
+    let kind = if preference {
+        MemoryKind::Preference
+    } else {
+        MemoryKind::Fact
+    };
+    let memory = LongTermMemory::open()?.add(kind, &content, None).await?;
+    println!("Remembered {}", memory.id);
+    Ok(())
+}
+
+pub async fn handle_memory_edit(id: String, content: String) -> Result<()> {
+    let id = resolve_id(&open_store()?, &id)?;
+    LongTermMemory::open()?.edit(&id, None, &content).await?;
+    println!("Updated {}", id);
+    Ok(())
+}
+
+pub async fn handle_memory_delete(id: String) -> Result<()> {
+    let store = open_store()?;
+    let id = resolve_id(&store, &id)?;
+    store.delete(&id).await?;
+    println!("Forgot {}", id);
+    Ok(())
+}
+
+/// The id of the one memory whose id starts with `prefix`, as ids are listed shortened
+fn resolve_id(store: &MemoryStore, prefix: &str) -> Result<String> {
+    let matching: Vec<String> = store
+        .list()?
+        .into_iter()
+        .map(|memory| memory.id)
+        .filter(|id| id.starts_with(prefix))
+        .collect();
+    match matching.as_slice() {
+        [id] => Ok(id.clone()),
+        [] => Err(anyhow!("There is no memory {}", prefix)),
+        _ => Err(anyhow!("More than one memory starts with {}", prefix)),
+    }
+}
+
+/// Extract memories from the messages of a session that haven't been remembered yet, with the
+/// configured provider and model
+pub async fn handle_memory_extract(identifier: Option<session::Identifier>) -> Result<()> {
+    let session_file = match identifier {
+        Some(identifier) => session::get_path(identifier),
+        None => session::get_most_recent_session()?,
+    };
+    if !session::session_exists(&session_file) {
+        return Err(anyhow!("No such session {}", session_file.display()));
+    }
+    let config = Config::global();
+    let provider_name: String = config
+        .get_param("GOOSE_PROVIDER")
+        .map_err(|_| anyhow!("No provider configured. Run 'goose configure' first"))?;
+    let model: String = config
+        .get_param("GOOSE_MODEL")
+        .map_err(|_| anyhow!("No model configured. Run 'goose configure' first"))?;
+    let provider = goose::providers::create(&provider_name, ModelConfig::new(model))?;
+
+    let memory = LongTermMemory::open()?;
+    let remembered = memory
+        .remember_session(&session_file, provider.as_ref(), 0)
+        .await?;
+    if remembered.is_empty() {
+        println!("Nothing new to remember");
+    }
+    for memory in &remembered {
+        print_memory(memory, None);
+    }
+    Ok(())
+}
+
+fn print_memory(memory: &Memory, score: Option<f32>) {
+    let detail = match score {
+        Some(score) => format!("{} {:.2}", memory.kind.as_str(), score),
+        None => format!(
+            "{} {}",
+            memory.kind.as_str(),
+            memory.updated.format("%Y-%m-%d")
+        ),
+    };
+    println!(
+        "  {}  {}  {}",
+        style(&memory.id[..8.min(memory.id.len())]).dim(),
+        memory.content,
+        style(format!("({})", detail)).dim()
+    );
+}
@@ -1,7 +1,15 @@
 pub mod agent_version;
+pub mod audit;
 pub mod bench;
+pub mod code;
 pub mod configure;
+pub mod doctor;
 pub mod info;
+pub mod knowledge;
 pub mod mcp;
+pub mod memory;
+pub mod recipe;
+pub mod schedule;
 pub mod session;
 pub mod update;
+pub mod usage;
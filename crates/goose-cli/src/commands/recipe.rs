@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use console::style;
+use goose::recipe::{ParameterType, Recipe};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::logging::setup_logging;
+use crate::session::{build_session, Identifier};
+
+pub async fn handle_recipe_run(
+    source: String,
+    parameters: Vec<(String, String)>,
+    identifier: Option<Identifier>,
+    interactive: bool,
+    debug: bool,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let recipe = Recipe::load(&source)
+        .await?
+        .with_parameters(&parameters.into_iter().collect::<HashMap<_, _>>())?;
+    if output.is_some() && recipe.response.is_none() {
+        return Err(anyhow!(
+            "The recipe {} has no response schema, so there is no result to write",
+            recipe.name
+        ));
+    }
+
+    let mut session = build_session(identifier, false, vec![], vec![], None, debug).await;
+    setup_logging(
+        session.session_file().file_stem().and_then(|s| s.to_str()),
+        None,
+    )?;
+    session
+        .run_recipe(&recipe, interactive, output.as_deref())
+        .await
+}
+
+/// Check a recipe, and show what it does and the parameters it takes
+pub async fn handle_recipe_validate(source: String) -> Result<()> {
+    let recipe = Recipe::load(&source).await?;
+    println!("{} is a valid recipe", style(&recipe.name).cyan().bold());
+    if let Some(description) = &recipe.description {
+        println!("  {}", description);
+    }
+    println!(
+        "  {} steps after the prompt, {} success criteria",
+        recipe.steps.len(),
+        recipe.success_criteria.len()
+    );
+    if recipe.parameters.is_empty() {
+        return Ok(());
+    }
+    println!("{}", style("Parameters").cyan().bold());
+    let width = recipe
+        .parameters
+        .iter()
+        .map(|p| p.key.len())
+        .max()
+        .unwrap_or(0);
+    for parameter in &recipe.parameters {
+        let default = match &parameter.default {
+            Some(default) => format!("default {}", default),
+            None => "required".to_string(),
+        };
+        let kind = match parameter.kind {
+            ParameterType::String => "string".to_string(),
+            ParameterType::Enum => parameter.options.join("|"),
+            ParameterType::File => "file".to_string(),
+        };
+        println!(
+            "  {:<width$}  {}  {}  {}",
+            parameter.key,
+            style(kind).yellow(),
+            parameter.description.as_deref().unwrap_or_default(),
+            style(default).dim(),
+            width = width
+        );
+    }
+    Ok(())
+}
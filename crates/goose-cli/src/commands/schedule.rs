@@ -0,0 +1,295 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, Timelike};
+use console::style;
+use goose::schedule::{jobs, JobRun, RunStatus, ScheduleState, ScheduledJob};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+
+#[derive(Serialize)]
+struct JobSummary<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    job: &'a ScheduledJob,
+    next_run: Option<DateTime<Local>>,
+    last_run: Option<&'a JobRun>,
+}
+
+pub fn handle_schedule_list(format: String) -> Result<()> {
+    let jobs = jobs()?;
+    let state = ScheduleState::load()?;
+    let now = Local::now();
+    let summaries = jobs
+        .iter()
+        .map(|(name, job)| {
+            let next_run = if job.enabled {
+                job.next_run(&now)
+                    .map_err(|e| anyhow!("The schedule of {} is invalid: {}", name, e))?
+            } else {
+                None
+            };
+            Ok(JobSummary {
+                name,
+                job,
+                next_run,
+                last_run: state.last_runs.get(name),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match format.as_str() {
+        "json" => println!("{}", serde_json::to_string(&summaries)?),
+        _ => {
+            if summaries.is_empty() {
+                println!("No jobs are scheduled, add them under 'schedules' in the config file");
+                return Ok(());
+            }
+            println!("{}", style("Scheduled jobs").cyan().bold());
+            let width = summaries.iter().map(|s| s.name.len()).max().unwrap_or(0);
+            for summary in summaries {
+                let next = match (summary.job.enabled, summary.next_run) {
+                    (false, _) => "disabled".to_string(),
+                    (true, Some(next)) => format!("next {}", next.format("%a %Y-%m-%d %H:%M")),
+                    (true, None) => "never runs".to_string(),
+                };
+                let last = match summary.last_run {
+                    Some(run) => format!(
+                        "last {} {} ({})",
+                        status_label(run.status),
+                        run.started.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                        run.session
+                    ),
+                    None => "not run yet".to_string(),
+                };
+                println!(
+                    "  {:<width$}  {:<16}  {:<26}  {}",
+                    summary.name,
+                    summary.job.cron,
+                    next,
+                    style(last).dim(),
+                    width = width
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn status_label(status: RunStatus) -> &'static str {
+    match status {
+        RunStatus::Running => "running since",
+        RunStatus::Succeeded => "succeeded",
+        RunStatus::Failed => "failed",
+    }
+}
+
+/// Run a job now, whatever its schedule
+pub async fn handle_schedule_run(name: String) -> Result<()> {
+    let job = jobs()?
+        .remove(&name)
+        .ok_or_else(|| anyhow!("There is no scheduled job named {}", name))?;
+    let state = Mutex::new(ScheduleState::load()?);
+    let run = run_job(&name, &job, &state).await;
+    match run.error {
+        None => {
+            println!("{} ran, saved as session {}", name, run.session);
+            Ok(())
+        }
+        Some(error) => Err(anyhow!("{} failed: {}", name, error)),
+    }
+}
+
+/// Run the jobs as they come due, until interrupted
+pub async fn handle_schedule_start() -> Result<()> {
+    let state = Arc::new(Mutex::new(ScheduleState::load()?));
+    let mut tasks = JoinSet::new();
+    let mut running = HashSet::new();
+    // When each job is due next, with the cron expression that was worked out from
+    let mut next_runs: HashMap<String, (String, Option<DateTime<Local>>)> = HashMap::new();
+
+    println!(
+        "{}",
+        style("Running scheduled jobs, press Ctrl+C to stop").dim()
+    );
+    loop {
+        while let Some(finished) = tasks.try_join_next() {
+            if let Ok(name) = finished {
+                running.remove(&name);
+            }
+        }
+
+        // The config is read each time, so jobs can be changed without restarting
+        let jobs = match jobs() {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                eprintln!("{}", e);
+                Default::default()
+            }
+        };
+        next_runs.retain(|name, _| jobs.contains_key(name));
+        let now = Local::now();
+        for (name, job) in jobs.into_iter().filter(|(_, job)| job.enabled) {
+            let due = match next_runs.get(&name) {
+                Some((cron, due)) if *cron == job.cron => *due,
+                _ => {
+                    let due = next_run(&name, &job, &now);
+                    next_runs.insert(name.clone(), (job.cron.clone(), due));
+                    due
+                }
+            };
+            if due.is_none_or(|due| due > now) {
+                continue;
+            }
+            next_runs.insert(
+                name.clone(),
+                (job.cron.clone(), next_run(&name, &job, &now)),
+            );
+
+            if !running.insert(name.clone()) {
+                println!("Skipping {}, which is still running from before", name);
+                continue;
+            }
+            println!("{} Starting {}", now.format("%Y-%m-%d %H:%M"), name);
+            let state = state.clone();
+            tasks.spawn(async move {
+                let run = run_job(&name, &job, &state).await;
+                match &run.error {
+                    None => println!("{} succeeded, saved as session {}", name, run.session),
+                    Some(error) => eprintln!("{} failed: {}", name, error),
+                }
+                name
+            });
+        }
+
+        // Cron expressions are to the minute, so check again at the start of the next one
+        let wait = 60 - Local::now().second().min(59);
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(wait as u64)) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    while tasks.try_join_next().is_some() {}
+    if !tasks.is_empty() {
+        println!("Waiting for {} running jobs to stop", tasks.len());
+        while tasks.join_next().await.is_some() {}
+    }
+    Ok(())
+}
+
+fn next_run(name: &str, job: &ScheduledJob, now: &DateTime<Local>) -> Option<DateTime<Local>> {
+    match job.next_run(now) {
+        Ok(Some(next_run)) => Some(next_run),
+        Ok(None) => {
+            eprintln!("{} is never due, '{}' matches no date", name, job.cron);
+            None
+        }
+        Err(e) => {
+            eprintln!("{} isn't scheduled, its schedule is invalid: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Run `job` as a headless goose run, recording the run and notifying of it
+async fn run_job(name: &str, job: &ScheduledJob, state: &Mutex<ScheduleState>) -> JobRun {
+    let mut run = JobRun::start(name);
+    let record = |run: &JobRun| {
+        let run = run.clone();
+        async move {
+            if let Err(e) = state.lock().await.record(name, &run) {
+                tracing::warn!("Failed to save the run of {}: {}", name, e);
+            }
+        }
+    };
+
+    record(&run).await;
+    let error = run_headless(name, job, &run.session).await.err();
+    run.finish(error.map(|e| e.to_string()));
+    record(&run).await;
+
+    if let Some(notify) = &job.notify {
+        if let Err(e) = notify.send(name, &run).await {
+            eprintln!("Failed to notify of the run of {}: {}", name, e);
+        }
+    }
+    run
+}
+
+async fn run_headless(name: &str, job: &ScheduledJob, session: &str) -> Result<()> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(["run", "--text", &job.prompt, "--name", session])
+        .args(["--meta", &format!("scheduled_job={}", name)])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        // Backtraces would bury the error the run failed with
+        .env("RUST_BACKTRACE", "0");
+    if let Some(working_dir) = &job.working_dir {
+        command.current_dir(working_dir);
+    }
+    for (key, value) in [
+        ("GOOSE_PROVIDER", &job.provider),
+        ("GOOSE_MODEL", &job.model),
+        ("GOOSE_MODE", &job.mode),
+    ] {
+        if let Some(value) = value {
+            command.env(key, value);
+        }
+    }
+
+    let output = command.output().await?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match failure_reason(&stderr) {
+        Some(reason) => Err(anyhow!("{}", reason)),
+        None => Err(anyhow!("goose exited with {}", output.status)),
+    }
+}
+
+/// What a failed run stopped with, the error goose exited with or the message it panicked with
+fn failure_reason(stderr: &str) -> Option<&str> {
+    let mut lines = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let mut last = None;
+    while let Some(line) = lines.next() {
+        if let Some(error) = line.strip_prefix("Error: ") {
+            return Some(error);
+        }
+        if line.contains("panicked at") {
+            return lines.next();
+        }
+        if !line.starts_with("note:") {
+            last = Some(line);
+        }
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_reason() {
+        let error = "starting session | provider: openai\nError: Request failed: 401\n";
+        assert_eq!(failure_reason(error), Some("Request failed: 401"));
+        let panic = "\nthread 'main' panicked at src/session/builder.rs:37:10:\n\
+            No model configured. Run 'goose configure' first\n\
+            note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        assert_eq!(
+            failure_reason(panic),
+            Some("No model configured. Run 'goose configure' first")
+        );
+        assert_eq!(failure_reason("\n"), None);
+    }
+}
@@ -1,5 +1,14 @@
+use crate::session::build_replay_session;
 use anyhow::Result;
+use goose::config::Config;
+use goose::session::archive;
+use goose::session::export::{export_session, import_session, ExportFormat};
 use goose::session::info::{get_session_info, SessionInfo};
+use goose::session::retention::{self, RetentionPolicy};
+use goose::session::{self, Identifier, SearchQuery};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
 
 pub fn handle_session_list(verbose: bool, format: String) -> Result<()> {
     let sessions = match get_session_info() {
@@ -35,7 +44,19 @@ pub fn handle_session_list(verbose: bool, format: String) -> Result<()> {
                     let output = format!("{} - {} - {}", id, description, modified);
                     if verbose {
                         println!("  {}", output);
+                        if !metadata.summary.is_empty() {
+                            println!("    {}", metadata.summary);
+                        }
                         println!("    Path: {}", path);
+                        if let Some(parent) = &metadata.forked_from {
+                            println!(
+                                "    Forked from: {} at message {}",
+                                parent.session_id, parent.message_index
+                            );
+                        }
+                        if !metadata.forks.is_empty() {
+                            println!("    Forks: {}", metadata.forks.join(", "));
+                        }
                     } else {
                         println!("{}", output);
                     }
@@ -45,3 +66,308 @@ pub fn handle_session_list(verbose: bool, format: String) -> Result<()> {
     }
     Ok(())
 }
+
+pub fn handle_session_export(
+    identifier: Option<Identifier>,
+    all: bool,
+    format: String,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let format: ExportFormat = format.parse()?;
+    let session_files = if all {
+        if format != ExportFormat::OpenAi {
+            return Err(anyhow::anyhow!(
+                "--all needs --format openai, as other formats hold one session per file"
+            ));
+        }
+        get_session_info()?
+            .into_iter()
+            .map(|info| PathBuf::from(info.path))
+            .collect()
+    } else {
+        let session_file = match identifier {
+            Some(identifier) => session::get_path(identifier),
+            None => session::get_most_recent_session()?,
+        };
+        if !session::session_exists(&session_file) {
+            return Err(anyhow::anyhow!(
+                "No such session {}",
+                session_file.display()
+            ));
+        }
+        vec![session_file]
+    };
+
+    let mut writer: Box<dyn Write> = match &output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut exported = 0;
+    for session_file in session_files {
+        let saved = session::resume(&session_file)?;
+        if export_session(&saved, format, &mut writer)? {
+            exported += 1;
+        }
+    }
+    writer.flush()?;
+
+    if let Some(path) = output {
+        eprintln!("Exported {} session(s) to {}", exported, path.display());
+    }
+    Ok(())
+}
+
+pub fn handle_session_search(
+    query: Option<String>,
+    tool: Option<String>,
+    path: Option<String>,
+    limit: usize,
+    format: String,
+) -> Result<()> {
+    if query.is_none() && tool.is_none() && path.is_none() {
+        return Err(anyhow::anyhow!(
+            "Give words to search for, a --tool or a --path"
+        ));
+    }
+    let hits = session::search(&SearchQuery {
+        text: query,
+        tool,
+        path,
+        limit,
+    })?;
+
+    match format.as_str() {
+        "json" => {
+            println!("{}", serde_json::to_string(&hits)?);
+        }
+        _ => {
+            if hits.is_empty() {
+                println!("No matching sessions found");
+            }
+            for hit in hits {
+                let description = if hit.description.is_empty() {
+                    "(none)"
+                } else {
+                    &hit.description
+                };
+                println!("{} - {}", hit.session_id, description);
+                println!(
+                    "  message {} ({}): {}",
+                    hit.message_index, hit.role, hit.snippet
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_session_stats(identifier: Option<Identifier>, format: String) -> Result<()> {
+    let session_file = match identifier {
+        Some(identifier) => session::get_path(identifier),
+        None => session::get_most_recent_session()?,
+    };
+    if !session::session_exists(&session_file) {
+        return Err(anyhow::anyhow!(
+            "No such session {}",
+            session_file.display()
+        ));
+    }
+    let stats = session::stats::session_stats(&session_file)?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string(&stats)?);
+        return Ok(());
+    }
+    println!("Turns: {}", stats.turns);
+    println!(
+        "Duration: {} ({} active)",
+        format_duration(stats.duration_secs),
+        format_duration(stats.active_secs)
+    );
+    if let Some(tokens) = stats.tokens {
+        println!("Tokens: {}", tokens);
+    }
+    if let Some(cost) = stats.cost {
+        println!("Cost: ${:.2}", cost);
+    }
+    println!(
+        "Tool calls: {} ({} failed)",
+        stats.total_tool_calls(),
+        stats.failed_tool_calls
+    );
+    let mut tool_calls: Vec<_> = stats.tool_calls.iter().collect();
+    tool_calls.sort_by_key(|(_, calls)| std::cmp::Reverse(**calls));
+    for (tool, calls) in tool_calls {
+        println!("  {}: {}", tool, calls);
+    }
+    println!("Files touched: {}", stats.files_touched.len());
+    for file in &stats.files_touched {
+        println!("  {}", file);
+    }
+    Ok(())
+}
+
+fn format_duration(secs: i64) -> String {
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+pub fn handle_session_fork(
+    identifier: Option<Identifier>,
+    at: Option<usize>,
+    into: Option<String>,
+) -> Result<()> {
+    let session_file = match identifier {
+        Some(identifier) => session::get_path(identifier),
+        None => session::get_most_recent_session()?,
+    };
+    if !session::session_exists(&session_file) {
+        return Err(anyhow::anyhow!(
+            "No such session {}",
+            session_file.display()
+        ));
+    }
+    let at = match at {
+        Some(at) => at,
+        None => session::resume(&session_file)?.messages.len(),
+    };
+
+    let name = into.unwrap_or_else(session::generate_session_id);
+    let fork_file = session::get_path(Identifier::Name(name.clone()));
+    let forked = session::fork(&session_file, at, &fork_file)?;
+    println!(
+        "Forked {} messages into session {}, resume it with `goose session -r --name {}`",
+        forked.messages.len(),
+        name,
+        name
+    );
+    Ok(())
+}
+
+pub fn handle_session_import(file: PathBuf, name: Option<String>) -> Result<()> {
+    let reader = BufReader::new(File::open(&file)?);
+    let mut saved = import_session(reader)?;
+
+    let name = match name {
+        Some(name) => name,
+        None => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow::anyhow!("Name the session with --name"))?,
+    };
+    let session_file = session::get_path(Identifier::Name(name.clone()));
+    if session::session_exists(&session_file) {
+        return Err(anyhow::anyhow!(
+            "A session named {} already exists, choose another name with --name",
+            name
+        ));
+    }
+
+    // Sessions moved from another machine may have worked in a directory that isn't here
+    if !saved.metadata.working_dir.exists() {
+        saved.metadata.working_dir = std::env::current_dir()?;
+    }
+    session::storage::save_messages_with_metadata(&session_file, &saved.metadata, &saved.messages)?;
+    println!(
+        "Imported {} messages as session {}, resume it with `goose session -r --name {}`",
+        saved.messages.len(),
+        name,
+        name
+    );
+    Ok(())
+}
+
+pub async fn handle_session_replay(identifier: Option<Identifier>, debug: bool) -> Result<()> {
+    let session_file = match identifier {
+        Some(identifier) => session::get_path(identifier),
+        None => session::get_most_recent_session()?,
+    };
+    if !session::session_exists(&session_file) {
+        return Err(anyhow::anyhow!(
+            "No such session {}",
+            session_file.display()
+        ));
+    }
+    let (mut replay, recorded) = build_replay_session(&session_file, debug).await?;
+    replay.replay(&recorded).await
+}
+
+pub fn handle_session_archive(identifier: Option<Identifier>) -> Result<()> {
+    let archive = archive::global().ok_or_else(|| {
+        anyhow::anyhow!("Set GOOSE_SESSION_ARCHIVE_URL to the s3:// bucket to archive sessions to")
+    })?;
+    match identifier {
+        Some(identifier) => {
+            let session_file = session::get_path(identifier);
+            if !session_file.exists() {
+                return Err(anyhow::anyhow!(
+                    "No such session {}",
+                    session_file.display()
+                ));
+            }
+            let archived = archive.archive(&session_file)?;
+            println!("Archived the session to {}", archived.url);
+        }
+        None => {
+            let archived =
+                archive.archive_old_sessions(archive::archive_after(Config::global()))?;
+            for session_id in &archived {
+                println!("{}", session_id);
+            }
+            println!("Archived {} sessions", archived.len());
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_session_purge(
+    keep: Option<usize>,
+    older_than: Option<u64>,
+    max_size: Option<u64>,
+    dry_run: bool,
+) -> Result<()> {
+    let mut policy = RetentionPolicy {
+        max_sessions: keep,
+        max_age_days: older_than,
+        max_size_mb: max_size,
+    };
+    if policy.is_unlimited() {
+        policy = RetentionPolicy::from_config(Config::global());
+    }
+    if policy.is_unlimited() {
+        return Err(anyhow::anyhow!(
+            "Give a limit with --keep, --older-than or --max-size, or set GOOSE_SESSION_MAX_COUNT, GOOSE_SESSION_MAX_AGE_DAYS or GOOSE_SESSION_MAX_SIZE_MB"
+        ));
+    }
+
+    let report = retention::purge(&policy, dry_run)?;
+    for purged in &report.sessions {
+        println!(
+            "{} (updated {}, {:.1} MB)",
+            purged.id,
+            purged.updated_at.format("%Y-%m-%d"),
+            purged.size as f64 / (1024.0 * 1024.0)
+        );
+    }
+    let megabytes = report.freed as f64 / (1024.0 * 1024.0);
+    if dry_run {
+        println!(
+            "Would remove {} sessions, freeing {:.1} MB",
+            report.sessions.len(),
+            megabytes
+        );
+    } else {
+        println!(
+            "Removed {} sessions, freeing {:.1} MB",
+            report.sessions.len(),
+            megabytes
+        );
+    }
+    Ok(())
+}
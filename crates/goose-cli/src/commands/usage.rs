@@ -0,0 +1,88 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use console::style;
+use goose::usage::{UsageGroupBy, UsageStore, UsageSummary};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct UsageReport {
+    group_by: UsageGroupBy,
+    days: Option<u32>,
+    summaries: Vec<UsageSummary>,
+    total: UsageSummary,
+}
+
+pub fn handle_usage(days: Option<u32>, group_by: UsageGroupBy, format: String) -> Result<()> {
+    let store = UsageStore::open(&UsageStore::default_path())?;
+
+    let since = days.map(|days| Utc::now() - Duration::days(days as i64));
+    let report = UsageReport {
+        group_by,
+        days,
+        summaries: store.summarize(since, group_by)?,
+        total: store.total(since)?,
+    };
+
+    match format.as_str() {
+        "json" => {
+            println!("{}", serde_json::to_string(&report)?);
+        }
+        _ => {
+            if report.summaries.is_empty() {
+                println!("No usage recorded");
+                return Ok(());
+            }
+
+            let period = match days {
+                Some(days) => format!("last {} days", days),
+                None => "all time".to_string(),
+            };
+            println!(
+                "{}",
+                style(format!("Usage by {:?} ({})", group_by, period).to_lowercase())
+                    .cyan()
+                    .bold()
+            );
+
+            let width = report
+                .summaries
+                .iter()
+                .map(|s| display_key(&s.key).len())
+                .max()
+                .unwrap_or(0)
+                .max(5);
+            println!(
+                "  {:<width$}  {:>8}  {:>12}  {:>10}",
+                "",
+                "requests",
+                "tokens",
+                "cost",
+                width = width
+            );
+            for summary in &report.summaries {
+                print_summary(display_key(&summary.key), summary, width);
+            }
+            print_summary("total", &report.total, width);
+        }
+    }
+    Ok(())
+}
+
+fn display_key(key: &str) -> &str {
+    if key.is_empty() {
+        "(none)"
+    } else {
+        key
+    }
+}
+
+fn print_summary(label: &str, summary: &UsageSummary, width: usize) {
+    println!(
+        "  {:<width$}  {:>8}  {:>12}  {:>10}",
+        label,
+        summary.requests,
+        summary.total_tokens,
+        format!("${:.2}", summary.cost),
+        width = width
+    );
+}
@@ -1,45 +1,43 @@
+use anyhow::{anyhow, Result};
 use console::style;
 use goose::agents::extension::ExtensionError;
 use goose::agents::AgentFactory;
 use goose::config::{Config, ExtensionManager};
+use goose::message::{Message, Metadata};
+use goose::persona::Persona;
 use goose::session;
-use goose::session::Identifier;
+use goose::session::replay::{
+    self, Recorder, Recording, ReplayProvider, RECORD_SESSIONS_CONFIG_KEY,
+};
+use goose::session::storage::save_messages_with_metadata;
+use goose::session::{Identifier, SessionMetadata};
 use mcp_client::transport::Error as McpClientError;
+use serde_json::Value;
+use std::path::Path;
 use std::process;
+use std::sync::Arc;
 
 use super::output;
-use super::Session;
+use super::{Session, MODEL_METADATA_KEY, PROVIDER_METADATA_KEY};
+
+/// The session metadata key holding the persona a session runs in
+const PERSONA_METADATA_KEY: &str = "persona";
 
 pub async fn build_session(
     identifier: Option<Identifier>,
     resume: bool,
     extensions: Vec<String>,
     builtins: Vec<String>,
+    persona: Option<String>,
     debug: bool,
 ) -> Session {
-    // Load config and get provider/model
     let config = Config::global();
 
-    let provider_name: String = config
-        .get_param("GOOSE_PROVIDER")
-        .expect("No provider configured. Run 'goose configure' first");
-
-    let model: String = config
-        .get_param("GOOSE_MODEL")
-        .expect("No model configured. Run 'goose configure' first");
-    let model_config = goose::model::ModelConfig::new(model.clone());
-    let provider =
-        goose::providers::create(&provider_name, model_config).expect("Failed to create provider");
-
-    // Create the agent
-    let mut agent = AgentFactory::create(&AgentFactory::configured_version(), provider)
-        .expect("Failed to create agent");
-
     // Handle session file resolution and resuming
     let session_file = if resume {
         if let Some(identifier) = identifier {
             let session_file = session::get_path(identifier);
-            if !session_file.exists() {
+            if !session::session_exists(&session_file) {
                 output::render_error(&format!(
                     "Cannot resume session {} - no such session exists",
                     style(session_file.display()).cyan()
@@ -69,12 +67,26 @@ pub async fn build_session(
         session::get_path(id)
     };
 
+    let mut resumed_persona = None;
+    let mut resumed_model = None;
     if resume {
-        // Read the session metadata
-        let metadata = session::read_metadata(&session_file).unwrap_or_else(|e| {
-            output::render_error(&format!("Failed to read session metadata: {}", e));
-            process::exit(1);
-        });
+        // Read the session metadata, restoring the session file if goose stopped while writing it
+        let metadata = session::resume(&session_file)
+            .map(|saved| saved.metadata)
+            .unwrap_or_else(|e| {
+                output::render_error(&format!("Failed to read session metadata: {}", e));
+                process::exit(1);
+            });
+        resumed_persona = metadata
+            .custom
+            .get(PERSONA_METADATA_KEY)
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        // A session switched to another model with /model stays on it
+        let custom = |key: &str| metadata.custom.get(key).and_then(Value::as_str);
+        resumed_model = custom(PROVIDER_METADATA_KEY)
+            .zip(custom(MODEL_METADATA_KEY))
+            .map(|(provider, model)| (provider.to_string(), model.to_string()));
 
         let current_workdir =
             std::env::current_dir().expect("Failed to get current working directory");
@@ -90,6 +102,70 @@ pub async fn build_session(
         }
     }
 
+    // A resumed session stays in its persona unless asked for another
+    let persona = Persona::selected(persona.or(resumed_persona))
+        .and_then(|name| {
+            name.map(|name| Persona::named(&name).map(|persona| (name, persona)))
+                .transpose()
+        })
+        .unwrap_or_else(|e| {
+            output::render_error(&e.to_string());
+            process::exit(1);
+        });
+
+    // Load config and get provider/model
+    let provider_name: String = resumed_model
+        .as_ref()
+        .map(|(provider, _)| provider.clone())
+        .or_else(|| {
+            persona
+                .as_ref()
+                .and_then(|(_, persona)| persona.provider.clone())
+        })
+        .unwrap_or_else(|| {
+            config
+                .get_param("GOOSE_PROVIDER")
+                .expect("No provider configured. Run 'goose configure' first")
+        });
+
+    let model: String = resumed_model
+        .map(|(_, model)| model)
+        .or_else(|| {
+            persona
+                .as_ref()
+                .and_then(|(_, persona)| persona.model.clone())
+        })
+        .unwrap_or_else(|| {
+            config
+                .get_param("GOOSE_MODEL")
+                .expect("No model configured. Run 'goose configure' first")
+        });
+    let model_config = match &persona {
+        Some((_, persona)) => persona.model_config(model.clone()),
+        None => goose::model::ModelConfig::new(model.clone()),
+    };
+    let provider =
+        goose::providers::create(&provider_name, model_config).expect("Failed to create provider");
+
+    // Create the agent
+    let mut agent = AgentFactory::create(&AgentFactory::configured_version(), provider)
+        .expect("Failed to create agent");
+    if let Some((_, persona)) = &persona {
+        agent.set_persona(Some(persona.clone())).await;
+    }
+
+    // Record the session to replay it later
+    let record: bool = config
+        .get_param(RECORD_SESSIONS_CONFIG_KEY)
+        .unwrap_or(false);
+    if record {
+        let model = agent.provider().await.get_model_config();
+        match Recorder::create(&replay::recording_path(&session_file), model) {
+            Ok(recorder) => agent.record(Arc::new(recorder)).await,
+            Err(e) => output::render_error(&format!("Failed to record the session: {}", e)),
+        }
+    }
+
     // Setup extensions for the agent
     // Extensions need to be added after the session is created because we change directory when resuming a session
     for extension in ExtensionManager::get_all().expect("should load extensions") {
@@ -116,7 +192,8 @@ pub async fn build_session(
     }
 
     // Create new session
-    let mut session = Session::new(agent, session_file.clone(), debug);
+    let mut session =
+        Session::new(agent, session_file.clone(), debug).with_provider_name(provider_name.clone());
 
     // Add extensions if provided
     for extension_str in extensions {
@@ -140,14 +217,58 @@ pub async fn build_session(
         .extend_system_prompt(super::prompt::get_cli_prompt())
         .await;
 
-    // Only override system prompt if a system override exists
-    let system_prompt_file: Option<String> = config.get_param("GOOSE_SYSTEM_PROMPT_FILE_PATH").ok();
-    if let Some(ref path) = system_prompt_file {
-        let override_prompt =
-            std::fs::read_to_string(path).expect("Failed to read system prompt file");
-        session.agent.override_system_prompt(override_prompt).await;
+    if let Err(e) = session.agent.configure_system_prompt().await {
+        eprintln!("Failed to set up the system prompt: {}", e);
+        process::exit(1);
+    }
+
+    if let Some((name, _)) = persona {
+        let metadata = Metadata::from([(PERSONA_METADATA_KEY.to_string(), Value::String(name))]);
+        if let Err(e) = session.set_metadata(metadata).await {
+            output::render_error(&format!("Failed to note the persona in the session: {}", e));
+        }
     }
 
     output::display_session_info(resume, &provider_name, &model, &session_file);
     session
 }
+
+/// Build a session replaying the recording of the session in `session_file`, returning it with
+/// the recorded messages
+///
+/// No extensions are started, as the tool calls are answered from the recording. The replay is
+/// saved to a new session file in the temp dir, leaving the recorded session as it was.
+pub async fn build_replay_session(
+    session_file: &Path,
+    debug: bool,
+) -> Result<(Session, Vec<Message>)> {
+    let recording_file = replay::recording_path(session_file);
+    if !recording_file.exists() {
+        return Err(anyhow!(
+            "The session {} wasn't recorded, set {}=true to record sessions",
+            session_file.display(),
+            RECORD_SESSIONS_CONFIG_KEY
+        ));
+    }
+    let recording = Arc::new(Recording::load(&recording_file)?);
+    let recorded = session::resume(session_file)?;
+
+    let provider = Box::new(ReplayProvider::new(recording.clone()));
+    let mut agent = AgentFactory::create(&AgentFactory::configured_version(), provider)
+        .ok_or_else(|| anyhow!("Unknown agent version"))?;
+    agent.replay(recording.clone()).await;
+
+    let replay_file = std::env::temp_dir().join(format!(
+        "goose-replay-{}",
+        session_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    ));
+    let metadata = SessionMetadata::new(recorded.metadata.working_dir.clone());
+    save_messages_with_metadata(&replay_file, &metadata, &[])?;
+
+    let mut session = Session::new(agent, replay_file, debug);
+    session.replay = Some(recording);
+    Ok((session, recorded.messages))
+}
@@ -1,4 +1,4 @@
-use rustyline::completion::{Completer, Pair};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
 use rustyline::highlight::{CmdKind, Highlighter};
 use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
@@ -11,12 +11,16 @@ use super::CompletionCache;
 /// Completer for Goose CLI commands
 pub struct GooseCompleter {
     completion_cache: Arc<std::sync::RwLock<CompletionCache>>,
+    filenames: FilenameCompleter,
 }
 
 impl GooseCompleter {
     /// Create a new GooseCompleter with a reference to the Session's completion cache
     pub fn new(completion_cache: Arc<std::sync::RwLock<CompletionCache>>) -> Self {
-        Self { completion_cache }
+        Self {
+            completion_cache,
+            filenames: FilenameCompleter::new(),
+        }
     }
 
     /// Complete prompt names for the /prompt command
@@ -77,7 +81,7 @@ impl GooseCompleter {
 
     /// Complete flags for the /mode command
     fn complete_mode_flags(&self, line: &str) -> Result<(usize, Vec<Pair>)> {
-        let modes = ["auto", "approve", "smart_approve", "chat"];
+        let modes = ["auto", "approve", "smart_approve", "chat", "dry_run"];
 
         let parts: Vec<&str> = line.split_whitespace().collect();
 
@@ -129,6 +133,12 @@ impl GooseCompleter {
             "/prompts",
             "/prompt",
             "/mode",
+            "/pin",
+            "/attach",
+            "/undo",
+            "/rollback",
+            "/model",
+            "/compare",
         ];
 
         // Find commands that match the prefix
@@ -337,6 +347,10 @@ impl Completer for GooseCompleter {
             if line.starts_with("/mode") {
                 return self.complete_mode_flags(line);
             }
+
+            if line.starts_with("/attach ") {
+                return self.filenames.complete_path(line, pos);
+            }
         }
 
         // Default: no completions
@@ -17,6 +17,17 @@ pub enum InputResult {
     GooseMode(String),
     Plan(PlanCommandOptions),
     EndPlan,
+    Pin,
+    Attach(String),
+    Undo {
+        retry: bool,
+    },
+    Rollback(usize),
+    Model {
+        provider: Option<String>,
+        model: Option<String>,
+    },
+    Compare(String),
 }
 
 #[derive(Debug)]
@@ -89,6 +100,10 @@ fn handle_slash_command(input: &str) -> Option<InputResult> {
     const CMD_MODE: &str = "/mode ";
     const CMD_PLAN: &str = "/plan";
     const CMD_ENDPLAN: &str = "/endplan";
+    const CMD_ATTACH: &str = "/attach ";
+    const CMD_ROLLBACK: &str = "/rollback ";
+    const CMD_MODEL: &str = "/model";
+    const CMD_COMPARE: &str = "/compare ";
 
     match input {
         "/exit" | "/quit" => Some(InputResult::Exit),
@@ -97,6 +112,9 @@ fn handle_slash_command(input: &str) -> Option<InputResult> {
             Some(InputResult::Retry)
         }
         "/t" => Some(InputResult::ToggleTheme),
+        "/pin" => Some(InputResult::Pin),
+        "/undo" => Some(InputResult::Undo { retry: false }),
+        "/undo --retry" => Some(InputResult::Undo { retry: true }),
         "/prompts" => Some(InputResult::ListPrompts(None)),
         s if s.starts_with(CMD_PROMPTS) => {
             // Parse arguments for /prompts command
@@ -130,6 +148,35 @@ fn handle_slash_command(input: &str) -> Option<InputResult> {
         }
         s if s.starts_with(CMD_PLAN) => parse_plan_command(s[CMD_PLAN.len()..].trim().to_string()),
         s if s == CMD_ENDPLAN => Some(InputResult::EndPlan),
+        s if s.starts_with(CMD_ROLLBACK) => s[CMD_ROLLBACK.len()..]
+            .trim()
+            .parse()
+            .ok()
+            .map(InputResult::Rollback),
+        s if s.starts_with(CMD_ATTACH) => Some(InputResult::Attach(
+            s[CMD_ATTACH.len()..].trim().to_string(),
+        )),
+        s if s == CMD_MODEL || s.starts_with("/model ") => {
+            let args: Vec<&str> = s[CMD_MODEL.len()..].split_whitespace().collect();
+            match args.as_slice() {
+                [] => Some(InputResult::Model {
+                    provider: None,
+                    model: None,
+                }),
+                [model] => Some(InputResult::Model {
+                    provider: None,
+                    model: Some(model.to_string()),
+                }),
+                [provider, model] => Some(InputResult::Model {
+                    provider: Some(provider.to_string()),
+                    model: Some(model.to_string()),
+                }),
+                _ => None,
+            }
+        }
+        s if s.starts_with(CMD_COMPARE) => Some(s[CMD_COMPARE.len()..].trim())
+            .filter(|message| !message.is_empty())
+            .map(|message| InputResult::Compare(message.to_string())),
         _ => None,
     }
 }
@@ -204,13 +251,19 @@ fn print_help() {
 /builtin <names> - Add builtin extensions by name (comma-separated)
 /prompts [--extension <name>] - List all available prompts, optionally filtered by extension
 /prompt <n> [--info] [key=value...] - Get prompt info or execute a prompt
-/mode <name> - Set the goose mode to use ('auto', 'approve', 'chat')
+/mode <name> - Set the goose mode to use ('auto', 'approve', 'smart_approve', 'chat', 'dry_run')
 /plan <message_text> -  Enters 'plan' mode with optional message. Create a plan based on the current messages and asks user if they want to act on it.
                         If user acts on the plan, goose mode is set to 'auto' and returns to 'normal' goose mode.
                         To warm up goose before using '/plan', we recommend setting '/mode approve' & putting appropriate context into goose.
                         The model is used based on $GOOSE_PLANNER_PROVIDER and $GOOSE_PLANNER_MODEL environment variables.
                         If no model is set, the default model is used.
 /endplan - Exit plan mode and return to 'normal' goose mode.
+/pin - Pin your last message, so it is never summarized away when the conversation is compacted
+/attach <path> - Attach a file to your next message
+/undo [--retry] - Remove goose's reply to your last message, and with --retry have goose answer it again
+/rollback <turn> - Put the files back as they were after that turn, and remove the turns after it (needs GOOSE_CHECKPOINTS)
+/model [provider] [model] - Show the model, or answer the next turns with another, keeping the conversation
+/compare <message> - Show how each model in GOOSE_COMPARE_MODELS answers the message, without running their tool calls
 /? or /help - Display this help message
 
 Navigation:
@@ -251,6 +304,28 @@ mod tests {
             handle_slash_command("/t"),
             Some(InputResult::ToggleTheme)
         ));
+        assert!(matches!(
+            handle_slash_command("/pin"),
+            Some(InputResult::Pin)
+        ));
+        assert!(matches!(
+            handle_slash_command("/undo"),
+            Some(InputResult::Undo { retry: false })
+        ));
+        assert!(matches!(
+            handle_slash_command("/undo --retry"),
+            Some(InputResult::Undo { retry: true })
+        ));
+        assert!(matches!(
+            handle_slash_command("/rollback 3"),
+            Some(InputResult::Rollback(3))
+        ));
+        assert!(handle_slash_command("/rollback three").is_none());
+        if let Some(InputResult::Attach(path)) = handle_slash_command("/attach  data/sales.csv ") {
+            assert_eq!(path, "data/sales.csv");
+        } else {
+            panic!("Expected Attach");
+        }
 
         // Test extension command
         if let Some(InputResult::AddExtension(cmd)) = handle_slash_command("/extension foo bar") {
@@ -268,6 +343,33 @@ mod tests {
 
         // Test unknown commands
         assert!(handle_slash_command("/unknown").is_none());
+
+        assert!(matches!(
+            handle_slash_command("/model"),
+            Some(InputResult::Model {
+                provider: None,
+                model: None
+            })
+        ));
+        if let Some(InputResult::Model { provider, model }) =
+            handle_slash_command("/model openrouter anthropic/claude-3.5-sonnet")
+        {
+            assert_eq!(provider.as_deref(), Some("openrouter"));
+            assert_eq!(model.as_deref(), Some("anthropic/claude-3.5-sonnet"));
+        } else {
+            panic!("Expected Model");
+        }
+        assert!(handle_slash_command("/model a b c").is_none());
+        assert!(handle_slash_command("/models").is_none());
+
+        if let Some(InputResult::Compare(message)) =
+            handle_slash_command("/compare Why is the build slow?")
+        {
+            assert_eq!(message, "Why is the build slow?");
+        } else {
+            panic!("Expected Compare");
+        }
+        assert!(handle_slash_command("/compare ").is_none());
     }
 
     #[test]
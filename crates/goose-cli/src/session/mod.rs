@@ -2,10 +2,13 @@ mod builder;
 mod completion;
 mod input;
 mod output;
+mod pause;
+mod progress;
 mod prompt;
+mod sampling;
 mod thinking;
 
-pub use builder::build_session;
+pub use builder::{build_replay_session, build_session};
 use goose::providers::base::Provider;
 pub use goose::session::Identifier;
 
@@ -13,21 +16,33 @@ use anyhow::Result;
 use completion::GooseCompleter;
 use etcetera::choose_app_strategy;
 use etcetera::AppStrategy;
+use goose::agents::approval::{ToolApproval, TOOL_CONFIRMATION_PROMPT};
+use goose::agents::budget::BUDGET_CONFIRMATION_TOOL;
+use goose::agents::compare::candidates_from_config;
 use goose::agents::extension::{Envs, ExtensionConfig};
+use goose::agents::limits::stop_reason;
+use goose::agents::progress::ToolProgress;
 use goose::agents::{Agent, SessionConfig};
+use goose::compaction;
+use goose::config::reload::{apply_config_reload, config_reload_events, ConfigReloadEvent};
 use goose::config::Config;
-use goose::message::{Message, MessageContent};
+use goose::message::{AttachmentContent, Message, MessageContent, Metadata};
+use goose::recipe::{retry_message, Recipe, MAX_CHECK_ROUNDS};
 use goose::session;
+use goose::session::replay::Recording;
 use mcp_core::handler::ToolError;
 use mcp_core::prompt::PromptMessage;
+use mcp_core::tool::ToolCall;
 
 use rand::{distributions::Alphanumeric, Rng};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
 
 pub enum RunMode {
     Normal,
@@ -42,8 +57,21 @@ pub struct Session {
     completion_cache: Arc<std::sync::RwLock<CompletionCache>>,
     debug: bool, // New field for debug mode
     run_mode: RunMode,
+    // Files to send with the next message
+    attachments: Vec<AttachmentContent>,
+    // The recording answering completions and tool calls, when replaying a session
+    replay: Option<Arc<Recording>>,
+    // The provider answering the session, which /model switches models within
+    provider_name: String,
+    // Extensions' requests for completions, which wait for the user while a reply is shown
+    sampling_requests: Arc<tokio::sync::Mutex<mpsc::Receiver<sampling::SamplingRequest>>>,
+    tool_progress: Arc<tokio::sync::Mutex<mpsc::Receiver<ToolProgress>>>,
 }
 
+/// Session metadata keys naming the provider and model the session was switched to
+pub const PROVIDER_METADATA_KEY: &str = "provider";
+pub const MODEL_METADATA_KEY: &str = "model";
+
 // Cache structure for completion data
 struct CompletionCache {
     prompts: HashMap<String, Vec<String>>,
@@ -99,14 +127,23 @@ pub async fn classify_planner_response(
 
 impl Session {
     pub fn new(agent: Box<dyn Agent>, session_file: PathBuf, debug: bool) -> Self {
-        let messages = match session::read_messages(&session_file) {
-            Ok(msgs) => msgs,
+        let messages = match session::resume(&session_file) {
+            Ok(saved) => saved.messages,
             Err(e) => {
                 eprintln!("Warning: Failed to load message history: {}", e);
                 Vec::new()
             }
         };
 
+        let (sampling_tx, sampling_rx) = mpsc::channel(8);
+        goose::agents::sampling::set_approver(Arc::new(sampling::ChannelApprover::new(
+            sampling_tx,
+        )));
+        let (progress_tx, progress_rx) = mpsc::channel(64);
+        goose::agents::progress::set_listener(Arc::new(progress::ChannelListener::new(
+            progress_tx,
+        )));
+
         Session {
             agent,
             messages,
@@ -114,7 +151,89 @@ impl Session {
             completion_cache: Arc::new(std::sync::RwLock::new(CompletionCache::new())),
             debug,
             run_mode: RunMode::Normal,
+            attachments: Vec::new(),
+            replay: None,
+            provider_name: Config::global()
+                .get_param("GOOSE_PROVIDER")
+                .unwrap_or_default(),
+            sampling_requests: Arc::new(tokio::sync::Mutex::new(sampling_rx)),
+            tool_progress: Arc::new(tokio::sync::Mutex::new(progress_rx)),
+        }
+    }
+
+    /// Name the provider the session was started with, when it isn't GOOSE_PROVIDER
+    pub fn with_provider_name(mut self, provider_name: String) -> Self {
+        self.provider_name = provider_name;
+        self
+    }
+
+    /// Show how each model in GOOSE_COMPARE_MODELS answers `content` after the conversation so
+    /// far, which is left as it is, as are the files attached for the next message
+    async fn compare(&self, content: &str) -> Result<()> {
+        let candidates = candidates_from_config()?;
+        let mut messages = self.messages.clone();
+        messages.push(
+            self.attachments
+                .iter()
+                .cloned()
+                .fold(Message::user().with_text(content), |message, attachment| {
+                    message.with_attachment(attachment)
+                }),
+        );
+
+        output::show_thinking();
+        let comparisons = self.agent.compare(&messages, &candidates).await;
+        output::hide_thinking();
+        for comparison in comparisons? {
+            output::render_comparison(&comparison, self.debug);
+        }
+        println!();
+        Ok(())
+    }
+
+    /// Answer the next turns with `model`, from `provider` or else the session's provider,
+    /// keeping the conversation
+    ///
+    /// The session's metadata notes the switch, so resuming the session stays on the model.
+    pub async fn switch_model(&mut self, provider: Option<String>, model: String) -> Result<()> {
+        if self.replay.is_some() {
+            return Err(anyhow::anyhow!(
+                "A replayed session is answered from its recording, not a model"
+            ));
         }
+        let provider = provider.unwrap_or_else(|| self.provider_name.clone());
+        let created =
+            goose::providers::create(&provider, goose::model::ModelConfig::new(model.clone()))
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Failed to switch to {} with model {}: {}",
+                        provider,
+                        model,
+                        e
+                    )
+                })?;
+        self.agent.update_provider(created).await;
+        self.provider_name = provider.clone();
+        self.set_metadata(Metadata::from([
+            (PROVIDER_METADATA_KEY.to_string(), Value::String(provider)),
+            (MODEL_METADATA_KEY.to_string(), Value::String(model)),
+        ]))
+        .await
+    }
+
+    /// Attach a file to the next message
+    pub fn attach(&mut self, path: &Path) -> Result<&AttachmentContent> {
+        self.attachments.push(AttachmentContent::from_path(path)?);
+        Ok(self.attachments.last().unwrap())
+    }
+
+    /// A user message with the given text and the files attached since the last one
+    fn user_message(&mut self, text: &str) -> Message {
+        self.attachments
+            .drain(..)
+            .fold(Message::user().with_text(text), |message, attachment| {
+                message.with_attachment(attachment)
+            })
     }
 
     /// Add a stdio extension to the session
@@ -242,7 +361,8 @@ impl Session {
 
     /// Process a single message and get the response
     async fn process_message(&mut self, message: String) -> Result<()> {
-        self.messages.push(Message::user().with_text(&message));
+        let message = self.user_message(&message);
+        self.messages.push(message);
         // Get the provider from the agent for description generation
         let provider = self.agent.provider().await;
 
@@ -253,8 +373,90 @@ impl Session {
         Ok(())
     }
 
+    /// Go on with the agent's run if it was paused the last time the session was open
+    async fn resume_paused_run(&mut self, interactive: bool) -> Result<()> {
+        let paused = session::pause::take_paused(&self.session_file, &self.messages).await?;
+        if paused.is_none() {
+            return Ok(());
+        }
+        output::goose_mode_message("Resuming the run that was paused in this session");
+        if interactive {
+            output::show_thinking();
+        }
+        self.process_agent_response(interactive).await?;
+        if interactive {
+            output::hide_thinking();
+        }
+        Ok(())
+    }
+
+    /// Re-drive the agent through the turns of `recorded`, the messages of a recorded session,
+    /// and report where the replay differs from them
+    pub async fn replay(&mut self, recorded: &[Message]) -> Result<()> {
+        for message in recorded
+            .iter()
+            .filter(|message| compaction::starts_turn(message))
+        {
+            self.messages.push(message.clone());
+            session::persist_messages(&self.session_file, &self.messages, None).await?;
+            println!(
+                "{} {}",
+                console::style("( O)>").cyan().bold(),
+                message.as_concat_text()
+            );
+            self.process_agent_response(false).await?;
+        }
+
+        let diverged = self
+            .messages
+            .iter()
+            .zip(recorded)
+            .position(|(replayed, recorded)| {
+                replayed.role != recorded.role || replayed.content != recorded.content
+            })
+            .or_else(|| {
+                (self.messages.len() != recorded.len())
+                    .then(|| self.messages.len().min(recorded.len()))
+            });
+        match diverged {
+            Some(index) => output::render_error(&format!(
+                "The replay diverged from the recorded session at message {}",
+                index
+            )),
+            None => output::goose_mode_message("The replay matches the recorded session"),
+        }
+        if let Some(recording) = &self.replay {
+            let (completions, tool_results) = recording.unused();
+            if completions + tool_results > 0 {
+                output::goose_mode_message(&format!(
+                    "{} completions and {} tool results of the recording weren't replayed",
+                    completions, tool_results
+                ));
+            }
+        }
+        println!("The replay is saved to {}", self.session_file.display());
+        Ok(())
+    }
+
+    /// Apply the config changes published since the last call
+    async fn apply_config_reloads(&mut self, reloads: &mut broadcast::Receiver<ConfigReloadEvent>) {
+        loop {
+            match reloads.try_recv() {
+                Ok(event) => {
+                    for change in apply_config_reload(self.agent.as_mut(), &event).await {
+                        output::render_config_change(&change);
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+    }
+
     /// Start an interactive session, optionally with an initial message
     pub async fn interactive(&mut self, message: Option<String>) -> Result<()> {
+        self.resume_paused_run(true).await?;
+
         // Process initial message if provided
         if let Some(msg) = message {
             self.process_message(msg).await?;
@@ -305,15 +507,23 @@ impl Session {
                 }
             };
 
+        // Config changes saved while the session runs are applied before the next message
+        goose::config::reload::watch_config();
+        // Old sessions past the retention limits are removed while the session runs
+        goose::session::retention::start_cleanup();
+        let mut config_reloads = config_reload_events();
+
         output::display_greeting();
         loop {
             match input::get_input(&mut editor)? {
                 input::InputResult::Message(content) => {
+                    self.apply_config_reloads(&mut config_reloads).await;
                     match self.run_mode {
                         RunMode::Normal => {
                             save_history(&mut editor);
 
-                            self.messages.push(Message::user().with_text(&content));
+                            let message = self.user_message(&content);
+                            self.messages.push(message);
 
                             // Get the provider from the agent for description generation
                             let provider = self.agent.provider().await;
@@ -393,9 +603,11 @@ impl Session {
                     let mode = mode.to_lowercase();
 
                     // Check if mode is valid
-                    if !["auto", "approve", "chat", "smart_approve"].contains(&mode.as_str()) {
+                    if !["auto", "approve", "chat", "smart_approve", "dry_run"]
+                        .contains(&mode.as_str())
+                    {
                         output::render_error(&format!(
-                            "Invalid mode '{}'. Mode must be one of: auto, approve, chat",
+                            "Invalid mode '{}'. Mode must be one of: auto, approve, smart_approve, chat, dry_run",
                             mode
                         ));
                         continue;
@@ -422,6 +634,94 @@ impl Session {
                     self.plan_with_reasoner_model(plan_messages, reasoner)
                         .await?;
                 }
+                input::InputResult::Attach(path) => {
+                    save_history(&mut editor);
+
+                    match self.attach(Path::new(&path)) {
+                        Ok(attachment) => output::goose_mode_message(&format!(
+                            "Attached {}, it is sent with your next message",
+                            attachment.name
+                        )),
+                        Err(e) => output::render_error(&e.to_string()),
+                    }
+                    continue;
+                }
+                input::InputResult::Pin => {
+                    save_history(&mut editor);
+
+                    match self.pin_last_message().await {
+                        Ok(Some(text)) => {
+                            let preview: String = text.chars().take(60).collect();
+                            output::goose_mode_message(&format!("Pinned \"{}\"", preview));
+                        }
+                        Ok(None) => output::render_error("There is no message to pin yet"),
+                        Err(e) => output::render_error(&e.to_string()),
+                    }
+                    continue;
+                }
+                input::InputResult::Undo { retry } => {
+                    save_history(&mut editor);
+
+                    match session::undo_turn(&self.session_file) {
+                        Ok(saved) => {
+                            self.messages = saved.messages;
+                            output::goose_mode_message("Removed the reply to your last message");
+                        }
+                        Err(e) => {
+                            output::render_error(&e.to_string());
+                            continue;
+                        }
+                    }
+                    if retry {
+                        output::show_thinking();
+                        self.process_agent_response(true).await?;
+                        output::hide_thinking();
+                    }
+                    continue;
+                }
+                input::InputResult::Rollback(turn) => {
+                    save_history(&mut editor);
+
+                    match session::checkpoint::rollback(&self.session_file, turn).await {
+                        Ok(saved) => {
+                            self.messages = saved.messages;
+                            output::goose_mode_message(&format!(
+                                "Rolled the files and the conversation back to the end of turn {}",
+                                turn
+                            ));
+                        }
+                        Err(e) => output::render_error(&e.to_string()),
+                    }
+                    continue;
+                }
+                input::InputResult::Model { provider, model } => {
+                    save_history(&mut editor);
+
+                    let Some(model) = model else {
+                        let config = self.agent.provider().await.get_model_config();
+                        output::goose_mode_message(&format!(
+                            "Using {} with model {}",
+                            self.provider_name, config.model_name
+                        ));
+                        continue;
+                    };
+                    match self.switch_model(provider, model.clone()).await {
+                        Ok(()) => output::goose_mode_message(&format!(
+                            "Switched to {} with model {}, the conversation is kept",
+                            self.provider_name, model
+                        )),
+                        Err(e) => output::render_error(&e.to_string()),
+                    }
+                    continue;
+                }
+                input::InputResult::Compare(content) => {
+                    save_history(&mut editor);
+
+                    if let Err(e) = self.compare(&content).await {
+                        output::render_error(&e.to_string());
+                    }
+                    continue;
+                }
                 input::InputResult::EndPlan => {
                     self.run_mode = RunMode::Normal;
                     output::render_exit_plan_mode();
@@ -491,6 +791,7 @@ impl Session {
             }
         }
 
+        self.remember().await;
         println!(
             "\nClosing session. Recorded to {}",
             self.session_file.display()
@@ -566,11 +867,109 @@ impl Session {
 
     /// Process a single message and exit
     pub async fn headless(&mut self, message: String) -> Result<()> {
-        self.process_message(message).await
+        self.resume_paused_run(false).await?;
+        self.process_message(message).await?;
+        // A run stopped by one of its limits didn't get to finish the task
+        match self.messages.last().and_then(stop_reason) {
+            Some(reason) => Err(anyhow::anyhow!("The run was stopped: {}", reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Run a recipe's prompt and then its steps, continuing in interactive mode after them if
+    /// `interactive`
+    ///
+    /// The extensions the recipe names that aren't running yet are started as builtins.
+    pub async fn run_recipe(
+        &mut self,
+        recipe: &Recipe,
+        interactive: bool,
+        output: Option<&Path>,
+    ) -> Result<()> {
+        let running = self.agent.list_extensions().await;
+        for name in recipe.extensions.iter().flatten() {
+            if !running.contains(name) {
+                self.add_builtin(name.clone()).await.map_err(|e| {
+                    anyhow::anyhow!(
+                        "The recipe uses the extension {}, which isn't configured: {}",
+                        name,
+                        e
+                    )
+                })?;
+            }
+        }
+        self.agent.set_recipe(Some(recipe.clone())).await;
+        self.set_metadata(Metadata::from([(
+            "recipe".to_string(),
+            Value::String(recipe.name.clone()),
+        )]))
+        .await?;
+
+        for message in recipe.messages() {
+            self.headless(message.to_string()).await?;
+        }
+        let failed = self.run_checks(recipe).await?;
+        if recipe.response.is_some() {
+            self.write_final_output(output).await?;
+        }
+        if !failed.is_empty() {
+            let message = format!(
+                "The recipe's checks still fail after {} rounds:\n- {}",
+                MAX_CHECK_ROUNDS,
+                failed.join("\n- ")
+            );
+            if !interactive {
+                self.remember().await;
+                return Err(anyhow::anyhow!(message));
+            }
+            eprintln!("{}", console::style(message).yellow());
+        }
+        if interactive {
+            self.interactive(None).await?;
+        } else {
+            self.remember().await;
+        }
+        Ok(())
+    }
+
+    /// Run the recipe's checks, sending those that fail back to the agent until they pass or
+    /// it has had [`MAX_CHECK_ROUNDS`] more tries, and return those that still fail
+    async fn run_checks(&mut self, recipe: &Recipe) -> Result<Vec<String>> {
+        if recipe.checks().next().is_none() {
+            return Ok(Vec::new());
+        }
+        let working_dir = std::env::current_dir()?;
+        let mut round = 0;
+        loop {
+            let answer = self
+                .messages
+                .iter()
+                .rev()
+                .find(|message| message.role == mcp_core::role::Role::Assistant)
+                .map(Message::as_concat_text)
+                .unwrap_or_default();
+            let failed = recipe.failed_checks(&working_dir, &answer).await;
+            if failed.is_empty() || round == MAX_CHECK_ROUNDS {
+                return Ok(failed);
+            }
+            round += 1;
+            println!(
+                "{}",
+                console::style(format!(
+                    "{} of the recipe's checks failed, continuing ({} of {})",
+                    failed.len(),
+                    round,
+                    MAX_CHECK_ROUNDS
+                ))
+                .dim()
+            );
+            self.headless(retry_message(&failed)).await?;
+        }
     }
 
     async fn process_agent_response(&mut self, interactive: bool) -> Result<()> {
         let session_id = session::Identifier::Path(self.session_file.clone());
+        let cancel_token = CancellationToken::new();
         let mut stream = self
             .agent
             .reply(
@@ -579,10 +978,19 @@ impl Session {
                     id: session_id,
                     working_dir: std::env::current_dir()
                         .expect("failed to get current session working directory"),
+                    recipe: None,
                 }),
+                cancel_token.clone(),
             )
             .await?;
 
+        let mut pause_signal = pause::PauseSignal::new();
+        let mut pause_requested = false;
+        let sampling_requests = Arc::clone(&self.sampling_requests);
+        let mut sampling_requests = sampling_requests.lock().await;
+        let tool_progress = Arc::clone(&self.tool_progress);
+        let mut tool_progress = tool_progress.lock().await;
+
         use futures::StreamExt;
         loop {
             tokio::select! {
@@ -593,12 +1001,27 @@ impl Session {
                             if let Some(MessageContent::ToolConfirmationRequest(confirmation)) = message.content.first() {
                                 output::hide_thinking();
 
+                                if let Some(recording) = &self.replay {
+                                    // The calls that were allowed are the ones with recorded results
+                                    let call = ToolCall::new(&confirmation.tool_name, confirmation.arguments.clone());
+                                    let approved = confirmation.tool_name == BUDGET_CONFIRMATION_TOOL
+                                        || recording.has_tool_result(&call);
+                                    self.agent.handle_confirmation(confirmation.id.clone(), approved).await;
+                                }
                                 // Format the confirmation prompt
-                                let prompt = "Goose would like to call the above tool, do you approve?".to_string();
-
-                                // Get confirmation from user
-                                let confirmed = cliclack::confirm(prompt).initial_value(true).interact()?;
-                                self.agent.handle_confirmation(confirmation.id.clone(), confirmed).await;
+                                else if confirmation.tool_name == BUDGET_CONFIRMATION_TOOL {
+                                    let prompt = confirmation.prompt.clone().unwrap_or_default();
+                                    let confirmed = cliclack::confirm(prompt).initial_value(true).interact()?;
+                                    self.agent.handle_confirmation(confirmation.id.clone(), confirmed).await;
+                                } else {
+                                    // Calls matching an approval rule say why they need approval
+                                    let prompt = match &confirmation.prompt {
+                                        Some(prompt) if prompt != TOOL_CONFIRMATION_PROMPT => prompt.clone(),
+                                        _ => "Goose would like to call the above tool, do you approve?".to_string(),
+                                    };
+                                    let approval = ask_tool_approval(&prompt, &confirmation.arguments)?;
+                                    self.agent.handle_approval(confirmation.id.clone(), approval).await;
+                                }
                             }
                             // otherwise we have a model/tool to render
                             else {
@@ -610,6 +1033,17 @@ impl Session {
                                 if interactive {output::hide_thinking()};
                                 output::render_message(&message, self.debug);
                                 if interactive {output::show_thinking()};
+
+                                if pause_requested && session::pause::can_pause_after(&message) {
+                                    // The next completion hasn't started, so the run stops here
+                                    drop(stream);
+                                    session::pause::pause(&self.session_file, &self.messages).await?;
+                                    if interactive {output::hide_thinking()};
+                                    output::goose_mode_message(
+                                        "Paused the run, resuming this session picks it up where it left off",
+                                    );
+                                    break;
+                                }
                             }
                         }
                         Some(Err(e)) => {
@@ -629,7 +1063,35 @@ impl Session {
                         None => break,
                     }
                 }
+                Some(request) = sampling_requests.recv() => {
+                    if interactive {output::hide_thinking()};
+                    println!(
+                        "{}\n{}",
+                        console::style(format!(
+                            "The {} extension would like the model to answer:",
+                            request.extension
+                        ))
+                        .yellow(),
+                        console::style(&request.preview).dim()
+                    );
+                    let approved = cliclack::confirm("Allow?").initial_value(false).interact()?;
+                    let _ = request.respond.send(approved);
+                    if interactive {output::show_thinking()};
+                }
+                Some(progress) = tool_progress.recv() => {
+                    if interactive {output::hide_thinking()};
+                    output::render_tool_progress(&progress);
+                    if interactive {output::show_thinking()};
+                }
+                _ = pause_signal.recv(), if !pause_requested => {
+                    pause_requested = true;
+                    if interactive {output::hide_thinking()};
+                    output::goose_mode_message("Pausing once the running tools finish...");
+                    if interactive {output::show_thinking()};
+                }
                 _ = tokio::signal::ctrl_c() => {
+                    // Stop the request to the provider and any tools that are running
+                    cancel_token.cancel();
                     drop(stream);
                     if let Err(e) = self.handle_interrupted_messages(true).await {
                         eprintln!("Error handling interruption: {}", e);
@@ -776,6 +1238,75 @@ impl Session {
         cache.last_updated = Instant::now();
     }
 
+    /// Pin the last message the user sent, so compaction keeps it verbatim
+    ///
+    /// Returns the pinned message's text, or None if the user hasn't sent one yet.
+    async fn pin_last_message(&mut self) -> Result<Option<String>> {
+        let Some(index) = self.messages.iter().rposition(compaction::starts_turn) else {
+            return Ok(None);
+        };
+        let mut metadata = session::read_metadata(&self.session_file)?;
+        if !metadata.pinned_messages.contains(&index) {
+            metadata.pinned_messages.push(index);
+            session::update_metadata(&self.session_file, &metadata).await?;
+        }
+        Ok(Some(self.messages[index].as_concat_text()))
+    }
+
+    /// Extract long-term memories from the messages added since the session was last
+    /// remembered, when memory is on
+    ///
+    /// Failing to is only a warning, as the session itself has been saved.
+    pub async fn remember(&self) {
+        let Some(memory) = goose::memory::global() else {
+            return;
+        };
+        let provider = self.agent.provider().await;
+        match memory
+            .remember_session(&self.session_file, provider.as_ref().as_ref(), 0)
+            .await
+        {
+            Ok(remembered) if !remembered.is_empty() => println!(
+                "{}",
+                console::style(format!("Remembered {} new memories", remembered.len())).dim()
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Failed to extract memories: {}", e),
+        }
+    }
+
+    /// Have each reply end with a value matching the JSON schema in the file at `path`, which
+    /// can be JSON or YAML
+    pub async fn set_response_schema(&mut self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let schema: Value = serde_yaml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("{} isn't a JSON schema: {}", path.display(), e))?;
+        self.agent.set_response_schema(Some(schema)).await
+    }
+
+    /// Write the value the last reply ended with as JSON to `path`, or to stdout when it is
+    /// None or `-`, failing if the agent didn't give one that matches the response schema
+    pub async fn write_final_output(&self, path: Option<&Path>) -> Result<()> {
+        let output = self.agent.final_output().await.ok_or_else(|| {
+            anyhow::anyhow!("The agent didn't give a result matching the response schema")
+        })?;
+        let json = serde_json::to_string_pretty(&output)?;
+        match path.filter(|path| *path != Path::new("-")) {
+            Some(path) => std::fs::write(path, json + "\n")
+                .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?,
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+
+    /// Set metadata values on the session, keeping the values of other keys
+    pub async fn set_metadata(&self, values: Metadata) -> Result<()> {
+        let mut metadata = session::read_metadata(&self.session_file)?;
+        metadata.custom.extend(values);
+        session::update_metadata(&self.session_file, &metadata).await
+    }
+
     pub fn message_history(&self) -> Vec<Message> {
         self.messages.clone()
     }
@@ -796,6 +1327,26 @@ impl Session {
     }
 }
 
+/// Ask the user whether to make a tool call, letting them change its arguments first
+fn ask_tool_approval(prompt: &str, arguments: &Value) -> Result<ToolApproval> {
+    let choice = cliclack::select(prompt)
+        .item("approve", "Yes", "")
+        .item("edit", "Yes, with other arguments", "")
+        .item("deny", "No", "")
+        .interact()?;
+    Ok(match choice {
+        "approve" => ToolApproval::Approve,
+        "edit" => {
+            let edited: String = cliclack::input("Arguments, as JSON")
+                .default_input(&arguments.to_string())
+                .validate(|input: &String| serde_json::from_str::<Value>(input).map(|_| ()))
+                .interact()?;
+            ToolApproval::Edit(serde_json::from_str(&edited)?)
+        }
+        _ => ToolApproval::Deny,
+    })
+}
+
 fn get_reasoner() -> Result<Box<dyn Provider + Send + Sync>, anyhow::Error> {
     use goose::model::ModelConfig;
     use goose::providers::create;
@@ -1,5 +1,7 @@
 use bat::WrappingMode;
 use console::style;
+use goose::agents::compare::Comparison;
+use goose::agents::progress::ToolProgress;
 use goose::config::Config;
 use goose::message::{Message, MessageContent, ToolRequest, ToolResponse};
 use mcp_core::prompt::PromptArgument;
@@ -107,6 +109,14 @@ pub fn render_message(message: &Message, debug: bool) {
             MessageContent::Image(image) => {
                 println!("Image: [data: {}, type: {}]", image.data, image.mime_type);
             }
+            MessageContent::Attachment(attachment) => {
+                println!(
+                    "{} {} {}",
+                    style("Attached").dim(),
+                    attachment.name,
+                    style(format!("({})", attachment.mime_type)).dim()
+                );
+            }
             MessageContent::Thinking(thinking) => {
                 if std::env::var("GOOSE_CLI_SHOW_THINKING").is_ok() {
                     println!("\n{}", style("Thinking:").dim().italic());
@@ -126,6 +136,36 @@ pub fn render_message(message: &Message, debug: bool) {
     println!();
 }
 
+/// Show one model's answer in a comparison, headed by its latency, tokens and cost
+pub fn render_comparison(comparison: &Comparison, debug: bool) {
+    let mut header = comparison.candidate.to_string();
+    if let Ok(answer) = &comparison.answer {
+        let tokens = answer
+            .usage
+            .usage
+            .total_tokens
+            .map(|tokens| format!("{} tokens", tokens))
+            .unwrap_or_else(|| "tokens unknown".to_string());
+        let cost = answer
+            .usage
+            .cost
+            .map(|cost| format!("${:.4}", cost))
+            .unwrap_or_else(|| "cost unknown".to_string());
+        header = format!(
+            "{} | {:.1}s | {} | {}",
+            header,
+            answer.latency.as_secs_f64(),
+            tokens,
+            cost
+        );
+    }
+    println!("\n{}", style(format!("── {}", header)).cyan().bold());
+    match &comparison.answer {
+        Ok(answer) => render_message(&answer.message, debug),
+        Err(e) => render_error(e),
+    }
+}
+
 pub fn render_enter_plan_mode() {
     println!(
         "\n{} {}\n",
@@ -200,6 +240,16 @@ fn render_tool_response(resp: &ToolResponse, theme: Theme, debug: bool) {
     }
 }
 
+/// Show what a running tool reported, such as a line a shell command printed
+pub fn render_tool_progress(progress: &ToolProgress) {
+    let line = match (&progress.message, progress.total) {
+        (Some(message), _) => message.clone(),
+        (None, Some(total)) => format!("{} of {}", progress.progress, total),
+        (None, None) => return,
+    };
+    println!("  {} {}", style("│").dim(), style(line).dim());
+}
+
 pub fn render_error(message: &str) {
     println!("\n  {} {}\n", style("error:").red().bold(), message);
 }
@@ -271,6 +321,13 @@ pub fn render_extension_error(name: &str, error: &str) {
     println!();
 }
 
+pub fn render_config_change(change: &Result<String, String>) {
+    match change {
+        Ok(change) => println!("  {} {}", style("config reloaded,").green(), change),
+        Err(e) => println!("  {} {}", style("config reload").red(), style(e).dim()),
+    }
+}
+
 pub fn render_builtin_success(names: &str) {
     println!();
     println!(
@@ -0,0 +1,90 @@
+/// Listens for the user asking to pause a run with CTRL+Z
+///
+/// Extensions run in their own process groups, so the shell commands they run aren't stopped by
+/// the key, and goose stops the run itself once the running tools finish. Outside a run, once
+/// the listener is dropped, the key stops goose as usual. There is no such key on Windows, where
+/// runs can't be paused.
+pub struct PauseSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+/// The handler tokio installed for CTRL+Z, kept while the key is back to stopping goose
+///
+/// Tokio installs its handler only for the first listener, so later listeners put it back.
+#[cfg(unix)]
+static LISTENER_ACTION: std::sync::Mutex<Option<libc::sigaction>> = std::sync::Mutex::new(None);
+
+impl PauseSignal {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        let signal =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::from_raw(libc::SIGTSTP))
+                .map_err(|e| tracing::warn!("Failed to listen for CTRL+Z: {}", e))
+                .ok();
+        #[cfg(unix)]
+        if signal.is_some() {
+            if let Some(action) = LISTENER_ACTION.lock().unwrap().take() {
+                // SAFETY: the action is the one sigaction returned for this signal
+                unsafe { libc::sigaction(libc::SIGTSTP, &action, std::ptr::null_mut()) };
+            }
+        }
+        Self {
+            #[cfg(unix)]
+            signal,
+        }
+    }
+
+    /// Wait for the user to ask for a pause
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut() {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+#[cfg(unix)]
+impl Drop for PauseSignal {
+    fn drop(&mut self) {
+        if self.signal.is_none() {
+            return;
+        }
+        // SAFETY: both actions are plain values, and the default action has no handler to call
+        unsafe {
+            let mut default: libc::sigaction = std::mem::zeroed();
+            default.sa_sigaction = libc::SIG_DFL;
+            let mut listener: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(libc::SIGTSTP, &default, &mut listener) == 0 {
+                *LISTENER_ACTION.lock().unwrap() = Some(listener);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn handler() -> libc::sighandler_t {
+        // SAFETY: a null action only reads the current one
+        unsafe {
+            let mut current: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGTSTP, std::ptr::null(), &mut current);
+            current.sa_sigaction
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_restored_outside_runs() {
+        let signal = PauseSignal::new();
+        assert_ne!(handler(), libc::SIG_DFL);
+        drop(signal);
+        assert_eq!(handler(), libc::SIG_DFL);
+
+        let _signal = PauseSignal::new();
+        assert_ne!(handler(), libc::SIG_DFL);
+    }
+}
@@ -0,0 +1,21 @@
+//! Shows the output of the tools that are running, from the loop that shows a reply
+use goose::agents::progress::{ProgressListener, ToolProgress};
+use tokio::sync::mpsc;
+
+pub struct ChannelListener {
+    sender: mpsc::Sender<ToolProgress>,
+}
+
+impl ChannelListener {
+    pub fn new(sender: mpsc::Sender<ToolProgress>) -> Self {
+        Self { sender }
+    }
+}
+
+impl ProgressListener for ChannelListener {
+    fn report(&self, progress: ToolProgress) {
+        // Reports that come faster than they are shown are dropped, as is everything once the
+        // session is gone
+        let _ = self.sender.try_send(progress);
+    }
+}
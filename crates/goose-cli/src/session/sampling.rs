@@ -0,0 +1,56 @@
+//! Asks the user about extensions' requests for completions, from the loop that shows a reply
+//!
+//! Extensions ask while one of their tools runs, so the question is passed to the loop, which
+//! hides the thinking indicator while the user answers.
+use async_trait::async_trait;
+use goose::agents::sampling::SamplingApprover;
+use mcp_core::protocol::CreateMessageParams;
+use mcp_core::Content;
+use tokio::sync::{mpsc, oneshot};
+
+/// How much of the request the user is shown
+const MAX_PREVIEW_CHARS: usize = 300;
+
+pub struct SamplingRequest {
+    pub extension: String,
+    /// The start of the last message the extension would like answered
+    pub preview: String,
+    pub respond: oneshot::Sender<bool>,
+}
+
+pub struct ChannelApprover {
+    sender: mpsc::Sender<SamplingRequest>,
+}
+
+impl ChannelApprover {
+    pub fn new(sender: mpsc::Sender<SamplingRequest>) -> Self {
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl SamplingApprover for ChannelApprover {
+    async fn approve(&self, extension: &str, params: &CreateMessageParams) -> bool {
+        let text = match params.messages.last().map(|message| &message.content) {
+            Some(Content::Text(text)) => text.text.clone(),
+            Some(Content::Image(_)) => "(an image)".to_string(),
+            Some(Content::Resource(resource)) => resource.get_text(),
+            None => String::new(),
+        };
+        let mut preview: String = text.chars().take(MAX_PREVIEW_CHARS).collect();
+        if preview.len() < text.len() {
+            preview.push_str("...");
+        }
+
+        let (respond, answer) = oneshot::channel();
+        let request = SamplingRequest {
+            extension: extension.to_string(),
+            preview,
+            respond,
+        };
+        if self.sender.send(request).await.is_err() {
+            return false;
+        }
+        answer.await.unwrap_or(false)
+    }
+}
@@ -0,0 +1,194 @@
+//! Which paths the file tools may read and write
+//!
+//! By default only the project, the directory the server runs in, is open to them.
+//! `GOOSE_FILE_ALLOW` opens other directories instead, and `GOOSE_FILE_DENY` closes parts of the
+//! allowed ones again, a denied path winning over an allowed one. Both are lists of paths, as a
+//! JSON array or separated like `PATH`. A path is checked where it really is, with symlinks
+//! followed and `..` resolved, so neither a link in the project nor `../..` leads out of it. That
+//! includes links to files that don't exist yet, which a write would create where they point.
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::shell::expand_path;
+
+/// The most symlinks followed for one path, past which it is taken to loop
+const MAX_LINKS: usize = 40;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathAccess {
+    /// The directories the tools may use, the working directory when there are none
+    allow: Vec<PathBuf>,
+    deny: Vec<PathBuf>,
+}
+
+impl PathAccess {
+    pub fn new(allow: Vec<PathBuf>, deny: Vec<PathBuf>) -> Self {
+        Self { allow, deny }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            paths_from_env("GOOSE_FILE_ALLOW"),
+            paths_from_env("GOOSE_FILE_DENY"),
+        )
+    }
+
+    /// Whether the file tools may use `path`, explaining why not when they may not
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        let real = resolve(path).map_err(|e| format!("Can't resolve {}: {}", path.display(), e))?;
+        if let Some(denied) = self
+            .deny
+            .iter()
+            .find(|denied| real.starts_with(resolve_rule(denied)))
+        {
+            return Err(format!(
+                "Access to '{}' is denied, since GOOSE_FILE_DENY includes {}",
+                path.display(),
+                denied.display()
+            ));
+        }
+
+        let allowed = if self.allow.is_empty() {
+            let cwd = env::current_dir().map_err(|e| e.to_string())?;
+            vec![cwd]
+        } else {
+            self.allow.clone()
+        };
+        if allowed
+            .iter()
+            .any(|allowed| real.starts_with(resolve_rule(allowed)))
+        {
+            Ok(())
+        } else {
+            Err(format!(
+                "Access to '{}' is denied, since it is outside {}. Add it to GOOSE_FILE_ALLOW to allow it",
+                path.display(),
+                allowed
+                    .iter()
+                    .map(|allowed| allowed.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        }
+    }
+}
+
+/// The paths the environment variable `key` lists
+fn paths_from_env(key: &str) -> Vec<PathBuf> {
+    let Ok(value) = env::var(key) else {
+        return Vec::new();
+    };
+    let paths = match serde_json::from_str::<Vec<String>>(&value) {
+        Ok(paths) => paths.into_iter().map(PathBuf::from).collect(),
+        Err(_) => env::split_paths(&value).collect::<Vec<_>>(),
+    };
+    paths
+        .into_iter()
+        .filter(|path| !path.as_os_str().is_empty())
+        .collect()
+}
+
+/// Where a path in a rule really is, as it is written when it doesn't exist
+fn resolve_rule(path: &Path) -> PathBuf {
+    let expanded = PathBuf::from(expand_path(&path.to_string_lossy()));
+    let absolute = match expanded.is_absolute() {
+        true => expanded,
+        false => env::current_dir().unwrap_or_default().join(expanded),
+    };
+    resolve(&absolute).unwrap_or(absolute)
+}
+
+/// Where `path` really is, following symlinks in the part of it that exists
+///
+/// The rest, a file about to be written, can't be resolved, so it may not step back with `..`.
+/// A symlink to something that doesn't exist is followed to where it points, which is where
+/// writing to it would create the file.
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    resolve_following(path, MAX_LINKS)
+}
+
+fn resolve_following(path: &Path, links: usize) -> io::Result<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    // A dangling symlink is there even though what it points to isn't
+    while std::fs::symlink_metadata(existing).is_err() {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            break;
+        };
+        rest.push(name);
+        existing = parent;
+    }
+    if std::fs::symlink_metadata(existing).is_err() {
+        // Only a name that steps back stops the walk before an existing directory
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a path that doesn't exist yet can't step back with ..",
+        ));
+    }
+
+    let mut real = match existing.canonicalize() {
+        Ok(real) => real,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && existing.is_symlink() => {
+            if links == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "too many levels of symbolic links",
+                ));
+            }
+            let target = std::fs::read_link(existing)?;
+            let target = match existing.parent() {
+                Some(parent) => parent.join(target),
+                None => target,
+            };
+            resolve_following(&target, links - 1)?
+        }
+        Err(e) => return Err(e),
+    };
+    for name in rest.into_iter().rev() {
+        real.push(name);
+    }
+    Ok(real)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let home = tempfile::tempdir().unwrap();
+        let project = home.path().join("project");
+        let secrets = project.join("secrets");
+        std::fs::create_dir_all(&secrets).unwrap();
+        std::fs::create_dir_all(home.path().join(".ssh")).unwrap();
+        let access = PathAccess::new(vec![project.clone()], vec![secrets.clone()]);
+
+        assert!(access.check(&project.join("src/main.rs")).is_ok());
+        assert!(access.check(&project.join("new.txt")).is_ok());
+        assert!(access.check(&secrets.join("key.pem")).is_err());
+        assert!(access.check(&home.path().join(".ssh/id_rsa")).is_err());
+        assert!(access.check(&project.join("../.ssh/id_rsa")).is_err());
+        assert!(access
+            .check(&project.join("new/../../.ssh/id_rsa"))
+            .is_err());
+
+        #[cfg(unix)]
+        {
+            let link = project.join("ssh");
+            std::os::unix::fs::symlink(home.path().join(".ssh"), &link).unwrap();
+            assert!(access.check(&link.join("id_rsa")).is_err());
+
+            // A link to a file that doesn't exist yet is where writing to it would create it
+            let dangling = project.join("profile");
+            std::os::unix::fs::symlink(home.path().join(".profile"), &dangling).unwrap();
+            assert!(access.check(&dangling).is_err());
+            let inside = project.join("notes");
+            std::os::unix::fs::symlink("new/notes.md", &inside).unwrap();
+            assert!(access.check(&inside).is_ok());
+            let looped = project.join("loop");
+            std::os::unix::fs::symlink(&looped, &looped).unwrap();
+            assert!(access.check(&looped).is_err());
+        }
+    }
+}
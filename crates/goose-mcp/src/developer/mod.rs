@@ -1,4 +1,6 @@
+mod access;
 mod lang;
+mod sandbox;
 mod shell;
 
 use anyhow::Result;
@@ -12,8 +14,9 @@ use std::{
     io::Cursor,
     path::{Path, PathBuf},
     pin::Pin,
+    time::{Duration, Instant},
 };
-use tokio::process::Command;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use url::Url;
 
 use include_dir::{include_dir, Dir};
@@ -30,6 +33,8 @@ use mcp_server::Router;
 use mcp_core::content::Content;
 use mcp_core::role::Role;
 
+use self::access::PathAccess;
+use self::sandbox::{Sandbox, SandboxCommand};
 use self::shell::{
     expand_path, format_command_for_platform, get_shell_config, is_absolute_path,
     normalize_line_endings,
@@ -41,6 +46,9 @@ use xcap::{Monitor, Window};
 
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
 
+/// How often the lines a shell command prints are passed on while it runs
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
 // Embeds the prompts directory to the build
 static PROMPTS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/developer/prompts");
 
@@ -87,12 +95,53 @@ pub fn load_prompt_files() -> HashMap<String, Prompt> {
     prompts
 }
 
+/// Stops a shell command and every process it started if dropped while it runs, as when the
+/// client cancels the call because it timed out
+struct RunningCommand {
+    pid: Option<u32>,
+    /// What stops the container the command runs in, if it runs in one
+    stop: Option<std::process::Command>,
+}
+
+impl RunningCommand {
+    /// Leave what the command started in the background running
+    fn finished(mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for RunningCommand {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid {
+            if let Some(mut stop) = self.stop.take() {
+                // Stopping a container waits on the runtime, which mustn't hold up the worker
+                // the call was dropped on
+                let mut kill = move || {
+                    if let Err(e) = stop.output() {
+                        tracing::warn!("Failed to stop the container that was cancelled: {}", e);
+                    }
+                };
+                match tokio::runtime::Handle::try_current() {
+                    Ok(runtime) => drop(runtime.spawn_blocking(kill)),
+                    Err(_) => kill(),
+                }
+            }
+            if let Err(e) = kill_tree::blocking::kill_tree(pid) {
+                tracing::warn!("Failed to stop the command that was cancelled: {}", e);
+            }
+        }
+    }
+}
+
 pub struct DeveloperRouter {
     tools: Vec<Tool>,
     prompts: Arc<HashMap<String, Prompt>>,
     instructions: String,
     file_history: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
     ignore_patterns: Arc<Gitignore>,
+    /// Where shell commands run, or why they can't
+    sandbox: Result<Sandbox, String>,
+    access: PathAccess,
 }
 
 impl Default for DeveloperRouter {
@@ -344,6 +393,12 @@ impl DeveloperRouter {
             }
         }
 
+        let sandbox = Sandbox::from_env();
+        let base_instructions = match sandbox.as_ref().ok().and_then(Sandbox::describe) {
+            Some(sandboxed) => format!("{base_instructions}{sandboxed}\n"),
+            None => base_instructions,
+        };
+
         // Return base instructions directly when no hints are found
         let instructions = if hints.is_empty() {
             base_instructions
@@ -403,6 +458,8 @@ impl DeveloperRouter {
             instructions,
             file_history: Arc::new(Mutex::new(HashMap::new())),
             ignore_patterns: Arc::new(ignore_patterns),
+            sandbox,
+            access: PathAccess::from_env(),
         }
     }
 
@@ -460,28 +517,78 @@ impl DeveloperRouter {
             }
         }
 
+        let sandbox = self
+            .sandbox
+            .as_ref()
+            .map_err(|e| ToolError::ExecutionError(format!("Not running the command: {}", e)))?;
+
         // Get platform-specific shell configuration
         let shell_config = get_shell_config();
         let cmd_with_redirect = format_command_for_platform(command);
-
-        // Execute the command using platform-specific shell
-        let child = Command::new(&shell_config.executable)
+        let cwd = std::env::current_dir().map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+        let SandboxCommand {
+            command: mut shell_command,
+            stop,
+        } = sandbox.command(&shell_config, &cmd_with_redirect, &cwd);
+
+        // Execute the command using platform-specific shell, its stderr redirected to stdout
+        let mut child = shell_command
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+            .stderr(Stdio::null())
             .stdin(Stdio::null())
             .kill_on_drop(true)
-            .arg(&shell_config.arg)
-            .arg(cmd_with_redirect)
             .spawn()
             .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+        // Dropped before the child, so what the command started is found while it is running
+        let running = RunningCommand {
+            pid: child.id(),
+            stop,
+        };
 
-        // Wait for the command to complete and get output
-        let output = child
-            .wait_with_output()
+        // Read the output as it comes, passing the lines on to the client so the user can
+        // follow a long build or test run while it goes, together at most a few times a second
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut output = Vec::new();
+        let mut reported = 0;
+        let mut last_report: Option<Instant> = None;
+        loop {
+            let due = last_report.map_or(Duration::ZERO, |last| {
+                PROGRESS_INTERVAL.saturating_sub(last.elapsed())
+            });
+            // Lines waiting to be passed on are sent once they are due, even with no more
+            // coming; a read cut short keeps what it read in `output`
+            let waiting = output[reported..].contains(&b'\n');
+            let read = stdout.read_until(b'\n', &mut output);
+            let read = if waiting {
+                tokio::time::timeout(due, read).await.ok()
+            } else {
+                Some(read.await)
+            };
+            if let Some(read) = read {
+                let read = read.map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+                if read == 0 {
+                    break;
+                }
+            }
+            if last_report.is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL) {
+                continue;
+            }
+            if let Some(end) = output[reported..].iter().rposition(|&byte| byte == b'\n') {
+                let lines = String::from_utf8_lossy(&output[reported..reported + end]);
+                if !lines.trim().is_empty() {
+                    mcp_server::progress::report(lines.trim());
+                }
+                reported += end + 1;
+                last_report = Some(Instant::now());
+            }
+        }
+        child
+            .wait()
             .await
             .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+        running.finished();
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
+        let output_str = String::from_utf8_lossy(&output);
 
         // Check the character count of the output
         const MAX_CHAR_COUNT: usize = 400_000; // 409600 chars = 400KB
@@ -525,6 +632,9 @@ impl DeveloperRouter {
                 path.display()
             )));
         }
+        self.access
+            .check(&path)
+            .map_err(ToolError::ExecutionError)?;
 
         match command {
             "view" => self.text_editor_view(&path).await,
@@ -863,6 +973,9 @@ impl DeveloperRouter {
                 path.display()
             )));
         }
+        self.access
+            .check(&path)
+            .map_err(ToolError::ExecutionError)?;
 
         // Check if file exists
         if !path.exists() {
@@ -1088,6 +1201,8 @@ impl Clone for DeveloperRouter {
             instructions: self.instructions.clone(),
             file_history: Arc::clone(&self.file_history),
             ignore_patterns: Arc::clone(&self.ignore_patterns),
+            sandbox: self.sandbox.clone(),
+            access: self.access.clone(),
         }
     }
 }
@@ -1495,6 +1610,8 @@ mod tests {
             instructions: String::new(),
             file_history: Arc::new(Mutex::new(HashMap::new())),
             ignore_patterns: Arc::new(ignore_patterns),
+            sandbox: Ok(Sandbox::Host),
+            access: PathAccess::default(),
         };
 
         // Test basic file matching
@@ -1545,6 +1662,8 @@ mod tests {
             instructions: String::new(),
             file_history: Arc::new(Mutex::new(HashMap::new())),
             ignore_patterns: Arc::new(ignore_patterns),
+            sandbox: Ok(Sandbox::Host),
+            access: PathAccess::default(),
         };
 
         // Try to write to an ignored file
@@ -1602,6 +1721,8 @@ mod tests {
             instructions: String::new(),
             file_history: Arc::new(Mutex::new(HashMap::new())),
             ignore_patterns: Arc::new(ignore_patterns),
+            sandbox: Ok(Sandbox::Host),
+            access: PathAccess::default(),
         };
 
         // Create an ignored file
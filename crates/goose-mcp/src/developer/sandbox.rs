@@ -0,0 +1,239 @@
+//! Where the shell tool runs commands
+//!
+//! Commands run on the host unless `GOOSE_SHELL_SANDBOX` names a container runtime, `docker` or
+//! `podman`. Then each runs in a fresh container of `GOOSE_SHELL_SANDBOX_IMAGE`, with the working
+//! directory mounted at the same path and nothing else of the host's, and without a network
+//! unless `GOOSE_SHELL_SANDBOX_NETWORK` is true. Commands run as the user who owns the working
+//! directory, so the files they write there belong to that user rather than to root. Goose
+//! passes these settings on from its own configuration, so a project can choose them in its
+//! `.goose/config.yaml`.
+use std::env;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::process::Command;
+
+use super::shell::ShellConfig;
+
+/// The image commands run in when the sandbox doesn't name one
+pub const DEFAULT_IMAGE: &str = "debian:stable-slim";
+
+/// Containers started by this server so far, to name the next one
+static CONTAINERS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sandbox {
+    /// Run commands directly on the host
+    Host,
+    Container {
+        /// The program that runs containers, `docker` or `podman`
+        runtime: String,
+        image: String,
+        network: bool,
+    },
+}
+
+impl Sandbox {
+    /// The sandbox the environment asks for
+    pub fn from_env() -> Result<Self, String> {
+        let backend = env::var("GOOSE_SHELL_SANDBOX").unwrap_or_default();
+        match backend.trim().to_lowercase().as_str() {
+            "" | "host" | "none" => Ok(Self::Host),
+            runtime @ ("docker" | "podman") => Ok(Self::Container {
+                runtime: runtime.to_string(),
+                image: env::var("GOOSE_SHELL_SANDBOX_IMAGE")
+                    .ok()
+                    .filter(|image| !image.trim().is_empty())
+                    .unwrap_or_else(|| DEFAULT_IMAGE.to_string()),
+                network: env::var("GOOSE_SHELL_SANDBOX_NETWORK")
+                    .map(|network| network.trim().eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+            }),
+            other => Err(format!(
+                "GOOSE_SHELL_SANDBOX is {}, but commands can only run on the host, in docker or in podman",
+                other
+            )),
+        }
+    }
+
+    /// A command that runs `command` in this sandbox with `workspace` as its working directory
+    pub fn command(&self, shell: &ShellConfig, command: &str, workspace: &Path) -> SandboxCommand {
+        match self {
+            Self::Host => {
+                let mut host = Command::new(&shell.executable);
+                host.arg(&shell.arg).arg(command);
+                SandboxCommand {
+                    command: host,
+                    stop: None,
+                }
+            }
+            Self::Container { runtime, .. } => {
+                let name = format!(
+                    "goose-{}-{}",
+                    std::process::id(),
+                    CONTAINERS.fetch_add(1, Ordering::SeqCst)
+                );
+                let mut container = Command::new(runtime);
+                container.args(self.run_args(&name, workspace, owner(workspace)));
+                // Images don't all have bash, and a container isn't Windows
+                container.args(["sh", "-c", command]);
+
+                let mut stop = std::process::Command::new(runtime);
+                stop.args(["kill", &name]);
+                SandboxCommand {
+                    command: container,
+                    stop: Some(stop),
+                }
+            }
+        }
+    }
+
+    /// What to tell the model about where its commands run
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            Self::Host => None,
+            Self::Container {
+                runtime,
+                image,
+                network,
+            } => Some(format!(
+                "Shell commands run in a {} container from the image {}, a fresh one each time, with \
+                only the current directory of the host mounted, so only changes to files in it last. {}",
+                runtime,
+                image,
+                if *network {
+                    "The container can reach the network."
+                } else {
+                    "The container has no network."
+                }
+            )),
+        }
+    }
+
+    /// The arguments to the runtime naming the container and the image to run, as `user`, a
+    /// uid and gid, when there is one
+    fn run_args(&self, name: &str, workspace: &Path, user: Option<(u32, u32)>) -> Vec<String> {
+        let Self::Container {
+            runtime,
+            image,
+            network,
+        } = self
+        else {
+            return Vec::new();
+        };
+        let workspace = workspace.to_string_lossy();
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "--init".to_string(),
+            "--name".to_string(),
+            name.to_string(),
+            // Unlike --volume, a path with a colon in it is mounted as it is
+            "--mount".to_string(),
+            format!(
+                "type=bind,{},{}",
+                csv_field(&format!("source={}", workspace)),
+                csv_field(&format!("target={}", workspace))
+            ),
+            "--workdir".to_string(),
+            workspace.to_string(),
+        ];
+        if let Some((uid, gid)) = user {
+            // Rootless podman maps root in the container to the user, and the user to another
+            // id, unless it keeps their id
+            if runtime == "podman" {
+                args.push("--userns=keep-id".to_string());
+            }
+            args.extend(["--user".to_string(), format!("{}:{}", uid, gid)]);
+        }
+        if !network {
+            args.extend(["--network".to_string(), "none".to_string()]);
+        }
+        args.push(image.clone());
+        args
+    }
+}
+
+/// `field` quoted for the runtime's --mount option, which is read as CSV, so commas and quotes in
+/// a path can't end the field or add options
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// The uid and gid of the owner of `path`, which commands in a container run as
+#[cfg(unix)]
+fn owner(path: &Path) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_path: &Path) -> Option<(u32, u32)> {
+    None
+}
+
+/// A command ready to run in a sandbox
+pub struct SandboxCommand {
+    pub command: Command,
+    /// What stops the command's container, since stopping the runtime's client leaves it running
+    pub stop: Option<std::process::Command>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_args() {
+        let sandbox = Sandbox::Container {
+            runtime: "podman".to_string(),
+            image: "rust:1".to_string(),
+            network: false,
+        };
+        assert_eq!(
+            sandbox.run_args(
+                "goose-1-0",
+                Path::new("/home/me/project"),
+                Some((1000, 100))
+            ),
+            [
+                "run",
+                "--rm",
+                "--init",
+                "--name",
+                "goose-1-0",
+                "--mount",
+                "type=bind,\"source=/home/me/project\",\"target=/home/me/project\"",
+                "--workdir",
+                "/home/me/project",
+                "--userns=keep-id",
+                "--user",
+                "1000:100",
+                "--network",
+                "none",
+                "rust:1"
+            ]
+        );
+
+        let sandbox = Sandbox::Container {
+            runtime: "docker".to_string(),
+            image: "rust:1".to_string(),
+            network: true,
+        };
+        let args = sandbox.run_args("goose-1-1", Path::new("/project"), Some((501, 20)));
+        assert!(!args.contains(&"--network".to_string()));
+        assert!(!args.contains(&"--userns=keep-id".to_string()));
+        assert!(args.windows(2).any(|pair| pair == ["--user", "501:20"]));
+        // A path can't add options to the mount
+        let args = sandbox.run_args("goose-1-2", Path::new("/p,readonly=false\""), None);
+        assert!(args.contains(
+            &"type=bind,\"source=/p,readonly=false\"\"\",\"target=/p,readonly=false\"\"\""
+                .to_string()
+        ));
+        assert_eq!(args.last().map(String::as_str), Some("rust:1"));
+        assert!(Sandbox::Host
+            .run_args("goose-1-3", Path::new("/"), None)
+            .is_empty());
+    }
+}
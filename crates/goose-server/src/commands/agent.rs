@@ -1,8 +1,11 @@
 use crate::configuration;
 use crate::state;
 use anyhow::Result;
+use goose::config::migrations::migrate;
+use goose::config::{reload, Config};
+use tokio::sync::broadcast::error::RecvError;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tracing::{info, warn};
 
 pub async fn run() -> Result<()> {
     // Initialize logging
@@ -15,9 +18,49 @@ pub async fn run() -> Result<()> {
     let secret_key =
         std::env::var("GOOSE_SERVER__SECRET_KEY").unwrap_or_else(|_| "test".to_string());
 
+    // Bring a config file from an older version up to date before anything reads it
+    match migrate(Config::global(), &goose::providers::providers()) {
+        Ok(Some(report)) => info!(
+            "Migrated the config from version {} to {}, backup at {:?}: {}",
+            report.from_version,
+            report.to_version,
+            report.backup,
+            report.applied.join("; ")
+        ),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to migrate the config: {}", e),
+    }
+
+    // Resolve secrets from a secret manager up front, they are cached and refreshed from here on
+    if let Err(e) = Config::global().preload_secrets() {
+        warn!("Failed to load secrets from the secret store: {}", e);
+    }
+
     // Create app state - agent will start as None
     let state = state::AppState::new(secret_key.clone()).await?;
 
+    // Apply config changes to the agent as they are saved, the write lock waits for any
+    // reply in progress to finish
+    reload::watch_config();
+    let agent = state.agent.clone();
+    tokio::spawn(async move {
+        let mut reloads = reload::config_reload_events();
+        loop {
+            match reloads.recv().await {
+                Ok(event) => {
+                    if let Some(agent) = agent.write().await.as_mut() {
+                        reload::apply_config_reload(agent.as_mut(), &event).await;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    // Remove old sessions past the retention limits, if any are set
+    goose::session::retention::start_cleanup();
+
     // Create router with CORS support
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -4,6 +4,7 @@ use goose::agents::extension::Envs;
 use goose::agents::ExtensionConfig;
 use goose::config::ExtensionEntry;
 use goose::providers::base::ConfigKey;
+use goose::providers::base::HealthStatus;
 use goose::providers::base::ProviderMetadata;
 
 #[allow(dead_code)] // Used by utoipa for OpenAPI generation
@@ -17,7 +18,10 @@ use goose::providers::base::ProviderMetadata;
         super::routes::config_management::remove_extension,
         super::routes::config_management::get_extensions,
         super::routes::config_management::read_all_config,
-        super::routes::config_management::providers
+        super::routes::config_management::read_config_schema,
+        super::routes::config_management::providers,
+        super::routes::config_management::provider_models,
+        super::routes::config_management::provider_health
     ),
     components(schemas(
         super::routes::config_management::UpsertConfigQuery,
@@ -28,6 +32,7 @@ use goose::providers::base::ProviderMetadata;
         super::routes::config_management::ExtensionResponse,
         super::routes::config_management::ExtensionQuery,
         ProviderMetadata,
+        HealthStatus,
         ExtensionEntry,
         ExtensionConfig,
         ConfigKey,
@@ -6,9 +6,11 @@ use axum::{
     Json, Router,
 };
 use goose::config::Config;
-use goose::{agents::AgentFactory, model::ModelConfig, providers};
+use goose::persona::{personas, Persona};
+use goose::{agents::AgentFactory, model::ModelConfig, prompt_template, providers};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 
 #[derive(Serialize)]
@@ -27,11 +29,25 @@ struct ExtendPromptResponse {
     success: bool,
 }
 
+#[derive(Deserialize)]
+struct OverridePromptRequest {
+    /// A template to use instead of the built-in system prompt
+    template: String,
+}
+
+#[derive(Serialize)]
+struct SystemPromptResponse {
+    system_prompt: String,
+}
+
 #[derive(Deserialize)]
 struct CreateAgentRequest {
     version: Option<String>,
     provider: String,
     model: Option<String>,
+    /// The persona to work as, instead of the one in GOOSE_PERSONA
+    #[serde(default)]
+    persona: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -95,6 +111,93 @@ async fn extend_prompt(
     }
 }
 
+// The system prompt the agent sends, with the extensions and overrides applied
+async fn get_system_prompt(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SystemPromptResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let agent = state.agent.read().await;
+    let agent = agent.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let system_prompt = agent.system_prompt().await.map_err(|e| {
+        tracing::error!("{}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(SystemPromptResponse { system_prompt }))
+}
+
+async fn override_prompt(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<OverridePromptRequest>,
+) -> Result<Json<ExtendPromptResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // The template is rendered on every reply, so reject one that can't be now
+    if let Err(e) =
+        prompt_template::render_inline_once(&payload.template, &HashMap::<String, Value>::new())
+    {
+        tracing::error!("Invalid system prompt template: {}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut agent = state.agent.write().await;
+    let agent = agent.as_mut().ok_or(StatusCode::NOT_FOUND)?;
+    agent.override_system_prompt(payload.template).await;
+    Ok(Json(ExtendPromptResponse { success: true }))
+}
+
+#[derive(Deserialize)]
+struct SwitchProviderRequest {
+    provider: String,
+    model: String,
+}
+
+/// Answer the next replies with another model, keeping the conversation the client sends
+async fn switch_provider(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<SwitchProviderRequest>,
+) -> Result<Json<ExtendPromptResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let provider =
+        providers::create(&payload.provider, ModelConfig::new(payload.model)).map_err(|e| {
+            tracing::error!("Failed to create the provider: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    let mut agent = state.agent.write().await;
+    let agent = agent.as_mut().ok_or(StatusCode::NOT_FOUND)?;
+    agent.update_provider(provider).await;
+    Ok(Json(ExtendPromptResponse { success: true }))
+}
+
 #[axum::debug_handler]
 async fn create_agent(
     State(state): State<AppState>,
@@ -111,28 +214,57 @@ async fn create_agent(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    let persona = Persona::selected(payload.persona)
+        .and_then(|name| name.map(|name| Persona::named(&name)).transpose())
+        .map_err(|e| {
+            tracing::error!("Failed to read the persona: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    // A persona's provider is used over the one asked for, and then the model asked for, which
+    // is one of the other provider's, gives way to the persona's
+    let persona_model = persona.as_ref().and_then(|persona| persona.model.clone());
+    let (provider_name, model) = match persona
+        .as_ref()
+        .and_then(|persona| persona.provider.clone())
+        .filter(|provider| *provider != payload.provider)
+    {
+        Some(provider) => (provider, persona_model),
+        None => (payload.provider, payload.model.or(persona_model)),
+    };
+
     // Set the environment variable for the model if provided
-    if let Some(model) = &payload.model {
-        let env_var_key = format!("{}_MODEL", payload.provider.to_uppercase());
+    if let Some(model) = &model {
+        let env_var_key = format!("{}_MODEL", provider_name.to_uppercase());
         env::set_var(env_var_key.clone(), model);
         println!("Set environment variable: {}={}", env_var_key, model);
     }
 
     let config = Config::global();
-    let model = payload.model.unwrap_or_else(|| {
+    let model = model.unwrap_or_else(|| {
         config
             .get_param("GOOSE_MODEL")
             .expect("Did not find a model on payload or in env")
     });
-    let model_config = ModelConfig::new(model);
+    let model_config = match &persona {
+        Some(persona) => persona.model_config(model),
+        None => ModelConfig::new(model),
+    };
     let provider =
-        providers::create(&payload.provider, model_config).expect("Failed to create provider");
+        providers::create(&provider_name, model_config).expect("Failed to create provider");
 
     let version = payload
         .version
         .unwrap_or_else(|| AgentFactory::default_version().to_string());
 
-    let new_agent = AgentFactory::create(&version, provider).expect("Failed to create agent");
+    let mut new_agent = AgentFactory::create(&version, provider).expect("Failed to create agent");
+    new_agent.configure_system_prompt().await.map_err(|e| {
+        tracing::error!("Failed to set up the system prompt: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if persona.is_some() {
+        new_agent.set_persona(persona).await;
+    }
 
     let mut agent = state.agent.write().await;
     *agent = Some(new_agent);
@@ -140,6 +272,28 @@ async fn create_agent(
     Ok(Json(CreateAgentResponse { version }))
 }
 
+// The configured personas, for a client to offer when it creates the agent
+async fn list_personas(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BTreeMap<String, Persona>>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let personas = personas().map_err(|e| {
+        tracing::error!("{}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(personas))
+}
+
 async fn list_providers() -> Json<Vec<ProviderList>> {
     let contents = include_str!("providers_and_keys.json");
 
@@ -167,7 +321,11 @@ pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/agent/versions", get(get_versions))
         .route("/agent/providers", get(list_providers))
+        .route("/agent/personas", get(list_personas))
         .route("/agent/prompt", post(extend_prompt))
+        .route("/agent/prompt/override", post(override_prompt))
+        .route("/agent/system_prompt", get(get_system_prompt))
+        .route("/agent/provider", post(switch_provider))
         .route("/agent", post(create_agent))
         .with_state(state)
 }
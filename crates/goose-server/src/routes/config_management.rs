@@ -1,15 +1,18 @@
 use crate::routes::utils::check_provider_configured;
 use crate::state::AppState;
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     routing::{delete, get, post},
     Json, Router,
 };
 use goose::agents::ExtensionConfig;
 use goose::config::extensions::name_to_key;
+use goose::config::schema::{config_schema, validate_setting};
 use goose::config::Config;
 use goose::config::{ExtensionEntry, ExtensionManager};
-use goose::providers::base::ProviderMetadata;
+use goose::model::ModelConfig;
+use goose::providers::base::{HealthStatus, ProviderMetadata};
+use goose::providers::create;
 use goose::providers::providers as get_providers;
 use http::{HeaderMap, StatusCode};
 use serde::{Deserialize, Serialize};
@@ -83,6 +86,7 @@ pub struct ProvidersResponse {
     request_body = UpsertConfigQuery,
     responses(
         (status = 200, description = "Configuration value upserted successfully", body = String),
+        (status = 400, description = "Value doesn't match the configuration schema"),
         (status = 500, description = "Internal server error")
     )
 )]
@@ -94,6 +98,11 @@ pub async fn upsert_config(
     // Use the helper function to verify the secret key
     verify_secret_key(&headers, &state)?;
 
+    if let Err(e) = validate_setting(&query.key, &query.value, &get_providers()) {
+        tracing::warn!("Rejected config upsert: {}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     let config = Config::global();
     let result = config.set(&query.key, query.value, query.is_secret);
 
@@ -267,6 +276,22 @@ pub async fn read_all_config(
     Ok(Json(ConfigResponse { config: values }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/config/schema",
+    responses(
+        (status = 200, description = "JSON Schema of the configuration file", body = Value)
+    )
+)]
+pub async fn read_config_schema(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    Ok(Json(config_schema(&get_providers())))
+}
+
 // Modified providers function using the new response type
 #[utoipa::path(
     get,
@@ -302,15 +327,98 @@ pub async fn providers(
     Ok(Json(providers_response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/config/providers/{name}/models",
+    params(
+        ("name" = String, Path, description = "Provider name")
+    ),
+    responses(
+        (status = 200, description = "Models offered by the provider", body = [String]),
+        (status = 404, description = "Provider not found")
+    )
+)]
+pub async fn provider_models(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let metadata = get_providers()
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Prefer the live model list, but keep the UI usable with the known models
+    // when the provider isn't configured yet or has no listing API
+    let model_config = ModelConfig::new(metadata.default_model.clone());
+    let fetched = match create(&name, model_config) {
+        Ok(provider) => provider.fetch_supported_models().await.unwrap_or_else(|e| {
+            tracing::debug!("Failed to fetch models for {}: {}", name, e);
+            None
+        }),
+        Err(_) => None,
+    };
+
+    Ok(Json(
+        fetched
+            .filter(|models| !models.is_empty())
+            .unwrap_or(metadata.known_models),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ProviderHealthQuery {
+    /// Model to check, defaults to the provider's default model
+    pub model: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/config/providers/{name}/health",
+    params(
+        ("name" = String, Path, description = "Provider name"),
+        ("model" = Option<String>, Query, description = "Model to check")
+    ),
+    responses(
+        (status = 200, description = "Result of the provider health check", body = HealthStatus),
+        (status = 404, description = "Provider not found")
+    )
+)]
+pub async fn provider_health(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(query): Query<ProviderHealthQuery>,
+) -> Result<Json<HealthStatus>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let metadata = get_providers()
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let model_config = ModelConfig::new(query.model.unwrap_or(metadata.default_model));
+    let status = match create(&name, model_config) {
+        Ok(provider) => provider.health_check().await,
+        Err(e) => HealthStatus::Unhealthy(format!("Provider is not configured: {}", e)),
+    };
+    Ok(Json(status))
+}
+
 pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/config", get(read_all_config))
         .route("/config/upsert", post(upsert_config))
         .route("/config/remove", post(remove_config))
         .route("/config/read", post(read_config))
+        .route("/config/schema", get(read_config_schema))
         .route("/config/extensions", get(get_extensions))
         .route("/config/extensions", post(add_extension))
         .route("/config/extensions/:name", delete(remove_extension))
         .route("/config/providers", get(providers))
+        .route("/config/providers/:name/models", get(provider_models))
+        .route("/config/providers/:name/health", get(provider_health))
         .with_state(state)
 }
@@ -26,6 +26,24 @@ enum ExtensionConfigRequest {
         /// List of environment variable keys. The server will fetch their values from the keyring.
         #[serde(default)]
         env_keys: Vec<String>,
+        /// Headers sent with each request to the server, such as `Authorization`.
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        timeout: Option<u64>,
+    },
+    /// Streamable HTTP extension, reached at a single MCP endpoint.
+    #[serde(rename = "streamable_http")]
+    StreamableHttp {
+        /// The name to identify this extension
+        name: String,
+        /// The URI of the extension's MCP endpoint.
+        uri: String,
+        /// List of environment variable keys. The server will fetch their values from the keyring.
+        #[serde(default)]
+        env_keys: Vec<String>,
+        /// Headers sent with each request to the server, such as `Authorization`.
+        #[serde(default)]
+        headers: HashMap<String, String>,
         timeout: Option<u64>,
     },
     /// Standard I/O (stdio) extension.
@@ -91,6 +109,7 @@ async fn add_extension(
             name,
             uri,
             env_keys,
+            headers,
             timeout,
         } => {
             let mut env_map = HashMap::new();
@@ -119,6 +138,45 @@ async fn add_extension(
                 name,
                 uri,
                 envs: Envs::new(env_map),
+                headers,
+                description: None,
+                timeout,
+            }
+        }
+        ExtensionConfigRequest::StreamableHttp {
+            name,
+            uri,
+            env_keys,
+            headers,
+            timeout,
+        } => {
+            let mut env_map = HashMap::new();
+            for key in env_keys {
+                match config.get_secret(&key) {
+                    Ok(value) => {
+                        env_map.insert(key, value);
+                    }
+                    Err(_) => {
+                        missing_keys.push(key);
+                    }
+                }
+            }
+
+            if !missing_keys.is_empty() {
+                return Ok(Json(ExtensionResponse {
+                    error: true,
+                    message: Some(format!(
+                        "Missing secrets for keys: {}",
+                        missing_keys.join(", ")
+                    )),
+                }));
+            }
+
+            ExtensionConfig::StreamableHttp {
+                name,
+                uri,
+                envs: Envs::new(env_map),
+                headers,
                 description: None,
                 timeout,
             }
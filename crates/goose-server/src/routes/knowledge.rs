@@ -0,0 +1,170 @@
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use goose::config::Config;
+use goose::knowledge::{IndexReport, Knowledge, KnowledgeIndex, Passage};
+use goose::vector_store;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize)]
+struct SourcesResponse {
+    sources: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct SourceRequest {
+    /// A file or directory, absolute or relative to where goosed runs
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct KnowledgeSearchRequest {
+    query: String,
+    #[serde(default = "default_search_limit")]
+    limit: usize,
+}
+
+fn default_search_limit() -> usize {
+    5
+}
+
+#[derive(Serialize)]
+struct KnowledgeSearchResponse {
+    passages: Vec<Passage>,
+}
+
+fn verify_secret_key(headers: &HeaderMap, state: &AppState) -> Result<(), StatusCode> {
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(())
+}
+
+fn open_index() -> Result<KnowledgeIndex, StatusCode> {
+    vector_store::from_config(Config::global())
+        .and_then(|vectors| KnowledgeIndex::open(&KnowledgeIndex::default_path(), vectors))
+        .map_err(|e| {
+            tracing::error!("Failed to open the knowledge index: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+// The index and the embedding model, which needs the OpenAI key
+fn open_knowledge() -> Result<Knowledge, StatusCode> {
+    Knowledge::open().map_err(|e| {
+        tracing::error!("Failed to open the knowledge index: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// List the indexed files and directories
+async fn list_sources(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SourcesResponse>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let sources = open_index()?.sources().map_err(|e| {
+        tracing::error!("Failed to list the indexed documents: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(SourcesResponse { sources }))
+}
+
+// Index a file or directory
+async fn add_source(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<SourceRequest>,
+) -> Result<Json<IndexReport>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    if !request.path.exists() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let report = open_knowledge()?
+        .add_source(&request.path)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to index {}: {:?}", request.path.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(report))
+}
+
+// Remove a file or directory from the index
+async fn remove_source(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<SourceRequest>,
+) -> Result<StatusCode, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let source = request
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| request.path.clone());
+    let removed = open_index()?.remove_source(&source).await.map_err(|e| {
+        tracing::error!("Failed to remove {}: {:?}", source.display(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+// Index the files that changed since they were indexed
+async fn reindex(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<IndexReport>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let report = open_knowledge()?.reindex().await.map_err(|e| {
+        tracing::error!("Failed to reindex the documents: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(report))
+}
+
+// Find the indexed passages most like a query
+async fn search(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<KnowledgeSearchRequest>,
+) -> Result<Json<KnowledgeSearchResponse>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let passages = open_knowledge()?
+        .search(&request.query, request.limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to search the documents: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(KnowledgeSearchResponse { passages }))
+}
+
+// Configure routes for this module
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/knowledge/sources",
+            get(list_sources).post(add_source).delete(remove_source),
+        )
+        .route("/knowledge/reindex", post(reindex))
+        .route("/knowledge/search", post(search))
+        .with_state(state)
+}
@@ -0,0 +1,217 @@
+use crate::state::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use goose::config::Config;
+use goose::memory::{LongTermMemory, Memory, MemoryKind, MemoryStore};
+use goose::session;
+use goose::vector_store;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+struct MemoryListResponse {
+    memories: Vec<Memory>,
+}
+
+#[derive(Deserialize)]
+struct MemorySearchRequest {
+    query: String,
+    #[serde(default = "default_search_limit")]
+    limit: usize,
+}
+
+fn default_search_limit() -> usize {
+    10
+}
+
+#[derive(Serialize)]
+struct MemoryHit {
+    memory: Memory,
+    score: f32,
+}
+
+#[derive(Serialize)]
+struct MemorySearchResponse {
+    hits: Vec<MemoryHit>,
+}
+
+#[derive(Deserialize)]
+struct MemoryRequest {
+    content: String,
+    /// Keeps the memory's kind if not given when editing, a fact when adding
+    kind: Option<MemoryKind>,
+}
+
+#[derive(Deserialize)]
+struct ExtractMemoriesRequest {
+    session_id: String,
+}
+
+fn verify_secret_key(headers: &HeaderMap, state: &AppState) -> Result<(), StatusCode> {
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(())
+}
+
+fn open_store() -> Result<MemoryStore, StatusCode> {
+    vector_store::from_config(Config::global())
+        .and_then(|vectors| MemoryStore::open(&MemoryStore::default_path(), vectors))
+        .map_err(|e| {
+            tracing::error!("Failed to open the memory store: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+// The memories and the embedding model, which needs the OpenAI key
+fn open_memory() -> Result<LongTermMemory, StatusCode> {
+    LongTermMemory::open().map_err(|e| {
+        tracing::error!("Failed to open long-term memory: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// List the memories, the most recently updated first
+async fn list_memories(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<MemoryListResponse>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let memories = open_store()?.list().map_err(|e| {
+        tracing::error!("Failed to list memories: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(MemoryListResponse { memories }))
+}
+
+// Find the memories most like a query
+async fn search_memories(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<MemorySearchRequest>,
+) -> Result<Json<MemorySearchResponse>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let hits = open_memory()?
+        .search(&request.query, request.limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to search memories: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .map(|(memory, score)| MemoryHit { memory, score })
+        .collect();
+    Ok(Json(MemorySearchResponse { hits }))
+}
+
+// Remember something the user entered
+async fn add_memory(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<MemoryRequest>,
+) -> Result<Json<Memory>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    if request.content.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let kind = request.kind.unwrap_or(MemoryKind::Fact);
+    let memory = open_memory()?
+        .add(kind, &request.content, None)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to add memory: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(memory))
+}
+
+// Change what a memory says
+async fn edit_memory(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(request): Json<MemoryRequest>,
+) -> Result<Json<Memory>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    if request.content.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    open_memory()?
+        .edit(&id, request.kind, &request.content)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to edit memory: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+// Forget a memory
+async fn delete_memory(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let deleted = open_store()?.delete(&id).await.map_err(|e| {
+        tracing::error!("Failed to delete memory: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+// Extract memories from the messages of a session not remembered yet, with the agent's model
+async fn extract_memories(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ExtractMemoriesRequest>,
+) -> Result<Json<MemoryListResponse>, StatusCode> {
+    verify_secret_key(&headers, &state)?;
+
+    let session_path = session::get_path(session::Identifier::Name(request.session_id));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let provider = {
+        let agent = state.agent.read().await;
+        let agent = agent.as_ref().ok_or(StatusCode::PRECONDITION_REQUIRED)?;
+        agent.provider().await
+    };
+
+    let memories = open_memory()?
+        .remember_session(&session_path, provider.as_ref().as_ref(), 0)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to extract memories: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(MemoryListResponse { memories }))
+}
+
+// Configure routes for this module
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/memories", get(list_memories).post(add_memory))
+        .route("/memories/search", post(search_memories))
+        .route("/memories/extract", post(extract_memories))
+        .route("/memories/:id", put(edit_memory).delete(delete_memory))
+        .with_state(state)
+}
@@ -4,8 +4,12 @@ pub mod config_management;
 pub mod configs;
 pub mod extension;
 pub mod health;
+pub mod knowledge;
+pub mod memory;
+pub mod recipe;
 pub mod reply;
 pub mod session;
+pub mod usage;
 pub mod utils;
 use axum::Router;
 
@@ -18,5 +22,9 @@ pub fn configure(state: crate::state::AppState) -> Router {
         .merge(extension::routes(state.clone()))
         .merge(configs::routes(state.clone()))
         .merge(config_management::routes(state.clone()))
-        .merge(session::routes(state))
+        .merge(knowledge::routes(state.clone()))
+        .merge(memory::routes(state.clone()))
+        .merge(recipe::routes(state.clone()))
+        .merge(session::routes(state.clone()))
+        .merge(usage::routes(state))
 }
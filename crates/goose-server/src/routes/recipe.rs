@@ -0,0 +1,286 @@
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use futures::StreamExt;
+use goose::agents::final_output::FinalOutput;
+use goose::agents::{Agent, SessionConfig};
+use goose::message::{Message, MessageContent};
+use goose::recipe::{retry_message, Recipe, RecipeResponse, MAX_CHECK_ROUNDS};
+use goose::session;
+use mcp_core::role::Role;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Deserialize)]
+struct LoadRecipeRequest {
+    /// The recipe's file or http(s) URL
+    source: String,
+}
+
+#[derive(Deserialize)]
+struct RunRecipeRequest {
+    /// The recipe to run, or its file or URL in `source`
+    #[serde(default)]
+    recipe: Option<Recipe>,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    parameters: HashMap<String, String>,
+    /// A JSON schema for the result, in place of the recipe's response schema
+    #[serde(default)]
+    response_schema: Option<Value>,
+    session_id: Option<String>,
+    session_working_dir: String,
+}
+
+#[derive(Serialize)]
+struct RunRecipeResponse {
+    session_id: String,
+    /// The agent's answer to the prompt, to each step, and to each round of failed checks
+    responses: Vec<String>,
+    /// The value of the last answer, when the recipe or the request has a response schema and
+    /// the agent gave a value matching it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<Value>,
+    /// What is wrong with each of the recipe's checks that still fail at the end
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed_checks: Vec<String>,
+}
+
+// Read a recipe, for a client to show it and ask for its parameters
+async fn load_recipe(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<LoadRecipeRequest>,
+) -> Result<Json<Recipe>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let recipe = Recipe::load(&request.source).await.map_err(|e| {
+        tracing::error!("Failed to load recipe: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    Ok(Json(recipe))
+}
+
+// Run a recipe's prompt and steps to the end, non streaming
+async fn run_recipe(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<RunRecipeRequest>,
+) -> Result<Json<RunRecipeResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let recipe = match (request.recipe, request.source) {
+        (Some(recipe), _) => recipe,
+        (None, Some(source)) => Recipe::load(&source).await.map_err(|e| {
+            tracing::error!("Failed to load recipe: {}", e);
+            StatusCode::BAD_REQUEST
+        })?,
+        (None, None) => return Err(StatusCode::BAD_REQUEST),
+    };
+    let mut recipe = recipe.with_parameters(&request.parameters).map_err(|e| {
+        tracing::error!("Failed to fill in the recipe: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    if let Some(json_schema) = request.response_schema {
+        recipe.response = Some(RecipeResponse { json_schema });
+    }
+    let final_output = recipe
+        .response
+        .as_ref()
+        .map(|response| FinalOutput::new(response.json_schema.clone()))
+        .transpose()
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    let session_id = request
+        .session_id
+        .unwrap_or_else(session::generate_session_id);
+
+    // The recipe goes with each reply rather than being set on the agent, which other
+    // requests share, so it only applies to this run
+    let guard = state.agent.read().await;
+    let agent = guard.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let running = agent.list_extensions().await;
+    if let Some(missing) = recipe
+        .extensions
+        .iter()
+        .flatten()
+        .find(|name| !running.contains(name))
+    {
+        tracing::error!(
+            "The recipe uses the extension {}, which isn't running",
+            missing
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let session_config = SessionConfig {
+        id: session::Identifier::Name(session_id.clone()),
+        working_dir: PathBuf::from(request.session_working_dir),
+        recipe: Some(recipe.clone()),
+    };
+    let result = run_messages(agent.as_ref(), &recipe, session_config).await;
+    let provider = agent.provider().await;
+    drop(guard);
+    let RecipeRun {
+        messages,
+        responses,
+        failed_checks,
+        last_reply,
+    } = result?;
+    let output =
+        final_output.and_then(|final_output| final_output.given_in(&messages[last_reply..]));
+
+    let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
+    let recipe_name = recipe.name.clone();
+    tokio::spawn(async move {
+        if let Err(e) = session::persist_messages(&session_path, &messages, Some(provider)).await {
+            tracing::error!("Failed to store session history: {:?}", e);
+            return;
+        }
+        let result = match session::read_metadata(&session_path) {
+            Ok(mut metadata) => {
+                metadata
+                    .custom
+                    .insert("recipe".to_string(), Value::String(recipe_name));
+                session::update_metadata(&session_path, &metadata).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::error!("Failed to note the recipe in the session: {:?}", e);
+        }
+    });
+
+    Ok(Json(RunRecipeResponse {
+        session_id,
+        responses,
+        output,
+        failed_checks,
+    }))
+}
+
+/// Send the recipe's messages one after the other, and then the checks that fail until they
+/// pass or have had [`MAX_CHECK_ROUNDS`] more tries
+async fn run_messages(
+    agent: &dyn Agent,
+    recipe: &Recipe,
+    session_config: SessionConfig,
+) -> Result<RecipeRun, StatusCode> {
+    let mut run = RecipeRun::default();
+    for text in recipe.messages() {
+        run.last_reply = run.messages.len();
+        let response = send(agent, &mut run.messages, text, &session_config).await?;
+        run.responses.push(response);
+    }
+    if recipe.checks().next().is_none() {
+        return Ok(run);
+    }
+    let working_dir = session_config.working_dir.clone();
+    for round in 0..=MAX_CHECK_ROUNDS {
+        let answer = run.responses.last().map(String::as_str).unwrap_or_default();
+        run.failed_checks = recipe.failed_checks(&working_dir, answer).await;
+        if run.failed_checks.is_empty() || round == MAX_CHECK_ROUNDS {
+            break;
+        }
+        let text = retry_message(&run.failed_checks);
+        run.last_reply = run.messages.len();
+        let response = send(agent, &mut run.messages, &text, &session_config).await?;
+        run.responses.push(response);
+    }
+    Ok(run)
+}
+
+/// The conversation of a recipe run, the text of each answer, and the checks that still fail
+#[derive(Default)]
+struct RecipeRun {
+    messages: Vec<Message>,
+    responses: Vec<String>,
+    failed_checks: Vec<String>,
+    /// Where the messages of the last reply start
+    last_reply: usize,
+}
+
+/// Send `text` and add the reply to `messages`, returning the text of the answer
+async fn send(
+    agent: &dyn Agent,
+    messages: &mut Vec<Message>,
+    text: &str,
+    session_config: &SessionConfig,
+) -> Result<String, StatusCode> {
+    messages.push(Message::user().with_text(text));
+    let mut stream = agent
+        .reply(
+            messages,
+            Some(session_config.clone()),
+            CancellationToken::new(),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to start reply stream: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut response_text = String::new();
+    while let Some(response) = stream.next().await {
+        let message = response.map_err(|e| {
+            tracing::error!("Error running recipe: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        // The caller waits for the whole run and can't be asked, so calls that need approval
+        // are declined
+        if let Some(MessageContent::ToolConfirmationRequest(confirmation)) = message.content.first()
+        {
+            tracing::warn!(
+                "Declined the call to {} in a recipe run, as there is no one to approve it",
+                confirmation.tool_name
+            );
+            agent
+                .handle_confirmation(confirmation.id.clone(), false)
+                .await;
+            continue;
+        }
+        if message.role == Role::Assistant {
+            for content in &message.content {
+                if let MessageContent::Text(text) = content {
+                    response_text.push_str(&text.text);
+                    response_text.push('\n');
+                }
+            }
+        }
+        messages.push(message);
+    }
+    Ok(response_text.trim().to_string())
+}
+
+// Configure routes for this module
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/recipes/load", post(load_recipe))
+        .route("/recipes/run", post(run_recipe))
+        .with_state(state)
+}
@@ -10,7 +10,12 @@ use bytes::Bytes;
 use futures::{stream::StreamExt, Stream};
 use goose::session;
 use goose::{
-    agents::SessionConfig,
+    agents::{
+        approval::ToolApproval,
+        limits::stop_reason,
+        progress::{self, ProgressListener, ToolProgress},
+        SessionConfig,
+    },
     message::{Message, MessageContent},
 };
 
@@ -18,15 +23,41 @@ use mcp_core::role::Role;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
+    collections::HashMap,
     convert::Infallible,
     path::PathBuf,
     pin::Pin,
+    sync::{Arc, LazyLock, Mutex},
     task::{Context, Poll},
     time::Duration,
 };
 use tokio::sync::mpsc;
 use tokio::time::timeout;
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+
+/// The fewest new messages in a session worth extracting memories from
+const REMEMBER_AFTER_MESSAGES: usize = 10;
+
+/// How many progress reports can wait to be streamed, past which they are dropped
+const PROGRESS_BUFFER: usize = 64;
+
+/// Passes the progress of tool calls to the reply streaming in their session
+#[derive(Default)]
+struct ReplyProgress {
+    replies: Mutex<HashMap<String, mpsc::Sender<ToolProgress>>>,
+}
+
+impl ProgressListener for ReplyProgress {
+    fn report(&self, progress: ToolProgress) {
+        let replies = self.replies.lock().unwrap();
+        if let Some(reply) = progress.session_id.as_ref().and_then(|id| replies.get(id)) {
+            let _ = reply.try_send(progress);
+        }
+    }
+}
+
+static REPLY_PROGRESS: LazyLock<Arc<ReplyProgress>> = LazyLock::new(Arc::default);
 
 // Direct message serialization for the chat request
 #[derive(Debug, Deserialize)]
@@ -75,9 +106,22 @@ impl IntoResponse for SseResponse {
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
 enum MessageEvent {
-    Message { message: Message },
-    Error { error: String },
-    Finish { reason: String },
+    Message {
+        message: Message,
+    },
+    /// How a tool call that is running is getting on, such as the lines a command printed
+    Progress {
+        tool: String,
+        progress: f64,
+        total: Option<f64>,
+        message: Option<String>,
+    },
+    Error {
+        error: String,
+    },
+    Finish {
+        reason: String,
+    },
 }
 
 // Stream a message as an SSE event
@@ -151,13 +195,17 @@ async fn handler(
         // Get the provider first, before starting the reply stream
         let provider = agent.provider().await;
 
+        // Cancelled when the client goes away, e.g. when the user stops the reply
+        let cancel_token = CancellationToken::new();
         let mut stream = match agent
             .reply(
                 &messages,
                 Some(SessionConfig {
                     id: session::Identifier::Name(session_id.clone()),
                     working_dir: PathBuf::from(session_working_dir),
+                    recipe: None,
                 }),
+                cancel_token.clone(),
             )
             .await
         {
@@ -185,12 +233,35 @@ async fn handler(
         // Collect all messages for storage
         let mut all_messages = messages.clone();
         let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
+        // A run stopped by one of its limits finishes as such, rather than as a normal stop
+        let mut finish_reason = "stop";
+        let (progress_tx, mut progress_rx) = mpsc::channel(PROGRESS_BUFFER);
+        REPLY_PROGRESS
+            .replies
+            .lock()
+            .unwrap()
+            .insert(session_id.clone(), progress_tx);
 
         loop {
             tokio::select! {
+                Some(progress) = progress_rx.recv() => {
+                    let event = MessageEvent::Progress {
+                        tool: progress.tool,
+                        progress: progress.progress,
+                        total: progress.total,
+                        message: progress.message,
+                    };
+                    if stream_event(event, &tx).await.is_err() {
+                        cancel_token.cancel();
+                        break;
+                    }
+                }
                 response = timeout(Duration::from_millis(500), stream.next()) => {
                     match response {
                         Ok(Some(Ok(message))) => {
+                            if stop_reason(&message).is_some() {
+                                finish_reason = "limit";
+                            }
                             all_messages.push(message.clone());
                             if let Err(e) = stream_event(MessageEvent::Message { message }, &tx).await {
                                 tracing::error!("Error sending message through channel: {}", e);
@@ -228,19 +299,48 @@ async fn handler(
                         }
                         Err(_) => { // Heartbeat, used to detect disconnected clients
                             if tx.is_closed() {
+                                cancel_token.cancel();
                                 break;
                             }
                             continue;
                         }
                     }
                 }
+                _ = tx.closed() => {
+                    cancel_token.cancel();
+                    break;
+                }
             }
         }
 
+        REPLY_PROGRESS.replies.lock().unwrap().remove(&session_id);
+
+        // Sessions here don't end, so memories are extracted once enough new messages build up
+        if let Some(memory) = goose::memory::global() {
+            let provider = provider.clone();
+            tokio::spawn(async move {
+                if let Err(e) = session::persist_messages(&session_path, &all_messages, None).await
+                {
+                    tracing::error!("Failed to store session history: {:?}", e);
+                    return;
+                }
+                if let Err(e) = memory
+                    .remember_session(
+                        &session_path,
+                        provider.as_ref().as_ref(),
+                        REMEMBER_AFTER_MESSAGES,
+                    )
+                    .await
+                {
+                    tracing::warn!("Failed to extract memories: {}", e);
+                }
+            });
+        }
+
         // Send finish event
         let _ = stream_event(
             MessageEvent::Finish {
-                reason: "stop".to_string(),
+                reason: finish_reason.to_string(),
             },
             &tx,
         )
@@ -303,7 +403,9 @@ async fn ask_handler(
             Some(SessionConfig {
                 id: session::Identifier::Name(session_id.clone()),
                 working_dir: PathBuf::from(session_working_dir),
+                recipe: None,
             }),
+            CancellationToken::new(),
         )
         .await
     {
@@ -365,6 +467,9 @@ async fn ask_handler(
 struct ToolConfirmationRequest {
     id: String,
     confirmed: bool,
+    /// Other arguments to make the call with, when confirmed
+    #[serde(default)]
+    arguments: Option<Value>,
 }
 
 async fn confirm_handler(
@@ -385,14 +490,17 @@ async fn confirm_handler(
     let agent = state.agent.clone();
     let agent = agent.read().await;
     let agent = agent.as_ref().ok_or(StatusCode::NOT_FOUND)?;
-    agent
-        .handle_confirmation(request.id.clone(), request.confirmed)
-        .await;
+    let approval = match request.arguments {
+        Some(arguments) if request.confirmed => ToolApproval::Edit(arguments),
+        _ => request.confirmed.into(),
+    };
+    agent.handle_approval(request.id.clone(), approval).await;
     Ok(Json(Value::Object(serde_json::Map::new())))
 }
 
 // Configure routes for this module
 pub fn routes(state: AppState) -> Router {
+    progress::set_listener(REPLY_PROGRESS.clone());
     Router::new()
         .route("/reply", post(handler))
         .route("/ask", post(ask_handler))
@@ -1,20 +1,70 @@
 use crate::state::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
-    routing::get,
+    routing::{get, patch, post, put},
     Json, Router,
 };
-use goose::message::Message;
-use goose::session;
+use goose::config::Config;
+use goose::message::{Message, Metadata};
 use goose::session::info::{get_session_info, SessionInfo};
-use serde::Serialize;
+use goose::session::retention::{self, PurgeReport, RetentionPolicy};
+use goose::session::{self, SessionStats};
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
 struct SessionListResponse {
     sessions: Vec<SessionInfo>,
 }
 
+#[derive(Deserialize)]
+struct SessionSearchParams {
+    q: Option<String>,
+    tool: Option<String>,
+    path: Option<String>,
+    #[serde(default = "default_search_limit")]
+    limit: usize,
+}
+
+fn default_search_limit() -> usize {
+    20
+}
+
+#[derive(Serialize)]
+struct SessionSearchResponse {
+    hits: Vec<session::SearchHit>,
+}
+
+#[derive(Deserialize)]
+struct ForkSessionRequest {
+    /// The fork keeps the messages before this one
+    message_index: usize,
+    /// Name for the fork, by default a new session id
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EditMessageRequest {
+    /// The new text of the message
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct SessionMetadataRequest {
+    /// Values to set, a null value removes its key
+    custom: Metadata,
+}
+
+#[derive(Deserialize)]
+struct PurgeSessionsRequest {
+    /// Limits to purge by instead of the configured ones
+    #[serde(flatten)]
+    policy: RetentionPolicy,
+    /// Only list the sessions that would be removed
+    #[serde(default)]
+    dry_run: bool,
+}
+
 #[derive(Serialize)]
 struct SessionHistoryResponse {
     session_id: String,
@@ -42,6 +92,71 @@ async fn list_sessions(
     Ok(Json(SessionListResponse { sessions }))
 }
 
+// Search past sessions by their messages, tools called and files touched
+async fn search_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SessionSearchParams>,
+) -> Result<Json<SessionSearchResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if params.q.is_none() && params.tool.is_none() && params.path.is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let hits = session::search(&session::SearchQuery {
+        text: params.q,
+        tool: params.tool,
+        path: params.path,
+        limit: params.limit,
+    })
+    .map_err(|e| {
+        tracing::error!("Failed to search sessions: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(SessionSearchResponse { hits }))
+}
+
+// Remove old sessions past the retention limits
+async fn purge_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<PurgeSessionsRequest>,
+) -> Result<Json<PurgeReport>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let policy = if request.policy.is_unlimited() {
+        RetentionPolicy::from_config(Config::global())
+    } else {
+        request.policy
+    };
+    if policy.is_unlimited() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let report = retention::purge(&policy, request.dry_run).map_err(|e| {
+        tracing::error!("Failed to purge sessions: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(report))
+}
+
 // Get a specific session's history
 async fn get_session_history(
     State(state): State<AppState>,
@@ -60,11 +175,9 @@ async fn get_session_history(
 
     let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
 
-    // Read metadata
-    let metadata = session::read_metadata(&session_path).map_err(|_| StatusCode::NOT_FOUND)?;
-
-    let messages = match session::read_messages(&session_path) {
-        Ok(messages) => messages,
+    // Load from the session store where possible, so a session cut off mid-write resumes whole
+    let saved = match session::resume(&session_path) {
+        Ok(saved) => saved,
         Err(e) => {
             tracing::error!("Failed to read session messages: {:?}", e);
             return Err(StatusCode::NOT_FOUND);
@@ -73,15 +186,279 @@ async fn get_session_history(
 
     Ok(Json(SessionHistoryResponse {
         session_id,
-        metadata,
-        messages,
+        metadata: saved.metadata,
+        messages: saved.messages,
+    }))
+}
+
+// Get the turns, tool calls, tokens, cost, duration and files touched of a session
+async fn get_session_stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionStats>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id));
+    let stats = session::stats::session_stats(&session_path).map_err(|e| {
+        tracing::error!("Failed to read session messages: {:?}", e);
+        StatusCode::NOT_FOUND
+    })?;
+
+    Ok(Json(stats))
+}
+
+// Fork a session at a message into a new session, leaving the original as it is
+async fn fork_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Json(request): Json<ForkSessionRequest>,
+) -> Result<Json<SessionHistoryResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let fork_id = request.name.unwrap_or_else(session::generate_session_id);
+    let fork_path = session::get_path(session::Identifier::Name(fork_id.clone()));
+    if session::session_exists(&fork_path) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let forked = session::fork(&session_path, request.message_index, &fork_path).map_err(|e| {
+        tracing::error!("Failed to fork session: {:?}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(SessionHistoryResponse {
+        session_id: fork_id,
+        metadata: forked.metadata,
+        messages: forked.messages,
     }))
 }
 
+// Rewrite a message the user sent, dropping the replies that followed so it can be answered again
+async fn edit_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((session_id, message_index)): Path<(String, usize)>,
+    Json(request): Json<EditMessageRequest>,
+) -> Result<Json<SessionHistoryResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let edited =
+        session::edit_message(&session_path, message_index, &request.text).map_err(|e| {
+            tracing::error!("Failed to edit message: {:?}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    Ok(Json(SessionHistoryResponse {
+        session_id,
+        metadata: edited.metadata,
+        messages: edited.messages,
+    }))
+}
+
+// Delete the turn a message the user sent starts, and everything after it
+async fn delete_turn(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((session_id, message_index)): Path<(String, usize)>,
+) -> Result<Json<SessionHistoryResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let remaining = session::delete_turn(&session_path, message_index).map_err(|e| {
+        tracing::error!("Failed to delete turn: {:?}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(SessionHistoryResponse {
+        session_id,
+        metadata: remaining.metadata,
+        messages: remaining.messages,
+    }))
+}
+
+// Remove the agent's reply to the last turn, so the client can have it answered again
+async fn undo_turn(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionHistoryResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let undone = session::undo_turn(&session_path).map_err(|e| {
+        tracing::error!("Failed to undo turn: {:?}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(SessionHistoryResponse {
+        session_id,
+        metadata: undone.metadata,
+        messages: undone.messages,
+    }))
+}
+
+#[derive(Deserialize)]
+struct RollbackRequest {
+    /// The turn whose checkpoint to roll back to
+    turn: usize,
+}
+
+// Put the session's files back as they were after a turn, and remove the turns after it
+async fn rollback_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Json(request): Json<RollbackRequest>,
+) -> Result<Json<SessionHistoryResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id.clone()));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let rolled_back = session::checkpoint::rollback(&session_path, request.turn)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to roll back the session: {:?}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    Ok(Json(SessionHistoryResponse {
+        session_id,
+        metadata: rolled_back.metadata,
+        messages: rolled_back.messages,
+    }))
+}
+
+// Set metadata values on a session, so integrations can match it up with their own records
+async fn update_session_metadata(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Json(request): Json<SessionMetadataRequest>,
+) -> Result<Json<session::SessionMetadata>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let session_path = session::get_path(session::Identifier::Name(session_id));
+    if !session::session_exists(&session_path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut metadata = session::read_metadata(&session_path).map_err(|e| {
+        tracing::error!("Failed to read session metadata: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    for (key, value) in request.custom {
+        if value.is_null() {
+            metadata.custom.remove(&key);
+        } else {
+            metadata.custom.insert(key, value);
+        }
+    }
+    session::update_metadata(&session_path, &metadata)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to update session metadata: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(metadata))
+}
+
 // Configure routes for this module
 pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/sessions", get(list_sessions))
+        .route("/sessions/search", get(search_sessions))
+        .route("/sessions/purge", post(purge_sessions))
         .route("/sessions/:session_id", get(get_session_history))
+        .route("/sessions/:session_id/stats", get(get_session_stats))
+        .route("/sessions/:session_id/fork", post(fork_session))
+        .route(
+            "/sessions/:session_id/messages/:message_index",
+            put(edit_message).delete(delete_turn),
+        )
+        .route("/sessions/:session_id/undo", post(undo_turn))
+        .route("/sessions/:session_id/rollback", post(rollback_session))
+        .route(
+            "/sessions/:session_id/metadata",
+            patch(update_session_metadata),
+        )
         .with_state(state)
 }
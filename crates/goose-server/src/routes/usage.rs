@@ -0,0 +1,71 @@
+use crate::state::AppState;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use chrono::{Duration, Utc};
+use goose::usage::{UsageGroupBy, UsageStore, UsageSummary};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    /// Only include usage from the last `days` days, all usage if not set
+    days: Option<u32>,
+    group_by: Option<UsageGroupBy>,
+}
+
+#[derive(Serialize)]
+struct UsageResponse {
+    group_by: UsageGroupBy,
+    days: Option<u32>,
+    summaries: Vec<UsageSummary>,
+    total: UsageSummary,
+}
+
+// Aggregate recorded usage by day, provider, model or session
+async fn get_usage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageResponse>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let store = UsageStore::global().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let group_by = query.group_by.unwrap_or(UsageGroupBy::Day);
+    let since = query
+        .days
+        .map(|days| Utc::now() - Duration::days(days as i64));
+
+    let summaries = store.summarize(since, group_by).map_err(|e| {
+        tracing::error!("Failed to summarize usage: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let total = store.total(since).map_err(|e| {
+        tracing::error!("Failed to summarize usage: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(UsageResponse {
+        group_by,
+        days: query.days,
+        summaries,
+        total,
+    }))
+}
+
+// Configure routes for this module
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/usage", get(get_usage))
+        .with_state(state)
+}
@@ -4,6 +4,7 @@ use goose::agents::{AgentFactory, ExtensionConfig};
 use goose::config::{DEFAULT_EXTENSION_DESCRIPTION, DEFAULT_EXTENSION_TIMEOUT};
 use goose::message::Message;
 use goose::providers::databricks::DatabricksProvider;
+use tokio_util::sync::CancellationToken;
 
 #[tokio::main]
 async fn main() {
@@ -31,7 +32,10 @@ async fn main() {
     let messages = vec![Message::user()
         .with_text("can you summarize the readme.md in this dir using just a haiku?")];
 
-    let mut stream = agent.reply(&messages, None).await.unwrap();
+    let mut stream = agent
+        .reply(&messages, None, CancellationToken::new())
+        .await
+        .unwrap();
     while let Some(message) = stream.next().await {
         println!(
             "{}",
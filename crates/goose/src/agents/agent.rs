@@ -1,17 +1,24 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
+use super::approval::ToolApproval;
+use super::compare::{Candidate, Comparison};
 use super::extension::{ExtensionConfig, ExtensionResult};
+use crate::config::{Config, ConfigError};
 use crate::message::Message;
+use crate::persona::Persona;
 use crate::providers::base::Provider;
+use crate::recipe::Recipe;
 use crate::session;
+use crate::session::replay::{Recorder, Recording};
 use mcp_core::prompt::Prompt;
 use mcp_core::protocol::GetPromptResult;
 
@@ -22,16 +29,33 @@ pub struct SessionConfig {
     pub id: session::Identifier,
     /// Working directory for the session
     pub working_dir: PathBuf,
+    /// A recipe to run the reply with, in place of the one set on the agent, for this reply
+    /// only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<Recipe>,
+}
+
+impl SessionConfig {
+    /// The name of the session file, which usage and the audit log record the session by
+    pub fn name(&self) -> Option<String> {
+        session::get_path(self.id.clone())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+    }
 }
 
 /// Core trait defining the behavior of an Agent
 #[async_trait]
 pub trait Agent: Send + Sync {
     /// Create a stream that yields each message as it's generated by the agent
+    ///
+    /// Cancelling `cancel_token` stops the reply at the next completion or tool call, aborting
+    /// the request to the provider and the tool calls that are running, and ends the stream.
     async fn reply(
         &self,
         messages: &[Message],
         session: Option<SessionConfig>,
+        cancel_token: CancellationToken,
     ) -> Result<BoxStream<'_, Result<Message>>>;
 
     /// Add a new MCP client to the agent
@@ -53,9 +77,43 @@ pub trait Agent: Send + Sync {
     /// Handle a confirmation response for a tool request
     async fn handle_confirmation(&self, request_id: String, confirmed: bool);
 
+    /// Answer a request to call a tool, possibly with other arguments for the call
+    ///
+    /// Agents that can't change the arguments of a call deny it when asked to.
+    async fn handle_approval(&self, request_id: String, approval: ToolApproval) {
+        self.handle_confirmation(request_id, approval == ToolApproval::Approve)
+            .await
+    }
+
     /// Override the system prompt with custom text
     async fn override_system_prompt(&mut self, template: String);
 
+    /// Apply the system prompt settings of the config, the file in
+    /// GOOSE_SYSTEM_PROMPT_FILE_PATH replacing the built-in prompt and the text in
+    /// GOOSE_SYSTEM_PROMPT_APPEND added to it
+    async fn configure_system_prompt(&mut self) -> Result<()> {
+        let config = Config::global();
+        match config.get_param::<String>("GOOSE_SYSTEM_PROMPT_FILE_PATH") {
+            Ok(path) => {
+                let template = std::fs::read_to_string(&path).map_err(|e| {
+                    anyhow!("Failed to read the system prompt file {}: {}", path, e)
+                })?;
+                self.override_system_prompt(template).await;
+            }
+            Err(ConfigError::NotFound(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+        match config.get_param::<String>("GOOSE_SYSTEM_PROMPT_APPEND") {
+            Ok(extension) => self.extend_system_prompt(extension).await,
+            Err(ConfigError::NotFound(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// The system prompt as it would be sent now, or why it can't be rendered
+    async fn system_prompt(&self) -> Result<String>;
+
     /// Lists all prompts from all extensions
     async fn list_extension_prompts(&self) -> HashMap<String, Vec<Prompt>>;
 
@@ -66,6 +124,41 @@ pub trait Agent: Send + Sync {
     /// Get the plan prompt, which will be used with the planner (reasoner) model
     async fn get_plan_prompt(&self) -> anyhow::Result<String>;
 
+    /// Answer the turn `messages` end with with each of `candidates`, given this agent's system
+    /// prompt and tools, without making their tool calls or keeping their answers
+    async fn compare(
+        &self,
+        messages: &[Message],
+        candidates: &[Candidate],
+    ) -> Result<Vec<Comparison>>;
+
     /// Get a reference to the provider used by this agent
     async fn provider(&self) -> Arc<Box<dyn Provider>>;
+
+    /// Replace the provider, e.g. to switch models, keeping the extensions and prompts.
+    /// Replies that are already running finish with the previous provider
+    async fn update_provider(&mut self, provider: Box<dyn Provider>);
+
+    /// Record the provider's completions and the results of tool calls, to replay them later
+    async fn record(&mut self, recorder: Arc<Recorder>);
+
+    /// Answer completions and tool calls from `recording` instead of the provider and the
+    /// extensions, to re-drive the agent through a recorded session
+    async fn replay(&mut self, recording: Arc<Recording>);
+
+    /// Follow a recipe's instructions and use only the tools it allows, or stop following one
+    /// when None
+    async fn set_recipe(&mut self, recipe: Option<Recipe>);
+
+    /// Have each reply end with a value matching a JSON schema, given through the final output
+    /// tool, or stop asking for one when None. Setting a recipe sets its response schema.
+    async fn set_response_schema(&mut self, schema: Option<Value>) -> Result<()>;
+
+    /// The value the latest reply ended with, when a response schema is set and it gave one
+    /// that matches
+    async fn final_output(&self) -> Option<Value>;
+
+    /// Work as a persona, with its instructions and the tool policy it defaults to, or as no
+    /// persona when None. The persona's model is chosen with the provider.
+    async fn set_persona(&mut self, persona: Option<Persona>);
 }
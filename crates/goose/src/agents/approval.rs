@@ -0,0 +1,257 @@
+//! Rules for tool calls that always wait for the user's approval
+//!
+//! Rules are configured as an `approval_rules` list in the config file. Each names the tools it
+//! applies to, with `*` matching any characters, and can narrow them down with a regular
+//! expression the call's arguments must match, as JSON:
+//!
+//! ```yaml
+//! approval_rules:
+//!   - tool: developer__shell
+//!     reason: Runs a shell command
+//!   - tool: "*"
+//!     arguments: '\brm\b|"method":\s*"(POST|PUT|PATCH|DELETE)"'
+//!     reason: Deletes files or writes over the network
+//! ```
+//!
+//! A call matching a rule waits for the user in every mode but chat, even when its tool was
+//! allowed before, and the user can approve it, deny it or change its arguments first.
+//! The rules are checked again when a call is dispatched, so agents that can't ask the user,
+//! such as subagents and the reference agent, refuse the calls that match a rule.
+use anyhow::Result;
+use mcp_core::tool::ToolCall;
+use mcp_core::{Content, ToolError, ToolResult};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+
+/// Config key holding the list of approval rules
+pub const APPROVAL_RULES_CONFIG_KEY: &str = "approval_rules";
+
+/// The question asked for tool calls that need approval in the approve modes
+pub const TOOL_CONFIRMATION_PROMPT: &str = "Goose would like to call the above tool. Allow? (y/n):";
+
+/// Tool calls that need the user's approval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ApprovalRule {
+    /// The tools the rule applies to, like `developer__shell` or `github__*`
+    pub tool: String,
+    /// A regular expression the call's arguments, as JSON, must match for the rule to apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+    /// Why the calls are risky, shown when asking for approval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// The user's answer to a request to call a tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolApproval {
+    Approve,
+    Deny,
+    /// Call the tool with these arguments instead
+    Edit(Value),
+}
+
+impl ToolApproval {
+    /// The call to make for this answer to a request to make `tool_call`, None if it was denied
+    pub fn apply(self, mut tool_call: ToolCall) -> Option<ToolCall> {
+        match self {
+            ToolApproval::Approve => Some(tool_call),
+            ToolApproval::Deny => None,
+            ToolApproval::Edit(arguments) => {
+                tool_call.arguments = arguments;
+                Some(tool_call)
+            }
+        }
+    }
+}
+
+impl From<bool> for ToolApproval {
+    fn from(approved: bool) -> Self {
+        if approved {
+            ToolApproval::Approve
+        } else {
+            ToolApproval::Deny
+        }
+    }
+}
+
+struct CompiledRule {
+    rule: ApprovalRule,
+    tool: Regex,
+    arguments: Option<Regex>,
+}
+
+/// The configured approval rules
+#[derive(Default)]
+pub struct ApprovalRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl ApprovalRules {
+    pub fn new(rules: Vec<ApprovalRule>) -> Result<Self> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let tool = rule
+                    .tool
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(".*");
+                Ok(CompiledRule {
+                    tool: Regex::new(&format!("^{}$", tool))?,
+                    arguments: rule.arguments.as_deref().map(Regex::new).transpose()?,
+                    rule,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Read the rules from the config
+    ///
+    /// Rules that can't be read would let risky calls through, so then every call needs approval.
+    pub fn from_config() -> Self {
+        let rules: Vec<ApprovalRule> = match Config::global().get_param(APPROVAL_RULES_CONFIG_KEY) {
+            Ok(rules) => rules,
+            Err(crate::config::ConfigError::NotFound(_)) => return Self::default(),
+            Err(e) => {
+                tracing::warn!("Failed to read the approval rules: {}", e);
+                vec![Self::fallback_rule()]
+            }
+        };
+        Self::new(rules).unwrap_or_else(|e| {
+            tracing::warn!(
+                "Invalid approval rule, every tool call needs approval: {}",
+                e
+            );
+            Self::new(vec![Self::fallback_rule()]).expect("the fallback rule is valid")
+        })
+    }
+
+    fn fallback_rule() -> ApprovalRule {
+        ApprovalRule {
+            tool: "*".to_string(),
+            arguments: None,
+            reason: Some("The approval rules in the config are invalid".to_string()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule requiring approval for `tool_call`, if any
+    pub fn check(&self, tool_call: &ToolCall) -> Option<&ApprovalRule> {
+        let arguments = tool_call.arguments.to_string();
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.tool.is_match(&tool_call.name)
+                    && rule
+                        .arguments
+                        .as_ref()
+                        .is_none_or(|pattern| pattern.is_match(&arguments))
+            })
+            .map(|rule| &rule.rule)
+    }
+}
+
+/// The question asked of the user for a call matching `rule`
+pub fn approval_prompt(rule: &ApprovalRule) -> String {
+    match &rule.reason {
+        Some(reason) => format!("{}. Allow this tool call?", reason.trim_end_matches('.')),
+        None => format!(
+            "This call matches the approval rule for {}. Allow it?",
+            rule.tool
+        ),
+    }
+}
+
+/// The error for a call matching an approval rule that the user wasn't asked to approve
+pub fn approval_needed(tool_name: &str) -> ToolError {
+    ToolError::ExecutionError(format!(
+        "Calls like this one to {} need the user's approval, which can't be asked for here. \
+        Don't try to make it again, find another way or report this step back.",
+        tool_name
+    ))
+}
+
+/// Tell the model the user changed the arguments of a call, as it only knows the ones it sent
+pub fn note_edit(output: ToolResult<Vec<Content>>, arguments: &Value) -> ToolResult<Vec<Content>> {
+    output.map(|mut content| {
+        content.insert(
+            0,
+            Content::text(format!(
+                "The user changed the arguments of this call before it ran, to: {}",
+                arguments
+            )),
+        );
+        content
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check() -> Result<()> {
+        let rules = ApprovalRules::new(vec![
+            ApprovalRule {
+                tool: "developer__shell".to_string(),
+                arguments: Some(r"\brm\b".to_string()),
+                reason: Some("Deletes files".to_string()),
+            },
+            ApprovalRule {
+                tool: "github__*".to_string(),
+                arguments: None,
+                reason: None,
+            },
+        ])?;
+
+        let delete = ToolCall::new("developer__shell", json!({"command": "rm -rf build"}));
+        let rule = rules.check(&delete).unwrap();
+        assert_eq!(
+            approval_prompt(rule),
+            "Deletes files. Allow this tool call?"
+        );
+        let list = ToolCall::new("developer__shell", json!({"command": "ls -la"}));
+        assert!(rules.check(&list).is_none());
+        let format = ToolCall::new("developer__shell", json!({"command": "cargo fmt"}));
+        assert!(rules.check(&format).is_none());
+
+        let comment = ToolCall::new("github__add_comment", json!({"body": "LGTM"}));
+        assert_eq!(rules.check(&comment).unwrap().tool, "github__*");
+        let other = ToolCall::new("gitlab__add_comment", json!({"body": "LGTM"}));
+        assert!(rules.check(&other).is_none());
+
+        assert!(ApprovalRules::new(vec![ApprovalRule {
+            tool: "*".to_string(),
+            arguments: Some("(".to_string()),
+            reason: None,
+        }])
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply() {
+        let call = ToolCall::new("developer__shell", json!({"command": "rm -rf /"}));
+        assert_eq!(
+            ToolApproval::Approve.apply(call.clone()),
+            Some(call.clone())
+        );
+        assert_eq!(ToolApproval::Deny.apply(call.clone()), None);
+        let edited = ToolApproval::Edit(json!({"command": "rm -rf build"}))
+            .apply(call)
+            .unwrap();
+        assert_eq!(edited.arguments, json!({"command": "rm -rf build"}));
+    }
+}
@@ -0,0 +1,453 @@
+//! Budget limits on the cost and tokens spent per session and per day
+//!
+//! Limits are read from the config (or env), and are all optional:
+//! - GOOSE_SESSION_MAX_COST / GOOSE_SESSION_MAX_TOKENS
+//! - GOOSE_DAILY_MAX_COST / GOOSE_DAILY_MAX_TOKENS
+//! - GOOSE_BUDGET_WARN_THRESHOLD, the fraction of a limit at which to warn (default 0.8)
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::borrow::Cow;
+use tracing::warn;
+
+use super::agent::SessionConfig;
+use crate::config::Config;
+use crate::message::Message;
+use crate::providers::base::ProviderUsage;
+use crate::session;
+use crate::usage::{AlertPeriod, CostAlerts, UsageRecord, UsageStore};
+
+/// Tool name used for the confirmation request sent when a budget is exceeded
+pub const BUDGET_CONFIRMATION_TOOL: &str = "platform__budget";
+
+/// The metadata key of a message telling the user about their spend, naming what it is about
+///
+/// These messages are for the user alone, and aren't sent to the model.
+pub const SPEND_NOTICE: &str = "goose_spend_notice";
+
+const DEFAULT_WARN_THRESHOLD: f64 = 0.8;
+
+/// Tokens and cost accumulated over a session or a day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub tokens: i64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, usage: &ProviderUsage) {
+        self.tokens += usage.usage.total_tokens.unwrap_or(0).max(0) as i64;
+        self.cost += usage.cost.unwrap_or(0.0);
+    }
+}
+
+/// Outcome of checking usage against the configured limits
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetStatus {
+    WithinBudget,
+    /// A limit is past the warning threshold
+    Warning(String),
+    /// A limit has been reached
+    Exceeded(String),
+}
+
+/// The configured budget limits
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetLimits {
+    pub session_max_cost: Option<f64>,
+    pub session_max_tokens: Option<i64>,
+    pub daily_max_cost: Option<f64>,
+    pub daily_max_tokens: Option<i64>,
+    pub warn_threshold: f64,
+}
+
+impl Default for BudgetLimits {
+    fn default() -> Self {
+        Self {
+            session_max_cost: None,
+            session_max_tokens: None,
+            daily_max_cost: None,
+            daily_max_tokens: None,
+            warn_threshold: DEFAULT_WARN_THRESHOLD,
+        }
+    }
+}
+
+impl BudgetLimits {
+    pub fn from_config() -> Self {
+        let config = Config::global();
+        Self {
+            session_max_cost: config.get_param("GOOSE_SESSION_MAX_COST").ok(),
+            session_max_tokens: config.get_param("GOOSE_SESSION_MAX_TOKENS").ok(),
+            daily_max_cost: config.get_param("GOOSE_DAILY_MAX_COST").ok(),
+            daily_max_tokens: config.get_param("GOOSE_DAILY_MAX_TOKENS").ok(),
+            warn_threshold: config
+                .get_param("GOOSE_BUDGET_WARN_THRESHOLD")
+                .unwrap_or(DEFAULT_WARN_THRESHOLD),
+        }
+    }
+
+    /// Whether any limit is configured
+    pub fn is_enabled(&self) -> bool {
+        self.session_max_cost.is_some()
+            || self.session_max_tokens.is_some()
+            || self.has_daily_limit()
+    }
+
+    /// Whether any daily limit is configured, which requires tracking usage across sessions
+    pub fn has_daily_limit(&self) -> bool {
+        self.daily_max_cost.is_some() || self.daily_max_tokens.is_some()
+    }
+
+    /// Check the session and daily totals against the limits, reporting the limit
+    /// closest to (or furthest past) its cap
+    pub fn check(&self, session: &UsageTotals, daily: &UsageTotals) -> BudgetStatus {
+        let candidates = [
+            self.session_max_cost.map(|max| {
+                (
+                    "Session cost",
+                    format_cost(session.cost),
+                    format_cost(max),
+                    session.cost / max,
+                )
+            }),
+            self.session_max_tokens.map(|max| {
+                (
+                    "Session tokens",
+                    session.tokens.to_string(),
+                    max.to_string(),
+                    session.tokens as f64 / max as f64,
+                )
+            }),
+            self.daily_max_cost.map(|max| {
+                (
+                    "Daily cost",
+                    format_cost(daily.cost),
+                    format_cost(max),
+                    daily.cost / max,
+                )
+            }),
+            self.daily_max_tokens.map(|max| {
+                (
+                    "Daily tokens",
+                    daily.tokens.to_string(),
+                    max.to_string(),
+                    daily.tokens as f64 / max as f64,
+                )
+            }),
+        ];
+
+        let worst = candidates
+            .into_iter()
+            .flatten()
+            .max_by(|a, b| a.3.total_cmp(&b.3));
+
+        match worst {
+            Some((label, used, max, fraction)) if fraction >= 1.0 => {
+                BudgetStatus::Exceeded(format!("{label} of {used} has reached the limit of {max}"))
+            }
+            Some((label, used, max, fraction)) if fraction >= self.warn_threshold => {
+                BudgetStatus::Warning(format!(
+                    "{label} of {used} is {:.0}% of the limit of {max}",
+                    fraction * 100.0
+                ))
+            }
+            _ => BudgetStatus::WithinBudget,
+        }
+    }
+}
+
+fn format_cost(cost: f64) -> String {
+    format!("${:.2}", cost)
+}
+
+/// Today's usage across all sessions, summed from the usage store
+///
+/// Without a store, or when it can't be read, nothing is counted for the day.
+pub fn daily_usage(store: Option<&UsageStore>) -> UsageTotals {
+    let Some(store) = store else {
+        return UsageTotals::default();
+    };
+    match store.total(AlertPeriod::Day.start(Local::now())) {
+        Ok(summary) => UsageTotals {
+            tokens: summary.total_tokens,
+            cost: summary.cost,
+        },
+        Err(e) => {
+            warn!("Failed to read today's usage: {}", e);
+            UsageTotals::default()
+        }
+    }
+}
+
+/// What a reply spends, kept against the budget and the cost alerts
+///
+/// Every completion's usage is recorded in the usage store, which the daily limits and
+/// alerts are read from. Replayed completions were paid for when they were recorded, so
+/// they count for nothing.
+pub struct Spend {
+    budget: BudgetLimits,
+    alerts: CostAlerts,
+    session: UsageTotals,
+    daily: UsageTotals,
+    session_id: Option<String>,
+    replaying: bool,
+    warned: bool,
+    approved: bool,
+}
+
+impl Spend {
+    /// Start from what the session has spent in earlier replies
+    pub fn start(session: Option<&SessionConfig>, replaying: bool) -> Self {
+        let spent = session
+            .and_then(|session| session::read_metadata(&session::get_path(session.id.clone())).ok())
+            .map(|metadata| UsageTotals {
+                tokens: metadata.accumulated_tokens.unwrap_or(0),
+                cost: metadata.accumulated_cost.unwrap_or(0.0),
+            })
+            .unwrap_or_default();
+        Self {
+            budget: BudgetLimits::from_config(),
+            alerts: CostAlerts::from_config(),
+            session: spent,
+            daily: UsageTotals::default(),
+            session_id: session.and_then(SessionConfig::name),
+            replaying,
+            warned: false,
+            approved: false,
+        }
+    }
+
+    /// The session's spend so far, for its metadata
+    pub fn session(&self) -> UsageTotals {
+        self.session
+    }
+
+    /// Check the budget before a completion
+    ///
+    /// A warning is only given once a reply, and once the user has agreed to go past a cap
+    /// the budget isn't checked again.
+    pub fn check(&mut self) -> BudgetStatus {
+        if !self.budget.is_enabled() || self.approved {
+            return BudgetStatus::WithinBudget;
+        }
+        if self.budget.has_daily_limit() {
+            self.daily = daily_usage(UsageStore::global());
+        }
+        match self.budget.check(&self.session, &self.daily) {
+            BudgetStatus::Warning(_) if self.warned => BudgetStatus::WithinBudget,
+            BudgetStatus::Warning(reason) => {
+                self.warned = true;
+                warn!("Budget warning: {}", reason);
+                BudgetStatus::Warning(reason)
+            }
+            status => status,
+        }
+    }
+
+    /// Ask the user whether to go past the cap `reason` describes, returning the request's id
+    pub fn confirmation_request(&self, reason: &str) -> (String, Message) {
+        let request_id = format!("budget_{}", uuid::Uuid::new_v4());
+        let request = Message::user().with_tool_confirmation_request(
+            request_id.clone(),
+            BUDGET_CONFIRMATION_TOOL.to_string(),
+            json!({"session": self.session, "daily": self.daily}),
+            Some(format!("{reason}. Continue anyway?")),
+        );
+        (request_id, request)
+    }
+
+    /// The user agreed to go past the budget for the rest of the reply
+    pub fn approve(&mut self) {
+        self.approved = true;
+    }
+
+    /// Record the usage of a completion made by `provider`, in `role` when it had one,
+    /// and return notices of the cost alerts it crossed
+    ///
+    /// Failing to record or to check the alerts is only logged, as the completion was
+    /// already paid for.
+    pub fn record(
+        &mut self,
+        provider: &str,
+        role: Option<&str>,
+        usage: &ProviderUsage,
+    ) -> Vec<Message> {
+        if self.replaying {
+            return Vec::new();
+        }
+        self.session.add(usage);
+        let store = UsageStore::global();
+        if let Some(store) = store {
+            let mut record = UsageRecord::new(provider, self.session_id.clone(), usage);
+            record.role = role.map(str::to_string);
+            if let Err(e) = store.record(&record) {
+                warn!("Failed to record usage: {}", e);
+            }
+        }
+        if self.alerts.is_empty() {
+            return Vec::new();
+        }
+        match self
+            .alerts
+            .crossed(usage.cost.unwrap_or(0.0), self.session.cost, store)
+        {
+            Ok(alerts) => alerts
+                .into_iter()
+                .map(|alert| {
+                    warn!("{}", alert);
+                    spend_notice("cost_alert", format!("{alert}."))
+                })
+                .collect(),
+            Err(e) => {
+                warn!("Failed to check cost alerts: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn spend_notice(kind: &str, text: String) -> Message {
+    Message::assistant()
+        .with_text(text)
+        .with_metadata(SPEND_NOTICE, kind)
+}
+
+/// The message ending a reply that stopped at a budget cap
+pub fn budget_reached(reason: &str) -> Message {
+    spend_notice(
+        "budget_reached",
+        format!("Stopped because the budget was reached: {reason}."),
+    )
+}
+
+/// The message warning that a budget cap is close
+pub fn budget_warning(reason: &str) -> Message {
+    spend_notice("budget_warning", format!("Budget warning: {reason}."))
+}
+
+/// Whether `message` is a notice of the spend, see [`SPEND_NOTICE`]
+pub fn is_spend_notice(message: &Message) -> bool {
+    message.metadata.contains_key(SPEND_NOTICE)
+}
+
+/// `messages` without the notices of the spend, for sending to the model
+pub fn without_spend_notices(messages: Cow<'_, Vec<Message>>) -> Cow<'_, Vec<Message>> {
+    if !messages.iter().any(is_spend_notice) {
+        return messages;
+    }
+    Cow::Owned(
+        messages
+            .iter()
+            .filter(|message| !is_spend_notice(message))
+            .cloned()
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::Usage;
+    use anyhow::Result;
+
+    fn usage(tokens: i32, cost: f64) -> ProviderUsage {
+        let mut usage = ProviderUsage::new(
+            "test-model".to_string(),
+            Usage::new(None, None, Some(tokens)),
+        );
+        usage.cost = Some(cost);
+        usage
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = BudgetLimits {
+            session_max_cost: Some(1.0),
+            daily_max_tokens: Some(1000),
+            ..Default::default()
+        };
+
+        let session = UsageTotals {
+            tokens: 10,
+            cost: 0.5,
+        };
+        let daily = UsageTotals {
+            tokens: 100,
+            cost: 0.5,
+        };
+        assert_eq!(limits.check(&session, &daily), BudgetStatus::WithinBudget);
+
+        let session = UsageTotals {
+            tokens: 10,
+            cost: 0.85,
+        };
+        assert_eq!(
+            limits.check(&session, &daily),
+            BudgetStatus::Warning("Session cost of $0.85 is 85% of the limit of $1.00".to_string())
+        );
+
+        let daily = UsageTotals {
+            tokens: 1200,
+            cost: 0.85,
+        };
+        assert_eq!(
+            limits.check(&session, &daily),
+            BudgetStatus::Exceeded(
+                "Daily tokens of 1200 has reached the limit of 1000".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_no_limits() {
+        let limits = BudgetLimits::default();
+        assert!(!limits.is_enabled());
+        let totals = UsageTotals {
+            tokens: i64::MAX,
+            cost: 1e9,
+        };
+        assert_eq!(limits.check(&totals, &totals), BudgetStatus::WithinBudget);
+    }
+
+    #[test]
+    fn test_daily_usage_from_store() -> Result<()> {
+        assert_eq!(daily_usage(None), UsageTotals::default());
+
+        let store = UsageStore::open_in_memory()?;
+        store.record(&UsageRecord::new(
+            "openai",
+            Some("a".to_string()),
+            &usage(100, 0.25),
+        ))?;
+        store.record(&UsageRecord::new(
+            "openai",
+            Some("b".to_string()),
+            &usage(50, 0.25),
+        ))?;
+
+        // Usage from every session counts towards the day
+        let daily = daily_usage(Some(&store));
+        assert_eq!(daily.tokens, 150);
+        assert!((daily.cost - 0.5).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spend_notices_are_not_sent() {
+        let messages = vec![
+            Message::user().with_text("hi"),
+            budget_warning("Session cost of $0.85 is 85% of the limit of $1.00"),
+            Message::assistant().with_text("hello"),
+        ];
+        assert!(is_spend_notice(&messages[1]));
+
+        let sent = without_spend_notices(Cow::Borrowed(&messages));
+        assert_eq!(sent.len(), 2);
+        assert!(!sent.iter().any(is_spend_notice));
+
+        let sent = without_spend_notices(Cow::Borrowed(&sent));
+        assert!(matches!(sent, Cow::Borrowed(_)));
+    }
+}
@@ -1,21 +1,58 @@
 use anyhow::Result;
 use chrono::{DateTime, TimeZone, Utc};
-use futures::stream::{FuturesUnordered, StreamExt};
+use futures::stream::{self, FuturesUnordered, StreamExt};
 use mcp_client::McpService;
-use mcp_core::protocol::GetPromptResult;
+use mcp_core::protocol::{CallToolResult, GetPromptResult, InitializeResult};
 use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
 use std::time::Duration;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{debug, instrument};
 
+use super::approval::{approval_needed, ApprovalRules};
+use super::budget::BUDGET_CONFIRMATION_TOOL;
+use super::dry_run::{simulated_result, DRY_RUN_MODE};
 use super::extension::{ExtensionConfig, ExtensionError, ExtensionInfo, ExtensionResult, ToolInfo};
+use super::final_output::{FinalOutput, FINAL_OUTPUT_TOOL};
+use super::policy::{policy_approval_needed, policy_refusal, PolicyAction, ToolPolicy};
+use super::progress;
+use super::roles::{ModelRole, RoleAssignment, WorkerModel};
+use super::router::{ModelRouter, Route, TaskKind};
+use super::sampling::{Sampler, SamplingPolicy};
+use super::subagent::{run_subagent, run_subagents, SPAWN_SUBAGENTS_TOOL, SPAWN_SUBAGENT_TOOL};
+use super::supervisor;
+use super::tool_list;
+use crate::audit::{Approval, AuditLog, AuditRecord};
+use crate::code_index::{search_code, SEARCH_CODE_TOOL};
 use crate::config::Config;
+use crate::knowledge::{search_knowledge, SEARCH_KNOWLEDGE_TOOL};
+use crate::message::{Message, ToolRequest};
+use crate::persona::Persona;
 use crate::prompt_template;
 use crate::providers::base::Provider;
-use mcp_client::client::{ClientCapabilities, ClientInfo, McpClient, McpClientTrait};
-use mcp_client::transport::{SseTransport, StdioTransport, Transport};
+use crate::providers::handoff;
+use crate::recipe::Recipe;
+use crate::session::replay::{
+    RecordedEvent, Recorder, Recording, RecordingProvider, ReplayProvider,
+};
+use crate::token_counter::TokenCounter;
+use crate::tool_arguments::ToolSchema;
+use crate::tool_names::ToolNames;
+use crate::tool_output::{
+    read_tool_output, ToolOutputLimit, ToolOutputStore, READ_TOOL_OUTPUT_TOOL,
+};
+use crate::tool_overrides::ToolOverrides;
+use crate::tool_retries::{self, ToolRetries};
+use crate::tool_timeouts::{self, ToolTimeouts};
+use crate::web_fetch::{fetch_url, FETCH_URL_TOOL};
+use mcp_client::client::{
+    ClientCapabilities, ClientInfo, Error as ClientError, McpClient, McpClientTrait,
+    SamplingCapability,
+};
+use mcp_client::transport::{SseTransport, StdioTransport, StreamableHttpTransport, Transport};
 use mcp_core::{prompt::Prompt, Content, Tool, ToolCall, ToolError, ToolResult};
 use serde_json::Value;
 
@@ -24,16 +61,76 @@ use serde_json::Value;
 static DEFAULT_TIMESTAMP: LazyLock<DateTime<Utc>> =
     LazyLock::new(|| Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
 
-type McpClientBox = Arc<Mutex<Box<dyn McpClientTrait>>>;
+/// How many of a turn's tool calls run at once, unless `GOOSE_TOOL_CONCURRENCY` is set
+const DEFAULT_TOOL_CONCURRENCY: usize = 4;
+
+/// The tools Goose handles itself, whose names an extension's tools can't take
+const PLATFORM_TOOLS: [&str; 10] = [
+    "platform__read_resource",
+    "platform__list_resources",
+    SEARCH_KNOWLEDGE_TOOL,
+    SEARCH_CODE_TOOL,
+    FETCH_URL_TOOL,
+    FINAL_OUTPUT_TOOL,
+    READ_TOOL_OUTPUT_TOOL,
+    SPAWN_SUBAGENT_TOOL,
+    SPAWN_SUBAGENTS_TOOL,
+    BUDGET_CONFIRMATION_TOOL,
+];
+
+type McpClientBox = Arc<RwLock<Box<dyn McpClientTrait>>>;
 
 /// Manages MCP clients and their interactions
 pub struct Capabilities {
     clients: HashMap<String, McpClientBox>,
+    /// The configs of the extensions whose servers are started again when they fail
+    supervised: HashMap<String, ExtensionConfig>,
+    /// The extensions whose tools are called one at a time, each with the lock a call holds
+    serial: HashMap<String, Arc<Mutex<()>>>,
+    /// Answers the extensions' requests for completions
+    sampler: Arc<Sampler>,
     instructions: HashMap<String, String>,
     resource_capable_extensions: HashSet<String>,
     provider: Arc<Box<dyn Provider>>,
     system_prompt_override: Option<String>,
     system_prompt_extensions: Vec<String>,
+    recorder: Option<Arc<Recorder>>,
+    recording: Option<Arc<Recording>>,
+    /// The session the tool calls are audited under, and the usage of completions made for it
+    /// recorded under
+    session_id: Option<String>,
+    /// The session's working directory, where the project the code tools look at is
+    working_dir: Option<PathBuf>,
+    recipe: Option<Recipe>,
+    /// The recipe the current reply runs in place of `recipe`, with its response schema
+    reply_recipe: Option<(Recipe, Option<Arc<FinalOutput>>)>,
+    persona: Option<Persona>,
+    router: Option<ModelRouter>,
+    /// The small model answering the current turn, when the router gave it one
+    routed: Option<Arc<Box<dyn Provider>>>,
+    worker: Option<WorkerModel>,
+    /// The worker model making the current completion, when it was assigned one
+    assigned: Option<Arc<Box<dyn Provider>>>,
+    /// Whether the provider was replaced since the last reply
+    switched: bool,
+    /// How many messages of the conversation were written before the provider was replaced
+    handed_off: Option<usize>,
+    final_output: Option<Arc<FinalOutput>>,
+    tool_output_limit: Option<ToolOutputLimit>,
+    tool_outputs: ToolOutputStore,
+    /// Built when an output is first large enough to need counting, for the provider's model
+    token_counter: OnceLock<TokenCounter>,
+    /// The input schemas of the extensions' tools, by prefixed name, as they were last listed
+    tool_schemas: std::sync::Mutex<HashMap<String, Arc<ToolSchema>>>,
+    /// Where calls to the extensions' tools go, by the names they were last listed with
+    tool_names: std::sync::Mutex<ToolNames>,
+    tool_overrides: ToolOverrides,
+    /// The names the renamed tools were listed with, by their new names
+    renamed_tools: std::sync::Mutex<HashMap<String, String>>,
+    tool_timeouts: ToolTimeouts,
+    tool_retries: ToolRetries,
+    /// How many of a turn's tool calls run at once
+    tool_concurrency: usize,
 }
 
 /// A flattened representation of a resource used by the agent to prepare inference
@@ -71,7 +168,7 @@ impl ResourceItem {
 
 /// Sanitizes a string by replacing invalid characters with underscores.
 /// Valid characters match [a-zA-Z0-9_-]
-fn normalize(input: String) -> String {
+pub(crate) fn normalize(input: String) -> String {
     let mut result = String::with_capacity(input.len());
     for c in input.chars() {
         result.push(match c {
@@ -91,17 +188,193 @@ pub fn get_parameter_names(tool: &Tool) -> Vec<String> {
         .unwrap_or_default()
 }
 
+/// Start the server of the extension `config` describes and initialize a client for it, which
+/// answers the server's requests for completions with `sampler` and passes on the progress its
+/// tools report
+pub(super) async fn start_client(
+    config: &ExtensionConfig,
+    sampler: &Arc<Sampler>,
+) -> ExtensionResult<(Box<dyn McpClientTrait>, InitializeResult)> {
+    let handler =
+        progress::with_progress(tool_list::with_list_changed(sampler.handler(config.name())));
+    // Requests wait as long as the slowest of the extension's tools may run
+    let longest_tool = ToolTimeouts::from_config().longest_for_extension(&config.name());
+    let request_timeout = |timeout: Option<u64>| {
+        Duration::from_secs(timeout.unwrap_or(crate::config::DEFAULT_EXTENSION_TIMEOUT))
+            .max(longest_tool.unwrap_or_default())
+    };
+    let mut client: Box<dyn McpClientTrait> = match config {
+        ExtensionConfig::Sse {
+            uri, envs, timeout, ..
+        } => {
+            let transport = SseTransport::new(uri, envs.get_env())
+                .with_headers(config.request_headers())
+                .with_request_handler(handler);
+            let handle = transport.start().await?;
+            let service = McpService::with_timeout(handle, request_timeout(*timeout));
+            Box::new(McpClient::new(service))
+        }
+        ExtensionConfig::StreamableHttp {
+            uri, envs, timeout, ..
+        } => {
+            let transport = StreamableHttpTransport::new(uri, envs.get_env())
+                .with_headers(config.request_headers())
+                .with_request_handler(handler);
+            let handle = transport.start().await?;
+            let service = McpService::with_timeout(handle, request_timeout(*timeout));
+            Box::new(McpClient::new(service))
+        }
+        ExtensionConfig::Stdio {
+            cmd,
+            args,
+            envs,
+            timeout,
+            ..
+        } => {
+            let transport = StdioTransport::new(cmd, args.to_vec(), envs.get_env())
+                .with_request_handler(handler);
+            let handle = transport.start().await?;
+            let service = McpService::with_timeout(handle, request_timeout(*timeout));
+            Box::new(McpClient::new(service))
+        }
+        #[allow(unused_variables)]
+        ExtensionConfig::Builtin {
+            name,
+            display_name,
+            timeout,
+        } => {
+            // For builtin extensions, we run the current executable with mcp and extension name
+            let cmd = std::env::current_exe()
+                .expect("should find the current executable")
+                .to_str()
+                .expect("should resolve executable to string path")
+                .to_string();
+            let transport =
+                StdioTransport::new(&cmd, vec!["mcp".to_string(), name.clone()], builtin_envs())
+                    .with_request_handler(handler);
+            let handle = transport.start().await?;
+            let service = McpService::with_timeout(handle, request_timeout(*timeout));
+            Box::new(McpClient::new(service))
+        }
+    };
+
+    // Initialize the client with default capabilities
+    let info = ClientInfo {
+        name: "goose".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let capabilities = ClientCapabilities {
+        sampling: sampler.is_enabled().then(SamplingCapability::default),
+    };
+
+    let init_result = client
+        .initialize(info, capabilities)
+        .await
+        .map_err(|e| ExtensionError::Initialization(config.clone(), e))?;
+
+    Ok((client, init_result))
+}
+
+/// Settings for Goose's own extensions, which only see the environment of their process
+const BUILTIN_SETTINGS: [&str; 5] = [
+    "GOOSE_SHELL_SANDBOX",
+    "GOOSE_SHELL_SANDBOX_IMAGE",
+    "GOOSE_SHELL_SANDBOX_NETWORK",
+    "GOOSE_FILE_ALLOW",
+    "GOOSE_FILE_DENY",
+];
+
+/// The environment passing on the settings for Goose's own extensions that the configuration,
+/// including a project's, has
+fn builtin_envs() -> HashMap<String, String> {
+    let config = Config::global();
+    BUILTIN_SETTINGS
+        .iter()
+        .filter_map(|key| {
+            let value = match config.get_param::<serde_json::Value>(key).ok()? {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Null => return None,
+                value => value.to_string(),
+            };
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+/// Whether the tools of the extension `config` describes are called one at a time
+///
+/// A local server often keeps state between calls, such as the directory a shell is in or a
+/// browser it drives, so its calls are made one after another unless the extension is in
+/// `GOOSE_PARALLEL_EXTENSIONS`. Remote servers take calls at once.
+fn calls_one_at_a_time(config: &ExtensionConfig) -> bool {
+    if !supervisor::is_supervised(config) {
+        return false;
+    }
+    let parallel: Vec<String> = Config::global()
+        .get_param("GOOSE_PARALLEL_EXTENSIONS")
+        .unwrap_or_default();
+    !parallel
+        .iter()
+        .any(|name| normalize(name.clone()) == normalize(config.key()))
+}
+
 impl Capabilities {
     /// Create a new Capabilities with the specified provider
     pub fn new(provider: Box<dyn Provider>) -> Self {
-        Self {
+        let provider = Arc::new(provider);
+        let capabilities = Self {
             clients: HashMap::new(),
+            supervised: HashMap::new(),
+            serial: HashMap::new(),
+            sampler: Arc::new(Sampler::new(
+                SamplingPolicy::from_config(),
+                Arc::clone(&provider),
+            )),
             instructions: HashMap::new(),
             resource_capable_extensions: HashSet::new(),
-            provider: Arc::new(provider),
+            provider,
             system_prompt_override: None,
             system_prompt_extensions: Vec::new(),
-        }
+            recorder: None,
+            recording: None,
+            session_id: None,
+            working_dir: None,
+            recipe: None,
+            reply_recipe: None,
+            persona: None,
+            router: ModelRouter::from_config(None, None),
+            routed: None,
+            worker: WorkerModel::from_config(None),
+            assigned: None,
+            switched: false,
+            handed_off: None,
+            final_output: None,
+            tool_output_limit: ToolOutputLimit::from_config(),
+            tool_outputs: ToolOutputStore::default(),
+            token_counter: OnceLock::new(),
+            tool_schemas: std::sync::Mutex::new(HashMap::new()),
+            tool_names: std::sync::Mutex::new(ToolNames::reserving(PLATFORM_TOOLS)),
+            tool_overrides: ToolOverrides::from_config(),
+            renamed_tools: std::sync::Mutex::new(HashMap::new()),
+            tool_timeouts: ToolTimeouts::from_config(),
+            tool_retries: ToolRetries::from_config(),
+            tool_concurrency: Config::global()
+                .get_param::<usize>("GOOSE_TOOL_CONCURRENCY")
+                .unwrap_or(DEFAULT_TOOL_CONCURRENCY)
+                .max(1),
+        };
+        capabilities.update_sampler();
+        capabilities
+    }
+
+    /// Give the sampler the smaller model of the router, or else of the worker
+    fn update_sampler(&self) {
+        self.sampler.set_small(
+            self.router
+                .as_ref()
+                .map(ModelRouter::small)
+                .or_else(|| self.worker.as_ref().map(WorkerModel::provider)),
+        );
     }
 
     pub fn supports_resources(&self) -> bool {
@@ -111,76 +384,7 @@ impl Capabilities {
     /// Add a new MCP extension based on the provided client type
     // TODO IMPORTANT need to ensure this times out if the extension command is broken!
     pub async fn add_extension(&mut self, config: ExtensionConfig) -> ExtensionResult<()> {
-        let mut client: Box<dyn McpClientTrait> = match &config {
-            ExtensionConfig::Sse {
-                uri, envs, timeout, ..
-            } => {
-                let transport = SseTransport::new(uri, envs.get_env());
-                let handle = transport.start().await?;
-                let service = McpService::with_timeout(
-                    handle,
-                    Duration::from_secs(
-                        timeout.unwrap_or(crate::config::DEFAULT_EXTENSION_TIMEOUT),
-                    ),
-                );
-                Box::new(McpClient::new(service))
-            }
-            ExtensionConfig::Stdio {
-                cmd,
-                args,
-                envs,
-                timeout,
-                ..
-            } => {
-                let transport = StdioTransport::new(cmd, args.to_vec(), envs.get_env());
-                let handle = transport.start().await?;
-                let service = McpService::with_timeout(
-                    handle,
-                    Duration::from_secs(
-                        timeout.unwrap_or(crate::config::DEFAULT_EXTENSION_TIMEOUT),
-                    ),
-                );
-                Box::new(McpClient::new(service))
-            }
-            #[allow(unused_variables)]
-            ExtensionConfig::Builtin {
-                name,
-                display_name,
-                timeout,
-            } => {
-                // For builtin extensions, we run the current executable with mcp and extension name
-                let cmd = std::env::current_exe()
-                    .expect("should find the current executable")
-                    .to_str()
-                    .expect("should resolve executable to string path")
-                    .to_string();
-                let transport = StdioTransport::new(
-                    &cmd,
-                    vec!["mcp".to_string(), name.clone()],
-                    HashMap::new(),
-                );
-                let handle = transport.start().await?;
-                let service = McpService::with_timeout(
-                    handle,
-                    Duration::from_secs(
-                        timeout.unwrap_or(crate::config::DEFAULT_EXTENSION_TIMEOUT),
-                    ),
-                );
-                Box::new(McpClient::new(service))
-            }
-        };
-
-        // Initialize the client with default capabilities
-        let info = ClientInfo {
-            name: "goose".to_string(),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-        };
-        let capabilities = ClientCapabilities::default();
-
-        let init_result = client
-            .initialize(info, capabilities)
-            .await
-            .map_err(|e| ExtensionError::Initialization(config.clone(), e))?;
+        let (client, init_result) = start_client(&config, &self.sampler).await?;
 
         let sanitized_name = normalize(config.key().to_string());
 
@@ -197,8 +401,16 @@ impl Capabilities {
         }
 
         // Store the client using the provided name
-        self.clients
-            .insert(sanitized_name.clone(), Arc::new(Mutex::new(client)));
+        let client = Arc::new(RwLock::new(client));
+        if calls_one_at_a_time(&config) {
+            self.serial
+                .insert(sanitized_name.clone(), Arc::new(Mutex::new(())));
+        }
+        if supervisor::is_supervised(&config) {
+            supervisor::supervise(&config, &self.sampler, &client);
+            self.supervised.insert(sanitized_name.clone(), config);
+        }
+        self.clients.insert(sanitized_name.clone(), client);
 
         Ok(())
     }
@@ -213,9 +425,283 @@ impl Capabilities {
         self.system_prompt_override = Some(template);
     }
 
-    /// Get a reference to the provider
+    /// Get a reference to the provider, the small model's while it answers the turn and the
+    /// worker's while it makes the completion
     pub fn provider(&self) -> Arc<Box<dyn Provider>> {
-        Arc::clone(&self.provider)
+        Arc::clone(
+            self.routed
+                .as_ref()
+                .or(self.assigned.as_ref())
+                .unwrap_or(&self.provider),
+        )
+    }
+
+    /// Replace the provider used for later completions
+    pub fn set_provider(&mut self, provider: Box<dyn Provider>) {
+        self.provider = Arc::new(provider);
+        // Completions for extensions aren't recorded, so they don't take the place of the
+        // agent's in a replay
+        self.sampler.set_session(Arc::clone(&self.provider));
+        self.routed = None;
+        self.assigned = None;
+        self.switched = true;
+        self.token_counter = OnceLock::new();
+        if let Some(recorder) = self.recorder.clone() {
+            self.record_provider(recorder);
+        }
+    }
+
+    /// Make the messages written before the provider was replaced fit the one replacing it,
+    /// see [`handoff`]
+    ///
+    /// The first reply after the replacement is given every message from before it, so those
+    /// are the ones counted.
+    pub fn hand_off(&mut self, messages: &mut Vec<Message>) {
+        if std::mem::take(&mut self.switched) {
+            self.handed_off = Some(messages.len());
+        }
+        if let Some(handed_off) = self.handed_off {
+            handoff::hand_off(messages, handed_off);
+        }
+    }
+
+    /// Record the provider's completions and the results of tool calls with `recorder`
+    pub fn set_recorder(&mut self, recorder: Arc<Recorder>) {
+        self.record_provider(recorder.clone());
+        self.recorder = Some(recorder);
+    }
+
+    fn record_provider(&mut self, recorder: Arc<Recorder>) {
+        let provider = RecordingProvider::new(self.provider(), recorder);
+        self.provider = Arc::new(Box::new(provider));
+    }
+
+    /// Answer completions and tool calls from `recording`, instead of the provider and extensions
+    pub fn set_replay(&mut self, recording: Arc<Recording>) {
+        self.provider = Arc::new(Box::new(ReplayProvider::new(recording.clone())));
+        self.recording = Some(recording);
+    }
+
+    /// Whether completions and tool calls are answered from a recording
+    pub fn replaying(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Audit the tool calls from here on as the session `session_id`'s
+    pub fn set_session_id(&mut self, session_id: Option<String>) {
+        self.sampler.set_session_id(session_id.clone());
+        self.session_id = session_id;
+    }
+
+    /// Look at the project in `working_dir` with the code tools, or else the current directory's
+    pub fn set_working_dir(&mut self, working_dir: Option<PathBuf>) {
+        self.working_dir = working_dir;
+    }
+
+    /// The session the agent is replying in, if it is saved
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// The audit log the tool calls go to, if it is on, and they aren't replayed
+    fn audit_log(&self) -> Option<&'static AuditLog> {
+        AuditLog::global().filter(|_| !self.replaying())
+    }
+
+    /// Record in the audit log that `tool_call` wasn't made, as `approval` says why
+    pub fn audit_declined(&self, tool_call: &ToolCall, approval: Approval) {
+        if let Some(log) = self.audit_log() {
+            let record = AuditRecord::new(self.session_id.clone(), tool_call, approval);
+            if let Err(e) = log.record(&record) {
+                tracing::warn!("Failed to audit the call to {}: {}", tool_call.name, e);
+            }
+        }
+    }
+
+    /// Run with the instructions of `recipe`, only the tools it allows and its response schema,
+    /// or without a recipe
+    pub fn set_recipe(&mut self, recipe: Option<Recipe>) {
+        let schema = recipe
+            .as_ref()
+            .and_then(|recipe| recipe.response.as_ref())
+            .map(|response| response.json_schema.clone());
+        // Loading the recipe checked its schema
+        if let Err(e) = self.set_response_schema(schema) {
+            tracing::warn!("Ignoring the recipe's response schema: {}", e);
+        }
+        self.recipe = recipe;
+    }
+
+    /// Run the reply starting now with `recipe` in place of the one set on the agent, or with
+    /// the agent's own
+    ///
+    /// Each reply sets its own, so a recipe given for one reply never outlasts it.
+    pub fn set_reply_recipe(&mut self, recipe: Option<Recipe>) {
+        self.reply_recipe = recipe.map(|recipe| {
+            let final_output = recipe.response.as_ref().and_then(|response| {
+                FinalOutput::new(response.json_schema.clone())
+                    .map_err(|e| tracing::warn!("Ignoring the recipe's response schema: {}", e))
+                    .ok()
+                    .map(Arc::new)
+            });
+            (recipe, final_output)
+        });
+    }
+
+    /// The recipe being run, if any
+    fn recipe(&self) -> Option<&Recipe> {
+        match &self.reply_recipe {
+            Some((recipe, _)) => Some(recipe),
+            None => self.recipe.as_ref(),
+        }
+    }
+
+    /// Have replies end with a value matching `schema`, or stop asking for one
+    pub fn set_response_schema(&mut self, schema: Option<Value>) -> Result<()> {
+        self.final_output = schema.map(FinalOutput::new).transpose()?.map(Arc::new);
+        Ok(())
+    }
+
+    /// The response schema replies have to end with a value of, and the latest value
+    pub fn final_output(&self) -> Option<Arc<FinalOutput>> {
+        match &self.reply_recipe {
+            Some((_, final_output)) => final_output.clone(),
+            None => self.final_output.clone(),
+        }
+    }
+
+    /// Work as `persona` with its instructions, tool policy and routing, or without one
+    pub fn set_persona(&mut self, persona: Option<Persona>) {
+        self.router = match &persona {
+            Some(persona) => {
+                ModelRouter::from_config(persona.routing.as_ref(), persona.provider.as_deref())
+            }
+            None => ModelRouter::from_config(None, None),
+        };
+        self.worker = WorkerModel::from_config(
+            persona
+                .as_ref()
+                .and_then(|persona| persona.provider.as_deref()),
+        );
+        self.persona = persona;
+        self.update_sampler();
+    }
+
+    /// Route simple questions to the small model with `router`, or answer every turn with the
+    /// session's model
+    pub fn set_router(&mut self, router: Option<ModelRouter>) {
+        self.router = router;
+        self.routed = None;
+        self.update_sampler();
+    }
+
+    /// Pick the model that answers the turn `messages` end with, returning the choice to
+    /// keep with the answer when routing is on
+    ///
+    /// Replayed sessions aren't routed, as the recording already answers each completion.
+    pub async fn route(&mut self, messages: &[Message]) -> Option<Route> {
+        self.routed = None;
+        if self.replaying() {
+            return None;
+        }
+        let router = self.router.as_ref()?;
+        let kind = router.classify(messages).await?;
+        let mut provider = None;
+        if kind == TaskKind::Simple {
+            let small = router.small();
+            provider = Some(router.provider_name().to_string());
+            self.routed = Some(match &self.recorder {
+                Some(recorder) => {
+                    Arc::new(Box::new(RecordingProvider::new(small, recorder.clone())))
+                }
+                None => small,
+            });
+        }
+        let model = self.provider().get_model_config().model_name.clone();
+        tracing::info!("Routed a {} turn to {}", kind.as_str(), model);
+        Some(Route {
+            kind,
+            model,
+            provider,
+        })
+    }
+
+    /// Have the worker model make the completions after tool results, or the session's model
+    /// make every one
+    pub fn set_worker(&mut self, worker: Option<WorkerModel>) {
+        self.worker = worker;
+        self.assigned = None;
+        self.update_sampler();
+    }
+
+    /// Pick the model that makes the completion after `messages`, returning the choice to keep
+    /// with the answer when there is a worker model
+    ///
+    /// A turn the router gave to the small model stays with it, and replayed sessions are
+    /// answered from their recording.
+    pub fn assign_role(&mut self, messages: &[Message]) -> Option<RoleAssignment> {
+        self.assigned = None;
+        if self.replaying() || self.routed.is_some() {
+            return None;
+        }
+        let worker = self.worker.as_ref()?;
+        let role = ModelRole::after(messages);
+        let mut provider = None;
+        if role == ModelRole::Worker {
+            provider = Some(worker.provider_name().to_string());
+            self.assigned = Some(match &self.recorder {
+                Some(recorder) => Arc::new(Box::new(RecordingProvider::new(
+                    worker.provider(),
+                    recorder.clone(),
+                ))),
+                None => worker.provider(),
+            });
+        }
+        let model = self.provider().get_model_config().model_name.clone();
+        Some(RoleAssignment {
+            role,
+            model,
+            provider,
+        })
+    }
+
+    /// The configured tool policy, made stricter where the persona's is
+    pub fn tool_policy(&self) -> ToolPolicy {
+        let policy = ToolPolicy::from_config();
+        match &self.persona {
+            Some(persona) => policy.with_persona(persona.tool_policy.clone()),
+            None => policy,
+        }
+    }
+
+    /// Cut or summarize tool outputs with more tokens than the limit, or keep them whole
+    pub fn set_tool_output_limit(&mut self, limit: Option<ToolOutputLimit>) {
+        self.tool_output_limit = limit;
+    }
+
+    /// Whether outputs that are too long are cut or summarized, so the agent needs the tool to
+    /// read them in full, which any call it makes can return
+    pub fn limits_tool_outputs(&self) -> bool {
+        self.tool_output_limit.is_some()
+    }
+
+    fn allows_tool(&self, name: &str) -> bool {
+        self.recipe().is_none_or(|recipe| recipe.allows_tool(name))
+    }
+
+    /// Whether the recipe being run lets the agent use Goose's own tool `name`
+    ///
+    /// The tools an agent needs to finish a run are always allowed. Extensions' tools are
+    /// checked by their names in their extension when they are listed and called.
+    pub fn allows_platform_tool(&self, name: &str) -> bool {
+        !PLATFORM_TOOLS.contains(&name)
+            || [
+                FINAL_OUTPUT_TOOL,
+                READ_TOOL_OUTPUT_TOOL,
+                BUDGET_CONFIRMATION_TOOL,
+            ]
+            .contains(&name)
+            || self.allows_tool(name)
     }
 
     /// Get aggregated usage statistics
@@ -223,6 +709,8 @@ impl Capabilities {
         let sanitized_name = normalize(name.to_string());
 
         self.clients.remove(&sanitized_name);
+        self.supervised.remove(&sanitized_name);
+        self.serial.remove(&sanitized_name);
         self.instructions.remove(&sanitized_name);
         self.resource_capable_extensions.remove(&sanitized_name);
         Ok(())
@@ -233,19 +721,34 @@ impl Capabilities {
     }
 
     /// Get all tools from all clients with proper prefixing
-    pub async fn get_prefixed_tools(&mut self) -> ExtensionResult<Vec<Tool>> {
-        let mut tools = Vec::new();
-        for (name, client) in &self.clients {
-            let client_guard = client.lock().await;
-            let mut client_tools = client_guard.list_tools(None).await?;
+    pub async fn get_prefixed_tools(&self) -> ExtensionResult<Vec<Tool>> {
+        let mut tools: Vec<Tool> = Vec::new();
+        let mut names = ToolNames::reserving(PLATFORM_TOOLS);
+        // In the same order each time, so tools whose names meet keep the ones they were given
+        let mut clients: Vec<_> = self.clients.iter().collect();
+        clients.sort_by_key(|(name, _)| name.as_str());
+        for (name, client) in clients {
+            // Listing doesn't wait for the extension's running calls, only a restart does
+            let listed = client.read().await.list_tools(None).await;
+            let mut client_tools = match listed {
+                Err(e) => {
+                    let mut client_guard = client.write().await;
+                    if !self.recover(name, &e, &mut client_guard).await? {
+                        return Err(e.into());
+                    }
+                    client_guard.list_tools(None).await?
+                }
+                Ok(listed) => listed,
+            };
+            let client_guard = client.read().await;
 
             loop {
                 for tool in client_tools.tools {
-                    tools.push(Tool::new(
-                        format!("{}__{}", name, tool.name),
-                        &tool.description,
-                        tool.input_schema,
-                    ));
+                    let prefixed = format!("{}__{}", name, tool.name);
+                    if self.allows_tool(&prefixed) {
+                        let named = names.name(name, &tool.name);
+                        tools.push(Tool::new(named, &tool.description, tool.input_schema));
+                    }
                 }
 
                 // exit loop when there are no more pages
@@ -256,15 +759,91 @@ impl Capabilities {
                 client_tools = client_guard.list_tools(client_tools.next_cursor).await?;
             }
         }
+        // Kept to check the arguments of the calls the model makes to them, compiled again only
+        // when a tool's schema changed
+        {
+            let mut schemas = self.tool_schemas.lock().unwrap();
+            *schemas = tools
+                .iter()
+                .map(|tool| {
+                    let schema = schemas
+                        .get(&tool.name)
+                        .filter(|schema| *schema.schema() == tool.input_schema)
+                        .cloned()
+                        .unwrap_or_else(|| Arc::new(ToolSchema::new(tool.input_schema.clone())));
+                    (tool.name.clone(), schema)
+                })
+                .collect();
+        }
+        *self.tool_names.lock().unwrap() = names;
+        *self.renamed_tools.lock().unwrap() = self.tool_overrides.apply(&mut tools, PLATFORM_TOOLS);
         Ok(tools)
     }
 
+    /// `request` as a call to the name its tool was listed with, when the model called it by
+    /// the name `GOOSE_TOOL_OVERRIDES` gave it, and with the mistakes in its arguments fixed, so
+    /// that it is approved, and logged, with the arguments the tool is called with
+    pub fn resolve_tool_request(&self, request: &ToolRequest) -> ToolRequest {
+        let mut request = request.clone();
+        if let Ok(tool_call) = &mut request.tool_call {
+            if let Some(listed) = self.renamed_tools.lock().unwrap().get(&tool_call.name) {
+                tool_call.name = listed.clone();
+            }
+            // Arguments that don't match are left for the call to refuse
+            if let Ok(arguments) = self.conform_arguments(tool_call) {
+                tool_call.arguments = arguments;
+            }
+        }
+        request
+    }
+
+    /// The arguments of `tool_call` with the common mistakes fixed, going by the schema of the
+    /// tool, or an error saying what is wrong with them
+    fn conform_arguments(&self, tool_call: &ToolCall) -> ToolResult<Value> {
+        let schema = self
+            .tool_schemas
+            .lock()
+            .unwrap()
+            .get(&tool_call.name)
+            .cloned();
+        match schema {
+            Some(schema) => schema
+                .conform(tool_call.arguments.clone())
+                .map_err(|errors| {
+                    ToolError::InvalidParameters(format!(
+                        "The arguments don't match the schema of {}, so it wasn't called:\n{}\nFix the arguments and call it again.",
+                        tool_call.name, errors
+                    ))
+                }),
+            None => Ok(tool_call.arguments.clone()),
+        }
+    }
+
+    /// Start the server of the extension `name` again when `error` came from it failing and it
+    /// doesn't answer a ping, returning whether it was restarted
+    async fn recover(
+        &self,
+        name: &str,
+        error: &ClientError,
+        client: &mut Box<dyn McpClientTrait>,
+    ) -> ExtensionResult<bool> {
+        let Some(config) = self.supervised.get(name) else {
+            return Ok(false);
+        };
+        if !supervisor::is_failure(error) || supervisor::is_alive(client.as_ref()).await {
+            return Ok(false);
+        }
+        tracing::warn!("The {} extension failed: {}; restarting it", name, error);
+        supervisor::restart(config, &self.sampler, client).await?;
+        Ok(true)
+    }
+
     /// Get client resources and their contents
     pub async fn get_resources(&self) -> ExtensionResult<Vec<ResourceItem>> {
         let mut result: Vec<ResourceItem> = Vec::new();
 
         for (name, client) in &self.clients {
-            let client_guard = client.lock().await;
+            let client_guard = client.read().await;
             let resources = client_guard.list_resources(None).await?;
 
             for resource in resources.resources {
@@ -312,34 +891,56 @@ impl Capabilities {
         prompt_template::render_global_file("plan.md", &context).expect("Prompt should render")
     }
 
-    /// Get the extension prompt including client instructions
-    pub async fn get_system_prompt(&self) -> String {
-        let mut context: HashMap<&str, Value> = HashMap::new();
-
-        let extensions_info: Vec<ExtensionInfo> = self
-            .clients
+    /// The extensions and their instructions, for the system prompt
+    pub(crate) fn extensions_info(&self) -> Vec<ExtensionInfo> {
+        self.clients
             .keys()
             .map(|name| {
                 let instructions = self.instructions.get(name).cloned().unwrap_or_default();
                 let has_resources = self.resource_capable_extensions.contains(name);
                 ExtensionInfo::new(name, &instructions, has_resources)
             })
-            .collect();
-        context.insert("extensions", serde_json::to_value(extensions_info).unwrap());
+            .collect()
+    }
+
+    /// Get the extension prompt including client instructions
+    ///
+    /// Fails when a recipe's or the session's system prompt can't be rendered.
+    pub async fn get_system_prompt(&self) -> Result<String> {
+        let mut context: HashMap<&str, Value> = HashMap::new();
+
+        context.insert(
+            "extensions",
+            serde_json::to_value(self.extensions_info()).unwrap(),
+        );
 
         let current_date_time = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
         context.insert("current_date_time", Value::String(current_date_time));
 
-        // Conditionally load the override prompt or the global system prompt
-        let base_prompt = if let Some(override_prompt) = &self.system_prompt_override {
+        // A recipe's prompt replaces the session's, which replaces the built-in one
+        let override_prompt = self
+            .recipe()
+            .and_then(|recipe| recipe.system_prompt.as_ref())
+            .or(self.system_prompt_override.as_ref());
+        let base_prompt = if let Some(override_prompt) = override_prompt {
             prompt_template::render_inline_once(override_prompt, &context)
-                .expect("Prompt should render")
+                .map_err(|e| anyhow::anyhow!("The system prompt can't be rendered: {}", e))?
         } else {
             prompt_template::render_global_file("system.md", &context)
                 .expect("Prompt should render")
         };
 
         let mut system_prompt_extensions = self.system_prompt_extensions.clone();
+        if let Some(instructions) = self
+            .persona
+            .as_ref()
+            .and_then(|persona| persona.instructions.clone())
+        {
+            system_prompt_extensions.push(instructions);
+        }
+        if let Some(recipe_prompt) = self.recipe().and_then(Recipe::system_prompt_additions) {
+            system_prompt_extensions.push(recipe_prompt);
+        }
         let config = Config::global();
         let goose_mode = config.get_param("GOOSE_MODE").unwrap_or("auto".to_string());
         if goose_mode == "chat" {
@@ -353,21 +954,28 @@ impl Capabilities {
         }
 
         if system_prompt_extensions.is_empty() {
-            base_prompt
+            Ok(base_prompt)
         } else {
-            format!(
+            Ok(format!(
                 "{}\n\n# Additional Instructions:\n\n{}",
                 base_prompt,
                 system_prompt_extensions.join("\n\n")
-            )
+            ))
         }
     }
 
     /// Find and return a reference to the appropriate client for a tool call
+    /// The extension whose tool `prefixed_name` is by the prefix naming convention, the
+    /// longest extension name it starts with followed by `__`, so `github__` isn't taken for `git`
     fn get_client_for_tool(&self, prefixed_name: &str) -> Option<(&str, McpClientBox)> {
         self.clients
             .iter()
-            .find(|(key, _)| prefixed_name.starts_with(*key))
+            .filter(|(key, _)| {
+                prefixed_name
+                    .strip_prefix(key.as_str())
+                    .is_some_and(|rest| rest.starts_with("__"))
+            })
+            .max_by_key(|(key, _)| key.len())
             .map(|(name, client)| (name.as_str(), Arc::clone(client)))
     }
 
@@ -436,7 +1044,7 @@ impl Capabilities {
             .get(extension_name)
             .ok_or(ToolError::InvalidParameters(error_msg))?;
 
-        let client_guard = client.lock().await;
+        let client_guard = client.read().await;
         let read_result = client_guard.read_resource(uri).await.map_err(|_| {
             ToolError::ExecutionError(format!("Could not read resource with uri: {}", uri))
         })?;
@@ -462,7 +1070,7 @@ impl Capabilities {
             ToolError::InvalidParameters(format!("Extension {} is not valid", extension_name))
         })?;
 
-        let client_guard = client.lock().await;
+        let client_guard = client.read().await;
         client_guard
             .list_resources(None)
             .await
@@ -534,34 +1142,122 @@ impl Capabilities {
         }
     }
 
-    /// Dispatch a single tool call to the appropriate client
-    #[instrument(skip(self, tool_call), fields(input, output))]
+    /// Dispatch a single tool call to the appropriate client, made without asking the user
+    ///
+    /// It is audited as allowed by a tool policy when one allows it, and otherwise as made
+    /// without asking.
     pub async fn dispatch_tool_call(&self, tool_call: ToolCall) -> ToolResult<Vec<Content>> {
-        let result = if tool_call.name == "platform__read_resource" {
-            // Check if the tool is read_resource and handle it separately
-            self.read_resource(tool_call.arguments.clone()).await
-        } else if tool_call.name == "platform__list_resources" {
-            self.list_resources(tool_call.arguments.clone()).await
+        let approval = match self.tool_policy().check(&tool_call) {
+            Some(PolicyAction::Allow) => Approval::Policy,
+            _ => Approval::Auto,
+        };
+        self.dispatch_approved_tool_call(tool_call, approval).await
+    }
+
+    /// Dispatch a single tool call that `approval` allowed, recording it in the audit log
+    ///
+    /// The tool policy and the approval rules are checked here too, so they hold whichever agent
+    /// made the call: calls the policy refuses aren't made, nor are calls the policy asks about or
+    /// an approval rule matches that the user didn't approve. In a dry run no call is made.
+    pub async fn dispatch_approved_tool_call(
+        &self,
+        mut tool_call: ToolCall,
+        approval: Approval,
+    ) -> ToolResult<Vec<Content>> {
+        // Audited with the arguments the tool is called with
+        if let Ok(arguments) = self.conform_arguments(&tool_call) {
+            tool_call.arguments = arguments;
+        }
+        let dry_run = Config::global()
+            .get_param::<String>("GOOSE_MODE")
+            .is_ok_and(|mode| mode == DRY_RUN_MODE);
+        if dry_run {
+            return Ok(simulated_result(&tool_call));
+        }
+        let approved_by_user = matches!(approval, Approval::User | Approval::Edited);
+        match self.tool_policy().check(&tool_call) {
+            Some(PolicyAction::Never) => {
+                self.audit_declined(&tool_call, Approval::Refused);
+                return Err(policy_refusal(&tool_call.name));
+            }
+            Some(PolicyAction::Ask) if !approved_by_user => {
+                self.audit_declined(&tool_call, Approval::Refused);
+                return Err(policy_approval_needed(&tool_call.name));
+            }
+            _ => {}
+        }
+        if !approved_by_user && ApprovalRules::from_config().check(&tool_call).is_some() {
+            self.audit_declined(&tool_call, Approval::Refused);
+            return Err(approval_needed(&tool_call.name));
+        }
+        let mut attempts = 1;
+        let Some(log) = self.audit_log() else {
+            return self.call_tool(tool_call, approval, &mut attempts).await;
+        };
+        // Recorded as it is made, so a call that never ends still shows, and its outcome added
+        // once it does
+        let record = AuditRecord::new(self.session_id.clone(), &tool_call, approval).running();
+        if let Err(e) = log.record(&record) {
+            tracing::warn!("Failed to audit the call to {}: {}", record.tool, e);
+        }
+        let started = std::time::Instant::now();
+        let result = self.call_tool(tool_call, approval, &mut attempts).await;
+        let record = record.finished(&result, started.elapsed(), attempts);
+        if let Err(e) = log.record(&record) {
+            tracing::warn!("Failed to audit the call to {}: {}", record.tool, e);
+        }
+        result
+    }
+
+    /// Call the tool, counting in `attempts` the times it is made again
+    #[instrument(skip(self, tool_call, attempts), fields(input, output))]
+    async fn call_tool(
+        &self,
+        tool_call: ToolCall,
+        approval: Approval,
+        attempts: &mut u32,
+    ) -> ToolResult<Vec<Content>> {
+        if !self.allows_platform_tool(&tool_call.name) {
+            return Err(recipe_refusal(&tool_call.name));
+        }
+        // Subagents run again in a replay, as their completions and tool calls are recorded
+        let result = if tool_call.name == SPAWN_SUBAGENT_TOOL {
+            run_subagent(self, tool_call.arguments.clone()).await
+        } else if tool_call.name == SPAWN_SUBAGENTS_TOOL {
+            run_subagents(self, tool_call.arguments.clone()).await
+        } else if let Some(recording) = &self.recording {
+            recording.tool_result(&tool_call)
         } else {
-            // Else, dispatch tool call based on the prefix naming convention
-            let (client_name, client) = self
-                .get_client_for_tool(&tool_call.name)
-                .ok_or_else(|| ToolError::NotFound(tool_call.name.clone()))?;
-
-            // rsplit returns the iterator in reverse, tool_name is then at 0
-            let tool_name = tool_call
-                .name
-                .strip_prefix(client_name)
-                .and_then(|s| s.strip_prefix("__"))
-                .ok_or_else(|| ToolError::NotFound(tool_call.name.clone()))?;
-
-            let client_guard = client.lock().await;
-
-            client_guard
-                .call_tool(tool_name, tool_call.clone().arguments)
-                .await
-                .map(|result| result.content)
-                .map_err(|e| ToolError::ExecutionError(e.to_string()))
+            let result = self
+                .call_extension_tool(&tool_call, approval, attempts)
+                .await;
+            if let Some(recorder) = &self.recorder {
+                recorder.record(&RecordedEvent::ToolResult {
+                    tool_call: tool_call.clone(),
+                    result: result.clone(),
+                });
+            }
+            result
+        };
+        // Recorded whole, so a replay cuts it in the same way
+        let result = match (result, &self.tool_output_limit) {
+            (Ok(mut contents), Some(limit)) if tool_call.name != READ_TOOL_OUTPUT_TOOL => {
+                let counter = self.token_counter.get_or_init(|| {
+                    TokenCounter::new(self.provider.get_model_config().tokenizer_name())
+                });
+                limit
+                    .apply(
+                        &tool_call.name,
+                        &mut contents,
+                        &self.tool_outputs,
+                        counter,
+                        self.provider.as_ref().as_ref(),
+                        self.session_id(),
+                    )
+                    .await;
+                Ok(contents)
+            }
+            (result, _) => result,
         };
 
         debug!(
@@ -572,6 +1268,174 @@ impl Capabilities {
         result
     }
 
+    /// Call a platform tool or an extension's tool
+    async fn call_extension_tool(
+        &self,
+        tool_call: &ToolCall,
+        approval: Approval,
+        attempts: &mut u32,
+    ) -> ToolResult<Vec<Content>> {
+        if tool_call.name == "platform__read_resource" {
+            // Check if the tool is read_resource and handle it separately
+            self.read_resource(tool_call.arguments.clone()).await
+        } else if tool_call.name == "platform__list_resources" {
+            self.list_resources(tool_call.arguments.clone()).await
+        } else if tool_call.name == SEARCH_KNOWLEDGE_TOOL {
+            search_knowledge(tool_call.arguments.clone()).await
+        } else if tool_call.name == SEARCH_CODE_TOOL {
+            let dir = match &self.working_dir {
+                Some(dir) => dir.clone(),
+                None => {
+                    std::env::current_dir().map_err(|e| ToolError::ExecutionError(e.to_string()))?
+                }
+            };
+            search_code(tool_call.arguments.clone(), &dir).await
+        } else if tool_call.name == FETCH_URL_TOOL {
+            fetch_url(tool_call.arguments.clone()).await
+        } else if tool_call.name == FINAL_OUTPUT_TOOL {
+            match self.final_output() {
+                Some(final_output) => final_output.submit(tool_call.arguments.clone()),
+                None => Err(ToolError::NotFound(tool_call.name.clone())),
+            }
+        } else if tool_call.name == READ_TOOL_OUTPUT_TOOL {
+            read_tool_output(&self.tool_outputs, tool_call.arguments.clone())
+        } else {
+            // Else, dispatch the tool call by the name the tool was listed with, or based on the
+            // prefix naming convention
+            let route = self
+                .tool_names
+                .lock()
+                .unwrap()
+                .route(&tool_call.name)
+                .cloned();
+            let (client_name, client, tool_name) = match &route {
+                Some(route) => {
+                    let (client_name, client) = self
+                        .clients
+                        .get_key_value(&route.extension)
+                        .ok_or_else(|| ToolError::NotFound(tool_call.name.clone()))?;
+                    (
+                        client_name.as_str(),
+                        Arc::clone(client),
+                        route.tool.as_str(),
+                    )
+                }
+                None => {
+                    let (client_name, client) = self
+                        .get_client_for_tool(&tool_call.name)
+                        .ok_or_else(|| ToolError::NotFound(tool_call.name.clone()))?;
+                    let tool_name = tool_call
+                        .name
+                        .strip_prefix(client_name)
+                        .and_then(|s| s.strip_prefix("__"))
+                        .ok_or_else(|| ToolError::NotFound(tool_call.name.clone()))?;
+                    (client_name, client, tool_name)
+                }
+            };
+            if !self.allows_tool(&format!("{}__{}", client_name, tool_name)) {
+                return Err(recipe_refusal(&tool_call.name));
+            }
+
+            let arguments = self.conform_arguments(tool_call)?;
+
+            // Calls to an extension that takes them one at a time wait for the one running
+            let _turn = match self.serial.get(client_name) {
+                Some(lock) => Some(lock.lock().await),
+                None => None,
+            };
+            let mut result = self
+                .call_tool_once(&client, tool_call, tool_name, &arguments)
+                .await?;
+
+            // A server that crashed or hung is started again, and the call made once more
+            let mut restarted = false;
+            if let Err(e) = &result {
+                restarted = {
+                    let mut client_guard = client.write().await;
+                    self.recover(client_name, e, &mut client_guard)
+                        .await
+                        .map_err(|restart_error| {
+                            ToolError::ExecutionError(format!(
+                                "{}, and the extension could not be restarted: {}",
+                                e, restart_error
+                            ))
+                        })?
+                };
+                if restarted {
+                    *attempts += 1;
+                    result = self
+                        .call_tool_once(&client, tool_call, tool_name, &arguments)
+                        .await?;
+                }
+            }
+
+            // A call that surely failed on the way to the tool is made again after a while,
+            // unless it was made again on a restarted server already
+            let retries = if restarted {
+                0
+            } else {
+                self.tool_retries
+                    .for_tool(&tool_call.name, approval == Approval::ReadOnly)
+            };
+            let mut attempt = 0;
+            while let Err(e) = &result {
+                if attempt >= retries || !tool_retries::is_transient(e) {
+                    break;
+                }
+                tracing::warn!(
+                    "Calling {} failed: {}; trying again, attempt {} of {}",
+                    tool_call.name,
+                    e,
+                    attempt + 1,
+                    retries
+                );
+                tokio::time::sleep(tool_retries::retry_delay(attempt)).await;
+                attempt += 1;
+                *attempts += 1;
+                result = self
+                    .call_tool_once(&client, tool_call, tool_name, &arguments)
+                    .await?;
+            }
+
+            result
+                .map(|result| result.content)
+                .map_err(|e| ToolError::ExecutionError(e.to_string()))
+        }
+    }
+
+    /// Call the extension's tool `tool_name` once, within its timeout, sharing the client with
+    /// the other calls running
+    async fn call_tool_once(
+        &self,
+        client: &McpClientBox,
+        tool_call: &ToolCall,
+        tool_name: &str,
+        arguments: &Value,
+    ) -> ToolResult<Result<CallToolResult, ClientError>> {
+        let client = client.read().await;
+        tool_timeouts::within(
+            &tool_call.name,
+            self.tool_timeouts.for_tool(&tool_call.name),
+            progress::call_tool(
+                client.as_ref(),
+                &tool_call.name,
+                tool_name,
+                arguments.clone(),
+                self.session_id(),
+            ),
+        )
+        .await
+    }
+
+    /// Run the tool calls of a turn, at most `GOOSE_TOOL_CONCURRENCY` at once, returning their
+    /// results in the order of the calls
+    pub async fn run_tool_calls<F: Future>(&self, calls: Vec<F>) -> Vec<F::Output> {
+        stream::iter(calls)
+            .buffered(self.tool_concurrency)
+            .collect()
+            .await
+    }
+
     pub async fn list_prompts_from_extension(
         &self,
         extension_name: &str,
@@ -580,7 +1444,7 @@ impl Capabilities {
             ToolError::InvalidParameters(format!("Extension {} is not valid", extension_name))
         })?;
 
-        let client_guard = client.lock().await;
+        let client_guard = client.read().await;
         client_guard
             .list_prompts(None)
             .await
@@ -646,7 +1510,7 @@ impl Capabilities {
             .get(extension_name)
             .ok_or_else(|| anyhow::anyhow!("Extension {} not found", extension_name))?;
 
-        let client_guard = client.lock().await;
+        let client_guard = client.read().await;
         client_guard
             .get_prompt(name, arguments)
             .await
@@ -654,13 +1518,22 @@ impl Capabilities {
     }
 }
 
+/// The error for a call to a tool the recipe being run doesn't allow
+fn recipe_refusal(tool_name: &str) -> ToolError {
+    ToolError::ExecutionError(format!(
+        "The recipe being run doesn't allow {}, only the tools you were given",
+        tool_name
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::message::Message;
+    use crate::agents::router::Classifier;
     use crate::model::ModelConfig;
     use crate::providers::base::{Provider, ProviderMetadata, ProviderUsage, Usage};
     use crate::providers::errors::ProviderError;
+    use crate::tool_output::OversizedOutput;
     use mcp_client::client::Error;
     use mcp_client::client::McpClientTrait;
     use mcp_core::protocol::{
@@ -731,6 +1604,14 @@ mod tests {
                     content: vec![],
                     is_error: None,
                 }),
+                "build" => Ok(CallToolResult {
+                    content: vec![Content::text(
+                        (1..=5000)
+                            .map(|i| format!("compiling crate {}\n", i))
+                            .collect::<String>(),
+                    )],
+                    is_error: None,
+                }),
                 _ => Err(Error::NotInitialized),
             }
         }
@@ -749,6 +1630,10 @@ mod tests {
         ) -> Result<GetPromptResult, Error> {
             Err(Error::NotInitialized)
         }
+
+        async fn ping(&self) -> Result<(), Error> {
+            Ok(())
+        }
     }
 
     #[test]
@@ -763,22 +1648,22 @@ mod tests {
         // Add some mock clients
         capabilities.clients.insert(
             normalize("test_client".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         capabilities.clients.insert(
             normalize("__client".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         capabilities.clients.insert(
             normalize("__cli__ent__".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         capabilities.clients.insert(
             normalize("client 🚀".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         // Test basic case
@@ -798,6 +1683,28 @@ mod tests {
         assert!(capabilities.get_client_for_tool("client___tool").is_some());
     }
 
+    #[test]
+    fn test_get_client_for_tool_with_overlapping_names() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider {
+            model_config: ModelConfig::new("test-model".to_string()),
+        }));
+        for name in ["git", "github"] {
+            capabilities.clients.insert(
+                name.to_string(),
+                Arc::new(RwLock::new(Box::new(MockClient {}))),
+            );
+        }
+
+        let client = |tool: &str| {
+            capabilities
+                .get_client_for_tool(tool)
+                .map(|(name, _)| name.to_string())
+        };
+        assert_eq!(client("github__create_issue").as_deref(), Some("github"));
+        assert_eq!(client("git__status").as_deref(), Some("git"));
+        assert_eq!(client("gitlab__status"), None);
+    }
+
     #[tokio::test]
     async fn test_dispatch_tool_call() {
         // test that dispatch_tool_call parses out the sanitized name correctly, and extracts
@@ -812,17 +1719,17 @@ mod tests {
         // Add some mock clients
         capabilities.clients.insert(
             normalize("test_client".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         capabilities.clients.insert(
             normalize("__cli__ent__".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         capabilities.clients.insert(
             normalize("client 🚀".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
         );
 
         // verify a normal tool call
@@ -890,4 +1797,243 @@ mod tests {
         let result = capabilities.dispatch_tool_call(invalid_tool_call).await;
         assert!(matches!(result.err().unwrap(), ToolError::NotFound(_)));
     }
+
+    #[test]
+    fn test_resolve_tool_request_conforms_arguments() {
+        let capabilities = Capabilities::new(Box::new(MockProvider {
+            model_config: ModelConfig::new("test-model".to_string()),
+        }));
+        capabilities.tool_schemas.lock().unwrap().insert(
+            "test__tool".to_string(),
+            Arc::new(ToolSchema::new(json!({
+                "type": "object",
+                "properties": {"line": {"type": "integer"}}
+            }))),
+        );
+
+        let request = |arguments: Value| ToolRequest {
+            id: "1".to_string(),
+            tool_call: Ok(ToolCall::new("test__tool", arguments)),
+        };
+        // The arguments are approved as the tool is called with them
+        let resolved = capabilities.resolve_tool_request(&request(json!({"line": "42"})));
+        assert_eq!(resolved.tool_call.unwrap().arguments, json!({"line": 42}));
+        // and left for the call to refuse when they can't be fixed
+        let resolved = capabilities.resolve_tool_request(&request(json!({"line": "x"})));
+        assert_eq!(resolved.tool_call.unwrap().arguments, json!({"line": "x"}));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_tool_output() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider {
+            model_config: ModelConfig::new("test-model".to_string()),
+        }));
+        capabilities
+            .set_tool_output_limit(Some(ToolOutputLimit::new(1000, OversizedOutput::Truncate)));
+        capabilities.clients.insert(
+            normalize("developer".to_string()),
+            Arc::new(RwLock::new(Box::new(MockClient {}))),
+        );
+        assert!(capabilities.limits_tool_outputs());
+        assert!(capabilities.tool_outputs.is_empty());
+
+        let tool_call = ToolCall {
+            name: "developer__build".to_string(),
+            arguments: json!({}),
+        };
+        let result = capabilities.dispatch_tool_call(tool_call).await.unwrap();
+        let Content::Text(text) = &result[0] else {
+            panic!("expected text");
+        };
+        assert!(text.text.starts_with("compiling crate 1\n"));
+        assert!(text.text.ends_with("compiling crate 5000\n"));
+        assert!(text
+            .text
+            .contains("The full output is kept as tool-output-1"));
+        assert!(text.text.len() < 10_000);
+        assert!(!capabilities.tool_outputs.is_empty());
+
+        let tool_call = ToolCall {
+            name: READ_TOOL_OUTPUT_TOOL.to_string(),
+            arguments: json!({"id": "tool-output-1", "pattern": "crate 2500$"}),
+        };
+        let result = capabilities.dispatch_tool_call(tool_call).await.unwrap();
+        let Content::Text(text) = &result[0] else {
+            panic!("expected text");
+        };
+        assert!(text.text.ends_with("\n2500: compiling crate 2500"));
+    }
+
+    #[tokio::test]
+    async fn test_run_tool_calls() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut capabilities = Capabilities::new(Box::new(MockProvider {
+            model_config: ModelConfig::new("test-model".to_string()),
+        }));
+        capabilities.tool_concurrency = 2;
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+        // The first calls take the longest, so they finish last
+        let calls = (0..5u64).map(|i| {
+            let (running, most_running) = (&running, &most_running);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50 * (5 - i))).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        });
+
+        let results = capabilities.run_tool_calls(calls.collect()).await;
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+        assert_eq!(most_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_system_prompt_overrides() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider {
+            model_config: ModelConfig::new("test-model".to_string()),
+        }));
+        capabilities.add_system_prompt_extension("Answer in French.".to_string());
+        let built_in = capabilities.get_system_prompt().await.unwrap();
+        assert!(built_in.contains("Answer in French."));
+
+        capabilities.set_system_prompt_override("You review code.".to_string());
+        let overridden = capabilities.get_system_prompt().await.unwrap();
+        assert!(overridden.starts_with("You review code."));
+        assert!(overridden.contains("Answer in French."));
+
+        // A recipe's system prompt takes over from the session's while it runs
+        let recipe = Recipe::parse("name: a\nprompt: Audit\nsystem_prompt: You audit code.");
+        capabilities.set_recipe(Some(recipe.unwrap()));
+        assert!(capabilities
+            .get_system_prompt()
+            .await
+            .unwrap()
+            .starts_with("You audit code."));
+        capabilities.set_recipe(None);
+        assert!(capabilities
+            .get_system_prompt()
+            .await
+            .unwrap()
+            .starts_with("You review code."));
+
+        // A recipe given for a reply lasts until the next reply sets its own
+        let recipe = Recipe::parse(
+            "name: a\nprompt: Audit\nsystem_prompt: You triage.\nresponse:\n  json_schema:\n    type: object",
+        );
+        capabilities.set_reply_recipe(Some(recipe.unwrap()));
+        assert!(capabilities
+            .get_system_prompt()
+            .await
+            .unwrap()
+            .starts_with("You triage."));
+        assert!(capabilities.final_output().is_some());
+        capabilities.set_reply_recipe(None);
+        assert!(capabilities
+            .get_system_prompt()
+            .await
+            .unwrap()
+            .starts_with("You review code."));
+        assert!(capabilities.final_output().is_none());
+
+        // A prompt that fails to render is an error, not a panic
+        capabilities.set_system_prompt_override("{{ 'a' + 1 }}".to_string());
+        assert!(capabilities.get_system_prompt().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recipe_limits_platform_tools() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider {
+            model_config: ModelConfig::new("test-model".to_string()),
+        }));
+        let recipe = Recipe::parse("name: a\nprompt: Audit\nextensions: [developer]");
+        capabilities.set_recipe(Some(recipe.unwrap()));
+        assert!(!capabilities.allows_platform_tool(FETCH_URL_TOOL));
+        assert!(capabilities.allows_platform_tool(FINAL_OUTPUT_TOOL));
+        assert!(capabilities.allows_platform_tool("developer__shell"));
+        let result = capabilities
+            .dispatch_tool_call(ToolCall::new(
+                FETCH_URL_TOOL,
+                json!({"url": "http://169.254.169.254/"}),
+            ))
+            .await;
+        assert!(matches!(result, Err(ToolError::ExecutionError(e)) if e.contains("doesn't allow")));
+
+        let recipe = Recipe::parse("name: a\nprompt: Audit\ntools: [platform__fetch_url]");
+        capabilities.set_recipe(Some(recipe.unwrap()));
+        assert!(capabilities.allows_platform_tool(FETCH_URL_TOOL));
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let mock = |model: &str| {
+            Box::new(MockProvider {
+                model_config: ModelConfig::new(model.to_string()),
+            })
+        };
+        let mut capabilities = Capabilities::new(mock("gpt-4o"));
+        let question = [Message::user().with_text("What does HTTP 418 mean?")];
+        assert_eq!(capabilities.route(&question).await, None);
+
+        capabilities.set_router(Some(ModelRouter::new(
+            mock("gpt-4o-mini"),
+            "openai".to_string(),
+            Classifier::Rules,
+        )));
+        let route = capabilities.route(&question).await.unwrap();
+        assert_eq!(route.kind, TaskKind::Simple);
+        assert_eq!(route.model, "gpt-4o-mini");
+        assert_eq!(route.provider.as_deref(), Some("openai"));
+        assert_eq!(
+            capabilities.provider().get_model_config().model_name,
+            "gpt-4o-mini"
+        );
+
+        let task = [Message::user().with_text("Fix the failing test")];
+        let route = capabilities.route(&task).await.unwrap();
+        assert_eq!(route.kind, TaskKind::Complex);
+        assert_eq!(route.model, "gpt-4o");
+        assert_eq!(route.provider, None);
+        assert_eq!(
+            capabilities.provider().get_model_config().model_name,
+            "gpt-4o"
+        );
+    }
+
+    #[test]
+    fn test_assign_role() {
+        let mock = |model: &str| {
+            Box::new(MockProvider {
+                model_config: ModelConfig::new(model.to_string()),
+            })
+        };
+        let mut capabilities = Capabilities::new(mock("gpt-4o"));
+        capabilities.set_worker(None);
+        let call = ToolCall::new("developer__shell", json!({"command": "ls"}));
+        let mut messages = vec![Message::user().with_text("List the files")];
+        assert_eq!(capabilities.assign_role(&messages), None);
+
+        capabilities.set_worker(Some(WorkerModel::new(
+            mock("gpt-4o-mini"),
+            "openai".to_string(),
+        )));
+        let assignment = capabilities.assign_role(&messages).unwrap();
+        assert_eq!(assignment.role, ModelRole::Lead);
+        assert_eq!(assignment.model, "gpt-4o");
+        assert_eq!(assignment.provider, None);
+
+        messages.push(Message::assistant().with_tool_request("1", Ok(call)));
+        messages.push(Message::user().with_tool_response("1", Ok(vec![Content::text("a.txt")])));
+        let assignment = capabilities.assign_role(&messages).unwrap();
+        assert_eq!(assignment.role, ModelRole::Worker);
+        assert_eq!(assignment.model, "gpt-4o-mini");
+        assert_eq!(assignment.provider.as_deref(), Some("openai"));
+        assert_eq!(
+            capabilities.provider().get_model_config().model_name,
+            "gpt-4o-mini"
+        );
+    }
 }
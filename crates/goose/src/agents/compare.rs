@@ -0,0 +1,210 @@
+//! Answering the same turn with several models, to weigh them against each other
+//!
+//! `GOOSE_COMPARE_MODELS` lists the models to compare as `provider/model` pairs, separated by
+//! commas, such as `openai/gpt-4o,anthropic/claude-3-7-sonnet-latest`. Each is sent the
+//! conversation with the agent's system prompt, with the memories and passages recalled for
+//! the turn, and the extensions' tools, all at once, and its answer comes back with how long it
+//! took, the tokens it used and what it cost, which are recorded for the session. The answers
+//! are only shown: tool calls in them aren't made, and none of them is added to the
+//! conversation.
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use mcp_core::Tool;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::capabilities::Capabilities;
+use crate::config::Config;
+use crate::knowledge;
+use crate::memory;
+use crate::message::Message;
+use crate::model::ModelConfig;
+use crate::providers::base::ProviderUsage;
+use crate::usage;
+
+/// A model to compare, with the provider it comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub provider: String,
+    pub model: String,
+}
+
+impl FromStr for Candidate {
+    type Err = anyhow::Error;
+
+    /// Parse `provider/model`, where the model can have slashes of its own
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().split_once('/') {
+            Some((provider, model)) if !provider.is_empty() && !model.is_empty() => Ok(Self {
+                provider: provider.to_string(),
+                model: model.to_string(),
+            }),
+            _ => Err(anyhow!(
+                "Expected a model to compare as provider/model, got '{}'",
+                s.trim()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.provider, self.model)
+    }
+}
+
+/// The models in GOOSE_COMPARE_MODELS, of which there have to be at least two
+pub fn candidates_from_config() -> Result<Vec<Candidate>> {
+    let models: String = Config::global()
+        .get_param("GOOSE_COMPARE_MODELS")
+        .map_err(|_| {
+            anyhow!("Set GOOSE_COMPARE_MODELS to the models to compare, such as openai/gpt-4o,anthropic/claude-3-7-sonnet-latest")
+        })?;
+    parse_candidates(&models)
+}
+
+fn parse_candidates(models: &str) -> Result<Vec<Candidate>> {
+    let candidates = models
+        .split(',')
+        .filter(|model| !model.trim().is_empty())
+        .map(Candidate::from_str)
+        .collect::<Result<Vec<_>>>()?;
+    if candidates.len() < 2 {
+        return Err(anyhow!(
+            "GOOSE_COMPARE_MODELS needs at least two models to compare, got {}",
+            candidates.len()
+        ));
+    }
+    Ok(candidates)
+}
+
+/// One model's answer to the turn
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub message: Message,
+    pub usage: ProviderUsage,
+    pub latency: Duration,
+}
+
+/// One model's result: its answer, or why it has none
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub candidate: Candidate,
+    pub answer: Result<Answer, String>,
+}
+
+/// Send the turn `messages` end with to each of `candidates`, with the system prompt and tools
+/// a reply of the agent with `capabilities` gives its model
+///
+/// The capabilities are only held while the prompt and tools are read, so the models are
+/// asked without holding up the agent.
+pub async fn compare_turn(
+    capabilities: &Mutex<Capabilities>,
+    messages: &[Message],
+    candidates: &[Candidate],
+) -> Result<Vec<Comparison>> {
+    let (mut system_prompt, tools, session_id) = {
+        let capabilities = capabilities.lock().await;
+        (
+            capabilities.get_system_prompt().await?,
+            capabilities.get_prefixed_tools().await?,
+            capabilities.session_id().map(str::to_string),
+        )
+    };
+    if let Some(memories) = memory::recall_prompt(messages).await {
+        system_prompt.push_str(&memories);
+    }
+    if let Some(passages) = knowledge::retrieval_prompt(messages).await {
+        system_prompt.push_str(&passages);
+    }
+    Ok(compare(
+        &system_prompt,
+        messages,
+        &tools,
+        candidates,
+        session_id.as_deref(),
+    )
+    .await)
+}
+
+/// Send the turn `messages` end with to each of `candidates` at once, returning their results
+/// in the same order
+///
+/// The usage of each answer is recorded like any other completion's, for `session_id`.
+pub async fn compare(
+    system_prompt: &str,
+    messages: &[Message],
+    tools: &[Tool],
+    candidates: &[Candidate],
+    session_id: Option<&str>,
+) -> Vec<Comparison> {
+    join_all(candidates.iter().map(|candidate| async move {
+        let answer = answer(candidate, system_prompt, messages, tools, session_id).await;
+        Comparison {
+            candidate: candidate.clone(),
+            answer,
+        }
+    }))
+    .await
+}
+
+async fn answer(
+    candidate: &Candidate,
+    system_prompt: &str,
+    messages: &[Message],
+    tools: &[Tool],
+    session_id: Option<&str>,
+) -> Result<Answer, String> {
+    let provider = crate::providers::create(
+        &candidate.provider,
+        ModelConfig::new(candidate.model.clone()),
+    )
+    .map_err(|e| format!("Failed to create the provider: {}", e))?;
+    let started = Instant::now();
+    let (message, usage) = provider
+        .complete(system_prompt, messages, tools)
+        .await
+        .map_err(|e| e.to_string())?;
+    let latency = started.elapsed();
+
+    usage::record(&candidate.provider, session_id.map(str::to_string), &usage);
+    Ok(Answer {
+        message,
+        usage,
+        latency,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_candidates() {
+        let candidates =
+            parse_candidates("openai/gpt-4o, openrouter/anthropic/claude-3.5-sonnet").unwrap();
+        assert_eq!(
+            candidates,
+            vec![
+                Candidate {
+                    provider: "openai".to_string(),
+                    model: "gpt-4o".to_string(),
+                },
+                Candidate {
+                    provider: "openrouter".to_string(),
+                    model: "anthropic/claude-3.5-sonnet".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            candidates[1].to_string(),
+            "openrouter/anthropic/claude-3.5-sonnet"
+        );
+
+        assert!(parse_candidates("openai/gpt-4o").is_err());
+        assert!(parse_candidates("openai/gpt-4o,gpt-4o-mini").is_err());
+        assert!(parse_candidates("openai/gpt-4o,/gpt-4o-mini").is_err());
+    }
+}
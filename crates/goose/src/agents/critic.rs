@@ -0,0 +1,235 @@
+//! An optional reviewer pass over the agent's answers before they are final
+//!
+//! With `GOOSE_CRITIC=true`, each time the agent replies without calling tools, a critic reads
+//! the user's request and what the agent did for it, and either approves the answer or says
+//! what is wrong with it. The critique is sent back to the agent, which revises its answer, up
+//! to `GOOSE_CRITIC_MAX_ITERATIONS` times a turn. The critic is the agent's own model unless
+//! `GOOSE_CRITIC_MODEL` names another, from `GOOSE_CRITIC_PROVIDER` or else `GOOSE_PROVIDER`.
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::compaction::{starts_turn, transcript_entry};
+use crate::config::Config;
+use crate::message::Message;
+use crate::model::ModelConfig;
+use crate::providers::base::{Provider, ProviderUsage};
+
+const DEFAULT_MAX_ITERATIONS: usize = 2;
+
+/// What the critic replies with when it has nothing to add
+const APPROVED: &str = "APPROVED";
+
+const SYSTEM_PROMPT: &str = "You review the work of an AI agent that uses tools, before its \
+    answer is given to the user. You are strict about real problems and don't nitpick.";
+
+const REVIEW_INSTRUCTIONS: &str = "Below is a request from the user and what the agent did for \
+    it, ending with the agent's answer. Check the answer and any changes the agent made against \
+    the request: is anything missing, wrong or unverified, are there bugs in the changes, and \
+    does the answer claim anything the work doesn't show? If the answer is complete and \
+    correct, reply with only APPROVED. Otherwise reply with the problems to fix, most important \
+    first, as a short list.";
+
+/// The critic's verdict on an answer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Review {
+    Approved,
+    /// What needs to change before the answer is final
+    Revise(String),
+}
+
+pub struct Critic {
+    /// The provider to critique with and its name, or the agent's own when None
+    provider: Option<(String, Arc<dyn Provider>)>,
+    /// The most critiques the agent revises its answer for in a turn
+    pub max_iterations: usize,
+}
+
+impl Critic {
+    pub fn new(max_iterations: usize) -> Self {
+        Self {
+            provider: None,
+            max_iterations,
+        }
+    }
+
+    /// Critique with another provider than the agent's own
+    pub fn with_provider<S: Into<String>>(mut self, name: S, provider: Arc<dyn Provider>) -> Self {
+        self.provider = Some((name.into(), provider));
+        self
+    }
+
+    /// The name of the provider the critic has of its own, if any, for recording its usage
+    pub fn provider_name(&self) -> Option<&str> {
+        self.provider.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// Read the critic settings, returning None unless the critic is enabled
+    ///
+    /// - GOOSE_CRITIC: set to true to enable
+    /// - GOOSE_CRITIC_MAX_ITERATIONS: the most revisions a turn (default 2)
+    /// - GOOSE_CRITIC_MODEL / GOOSE_CRITIC_PROVIDER: a second model to critique with
+    pub fn from_config() -> Option<Self> {
+        let config = Config::global();
+        if !config.get_param::<bool>("GOOSE_CRITIC").unwrap_or(false) {
+            return None;
+        }
+        let max_iterations = config
+            .get_param::<usize>("GOOSE_CRITIC_MAX_ITERATIONS")
+            .unwrap_or(DEFAULT_MAX_ITERATIONS);
+        let critic = Self::new(max_iterations);
+        let Ok(model) = config.get_param::<String>("GOOSE_CRITIC_MODEL") else {
+            return Some(critic);
+        };
+        let Ok(name) = config
+            .get_param::<String>("GOOSE_CRITIC_PROVIDER")
+            .or_else(|_| config.get_param("GOOSE_PROVIDER"))
+        else {
+            return Some(critic);
+        };
+        match crate::providers::create(&name, ModelConfig::new(model)) {
+            Ok(provider) => {
+                Some(critic.with_provider(name, Arc::from(provider as Box<dyn Provider>)))
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to create the critic's provider, using the agent's: {}",
+                    e
+                );
+                Some(critic)
+            }
+        }
+    }
+
+    /// Review `draft`, the agent's answer to the last turn of `messages`, with the usage of the
+    /// review
+    ///
+    /// `provider` is the agent's own, used unless the critic has another.
+    pub async fn review(
+        &self,
+        provider: &dyn Provider,
+        messages: &[Message],
+        draft: &Message,
+    ) -> Result<(Review, ProviderUsage)> {
+        let provider = match &self.provider {
+            Some((_, provider)) => provider.as_ref(),
+            None => provider,
+        };
+        let turn_start = messages.iter().rposition(starts_turn).unwrap_or(0);
+        let transcript: String = messages[turn_start..]
+            .iter()
+            .chain(std::iter::once(draft))
+            .map(transcript_entry)
+            .collect();
+        let request = format!("{}\n\n{}", REVIEW_INSTRUCTIONS, transcript);
+
+        let (response, usage) = provider
+            .complete(SYSTEM_PROMPT, &[Message::user().with_text(request)], &[])
+            .await?;
+        let critique = response.as_concat_text();
+        let critique = critique.trim();
+        if critique.is_empty() {
+            return Err(anyhow!("The critic returned an empty review"));
+        }
+        if critique.trim_matches(|c: char| !c.is_alphabetic()) == APPROVED {
+            return Ok((Review::Approved, usage));
+        }
+        Ok((Review::Revise(critique.to_string()), usage))
+    }
+}
+
+/// The note asking the agent to revise its answer for a critique
+pub fn revision_request(critique: &str) -> Message {
+    Message::agent_note(
+        "critique",
+        format!(
+        "A reviewer checked your answer before it reaches the user and found these problems:\n\n\
+        {}\n\nFix them, with tools if needed, then give your revised answer in full. If a point \
+        is mistaken, say why briefly instead.",
+            critique
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::{ProviderMetadata, Usage};
+    use crate::providers::errors::ProviderError;
+    use mcp_core::tool::Tool;
+    use std::sync::Mutex;
+
+    /// Replies with the given reviews in turn, recording the requests it gets
+    struct ReviewingProvider {
+        reviews: Mutex<Vec<&'static str>>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for ReviewingProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            ModelConfig::new("critic".to_string())
+        }
+
+        async fn complete(
+            &self,
+            _system: &str,
+            messages: &[Message],
+            _tools: &[Tool],
+        ) -> Result<(Message, ProviderUsage), ProviderError> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push(messages[0].as_concat_text());
+            let review = self.reviews.lock().unwrap().remove(0);
+            Ok((
+                Message::assistant().with_text(review),
+                ProviderUsage::new("critic".to_string(), Usage::default()),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_review() -> Result<()> {
+        let provider = ReviewingProvider {
+            reviews: Mutex::new(vec![
+                "- The tests for parse_args weren't run",
+                "**APPROVED**",
+                "",
+            ]),
+            requests: Mutex::new(vec![]),
+        };
+        let messages = vec![
+            Message::user().with_text("Rename the config module"),
+            Message::assistant().with_text("Done"),
+            Message::user().with_text("Now add a flag to parse_args"),
+        ];
+        let draft = Message::assistant().with_text("I added --verbose");
+        let critic = Critic::new(2);
+
+        assert_eq!(
+            critic.review(&provider, &messages, &draft).await?.0,
+            Review::Revise("- The tests for parse_args weren't run".to_string())
+        );
+        assert_eq!(
+            critic.review(&provider, &messages, &draft).await?.0,
+            Review::Approved
+        );
+        assert!(critic.review(&provider, &messages, &draft).await.is_err());
+
+        // Only the last turn is reviewed
+        let request = &provider.requests.lock().unwrap()[0];
+        assert!(request.contains("user: Now add a flag to parse_args"));
+        assert!(request.contains("assistant: I added --verbose"));
+        assert!(!request.contains("Rename the config module"));
+
+        // The revision request is the agent's note, not a turn of the user's
+        let note = revision_request("- The tests for parse_args weren't run");
+        assert!(note.is_agent_note());
+        assert!(!starts_turn(&note));
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+//! A mode that shows what goose would do, without letting it do anything
+//!
+//! With `GOOSE_MODE=dry_run` the agent works through the task as usual, but its tool calls are
+//! recorded instead of made. Each call is logged, shown like any other, and answered with a
+//! result saying it wasn't made, so the model plans the rest of the task from there and the
+//! session ends up with the calls goose would have made, for review.
+use mcp_core::tool::ToolCall;
+use mcp_core::Content;
+
+/// The value of `GOOSE_MODE` for dry runs
+pub const DRY_RUN_MODE: &str = "dry_run";
+
+/// Record a tool call in a dry run, returning the result the model gets in place of its output
+pub fn simulated_result(tool_call: &ToolCall) -> Vec<Content> {
+    tracing::info!(
+        tool = %tool_call.name,
+        arguments = %tool_call.arguments,
+        "Dry run, recorded a tool call without making it"
+    );
+    vec![Content::text(format!(
+        "Not executed (dry-run): goose is in dry-run mode, which records the call to {} with \
+        these arguments without making it. There is no output. Carry on with the rest of the \
+        task as if the call had succeeded, without making up what it would have returned.",
+        tool_call.name
+    ))]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_simulated_result() {
+        let tool_call = ToolCall::new("developer__shell", json!({"command": "rm -rf build"}));
+        let result = simulated_result(&tool_call);
+        let text = result[0].as_text().unwrap();
+        assert!(text.starts_with("Not executed (dry-run)"));
+        assert!(text.contains("developer__shell"));
+    }
+}
@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 
 use mcp_client::client::Error as ClientError;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::warn;
 use utoipa::ToSchema;
 
 use crate::config;
-use crate::config::extensions::name_to_key;
+use crate::config::extensions::{headers_secret, name_to_key};
 
 /// Errors from Extension operation
 #[derive(Error, Debug)]
@@ -26,7 +27,7 @@ pub enum ExtensionError {
 
 pub type ExtensionResult<T> = Result<T, ExtensionError>;
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default, ToSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default, ToSchema, JsonSchema)]
 pub struct Envs {
     /// A map of environment variables to set, e.g. API_KEY -> some_secret, HOST -> host
     #[serde(default)]
@@ -113,7 +114,7 @@ impl Envs {
 }
 
 /// Represents the different types of MCP extensions that can be added to the manager
-#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, JsonSchema)]
 #[serde(tag = "type")]
 pub enum ExtensionConfig {
     /// Server-sent events client with a URI endpoint
@@ -124,11 +125,28 @@ pub enum ExtensionConfig {
         uri: String,
         #[serde(default)]
         envs: Envs,
+        /// Headers sent with each request to the server, such as `Authorization`
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
         description: Option<String>,
         // NOTE: set timeout to be optional for compatibility.
         // However, new configurations should include this field.
         timeout: Option<u64>,
     },
+    /// Streamable HTTP client with the URI of the server's single MCP endpoint
+    #[serde(rename = "streamable_http")]
+    StreamableHttp {
+        /// The name used to identify this extension
+        name: String,
+        uri: String,
+        #[serde(default)]
+        envs: Envs,
+        /// Headers sent with each request to the server, such as `Authorization`
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+        description: Option<String>,
+        timeout: Option<u64>,
+    },
     /// Standard I/O client with command and arguments
     #[serde(rename = "stdio")]
     Stdio {
@@ -167,6 +185,23 @@ impl ExtensionConfig {
             name: name.into(),
             uri: uri.into(),
             envs: Envs::default(),
+            headers: HashMap::new(),
+            description: Some(description.into()),
+            timeout: Some(timeout.into()),
+        }
+    }
+
+    pub fn streamable_http<S: Into<String>, T: Into<u64>>(
+        name: S,
+        uri: S,
+        description: S,
+        timeout: T,
+    ) -> Self {
+        Self::StreamableHttp {
+            name: name.into(),
+            uri: uri.into(),
+            envs: Envs::default(),
+            headers: HashMap::new(),
             description: Some(description.into()),
             timeout: Some(timeout.into()),
         }
@@ -218,10 +253,26 @@ impl ExtensionConfig {
         name_to_key(&name)
     }
 
+    /// The headers sent to a remote extension's server: those in its config, and those kept in
+    /// the keyring under [`headers_secret`], which take their place
+    pub fn request_headers(&self) -> HashMap<String, String> {
+        let mut headers = match self {
+            Self::Sse { headers, .. } | Self::StreamableHttp { headers, .. } => headers.clone(),
+            Self::Stdio { .. } | Self::Builtin { .. } => return HashMap::new(),
+        };
+        if let Ok(secret) = config::Config::global()
+            .get_secret::<HashMap<String, String>>(&headers_secret(&self.key()))
+        {
+            headers.extend(secret);
+        }
+        headers
+    }
+
     /// Get the extension name regardless of variant
     pub fn name(&self) -> String {
         match self {
             Self::Sse { name, .. } => name,
+            Self::StreamableHttp { name, .. } => name,
             Self::Stdio { name, .. } => name,
             Self::Builtin { name, .. } => name,
         }
@@ -233,6 +284,9 @@ impl std::fmt::Display for ExtensionConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ExtensionConfig::Sse { name, uri, .. } => write!(f, "SSE({}: {})", name, uri),
+            ExtensionConfig::StreamableHttp { name, uri, .. } => {
+                write!(f, "StreamableHttp({}: {})", name, uri)
+            }
             ExtensionConfig::Stdio {
                 name, cmd, args, ..
             } => {
@@ -0,0 +1,348 @@
+//! Answers as JSON that matches a schema, for callers that read the result of a run
+//!
+//! A recipe's `response.json_schema`, `goose run --response-schema` or a caller of the agent
+//! can ask for the answer of each reply as a value of a JSON schema. The agent is then given
+//! the `platform__final_output` tool, and finishes by calling it with the value. A value that
+//! doesn't match the schema is refused with what is wrong with it, so the agent can fix it and
+//! call again. An agent that answers without calling the tool has its answer taken if it is
+//! JSON that matches, and is otherwise asked for the value again, up to [`MAX_REPAIRS`] times
+//! a reply. In chat mode, where tool calls are skipped, it isn't asked again.
+use anyhow::{anyhow, Result};
+use indoc::{formatdoc, indoc};
+use mcp_core::role::Role;
+use mcp_core::{Content, Tool, ToolError, ToolResult};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::message::Message;
+
+pub const FINAL_OUTPUT_TOOL: &str = "platform__final_output";
+
+/// The most times a reply is sent back for a final output that matches
+pub const MAX_REPAIRS: usize = 3;
+
+/// The most times a reply is sent back in `goose_mode`: none in chat mode, where the call it
+/// asks for would be skipped
+pub fn max_repairs(goose_mode: &str) -> usize {
+    if goose_mode == "chat" {
+        0
+    } else {
+        MAX_REPAIRS
+    }
+}
+
+/// The most problems with a value listed back to the agent
+const MAX_ERRORS: usize = 10;
+
+/// The schema the answer has to match, and the value given for it
+pub struct FinalOutput {
+    schema: Value,
+    validator: jsonschema::Validator,
+    output: Mutex<Option<Value>>,
+}
+
+impl FinalOutput {
+    pub fn new(schema: Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| anyhow!("The response schema is invalid: {}", e))?;
+        Ok(Self {
+            schema,
+            validator,
+            output: Mutex::new(None),
+        })
+    }
+
+    /// The value given for the latest reply, if it gave one
+    pub fn output(&self) -> Option<Value> {
+        self.output.lock().unwrap().clone()
+    }
+
+    /// Forget the value, before a reply that has to give its own
+    pub fn clear(&self) {
+        *self.output.lock().unwrap() = None;
+    }
+
+    pub fn tool(&self) -> Tool {
+        Tool::new(
+            FINAL_OUTPUT_TOOL.to_string(),
+            indoc! {"
+                Give the result of the task as a value matching the schema of `output`, for the
+                program that asked for it. Call this once you are done, as the last thing you
+                do. If the value doesn't match, you get back what is wrong with it: fix it and
+                call again. After it is accepted, end with a one line summary, without
+                repeating the value.
+            "}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["output"],
+                "properties": {
+                    "output": rebase_refs(self.schema.clone(), "#/properties/output"),
+                }
+            }),
+        )
+    }
+
+    /// The problems with `value`, one a line with where they are, if it doesn't match
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(value)
+            .take(MAX_ERRORS)
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                if path.is_empty() {
+                    format!("- {}", error)
+                } else {
+                    format!("- at {}: {}", path, error)
+                }
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Run a [`FINAL_OUTPUT_TOOL`] call, keeping the value if it matches
+    pub fn submit(&self, arguments: Value) -> ToolResult<Vec<Content>> {
+        let value = arguments.get("output").cloned().ok_or_else(|| {
+            ToolError::InvalidParameters("The value has to be given as output".to_string())
+        })?;
+        self.check(&value).map_err(|errors| {
+            ToolError::InvalidParameters(format!(
+                "The output doesn't match the schema:\n{}",
+                errors
+            ))
+        })?;
+        *self.output.lock().unwrap() = Some(value);
+        Ok(vec![Content::text(
+            "The output is accepted. End with a one line summary.",
+        )])
+    }
+
+    /// What to ask the agent, if its `answer` ends a reply without a value that matches
+    ///
+    /// An answer that is itself JSON matching the schema, alone or in a code block, is taken
+    /// as the value.
+    pub fn repair_request(&self, answer: &Message) -> Option<Message> {
+        if self.output().is_some() {
+            return None;
+        }
+        let problem = match parse_json(&answer.as_concat_text()) {
+            Some(value) => match self.check(&value) {
+                Ok(()) => {
+                    *self.output.lock().unwrap() = Some(value);
+                    return None;
+                }
+                Err(errors) => format!(
+                    "The JSON in your answer doesn't match the schema:\n{}",
+                    errors
+                ),
+            },
+            None => format!("You haven't called {} yet.", FINAL_OUTPUT_TOOL),
+        };
+        Some(Message::agent_note(
+            "final_output",
+            formatdoc! {"
+                {problem}
+
+                This task has to end with its result as a value of the schema. Call {tool} with
+                the value now, completing the task first if it isn't done.",
+                tool = FINAL_OUTPUT_TOOL,
+            },
+        ))
+    }
+
+    /// The value the reply made of `messages` gave, read from the messages rather than kept,
+    /// for callers that don't share the agent's: the output of its last accepted call to the
+    /// tool, or else its answer if that is a matching value
+    pub fn given_in(&self, messages: &[Message]) -> Option<Value> {
+        let mut submitted = HashMap::new();
+        let mut given = None;
+        for content in messages.iter().flat_map(|message| &message.content) {
+            if let Some(request) = content.as_tool_request() {
+                if let Ok(tool_call) = &request.tool_call {
+                    if tool_call.name == FINAL_OUTPUT_TOOL {
+                        submitted.insert(request.id.clone(), tool_call.arguments.get("output"));
+                    }
+                }
+            } else if let Some(response) = content.as_tool_response() {
+                if let Some(Some(value)) = submitted.remove(&response.id) {
+                    if response.tool_result.is_ok() && self.check(value).is_ok() {
+                        given = Some(value.clone());
+                    }
+                }
+            }
+        }
+        given.or_else(|| {
+            let answer = messages
+                .iter()
+                .rev()
+                .find(|message| message.role == Role::Assistant)?;
+            parse_json(&answer.as_concat_text()).filter(|value| self.check(value).is_ok())
+        })
+    }
+}
+
+/// `schema` with its local `$ref`s pointing under `base`, for when it is nested there
+fn rebase_refs(mut schema: Value, base: &str) -> Value {
+    match &mut schema {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    Value::String(reference) if key == "$ref" && reference.starts_with('#') => {
+                        *reference = format!("{}{}", base, &reference[1..]);
+                    }
+                    _ => *value = rebase_refs(value.take(), base),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                *item = rebase_refs(item.take(), base);
+            }
+        }
+        _ => {}
+    }
+    schema
+}
+
+/// The JSON an answer is made of, on its own or in a fenced code block
+fn parse_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+    let start = text.find("```")?;
+    let block = &text[start + 3..];
+    let block = block.strip_prefix("json").unwrap_or(block);
+    let end = block.find("```")?;
+    serde_json::from_str(block[..end].trim()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn final_output() -> FinalOutput {
+        FinalOutput::new(json!({
+            "type": "object",
+            "required": ["passed", "failures"],
+            "properties": {
+                "passed": {"type": "boolean"},
+                "failures": {"type": "array", "items": {"type": "string"}}
+            },
+            "additionalProperties": false
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        let error = FinalOutput::new(json!({"type": "nope"})).err().unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("The response schema is invalid"));
+    }
+
+    #[test]
+    fn test_submit() {
+        let final_output = final_output();
+        let refused = final_output.submit(json!({"output": {"passed": "yes", "failures": [1]}}));
+        let Err(ToolError::InvalidParameters(message)) = refused else {
+            panic!("expected the output to be refused");
+        };
+        assert!(message.starts_with("The output doesn't match the schema:\n- at /failures/0: "));
+        assert!(message.contains("- at /passed: "));
+        assert_eq!(final_output.output(), None);
+
+        let value = json!({"passed": false, "failures": ["test_login"]});
+        final_output.submit(json!({"output": value})).unwrap();
+        assert_eq!(final_output.output(), Some(value));
+        final_output.clear();
+        assert_eq!(final_output.output(), None);
+    }
+
+    #[test]
+    fn test_repair_request() {
+        let final_output = final_output();
+        let request = final_output
+            .repair_request(&Message::assistant().with_text("All tests pass."))
+            .unwrap();
+        assert!(request.is_agent_note());
+        assert!(request
+            .as_concat_text()
+            .starts_with("You haven't called platform__final_output yet."));
+
+        let request = final_output
+            .repair_request(&Message::assistant().with_text("```json\n{\"passed\": true}\n```"))
+            .unwrap();
+        assert!(request
+            .as_concat_text()
+            .starts_with("The JSON in your answer doesn't match the schema:\n- "));
+
+        let answer = "Done:\n```json\n{\"passed\": true, \"failures\": []}\n```";
+        assert!(final_output
+            .repair_request(&Message::assistant().with_text(answer))
+            .is_none());
+        assert_eq!(
+            final_output.output(),
+            Some(json!({"passed": true, "failures": []}))
+        );
+        // Once there is a value, the answer can be anything
+        assert!(final_output
+            .repair_request(&Message::assistant().with_text("Done"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_tool_resolves_refs() {
+        let final_output = FinalOutput::new(json!({
+            "type": "object",
+            "properties": {"files": {"type": "array", "items": {"$ref": "#/$defs/file"}}},
+            "$defs": {"file": {"type": "object", "required": ["path"]}}
+        }))
+        .unwrap();
+        let schema = final_output.tool().input_schema;
+        assert_eq!(
+            schema["properties"]["output"]["properties"]["files"]["items"]["$ref"],
+            "#/properties/output/$defs/file"
+        );
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        assert!(validator.is_valid(&json!({"output": {"files": [{"path": "a"}]}})));
+        assert!(!validator.is_valid(&json!({"output": {"files": [{}]}})));
+    }
+
+    #[test]
+    fn test_given_in() {
+        let final_output = final_output();
+        let call = |id: &str, output: Value| {
+            Message::assistant().with_tool_request(
+                id,
+                Ok(mcp_core::tool::ToolCall::new(
+                    FINAL_OUTPUT_TOOL,
+                    json!({"output": output}),
+                )),
+            )
+        };
+        let value = json!({"passed": true, "failures": []});
+        let messages = vec![
+            call("1", json!({"passed": "yes"})),
+            Message::user().with_tool_response("1", Err(ToolError::InvalidParameters("no".into()))),
+            call("2", value.clone()),
+            Message::user().with_tool_response("2", Ok(vec![])),
+            Message::assistant().with_text("Done"),
+        ];
+        assert_eq!(final_output.given_in(&messages), Some(value));
+        assert_eq!(final_output.given_in(&messages[..2]), None);
+
+        let answer = Message::assistant().with_text("{\"passed\": false, \"failures\": [\"a\"]}");
+        assert_eq!(
+            final_output.given_in(&[answer]),
+            Some(json!({"passed": false, "failures": ["a"]}))
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Hard limits on a run of the agent, so a runaway loop can't go on calling a paid API
+//!
+//! A run is everything the agent does for one reply: its completions and the tool calls in
+//! between, until it answers without calling tools. The limits are all optional:
+//! - GOOSE_MAX_TURNS, the most completions in a run
+//! - GOOSE_MAX_TOOL_CALLS, the most tool calls in a run
+//! - GOOSE_MAX_RUN_SECONDS, the most time a run can take
+//!
+//! The limits are checked before each completion, tool calls past the limit on tool calls
+//! aren't made, and completions and tool calls still running when the time is up are cancelled.
+//! A run that trips a limit ends with a [`stopped`] message saying which, that clients can tell
+//! apart from an answer with [`stop_reason`].
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use mcp_core::ToolError;
+
+use crate::config::Config;
+use crate::message::{Message, ToolRequest};
+
+/// The metadata key of the message ending a run stopped by a limit, with the reason as its value
+pub const RUN_STOPPED: &str = "goose_run_stopped";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunLimits {
+    pub max_turns: Option<usize>,
+    pub max_tool_calls: Option<usize>,
+    pub max_duration: Option<Duration>,
+}
+
+impl RunLimits {
+    pub fn from_config() -> Self {
+        let config = Config::global();
+        Self {
+            max_turns: config.get_param("GOOSE_MAX_TURNS").ok(),
+            max_tool_calls: config.get_param("GOOSE_MAX_TOOL_CALLS").ok(),
+            max_duration: config
+                .get_param::<u64>("GOOSE_MAX_RUN_SECONDS")
+                .ok()
+                .map(Duration::from_secs),
+        }
+    }
+
+    /// The limit the run has reached, if any, after `turns` completions and `tool_calls` tool
+    /// calls in `elapsed`
+    pub fn check(&self, turns: usize, tool_calls: usize, elapsed: Duration) -> Option<String> {
+        if let Some(max) = self.max_turns.filter(|&max| turns >= max) {
+            return Some(format!("the run took {turns} turns, the limit is {max}"));
+        }
+        if let Some(max) = self.max_tool_calls.filter(|&max| tool_calls >= max) {
+            return Some(format!(
+                "the run made {tool_calls} tool calls, the limit is {max}"
+            ));
+        }
+        if let Some(max) = self.max_duration.filter(|&max| elapsed >= max) {
+            return Some(time_limit_reason(max));
+        }
+        None
+    }
+
+    /// The time left in the run, if it has a time limit
+    pub fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        self.max_duration.map(|max| max.saturating_sub(elapsed))
+    }
+
+    /// How many more tool calls the run can make after `tool_calls`, if it has a limit on them
+    pub fn tool_calls_left(&self, tool_calls: usize) -> Option<usize> {
+        self.max_tool_calls
+            .map(|max| max.saturating_sub(tool_calls))
+    }
+
+    /// Run `future` for the time left in a run started at `started`, or the reason it was
+    /// stopped if the time ran out first
+    pub async fn within<F: Future>(
+        &self,
+        started: Instant,
+        future: F,
+    ) -> Result<F::Output, String> {
+        match self.max_duration.zip(self.remaining(started.elapsed())) {
+            Some((max, remaining)) => tokio::time::timeout(remaining, future)
+                .await
+                .map_err(|_| time_limit_reason(max)),
+            None => Ok(future.await),
+        }
+    }
+}
+
+/// Why a run was stopped for its time limit
+pub fn time_limit_reason(max_duration: Duration) -> String {
+    format!(
+        "the run took longer than the limit of {}s",
+        max_duration.as_secs()
+    )
+}
+
+/// The last message of a run stopped by a limit
+pub fn stopped(reason: &str) -> Message {
+    Message::assistant()
+        .with_text(format!(
+            "Stopped because a limit on the run was reached: {reason}."
+        ))
+        .with_metadata(RUN_STOPPED, reason)
+}
+
+/// Why the run was stopped, if `message` is the one that ended a run stopped by a limit
+pub fn stop_reason(message: &Message) -> Option<&str> {
+    message
+        .metadata
+        .get(RUN_STOPPED)
+        .and_then(|reason| reason.as_str())
+}
+
+/// The error for a tool call that wasn't made, or didn't finish, because the run was stopped
+pub fn stopped_call(reason: &str) -> ToolError {
+    ToolError::ExecutionError(format!(
+        "The tool call was stopped because a limit on the run was reached: {reason}"
+    ))
+}
+
+/// `response` with the requests it has no result for answered as stopped
+pub fn answer_stopped(mut response: Message, requests: &[ToolRequest], reason: &str) -> Message {
+    for request in requests {
+        let answered = response.content.iter().any(|content| {
+            content
+                .as_tool_response()
+                .is_some_and(|answer| answer.id == request.id)
+        });
+        if !answered {
+            response = response.with_tool_response(request.id.clone(), Err(stopped_call(reason)));
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(RunLimits::default().check(1000, 1000, minute * 1000), None);
+
+        let limits = RunLimits {
+            max_turns: Some(20),
+            max_tool_calls: Some(50),
+            max_duration: Some(minute * 10),
+        };
+        assert_eq!(limits.check(19, 49, minute * 9), None);
+        assert_eq!(
+            limits.check(20, 0, minute),
+            Some("the run took 20 turns, the limit is 20".to_string())
+        );
+        assert_eq!(
+            limits.check(5, 52, minute),
+            Some("the run made 52 tool calls, the limit is 50".to_string())
+        );
+        assert_eq!(
+            limits.check(5, 5, minute * 10),
+            Some("the run took longer than the limit of 600s".to_string())
+        );
+
+        assert_eq!(limits.remaining(minute * 4), Some(minute * 6));
+        assert_eq!(limits.remaining(minute * 11), Some(Duration::ZERO));
+        assert_eq!(RunLimits::default().remaining(minute), None);
+
+        assert_eq!(limits.tool_calls_left(48), Some(2));
+        assert_eq!(limits.tool_calls_left(52), Some(0));
+        assert_eq!(RunLimits::default().tool_calls_left(52), None);
+    }
+
+    #[tokio::test]
+    async fn test_within() {
+        let limits = RunLimits {
+            max_duration: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        assert_eq!(limits.within(started, async { 1 }).await, Ok(1));
+        let slow = tokio::time::sleep(Duration::from_secs(10));
+        assert_eq!(
+            limits.within(started, slow).await,
+            Err("the run took longer than the limit of 0s".to_string())
+        );
+    }
+
+    #[test]
+    fn test_answer_stopped() {
+        let call = Ok(mcp_core::tool::ToolCall::new(
+            "shell",
+            serde_json::json!({}),
+        ));
+        let requests = vec![
+            ToolRequest {
+                id: "1".to_string(),
+                tool_call: call.clone(),
+            },
+            ToolRequest {
+                id: "2".to_string(),
+                tool_call: call,
+            },
+        ];
+        let response = Message::user().with_tool_response("1", Ok(vec![]));
+        let response = answer_stopped(response, &requests, "time");
+        assert_eq!(response.content.len(), 2);
+        let answer = response.content[1].as_tool_response().unwrap();
+        assert_eq!(answer.id, "2");
+        assert!(answer.tool_result.is_err());
+
+        let message = stopped("time");
+        assert_eq!(stop_reason(&message), Some("time"));
+        assert_eq!(stop_reason(&Message::assistant().with_text("done")), None);
+    }
+}
@@ -1,14 +1,30 @@
 mod agent;
+pub mod approval;
+pub mod budget;
 mod capabilities;
+pub mod compare;
+pub mod critic;
+pub mod dry_run;
 pub mod extension;
 mod factory;
+pub mod final_output;
+pub mod limits;
 mod permission_judge;
 mod permission_store;
+pub mod policy;
+pub mod progress;
 mod reference;
+pub mod roles;
+pub mod router;
+pub mod sampling;
+mod subagent;
 mod summarize;
+mod supervisor;
+mod tool_list;
 mod truncate;
 
 pub use agent::{Agent, SessionConfig};
+pub(crate) use capabilities::normalize;
 pub use capabilities::Capabilities;
 pub use extension::ExtensionConfig;
 pub use factory::{register_agent, AgentFactory};
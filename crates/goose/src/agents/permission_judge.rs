@@ -71,6 +71,7 @@ fn create_check_messages(tool_requests: Vec<&ToolRequest>) -> Vec<Message> {
             ),
             annotations: None,
         })],
+        metadata: Default::default(),
     });
     check_messages
 }
@@ -175,6 +176,7 @@ mod tests {
                             }),
                         }),
                     })],
+                    metadata: Default::default(),
                 },
                 ProviderUsage::new("mock".to_string(), Usage::default()),
             ))
@@ -233,6 +235,7 @@ mod tests {
                     }),
                 }),
             })],
+            metadata: Default::default(),
         };
 
         let result = extract_read_only_tools(&message);
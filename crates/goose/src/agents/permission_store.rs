@@ -1,3 +1,4 @@
+use crate::agents::approval::ToolApproval;
 use crate::message::ToolRequest;
 use anyhow::Result;
 use blake3::Hasher;
@@ -8,6 +9,9 @@ use std::collections::HashMap;
 use std::time::Duration;
 use std::{fs::File, path::PathBuf};
 
+/// How long the user's answer to a tool call is remembered for
+pub const REMEMBER_FOR: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ToolPermissionRecord {
     tool_name: String,
@@ -94,6 +98,25 @@ impl ToolPermissionStore {
         })
     }
 
+    /// Whether the user allowed a call like `tool_request` before, so it can be made without
+    /// asking them again
+    pub fn remembered(tool_request: &ToolRequest) -> Result<bool> {
+        Ok(Self::load()?.check_permission(tool_request) == Some(true))
+    }
+
+    /// Remember for [`REMEMBER_FOR`] whether the user allowed `tool_request`
+    ///
+    /// Only answers to the call as it was asked about are remembered: a call the user edited
+    /// isn't the one they allowed, so its answer isn't kept for the original arguments.
+    pub fn remember(tool_request: &ToolRequest, approval: &ToolApproval) -> Result<()> {
+        let allowed = match approval {
+            ToolApproval::Approve => true,
+            ToolApproval::Deny => false,
+            ToolApproval::Edit(_) => return Ok(()),
+        };
+        Self::load()?.record_permission(tool_request, allowed, Some(REMEMBER_FOR))
+    }
+
     pub fn record_permission(
         &mut self,
         tool_request: &ToolRequest,
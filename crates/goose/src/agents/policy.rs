@@ -0,0 +1,387 @@
+//! A policy on which tool calls goose makes, asks about or refuses
+//!
+//! The policy is a `tool_policy` map in the config file, keyed by extension or by tool. An
+//! entry is either an action for every call, or lists of argument patterns for each action,
+//! with `*` in a pattern matching any characters:
+//!
+//! ```yaml
+//! tool_policy:
+//!   developer: ask
+//!   developer__text_editor: allow
+//!   developer__shell:
+//!     never:
+//!       - command: "rm -rf /*"
+//!     allow:
+//!       - command: "git status*"
+//!       - command: "cargo test*"
+//!     default: ask
+//! ```
+//!
+//! A pattern matches a call when each of the arguments it names matches, as a string. The
+//! first action with a matching pattern applies, checked in the order never, ask, allow, and
+//! then the entry's default. A call is checked against its tool's entry and then, if that
+//! decides nothing, its extension's, and calls neither decides on are left to the mode.
+//! An entry under `"*"` applies to every tool. A project's `.goose/config.yaml` can set its
+//! own entries, which replace the global ones key by key, except that calls the global
+//! entries refuse stay refused whatever the project's say.
+//!
+//! Calls the policy allows are made without asking even in the approve modes, calls it asks
+//! about wait for the user in every mode but chat, and calls it refuses are never made. This
+//! holds for every agent, as the calls are checked again when they are dispatched: there a
+//! call the policy asks about is refused unless the user approved it.
+use std::collections::{BTreeMap, HashMap};
+
+use mcp_core::tool::ToolCall;
+use mcp_core::ToolError;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+
+/// Config key holding the tool policy
+pub const TOOL_POLICY_CONFIG_KEY: &str = "tool_policy";
+
+/// The question asked for calls the policy asks about
+pub const POLICY_APPROVAL_PROMPT: &str =
+    "The tool policy asks for approval of this call. Allow it?";
+
+/// The error for a call the policy refuses
+pub fn policy_refusal(tool_name: &str) -> ToolError {
+    ToolError::ExecutionError(format!(
+        "The tool policy doesn't allow this call to {}. Don't try to make it again, find \
+        another way or stop.",
+        tool_name
+    ))
+}
+
+/// The error for a call the policy asks about that the user wasn't asked to approve
+pub fn policy_approval_needed(tool_name: &str) -> ToolError {
+    ToolError::ExecutionError(format!(
+        "The tool policy needs the user's approval of this call to {}, which can't be asked \
+        for here. Don't try to make it again, find another way or report this step back.",
+        tool_name
+    ))
+}
+
+/// What to do with a tool call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Make the call without asking
+    Allow,
+    /// Ask the user before making the call
+    Ask,
+    /// Refuse to make the call
+    Never,
+}
+
+/// Argument names and the patterns their values must match
+pub type ArgumentPattern = BTreeMap<String, String>;
+
+/// The policy for an extension or a tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum PolicyEntry {
+    /// The same action for every call
+    Action(PolicyAction),
+    /// Actions for the calls with matching arguments
+    Rules(PolicyRules),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PolicyRules {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub never: Vec<ArgumentPattern>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ask: Vec<ArgumentPattern>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<ArgumentPattern>,
+    /// The action for calls no pattern matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<PolicyAction>,
+}
+
+impl PolicyRules {
+    fn check(&self, arguments: &Value) -> Option<PolicyAction> {
+        [
+            (PolicyAction::Never, &self.never),
+            (PolicyAction::Ask, &self.ask),
+            (PolicyAction::Allow, &self.allow),
+        ]
+        .into_iter()
+        .find(|(_, patterns)| {
+            patterns
+                .iter()
+                .any(|pattern| pattern_matches(pattern, arguments))
+        })
+        .map(|(action, _)| action)
+        .or(self.default)
+    }
+}
+
+fn pattern_matches(pattern: &ArgumentPattern, arguments: &Value) -> bool {
+    pattern.iter().all(|(name, glob)| {
+        let value = match arguments.get(name) {
+            Some(Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+            None => return false,
+        };
+        glob_matches(glob, &value)
+    })
+}
+
+/// Whether `value` matches `glob`, in which `*` matches any characters
+fn glob_matches(glob: &str, value: &str) -> bool {
+    let pattern = glob
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    Regex::new(&format!("(?s)^{}$", pattern)).is_ok_and(|regex| regex.is_match(value))
+}
+
+/// The configured tool policy
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolPolicy {
+    pub entries: HashMap<String, PolicyEntry>,
+    /// The global config's entries, whose refusals stand whatever the other entries say
+    pub global: HashMap<String, PolicyEntry>,
+    /// The persona's entries, which can make the policy stricter but not looser
+    pub persona: HashMap<String, PolicyEntry>,
+}
+
+impl ToolPolicy {
+    pub fn new(entries: HashMap<String, PolicyEntry>) -> Self {
+        Self {
+            entries,
+            global: HashMap::new(),
+            persona: HashMap::new(),
+        }
+    }
+
+    /// The policy with the global config's `global` entries, which `entries` can't loosen
+    /// into making calls they refuse
+    pub fn with_global(mut self, global: HashMap<String, PolicyEntry>) -> Self {
+        self.global = global;
+        self
+    }
+
+    /// Read the policy from the global and project config
+    ///
+    /// A policy that can't be read would let calls through that it refuses, so then every call
+    /// is asked about.
+    pub fn from_config() -> Self {
+        let config = Config::global();
+        let global = match config.get_global_param(TOOL_POLICY_CONFIG_KEY) {
+            Ok(global) => global,
+            Err(crate::config::ConfigError::NotFound(_)) => HashMap::new(),
+            Err(e) => {
+                tracing::warn!("Invalid tool policy, every tool call needs approval: {}", e);
+                return Self::new(HashMap::from([(
+                    "*".to_string(),
+                    PolicyEntry::Action(PolicyAction::Ask),
+                )]));
+            }
+        };
+        match config.get_param(TOOL_POLICY_CONFIG_KEY) {
+            Ok(entries) => Self::new(entries).with_global(global),
+            Err(crate::config::ConfigError::NotFound(_)) => Self::default(),
+            Err(e) => {
+                tracing::warn!("Invalid tool policy, every tool call needs approval: {}", e);
+                Self::new(HashMap::from([(
+                    "*".to_string(),
+                    PolicyEntry::Action(PolicyAction::Ask),
+                )]))
+            }
+        }
+    }
+
+    /// The policy with a persona's `persona` entries, which only count where they are stricter
+    pub fn with_persona(mut self, persona: HashMap<String, PolicyEntry>) -> Self {
+        self.persona = persona;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.global.is_empty() && self.persona.is_empty()
+    }
+
+    /// What the policy says to do with `tool_call`, None if it is left to the mode
+    pub fn check(&self, tool_call: &ToolCall) -> Option<PolicyAction> {
+        if check_entries(&self.global, tool_call) == Some(PolicyAction::Never) {
+            return Some(PolicyAction::Never);
+        }
+        let action = check_entries(&self.entries, tool_call);
+        if self.persona.is_empty() {
+            return action;
+        }
+        // Leaving a call to the mode is stricter than allowing it
+        let strictness = |action: Option<PolicyAction>| match action {
+            Some(PolicyAction::Allow) => 0,
+            None => 1,
+            Some(PolicyAction::Ask) => 2,
+            Some(PolicyAction::Never) => 3,
+        };
+        let persona_action = check_entries(&self.persona, tool_call);
+        if strictness(persona_action) > strictness(action) {
+            persona_action
+        } else {
+            action
+        }
+    }
+}
+
+/// What `entries` say to do with `tool_call`: its tool's entry decides, then its extension's
+fn check_entries(
+    entries: &HashMap<String, PolicyEntry>,
+    tool_call: &ToolCall,
+) -> Option<PolicyAction> {
+    let extension = tool_call
+        .name
+        .split_once("__")
+        .map(|(extension, _)| extension);
+    [Some(tool_call.name.as_str()), extension, Some("*")]
+        .into_iter()
+        .flatten()
+        .filter_map(|key| entries.get(key))
+        .find_map(|entry| match entry {
+            PolicyEntry::Action(action) => Some(*action),
+            PolicyEntry::Rules(rules) => rules.check(&tool_call.arguments),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check() {
+        let entries: HashMap<String, PolicyEntry> = serde_yaml::from_str(
+            r#"
+            developer: ask
+            developer__text_editor: allow
+            developer__shell:
+              never:
+                - command: "rm -rf /*"
+              allow:
+                - command: "git status*"
+                - command: "cargo test*"
+            github__create_issue:
+              allow:
+                - owner: block
+                  repo: "goose*"
+            "#,
+        )
+        .unwrap();
+        let policy = ToolPolicy::new(entries);
+        let check = |name: &str, arguments: Value| policy.check(&ToolCall::new(name, arguments));
+
+        assert_eq!(
+            check("developer__text_editor", json!({"path": "/tmp/a"})),
+            Some(PolicyAction::Allow)
+        );
+        assert_eq!(
+            check("developer__shell", json!({"command": "git status --short"})),
+            Some(PolicyAction::Allow)
+        );
+        assert_eq!(
+            check("developer__shell", json!({"command": "rm -rf /usr"})),
+            Some(PolicyAction::Never)
+        );
+        // Calls the tool's entry doesn't decide fall back to the extension's
+        assert_eq!(
+            check("developer__shell", json!({"command": "npm publish"})),
+            Some(PolicyAction::Ask)
+        );
+        assert_eq!(
+            check("developer__list_windows", json!({})),
+            Some(PolicyAction::Ask)
+        );
+
+        // Every argument of a pattern has to match
+        assert_eq!(
+            check(
+                "github__create_issue",
+                json!({"owner": "block", "repo": "goose-plugins"})
+            ),
+            Some(PolicyAction::Allow)
+        );
+        assert_eq!(
+            check(
+                "github__create_issue",
+                json!({"owner": "someone", "repo": "goose"})
+            ),
+            None
+        );
+        assert_eq!(
+            check("github__create_issue", json!({"owner": "block"})),
+            None
+        );
+        assert_eq!(check("memory__remember", json!({})), None);
+    }
+
+    #[test]
+    fn test_persona_only_tightens() {
+        let policy = ToolPolicy::new(HashMap::from([
+            (
+                "developer".to_string(),
+                PolicyEntry::Action(PolicyAction::Allow),
+            ),
+            (
+                "github".to_string(),
+                PolicyEntry::Action(PolicyAction::Never),
+            ),
+        ]));
+        let policy = policy.with_persona(HashMap::from([
+            (
+                "developer__text_editor".to_string(),
+                PolicyEntry::Action(PolicyAction::Never),
+            ),
+            (
+                "github".to_string(),
+                PolicyEntry::Action(PolicyAction::Allow),
+            ),
+            (
+                "memory".to_string(),
+                PolicyEntry::Action(PolicyAction::Allow),
+            ),
+            ("*".to_string(), PolicyEntry::Action(PolicyAction::Ask)),
+        ]));
+        let check = |name: &str| policy.check(&ToolCall::new(name, json!({})));
+        assert_eq!(check("developer__text_editor"), Some(PolicyAction::Never));
+        assert_eq!(check("developer__shell"), Some(PolicyAction::Ask));
+        assert_eq!(check("github__create_issue"), Some(PolicyAction::Never));
+        // Allowing what the user's policy leaves to the mode would loosen it
+        assert_eq!(check("memory__remember"), None);
+    }
+
+    #[test]
+    fn test_global_refusals_stand() {
+        let global: HashMap<String, PolicyEntry> = serde_yaml::from_str(
+            r#"
+            developer__shell:
+              never:
+                - command: "git push*"
+              default: ask
+            "#,
+        )
+        .unwrap();
+        // The project's entry replaced the global one for the tool
+        let policy = ToolPolicy::new(HashMap::from([(
+            "developer__shell".to_string(),
+            PolicyEntry::Action(PolicyAction::Allow),
+        )]))
+        .with_global(global);
+        let check = |command: &str| {
+            policy.check(&ToolCall::new(
+                "developer__shell",
+                json!({"command": command}),
+            ))
+        };
+        assert_eq!(check("git push --force"), Some(PolicyAction::Never));
+        assert_eq!(check("cargo test"), Some(PolicyAction::Allow));
+    }
+}
@@ -0,0 +1,180 @@
+//! Passing on how a tool call is getting on while it runs
+//!
+//! When something shows the user the progress of tools, as the CLI and the server's reply
+//! stream do, each call to an extension's tool is made with a progress token, and the
+//! `notifications/progress` the server sends with it are passed to the listener while the call
+//! runs, with the session the call was made in. The developer extension sends the lines a
+//! shell command prints, a few times a second, so a long build shows its output as it goes.
+use async_trait::async_trait;
+use mcp_client::client::{Error as ClientError, McpClientTrait};
+use mcp_client::RequestHandler;
+use mcp_core::protocol::{
+    CallToolResult, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest,
+    ProgressNotificationParams, PROGRESS_NOTIFICATION,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+
+/// How far a tool call has got, as its server reported it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolProgress {
+    /// The prefixed name of the tool, such as `developer__shell`
+    pub tool: String,
+    /// The session the tool was called in
+    pub session_id: Option<String>,
+    pub progress: f64,
+    pub total: Option<f64>,
+    pub message: Option<String>,
+}
+
+/// Shows the user the progress of the tools that are running
+pub trait ProgressListener: Send + Sync {
+    /// Hear a report, which mustn't wait on the user
+    fn report(&self, progress: ToolProgress);
+}
+
+static LISTENER: RwLock<Option<Arc<dyn ProgressListener>>> = RwLock::new(None);
+
+/// Pass the progress of tool calls to `listener`, in place of the one set before, such as by
+/// the session before this one
+pub fn set_listener(listener: Arc<dyn ProgressListener>) {
+    *LISTENER.write().unwrap() = Some(listener);
+}
+
+fn listener() -> Option<Arc<dyn ProgressListener>> {
+    LISTENER.read().unwrap().clone()
+}
+
+/// A tool that is running, and the session it was called in
+type Call = (String, Option<String>);
+
+/// The call running with each progress token
+static CALLS: LazyLock<Mutex<HashMap<String, Call>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// A call to `tool` whose progress is passed on, until it is dropped
+struct TrackedCall {
+    token: String,
+}
+
+impl TrackedCall {
+    fn new(tool: &str, session_id: Option<&str>) -> Self {
+        let token = format!("goose-{}", NEXT_TOKEN.fetch_add(1, Ordering::Relaxed));
+        CALLS.lock().unwrap().insert(
+            token.clone(),
+            (tool.to_string(), session_id.map(str::to_string)),
+        );
+        Self { token }
+    }
+}
+
+impl Drop for TrackedCall {
+    fn drop(&mut self) {
+        CALLS.lock().unwrap().remove(&self.token);
+    }
+}
+
+/// Call the tool `name` of `client`, the one the model knows as `tool`, in the session
+/// `session_id`, passing on its progress when there is a listener
+pub(super) async fn call_tool(
+    client: &dyn McpClientTrait,
+    tool: &str,
+    name: &str,
+    arguments: Value,
+    session_id: Option<&str>,
+) -> Result<CallToolResult, ClientError> {
+    if listener().is_none() {
+        return client.call_tool(name, arguments).await;
+    }
+    let call = TrackedCall::new(tool, session_id);
+    client
+        .call_tool_with_progress(name, arguments, &call.token)
+        .await
+}
+
+/// The progress `notification` reports for a tool call that is running, if it is one
+fn to_progress(notification: &JsonRpcNotification) -> Option<ToolProgress> {
+    if notification.method != PROGRESS_NOTIFICATION {
+        return None;
+    }
+    let params: ProgressNotificationParams =
+        serde_json::from_value(notification.params.clone()?).ok()?;
+    let token = match &params.progress_token {
+        Value::String(token) => token.clone(),
+        token => token.to_string(),
+    };
+    let (tool, session_id) = CALLS.lock().unwrap().get(&token)?.clone();
+    Some(ToolProgress {
+        tool,
+        session_id,
+        progress: params.progress,
+        total: params.total,
+        message: params.message,
+    })
+}
+
+/// Hears the progress an extension reports, leaving its requests to `inner`
+struct ProgressHandler {
+    inner: Arc<dyn RequestHandler>,
+}
+
+/// `inner`, also passing on the progress the extension reports
+pub(super) fn with_progress(inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+    Arc::new(ProgressHandler { inner })
+}
+
+#[async_trait]
+impl RequestHandler for ProgressHandler {
+    async fn handle(&self, request: JsonRpcRequest) -> JsonRpcMessage {
+        self.inner.handle(request).await
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) {
+        if let (Some(listener), Some(progress)) = (listener(), to_progress(&notification)) {
+            listener.report(progress);
+        } else {
+            self.inner.notify(notification).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn notification(token: &str, message: &str) -> JsonRpcNotification {
+        JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: PROGRESS_NOTIFICATION.to_string(),
+            params: Some(json!({
+                "progressToken": token,
+                "progress": 3,
+                "message": message,
+            })),
+        }
+    }
+
+    #[test]
+    fn test_to_progress() {
+        let call = TrackedCall::new("developer__shell", Some("20250101_1"));
+        assert_eq!(
+            to_progress(&notification(&call.token, "Compiling goose v1.0.0")),
+            Some(ToolProgress {
+                tool: "developer__shell".to_string(),
+                session_id: Some("20250101_1".to_string()),
+                progress: 3.0,
+                total: None,
+                message: Some("Compiling goose v1.0.0".to_string()),
+            })
+        );
+
+        // Reports that come after the call are dropped
+        let token = call.token.clone();
+        drop(call);
+        assert_eq!(to_progress(&notification(&token, "Finished")), None);
+    }
+}
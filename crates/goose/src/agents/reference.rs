@@ -2,20 +2,37 @@
 /// It makes no attempt to handle context limits, and cannot read resources
 use async_trait::async_trait;
 use futures::stream::BoxStream;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tracing::{debug, instrument};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument, warn};
 
 use super::agent::SessionConfig;
+use super::budget::{budget_reached, budget_warning, without_spend_notices, BudgetStatus, Spend};
 use super::capabilities::get_parameter_names;
+use super::compare::{self, Candidate, Comparison};
 use super::extension::ToolInfo;
+use super::final_output::MAX_REPAIRS;
+use super::limits::{answer_stopped, stopped, stopped_call, RunLimits};
+use super::tool_list::ListedTools;
 use super::Agent;
 use crate::agents::capabilities::Capabilities;
 use crate::agents::extension::{ExtensionConfig, ExtensionResult};
+use crate::code_index;
+use crate::config::Config;
+use crate::knowledge;
+use crate::memory;
 use crate::message::{Message, ToolRequest};
+use crate::persona::Persona;
 use crate::providers::base::Provider;
+use crate::recipe::Recipe;
+use crate::redaction::Redactor;
+use crate::session::replay::{Recorder, Recording};
 use crate::token_counter::TokenCounter;
+use crate::tool_output;
+use crate::web_fetch;
 use crate::{register_agent, session};
 use anyhow::{anyhow, Result};
 use indoc::indoc;
@@ -72,16 +89,21 @@ impl Agent for ReferenceAgent {
         // TODO implement
     }
 
-    #[instrument(skip(self, messages, session), fields(user_message))]
+    #[instrument(skip(self, messages, session, cancel_token), fields(user_message))]
     async fn reply(
         &self,
         messages: &[Message],
         session: Option<SessionConfig>,
+        cancel_token: CancellationToken,
     ) -> anyhow::Result<BoxStream<'_, anyhow::Result<Message>>> {
         let mut messages = messages.to_vec();
         let reply_span = tracing::Span::current();
         let mut capabilities = self.capabilities.lock().await;
-        let mut tools = capabilities.get_prefixed_tools().await?;
+        capabilities.hand_off(&mut messages);
+        capabilities.set_session_id(session.as_ref().and_then(SessionConfig::name));
+        capabilities.set_working_dir(session.as_ref().map(|session| session.working_dir.clone()));
+        capabilities.set_reply_recipe(session.as_ref().and_then(|session| session.recipe.clone()));
+        let (mut listed_tools, mut tools) = ListedTools::list(&capabilities).await?;
         // we add in the read_resource tool by default
         // TODO: make sure there is no collision with another extension's tool name
         let read_resource_tool = Tool::new(
@@ -126,8 +148,37 @@ impl Agent for ReferenceAgent {
             tools.push(read_resource_tool);
             tools.push(list_resources_tool);
         }
+        if knowledge::search_tool_enabled() {
+            tools.push(knowledge::search_knowledge_tool());
+        }
+        if code_index::enabled() {
+            tools.push(code_index::search_code_tool());
+        }
+        if web_fetch::enabled() {
+            tools.push(web_fetch::fetch_url_tool());
+        }
+        // Goose's own tools too are limited to the ones a recipe being run allows
+        tools.retain(|tool| capabilities.allows_platform_tool(&tool.name));
+        if capabilities.limits_tool_outputs() {
+            tools.push(tool_output::read_tool_output_tool());
+        }
+        // Each reply gives its own value, which the caller reads once it is done
+        let final_output = capabilities.final_output();
+        if let Some(final_output) = &final_output {
+            final_output.clear();
+            tools.push(final_output.tool());
+        }
+        let mut repairs = 0;
 
-        let system_prompt = capabilities.get_system_prompt().await;
+        // Simple questions go to the small model, when routing is on
+        let route = capabilities.route(&messages).await;
+        let mut system_prompt = capabilities.get_system_prompt().await?;
+        if let Some(memories) = memory::recall_prompt(&messages).await {
+            system_prompt.push_str(&memories);
+        }
+        if let Some(passages) = knowledge::retrieval_prompt(&messages).await {
+            system_prompt.push_str(&passages);
+        }
 
         // Set the user_message field in the span instead of creating a new event
         if let Some(content) = messages
@@ -135,18 +186,80 @@ impl Agent for ReferenceAgent {
             .and_then(|msg| msg.content.first())
             .and_then(|c| c.as_text())
         {
-            debug!("user_message" = &content);
+            debug!("user_message" = &*Redactor::global().redact(content));
         }
 
+        let provider_name: String = Config::global()
+            .get_param("GOOSE_PROVIDER")
+            .unwrap_or_else(|_| "unknown".to_string());
+        let provider_name = route
+            .as_ref()
+            .and_then(|route| route.provider.clone())
+            .unwrap_or(provider_name);
+        let mut spend = Spend::start(session.as_ref(), capabilities.replaying());
+        let run_limits = RunLimits::from_config();
+        let run_started = std::time::Instant::now();
+        let (mut run_turns, mut run_tool_calls) = (0, 0);
+
         Ok(Box::pin(async_stream::try_stream! {
             let _reply_guard = reply_span.enter();
             loop {
-                // Get completion from provider
-                let (response, usage) = capabilities.provider().complete(
+                // Stop a run that has gone on for too long, whatever the model wants to do next
+                if let Some(reason) = run_limits.check(run_turns, run_tool_calls, run_started.elapsed()) {
+                    warn!("Run stopped: {}", reason);
+                    yield stopped(&reason);
+                    break;
+                }
+                run_turns += 1;
+
+                // Extensions that said their tools changed have them listed again
+                listed_tools.refresh(&capabilities, &mut tools).await;
+
+                // Check the budget before each completion. There is no one to ask about going past a
+                // cap, so the reply stops at it
+                match spend.check() {
+                    BudgetStatus::Exceeded(reason) => {
+                        yield budget_reached(&reason);
+                        break;
+                    }
+                    BudgetStatus::Warning(reason) => {
+                        yield budget_warning(&reason);
+                    }
+                    BudgetStatus::WithinBudget => {}
+                }
+
+                // Get completion from provider, the worker model's after tool results when one is set
+                let assignment = capabilities.assign_role(&messages);
+                // Notices of the spend are for the user, not the model
+                let provider_messages = without_spend_notices(Cow::Borrowed(&messages));
+                let provider = capabilities.provider();
+                let completion = cancel_token.run_until_cancelled(provider.complete_deduplicated(
                     &system_prompt,
-                    &messages,
+                    &provider_messages,
                     &tools,
-                ).await?;
+                ));
+                let completion = match run_limits.within(run_started, completion).await {
+                    Ok(completion) => completion,
+                    Err(reason) => {
+                        warn!("Run stopped: {}", reason);
+                        yield stopped(&reason);
+                        break;
+                    }
+                };
+                let Some(completion) = completion else {
+                    break;
+                };
+                let (mut response, usage) = completion?;
+
+                // persist the usage, and tell the user as soon as their spend crosses a configured threshold
+                let completion_provider = assignment
+                    .as_ref()
+                    .and_then(|assignment| assignment.provider.as_deref())
+                    .unwrap_or(&provider_name);
+                let role = assignment.as_ref().map(|assignment| assignment.role.as_str());
+                for notice in spend.record(completion_provider, role, &usage) {
+                    yield notice;
+                }
 
                 // record usage for the session in the session file
                 if let Some(session) = session.clone() {
@@ -155,27 +268,62 @@ impl Agent for ReferenceAgent {
                     let mut metadata = session::read_metadata(&session_file)?;
                     metadata.working_dir = session.working_dir;
                     metadata.total_tokens = usage.usage.total_tokens;
+                    metadata.accumulated_tokens = Some(spend.session().tokens);
+                    metadata.accumulated_cost = Some(spend.session().cost);
                     // The message count is the number of messages in the session + 1 for the response
                     // The message count does not include the tool response till next iteration
                     metadata.message_count = messages.len() + 1;
                     session::update_metadata(&session_file, &metadata).await?;
                 }
 
+                if let Some(route) = &route {
+                    route.annotate(&mut response);
+                }
+                if let Some(assignment) = &assignment {
+                    assignment.annotate(&mut response);
+                }
                 // Yield the assistant's response
                 yield response.clone();
 
                 tokio::task::yield_now().await;
 
-                // First collect any tool requests
-                let tool_requests: Vec<&ToolRequest> = response.content
+                // First collect any tool requests, calling renamed tools by the names they were listed with
+                let resolved_requests: Vec<ToolRequest> = response.content
                     .iter()
                     .filter_map(|content| content.as_tool_request())
+                    .map(|request| capabilities.resolve_tool_request(request))
                     .collect();
+                let mut tool_requests: Vec<&ToolRequest> = resolved_requests.iter().collect();
 
                 if tool_requests.is_empty() {
+                    // Ask again for a final output, with what was wrong with the last one
+                    if let Some(request) = final_output.as_ref().and_then(|final_output| final_output.repair_request(&response)) {
+                        if repairs < MAX_REPAIRS {
+                            repairs += 1;
+                            yield request.clone();
+                            messages.push(response);
+                            messages.push(request);
+                            continue;
+                        }
+                        warn!("The reply ended without a final output matching the response schema");
+                    }
                     break;
                 }
 
+                let mut message_tool_response = Message::user();
+
+                // Calls past the limit on tool calls aren't made, and the run stops after this turn
+                if let Some(left) = run_limits.tool_calls_left(run_tool_calls).filter(|&left| left < tool_requests.len()) {
+                    let reason = format!("the limit is {} tool calls", run_tool_calls + left);
+                    for request in tool_requests.split_off(left) {
+                        message_tool_response = message_tool_response.with_tool_response(
+                            request.id.clone(),
+                            Err(stopped_call(&reason)),
+                        );
+                    }
+                }
+                run_tool_calls += tool_requests.len();
+
                 // Then dispatch each in parallel
                 let futures: Vec<_> = tool_requests
                     .iter()
@@ -183,11 +331,21 @@ impl Agent for ReferenceAgent {
                     .map(|tool_call| capabilities.dispatch_tool_call(tool_call))
                     .collect();
 
-                // Process all the futures in parallel but wait until all are finished
-                let outputs = futures::future::join_all(futures).await;
-
-                // Create a message with the responses
-                let mut message_tool_response = Message::user();
+                // Process all the futures in parallel but wait until all are finished, unless the
+                // reply is cancelled or the run's time is up
+                let tool_calls = cancel_token.run_until_cancelled(capabilities.run_tool_calls(futures));
+                let outputs = match run_limits.within(run_started, tool_calls).await {
+                    Ok(Some(outputs)) => outputs,
+                    Ok(None) => break,
+                    Err(reason) => {
+                        warn!("Run stopped: {}", reason);
+                        yield answer_stopped(message_tool_response, &resolved_requests, &reason);
+                        yield stopped(&reason);
+                        break;
+                    }
+                };
+
+                // Add the responses to the message
                 // Now combine these into MessageContent::ToolResponse using the original ID
                 for (request, output) in tool_requests.iter().zip(outputs.into_iter()) {
                     message_tool_response = message_tool_response.with_tool_response(
@@ -214,6 +372,11 @@ impl Agent for ReferenceAgent {
         capabilities.set_system_prompt_override(template);
     }
 
+    async fn system_prompt(&self) -> Result<String> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.get_system_prompt().await
+    }
+
     async fn list_extension_prompts(&self) -> HashMap<String, Vec<Prompt>> {
         let capabilities = self.capabilities.lock().await;
         capabilities
@@ -246,7 +409,7 @@ impl Agent for ReferenceAgent {
     }
 
     async fn get_plan_prompt(&self) -> anyhow::Result<String> {
-        let mut capabilities = self.capabilities.lock().await;
+        let capabilities = self.capabilities.lock().await;
         let tools = capabilities.get_prefixed_tools().await?;
         let tools_info = tools
             .into_iter()
@@ -258,10 +421,55 @@ impl Agent for ReferenceAgent {
         Ok(plan_prompt)
     }
 
+    async fn compare(
+        &self,
+        messages: &[Message],
+        candidates: &[Candidate],
+    ) -> Result<Vec<Comparison>> {
+        compare::compare_turn(&self.capabilities, messages, candidates).await
+    }
+
     async fn provider(&self) -> Arc<Box<dyn Provider>> {
         let capabilities = self.capabilities.lock().await;
         capabilities.provider()
     }
+
+    async fn update_provider(&mut self, provider: Box<dyn Provider>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_provider(provider);
+    }
+
+    async fn record(&mut self, recorder: Arc<Recorder>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_recorder(recorder);
+    }
+
+    async fn replay(&mut self, recording: Arc<Recording>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_replay(recording);
+    }
+
+    async fn set_recipe(&mut self, recipe: Option<Recipe>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_recipe(recipe);
+    }
+
+    async fn set_response_schema(&mut self, schema: Option<Value>) -> Result<()> {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_response_schema(schema)
+    }
+
+    async fn final_output(&self) -> Option<Value> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities
+            .final_output()
+            .and_then(|final_output| final_output.output())
+    }
+
+    async fn set_persona(&mut self, persona: Option<Persona>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_persona(persona);
+    }
 }
 
 register_agent!("reference", ReferenceAgent);
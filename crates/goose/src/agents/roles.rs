@@ -0,0 +1,174 @@
+//! Giving the bulk of a task's tool calls to a cheaper worker model
+//!
+//! With `GOOSE_WORKER_MODEL` set, the session's model is the lead: it answers the messages
+//! from the user, which is where a task is planned and its first tools picked. The completions
+//! that go on after tool results, which are most of a long task, are made by the worker model,
+//! from `GOOSE_WORKER_PROVIDER` or else the session's provider. Once a tool call fails, the
+//! next completion goes back to the lead, which is better at finding another way.
+//!
+//! Each completion is recorded in the usage database with the role that made it, so
+//! `goose usage --by role` shows what each costs, and each answer keeps its model and role in
+//! its metadata, under `model` and `role`.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::message::{Message, MessageContent};
+use crate::model::ModelConfig;
+use crate::providers::base::Provider;
+
+/// Which of the two models makes a completion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelRole {
+    /// The session's model, which plans and answers the user
+    Lead,
+    /// The cheaper model, which carries on after tool results
+    Worker,
+}
+
+impl ModelRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelRole::Lead => "lead",
+            ModelRole::Worker => "worker",
+        }
+    }
+
+    /// The role that makes the completion after `messages`: the worker when they end with
+    /// tool results that all succeeded
+    pub fn after(messages: &[Message]) -> Self {
+        let Some(last) = messages.last() else {
+            return ModelRole::Lead;
+        };
+        let results: Vec<bool> = last
+            .content
+            .iter()
+            .map(|content| match content {
+                MessageContent::ToolResponse(response) => response.tool_result.is_ok(),
+                _ => false,
+            })
+            .collect();
+        if !results.is_empty() && results.into_iter().all(|succeeded| succeeded) {
+            ModelRole::Worker
+        } else {
+            ModelRole::Lead
+        }
+    }
+}
+
+/// The model a completion was given to, kept with its answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleAssignment {
+    pub role: ModelRole,
+    pub model: String,
+    /// The worker's provider, when the worker made the completion
+    pub provider: Option<String>,
+}
+
+impl RoleAssignment {
+    /// Note the role and model in the metadata of `message`
+    pub fn annotate(&self, message: &mut Message) {
+        message
+            .metadata
+            .insert("model".to_string(), Value::String(self.model.clone()));
+        message.metadata.insert(
+            "role".to_string(),
+            Value::String(self.role.as_str().to_string()),
+        );
+    }
+}
+
+pub struct WorkerModel {
+    provider: Arc<Box<dyn Provider>>,
+    provider_name: String,
+}
+
+impl WorkerModel {
+    pub fn new(provider: Box<dyn Provider>, provider_name: String) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            provider_name,
+        }
+    }
+
+    /// Read the worker model from the config, returning None unless one is set
+    ///
+    /// - GOOSE_WORKER_MODEL: the model that carries on after tool results
+    /// - GOOSE_WORKER_PROVIDER: its provider, `provider` or else GOOSE_PROVIDER by default
+    pub fn from_config(provider: Option<&str>) -> Option<Self> {
+        let config = Config::global();
+        let model: String = config.get_param("GOOSE_WORKER_MODEL").ok()?;
+        let provider_name = config
+            .get_param("GOOSE_WORKER_PROVIDER")
+            .ok()
+            .or_else(|| provider.map(str::to_string))
+            .or_else(|| config.get_param("GOOSE_PROVIDER").ok())?;
+        match crate::providers::create(&provider_name, ModelConfig::new(model)) {
+            Ok(worker) => Some(Self::new(worker, provider_name)),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to create the provider of the worker model, using the lead for every completion: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    pub fn provider(&self) -> Arc<Box<dyn Provider>> {
+        Arc::clone(&self.provider)
+    }
+
+    pub fn provider_name(&self) -> &str {
+        &self.provider_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::{Content, ToolCall, ToolError};
+    use serde_json::json;
+
+    #[test]
+    fn test_role_after() {
+        let call = ToolCall::new("developer__shell", json!({"command": "ls"}));
+        let request = Message::user().with_text("List the files");
+        let calls = Message::assistant()
+            .with_tool_request("1", Ok(call.clone()))
+            .with_tool_request("2", Ok(call));
+        let results = Message::user()
+            .with_tool_response("1", Ok(vec![Content::text("a.txt")]))
+            .with_tool_response("2", Ok(vec![]));
+        let failed = Message::user()
+            .with_tool_response("1", Ok(vec![Content::text("a.txt")]))
+            .with_tool_response("2", Err(ToolError::ExecutionError("denied".to_string())));
+
+        assert_eq!(ModelRole::after(&[]), ModelRole::Lead);
+        assert_eq!(
+            ModelRole::after(std::slice::from_ref(&request)),
+            ModelRole::Lead
+        );
+        assert_eq!(
+            ModelRole::after(&[request.clone(), calls.clone(), results]),
+            ModelRole::Worker
+        );
+        // A failed call sends the task back to the lead
+        assert_eq!(ModelRole::after(&[request, calls, failed]), ModelRole::Lead);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let assignment = RoleAssignment {
+            role: ModelRole::Worker,
+            model: "gpt-4o-mini".to_string(),
+            provider: Some("openai".to_string()),
+        };
+        let mut message = Message::assistant().with_text("Both files are listed");
+        assignment.annotate(&mut message);
+        assert_eq!(message.metadata["model"], "gpt-4o-mini");
+        assert_eq!(message.metadata["role"], "worker");
+    }
+}
@@ -0,0 +1,323 @@
+//! Sending simple questions to a smaller, cheaper model
+//!
+//! With `GOOSE_ROUTER_MODEL` set, each turn is classified before the agent answers it. A
+//! question that can be answered in one reply, without tools or code, goes to that model, from
+//! `GOOSE_ROUTER_PROVIDER` or else the session's provider, and everything else stays with the
+//! session's model. Clear cases are told apart by rules, and the rest are put to the small model
+//! in a one word request, unless `GOOSE_ROUTER_CLASSIFIER` is `rules`, which keeps them on the
+//! session's model. A persona's `routing` settings apply to sessions in it over the config.
+//!
+//! The model that answered a turn is kept in the metadata of its messages, under `model`, with
+//! how the turn was classified under `route`.
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::compaction::starts_turn;
+use crate::config::Config;
+use crate::message::Message;
+use crate::model::ModelConfig;
+use crate::providers::base::Provider;
+
+/// A request longer than this is not taken for a simple question by the rules alone
+const MAX_SIMPLE_CHARS: usize = 300;
+
+const SYSTEM_PROMPT: &str = "You sort the requests sent to an AI agent by how much work they \
+    take. You reply with one word.";
+
+const CLASSIFY_INSTRUCTIONS: &str = "Can the request below be answered in a single reply from \
+    general knowledge, without reading files, running commands or writing code? Reply with \
+    simple if it can, and with complex if it needs any of those or is a task with several \
+    steps.";
+
+/// Verbs that start a task rather than a question
+const TASK_VERBS: &[&str] = &[
+    "add",
+    "build",
+    "change",
+    "commit",
+    "configure",
+    "create",
+    "debug",
+    "delete",
+    "deploy",
+    "edit",
+    "find",
+    "fix",
+    "implement",
+    "install",
+    "migrate",
+    "move",
+    "refactor",
+    "remove",
+    "rename",
+    "review",
+    "run",
+    "set",
+    "test",
+    "update",
+    "upgrade",
+    "write",
+];
+
+const QUESTION_WORDS: &[&str] = &[
+    "are", "can", "could", "define", "describe", "do", "does", "explain", "how", "is", "should",
+    "what", "when", "where", "which", "who", "why",
+];
+
+/// A file name or path, which makes a request about the project rather than general knowledge
+static FILE_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"[\w-]+\.(rs|py|js|jsx|ts|tsx|go|java|kt|rb|c|h|cpp|cs|swift|toml|json|ya?ml|md|sh|sql)\b",
+        r"|\w/\w"
+    ))
+    .expect("the pattern is valid")
+});
+
+/// How much work a turn takes, which decides the model that answers it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskKind {
+    /// A question answered in one reply, which goes to the small model
+    Simple,
+    /// Anything else, which stays with the session's model
+    Complex,
+}
+
+impl TaskKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskKind::Simple => "simple",
+            TaskKind::Complex => "complex",
+        }
+    }
+}
+
+/// How turns the rules can't tell apart are classified
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Classifier {
+    /// Ask the small model
+    #[default]
+    Model,
+    /// Keep them on the session's model
+    Rules,
+}
+
+/// Routing settings of a persona, each over its `GOOSE_ROUTER_*` config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingSettings {
+    /// Set to false to answer every turn with the session's model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// The model simple questions go to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small_model: Option<String>,
+    /// The provider of the small model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small_provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classifier: Option<Classifier>,
+}
+
+/// The model a turn was given to, kept with its answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub kind: TaskKind,
+    pub model: String,
+    /// The small model's provider, when the turn went to it
+    pub provider: Option<String>,
+}
+
+impl Route {
+    /// Note the route in the metadata of `message`
+    pub fn annotate(&self, message: &mut Message) {
+        message
+            .metadata
+            .insert("model".to_string(), Value::String(self.model.clone()));
+        message.metadata.insert(
+            "route".to_string(),
+            Value::String(self.kind.as_str().to_string()),
+        );
+    }
+}
+
+pub struct ModelRouter {
+    small: Arc<Box<dyn Provider>>,
+    provider_name: String,
+    classifier: Classifier,
+}
+
+impl ModelRouter {
+    pub fn new(small: Box<dyn Provider>, provider_name: String, classifier: Classifier) -> Self {
+        Self {
+            small: Arc::new(small),
+            provider_name,
+            classifier,
+        }
+    }
+
+    /// Read the routing settings, with `persona`'s over the config, returning None unless a
+    /// small model is set
+    ///
+    /// - GOOSE_ROUTER_MODEL: the model simple questions go to
+    /// - GOOSE_ROUTER_PROVIDER: its provider, `provider` or else GOOSE_PROVIDER by default
+    /// - GOOSE_ROUTER_CLASSIFIER: `model` (the default) or `rules`
+    pub fn from_config(persona: Option<&RoutingSettings>, provider: Option<&str>) -> Option<Self> {
+        let config = Config::global();
+        let settings = persona.cloned().unwrap_or_default();
+        if settings.enabled == Some(false) {
+            return None;
+        }
+        let model = settings
+            .small_model
+            .or_else(|| config.get_param("GOOSE_ROUTER_MODEL").ok())?;
+        let provider_name = settings
+            .small_provider
+            .or_else(|| config.get_param("GOOSE_ROUTER_PROVIDER").ok())
+            .or_else(|| provider.map(str::to_string))
+            .or_else(|| config.get_param("GOOSE_PROVIDER").ok())?;
+        let classifier = settings
+            .classifier
+            .or_else(|| config.get_param("GOOSE_ROUTER_CLASSIFIER").ok())
+            .unwrap_or_default();
+        match crate::providers::create(&provider_name, ModelConfig::new(model)) {
+            Ok(small) => Some(Self::new(small, provider_name, classifier)),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to create the provider of the router's model, not routing: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// The provider simple questions go to
+    pub fn small(&self) -> Arc<Box<dyn Provider>> {
+        Arc::clone(&self.small)
+    }
+
+    pub fn provider_name(&self) -> &str {
+        &self.provider_name
+    }
+
+    /// Classify the turn `messages` ends with, or None when they don't end with one the user
+    /// started, as when a reply goes on after a tool call
+    pub async fn classify(&self, messages: &[Message]) -> Option<TaskKind> {
+        let last = messages.last().filter(|message| starts_turn(message))?;
+        let request = last.as_concat_text();
+        if let Some(kind) = classify_by_rules(&request) {
+            return Some(kind);
+        }
+        if self.classifier == Classifier::Rules {
+            return Some(TaskKind::Complex);
+        }
+        let prompt = format!("{}\n\nRequest:\n{}", CLASSIFY_INSTRUCTIONS, request);
+        match self
+            .small
+            .complete(SYSTEM_PROMPT, &[Message::user().with_text(prompt)], &[])
+            .await
+        {
+            Ok((response, _)) => Some(parse_kind(&response.as_concat_text())),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to classify the turn, using the session's model: {}",
+                    e
+                );
+                Some(TaskKind::Complex)
+            }
+        }
+    }
+}
+
+/// The kind of `request` when it is clear from its words, or None to ask the model
+pub fn classify_by_rules(request: &str) -> Option<TaskKind> {
+    let request = request.trim();
+    if request.contains("```") || FILE_NAME.is_match(request) {
+        return Some(TaskKind::Complex);
+    }
+    let words: Vec<String> = request
+        .split_whitespace()
+        .take(4)
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .collect();
+    // "please fix", "can you fix" and "could you please fix" are tasks put politely
+    let verb = words
+        .iter()
+        .map(String::as_str)
+        .find(|word| !matches!(*word, "please" | "can" | "could" | "would" | "will" | "you"));
+    if verb.is_some_and(|verb| TASK_VERBS.contains(&verb)) {
+        return Some(TaskKind::Complex);
+    }
+    let question = request.ends_with('?')
+        || words
+            .first()
+            .is_some_and(|word| QUESTION_WORDS.contains(&word.as_str()));
+    (question && request.len() <= MAX_SIMPLE_CHARS && !request.contains('\n'))
+        .then_some(TaskKind::Simple)
+}
+
+/// The kind in the classifier's answer, leaning to the session's model when it is unclear
+fn parse_kind(answer: &str) -> TaskKind {
+    if answer.trim().to_lowercase().starts_with("simple") {
+        TaskKind::Simple
+    } else {
+        TaskKind::Complex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_by_rules() {
+        let kind = |request: &str| classify_by_rules(request);
+        assert_eq!(kind("What does HTTP 418 mean?"), Some(TaskKind::Simple));
+        assert_eq!(
+            kind("explain the difference between a mutex and a semaphore"),
+            Some(TaskKind::Simple)
+        );
+        assert_eq!(kind("Fix the failing test"), Some(TaskKind::Complex));
+        assert_eq!(
+            kind("Can you please refactor the parser?"),
+            Some(TaskKind::Complex)
+        );
+        assert_eq!(kind("What does main.rs do?"), Some(TaskKind::Complex));
+        assert_eq!(kind("Why is src/lib slow?"), Some(TaskKind::Complex));
+        assert_eq!(
+            kind("How do I use this?\n```\nlet x = 1;\n```"),
+            Some(TaskKind::Complex)
+        );
+        // Neither a question nor a task verb, so the model decides
+        assert_eq!(kind("The login page is broken after the deploy"), None);
+        assert_eq!(kind(&format!("Why {}?", "so ".repeat(200))), None);
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(parse_kind(" Simple."), TaskKind::Simple);
+        assert_eq!(parse_kind("complex"), TaskKind::Complex);
+        assert_eq!(parse_kind("I think it is simple"), TaskKind::Complex);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let route = Route {
+            kind: TaskKind::Simple,
+            model: "gpt-4o-mini".to_string(),
+            provider: None,
+        };
+        let mut message = Message::assistant().with_text("418 means I'm a teapot");
+        route.annotate(&mut message);
+        assert_eq!(message.metadata["model"], "gpt-4o-mini");
+        assert_eq!(message.metadata["route"], "simple");
+    }
+}
@@ -0,0 +1,316 @@
+//! Answering extensions that ask goose's model for a completion
+//!
+//! An MCP server can send `sampling/createMessage` to have the client's model answer a
+//! conversation, such as to summarize what one of its tools found. Goose answers with the
+//! session's model, or with the smaller router or worker model when there is one and the
+//! server prefers speed or cost over intelligence. A server's model hints are matched against
+//! the names of those models first. The server's system prompt and token limit are used, the
+//! lower of it and the model's own, while the temperature and stop sequences are the session's.
+//! The tokens the answers use are recorded for the session like any other completion's.
+//!
+//! `GOOSE_SAMPLING` decides which requests are answered: `ask`, the default, asks the user
+//! about each one, `allow` answers them all, and `deny` refuses them, without telling servers
+//! goose can answer. Where nothing can ask the user, as in the desktop app, `ask` refuses.
+use async_trait::async_trait;
+use mcp_client::RequestHandler;
+use mcp_core::protocol::{
+    CreateMessageParams, CreateMessageResult, ErrorData, JsonRpcError, JsonRpcMessage,
+    JsonRpcRequest, JsonRpcResponse, ModelPreferences, SamplingMessage, CREATE_MESSAGE_METHOD,
+    INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND,
+};
+use mcp_core::{Content, Role};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use crate::config::Config;
+use crate::message::Message;
+use crate::model::with_output_limit;
+use crate::providers::base::Provider;
+
+/// The error code of a request the user or the config refused, as the MCP spec suggests
+const REJECTED: i32 = -1;
+
+/// Which of the servers' requests for a completion are answered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplingPolicy {
+    /// Ask the user about each request
+    #[default]
+    Ask,
+    Allow,
+    Deny,
+}
+
+impl SamplingPolicy {
+    pub fn from_config() -> Self {
+        Config::global()
+            .get_param("GOOSE_SAMPLING")
+            .unwrap_or_default()
+    }
+}
+
+/// Asks the user whether an extension may have the model answer its request
+#[async_trait]
+pub trait SamplingApprover: Send + Sync {
+    async fn approve(&self, extension: &str, params: &CreateMessageParams) -> bool;
+}
+
+static APPROVER: RwLock<Option<Arc<dyn SamplingApprover>>> = RwLock::new(None);
+
+/// Ask the user about requests with `approver` when GOOSE_SAMPLING is `ask`, in place of the
+/// one set before, such as by the session before this one
+pub fn set_approver(approver: Arc<dyn SamplingApprover>) {
+    *APPROVER.write().unwrap() = Some(approver);
+}
+
+/// The models that answer the extensions' requests
+pub struct Sampler {
+    policy: SamplingPolicy,
+    session: RwLock<Arc<Box<dyn Provider>>>,
+    /// The router's or the worker's model, for requests that prefer a cheap or fast one
+    small: RwLock<Option<Arc<Box<dyn Provider>>>>,
+    /// The session the answers' usage is recorded for
+    session_id: RwLock<Option<String>>,
+}
+
+impl Sampler {
+    pub fn new(policy: SamplingPolicy, session: Arc<Box<dyn Provider>>) -> Self {
+        Self {
+            policy,
+            session: RwLock::new(session),
+            small: RwLock::new(None),
+            session_id: RwLock::new(None),
+        }
+    }
+
+    /// Whether servers are told goose can answer their requests
+    pub fn is_enabled(&self) -> bool {
+        self.policy != SamplingPolicy::Deny
+    }
+
+    pub fn set_session(&self, provider: Arc<Box<dyn Provider>>) {
+        *self.session.write().unwrap() = provider;
+    }
+
+    pub fn set_small(&self, provider: Option<Arc<Box<dyn Provider>>>) {
+        *self.small.write().unwrap() = provider;
+    }
+
+    pub fn set_session_id(&self, session_id: Option<String>) {
+        *self.session_id.write().unwrap() = session_id;
+    }
+
+    /// The handler answering the requests of the extension `name`
+    pub fn handler(self: &Arc<Self>, name: String) -> Arc<dyn RequestHandler> {
+        Arc::new(SamplingHandler {
+            extension: name,
+            sampler: Arc::clone(self),
+        })
+    }
+
+    /// The model that answers a request with `preferences`
+    fn choose(&self, preferences: Option<&ModelPreferences>) -> Arc<Box<dyn Provider>> {
+        let mut models = vec![Arc::clone(&self.session.read().unwrap())];
+        models.extend(self.small.read().unwrap().iter().cloned());
+        let names: Vec<String> = models
+            .iter()
+            .map(|model| model.get_model_config().model_name)
+            .collect();
+        models.swap_remove(choose_model(&names, preferences))
+    }
+
+    async fn sample(
+        &self,
+        extension: &str,
+        params: CreateMessageParams,
+    ) -> Result<CreateMessageResult, ErrorData> {
+        let approver = APPROVER.read().unwrap().clone();
+        let approved = match self.policy {
+            SamplingPolicy::Allow => true,
+            SamplingPolicy::Deny => false,
+            SamplingPolicy::Ask => match approver {
+                Some(approver) => approver.approve(extension, &params).await,
+                None => {
+                    tracing::warn!(
+                        "Refused a request from {} for a completion, as there is no one to ask; set GOOSE_SAMPLING to allow to answer them",
+                        extension
+                    );
+                    false
+                }
+            },
+        };
+        if !approved {
+            return Err(error(REJECTED, "The user refused the request"));
+        }
+
+        let provider = self.choose(params.model_preferences.as_ref());
+        let messages: Vec<Message> = params.messages.into_iter().map(to_message).collect();
+        let max_tokens = i32::try_from(params.max_tokens).unwrap_or(i32::MAX);
+        let (response, usage) = with_output_limit(
+            max_tokens,
+            provider.complete(
+                params.system_prompt.as_deref().unwrap_or_default(),
+                &messages,
+                &[],
+            ),
+        )
+        .await
+        .map_err(|e| error(INTERNAL_ERROR, e.to_string()))?;
+        let provider_name: String = Config::global()
+            .get_param("GOOSE_PROVIDER")
+            .unwrap_or_else(|_| "unknown".to_string());
+        crate::usage::record(
+            &provider_name,
+            self.session_id.read().unwrap().clone(),
+            &usage,
+        );
+        Ok(CreateMessageResult {
+            role: Role::Assistant,
+            content: Content::text(response.as_concat_text()),
+            model: provider.get_model_config().model_name,
+            stop_reason: Some("endTurn".to_string()),
+        })
+    }
+}
+
+/// The index of the model in `models`, the session's first, that best fits `preferences`
+///
+/// The first hint naming part of a model's name picks it. Otherwise a second, smaller model is
+/// picked when cost or speed matter more to the server than intelligence.
+fn choose_model(models: &[String], preferences: Option<&ModelPreferences>) -> usize {
+    let Some(preferences) = preferences else {
+        return 0;
+    };
+    for hint in preferences
+        .hints
+        .iter()
+        .filter_map(|hint| hint.name.as_ref())
+    {
+        let hint = hint.to_lowercase();
+        if let Some(index) = models
+            .iter()
+            .position(|model| model.to_lowercase().contains(&hint))
+        {
+            return index;
+        }
+    }
+    let cheap = preferences
+        .cost_priority
+        .unwrap_or_default()
+        .max(preferences.speed_priority.unwrap_or_default());
+    if models.len() > 1 && cheap > preferences.intelligence_priority.unwrap_or_default() {
+        1
+    } else {
+        0
+    }
+}
+
+fn to_message(message: SamplingMessage) -> Message {
+    let to = match message.role {
+        Role::User => Message::user(),
+        Role::Assistant => Message::assistant(),
+    };
+    match message.content {
+        Content::Text(text) => to.with_text(text.text),
+        Content::Image(image) => to.with_image(image.data, image.mime_type),
+        Content::Resource(resource) => to.with_text(resource.get_text()),
+    }
+}
+
+fn error(code: i32, message: impl Into<String>) -> ErrorData {
+    ErrorData {
+        code,
+        message: message.into(),
+        data: None,
+    }
+}
+
+/// Answers the requests of one extension, which the user is told the name of
+struct SamplingHandler {
+    extension: String,
+    sampler: Arc<Sampler>,
+}
+
+#[async_trait]
+impl RequestHandler for SamplingHandler {
+    async fn handle(&self, request: JsonRpcRequest) -> JsonRpcMessage {
+        let result = if request.method != CREATE_MESSAGE_METHOD {
+            Err(error(
+                METHOD_NOT_FOUND,
+                format!("The client doesn't support {}", request.method),
+            ))
+        } else {
+            match serde_json::from_value(request.params.unwrap_or_default()) {
+                Ok(params) => self.sampler.sample(&self.extension, params).await,
+                Err(e) => Err(error(INVALID_PARAMS, e.to_string())),
+            }
+        };
+        match result.and_then(|result| {
+            serde_json::to_value(result).map_err(|e| error(INTERNAL_ERROR, e.to_string()))
+        }) {
+            Ok(result) => JsonRpcMessage::Response(JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                result: Some(result),
+                error: None,
+            }),
+            Err(error) => JsonRpcMessage::Error(JsonRpcError {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                error,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::ModelHint;
+
+    #[test]
+    fn test_choose_model() {
+        let models = vec![
+            "claude-3-7-sonnet".to_string(),
+            "claude-3-5-haiku".to_string(),
+        ];
+        let hint = |name: &str| ModelHint {
+            name: Some(name.to_string()),
+        };
+        assert_eq!(choose_model(&models, None), 0);
+        assert_eq!(
+            choose_model(
+                &models,
+                Some(&ModelPreferences {
+                    hints: vec![hint("gpt-4o"), hint("Haiku")],
+                    ..Default::default()
+                })
+            ),
+            1
+        );
+        let fast = ModelPreferences {
+            speed_priority: Some(0.9),
+            intelligence_priority: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(choose_model(&models, Some(&fast)), 1);
+        // Without a smaller model, the session's answers
+        assert_eq!(choose_model(&models[..1], Some(&fast)), 0);
+        let smart = ModelPreferences {
+            cost_priority: Some(0.3),
+            intelligence_priority: Some(0.8),
+            ..Default::default()
+        };
+        assert_eq!(choose_model(&models, Some(&smart)), 0);
+    }
+
+    #[test]
+    fn test_to_message() {
+        let message = to_message(SamplingMessage {
+            role: Role::Assistant,
+            content: Content::text("The build fails on Windows"),
+        });
+        assert_eq!(message.role, Role::Assistant);
+        assert_eq!(message.as_concat_text(), "The build fails on Windows");
+    }
+}
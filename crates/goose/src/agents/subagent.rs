@@ -0,0 +1,737 @@
+//! Subagents the lead agent delegates scoped tasks to, with the `platform__spawn_subagent` tool
+//!
+//! A subagent gets a fresh conversation holding only its task, its own system prompt, a subset
+//! of the lead agent's tools and a budget of turns. It runs with the lead agent's extensions and
+//! provider, and only its final reply, which summarizes what it did, is returned to the lead
+//! agent, so the steps it took don't take up the lead agent's context. Subagents are offered
+//! with `GOOSE_SUBAGENTS=true`, and can't spawn subagents of their own.
+//!
+//! With `platform__spawn_subagents` the lead agent can also run several subagents at once, say
+//! one for each crate with failing tests. Each has its own conversation, but subagents spawned
+//! together can leave each other artifacts, such as findings or changed interfaces, which are
+//! returned to the lead agent with their replies.
+use chrono::Utc;
+use futures::future::BoxFuture;
+use futures::stream::{self, StreamExt};
+use indoc::indoc;
+use mcp_core::{Content, Tool, ToolError, ToolResult};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use super::approval::ApprovalRules;
+use super::capabilities::Capabilities;
+use super::dry_run::{simulated_result, DRY_RUN_MODE};
+use super::policy::{policy_refusal, PolicyAction};
+use super::ToolPermissionStore;
+use crate::audit::Approval;
+use crate::config::Config;
+use crate::message::{Message, ToolRequest};
+use crate::prompt_template;
+use crate::usage;
+
+pub const SPAWN_SUBAGENT_TOOL: &str = "platform__spawn_subagent";
+pub const SPAWN_SUBAGENTS_TOOL: &str = "platform__spawn_subagents";
+const WRITE_ARTIFACT_TOOL: &str = "platform__write_artifact";
+const READ_ARTIFACT_TOOL: &str = "platform__read_artifact";
+
+const DEFAULT_MAX_TURNS: usize = 10;
+
+/// The most turns a subagent may be given, unless `GOOSE_SUBAGENT_MAX_TURNS` allows more
+const MAX_TURNS_LIMIT: usize = 25;
+
+/// How many subagents spawned together run at once, unless `GOOSE_SUBAGENT_CONCURRENCY` is set
+const DEFAULT_CONCURRENCY: usize = 3;
+
+const OUT_OF_TURNS: &str = "You have used all your turns. Don't call any more tools: reply \
+    now with a summary of what you did, what you found and what is left to do.";
+
+/// The task the lead agent gives a subagent, the arguments of [`SPAWN_SUBAGENT_TOOL`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubagentTask {
+    pub task: String,
+    /// Added to the subagent's system prompt
+    #[serde(default)]
+    pub instructions: Option<String>,
+    /// Tools, or whole extensions, the subagent may use; all of the lead agent's by default
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    #[serde(default)]
+    pub max_turns: Option<usize>,
+}
+
+/// The arguments of [`SPAWN_SUBAGENTS_TOOL`]
+#[derive(Debug, Clone, Deserialize)]
+struct SubagentTasks {
+    tasks: Vec<SubagentTask>,
+}
+
+#[derive(Debug, Clone)]
+struct Artifact {
+    content: String,
+    /// The number of the subagent that wrote it, from 1
+    author: usize,
+}
+
+/// What the subagents spawned together share with each other, by name
+#[derive(Debug, Default)]
+struct Artifacts(Mutex<BTreeMap<String, Artifact>>);
+
+impl Artifacts {
+    /// Handle a call to [`WRITE_ARTIFACT_TOOL`] or [`READ_ARTIFACT_TOOL`] from a subagent
+    fn call(&self, subagent: usize, tool: &str, arguments: Value) -> ToolResult<Vec<Content>> {
+        let name = arguments.get("name").and_then(Value::as_str);
+        let mut artifacts = self.0.lock().unwrap();
+        if tool == WRITE_ARTIFACT_TOOL {
+            let (Some(name), Some(content)) =
+                (name, arguments.get("content").and_then(Value::as_str))
+            else {
+                return Err(ToolError::InvalidParameters(
+                    "The name and content are required".to_string(),
+                ));
+            };
+            artifacts.insert(
+                name.to_string(),
+                Artifact {
+                    content: content.to_string(),
+                    author: subagent,
+                },
+            );
+            return Ok(vec![Content::text(format!("Shared {}", name))]);
+        }
+        match name {
+            Some(name) => artifacts
+                .get(name)
+                .map(|artifact| vec![Content::text(artifact.content.clone())])
+                .ok_or_else(|| ToolError::NotFound(format!("No artifact named {}", name))),
+            None if artifacts.is_empty() => Ok(vec![Content::text("Nothing has been shared yet")]),
+            None => Ok(vec![Content::text(
+                artifacts
+                    .iter()
+                    .map(|(name, artifact)| format!("{} (from subagent {})", name, artifact.author))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )]),
+        }
+    }
+}
+
+/// Whether the lead agent is offered [`spawn_subagent_tool`] and [`spawn_subagents_tool`], with
+/// `GOOSE_SUBAGENTS`
+pub fn subagents_enabled() -> bool {
+    Config::global()
+        .get_param::<bool>("GOOSE_SUBAGENTS")
+        .unwrap_or(false)
+}
+
+pub fn spawn_subagent_tool() -> Tool {
+    Tool::new(
+        SPAWN_SUBAGENT_TOOL.to_string(),
+        indoc! {r#"
+            Delegate a scoped task to a subagent and get back a summary of its result.
+
+            The subagent works on the task on its own with the tools you give it, in a fresh
+            conversation that sees nothing of this one, so describe the task with all the context
+            it needs. Only its final summary is returned, which keeps the steps it takes out of
+            your context. Use it for self-contained work like investigating a question across
+            many files or making a well defined change, not for steps that need the user.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "required": ["task"],
+            "properties": {
+                "task": {
+                    "type": "string",
+                    "description": "The task, with the context the subagent needs and what to report back"
+                },
+                "instructions": {
+                    "type": "string",
+                    "description": "Optional instructions for the subagent's system prompt, e.g. a role or conventions to follow"
+                },
+                "tools": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Optional tools the subagent may use, by name or by extension, e.g. [\"developer__shell\"] or [\"developer\"]; all of yours by default"
+                },
+                "max_turns": {
+                    "type": "integer",
+                    "description": format!("Optional number of replies the subagent gets, {} by default", DEFAULT_MAX_TURNS)
+                }
+            }
+        }),
+    )
+}
+
+pub fn spawn_subagents_tool() -> Tool {
+    let task = spawn_subagent_tool().input_schema;
+    Tool::new(
+        SPAWN_SUBAGENTS_TOOL.to_string(),
+        indoc! {r#"
+            Run several subagents at once on independent tasks, and get back a summary from each.
+
+            Use it to split work that can be done in parallel, like fixing the tests of several
+            crates, giving each subagent a task that doesn't depend on the others' results. Each
+            subagent works in a fresh conversation like with spawn_subagent, but subagents spawned
+            together can share artifacts, such as findings or interfaces they changed, which are
+            returned along with their summaries. Avoid giving two subagents the same files to
+            change.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "required": ["tasks"],
+            "properties": {
+                "tasks": {
+                    "type": "array",
+                    "items": task,
+                    "description": "The tasks, one for each subagent"
+                }
+            }
+        }),
+    )
+}
+
+/// The tools subagents spawned together share artifacts with
+fn artifact_tools() -> Vec<Tool> {
+    vec![
+        Tool::new(
+            WRITE_ARTIFACT_TOOL.to_string(),
+            "Share an artifact, such as a finding or an interface you changed, with the other \
+            subagents and the lead agent. Writing an artifact with the same name replaces it."
+                .to_string(),
+            json!({
+                "type": "object",
+                "required": ["name", "content"],
+                "properties": {
+                    "name": {"type": "string", "description": "A short name for the artifact"},
+                    "content": {"type": "string", "description": "The content of the artifact"}
+                }
+            }),
+        ),
+        Tool::new(
+            READ_ARTIFACT_TOOL.to_string(),
+            "Read an artifact shared by another subagent, or list the artifacts shared so far."
+                .to_string(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string", "description": "Optional name of the artifact to read; lists the artifacts when left out"}
+                }
+            }),
+        ),
+    ]
+}
+
+/// Run a subagent on the task in a [`SPAWN_SUBAGENT_TOOL`] call, returning its final reply
+///
+/// This is boxed as the subagent's tool calls are dispatched through `capabilities` too.
+pub fn run_subagent(
+    capabilities: &Capabilities,
+    arguments: Value,
+) -> BoxFuture<'_, ToolResult<Vec<Content>>> {
+    Box::pin(async move {
+        let task: SubagentTask = serde_json::from_value(arguments)
+            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+        let summary = run(capabilities, &task, None).await?;
+        Ok(vec![Content::text(summary)])
+    })
+}
+
+/// Run the subagents in a [`SPAWN_SUBAGENTS_TOOL`] call, returning their replies together
+///
+/// At most `GOOSE_SUBAGENT_CONCURRENCY` subagents run at once. A subagent failing doesn't stop
+/// the others, its error is reported in its place.
+pub fn run_subagents(
+    capabilities: &Capabilities,
+    arguments: Value,
+) -> BoxFuture<'_, ToolResult<Vec<Content>>> {
+    Box::pin(async move {
+        let tasks = serde_json::from_value::<SubagentTasks>(arguments)
+            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?
+            .tasks;
+        if tasks.is_empty() {
+            return Err(ToolError::InvalidParameters(
+                "At least one task is needed".to_string(),
+            ));
+        }
+        let concurrency = Config::global()
+            .get_param::<usize>("GOOSE_SUBAGENT_CONCURRENCY")
+            .unwrap_or(DEFAULT_CONCURRENCY)
+            .max(1);
+        let summary = run_all(capabilities, &tasks, concurrency).await;
+        Ok(vec![Content::text(summary)])
+    })
+}
+
+async fn run_all(
+    capabilities: &Capabilities,
+    tasks: &[SubagentTask],
+    concurrency: usize,
+) -> String {
+    let artifacts = Artifacts::default();
+    let runs: Vec<_> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, task)| run(capabilities, task, Some((&artifacts, i + 1))))
+        .collect();
+    let results: Vec<_> = stream::iter(runs).buffered(concurrency).collect().await;
+
+    let mut summary = String::new();
+    for (i, (task, result)) in tasks.iter().zip(results).enumerate() {
+        let title = task.task.lines().next().unwrap_or_default();
+        let title: String = title.chars().take(80).collect();
+        let result = result.unwrap_or_else(|e| format!("The subagent failed: {}", e));
+        summary.push_str(&format!(
+            "## Subagent {}: {}\n\n{}\n\n",
+            i + 1,
+            title,
+            result
+        ));
+    }
+    let artifacts = artifacts.0.into_inner().unwrap();
+    if !artifacts.is_empty() {
+        summary.push_str("## Shared artifacts\n\n");
+        for (name, artifact) in artifacts {
+            summary.push_str(&format!(
+                "### {} (from subagent {})\n\n{}\n\n",
+                name, artifact.author, artifact.content
+            ));
+        }
+    }
+    summary.trim_end().to_string()
+}
+
+/// Run a subagent on its own conversation until it replies without calling tools or runs out
+/// of turns, sharing `artifacts` with the subagents running alongside it as the given number
+async fn run(
+    capabilities: &Capabilities,
+    task: &SubagentTask,
+    artifacts: Option<(&Artifacts, usize)>,
+) -> ToolResult<String> {
+    let limit = Config::global()
+        .get_param::<usize>("GOOSE_SUBAGENT_MAX_TURNS")
+        .unwrap_or(MAX_TURNS_LIMIT);
+    let max_turns = task.max_turns.unwrap_or(DEFAULT_MAX_TURNS).clamp(1, limit);
+
+    let tools = capabilities
+        .get_prefixed_tools()
+        .await
+        .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+    let mut tools = select_tools(tools, task.tools.as_deref())?;
+    if artifacts.is_some() {
+        tools.extend(artifact_tools());
+    }
+    let system_prompt = system_prompt(capabilities, task, max_turns, artifacts.is_some());
+    let provider = capabilities.provider();
+    let provider_name: String = Config::global()
+        .get_param("GOOSE_PROVIDER")
+        .unwrap_or_else(|_| "unknown".to_string());
+    let goose_mode: String = Config::global()
+        .get_param("GOOSE_MODE")
+        .unwrap_or_else(|_| "auto".to_string());
+
+    let mut messages = vec![Message::user().with_text(&task.task)];
+    let mut tool_calls = 0;
+    for turn in 1..=max_turns + 1 {
+        let (response, usage) = provider
+            .complete(&system_prompt, &messages, &tools)
+            .await
+            .map_err(|e| ToolError::ExecutionError(format!("The subagent failed: {}", e)))?;
+        // The subagent's usage is part of the session that started it
+        usage::record(
+            &provider_name,
+            capabilities.session_id().map(str::to_string),
+            &usage,
+        );
+
+        let requests: Vec<ToolRequest> = response
+            .content
+            .iter()
+            .filter_map(|content| content.as_tool_request())
+            .cloned()
+            .collect();
+        if requests.is_empty() || turn > max_turns {
+            let summary = response.as_concat_text();
+            let note = if requests.is_empty() {
+                format!(
+                    "The subagent finished after {} turns and {} tool calls.",
+                    turn, tool_calls
+                )
+            } else {
+                format!(
+                    "The subagent ran out of its {} turns after {} tool calls, this is what \
+                    it had done so far.",
+                    max_turns, tool_calls
+                )
+            };
+            return Ok(format!("{}\n\n{}", summary, note));
+        }
+
+        tool_calls += requests.len();
+        let mut tool_response = Message::user();
+        let results = futures::future::join_all(
+            requests
+                .iter()
+                .map(|request| call_tool(capabilities, &tools, &goose_mode, artifacts, request)),
+        )
+        .await;
+        for (request, result) in requests.iter().zip(results) {
+            tool_response = tool_response.with_tool_response(request.id.clone(), result);
+        }
+        if turn == max_turns {
+            tool_response = tool_response.with_text(OUT_OF_TURNS);
+        }
+        messages.push(response);
+        messages.push(tool_response);
+    }
+    unreachable!("the last turn always returns")
+}
+
+/// The lead agent's tools the subagent may use, by the names or extensions given
+fn select_tools(tools: Vec<Tool>, names: Option<&[String]>) -> Result<Vec<Tool>, ToolError> {
+    let Some(names) = names else {
+        return Ok(tools);
+    };
+    let (selected, unknown): (Vec<_>, Vec<_>) = names.iter().partition(|name| {
+        tools
+            .iter()
+            .any(|tool| tool.name == **name || tool.name.starts_with(&format!("{}__", name)))
+    });
+    if !unknown.is_empty() {
+        let available: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        return Err(ToolError::InvalidParameters(format!(
+            "Unknown tools {:?}, the tools available are {:?}",
+            unknown, available
+        )));
+    }
+    Ok(tools
+        .into_iter()
+        .filter(|tool| {
+            selected
+                .iter()
+                .any(|name| tool.name == **name || tool.name.starts_with(&format!("{}__", name)))
+        })
+        .collect())
+}
+
+fn system_prompt(
+    capabilities: &Capabilities,
+    task: &SubagentTask,
+    max_turns: usize,
+    parallel: bool,
+) -> String {
+    let mut context: HashMap<&str, Value> = HashMap::new();
+    context.insert(
+        "extensions",
+        serde_json::to_value(capabilities.extensions_info()).unwrap(),
+    );
+    context.insert(
+        "current_date_time",
+        Value::String(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+    );
+    context.insert("max_turns", json!(max_turns));
+    context.insert("instructions", json!(task.instructions));
+    context.insert("parallel", json!(parallel));
+    prompt_template::render_global_file("subagent.md", &context).expect("Prompt should render")
+}
+
+/// Call a tool for the subagent, which can only use the tools it was given
+///
+/// Nobody can approve tool calls for a subagent, so outside of the `auto` mode it may only make
+/// the calls the user has already allowed.
+async fn call_tool(
+    capabilities: &Capabilities,
+    tools: &[Tool],
+    goose_mode: &str,
+    artifacts: Option<(&Artifacts, usize)>,
+    request: &ToolRequest,
+) -> ToolResult<Vec<Content>> {
+    let tool_call = request.tool_call.clone()?;
+    if !tools.iter().any(|tool| tool.name == tool_call.name) {
+        return Err(ToolError::NotFound(format!(
+            "{} isn't available to this subagent",
+            tool_call.name
+        )));
+    }
+    // The rules and permissions go by the name a renamed tool was listed with
+    let request = &capabilities.resolve_tool_request(request);
+    let tool_call = request.tool_call.clone()?;
+    if let Some((artifacts, subagent)) = artifacts {
+        if tool_call.name == WRITE_ARTIFACT_TOOL || tool_call.name == READ_ARTIFACT_TOOL {
+            return artifacts.call(subagent, &tool_call.name, tool_call.arguments);
+        }
+    }
+    if goose_mode == DRY_RUN_MODE {
+        return Ok(simulated_result(&tool_call));
+    }
+    let policy = match goose_mode {
+        "chat" => None,
+        _ => capabilities.tool_policy().check(&tool_call),
+    };
+    if policy == Some(PolicyAction::Never) {
+        capabilities.audit_declined(&tool_call, Approval::Refused);
+        return Err(policy_refusal(&tool_call.name));
+    }
+    if goose_mode != "chat"
+        && (policy == Some(PolicyAction::Ask)
+            || ApprovalRules::from_config().check(&tool_call).is_some())
+    {
+        return Err(ToolError::ExecutionError(format!(
+            "Calls like this one to {} need the user's approval, which a subagent can't ask \
+            for. Report this step back instead.",
+            tool_call.name
+        )));
+    }
+    if policy == Some(PolicyAction::Allow) {
+        return capabilities
+            .dispatch_approved_tool_call(tool_call, Approval::Policy)
+            .await;
+    }
+    match goose_mode {
+        "chat" => {
+            return Err(ToolError::ExecutionError(
+                "Tools can't be called in chat mode".to_string(),
+            ))
+        }
+        "approve" | "smart_approve" => {
+            let allowed = ToolPermissionStore::remembered(request)
+                .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+            if !allowed {
+                return Err(ToolError::ExecutionError(format!(
+                    "{} needs the user's approval, which a subagent can't ask for. Report \
+                    this step back instead.",
+                    tool_call.name
+                )));
+            }
+            return capabilities
+                .dispatch_approved_tool_call(tool_call, Approval::Remembered)
+                .await;
+        }
+        _ => {}
+    }
+    capabilities.dispatch_tool_call(tool_call).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelConfig;
+    use crate::providers::base::{Provider, ProviderMetadata, ProviderUsage, Usage};
+    use crate::providers::errors::ProviderError;
+    use mcp_core::tool::ToolCall;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// The system prompt, messages and number of tools of a completion
+    type Request = (String, Vec<Message>, usize);
+
+    /// Calls a tool, then sums up, recording what it was sent and how many completions ran at
+    /// once; tasks starting with "Share" share an artifact rather than running the tests
+    #[derive(Clone, Default)]
+    struct ScriptedProvider {
+        requests: Arc<Mutex<Vec<Request>>>,
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for ScriptedProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            ModelConfig::new("mock".to_string())
+        }
+
+        async fn complete(
+            &self,
+            system: &str,
+            messages: &[Message],
+            tools: &[Tool],
+        ) -> anyhow::Result<(Message, ProviderUsage), ProviderError> {
+            self.requests.lock().unwrap().push((
+                system.to_string(),
+                messages.to_vec(),
+                tools.len(),
+            ));
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            let sharing = messages[0].as_concat_text().starts_with("Share");
+            let response = match (messages.len(), sharing) {
+                (1, false) => Message::assistant().with_tool_request(
+                    "call_1",
+                    Ok(ToolCall::new(
+                        "developer__shell",
+                        json!({"command": "cargo test"}),
+                    )),
+                ),
+                (1, true) => Message::assistant().with_tool_request(
+                    "call_1",
+                    Ok(ToolCall::new(
+                        WRITE_ARTIFACT_TOOL,
+                        json!({"name": "api", "content": "fn parse(input: &str) -> Ast"}),
+                    )),
+                ),
+                (_, false) => Message::assistant().with_text("The tests pass"),
+                (_, true) => Message::assistant().with_text("Shared the new API"),
+            };
+            Ok((
+                response,
+                ProviderUsage::new("mock".to_string(), Usage::default()),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_select_tools() {
+        let tools = vec![
+            Tool::new("developer__shell", "", json!({})),
+            Tool::new("developer__text_editor", "", json!({})),
+            Tool::new("memory__remember", "", json!({})),
+        ];
+        let names =
+            |tools: Vec<Tool>| -> Vec<String> { tools.into_iter().map(|tool| tool.name).collect() };
+        assert_eq!(names(select_tools(tools.clone(), None).unwrap()).len(), 3);
+        assert_eq!(
+            names(select_tools(tools.clone(), Some(&["developer".to_string()])).unwrap()),
+            vec!["developer__shell", "developer__text_editor"]
+        );
+        assert_eq!(
+            names(select_tools(tools.clone(), Some(&["memory__remember".to_string()])).unwrap()),
+            vec!["memory__remember"]
+        );
+        assert!(select_tools(tools, Some(&["dev".to_string()])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_subagent() {
+        let provider = ScriptedProvider::default();
+        let capabilities = Capabilities::new(Box::new(provider.clone()));
+
+        let result = run_subagent(
+            &capabilities,
+            json!({
+                "task": "Run the tests",
+                "instructions": "Only report failures",
+                "tools": [],
+                "max_turns": 3
+            }),
+        )
+        .await
+        .unwrap();
+        let text = result[0].as_text().unwrap();
+        assert!(text.starts_with("The tests pass\n\n"));
+        assert!(text.contains("finished after 2 turns and 1 tool calls"));
+
+        let requests = provider.requests.lock().unwrap();
+        let (system, messages, tools) = &requests[1];
+        assert!(system.contains("Only report failures"));
+        assert!(system.contains("at most 3 replies"));
+        assert_eq!(*tools, 0);
+        assert_eq!(messages[0].as_concat_text(), "Run the tests");
+        // The subagent was told it can't use a tool it wasn't given
+        let response = messages[2].content[0].as_tool_response().unwrap();
+        assert!(matches!(response.tool_result, Err(ToolError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_subagent_out_of_turns() {
+        let provider = ScriptedProvider::default();
+        let capabilities = Capabilities::new(Box::new(provider.clone()));
+
+        let result = run_subagent(
+            &capabilities,
+            json!({"task": "Run the tests", "max_turns": 1}),
+        )
+        .await
+        .unwrap();
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .contains("finished after 2 turns"));
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].1[2].as_concat_text().contains(OUT_OF_TURNS));
+    }
+
+    #[tokio::test]
+    async fn test_run_subagents() {
+        let provider = ScriptedProvider::default();
+        let capabilities = Capabilities::new(Box::new(provider.clone()));
+        let task = |task: &str| SubagentTask {
+            task: task.to_string(),
+            instructions: None,
+            tools: Some(vec![]),
+            max_turns: None,
+        };
+        let tasks = [
+            task("Share the new parser API"),
+            task("Run the tests of goose"),
+            task("Run the tests of goose-cli"),
+        ];
+
+        let summary = run_all(&capabilities, &tasks, 2).await;
+        assert!(
+            summary.starts_with("## Subagent 1: Share the new parser API\n\nShared the new API")
+        );
+        assert!(summary.contains("## Subagent 2: Run the tests of goose\n\nThe tests pass"));
+        assert!(summary.contains("## Subagent 3: Run the tests of goose-cli\n\nThe tests pass"));
+        assert!(summary.ends_with(
+            "## Shared artifacts\n\n### api (from subagent 1)\n\nfn parse(input: &str) -> Ast"
+        ));
+        assert_eq!(provider.max_running.load(Ordering::SeqCst), 2);
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 6);
+        for (system, _, tools) in requests.iter() {
+            assert!(system.contains("Working alongside other subagents"));
+            // Only the artifact tools
+            assert_eq!(*tools, 2);
+        }
+    }
+
+    #[test]
+    fn test_artifacts() {
+        let artifacts = Artifacts::default();
+        let text =
+            |result: ToolResult<Vec<Content>>| result.unwrap()[0].as_text().unwrap().to_string();
+        assert_eq!(
+            text(artifacts.call(1, READ_ARTIFACT_TOOL, json!({}))),
+            "Nothing has been shared yet"
+        );
+        artifacts
+            .call(
+                2,
+                WRITE_ARTIFACT_TOOL,
+                json!({"name": "api", "content": "v1"}),
+            )
+            .unwrap();
+        artifacts
+            .call(
+                3,
+                WRITE_ARTIFACT_TOOL,
+                json!({"name": "api", "content": "v2"}),
+            )
+            .unwrap();
+        assert_eq!(
+            text(artifacts.call(1, READ_ARTIFACT_TOOL, json!({}))),
+            "api (from subagent 3)"
+        );
+        assert_eq!(
+            text(artifacts.call(1, READ_ARTIFACT_TOOL, json!({"name": "api"}))),
+            "v2"
+        );
+        assert!(artifacts
+            .call(1, READ_ARTIFACT_TOOL, json!({"name": "schema"}))
+            .is_err());
+        assert!(artifacts
+            .call(1, WRITE_ARTIFACT_TOOL, json!({"name": "schema"}))
+            .is_err());
+    }
+}
@@ -3,28 +3,51 @@
 /// truncation method. Still cannot read resources.
 use async_trait::async_trait;
 use futures::stream::BoxStream;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, instrument, warn};
 
 use super::agent::SessionConfig;
+use super::approval::{
+    approval_prompt, note_edit, ApprovalRules, ToolApproval, TOOL_CONFIRMATION_PROMPT,
+};
+use super::budget::{budget_reached, budget_warning, without_spend_notices, BudgetStatus, Spend};
 use super::capabilities::get_parameter_names;
+use super::compare::{self, Candidate, Comparison};
 use super::detect_read_only_tools;
+use super::dry_run::{simulated_result, DRY_RUN_MODE};
 use super::extension::ToolInfo;
+use super::final_output::max_repairs;
+use super::limits::{answer_stopped, stopped, stopped_call, RunLimits};
+use super::policy::{PolicyAction, POLICY_APPROVAL_PROMPT};
+use super::tool_list::ListedTools;
 use super::Agent;
 use crate::agents::capabilities::Capabilities;
 use crate::agents::extension::{ExtensionConfig, ExtensionResult};
+use crate::agents::ToolPermissionStore;
+use crate::audit::Approval;
+use crate::code_index;
 use crate::config::Config;
+use crate::knowledge;
+use crate::memory;
 use crate::memory_condense::condense_messages;
 use crate::message::{Message, ToolRequest};
+use crate::persona::Persona;
 use crate::providers::base::Provider;
 use crate::providers::errors::ProviderError;
+use crate::recipe::Recipe;
+use crate::redaction::Redactor;
 use crate::register_agent;
 use crate::session;
+use crate::session::replay::{Recorder, Recording};
 use crate::token_counter::TokenCounter;
+use crate::tool_output;
 use crate::truncate::{truncate_messages, OldestFirstTruncation};
+use crate::web_fetch;
 use anyhow::{anyhow, Result};
 use indoc::indoc;
 use mcp_core::prompt::Prompt;
@@ -39,8 +62,8 @@ const ESTIMATE_FACTOR_DECAY: f32 = 0.9;
 pub struct SummarizeAgent {
     capabilities: Mutex<Capabilities>,
     token_counter: TokenCounter,
-    confirmation_tx: mpsc::Sender<(String, bool)>, // (request_id, confirmed)
-    confirmation_rx: Mutex<mpsc::Receiver<(String, bool)>>,
+    confirmation_tx: mpsc::Sender<(String, ToolApproval)>, // (request_id, approval)
+    confirmation_rx: Mutex<mpsc::Receiver<(String, ToolApproval)>>,
 }
 
 impl SummarizeAgent {
@@ -158,21 +181,30 @@ impl Agent for SummarizeAgent {
 
     /// Handle a confirmation response for a tool request
     async fn handle_confirmation(&self, request_id: String, confirmed: bool) {
-        if let Err(e) = self.confirmation_tx.send((request_id, confirmed)).await {
+        self.handle_approval(request_id, confirmed.into()).await
+    }
+
+    async fn handle_approval(&self, request_id: String, approval: ToolApproval) {
+        if let Err(e) = self.confirmation_tx.send((request_id, approval)).await {
             error!("Failed to send confirmation: {}", e);
         }
     }
 
-    #[instrument(skip(self, messages, session), fields(user_message))]
+    #[instrument(skip(self, messages, session, cancel_token), fields(user_message))]
     async fn reply(
         &self,
         messages: &[Message],
         session: Option<SessionConfig>,
+        cancel_token: CancellationToken,
     ) -> anyhow::Result<BoxStream<'_, anyhow::Result<Message>>> {
         let mut messages = messages.to_vec();
         let reply_span = tracing::Span::current();
         let mut capabilities = self.capabilities.lock().await;
-        let mut tools = capabilities.get_prefixed_tools().await?;
+        capabilities.hand_off(&mut messages);
+        capabilities.set_session_id(session.as_ref().and_then(SessionConfig::name));
+        capabilities.set_working_dir(session.as_ref().map(|session| session.working_dir.clone()));
+        capabilities.set_reply_recipe(session.as_ref().and_then(|session| session.recipe.clone()));
+        let (mut listed_tools, mut tools) = ListedTools::list(&capabilities).await?;
         let mut truncation_attempt: usize = 0;
 
         // Load settings from config
@@ -223,8 +255,38 @@ impl Agent for SummarizeAgent {
             tools.push(read_resource_tool);
             tools.push(list_resources_tool);
         }
-
-        let system_prompt = capabilities.get_system_prompt().await;
+        if knowledge::search_tool_enabled() {
+            tools.push(knowledge::search_knowledge_tool());
+        }
+        if code_index::enabled() {
+            tools.push(code_index::search_code_tool());
+        }
+        if web_fetch::enabled() {
+            tools.push(web_fetch::fetch_url_tool());
+        }
+        // Goose's own tools too are limited to the ones a recipe being run allows
+        tools.retain(|tool| capabilities.allows_platform_tool(&tool.name));
+        if capabilities.limits_tool_outputs() {
+            tools.push(tool_output::read_tool_output_tool());
+        }
+        // Each reply gives its own value, which the caller reads once it is done
+        let final_output = capabilities.final_output();
+        if let Some(final_output) = &final_output {
+            final_output.clear();
+            tools.push(final_output.tool());
+        }
+        let mut repairs = 0;
+        let max_repairs = max_repairs(&goose_mode);
+
+        // Simple questions go to the small model, when routing is on
+        let route = capabilities.route(&messages).await;
+        let mut system_prompt = capabilities.get_system_prompt().await?;
+        if let Some(memories) = memory::recall_prompt(&messages).await {
+            system_prompt.push_str(&memories);
+        }
+        if let Some(passages) = knowledge::retrieval_prompt(&messages).await {
+            system_prompt.push_str(&passages);
+        }
 
         // Set the user_message field in the span instead of creating a new event
         if let Some(content) = messages
@@ -232,18 +294,98 @@ impl Agent for SummarizeAgent {
             .and_then(|msg| msg.content.first())
             .and_then(|c| c.as_text())
         {
-            debug!("user_message" = &content);
+            debug!("user_message" = &*Redactor::global().redact(content));
         }
 
+        let provider_name: String = Config::global()
+            .get_param("GOOSE_PROVIDER")
+            .unwrap_or_else(|_| "unknown".to_string());
+        let provider_name = route
+            .as_ref()
+            .and_then(|route| route.provider.clone())
+            .unwrap_or(provider_name);
+        let mut spend = Spend::start(session.as_ref(), capabilities.replaying());
+        let run_limits = RunLimits::from_config();
+        let approval_rules = ApprovalRules::from_config();
+        let run_started = std::time::Instant::now();
+        let (mut run_turns, mut run_tool_calls) = (0, 0);
+
         Ok(Box::pin(async_stream::try_stream! {
             let _reply_guard = reply_span.enter();
             loop {
-                match capabilities.provider().complete(
+                // Stop a run that has gone on for too long, whatever the model wants to do next
+                if let Some(reason) = run_limits.check(run_turns, run_tool_calls, run_started.elapsed()) {
+                    warn!("Run stopped: {}", reason);
+                    yield stopped(&reason);
+                    break;
+                }
+                run_turns += 1;
+
+                // Extensions that said their tools changed have them listed again
+                listed_tools.refresh(&capabilities, &mut tools).await;
+
+                // Check the budget before each completion, asking the user before going past a cap
+                match spend.check() {
+                    BudgetStatus::Exceeded(reason) => {
+                        let (request_id, request) = spend.confirmation_request(&reason);
+                        yield request;
+
+                        let mut confirmed = false;
+                        {
+                            let mut rx = self.confirmation_rx.lock().await;
+                            while let Some((req_id, approval)) = rx.recv().await {
+                                if req_id == request_id {
+                                    confirmed = approval == ToolApproval::Approve;
+                                    break;
+                                }
+                            }
+                        }
+
+                        if !confirmed {
+                            yield budget_reached(&reason);
+                            break;
+                        }
+                        spend.approve();
+                    }
+                    BudgetStatus::Warning(reason) => {
+                        yield budget_warning(&reason);
+                    }
+                    BudgetStatus::WithinBudget => {}
+                }
+
+                // After tool results that succeeded the worker model carries on, when one is set
+                let assignment = capabilities.assign_role(&messages);
+                // Notices of the spend are for the user, not the model
+                let provider_messages = without_spend_notices(Cow::Borrowed(&messages));
+                let provider = capabilities.provider();
+                let completion = cancel_token.run_until_cancelled(provider.complete_deduplicated(
                     &system_prompt,
-                    &messages,
+                    &provider_messages,
                     &tools,
-                ).await {
-                    Ok((response, usage)) => {
+                ));
+                let completion = match run_limits.within(run_started, completion).await {
+                    Ok(completion) => completion,
+                    Err(reason) => {
+                        warn!("Run stopped: {}", reason);
+                        yield stopped(&reason);
+                        break;
+                    }
+                };
+                let Some(completion) = completion else {
+                    break;
+                };
+                match completion {
+                    Ok((mut response, usage)) => {
+                        // persist the usage, and tell the user as soon as their spend crosses a configured threshold
+                        let completion_provider = assignment
+                            .as_ref()
+                            .and_then(|assignment| assignment.provider.as_deref())
+                            .unwrap_or(&provider_name);
+                        let role = assignment.as_ref().map(|assignment| assignment.role.as_str());
+                        for notice in spend.record(completion_provider, role, &usage) {
+                            yield notice;
+                        }
+
                         // record usage for the session in the session file
                         if let Some(session) = session.clone() {
                             // TODO: track session_id in langfuse tracing
@@ -251,6 +393,8 @@ impl Agent for SummarizeAgent {
                             let mut metadata = session::read_metadata(&session_file)?;
                             metadata.working_dir = session.working_dir;
                             metadata.total_tokens = usage.usage.total_tokens;
+                            metadata.accumulated_tokens = Some(spend.session().tokens);
+                            metadata.accumulated_cost = Some(spend.session().cost);
                             // The message count is the number of messages in the session + 1 for the response
                             // The message count does not include the tool response till next iteration
                             metadata.message_count = messages.len() + 1;
@@ -260,72 +404,57 @@ impl Agent for SummarizeAgent {
                         // Reset truncation attempt
                         truncation_attempt = 0;
 
+                        if let Some(route) = &route {
+                            route.annotate(&mut response);
+                        }
+                        if let Some(assignment) = &assignment {
+                            assignment.annotate(&mut response);
+                        }
                         // Yield the assistant's response
                         yield response.clone();
 
                         tokio::task::yield_now().await;
 
-                        // First collect any tool requests
-                        let tool_requests: Vec<&ToolRequest> = response.content
+                        // First collect any tool requests, calling renamed tools by the names they were listed with
+                        let resolved_requests: Vec<ToolRequest> = response.content
                             .iter()
                             .filter_map(|content| content.as_tool_request())
+                            .map(|request| capabilities.resolve_tool_request(request))
                             .collect();
+                        let mut tool_requests: Vec<&ToolRequest> = resolved_requests.iter().collect();
 
                         if tool_requests.is_empty() {
+                            // Ask again for a final output, with what was wrong with the last one
+                            if let Some(request) = final_output.as_ref().and_then(|final_output| final_output.repair_request(&response)) {
+                                if repairs < max_repairs {
+                                    repairs += 1;
+                                    yield request.clone();
+                                    messages.push(response);
+                                    messages.push(request);
+                                    continue;
+                                }
+                                warn!("The reply ended without a final output matching the response schema");
+                            }
                             break;
                         }
 
                         // Process tool requests depending on goose_mode
                         let mut message_tool_response = Message::user();
+
+                        // Calls past the limit on tool calls aren't made, and the run stops after this turn
+                        if let Some(left) = run_limits.tool_calls_left(run_tool_calls).filter(|&left| left < tool_requests.len()) {
+                            let reason = format!("the limit is {} tool calls", run_tool_calls + left);
+                            for request in tool_requests.split_off(left) {
+                                message_tool_response = message_tool_response.with_tool_response(
+                                    request.id.clone(),
+                                    Err(stopped_call(&reason)),
+                                );
+                            }
+                        }
+                        run_tool_calls += tool_requests.len();
                         // Clone goose_mode once before the match to avoid move issues
                         let mode = goose_mode.clone();
                         match mode.as_str() {
-                            "approve" => {
-                                let read_only_tools = detect_read_only_tools(&capabilities, tool_requests.clone()).await;
-                                for request in &tool_requests {
-                                    if let Ok(tool_call) = request.tool_call.clone() {
-                                        // Skip confirmation if the tool_call.name is in the read_only_tools list
-                                        if read_only_tools.contains(&tool_call.name) {
-                                            let output = capabilities.dispatch_tool_call(tool_call).await;
-                                                    message_tool_response = message_tool_response.with_tool_response(
-                                                        request.id.clone(),
-                                                        output,
-                                                    );
-                                        } else {
-                                            let confirmation = Message::user().with_tool_confirmation_request(
-                                                request.id.clone(),
-                                                tool_call.name.clone(),
-                                                tool_call.arguments.clone(),
-                                                Some("Goose would like to call the above tool. Allow? (y/n):".to_string()),
-                                            );
-                                            yield confirmation;
-
-                                            // Wait for confirmation response through the channel
-                                            let mut rx = self.confirmation_rx.lock().await;
-                                            // Loop the recv until we have a matched req_id due to potential duplicate messages.
-                                            while let Some((req_id, confirmed)) = rx.recv().await {
-                                                if req_id == request.id {
-                                                    if confirmed {
-                                                        // User approved - dispatch the tool call
-                                                        let output = capabilities.dispatch_tool_call(tool_call).await;
-                                                        message_tool_response = message_tool_response.with_tool_response(
-                                                            request.id.clone(),
-                                                            output,
-                                                        );
-                                                    } else {
-                                                        // User declined - add declined response
-                                                        message_tool_response = message_tool_response.with_tool_response(
-                                                            request.id.clone(),
-                                                            Ok(vec![Content::text("User declined to run this tool.")]),
-                                                        );
-                                                    }
-                                                    break; // Exit the loop once the matching `req_id` is found
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            },
                             "chat" => {
                                 // Skip all tool calls in chat mode
                                 for request in &tool_requests {
@@ -343,23 +472,117 @@ impl Agent for SummarizeAgent {
                                     );
                                 }
                             },
+                            DRY_RUN_MODE => {
+                                // Record the calls instead of making them
+                                for request in &tool_requests {
+                                    if let Ok(tool_call) = &request.tool_call {
+                                        message_tool_response = message_tool_response.with_tool_response(
+                                            request.id.clone(),
+                                            Ok(simulated_result(tool_call)),
+                                        );
+                                    }
+                                }
+                            }
                             _ => {
-                                if mode != "auto" {
+                                if mode != "auto" && mode != "approve" {
                                     warn!("Unknown GOOSE_MODE: {mode:?}. Defaulting to 'auto' mode.");
                                 }
-                                // Process tool requests in parallel
+                                let read_only_tools = if mode == "approve" {
+                                    detect_read_only_tools(&capabilities, tool_requests.clone()).await
+                                } else {
+                                    Vec::new()
+                                };
+                                let tool_policy = capabilities.tool_policy();
+                                let capabilities: &Capabilities = &capabilities;
                                 let mut tool_futures = Vec::new();
+                                let mut edited_arguments = HashMap::new();
                                 for request in &tool_requests {
-                                    if let Ok(tool_call) = request.tool_call.clone() {
-                                        tool_futures.push(async {
-                                            let output = capabilities.dispatch_tool_call(tool_call).await;
-                                            (request.id.clone(), output)
-                                        });
-                                    }
+                                    let Ok(tool_call) = request.tool_call.clone() else {
+                                        continue;
+                                    };
+                                    // The tool policy and the approval rules decide on calls before the mode does, and
+                                    // calls matching an approval rule wait for the user even if their tool was allowed
+                                    // before. The calls the policy refuses are refused when they are dispatched
+                                    let decision = match (tool_policy.check(&tool_call), approval_rules.check(&tool_call)) {
+                                        (Some(PolicyAction::Never), _) => Ok(Approval::Policy),
+                                        (_, Some(rule)) => Err(approval_prompt(rule)),
+                                        (Some(PolicyAction::Ask), None) => Err(POLICY_APPROVAL_PROMPT.to_string()),
+                                        (Some(PolicyAction::Allow), None) => Ok(Approval::Policy),
+                                        (None, None) if mode != "approve" => Ok(Approval::Auto),
+                                        // Calls like ones the user allowed before aren't asked about again
+                                        (None, None) if ToolPermissionStore::remembered(request)? => Ok(Approval::Remembered),
+                                        // Skip confirmation if the tool_call.name is in the read_only_tools list
+                                        (None, None) if read_only_tools.contains(&tool_call.name) => Ok(Approval::ReadOnly),
+                                        (None, None) => Err(TOOL_CONFIRMATION_PROMPT.to_string()),
+                                    };
+                                    let (tool_call, approval) = match decision {
+                                        Ok(approval) => (tool_call, approval),
+                                        Err(prompt) => {
+                                            let confirmation = Message::user().with_tool_confirmation_request(
+                                                request.id.clone(),
+                                                tool_call.name.clone(),
+                                                tool_call.arguments.clone(),
+                                                Some(prompt.clone()),
+                                            );
+                                            yield confirmation;
+
+                                            // Wait for confirmation response through the channel
+                                            let mut approval = ToolApproval::Deny;
+                                            let mut rx = self.confirmation_rx.lock().await;
+                                            // Loop the recv until we have a matched req_id due to potential duplicate messages.
+                                            while let Some((req_id, answer)) = rx.recv().await {
+                                                if req_id == request.id {
+                                                    approval = answer;
+                                                    break; // Exit the loop once the matching `req_id` is found
+                                                }
+                                            }
+                                            // Answers to the policy's and the rules' questions are asked again each time
+                                            if prompt == TOOL_CONFIRMATION_PROMPT {
+                                                ToolPermissionStore::remember(request, &approval)?;
+                                            }
+                                            let requested_call = tool_call.clone();
+                                            match approval.apply(tool_call) {
+                                                // Edited calls the policy refuses are refused when they are dispatched
+                                                Some(tool_call) if tool_call.arguments != requested_call.arguments => {
+                                                    edited_arguments.insert(request.id.clone(), tool_call.arguments.clone());
+                                                    (tool_call, Approval::Edited)
+                                                }
+                                                Some(tool_call) => (tool_call, Approval::User),
+                                                None => {
+                                                    // User declined - add declined response
+                                                    capabilities.audit_declined(&requested_call, Approval::Declined);
+                                                    message_tool_response = message_tool_response.with_tool_response(
+                                                        request.id.clone(),
+                                                        Ok(vec![Content::text("User declined to run this tool.")]),
+                                                    );
+                                                    continue;
+                                                }
+                                            }
+                                        }
+                                    };
+                                    let request_id = request.id.clone();
+                                    tool_futures.push(async move {
+                                        let output = capabilities.dispatch_approved_tool_call(tool_call, approval).await;
+                                        (request_id, output)
+                                    });
                                 }
-                                // Wait for all tool calls to complete
-                                let results = futures::future::join_all(tool_futures).await;
+                                // Wait for all tool calls to complete, unless the reply is cancelled or the run's time is up
+                                let tool_calls = cancel_token.run_until_cancelled(capabilities.run_tool_calls(tool_futures));
+                                let results = match run_limits.within(run_started, tool_calls).await {
+                                    Ok(Some(results)) => results,
+                                    Ok(None) => break,
+                                    Err(reason) => {
+                                        warn!("Run stopped: {}", reason);
+                                        yield answer_stopped(message_tool_response, &resolved_requests, &reason);
+                                        yield stopped(&reason);
+                                        break;
+                                    }
+                                };
                                 for (request_id, output) in results {
+                                    let output = match edited_arguments.get(&request_id) {
+                                        Some(arguments) => note_edit(output, arguments),
+                                        None => output,
+                                    };
                                     message_tool_response = message_tool_response.with_tool_response(
                                         request_id,
                                         output,
@@ -428,6 +651,11 @@ impl Agent for SummarizeAgent {
         capabilities.set_system_prompt_override(template);
     }
 
+    async fn system_prompt(&self) -> Result<String> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.get_system_prompt().await
+    }
+
     async fn list_extension_prompts(&self) -> HashMap<String, Vec<Prompt>> {
         let capabilities = self.capabilities.lock().await;
         capabilities
@@ -460,7 +688,7 @@ impl Agent for SummarizeAgent {
     }
 
     async fn get_plan_prompt(&self) -> anyhow::Result<String> {
-        let mut capabilities = self.capabilities.lock().await;
+        let capabilities = self.capabilities.lock().await;
         let tools = capabilities.get_prefixed_tools().await?;
         let tools_info = tools
             .into_iter()
@@ -472,10 +700,56 @@ impl Agent for SummarizeAgent {
         Ok(plan_prompt)
     }
 
+    async fn compare(
+        &self,
+        messages: &[Message],
+        candidates: &[Candidate],
+    ) -> Result<Vec<Comparison>> {
+        compare::compare_turn(&self.capabilities, messages, candidates).await
+    }
+
     async fn provider(&self) -> Arc<Box<dyn Provider>> {
         let capabilities = self.capabilities.lock().await;
         capabilities.provider()
     }
+
+    async fn update_provider(&mut self, provider: Box<dyn Provider>) {
+        self.token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_provider(provider);
+    }
+
+    async fn record(&mut self, recorder: Arc<Recorder>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_recorder(recorder);
+    }
+
+    async fn replay(&mut self, recording: Arc<Recording>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_replay(recording);
+    }
+
+    async fn set_recipe(&mut self, recipe: Option<Recipe>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_recipe(recipe);
+    }
+
+    async fn set_response_schema(&mut self, schema: Option<Value>) -> Result<()> {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_response_schema(schema)
+    }
+
+    async fn final_output(&self) -> Option<Value> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities
+            .final_output()
+            .and_then(|final_output| final_output.output())
+    }
+
+    async fn set_persona(&mut self, persona: Option<Persona>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_persona(persona);
+    }
 }
 
 register_agent!("summarize", SummarizeAgent);
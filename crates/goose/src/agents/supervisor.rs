@@ -0,0 +1,204 @@
+//! Restarting local extensions whose server crashed or hung
+//!
+//! The server of a stdio or builtin extension is a child process, which can exit or stop
+//! answering partway through a session. Each is pinged every `GOOSE_EXTENSION_HEARTBEAT`
+//! seconds, 30 by default, while no call to it is running. When a ping or a tool call fails
+//! and the server doesn't answer a ping after it, the process is started again, waiting
+//! longer after each attempt that fails, and initialized, and its tools are listed again. A
+//! tool call that failed that way is made once more on the new server, so the session goes on
+//! as if nothing happened.
+//!
+//! Remote extensions reconnect on their own, in their transports.
+use mcp_client::client::{Error as ClientError, McpClientTrait};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::capabilities::start_client;
+use super::extension::{ExtensionConfig, ExtensionError, ExtensionResult};
+use super::sampling::Sampler;
+use crate::config::Config;
+
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// How long a server has to answer a ping before it is taken for hung
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait before starting a server again, doubled after each attempt that fails
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RESTART_ATTEMPTS: u32 = 3;
+
+/// Whether the server of `config` is a process goose started, which it can start again
+pub fn is_supervised(config: &ExtensionConfig) -> bool {
+    matches!(
+        config,
+        ExtensionConfig::Stdio { .. } | ExtensionConfig::Builtin { .. }
+    )
+}
+
+/// Whether `error` could come from a server that is gone, rather than one that answered
+pub fn is_failure(error: &ClientError) -> bool {
+    matches!(
+        error,
+        ClientError::Transport(_)
+            | ClientError::McpServerError { .. }
+            | ClientError::NotReady
+            | ClientError::Timeout(_)
+    )
+}
+
+/// Whether the server answers a ping in time
+pub async fn is_alive(client: &dyn McpClientTrait) -> bool {
+    matches!(
+        tokio::time::timeout(PING_TIMEOUT, client.ping()).await,
+        Ok(Ok(()))
+    )
+}
+
+/// Start the server of `config` again in place of `client`, up to [`MAX_RESTART_ATTEMPTS`]
+/// times, and list its tools to check it serves them
+pub async fn restart(
+    config: &ExtensionConfig,
+    sampler: &Arc<Sampler>,
+    client: &mut Box<dyn McpClientTrait>,
+) -> ExtensionResult<()> {
+    let mut attempt = 0;
+    loop {
+        tokio::time::sleep(restart_delay(attempt)).await;
+        attempt += 1;
+        let started = match start_client(config, sampler).await {
+            Ok((started, _)) => started
+                .list_tools(None)
+                .await
+                .map(|_| started)
+                .map_err(ExtensionError::from),
+            Err(e) => Err(e),
+        };
+        match started {
+            Ok(started) => {
+                tracing::info!("Restarted the {} extension", config.name());
+                *client = started;
+                return Ok(());
+            }
+            Err(e) if attempt < MAX_RESTART_ATTEMPTS => {
+                warn!(
+                    "Failed to restart the {} extension, attempt {}: {}",
+                    config.name(),
+                    attempt,
+                    e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// How long to wait before the restart after `attempt` ones that failed
+fn restart_delay(attempt: u32) -> Duration {
+    RESTART_DELAY.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+/// The time between pings, or None when GOOSE_EXTENSION_HEARTBEAT turns them off
+pub fn heartbeat_interval() -> Option<Duration> {
+    match Config::global().get_param::<u64>("GOOSE_EXTENSION_HEARTBEAT") {
+        Ok(0) => None,
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => Some(DEFAULT_HEARTBEAT),
+    }
+}
+
+/// Ping the server of `config` every `interval`, restarting it when it stops answering, until
+/// the extension is removed
+pub fn spawn_heartbeat(
+    config: ExtensionConfig,
+    sampler: Arc<Sampler>,
+    client: Weak<RwLock<Box<dyn McpClientTrait>>>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+            // A call that is running will find a dead server itself
+            let Ok(mut guard) = client.try_write() else {
+                continue;
+            };
+            if is_alive(guard.as_ref()).await {
+                continue;
+            }
+            warn!(
+                "The {} extension stopped answering; restarting it",
+                config.name()
+            );
+            if let Err(e) = restart(&config, &sampler, &mut guard).await {
+                warn!("Failed to restart the {} extension: {}", config.name(), e);
+            }
+        }
+    });
+}
+
+/// Ping `client`, the extension `config` describes, to restart it when it stops answering
+pub fn supervise(
+    config: &ExtensionConfig,
+    sampler: &Arc<Sampler>,
+    client: &Arc<RwLock<Box<dyn McpClientTrait>>>,
+) {
+    if let Some(interval) = heartbeat_interval() {
+        spawn_heartbeat(
+            config.clone(),
+            Arc::clone(sampler),
+            Arc::downgrade(client),
+            interval,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_client::transport::Error as TransportError;
+
+    #[test]
+    fn test_is_failure() {
+        assert!(is_failure(&ClientError::Transport(
+            TransportError::ChannelClosed
+        )));
+        assert!(is_failure(&ClientError::McpServerError {
+            method: "tools/call".to_string(),
+            server: "developer".to_string(),
+            source: Box::new(TransportError::StdioProcessError("exited".to_string())),
+        }));
+        // The server answered, so it is running
+        assert!(!is_failure(&ClientError::RpcError {
+            code: -32602,
+            message: "Invalid params".to_string(),
+        }));
+        assert!(!is_failure(&ClientError::UnexpectedResponse(
+            "missing result".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_restart_delay() {
+        assert_eq!(restart_delay(0), Duration::from_secs(1));
+        assert_eq!(restart_delay(2), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_is_supervised() {
+        assert!(is_supervised(&ExtensionConfig::stdio(
+            "github", "npx", "GitHub", 300u64
+        )));
+        assert!(is_supervised(&ExtensionConfig::default()));
+        assert!(!is_supervised(&ExtensionConfig::sse(
+            "jira",
+            "https://mcp.example.com/sse",
+            "Jira",
+            300u64
+        )));
+    }
+}
@@ -0,0 +1,127 @@
+//! Listing the tools of extensions again when they change
+//!
+//! A server that adds or removes tools while it runs, such as one with a tool for each project
+//! the user opens, sends `notifications/tools/list_changed`. Each one moves a generation on, and
+//! a reply that listed the extensions' tools at an earlier generation lists them again before
+//! its next completion, so the model can call a tool in the same session it was added.
+use async_trait::async_trait;
+use mcp_client::RequestHandler;
+use mcp_core::protocol::{
+    JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, TOOLS_LIST_CHANGED_NOTIFICATION,
+};
+use mcp_core::Tool;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::capabilities::Capabilities;
+use super::extension::ExtensionResult;
+
+/// How many times an extension has said its tools changed
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Hears that an extension's tools changed, leaving everything else to `inner`
+struct ListChangedHandler {
+    inner: Arc<dyn RequestHandler>,
+}
+
+/// `inner`, also hearing when the extension's tools change
+pub(super) fn with_list_changed(inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+    Arc::new(ListChangedHandler { inner })
+}
+
+#[async_trait]
+impl RequestHandler for ListChangedHandler {
+    async fn handle(&self, request: JsonRpcRequest) -> JsonRpcMessage {
+        self.inner.handle(request).await
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) {
+        if notification.method == TOOLS_LIST_CHANGED_NOTIFICATION {
+            GENERATION.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.inner.notify(notification).await;
+        }
+    }
+}
+
+/// The extensions' tools among those a reply gives the model, and when they were listed
+pub struct ListedTools {
+    generation: u64,
+    names: HashSet<String>,
+}
+
+impl ListedTools {
+    /// List the tools of the extensions, to which a reply adds its own
+    pub async fn list(capabilities: &Capabilities) -> ExtensionResult<(Self, Vec<Tool>)> {
+        // Read first, so a change while listing is listed again
+        let generation = generation();
+        let tools = capabilities.get_prefixed_tools().await?;
+        let names = tools.iter().map(|tool| tool.name.clone()).collect();
+        Ok((Self { generation, names }, tools))
+    }
+
+    /// Swap the extensions' tools in `tools` for the current ones when an extension said they
+    /// changed since they were listed, returning whether they were swapped
+    ///
+    /// Tools that can't be listed again stay as they were, and are listed again before the next
+    /// completion, rather than ending the reply.
+    pub async fn refresh(&mut self, capabilities: &Capabilities, tools: &mut Vec<Tool>) -> bool {
+        if generation() == self.generation {
+            return false;
+        }
+        let (listed, current) = match Self::list(capabilities).await {
+            Ok(listed) => listed,
+            Err(e) => {
+                tracing::warn!("Failed to list the tools of extensions again: {}", e);
+                return false;
+            }
+        };
+        tools.retain(|tool| !self.names.contains(&tool.name));
+        tools.splice(0..0, current);
+        *self = listed;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ignore;
+
+    #[async_trait]
+    impl RequestHandler for Ignore {
+        async fn handle(&self, _request: JsonRpcRequest) -> JsonRpcMessage {
+            JsonRpcMessage::Nil
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_changed() {
+        let handler = with_list_changed(Arc::new(Ignore));
+        let before = generation();
+        handler
+            .notify(JsonRpcNotification {
+                jsonrpc: "2.0".to_string(),
+                method: TOOLS_LIST_CHANGED_NOTIFICATION.to_string(),
+                params: None,
+            })
+            .await;
+        assert!(generation() > before);
+
+        let before = generation();
+        handler
+            .notify(JsonRpcNotification {
+                jsonrpc: "2.0".to_string(),
+                method: "notifications/resources/list_changed".to_string(),
+                params: None,
+            })
+            .await;
+        assert_eq!(generation(), before);
+    }
+}
@@ -2,47 +2,75 @@
 /// It makes no attempt to handle context limits, and cannot read resources
 use async_trait::async_trait;
 use futures::stream::BoxStream;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, instrument, warn};
 
 use super::agent::SessionConfig;
+use super::approval::{
+    approval_prompt, note_edit, ApprovalRules, ToolApproval, TOOL_CONFIRMATION_PROMPT,
+};
+use super::budget::{budget_reached, budget_warning, without_spend_notices, BudgetStatus, Spend};
+use super::compare::{self, Candidate, Comparison};
+use super::critic::{revision_request, Critic, Review};
 use super::detect_read_only_tools;
+use super::dry_run::{simulated_result, DRY_RUN_MODE};
 use super::extension::ToolInfo;
+use super::final_output::max_repairs;
+use super::limits::{answer_stopped, stopped, stopped_call, RunLimits};
+use super::policy::{policy_refusal, PolicyAction, POLICY_APPROVAL_PROMPT};
+use super::subagent::{spawn_subagent_tool, spawn_subagents_tool, subagents_enabled};
+use super::tool_list::ListedTools;
 use super::Agent;
 use crate::agents::capabilities::{get_parameter_names, Capabilities};
 use crate::agents::extension::{ExtensionConfig, ExtensionResult};
 use crate::agents::ToolPermissionStore;
+use crate::audit::Approval;
+use crate::code_index;
+use crate::compaction::{compacted_messages, Compaction};
 use crate::config::Config;
-use crate::message::{Message, ToolRequest};
+use crate::knowledge;
+use crate::memory;
+use crate::message::{Message, MessageContent, ToolRequest};
+use crate::persona::Persona;
+use crate::prompt_compression::PromptCompression;
 use crate::providers::base::Provider;
 use crate::providers::errors::ProviderError;
 use crate::providers::toolshim::{
     augment_message_with_tool_calls, modify_system_prompt_for_tool_json, OllamaInterpreter,
 };
+use crate::recipe::Recipe;
+use crate::redaction::Redactor;
 use crate::register_agent;
 use crate::session;
+use crate::session::checkpoint::{checkpoint_turn, CHECKPOINTS_CONFIG_KEY};
+use crate::session::replay::{Recorder, Recording};
 use crate::token_counter::TokenCounter;
+use crate::tool_output;
 use crate::truncate::{truncate_messages, OldestFirstTruncation};
+use crate::web_fetch;
 use anyhow::{anyhow, Result};
 use indoc::indoc;
 use mcp_core::prompt::Prompt;
 use mcp_core::protocol::GetPromptResult;
 use mcp_core::{tool::Tool, Content, ToolError};
 use serde_json::{json, Value};
-use std::time::Duration;
 
 const MAX_TRUNCATION_ATTEMPTS: usize = 3;
 const ESTIMATE_FACTOR_DECAY: f32 = 0.9;
 
+const DECLINED_RESPONSE: &str = "User declined to run this tool. Don't try to make the same tool call again. If there is no other ways to do it, it is ok to stop.";
+
 /// Truncate implementation of an Agent
 pub struct TruncateAgent {
     capabilities: Mutex<Capabilities>,
     token_counter: TokenCounter,
-    confirmation_tx: mpsc::Sender<(String, bool)>, // (request_id, confirmed)
-    confirmation_rx: Mutex<mpsc::Receiver<(String, bool)>>,
+    confirmation_tx: mpsc::Sender<(String, ToolApproval)>, // (request_id, the user's answer)
+    confirmation_rx: Mutex<mpsc::Receiver<(String, ToolApproval)>>,
 }
 
 impl TruncateAgent {
@@ -118,8 +146,11 @@ impl TruncateAgent {
         capabilities: &Capabilities,
         tool_call: mcp_core::tool::ToolCall,
         request_id: String,
+        approval: Approval,
     ) -> (String, Result<Vec<Content>, ToolError>) {
-        let output = capabilities.dispatch_tool_call(tool_call).await;
+        let output = capabilities
+            .dispatch_approved_tool_call(tool_call, approval)
+            .await;
         (request_id, output)
     }
 }
@@ -154,27 +185,48 @@ impl Agent for TruncateAgent {
 
     /// Handle a confirmation response for a tool request
     async fn handle_confirmation(&self, request_id: String, confirmed: bool) {
-        if let Err(e) = self.confirmation_tx.send((request_id, confirmed)).await {
+        self.handle_approval(request_id, confirmed.into()).await
+    }
+
+    async fn handle_approval(&self, request_id: String, approval: ToolApproval) {
+        if let Err(e) = self.confirmation_tx.send((request_id, approval)).await {
             error!("Failed to send confirmation: {}", e);
         }
     }
 
-    #[instrument(skip(self, messages, session), fields(user_message))]
+    #[instrument(skip(self, messages, session, cancel_token), fields(user_message))]
     async fn reply(
         &self,
         messages: &[Message],
         session: Option<SessionConfig>,
+        cancel_token: CancellationToken,
     ) -> anyhow::Result<BoxStream<'_, anyhow::Result<Message>>> {
         let mut messages = messages.to_vec();
         let reply_span = tracing::Span::current();
         let mut capabilities = self.capabilities.lock().await;
-        let mut tools = capabilities.get_prefixed_tools().await?;
+        capabilities.hand_off(&mut messages);
+        capabilities.set_session_id(session.as_ref().and_then(SessionConfig::name));
+        capabilities.set_working_dir(session.as_ref().map(|session| session.working_dir.clone()));
+        capabilities.set_reply_recipe(session.as_ref().and_then(|session| session.recipe.clone()));
+        let (mut listed_tools, mut tools) = ListedTools::list(&capabilities).await?;
         let mut truncation_attempt: usize = 0;
 
         // Load settings from config
         let config = Config::global();
         let goose_mode = config.get_param("GOOSE_MODE").unwrap_or("auto".to_string());
 
+        // Snapshot the files before the turn changes them, so the session can be rolled back
+        if let Some(session) = session.as_ref().filter(|_| !capabilities.replaying()) {
+            if config.get_param(CHECKPOINTS_CONFIG_KEY).unwrap_or(false) {
+                let session_file = session::get_path(session.id.clone());
+                if let Err(e) =
+                    checkpoint_turn(&session_file, &session.working_dir, &messages).await
+                {
+                    warn!("Failed to checkpoint the working directory: {}", e);
+                }
+            }
+        }
+
         // we add in the 2 resource tools if any extensions support resources
         // TODO: make sure there is no collision with another extension's tool name
         let read_resource_tool = Tool::new(
@@ -219,11 +271,57 @@ impl Agent for TruncateAgent {
             tools.push(read_resource_tool);
             tools.push(list_resources_tool);
         }
+        if knowledge::search_tool_enabled() {
+            tools.push(knowledge::search_knowledge_tool());
+        }
+        if code_index::enabled() {
+            tools.push(code_index::search_code_tool());
+        }
+        if web_fetch::enabled() {
+            tools.push(web_fetch::fetch_url_tool());
+        }
+        if capabilities.limits_tool_outputs() {
+            tools.push(tool_output::read_tool_output_tool());
+        }
+        // Each reply gives its own value, which the caller reads once it is done
+        let final_output = capabilities.final_output();
+        if let Some(final_output) = &final_output {
+            final_output.clear();
+            tools.push(final_output.tool());
+        }
+        let mut repairs = 0;
+        let max_repairs = max_repairs(&goose_mode);
+
+        if goose_mode != "chat" && subagents_enabled() {
+            tools.push(spawn_subagent_tool());
+            tools.push(spawn_subagents_tool());
+        }
+        // Goose's own tools too are limited to the ones a recipe being run allows
+        tools.retain(|tool| capabilities.allows_platform_tool(&tool.name));
 
+        // Simple questions go to the small model, when routing is on
+        let route = capabilities.route(&messages).await;
         let config = capabilities.provider().get_model_config();
-        let mut system_prompt = capabilities.get_system_prompt().await;
+        let model_capabilities = capabilities.provider().capabilities();
+        // Fall back to prompted tool calling for models without native function calling
+        let use_toolshim =
+            config.toolshim || (!model_capabilities.supports_tools && !tools.is_empty());
+        if use_toolshim && !config.toolshim {
+            warn!(
+                "Model {} does not support tool calling, interpreting tool calls with toolshim",
+                config.model_name
+            );
+        }
+        let mut system_prompt = capabilities.get_system_prompt().await?;
+        if let Some(memories) = memory::recall_prompt(&messages).await {
+            system_prompt.push_str(&memories);
+        }
+        if let Some(passages) = knowledge::retrieval_prompt(&messages).await {
+            system_prompt.push_str(&passages);
+        }
+        let prompt_without_tools = system_prompt.clone();
         let mut toolshim_tools = vec![];
-        if config.toolshim {
+        if use_toolshim {
             // If tool interpretation is enabled, modify the system prompt to instruct to return JSON tool requests
             system_prompt = modify_system_prompt_for_tool_json(&system_prompt, &tools);
             // make a copy of tools before empty
@@ -238,26 +336,173 @@ impl Agent for TruncateAgent {
             .and_then(|msg| msg.content.first())
             .and_then(|c| c.as_text())
         {
-            debug!("user_message" = &content);
+            debug!("user_message" = &*Redactor::global().redact(content));
         }
 
+        let provider_name: String = Config::global()
+            .get_param("GOOSE_PROVIDER")
+            .unwrap_or_else(|_| "unknown".to_string());
+        let provider_name = route
+            .as_ref()
+            .and_then(|route| route.provider.clone())
+            .unwrap_or(provider_name);
+        let mut spend = Spend::start(session.as_ref(), capabilities.replaying());
+        let session_metadata = session.as_ref().and_then(|session| {
+            session::read_metadata(&session::get_path(session.id.clone())).ok()
+        });
+        let compaction = Compaction::from_config();
+        let (mut conversation_summary, pinned_messages) = session_metadata
+            .map(|metadata| (metadata.compaction, metadata.pinned_messages))
+            .unwrap_or_default();
+        let context_limit = config.context_limit();
+        // Truncation drops messages from the start, after which a synopsis no longer lines up
+        let mut history_truncated = false;
+        let compression = PromptCompression::from_config();
+        let critic = Critic::from_config();
+        let mut critiques = 0;
+        let run_limits = RunLimits::from_config();
+        let approval_rules = ApprovalRules::from_config();
+        let tool_policy = capabilities.tool_policy();
+        let run_started = std::time::Instant::now();
+        let (mut run_turns, mut run_tool_calls) = (0, 0);
+
         Ok(Box::pin(async_stream::try_stream! {
             let _reply_guard = reply_span.enter();
             loop {
-                match capabilities.provider().complete(
+                // Stop a run that has gone on for too long, whatever the model wants to do next
+                if let Some(reason) = run_limits.check(run_turns, run_tool_calls, run_started.elapsed()) {
+                    warn!("Run stopped: {}", reason);
+                    yield stopped(&reason);
+                    break;
+                }
+                run_turns += 1;
+
+                // Extensions that said their tools changed have them listed again
+                if use_toolshim {
+                    if listed_tools.refresh(&capabilities, &mut toolshim_tools).await {
+                        system_prompt = modify_system_prompt_for_tool_json(&prompt_without_tools, &toolshim_tools);
+                    }
+                } else {
+                    listed_tools.refresh(&capabilities, &mut tools).await;
+                }
+
+                // Check the budget before each completion, asking the user before going past a cap
+                match spend.check() {
+                    BudgetStatus::Exceeded(reason) => {
+                        let (request_id, request) = spend.confirmation_request(&reason);
+                        yield request;
+
+                        let mut confirmed = false;
+                        {
+                            let mut rx = self.confirmation_rx.lock().await;
+                            while let Some((req_id, approval)) = rx.recv().await {
+                                if req_id == request_id {
+                                    confirmed = approval == ToolApproval::Approve;
+                                    break;
+                                }
+                            }
+                        }
+
+                        if !confirmed {
+                            yield budget_reached(&reason);
+                            break;
+                        }
+                        spend.approve();
+                    }
+                    BudgetStatus::Warning(reason) => {
+                        yield budget_warning(&reason);
+                    }
+                    BudgetStatus::WithinBudget => {}
+                }
+
+                // Once the conversation nears the context limit its older turns are summarized,
+                // and the synopsis is sent in their place
+                let provider_messages = match &compaction {
+                    Some(compaction) if !history_truncated => {
+                        let mut compacted = match &conversation_summary {
+                            Some(summary) => Cow::Owned(compacted_messages(&messages, summary, &pinned_messages)),
+                            None => Cow::Borrowed(&messages),
+                        };
+                        let tokens = self.token_counter.count_chat_tokens(&system_prompt, &compacted, &tools);
+                        let summarized = conversation_summary.as_ref().map_or(0, |summary| summary.message_count);
+                        if let Some(split) = compaction.should_compact(tokens, context_limit).then(|| compaction.split_point(&messages, summarized)).flatten() {
+                            let provider = capabilities.provider();
+                            match compaction.summarize(provider.as_ref().as_ref(), &self.token_counter, &messages, conversation_summary.as_ref(), split).await {
+                                Ok((summary, usage)) => {
+                                    debug!("Compacted the first {} messages of the conversation", summary.message_count);
+                                    for notice in usage.iter().flat_map(|usage| spend.record(&provider_name, None, usage)) {
+                                        yield notice;
+                                    }
+                                    if let Some(session) = session.as_ref() {
+                                        let session_file = session::get_path(session.id.clone());
+                                        let mut metadata = session::read_metadata(&session_file)?;
+                                        metadata.compaction = Some(summary.clone());
+                                        session::update_metadata(&session_file, &metadata).await?;
+                                    }
+                                    compacted = Cow::Owned(compacted_messages(&messages, &summary, &pinned_messages));
+                                    conversation_summary = Some(summary);
+                                }
+                                Err(e) => warn!("Failed to compact the conversation: {}", e),
+                            }
+                        }
+                        compacted
+                    }
+                    _ => Cow::Borrowed(&messages),
+                };
+                // Models without vision can't accept the images that tools like screen capture return
+                let provider_messages = if model_capabilities.supports_vision {
+                    provider_messages
+                } else {
+                    Cow::Owned(replace_images(&provider_messages))
+                };
+                // Older turns can be compressed to save prompt tokens, the history keeps the full text
+                let provider_messages = match &compression {
+                    Some(compression) => Cow::Owned(compression.compress_messages(&provider_messages)),
+                    None => provider_messages,
+                };
+                // Notices of the spend are for the user, not the model
+                let provider_messages = without_spend_notices(provider_messages);
+                // After tool results that succeeded the worker model carries on, when one is set
+                let assignment = capabilities.assign_role(&messages);
+                let provider = capabilities.provider();
+                let completion = cancel_token.run_until_cancelled(provider.complete_deduplicated(
                     &system_prompt,
-                    &messages,
+                    &provider_messages,
                     &tools,
-                ).await {
+                ));
+                let completion = match run_limits.within(run_started, completion).await {
+                    Ok(completion) => completion,
+                    Err(reason) => {
+                        warn!("Run stopped: {}", reason);
+                        yield stopped(&reason);
+                        break;
+                    }
+                };
+                // Dropping the completion aborts the request to the provider
+                let Some(completion) = completion else {
+                    break;
+                };
+                match completion {
                     Ok((mut response, usage)) => {
                         // Post-process / structure the response only if tool interpretation is enabled
-                        if config.toolshim {
+                        if use_toolshim {
                             let interpreter = OllamaInterpreter::new()
                                 .map_err(|e| anyhow::anyhow!("Failed to create OllamaInterpreter: {}", e))?;
 
                             response = augment_message_with_tool_calls(&interpreter, response, &toolshim_tools).await?;
                         }
 
+                        // persist the usage so it survives restarts and can be queried later, and tell
+                        // the user as soon as their spend crosses a configured threshold
+                        let completion_provider = assignment
+                            .as_ref()
+                            .and_then(|assignment| assignment.provider.as_deref())
+                            .unwrap_or(&provider_name);
+                        let role = assignment.as_ref().map(|assignment| assignment.role.as_str());
+                        for notice in spend.record(completion_provider, role, &usage) {
+                            yield notice;
+                        }
+
                         // record usage for the session in the session file
                         if let Some(session) = session.clone() {
                             // TODO: track session_id in langfuse tracing
@@ -265,6 +510,8 @@ impl Agent for TruncateAgent {
                             let mut metadata = session::read_metadata(&session_file)?;
                             metadata.working_dir = session.working_dir;
                             metadata.total_tokens = usage.usage.total_tokens;
+                            metadata.accumulated_tokens = Some(spend.session().tokens);
+                            metadata.accumulated_cost = Some(spend.session().cost);
                             // The message count is the number of messages in the session + 1 for the response
                             // The message count does not include the tool response till next iteration
                             metadata.message_count = messages.len() + 1;
@@ -274,25 +521,161 @@ impl Agent for TruncateAgent {
                         // Reset truncation attempt
                         truncation_attempt = 0;
 
+                        if let Some(route) = &route {
+                            route.annotate(&mut response);
+                        }
+                        if let Some(assignment) = &assignment {
+                            assignment.annotate(&mut response);
+                        }
                         // Yield the assistant's response
                         yield response.clone();
 
                         tokio::task::yield_now().await;
 
-                        // First collect any tool requests
-                        let tool_requests: Vec<&ToolRequest> = response.content
+                        // First collect any tool requests, calling renamed tools by the names they were listed with
+                        let resolved_requests: Vec<ToolRequest> = response.content
                             .iter()
                             .filter_map(|content| content.as_tool_request())
+                            .map(|request| capabilities.resolve_tool_request(request))
                             .collect();
+                        let mut tool_requests: Vec<&ToolRequest> = resolved_requests.iter().collect();
 
                         if tool_requests.is_empty() {
+                            // Have the draft answer reviewed, and revise it for the critique
+                            if let Some(critic) = critic.as_ref().filter(|critic| critiques < critic.max_iterations) {
+                                critiques += 1;
+                                let provider = capabilities.provider();
+                                let review = critic.review(provider.as_ref().as_ref(), &messages, &response).await;
+                                if let Ok((_, usage)) = &review {
+                                    let critic_provider = critic.provider_name().unwrap_or(&provider_name);
+                                    for notice in spend.record(critic_provider, None, usage) {
+                                        yield notice;
+                                    }
+                                }
+                                match review {
+                                    Ok((Review::Revise(critique), _)) => {
+                                        let request = revision_request(&critique);
+                                        yield request.clone();
+                                        messages.push(response);
+                                        messages.push(request);
+                                        continue;
+                                    }
+                                    Ok((Review::Approved, _)) => {}
+                                    Err(e) => warn!("Failed to review the answer: {}", e),
+                                }
+                            }
+                            // Ask again for a final output, with what was wrong with the last one
+                            if let Some(request) = final_output.as_ref().and_then(|final_output| final_output.repair_request(&response)) {
+                                if repairs < max_repairs {
+                                    repairs += 1;
+                                    yield request.clone();
+                                    messages.push(response);
+                                    messages.push(request);
+                                    continue;
+                                }
+                                warn!("The reply ended without a final output matching the response schema");
+                            }
                             break;
                         }
 
                         // Process tool requests depending on goose_mode
                         let mut message_tool_response = Message::user();
+
+                        // Calls past the limit on tool calls aren't made, and the run stops after this turn
+                        if let Some(left) = run_limits.tool_calls_left(run_tool_calls).filter(|&left| left < tool_requests.len()) {
+                            let reason = format!("the limit is {} tool calls", run_tool_calls + left);
+                            for request in tool_requests.split_off(left) {
+                                message_tool_response = message_tool_response.with_tool_response(
+                                    request.id.clone(),
+                                    Err(stopped_call(&reason)),
+                                );
+                            }
+                        }
+                        run_tool_calls += tool_requests.len();
+
                         // Clone goose_mode once before the match to avoid move issues
                         let mode = goose_mode.clone();
+                        let mut tool_futures = Vec::new();
+                        let mut edited_arguments = HashMap::new();
+
+                        // The tool policy and the approval rules decide on calls before the mode does,
+                        // and calls matching an approval rule wait for the user even if their tool was allowed before
+                        let tool_requests = if mode == "chat" || mode == DRY_RUN_MODE || (approval_rules.is_empty() && tool_policy.is_empty()) {
+                            tool_requests
+                        } else {
+                            let mut undecided = Vec::new();
+                            for request in tool_requests {
+                                let Ok(tool_call) = request.tool_call.clone() else {
+                                    undecided.push(request);
+                                    continue;
+                                };
+                                let prompt = match (tool_policy.check(&tool_call), approval_rules.check(&tool_call)) {
+                                    (Some(PolicyAction::Never), _) => {
+                                        capabilities.audit_declined(&tool_call, Approval::Refused);
+                                        message_tool_response = message_tool_response.with_tool_response(
+                                            request.id.clone(),
+                                            Err(policy_refusal(&tool_call.name)),
+                                        );
+                                        continue;
+                                    }
+                                    (_, Some(rule)) => approval_prompt(rule),
+                                    (Some(PolicyAction::Ask), None) => POLICY_APPROVAL_PROMPT.to_string(),
+                                    (Some(PolicyAction::Allow), None) => {
+                                        tool_futures.push(Self::create_tool_future(&capabilities, tool_call, request.id.clone(), Approval::Policy));
+                                        continue;
+                                    }
+                                    (None, None) => {
+                                        undecided.push(request);
+                                        continue;
+                                    }
+                                };
+                                yield Message::user().with_tool_confirmation_request(
+                                    request.id.clone(),
+                                    tool_call.name.clone(),
+                                    tool_call.arguments.clone(),
+                                    Some(prompt),
+                                );
+
+                                let mut approval = ToolApproval::Deny;
+                                {
+                                    let mut rx = self.confirmation_rx.lock().await;
+                                    while let Some((req_id, answer)) = rx.recv().await {
+                                        if req_id == request.id {
+                                            approval = answer;
+                                            break;
+                                        }
+                                    }
+                                }
+                                let requested_call = tool_call.clone();
+                                match approval.apply(tool_call) {
+                                    // The user can't edit a call into one the policy refuses
+                                    Some(tool_call) if tool_policy.check(&tool_call) == Some(PolicyAction::Never) => {
+                                        capabilities.audit_declined(&tool_call, Approval::Refused);
+                                        message_tool_response = message_tool_response.with_tool_response(
+                                            request.id.clone(),
+                                            Err(policy_refusal(&tool_call.name)),
+                                        );
+                                    }
+                                    Some(tool_call) => {
+                                        let approval = if tool_call.arguments != requested_call.arguments {
+                                            edited_arguments.insert(request.id.clone(), tool_call.arguments.clone());
+                                            Approval::Edited
+                                        } else {
+                                            Approval::User
+                                        };
+                                        tool_futures.push(Self::create_tool_future(&capabilities, tool_call, request.id.clone(), approval));
+                                    }
+                                    None => {
+                                        capabilities.audit_declined(&requested_call, Approval::Declined);
+                                        message_tool_response = message_tool_response.with_tool_response(
+                                            request.id.clone(),
+                                            Ok(vec![Content::text(DECLINED_RESPONSE)]),
+                                        );
+                                    }
+                                }
+                            }
+                            undecided
+                        };
                         match mode.as_str() {
                             "approve" | "smart_approve" => {
                                 let mut read_only_tools = Vec::new();
@@ -300,16 +683,11 @@ impl Agent for TruncateAgent {
                                 let mut approved_tools = Vec::new();
 
                                 // First check permissions for all tools
-                                let store = ToolPermissionStore::load()?;
                                 for request in tool_requests.iter() {
                                     if let Ok(tool_call) = request.tool_call.clone() {
-                                        if let Some(allowed) = store.check_permission(request) {
-                                            if allowed {
-                                                // Instead of executing immediately, collect approved tools
-                                                approved_tools.push((request.id.clone(), tool_call));
-                                            } else {
-                                                needs_confirmation.push(request);
-                                            }
+                                        if ToolPermissionStore::remembered(request)? {
+                                            // Instead of executing immediately, collect approved tools
+                                            approved_tools.push((request.id.clone(), tool_call));
                                         } else {
                                             needs_confirmation.push(request);
                                         }
@@ -322,11 +700,10 @@ impl Agent for TruncateAgent {
                                 }
 
                                 // Handle pre-approved and read-only tools in parallel
-                                let mut tool_futures = Vec::new();
 
                                 // Add pre-approved tools
                                 for (request_id, tool_call) in approved_tools {
-                                    let tool_future = Self::create_tool_future(&capabilities, tool_call, request_id.clone());
+                                    let tool_future = Self::create_tool_future(&capabilities, tool_call, request_id.clone(), Approval::Remembered);
                                     tool_futures.push(tool_future);
                                 }
 
@@ -335,34 +712,41 @@ impl Agent for TruncateAgent {
                                     if let Ok(tool_call) = request.tool_call.clone() {
                                         // Skip confirmation if the tool_call.name is in the read_only_tools list
                                         if read_only_tools.contains(&tool_call.name) {
-                                            let tool_future = Self::create_tool_future(&capabilities, tool_call, request.id.clone());
+                                            let tool_future = Self::create_tool_future(&capabilities, tool_call, request.id.clone(), Approval::ReadOnly);
                                             tool_futures.push(tool_future);
                                         } else {
                                             let confirmation = Message::user().with_tool_confirmation_request(
                                                 request.id.clone(),
                                                 tool_call.name.clone(),
                                                 tool_call.arguments.clone(),
-                                                Some("Goose would like to call the above tool. Allow? (y/n):".to_string()),
+                                                Some(TOOL_CONFIRMATION_PROMPT.to_string()),
                                             );
                                             yield confirmation;
 
                                             // Wait for confirmation response through the channel
                                             let mut rx = self.confirmation_rx.lock().await;
-                                            while let Some((req_id, confirmed)) = rx.recv().await {
+                                            while let Some((req_id, approval)) = rx.recv().await {
                                                 if req_id == request.id {
-                                                    // Store the user's response with 30-day expiration
-                                                    let mut store = ToolPermissionStore::load()?;
-                                                    store.record_permission(request, confirmed, Some(Duration::from_secs(30 * 24 * 60 * 60)))?;
+                                                    // Remember the user's answer, unless they changed the call
+                                                    ToolPermissionStore::remember(request, &approval)?;
 
-                                                    if confirmed {
+                                                    let audited = if let ToolApproval::Edit(arguments) = &approval {
+                                                        edited_arguments.insert(request.id.clone(), arguments.clone());
+                                                        Approval::Edited
+                                                    } else {
+                                                        Approval::User
+                                                    };
+                                                    let requested_call = tool_call.clone();
+                                                    if let Some(tool_call) = approval.apply(tool_call) {
                                                         // Add this tool call to the futures collection
-                                                        let tool_future = Self::create_tool_future(&capabilities, tool_call, request.id.clone());
+                                                        let tool_future = Self::create_tool_future(&capabilities, tool_call, request.id.clone(), audited);
                                                         tool_futures.push(tool_future);
                                                     } else {
                                                         // User declined - add declined response
+                                                        capabilities.audit_declined(&requested_call, Approval::Declined);
                                                         message_tool_response = message_tool_response.with_tool_response(
                                                             request.id.clone(),
-                                                            Ok(vec![Content::text("User declined to run this tool. Don't try to make the same tool call again. If there is no other ways to do it, it is ok to stop.")]),
+                                                            Ok(vec![Content::text(DECLINED_RESPONSE)]),
                                                         );
                                                     }
                                                     break; // Exit the loop once the matching `req_id` is found
@@ -371,14 +755,6 @@ impl Agent for TruncateAgent {
                                         }
                                     }
                                 }
-                                // Wait for all tool calls to complete
-                                let results = futures::future::join_all(tool_futures).await;
-                                for (request_id, output) in results {
-                                    message_tool_response = message_tool_response.with_tool_response(
-                                        request_id,
-                                        output,
-                                    );
-                                }
                             },
                             "chat" => {
                                 // Skip all tool calls in chat mode
@@ -398,29 +774,54 @@ impl Agent for TruncateAgent {
                                     );
                                 }
                             },
+                            DRY_RUN_MODE => {
+                                // Record the calls instead of making them
+                                for request in &tool_requests {
+                                    if let Ok(tool_call) = &request.tool_call {
+                                        message_tool_response = message_tool_response.with_tool_response(
+                                            request.id.clone(),
+                                            Ok(simulated_result(tool_call)),
+                                        );
+                                    }
+                                }
+                            },
                             _ => {
                                 if mode != "auto" {
                                     warn!("Unknown GOOSE_MODE: {mode:?}. Defaulting to 'auto' mode.");
                                 }
                                 // Process tool requests in parallel
-                                let mut tool_futures = Vec::new();
                                 for request in &tool_requests {
                                     if let Ok(tool_call) = request.tool_call.clone() {
-                                        let tool_future = Self::create_tool_future(&capabilities, tool_call, request.id.clone());
+                                        let tool_future = Self::create_tool_future(&capabilities, tool_call, request.id.clone(), Approval::Auto);
                                         tool_futures.push(tool_future);
                                     }
                                 }
-                                // Wait for all tool calls to complete
-                                let results = futures::future::join_all(tool_futures).await;
-                                for (request_id, output) in results {
-                                    message_tool_response = message_tool_response.with_tool_response(
-                                        request_id,
-                                        output,
-                                    );
-                                }
                             }
                         }
 
+                        // Wait for all tool calls to complete, unless the reply is cancelled or the run's time is up
+                        let tool_calls = cancel_token.run_until_cancelled(capabilities.run_tool_calls(tool_futures));
+                        let results = match run_limits.within(run_started, tool_calls).await {
+                            Ok(Some(results)) => results,
+                            Ok(None) => break,
+                            Err(reason) => {
+                                warn!("Run stopped: {}", reason);
+                                yield answer_stopped(message_tool_response, &resolved_requests, &reason);
+                                yield stopped(&reason);
+                                break;
+                            }
+                        };
+                        for (request_id, output) in results {
+                            let output = match edited_arguments.get(&request_id) {
+                                Some(arguments) => note_edit(output, arguments),
+                                None => output,
+                            };
+                            message_tool_response = message_tool_response.with_tool_response(
+                                request_id,
+                                output,
+                            );
+                        }
+
                         yield message_tool_response.clone();
 
                         messages.push(response);
@@ -445,6 +846,7 @@ impl Agent for TruncateAgent {
                         // release the lock before truncation to prevent deadlock
                         drop(capabilities);
 
+                        history_truncated = true;
                         if let Err(err) = self.truncate_messages(&mut messages, estimate_factor, &system_prompt, &mut tools).await {
                             yield Message::assistant().with_text(format!("Error: Unable to truncate messages to stay within context limit. \n\nRan into this error: {}.\n\nPlease start a new session with fresh context and try again.", err));
                             break;
@@ -481,6 +883,11 @@ impl Agent for TruncateAgent {
         capabilities.set_system_prompt_override(template);
     }
 
+    async fn system_prompt(&self) -> Result<String> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.get_system_prompt().await
+    }
+
     async fn list_extension_prompts(&self) -> HashMap<String, Vec<Prompt>> {
         let capabilities = self.capabilities.lock().await;
         capabilities
@@ -513,7 +920,7 @@ impl Agent for TruncateAgent {
     }
 
     async fn get_plan_prompt(&self) -> anyhow::Result<String> {
-        let mut capabilities = self.capabilities.lock().await;
+        let capabilities = self.capabilities.lock().await;
         let tools = capabilities.get_prefixed_tools().await?;
         let tools_info = tools
             .into_iter()
@@ -525,10 +932,117 @@ impl Agent for TruncateAgent {
         Ok(plan_prompt)
     }
 
+    async fn compare(
+        &self,
+        messages: &[Message],
+        candidates: &[Candidate],
+    ) -> Result<Vec<Comparison>> {
+        compare::compare_turn(&self.capabilities, messages, candidates).await
+    }
+
     async fn provider(&self) -> Arc<Box<dyn Provider>> {
         let capabilities = self.capabilities.lock().await;
         capabilities.provider()
     }
+
+    async fn update_provider(&mut self, provider: Box<dyn Provider>) {
+        self.token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_provider(provider);
+    }
+
+    async fn record(&mut self, recorder: Arc<Recorder>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_recorder(recorder);
+    }
+
+    async fn replay(&mut self, recording: Arc<Recording>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_replay(recording);
+    }
+
+    async fn set_recipe(&mut self, recipe: Option<Recipe>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_recipe(recipe);
+    }
+
+    async fn set_response_schema(&mut self, schema: Option<Value>) -> Result<()> {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_response_schema(schema)
+    }
+
+    async fn final_output(&self) -> Option<Value> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities
+            .final_output()
+            .and_then(|final_output| final_output.output())
+    }
+
+    async fn set_persona(&mut self, persona: Option<Persona>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_persona(persona);
+    }
 }
 
 register_agent!("truncate", TruncateAgent);
+
+const IMAGE_OMITTED: &str = "[Image omitted: the current model does not support image inputs]";
+
+/// Replace image content, including images returned by tools, with a text placeholder
+fn replace_images(messages: &[Message]) -> Vec<Message> {
+    messages
+        .iter()
+        .map(|message| {
+            let mut message = message.clone();
+            for content in message.content.iter_mut() {
+                match content {
+                    MessageContent::Image(_) => *content = MessageContent::text(IMAGE_OMITTED),
+                    MessageContent::ToolResponse(response) => {
+                        if let Ok(contents) = response.tool_result.as_mut() {
+                            for item in contents.iter_mut() {
+                                if let Content::Image(_) = item {
+                                    *item = Content::text(IMAGE_OMITTED);
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            message
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_images() {
+        let messages = vec![
+            Message::user()
+                .with_text("What is in this picture?")
+                .with_image("abc", "image/png"),
+            Message::user().with_tool_response(
+                "1",
+                Ok(vec![
+                    Content::text("captured"),
+                    Content::image("abc", "image/png"),
+                ]),
+            ),
+        ];
+
+        let replaced = replace_images(&messages);
+        assert_eq!(
+            replaced[0].content[0].as_text(),
+            Some("What is in this picture?")
+        );
+        assert_eq!(replaced[0].content[1].as_text(), Some(IMAGE_OMITTED));
+
+        let response = replaced[1].content[0].as_tool_response().unwrap();
+        let contents = response.tool_result.as_ref().unwrap();
+        assert_eq!(contents[0].as_text(), Some("captured"));
+        assert_eq!(contents[1].as_text(), Some(IMAGE_OMITTED));
+    }
+}
@@ -0,0 +1,509 @@
+//! An append-only log of the tool calls the agent makes
+//!
+//! With `GOOSE_AUDIT_LOG` set to true, every tool call the model asks for is recorded in
+//! `audit.db` in the goose data dir: the tool, its arguments with secrets redacted, the session,
+//! how the call was approved or declined, how it ended, how long it ran and how many attempts it
+//! took. The database refuses to change or delete its rows, so it shows what the agent actually
+//! did when a session is reviewed afterwards. A call is recorded as running when it is made, and
+//! its outcome added as a row of its own once it ends, so a call that never ended, such as when
+//! goose was killed while it ran, still shows. `goose audit` lists the calls, each at its latest
+//! row.
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use etcetera::{choose_app_strategy, AppStrategy};
+use mcp_core::{Content, ToolCall, ToolResult};
+use once_cell::sync::OnceCell;
+use rusqlite::{params, Connection, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::redaction::Redactor;
+
+/// How the call to a tool came to be made, or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Approval {
+    /// Made without asking, as the mode allows
+    Auto,
+    /// Allowed by a tool policy
+    Policy,
+    /// Allowed by the user's answer for an earlier call like it
+    Remembered,
+    /// Made without asking because it only reads, in smart approve mode
+    ReadOnly,
+    /// Approved by the user
+    User,
+    /// Approved by the user with arguments they changed
+    Edited,
+    /// Declined by the user
+    Declined,
+    /// Refused by a tool policy
+    Refused,
+}
+
+/// How a tool call ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Made and not ended, or goose stopped before it did
+    Running,
+    Success,
+    Error,
+    /// Declined or refused, so the tool never ran
+    NotCalled,
+}
+
+impl FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        from_column(&s.to_lowercase().replace('-', "_")).map_err(|_| {
+            anyhow::anyhow!(
+                "Unknown outcome '{}', expected one of running, success, error, not_called",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for Approval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_column(self))
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_column(self))
+    }
+}
+
+/// The name an approval or outcome is stored as
+fn to_column(value: impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn from_column<T: DeserializeOwned>(name: &str) -> serde_json::Result<T> {
+    serde_json::from_value(Value::String(name.to_string()))
+}
+
+/// One tool call, as stored in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Ties the rows recorded for the same call together
+    pub call_id: String,
+    /// When the call was asked for
+    pub timestamp: DateTime<Utc>,
+    /// Name of the session the call belonged to, if any
+    pub session_id: Option<String>,
+    /// The prefixed name of the tool, such as `developer__shell`
+    pub tool: String,
+    pub arguments: Value,
+    pub approval: Approval,
+    pub outcome: Outcome,
+    /// What went wrong, for calls that failed
+    pub error: Option<String>,
+    /// How long the call ran, None for calls that weren't made or haven't ended
+    pub duration_ms: Option<i64>,
+    /// How many times the call was made, counting the ones made again after a restart or a
+    /// failure on the way to the tool, None for calls that weren't made or haven't ended
+    pub attempts: Option<i64>,
+}
+
+impl AuditRecord {
+    /// A call to `tool_call` that hasn't run, with the secrets in its arguments redacted
+    pub fn new(session_id: Option<String>, tool_call: &ToolCall, approval: Approval) -> Self {
+        let mut arguments = tool_call.arguments.clone();
+        Redactor::global().redact_value(&mut arguments);
+        Self {
+            call_id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            session_id,
+            tool: tool_call.name.clone(),
+            arguments,
+            approval,
+            outcome: Outcome::NotCalled,
+            error: None,
+            duration_ms: None,
+            attempts: None,
+        }
+    }
+
+    /// The call, made and not ended yet
+    pub fn running(mut self) -> Self {
+        self.outcome = Outcome::Running;
+        self
+    }
+
+    /// The call, having run for `duration` over `attempts` with `result`
+    pub fn finished(
+        mut self,
+        result: &ToolResult<Vec<Content>>,
+        duration: Duration,
+        attempts: u32,
+    ) -> Self {
+        self.duration_ms = Some(duration.as_millis() as i64);
+        self.attempts = Some(attempts as i64);
+        match result {
+            Ok(_) => self.outcome = Outcome::Success,
+            Err(e) => {
+                self.outcome = Outcome::Error;
+                self.error = Some(Redactor::global().redact(&e.to_string()).into_owned());
+            }
+        }
+        self
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let timestamp: i64 = row.get("timestamp")?;
+        let arguments: String = row.get("arguments")?;
+        let approval: String = row.get("approval")?;
+        let outcome: String = row.get("outcome")?;
+        // Rows from before calls had ids are calls of their own
+        let id: i64 = row.get("id")?;
+        Ok(Self {
+            call_id: row
+                .get::<_, Option<String>>("call_id")?
+                .unwrap_or_else(|| id.to_string()),
+            timestamp: Utc
+                .timestamp_millis_opt(timestamp)
+                .single()
+                .unwrap_or_default(),
+            session_id: row.get("session_id")?,
+            tool: row.get("tool")?,
+            arguments: serde_json::from_str(&arguments).unwrap_or(Value::String(arguments)),
+            approval: from_column(&approval).unwrap_or(Approval::Auto),
+            outcome: from_column(&outcome).unwrap_or(Outcome::Error),
+            error: row.get("error")?,
+            duration_ms: row.get("duration_ms")?,
+            attempts: row.get("attempts")?,
+        })
+    }
+}
+
+/// Which calls to list from the audit log, all of them by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    pub session_id: Option<String>,
+    /// A prefixed tool name, or an extension name for all of its tools
+    pub tool: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub outcome: Option<Outcome>,
+    /// Only the latest calls, this many
+    pub limit: Option<usize>,
+}
+
+/// Tool calls persisted in a local SQLite database that can only be added to
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+impl AuditLog {
+    /// Open (creating if needed) the audit database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open an in-memory audit database, which is discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tool_calls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                session_id TEXT,
+                tool TEXT NOT NULL,
+                arguments TEXT NOT NULL,
+                approval TEXT NOT NULL,
+                outcome TEXT NOT NULL,
+                error TEXT,
+                duration_ms INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_tool_calls_timestamp ON tool_calls(timestamp);
+            CREATE INDEX IF NOT EXISTS idx_tool_calls_session ON tool_calls(session_id);
+            CREATE TRIGGER IF NOT EXISTS tool_calls_no_update BEFORE UPDATE ON tool_calls
+            BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS tool_calls_no_delete BEFORE DELETE ON tool_calls
+            BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;",
+        )?;
+        // Logs made before calls were recorded as they started get the columns that tie the
+        // rows of a call together, which adds to them without changing any row
+        let columns = conn
+            .prepare("SELECT name FROM pragma_table_info('tool_calls')")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (column, kind) in [("call_id", "TEXT"), ("attempts", "INTEGER")] {
+            if !columns.iter().any(|name| name == column) {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE tool_calls ADD COLUMN {} {};",
+                    column, kind
+                ))?;
+            }
+        }
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_tool_calls_call ON tool_calls(call_id);",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Default location of the audit database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("audit.db")
+    }
+
+    /// The shared log at the default path, when `GOOSE_AUDIT_LOG` is on
+    ///
+    /// Returns None if it is off or the database couldn't be opened, in which case tool calls
+    /// aren't recorded
+    pub fn global() -> Option<&'static AuditLog> {
+        static LOG: OnceCell<Option<AuditLog>> = OnceCell::new();
+        LOG.get_or_init(|| {
+            if !Config::global()
+                .get_param::<bool>("GOOSE_AUDIT_LOG")
+                .unwrap_or(false)
+            {
+                return None;
+            }
+            match Self::open(&Self::default_path()) {
+                Ok(log) => Some(log),
+                Err(e) => {
+                    tracing::warn!("Failed to open the audit log: {}", e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+    }
+
+    pub fn record(&self, record: &AuditRecord) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO tool_calls (call_id, timestamp, session_id, tool, arguments, approval,
+                outcome, error, duration_ms, attempts)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                record.call_id,
+                record.timestamp.timestamp_millis(),
+                record.session_id,
+                record.tool,
+                record.arguments.to_string(),
+                to_column(record.approval),
+                to_column(record.outcome),
+                record.error,
+                record.duration_ms,
+                record.attempts,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// List the calls `query` picks, oldest first, each as its latest row
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT * FROM tool_calls
+            WHERE timestamp >= ?1
+                AND (call_id IS NULL OR id = (
+                    SELECT MAX(later.id) FROM tool_calls later
+                    WHERE later.call_id = tool_calls.call_id
+                ))
+                AND (?2 IS NULL OR session_id = ?2)
+                AND (?3 IS NULL OR tool = ?3 OR tool LIKE ?3 || '\\_\\_%' ESCAPE '\\')
+                AND (?4 IS NULL OR outcome = ?4)
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5",
+        )?;
+        let mut records = stmt
+            .query_map(
+                params![
+                    query.since.map(|t| t.timestamp_millis()).unwrap_or(0),
+                    query.session_id,
+                    query.tool,
+                    query.outcome.map(to_column),
+                    query.limit.map(|limit| limit as i64).unwrap_or(-1),
+                ],
+                AuditRecord::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        records.reverse();
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::ToolError;
+    use serde_json::json;
+
+    fn call(tool: &str) -> ToolCall {
+        ToolCall::new(tool, json!({"command": "ls"}))
+    }
+
+    #[test]
+    fn test_record_and_query() -> Result<()> {
+        let log = AuditLog::open_in_memory()?;
+        let session = Some("20250101_120000".to_string());
+        log.record(
+            &AuditRecord::new(session.clone(), &call("developer__shell"), Approval::User).finished(
+                &Ok(vec![]),
+                Duration::from_millis(1500),
+                2,
+            ),
+        )?;
+        log.record(
+            &AuditRecord::new(
+                session.clone(),
+                &call("developer__text_editor"),
+                Approval::Auto,
+            )
+            .finished(
+                &Err(ToolError::ExecutionError("no such file".to_string())),
+                Duration::from_millis(3),
+                1,
+            ),
+        )?;
+        log.record(&AuditRecord::new(
+            None,
+            &call("github__create_issue"),
+            Approval::Declined,
+        ))?;
+
+        let all = log.query(&AuditQuery::default())?;
+        let tools: Vec<&str> = all.iter().map(|record| record.tool.as_str()).collect();
+        assert_eq!(
+            tools,
+            [
+                "developer__shell",
+                "developer__text_editor",
+                "github__create_issue"
+            ]
+        );
+        assert_eq!(all[0].arguments, json!({"command": "ls"}));
+        assert_eq!(all[0].duration_ms, Some(1500));
+        assert_eq!(all[0].attempts, Some(2));
+        assert_eq!(all[1].outcome, Outcome::Error);
+        assert_eq!(
+            all[1].error.as_deref(),
+            Some("Execution failed: no such file")
+        );
+        assert_eq!(all[2].approval, Approval::Declined);
+        assert_eq!(all[2].outcome, Outcome::NotCalled);
+        assert_eq!(all[2].duration_ms, None);
+
+        let developer = log.query(&AuditQuery {
+            tool: Some("developer".to_string()),
+            ..Default::default()
+        })?;
+        assert_eq!(developer.len(), 2);
+        let failed = log.query(&AuditQuery {
+            session_id: session,
+            outcome: Some(Outcome::Error),
+            ..Default::default()
+        })?;
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].tool, "developer__text_editor");
+        let latest = log.query(&AuditQuery {
+            limit: Some(2),
+            ..Default::default()
+        })?;
+        assert_eq!(latest[1].tool, "github__create_issue");
+        assert_eq!(latest.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_is_listed_at_its_latest_row() -> Result<()> {
+        let log = AuditLog::open_in_memory()?;
+        let running = AuditRecord::new(None, &call("developer__shell"), Approval::Auto).running();
+        log.record(&running)?;
+        let calls = log.query(&AuditQuery::default())?;
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].outcome, Outcome::Running);
+
+        log.record(&running.finished(&Ok(vec![]), Duration::from_millis(20), 1))?;
+        let calls = log.query(&AuditQuery::default())?;
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].outcome, Outcome::Success);
+        let running = log.query(&AuditQuery {
+            outcome: Some(Outcome::Running),
+            ..Default::default()
+        })?;
+        assert!(running.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_from_before_call_ids() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE tool_calls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                session_id TEXT,
+                tool TEXT NOT NULL,
+                arguments TEXT NOT NULL,
+                approval TEXT NOT NULL,
+                outcome TEXT NOT NULL,
+                error TEXT,
+                duration_ms INTEGER
+            );
+            INSERT INTO tool_calls (timestamp, tool, arguments, approval, outcome)
+            VALUES (0, 'developer__shell', '{}', 'auto', 'success');",
+        )?;
+        let log = AuditLog::with_connection(conn)?;
+        log.record(&AuditRecord::new(
+            None,
+            &call("developer__shell"),
+            Approval::Declined,
+        ))?;
+        let calls = log.query(&AuditQuery::default())?;
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].call_id, "1");
+        assert_eq!(calls[0].attempts, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_is_append_only() -> Result<()> {
+        let log = AuditLog::open_in_memory()?;
+        log.record(&AuditRecord::new(
+            None,
+            &call("developer__shell"),
+            Approval::Auto,
+        ))?;
+        let conn = log.conn.lock().unwrap();
+        assert!(conn
+            .execute("UPDATE tool_calls SET outcome = 'success'", [])
+            .is_err());
+        assert!(conn.execute("DELETE FROM tool_calls", []).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_outcome_from_str() {
+        assert_eq!("not-called".parse::<Outcome>().unwrap(), Outcome::NotCalled);
+        assert!("maybe".parse::<Outcome>().is_err());
+    }
+}
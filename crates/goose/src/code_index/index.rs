@@ -0,0 +1,292 @@
+use anyhow::Result;
+use etcetera::{choose_app_strategy, AppStrategy};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use super::symbols::{parse_source, Lang, ParsedFile, Symbol, SymbolKind};
+use super::{score, words, Definition, IndexedFile, MAX_FILE_BYTES};
+use crate::knowledge::{walk, IndexReport};
+
+/// The definitions in the source files of projects, in a local SQLite database
+///
+/// Projects are kept apart by their root directory, and paths within them are relative to it.
+pub struct CodeIndex {
+    conn: Mutex<Connection>,
+}
+
+impl CodeIndex {
+    /// Open (creating if needed) the index database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open an in-memory index, which is discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS files (
+                root TEXT NOT NULL,
+                path TEXT NOT NULL,
+                modified INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                language TEXT NOT NULL,
+                doc TEXT,
+                PRIMARY KEY (root, path)
+            );
+            CREATE TABLE IF NOT EXISTS symbols (
+                root TEXT NOT NULL,
+                path TEXT NOT NULL,
+                name TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                container TEXT,
+                start_line INTEGER NOT NULL,
+                end_line INTEGER NOT NULL,
+                signature TEXT NOT NULL,
+                doc TEXT
+            );
+            CREATE INDEX IF NOT EXISTS symbols_path ON symbols (root, path);",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Default location of the index database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("code_index.db")
+    }
+
+    /// Bring the index of the project at `root` up to date, parsing the source files that
+    /// changed since they were indexed
+    pub fn update(&self, root: &Path) -> Result<IndexReport> {
+        let root_key = root.to_string_lossy().to_string();
+        let mut report = IndexReport::default();
+        let mut seen = HashSet::new();
+        for path in walk(root) {
+            let Some(lang) = Lang::from_path(&path) else {
+                continue;
+            };
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().to_string();
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            seen.insert(relative.clone());
+            let size = metadata.len() as i64;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_nanos() as i64)
+                .unwrap_or_default();
+            if self.is_current(&root_key, &relative, modified, size)? {
+                report.unchanged += 1;
+                continue;
+            }
+
+            // Large files are mostly generated or minified, and bundles are no help to search
+            let parsed = (metadata.len() <= MAX_FILE_BYTES)
+                .then(|| std::fs::read_to_string(&path).ok())
+                .flatten()
+                .and_then(|source| parse_source(lang, &source));
+            match parsed {
+                Some(parsed) => {
+                    self.put_file(&root_key, &relative, modified, size, lang, &parsed)?;
+                    report.indexed += 1;
+                }
+                None => {
+                    // Kept without definitions, so it isn't read again until it changes
+                    let empty = ParsedFile {
+                        doc: None,
+                        symbols: Vec::new(),
+                    };
+                    self.put_file(&root_key, &relative, modified, size, lang, &empty)?;
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        for file in self.files(root)? {
+            let relative = file.path.to_string_lossy().to_string();
+            if !seen.contains(&relative) {
+                self.remove_file(&root_key, &relative)?;
+                report.removed += 1;
+            }
+        }
+        Ok(report)
+    }
+
+    fn is_current(&self, root: &str, path: &str, modified: i64, size: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let indexed: Option<(i64, i64)> = conn
+            .query_row(
+                "SELECT modified, size FROM files WHERE root = ?1 AND path = ?2",
+                params![root, path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(indexed == Some((modified, size)))
+    }
+
+    fn put_file(
+        &self,
+        root: &str,
+        path: &str,
+        modified: i64,
+        size: i64,
+        lang: Lang,
+        parsed: &ParsedFile,
+    ) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM symbols WHERE root = ?1 AND path = ?2",
+            params![root, path],
+        )?;
+        for symbol in &parsed.symbols {
+            tx.execute(
+                "INSERT INTO symbols
+                    (root, path, name, kind, container, start_line, end_line, signature, doc)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    root,
+                    path,
+                    symbol.name,
+                    symbol.kind.as_str(),
+                    symbol.container,
+                    symbol.start_line as i64,
+                    symbol.end_line as i64,
+                    symbol.signature,
+                    symbol.doc
+                ],
+            )?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO files (root, path, modified, size, language, doc)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![root, path, modified, size, lang.as_str(), parsed.doc],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn remove_file(&self, root: &str, path: &str) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM symbols WHERE root = ?1 AND path = ?2",
+            params![root, path],
+        )?;
+        tx.execute(
+            "DELETE FROM files WHERE root = ?1 AND path = ?2",
+            params![root, path],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The indexed files of the project at `root`, by path
+    pub fn files(&self, root: &Path) -> Result<Vec<IndexedFile>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT files.path, language, files.doc, COUNT(symbols.name) FROM files
+                LEFT JOIN symbols ON symbols.root = files.root AND symbols.path = files.path
+                WHERE files.root = ?1
+                GROUP BY files.path ORDER BY files.path",
+        )?;
+        let files = statement
+            .query_map(params![root.to_string_lossy()], |row| {
+                Ok(IndexedFile {
+                    path: PathBuf::from(row.get::<_, String>(0)?),
+                    language: row.get(1)?,
+                    doc: row.get(2)?,
+                    symbols: row.get::<_, i64>(3)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(files)
+    }
+
+    /// The definitions in the file or under the directory `path` of the project, in the
+    /// order they are in the files
+    pub fn outline(&self, root: &Path, path: &Path) -> Result<Vec<Definition>> {
+        let prefix = path.to_string_lossy().trim_end_matches('/').to_string();
+        Ok(self
+            .definitions(root)?
+            .into_iter()
+            .filter(|definition| prefix.is_empty() || definition.path.starts_with(&prefix))
+            .collect())
+    }
+
+    /// The definitions of the project that best match `query`, the best first
+    ///
+    /// A definition named like the query ranks first, then those whose name, type, path or
+    /// docs have the words of the query in them.
+    pub fn search(
+        &self,
+        root: &Path,
+        query: &str,
+        kind: Option<SymbolKind>,
+        limit: usize,
+    ) -> Result<Vec<(Definition, f32)>> {
+        let query_words = words(query);
+        let mut scored: Vec<(Definition, f32)> = self
+            .definitions(root)?
+            .into_iter()
+            .filter(|definition| kind.is_none_or(|kind| definition.symbol.kind == kind))
+            .filter_map(|definition| {
+                let score = score(query, &query_words, &definition);
+                (score > 0.0).then_some((definition, score))
+            })
+            .collect();
+        scored.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .total_cmp(a_score)
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| a.symbol.start_line.cmp(&b.symbol.start_line))
+        });
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    fn definitions(&self, root: &Path) -> Result<Vec<Definition>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT path, name, kind, container, start_line, end_line, signature, doc
+                FROM symbols WHERE root = ?1 ORDER BY path, start_line",
+        )?;
+        let definitions = statement
+            .query_map(params![root.to_string_lossy()], read_definition)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(definitions)
+    }
+}
+
+fn read_definition(row: &Row) -> rusqlite::Result<Definition> {
+    Ok(Definition {
+        path: PathBuf::from(row.get::<_, String>(0)?),
+        symbol: Symbol {
+            name: row.get(1)?,
+            kind: SymbolKind::parse(&row.get::<_, String>(2)?).unwrap_or(SymbolKind::Function),
+            container: row.get(3)?,
+            start_line: row.get::<_, i64>(4)? as usize,
+            end_line: row.get::<_, i64>(5)? as usize,
+            signature: row.get(6)?,
+            doc: row.get(7)?,
+        },
+    })
+}
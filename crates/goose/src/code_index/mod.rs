@@ -0,0 +1,534 @@
+//! An index of the definitions in the project goose works in, for finding code by name
+//!
+//! With `GOOSE_CODE_INDEX` on, the agent is given the `platform__search_code` tool. The source
+//! files of the project are parsed with tree-sitter into their functions, types, traits,
+//! classes and constants, with their signatures and the first paragraph of their docs, and each
+//! file's own doc comment as its summary. The tool finds the definitions named like a query or
+//! about it, and outlines files and directories, so the agent can find the code it needs
+//! without reading or grepping whole files into its context.
+//!
+//! The index is kept in `code_index.db` in the data dir. Each search first parses the files
+//! that changed since the last one, so it is always up to date with the working tree. Files
+//! are walked the way git would, skipping what `.gitignore` or `.gooseignore` exclude. Rust,
+//! Python, JavaScript, TypeScript and Go are parsed.
+mod index;
+mod symbols;
+
+pub use index::CodeIndex;
+pub use symbols::{parse_source, Lang, ParsedFile, Symbol, SymbolKind};
+
+use indoc::indoc;
+use mcp_core::{Content, Tool, ToolError, ToolResult};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+pub const SEARCH_CODE_TOOL: &str = "platform__search_code";
+
+/// Larger source files are mostly generated or minified
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+
+const DEFAULT_LIMIT: usize = 10;
+
+/// The most lines of a definition's source to return
+const MAX_SOURCE_LINES: usize = 80;
+
+/// The most files to outline at once
+const MAX_OUTLINE_FILES: usize = 50;
+
+/// A definition in a file of the project
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Definition {
+    /// Relative to the project's root
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub symbol: Symbol,
+}
+
+impl Definition {
+    /// Where the definition is, as `path:start-end`
+    pub fn location(&self) -> String {
+        format!(
+            "{}:{}-{}",
+            self.path.display(),
+            self.symbol.start_line,
+            self.symbol.end_line
+        )
+    }
+}
+
+/// An indexed source file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexedFile {
+    /// Relative to the project's root
+    pub path: PathBuf,
+    pub language: String,
+    /// What the file says it is about
+    pub doc: Option<String>,
+    /// How many definitions it has
+    pub symbols: usize,
+}
+
+/// Whether `GOOSE_CODE_INDEX` is on
+pub fn enabled() -> bool {
+    Config::global()
+        .get_param::<bool>("GOOSE_CODE_INDEX")
+        .unwrap_or(false)
+}
+
+/// The root of the project `dir` is in, the closest directory with a `.git`, or `dir` itself
+pub fn project_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// The words of an identifier or a query, lowercase, splitting `camelCase` and `snake_case`
+pub fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let boundary = i > 0
+                && c.is_uppercase()
+                && (chars[i - 1].is_lowercase()
+                    || chars[i - 1].is_ascii_digit()
+                    || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.extend(c.to_lowercase());
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// How well a definition matches a query, 0 if it doesn't
+fn score(query: &str, query_words: &[String], definition: &Definition) -> f32 {
+    let symbol = &definition.symbol;
+    let name = symbol.name.to_lowercase();
+    let query = query.trim().to_lowercase();
+    // `Store::save` and `Store.save` name the method
+    let last = query
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or(&query);
+    let mut score: f32 = if name == query || name == last {
+        100.0
+    } else if !last.is_empty() && name.starts_with(last) {
+        50.0
+    } else if last.len() >= 3 && name.contains(last) {
+        30.0
+    } else {
+        0.0
+    };
+
+    let name_words = words(&symbol.name);
+    let container_words = symbol.container.as_deref().map(words).unwrap_or_default();
+    let path = definition.path.to_string_lossy().to_lowercase();
+    let docs = format!(
+        "{} {}",
+        symbol.signature,
+        symbol.doc.as_deref().unwrap_or_default()
+    )
+    .to_lowercase();
+    let mut matched = 0;
+    for word in query_words {
+        let points = if name_words.contains(word) {
+            15.0
+        } else if container_words.contains(word) {
+            8.0
+        } else if path.contains(word.as_str()) {
+            4.0
+        } else if docs.contains(word.as_str()) {
+            3.0
+        } else {
+            continue;
+        };
+        score += points;
+        matched += 1;
+    }
+    if matched > 1 && matched == query_words.len() {
+        score += 10.0;
+    }
+    score
+}
+
+/// The shared index, if `GOOSE_CODE_INDEX` is on and the index could be opened
+pub fn global() -> Option<&'static CodeIndex> {
+    static INDEX: OnceCell<Option<CodeIndex>> = OnceCell::new();
+    INDEX
+        .get_or_init(|| {
+            if !enabled() {
+                return None;
+            }
+            match CodeIndex::open(&CodeIndex::default_path()) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    tracing::warn!("Failed to open the code index: {}", e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+pub fn search_code_tool() -> Tool {
+    Tool::new(
+        SEARCH_CODE_TOOL.to_string(),
+        indoc! {r#"
+            Find code in the current project by what it defines, instead of searching or
+            reading whole files.
+
+            With a query, returns the functions, methods, types, traits, classes and constants
+            named like it or about it, each with its file and lines, signature and doc. Query by
+            a name or part of one (`from_config`, `SessionStore::save`) or by a few words
+            (`retry backoff`). Set source to also get the code of each definition.
+
+            With only a path, outlines the definitions of that file, or the files under that
+            directory with what each is about, to get the lay of the land first.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "A name, or words about what to find"},
+                "path": {
+                    "type": "string",
+                    "description": "A file or directory to outline, or to limit the search to, relative to the project root"
+                },
+                "kind": {
+                    "type": "string",
+                    "enum": ["function", "method", "struct", "enum", "class", "trait", "interface", "type", "module", "macro", "constant"],
+                    "description": "Optional kind of definition to find"
+                },
+                "source": {
+                    "type": "boolean",
+                    "description": "Whether to return the code of each definition found, false by default"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": format!("Optional number of definitions to return, {} by default", DEFAULT_LIMIT)
+                }
+            }
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    query: Option<String>,
+    path: Option<String>,
+    kind: Option<String>,
+    #[serde(default)]
+    source: bool,
+    limit: Option<usize>,
+}
+
+/// Run a [`SEARCH_CODE_TOOL`] call, for the project `dir` is in
+pub async fn search_code(arguments: Value, dir: &Path) -> ToolResult<Vec<Content>> {
+    let arguments: SearchArguments = serde_json::from_value(arguments)
+        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+    let index = global().ok_or_else(|| {
+        ToolError::ExecutionError("The code index couldn't be opened".to_string())
+    })?;
+    let root = project_root(dir);
+    // Parsing the files that changed is blocking work, which can take a while the first time
+    let update_root = root.clone();
+    tokio::task::spawn_blocking(move || index.update(&update_root))
+        .await
+        .map_err(|e| ToolError::ExecutionError(e.to_string()))?
+        .map_err(|e| ToolError::ExecutionError(format!("Failed to index the project: {}", e)))?;
+
+    let text = run_search(index, &root, arguments)?;
+    Ok(vec![Content::text(text)])
+}
+
+fn run_search(index: &CodeIndex, root: &Path, arguments: SearchArguments) -> ToolResult<String> {
+    let kind = match arguments.kind.as_deref() {
+        Some(kind) => Some(SymbolKind::parse(kind).ok_or_else(|| {
+            ToolError::InvalidParameters(format!("Unknown kind of definition {}", kind))
+        })?),
+        None => None,
+    };
+    // Paths may be given as absolute, or from the project root
+    let path = arguments.path.as_deref().map(|path| {
+        let path = Path::new(path);
+        path.strip_prefix(root)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf())
+    });
+    let failed = |e: anyhow::Error| ToolError::ExecutionError(e.to_string());
+
+    match (arguments.query.filter(|q| !q.trim().is_empty()), path) {
+        (Some(query), path) => {
+            let limit = arguments.limit.unwrap_or(DEFAULT_LIMIT);
+            // Searching everything first, so a path filter doesn't starve the results
+            let found: Vec<Definition> = index
+                .search(root, &query, kind, usize::MAX)
+                .map_err(failed)?
+                .into_iter()
+                .map(|(definition, _)| definition)
+                .filter(|definition| {
+                    path.as_ref()
+                        .is_none_or(|path| definition.path.starts_with(path))
+                })
+                .take(limit)
+                .collect();
+            if found.is_empty() {
+                return Ok(format!(
+                    "Nothing in {} is defined like {}",
+                    root.display(),
+                    query
+                ));
+            }
+            let source_root = arguments.source.then_some(root);
+            Ok(format_definitions(&found, source_root))
+        }
+        (None, Some(path)) => {
+            let definitions: Vec<Definition> = index
+                .outline(root, &path)
+                .map_err(failed)?
+                .into_iter()
+                .filter(|definition| kind.is_none_or(|kind| definition.symbol.kind == kind))
+                .collect();
+            let files: Vec<IndexedFile> = index
+                .files(root)
+                .map_err(failed)?
+                .into_iter()
+                .filter(|file| file.path.starts_with(&path))
+                .collect();
+            if files.is_empty() {
+                return Ok(format!(
+                    "No source files are indexed under {}",
+                    path.display()
+                ));
+            }
+            Ok(format_outline(&files, &definitions))
+        }
+        (None, None) => Err(ToolError::InvalidParameters(
+            "Give a query to search for, or a path to outline".to_string(),
+        )),
+    }
+}
+
+/// A definition's kind, name and the type it is in, e.g. `method save (in Store)`
+fn describe(symbol: &Symbol) -> String {
+    match &symbol.container {
+        Some(container) => format!(
+            "{} {} (in {})",
+            symbol.kind.as_str(),
+            symbol.name,
+            container
+        ),
+        None => format!("{} {}", symbol.kind.as_str(), symbol.name),
+    }
+}
+
+/// The definitions with their signatures and docs, and their code if `source_root` is given
+fn format_definitions(definitions: &[Definition], source_root: Option<&Path>) -> String {
+    let mut text = String::new();
+    for definition in definitions {
+        let symbol = &definition.symbol;
+        let _ = writeln!(text, "{} {}", definition.location(), describe(symbol));
+        let _ = writeln!(text, "    {}", symbol.signature);
+        if let Some(doc) = &symbol.doc {
+            let _ = writeln!(text, "    {}", doc);
+        }
+        if let Some(root) = source_root {
+            if let Some(code) = read_lines(
+                &root.join(&definition.path),
+                symbol.start_line,
+                symbol.end_line,
+            ) {
+                let _ = writeln!(text, "```\n{}\n```", code);
+            }
+        }
+        text.push('\n');
+    }
+    text.trim_end().to_string()
+}
+
+/// Each file with what it is about, and the definitions of the files when there are few
+fn format_outline(files: &[IndexedFile], definitions: &[Definition]) -> String {
+    let mut text = String::new();
+    let detailed = files.len() <= 3;
+    for file in files.iter().take(MAX_OUTLINE_FILES) {
+        let _ = write!(text, "{} ({}", file.path.display(), file.language);
+        if !detailed {
+            let _ = write!(text, ", {} definitions", file.symbols);
+        }
+        text.push_str(")\n");
+        if let Some(doc) = &file.doc {
+            let _ = writeln!(text, "    {}", doc);
+        }
+        if detailed {
+            for definition in definitions.iter().filter(|d| d.path == file.path) {
+                let symbol = &definition.symbol;
+                let indent = if symbol.container.is_some() {
+                    "    "
+                } else {
+                    ""
+                };
+                let _ = write!(
+                    text,
+                    "  {}{}-{} {}",
+                    indent,
+                    symbol.start_line,
+                    symbol.end_line,
+                    describe(symbol)
+                );
+                if let Some(doc) = &symbol.doc {
+                    let _ = write!(text, " - {}", doc);
+                }
+                text.push('\n');
+            }
+        }
+    }
+    if files.len() > MAX_OUTLINE_FILES {
+        let _ = writeln!(
+            text,
+            "... and {} more files, outline a subdirectory to see them",
+            files.len() - MAX_OUTLINE_FILES
+        );
+    }
+    text.trim_end().to_string()
+}
+
+/// Lines `start` to `end` of a file, counting from 1, cut to [`MAX_SOURCE_LINES`]
+fn read_lines(path: &Path, start: usize, end: usize) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let lines: Vec<&str> = content
+        .lines()
+        .skip(start.saturating_sub(1))
+        .take(end + 1 - start)
+        .collect();
+    if lines.len() > MAX_SOURCE_LINES {
+        Some(format!(
+            "{}\n... {} more lines",
+            lines[..MAX_SOURCE_LINES].join("\n"),
+            lines.len() - MAX_SOURCE_LINES
+        ))
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("SessionStore::save"), ["session", "store", "save"]);
+        assert_eq!(words("from_config"), ["from", "config"]);
+        assert_eq!(words("HTTPServer v2Api"), ["http", "server", "v2", "api"]);
+        assert_eq!(words("retry backoff"), ["retry", "backoff"]);
+    }
+
+    #[test]
+    fn test_search_and_outline() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/store"))?;
+        fs::write(
+            root.join("src/store/mod.rs"),
+            indoc! {r#"
+                //! Stores that persist sessions
+
+                /// A store for the full state of sessions
+                pub trait SessionStore {
+                    /// Save the whole state of a session
+                    fn save(&self, id: &str);
+                }
+
+                /// Open the store selected by the config
+                pub fn from_config() -> Box<dyn SessionStore> {
+                    todo!()
+                }
+            "#},
+        )?;
+        fs::write(
+            root.join("src/retry.py"),
+            "def save_with_backoff(attempts):\n    \"\"\"Retry saving with exponential backoff.\"\"\"\n",
+        )?;
+        fs::write(root.join("README.md"), "# Not code\n")?;
+        fs::write(root.join(".gooseignore"), "vendor/\n")?;
+        fs::create_dir_all(root.join("vendor"))?;
+        fs::write(root.join("vendor/lib.rs"), "pub fn save() {}\n")?;
+
+        let index = CodeIndex::open_in_memory()?;
+        let report = index.update(root)?;
+        assert_eq!((report.indexed, report.unchanged), (2, 0));
+
+        let found = index.search(root, "save", None, 10)?;
+        assert_eq!(found[0].0.symbol.name, "save");
+        assert_eq!(found[0].0.symbol.container.as_deref(), Some("SessionStore"));
+        assert_eq!(found[0].0.location(), "src/store/mod.rs:6-6");
+        assert_eq!(found[1].0.symbol.name, "save_with_backoff");
+        let found = index.search(root, "SessionStore::save", None, 1)?;
+        assert_eq!(found[0].0.symbol.kind, SymbolKind::Method);
+        let found = index.search(root, "exponential backoff", None, 10)?;
+        assert_eq!(found[0].0.symbol.name, "save_with_backoff");
+        let found = index.search(root, "store", Some(SymbolKind::Function), 10)?;
+        assert_eq!(found[0].0.symbol.name, "from_config");
+
+        let text = run_search(
+            &index,
+            root,
+            SearchArguments {
+                query: Some("from_config".to_string()),
+                path: None,
+                kind: None,
+                source: true,
+                limit: Some(1),
+            },
+        )?;
+        assert!(text.starts_with("src/store/mod.rs:10-12 function from_config\n"));
+        assert!(text.contains("    Open the store selected by the config\n"));
+        assert!(text.contains("```\npub fn from_config() -> Box<dyn SessionStore> {\n"));
+
+        let outline = run_search(
+            &index,
+            root,
+            SearchArguments {
+                query: None,
+                path: Some("src/store".to_string()),
+                kind: None,
+                source: false,
+                limit: None,
+            },
+        )?;
+        assert_eq!(
+            outline,
+            indoc! {"
+                src/store/mod.rs (rust)
+                    Stores that persist sessions
+                  4-7 trait SessionStore - A store for the full state of sessions
+                      6-6 method save (in SessionStore) - Save the whole state of a session
+                  10-12 function from_config - Open the store selected by the config"}
+        );
+
+        // Only what changed is parsed again
+        fs::remove_file(root.join("src/retry.py"))?;
+        fs::write(root.join("src/lib.rs"), "pub mod store;\n")?;
+        let report = index.update(root)?;
+        assert_eq!(
+            (report.indexed, report.unchanged, report.removed),
+            (1, 1, 1)
+        );
+        assert!(index.search(root, "backoff", None, 10)?.is_empty());
+        Ok(())
+    }
+}
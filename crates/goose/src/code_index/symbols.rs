@@ -0,0 +1,694 @@
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor};
+
+/// The languages whose definitions are indexed, by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lang {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+}
+
+impl Lang {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Lang::Rust),
+            "py" | "pyi" => Some(Lang::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Lang::JavaScript),
+            "ts" | "mts" | "cts" => Some(Lang::TypeScript),
+            "tsx" => Some(Lang::Tsx),
+            "go" => Some(Lang::Go),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Lang::Rust => "rust",
+            Lang::Python => "python",
+            Lang::JavaScript => "javascript",
+            Lang::TypeScript => "typescript",
+            Lang::Tsx => "tsx",
+            Lang::Go => "go",
+        }
+    }
+
+    fn language(self) -> Language {
+        match self {
+            Lang::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Lang::Python => tree_sitter_python::LANGUAGE.into(),
+            Lang::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Lang::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Lang::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Lang::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// The query for the language's definitions, each captured as `@definition.<kind>` with
+    /// its name as `@name`
+    fn query_source(self) -> String {
+        match self {
+            Lang::Rust => RUST_QUERY.to_string(),
+            Lang::Python => PYTHON_QUERY.to_string(),
+            Lang::JavaScript => JAVASCRIPT_QUERY.to_string(),
+            Lang::TypeScript | Lang::Tsx => format!("{}\n{}", JAVASCRIPT_QUERY, TYPESCRIPT_QUERY),
+            Lang::Go => GO_QUERY.to_string(),
+        }
+    }
+
+    fn query(self) -> &'static Query {
+        static QUERIES: [OnceCell<Query>; 6] = [const { OnceCell::new() }; 6];
+        QUERIES[self as usize].get_or_init(|| {
+            Query::new(&self.language(), &self.query_source())
+                .expect("the definition queries match their grammars")
+        })
+    }
+}
+
+const RUST_QUERY: &str = r#"
+(function_item name: (identifier) @name) @definition.function
+(function_signature_item name: (identifier) @name) @definition.function
+(struct_item name: (type_identifier) @name) @definition.struct
+(union_item name: (type_identifier) @name) @definition.struct
+(enum_item name: (type_identifier) @name) @definition.enum
+(trait_item name: (type_identifier) @name) @definition.trait
+(type_item name: (type_identifier) @name) @definition.type
+(mod_item name: (identifier) @name) @definition.module
+(macro_definition name: (identifier) @name) @definition.macro
+(const_item name: (identifier) @name) @definition.constant
+(static_item name: (identifier) @name) @definition.constant
+"#;
+
+const PYTHON_QUERY: &str = r#"
+(function_definition name: (identifier) @name) @definition.function
+(class_definition name: (identifier) @name) @definition.class
+(module (expression_statement (assignment left: (identifier) @name) @definition.constant))
+"#;
+
+const JAVASCRIPT_QUERY: &str = r#"
+(function_declaration name: (identifier) @name) @definition.function
+(generator_function_declaration name: (identifier) @name) @definition.function
+(class_declaration name: (_) @name) @definition.class
+(method_definition name: (property_identifier) @name) @definition.method
+(lexical_declaration
+  (variable_declarator
+    name: (identifier) @name
+    value: [(arrow_function) (function_expression)]) @definition.function)
+(variable_declaration
+  (variable_declarator
+    name: (identifier) @name
+    value: [(arrow_function) (function_expression)]) @definition.function)
+"#;
+
+const TYPESCRIPT_QUERY: &str = r#"
+(abstract_class_declaration name: (type_identifier) @name) @definition.class
+(interface_declaration name: (type_identifier) @name) @definition.interface
+(type_alias_declaration name: (type_identifier) @name) @definition.type
+(enum_declaration name: (identifier) @name) @definition.enum
+(function_signature name: (identifier) @name) @definition.function
+(method_signature name: (property_identifier) @name) @definition.method
+(abstract_method_signature name: (property_identifier) @name) @definition.method
+"#;
+
+const GO_QUERY: &str = r#"
+(function_declaration name: (identifier) @name) @definition.function
+(method_declaration name: (field_identifier) @name) @definition.method
+(type_spec name: (type_identifier) @name type: (struct_type)) @definition.struct
+(type_spec name: (type_identifier) @name type: (interface_type)) @definition.interface
+(type_spec name: (type_identifier) @name) @definition.type
+(const_spec name: (identifier) @name) @definition.constant
+"#;
+
+/// What kind of definition a symbol is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolKind {
+    Function,
+    /// A function of a type, trait or class
+    Method,
+    Struct,
+    Enum,
+    Class,
+    Trait,
+    Interface,
+    /// A type alias, or a Go type that isn't a struct or interface
+    Type,
+    Module,
+    Macro,
+    Constant,
+}
+
+impl SymbolKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Class => "class",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Type => "type",
+            SymbolKind::Module => "module",
+            SymbolKind::Macro => "macro",
+            SymbolKind::Constant => "constant",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "function" => SymbolKind::Function,
+            "method" => SymbolKind::Method,
+            "struct" => SymbolKind::Struct,
+            "enum" => SymbolKind::Enum,
+            "class" => SymbolKind::Class,
+            "trait" => SymbolKind::Trait,
+            "interface" => SymbolKind::Interface,
+            "type" => SymbolKind::Type,
+            "module" => SymbolKind::Module,
+            "macro" => SymbolKind::Macro,
+            "constant" => SymbolKind::Constant,
+            _ => return None,
+        })
+    }
+
+    /// Whether the functions defined in it are its methods
+    fn has_methods(self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Struct
+                | SymbolKind::Enum
+                | SymbolKind::Class
+                | SymbolKind::Trait
+                | SymbolKind::Interface
+        )
+    }
+}
+
+/// A definition in a source file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The type, trait or class it is defined in, e.g. the type of a Rust `impl`
+    pub container: Option<String>,
+    /// The first line of the definition, counting from 1, with its doc comment left out
+    pub start_line: usize,
+    /// The last line of the definition, inclusive
+    pub end_line: usize,
+    /// The definition up to its body, e.g. a function's name, parameters and return type
+    pub signature: String,
+    /// The first paragraph of its doc comment or docstring
+    pub doc: Option<String>,
+}
+
+/// The definitions of a source file, with what the file says it is about
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedFile {
+    /// The first paragraph of the file's module doc, docstring or leading comment
+    pub doc: Option<String>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Signatures and docs are cut to about this many characters
+const MAX_TEXT_CHARS: usize = 200;
+
+/// The definitions in `source`, None if it can't be parsed
+///
+/// Definitions local to a function, like a nested function or a callback assigned to a
+/// variable, are left out.
+pub fn parse_source(lang: Lang, source: &str) -> Option<ParsedFile> {
+    let mut parser = Parser::new();
+    parser.set_language(&lang.language()).ok()?;
+    let tree = parser.parse(source, None)?;
+    let root = tree.root_node();
+    let query = lang.query();
+    let name_index = query.capture_index_for_name("name")?;
+
+    // The definitions by node, the first pattern to match a node giving its kind, e.g. a Go
+    // struct is a struct rather than a type
+    let mut definitions: Vec<(Node, String, SymbolKind)> = Vec::new();
+    let mut patterns: Vec<usize> = Vec::new();
+    let mut by_node: HashMap<usize, usize> = HashMap::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, root, source.as_bytes());
+    while let Some(found) = matches.next() {
+        let mut name = None;
+        let mut definition = None;
+        for capture in found.captures {
+            if capture.index == name_index {
+                name = Some(capture.node);
+            } else if let Some(kind) = query.capture_names()[capture.index as usize]
+                .strip_prefix("definition.")
+                .and_then(SymbolKind::parse)
+            {
+                definition = Some((capture.node, kind));
+            }
+        }
+        let (Some(name), Some((node, kind))) = (name, definition) else {
+            continue;
+        };
+        if let Some(&index) = by_node.get(&node.id()) {
+            if found.pattern_index < patterns[index] {
+                definitions[index].2 = kind;
+                patterns[index] = found.pattern_index;
+            }
+            continue;
+        }
+        by_node.insert(node.id(), definitions.len());
+        definitions.push((node, text(name, source).to_string(), kind));
+        patterns.push(found.pattern_index);
+    }
+
+    let mut symbols = Vec::new();
+    for (node, name, kind) in &definitions {
+        let container = container(*node, source, &definitions, &by_node);
+        let mut kind = *kind;
+        match &container {
+            Some(Container::Local) => continue,
+            Some(Container::Type(_)) if kind == SymbolKind::Function => kind = SymbolKind::Method,
+            _ => {}
+        }
+        let outer = outer_node(*node);
+        symbols.push(Symbol {
+            name: name.clone(),
+            kind,
+            container: match container {
+                Some(Container::Type(name)) => Some(name),
+                _ => None,
+            },
+            start_line: outer.start_position().row + 1,
+            end_line: last_row(outer) + 1,
+            signature: signature(*node, source),
+            doc: docstring(*node, source).or_else(|| leading_doc(outer, source)),
+        });
+    }
+    symbols.sort_by_key(|symbol| symbol.start_line);
+    Some(ParsedFile {
+        doc: file_doc(lang, root, source),
+        symbols,
+    })
+}
+
+enum Container {
+    /// A type, trait or class, by name
+    Type(String),
+    /// A function, so the definition is only visible inside it
+    Local,
+}
+
+/// What a definition is defined in, None at the top level or in a module
+fn container(
+    node: Node,
+    source: &str,
+    definitions: &[(Node, String, SymbolKind)],
+    by_node: &HashMap<usize, usize>,
+) -> Option<Container> {
+    // A Go method is defined at the top level, for the type of its receiver
+    if node.kind() == "method_declaration" {
+        let receiver = node.child_by_field_name("receiver")?;
+        return find_descendant(receiver, "type_identifier")
+            .map(|name| Container::Type(text(name, source).to_string()));
+    }
+    let mut ancestor = node.parent();
+    while let Some(current) = ancestor {
+        if current.kind() == "impl_item" {
+            let name = current
+                .child_by_field_name("type")
+                .map(|type_node| text(type_node, source))?;
+            let name = name.split('<').next().unwrap_or(name).trim();
+            return Some(Container::Type(name.to_string()));
+        }
+        if let Some(&index) = by_node.get(&current.id()) {
+            let (_, name, kind) = &definitions[index];
+            return match kind {
+                SymbolKind::Function | SymbolKind::Method => Some(Container::Local),
+                kind if kind.has_methods() => Some(Container::Type(name.clone())),
+                // Modules and the like hold definitions as the file does
+                _ => None,
+            };
+        }
+        if matches!(
+            current.kind(),
+            "arrow_function" | "function_expression" | "function"
+        ) {
+            return Some(Container::Local);
+        }
+        ancestor = current.parent();
+    }
+    None
+}
+
+fn find_descendant<'tree>(node: Node<'tree>, kind: &str) -> Option<Node<'tree>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    let mut walker = node.walk();
+    let children: Vec<Node<'tree>> = node.named_children(&mut walker).collect();
+    children
+        .into_iter()
+        .find_map(|child| find_descendant(child, kind))
+}
+
+/// The statement a definition is part of, with its decorators and `export`, which its doc
+/// comment comes before
+fn outer_node(node: Node) -> Node {
+    let mut outer = node;
+    while let Some(parent) = outer.parent() {
+        let wraps = match parent.kind() {
+            "decorated_definition" | "export_statement" | "type_declaration" => true,
+            "lexical_declaration" | "variable_declaration" | "expression_statement" => {
+                parent.named_child_count() == 1
+            }
+            "const_declaration" => parent.named_child_count() == 1,
+            _ => false,
+        };
+        if !wraps {
+            break;
+        }
+        outer = parent;
+    }
+    outer
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    &source[node.byte_range()]
+}
+
+/// The definition up to its body, on one line
+fn signature(node: Node, source: &str) -> String {
+    let body = node.child_by_field_name("body").or_else(|| {
+        node.child_by_field_name("value")
+            .and_then(|value| value.child_by_field_name("body"))
+    });
+    let head = match body {
+        Some(body) => &source[node.start_byte()..body.start_byte()],
+        None => text(node, source).lines().next().unwrap_or_default(),
+    };
+    let head = head.split_whitespace().collect::<Vec<_>>().join(" ");
+    shorten(head.trim_end_matches(['{', ' ']).to_string())
+}
+
+/// A Python docstring, the string a function or class body starts with
+fn docstring(node: Node, source: &str) -> Option<String> {
+    let body = node.child_by_field_name("body")?;
+    first_string(body, source)
+}
+
+fn first_string(block: Node, source: &str) -> Option<String> {
+    let mut walker = block.walk();
+    let first = block.named_children(&mut walker).next()?;
+    if first.kind() != "expression_statement" {
+        return None;
+    }
+    let string = first
+        .named_child(0)
+        .filter(|node| node.kind() == "string")?;
+    let raw = text(string, source).trim_start_matches(['r', 'u', 'b', 'R', 'U', 'B']);
+    clean_doc(raw.trim_matches(['"', '\'']).lines())
+}
+
+/// The comments right before a definition, skipping the attributes in between
+fn leading_doc(node: Node, source: &str) -> Option<String> {
+    let mut comments = Vec::new();
+    let mut next_row = node.start_position().row;
+    let mut sibling = node.prev_sibling();
+    while let Some(current) = sibling {
+        if current.kind() == "attribute_item" {
+            next_row = current.start_position().row;
+        } else if current.kind().contains("comment")
+            && last_row(current) + 1 >= next_row
+            // The `//!` docs of the file or module around it aren't about it
+            && !text(current, source).starts_with("//!")
+        {
+            comments.push(text(current, source));
+            next_row = current.start_position().row;
+        } else {
+            break;
+        }
+        sibling = current.prev_sibling();
+    }
+    comments.reverse();
+    clean_doc(comments.iter().flat_map(|comment| comment.lines()))
+}
+
+/// The last row a node has text on, as Rust's line comments end past their newline
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+/// What the file says it is about: Rust's `//!` docs, a Python module docstring, or the
+/// comment the file starts with, unless that is a license header
+fn file_doc(lang: Lang, root: Node, source: &str) -> Option<String> {
+    if lang == Lang::Python {
+        if let Some(doc) = first_string(root, source) {
+            return Some(doc);
+        }
+    }
+    let mut walker = root.walk();
+    let mut comments = Vec::new();
+    for child in root.children(&mut walker) {
+        if !child.kind().contains("comment") {
+            if child.kind() == "hash_bang_line" {
+                continue;
+            }
+            break;
+        }
+        let comment = text(child, source);
+        if lang == Lang::Rust && !(comment.starts_with("//!") || comment.starts_with("/*!")) {
+            break;
+        }
+        comments.push(comment);
+    }
+    let doc = clean_doc(comments.iter().flat_map(|comment| comment.lines()))?;
+    let lower = doc.to_lowercase();
+    if lower.contains("copyright") || lower.contains("spdx-license") {
+        return None;
+    }
+    Some(doc)
+}
+
+/// The first paragraph of a comment's lines, without the comment markers
+fn clean_doc<'a>(lines: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut words: Vec<&str> = Vec::new();
+    for line in lines {
+        let mut line = line.trim();
+        for marker in ["///", "//!", "//", "/**", "/*!", "/*", "#"] {
+            if let Some(rest) = line.strip_prefix(marker) {
+                line = rest;
+                break;
+            }
+        }
+        let line = line.trim_end_matches("*/").trim_start_matches('*').trim();
+        if line.is_empty() {
+            if words.is_empty() {
+                continue;
+            }
+            break;
+        }
+        words.extend(line.split_whitespace());
+    }
+    if words.is_empty() {
+        None
+    } else {
+        Some(shorten(words.join(" ")))
+    }
+}
+
+fn shorten(mut text: String) -> String {
+    if text.chars().count() > MAX_TEXT_CHARS {
+        text = text.chars().take(MAX_TEXT_CHARS).collect::<String>() + "…";
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(parsed: &ParsedFile) -> Vec<(String, SymbolKind, Option<String>)> {
+        parsed
+            .symbols
+            .iter()
+            .map(|s| (s.name.clone(), s.kind, s.container.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust() {
+        let source = r#"//! Stores for sessions
+//!
+//! More detail.
+
+/// A saved session
+#[derive(Debug)]
+pub struct Saved {
+    id: String,
+}
+
+impl<T> Store<T> {
+    /// Save it, replacing what
+    /// was saved before
+    pub fn save(&self, id: &str) -> Result<()> {
+        fn helper() {}
+        Ok(())
+    }
+}
+
+pub trait SessionStore: Send {
+    fn resume(&self) -> Option<Saved>;
+}
+
+const LIMIT: usize = 10;
+"#;
+        let parsed = parse_source(Lang::Rust, source).unwrap();
+        assert_eq!(parsed.doc.as_deref(), Some("Stores for sessions"));
+        assert_eq!(
+            names(&parsed),
+            [
+                ("Saved".to_string(), SymbolKind::Struct, None),
+                ("save".to_string(), SymbolKind::Method, Some("Store".into())),
+                ("SessionStore".to_string(), SymbolKind::Trait, None),
+                (
+                    "resume".to_string(),
+                    SymbolKind::Method,
+                    Some("SessionStore".into())
+                ),
+                ("LIMIT".to_string(), SymbolKind::Constant, None),
+            ]
+        );
+        let saved = &parsed.symbols[0];
+        assert_eq!(saved.doc.as_deref(), Some("A saved session"));
+        assert_eq!((saved.start_line, saved.end_line), (7, 9));
+        assert_eq!(saved.signature, "pub struct Saved");
+        let save = &parsed.symbols[1];
+        assert_eq!(save.signature, "pub fn save(&self, id: &str) -> Result<()>");
+        assert_eq!(
+            save.doc.as_deref(),
+            Some("Save it, replacing what was saved before")
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        let source = r#""""Helpers for the CLI."""
+
+TIMEOUT = 30
+
+@dataclass
+class Config:
+    """Settings read from the environment."""
+
+    def load(self, path):
+        def inner():
+            pass
+        return path
+
+# Start the server
+def serve(port: int) -> None:
+    pass
+"#;
+        let parsed = parse_source(Lang::Python, source).unwrap();
+        assert_eq!(parsed.doc.as_deref(), Some("Helpers for the CLI."));
+        assert_eq!(
+            names(&parsed),
+            [
+                ("TIMEOUT".to_string(), SymbolKind::Constant, None),
+                ("Config".to_string(), SymbolKind::Class, None),
+                (
+                    "load".to_string(),
+                    SymbolKind::Method,
+                    Some("Config".into())
+                ),
+                ("serve".to_string(), SymbolKind::Function, None),
+            ]
+        );
+        // The decorator is part of the class
+        assert_eq!(parsed.symbols[1].start_line, 5);
+        assert_eq!(
+            parsed.symbols[1].doc.as_deref(),
+            Some("Settings read from the environment.")
+        );
+        assert_eq!(parsed.symbols[3].doc.as_deref(), Some("Start the server"));
+        assert_eq!(parsed.symbols[3].signature, "def serve(port: int) -> None:");
+    }
+
+    #[test]
+    fn test_typescript_and_go() {
+        let source = r#"// Copyright 2025 Example Inc.
+
+/** Options for a request */
+export interface RequestOptions {
+  timeout?: number;
+}
+
+export const fetchJson = async (url: string): Promise<unknown> => {
+  const parse = (text: string) => JSON.parse(text);
+  return parse(await (await fetch(url)).text());
+};
+
+export class Client {
+  get(path: string) {
+    return fetchJson(path);
+  }
+}
+"#;
+        let parsed = parse_source(Lang::TypeScript, source).unwrap();
+        assert_eq!(parsed.doc, None);
+        assert_eq!(
+            names(&parsed),
+            [
+                ("RequestOptions".to_string(), SymbolKind::Interface, None),
+                ("fetchJson".to_string(), SymbolKind::Function, None),
+                ("Client".to_string(), SymbolKind::Class, None),
+                ("get".to_string(), SymbolKind::Method, Some("Client".into())),
+            ]
+        );
+        assert_eq!(
+            parsed.symbols[0].doc.as_deref(),
+            Some("Options for a request")
+        );
+        assert_eq!(
+            parsed.symbols[1].signature,
+            "fetchJson = async (url: string): Promise<unknown> =>"
+        );
+
+        let source = r#"// Package store keeps sessions.
+package store
+
+type Store struct {
+	path string
+}
+
+// Open the store at path
+func (s *Store) Open(path string) error {
+	return nil
+}
+"#;
+        let parsed = parse_source(Lang::Go, source).unwrap();
+        assert_eq!(parsed.doc.as_deref(), Some("Package store keeps sessions."));
+        assert_eq!(
+            names(&parsed),
+            [
+                ("Store".to_string(), SymbolKind::Struct, None),
+                ("Open".to_string(), SymbolKind::Method, Some("Store".into())),
+            ]
+        );
+        assert_eq!(
+            parsed.symbols[1].doc.as_deref(),
+            Some("Open the store at path")
+        );
+    }
+}
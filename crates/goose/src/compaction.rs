@@ -0,0 +1,432 @@
+//! Automatic compaction of conversations that approach the model's context limit
+//!
+//! When enabled with `GOOSE_AUTO_COMPACT=true`, once the prompt for a completion fills most of
+//! the context window, the model summarizes the older turns into a synopsis. The synopsis is
+//! sent in place of those turns, while the most recent turns and any messages the user pinned
+//! are sent verbatim. Later compactions extend the synopsis rather than starting over.
+//!
+//! Only what is sent to the provider is compacted. The session keeps every message, with the
+//! synopsis in its metadata, so the full history can still be reviewed or exported.
+use anyhow::{anyhow, Result};
+use mcp_core::role::Role;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::message::{Message, MessageContent};
+use crate::providers::base::{Provider, ProviderUsage};
+use crate::token_counter::TokenCounter;
+
+const DEFAULT_THRESHOLD: f64 = 0.8;
+const DEFAULT_KEEP_RECENT: usize = 10;
+
+/// Tool output is cut to this many characters in the transcript that is summarized
+const MAX_TOOL_OUTPUT_CHARS: usize = 2000;
+
+const SYSTEM_PROMPT: &str = "You summarize conversations between a user and an AI agent that \
+    uses tools, so the agent can carry on with the summary in place of the conversation.";
+
+const SUMMARY_INSTRUCTIONS: &str = "Write a synopsis of the conversation below for the agent to \
+    continue from. Keep the user's goals and constraints, decisions made and why, files and \
+    commands involved, results, errors still open and what was about to happen next. Leave out \
+    pleasantries and output that no longer matters. Reply with only the synopsis.";
+
+/// A synopsis standing in for the first messages of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub summary: String,
+    /// The number of leading messages the synopsis replaces
+    pub message_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compaction {
+    /// Fraction of the context limit the prompt can fill before older turns are summarized
+    pub threshold: f64,
+    /// Number of most recent messages that are never summarized
+    pub keep_recent: usize,
+}
+
+impl Default for Compaction {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            keep_recent: DEFAULT_KEEP_RECENT,
+        }
+    }
+}
+
+impl Compaction {
+    /// Read the compaction settings, returning None unless compaction is enabled
+    ///
+    /// - GOOSE_AUTO_COMPACT: set to true to enable
+    /// - GOOSE_AUTO_COMPACT_THRESHOLD: fraction of the context limit to compact at (default 0.8)
+    /// - GOOSE_AUTO_COMPACT_KEEP_RECENT: messages always sent verbatim (default 10)
+    pub fn from_config() -> Option<Self> {
+        let config = Config::global();
+        if !config
+            .get_param::<bool>("GOOSE_AUTO_COMPACT")
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        Some(Self {
+            threshold: config
+                .get_param::<f64>("GOOSE_AUTO_COMPACT_THRESHOLD")
+                .unwrap_or(DEFAULT_THRESHOLD)
+                .clamp(0.1, 1.0),
+            keep_recent: config
+                .get_param::<usize>("GOOSE_AUTO_COMPACT_KEEP_RECENT")
+                .unwrap_or(DEFAULT_KEEP_RECENT)
+                .max(1),
+        })
+    }
+
+    /// Whether a prompt of `tokens` is close enough to the context limit to compact
+    pub fn should_compact(&self, tokens: usize, context_limit: usize) -> bool {
+        tokens as f64 > context_limit as f64 * self.threshold
+    }
+
+    /// The number of leading messages to summarize, or None if no more than `summarized` can be
+    ///
+    /// The recent messages kept verbatim start with a message from the user that isn't a tool
+    /// result, so tool calls are never separated from their results.
+    pub fn split_point(&self, messages: &[Message], summarized: usize) -> Option<usize> {
+        let latest = messages.len().saturating_sub(self.keep_recent);
+        (summarized + 1..=latest)
+            .rev()
+            .find(|&index| starts_turn(&messages[index]))
+    }
+
+    /// Summarize the first `split` messages, continuing the `previous` synopsis, returning it
+    /// with the usage of the completions that wrote it
+    ///
+    /// Messages that don't fit in one request are summarized a part at a time, each part
+    /// extending the synopsis of those before it.
+    pub async fn summarize(
+        &self,
+        provider: &dyn Provider,
+        token_counter: &TokenCounter,
+        messages: &[Message],
+        previous: Option<&ConversationSummary>,
+        split: usize,
+    ) -> Result<(ConversationSummary, Vec<ProviderUsage>)> {
+        let start = previous.map_or(0, |previous| previous.message_count);
+        if split <= start || split > messages.len() {
+            return Err(anyhow!("Nothing to summarize"));
+        }
+        // Room for the instructions, the synopsis so far and the reply
+        let part_tokens = provider.get_model_config().context_limit() / 2;
+
+        let mut summary = previous.map(|previous| previous.summary.clone());
+        let mut usage = Vec::new();
+        let mut part = String::new();
+        let mut part_size = 0;
+        for message in &messages[start..split] {
+            let entry = transcript_entry(message);
+            let entry_size = token_counter.count_tokens(&entry);
+            if !part.is_empty() && part_size + entry_size > part_tokens {
+                summary =
+                    Some(summarize_part(provider, summary.as_deref(), &part, &mut usage).await?);
+                part.clear();
+                part_size = 0;
+            }
+            part.push_str(&entry);
+            part_size += entry_size;
+        }
+        if !part.is_empty() {
+            summary = Some(summarize_part(provider, summary.as_deref(), &part, &mut usage).await?);
+        }
+
+        let summary = ConversationSummary {
+            summary: summary.unwrap_or_default(),
+            message_count: split,
+        };
+        Ok((summary, usage))
+    }
+}
+
+/// The messages to send in place of a conversation that has been summarized
+///
+/// The synopsis, followed by the pinned messages it covers, is prepended to the first message
+/// after those it replaces. The conversation is returned as it is if the summary doesn't fit it,
+/// e.g. because messages were removed since.
+pub fn compacted_messages(
+    messages: &[Message],
+    summary: &ConversationSummary,
+    pinned: &[usize],
+) -> Vec<Message> {
+    let Some(first) = messages.get(summary.message_count) else {
+        return messages.to_vec();
+    };
+    if summary.message_count == 0 || !starts_turn(first) {
+        return messages.to_vec();
+    }
+
+    let mut synopsis = format!(
+        "The start of this conversation was summarized to fit the context window. Synopsis:\n\n{}",
+        summary.summary
+    );
+    let mut pinned: Vec<usize> = pinned
+        .iter()
+        .copied()
+        .filter(|&index| index < summary.message_count)
+        .collect();
+    pinned.sort_unstable();
+    pinned.dedup();
+    if !pinned.is_empty() {
+        synopsis.push_str("\n\nMessages the user pinned, verbatim:");
+        for index in pinned {
+            let message = &messages[index];
+            synopsis.push_str(&format!(
+                "\n\n{}: {}",
+                role_name(&message.role),
+                message.as_concat_text()
+            ));
+        }
+    }
+
+    let mut first = first.clone();
+    first.content.insert(0, MessageContent::text(synopsis));
+    let mut compacted = vec![first];
+    compacted.extend_from_slice(&messages[summary.message_count + 1..]);
+    compacted
+}
+
+/// Whether a message starts a new turn: one from the user that isn't a tool result or a note
+/// from the agent
+pub fn starts_turn(message: &Message) -> bool {
+    message.role == Role::User
+        && !message.is_agent_note()
+        && message
+            .content
+            .iter()
+            .any(|content| matches!(content, MessageContent::Text(_)))
+        && !message
+            .content
+            .iter()
+            .any(|content| matches!(content, MessageContent::ToolResponse(_)))
+}
+
+async fn summarize_part(
+    provider: &dyn Provider,
+    previous: Option<&str>,
+    transcript: &str,
+    usage: &mut Vec<ProviderUsage>,
+) -> Result<String> {
+    let mut request = SUMMARY_INSTRUCTIONS.to_string();
+    if let Some(previous) = previous {
+        request.push_str(&format!(
+            "\n\nThe synopsis of the conversation before this part, to extend:\n\n{}",
+            previous
+        ));
+    }
+    request.push_str(&format!("\n\nConversation:\n\n{}", transcript));
+
+    let (response, part_usage) = provider
+        .complete(SYSTEM_PROMPT, &[Message::user().with_text(request)], &[])
+        .await?;
+    usage.push(part_usage);
+    let summary = response.as_concat_text();
+    if summary.trim().is_empty() {
+        return Err(anyhow!("The model returned an empty synopsis"));
+    }
+    Ok(summary)
+}
+
+/// A message as lines of plain text for the summarizer
+pub(crate) fn transcript_entry(message: &Message) -> String {
+    let role = role_name(&message.role);
+    let mut entry = String::new();
+    for content in &message.content {
+        match content {
+            MessageContent::Text(text) => entry.push_str(&format!("{}: {}\n", role, text.text)),
+            MessageContent::ToolRequest(request) => match &request.tool_call {
+                Ok(call) => entry.push_str(&format!(
+                    "{} called {} with {}\n",
+                    role, call.name, call.arguments
+                )),
+                Err(e) => entry.push_str(&format!("{} made an invalid tool call: {}\n", role, e)),
+            },
+            MessageContent::ToolResponse(response) => {
+                let output = match &response.tool_result {
+                    Ok(contents) => contents
+                        .iter()
+                        .filter_map(|content| content.as_text())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    Err(e) => format!("error: {}", e),
+                };
+                let output = match output.char_indices().nth(MAX_TOOL_OUTPUT_CHARS) {
+                    Some((end, _)) => format!("{} [...]", &output[..end]),
+                    None => output,
+                };
+                entry.push_str(&format!("tool result: {}\n", output));
+            }
+            _ => {}
+        }
+    }
+    entry
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelConfig;
+    use crate::providers::base::{ProviderMetadata, ProviderUsage, Usage};
+    use crate::providers::errors::ProviderError;
+    use mcp_core::tool::{Tool, ToolCall};
+    use mcp_core::Content;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Replies with a numbered synopsis, recording the requests it gets
+    struct SummarizingProvider {
+        model_config: ModelConfig,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl SummarizingProvider {
+        fn new(context_limit: usize) -> Self {
+            Self {
+                model_config: ModelConfig::new("test-model".to_string())
+                    .with_context_limit(Some(context_limit)),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for SummarizingProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            self.model_config.clone()
+        }
+
+        async fn complete(
+            &self,
+            _system: &str,
+            messages: &[Message],
+            _tools: &[Tool],
+        ) -> Result<(Message, ProviderUsage), ProviderError> {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(messages[0].as_concat_text());
+            Ok((
+                Message::assistant().with_text(format!("synopsis {}", requests.len())),
+                ProviderUsage::new("test-model".to_string(), Usage::default()),
+            ))
+        }
+    }
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::user().with_text("Only use the standard library"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(ToolCall::new("developer__shell", json!({"command": "ls"}))),
+            ),
+            Message::user().with_tool_response("call_1", Ok(vec![Content::text("main.rs")])),
+            Message::assistant().with_text("There is one file"),
+            Message::user().with_text("Add a test"),
+            Message::assistant().with_text("Done"),
+        ]
+    }
+
+    #[test]
+    fn test_split_point_keeps_tool_results_with_their_calls() {
+        let compaction = Compaction {
+            keep_recent: 3,
+            ..Default::default()
+        };
+        let messages = conversation();
+        // Keeping 3 messages would start at the tool result, and the turn before it is the first
+        assert_eq!(compaction.split_point(&messages, 0), None);
+
+        let compaction = Compaction {
+            keep_recent: 2,
+            ..Default::default()
+        };
+        assert_eq!(compaction.split_point(&messages, 0), Some(4));
+        assert_eq!(compaction.split_point(&messages, 4), None);
+    }
+
+    #[test]
+    fn test_compacted_messages() {
+        let messages = conversation();
+        let summary = ConversationSummary {
+            summary: "Listed the files".to_string(),
+            message_count: 4,
+        };
+        let compacted = compacted_messages(&messages, &summary, &[0, 4]);
+        assert_eq!(compacted.len(), 2);
+        let synopsis = compacted[0].content[0].as_text().unwrap();
+        assert!(synopsis.contains("Listed the files"));
+        // Only pinned messages that were summarized are repeated
+        assert!(synopsis.contains("user: Only use the standard library"));
+        assert!(!synopsis.contains("Add a test"));
+        assert_eq!(compacted[0].content[1].as_text(), Some("Add a test"));
+        assert_eq!(compacted[1], messages[5]);
+
+        // A synopsis that no longer lines up with the conversation is ignored
+        let stale = ConversationSummary {
+            summary: "Listed the files".to_string(),
+            message_count: 2,
+        };
+        assert_eq!(compacted_messages(&messages, &stale, &[]), messages);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_extends_the_synopsis() -> anyhow::Result<()> {
+        let provider = SummarizingProvider::new(100_000);
+        let token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
+        let compaction = Compaction::default();
+        let messages = conversation();
+
+        let (first, usage) = compaction
+            .summarize(&provider, &token_counter, &messages, None, 4)
+            .await?;
+        assert_eq!(usage.len(), 1);
+        assert_eq!(first.summary, "synopsis 1");
+        assert_eq!(first.message_count, 4);
+        {
+            let requests = provider.requests.lock().unwrap();
+            assert!(requests[0].contains("assistant called developer__shell"));
+            assert!(requests[0].contains("tool result: main.rs"));
+        }
+
+        let (second, _) = compaction
+            .summarize(&provider, &token_counter, &messages, Some(&first), 6)
+            .await?;
+        assert_eq!(second.message_count, 6);
+        let requests = provider.requests.lock().unwrap();
+        assert!(requests[1].contains("synopsis 1"));
+        assert!(requests[1].contains("user: Add a test"));
+        assert!(!requests[1].contains("developer__shell"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summarize_in_parts() -> anyhow::Result<()> {
+        // Too small a context for the whole conversation in one request
+        let provider = SummarizingProvider::new(20);
+        let token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
+        let (summary, usage) = Compaction::default()
+            .summarize(&provider, &token_counter, &conversation(), None, 4)
+            .await?;
+        let requests = provider.requests.lock().unwrap();
+        assert!(requests.len() > 1);
+        assert_eq!(usage.len(), requests.len());
+        assert_eq!(summary.summary, format!("synopsis {}", requests.len()));
+        assert!(requests[1].contains("synopsis 1"));
+        Ok(())
+    }
+}
@@ -30,7 +30,7 @@ impl ExperimentManager {
     pub fn set_enabled(name: &str, enabled: bool) -> Result<()> {
         let config = Config::global();
         let mut experiments: HashMap<String, bool> = config
-            .get_param("experiments")
+            .get_global_param("experiments")
             .unwrap_or_else(|_| HashMap::new());
         Self::refresh_experiments(&mut experiments);
         experiments.insert(name.to_string(), enabled);
@@ -1,6 +1,7 @@
 use super::base::Config;
 use crate::agents::ExtensionConfig;
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use utoipa::ToSchema;
@@ -10,7 +11,7 @@ pub const DEFAULT_EXTENSION_TIMEOUT: u64 = 300;
 pub const DEFAULT_EXTENSION_DESCRIPTION: &str = "";
 pub const DEFAULT_DISPLAY_NAME: &str = "Developer";
 
-#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema, JsonSchema)]
 pub struct ExtensionEntry {
     pub enabled: bool,
     #[serde(flatten)]
@@ -24,6 +25,12 @@ pub fn name_to_key(name: &str) -> String {
         .to_lowercase()
 }
 
+/// The secret holding the request headers of the remote extension with `key`, which are kept
+/// in the keyring rather than the config file as they usually carry credentials
+pub fn headers_secret(key: &str) -> String {
+    format!("EXTENSION_{}_HEADERS", key.to_uppercase())
+}
+
 /// Extension configuration management
 pub struct ExtensionManager;
 
@@ -68,7 +75,7 @@ impl ExtensionManager {
         let config = Config::global();
 
         let mut extensions: HashMap<String, ExtensionEntry> = config
-            .get_param("extensions")
+            .get_global_param("extensions")
             .unwrap_or_else(|_| HashMap::new());
 
         let key = entry.config.key();
@@ -83,11 +90,17 @@ impl ExtensionManager {
         let config = Config::global();
 
         let mut extensions: HashMap<String, ExtensionEntry> = config
-            .get_param("extensions")
+            .get_global_param("extensions")
             .unwrap_or_else(|_| HashMap::new());
 
         extensions.remove(key);
         config.set_param("extensions", serde_json::to_value(extensions)?)?;
+        let secret = headers_secret(key);
+        if config.get_secret::<serde_json::Value>(&secret).is_ok() {
+            if let Err(e) = config.delete_secret(&secret) {
+                tracing::warn!("Failed to delete the headers of {}: {}", key, e);
+            }
+        }
         Ok(())
     }
 
@@ -96,7 +109,7 @@ impl ExtensionManager {
         let config = Config::global();
 
         let mut extensions: HashMap<String, ExtensionEntry> = config
-            .get_param("extensions")
+            .get_global_param("extensions")
             .unwrap_or_else(|_| HashMap::new());
 
         if let Some(entry) = extensions.get_mut(key) {
@@ -0,0 +1,306 @@
+//! Migrations of the config file between versions of goose
+//!
+//! The config file records the version of its layout in `config_version`. On startup, the
+//! migrations newer than that version are applied in order, after copying the file to a
+//! backup next to it, so settings from older versions keep working instead of being ignored.
+//! Files without a version are at version 0.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use super::base::{Config, ConfigError};
+use super::secret_store::is_reference;
+use crate::providers::base::ProviderMetadata;
+
+/// Config key holding the version of the config file layout
+pub const CONFIG_VERSION_KEY: &str = "config_version";
+
+pub struct Migration {
+    /// The version of the config after this migration
+    pub version: u64,
+    pub description: &'static str,
+    migrate: fn(&mut Migrating) -> Result<(), ConfigError>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Replace the GOOSE_PROVIDER__* settings with the provider's own settings",
+        migrate: migrate_provider_settings,
+    },
+    Migration {
+        version: 2,
+        description: "Move provider secrets from the config file to the secret store",
+        migrate: move_secrets,
+    },
+];
+
+/// The version of the config file layout written by this version of goose
+pub fn current_version() -> u64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// The outcome of migrating a config file
+#[derive(Debug, Default, PartialEq)]
+pub struct MigrationReport {
+    pub from_version: u64,
+    pub to_version: u64,
+    /// The migrations that changed the config
+    pub applied: Vec<&'static str>,
+    /// The copy of the config from before the migrations
+    pub backup: PathBuf,
+}
+
+/// A config being migrated
+struct Migrating<'a> {
+    config: &'a Config,
+    providers: &'a [ProviderMetadata],
+    values: HashMap<String, Value>,
+    changed: bool,
+}
+
+impl Migrating<'_> {
+    /// Rename a key, unless the new key is already set, in which case the old one is dropped
+    fn rename(&mut self, from: &str, to: &str) {
+        if let Some(value) = self.values.remove(from) {
+            self.changed = true;
+            self.values.entry(to.to_string()).or_insert(value);
+        }
+    }
+
+    /// Move a value to the secret store, keeping any secret already stored under the key
+    fn move_to_secrets(&mut self, from: &str, to: &str) -> Result<(), ConfigError> {
+        if let Some(value) = self.values.remove(from) {
+            self.changed = true;
+            if !self.config.load_secrets()?.contains_key(to) {
+                self.config.set_secret(to, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn provider(&self) -> Option<&ProviderMetadata> {
+        let name = self.values.get("GOOSE_PROVIDER")?.as_str()?;
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+}
+
+/// The old settings applied to whichever provider was selected, so its host and key move to
+/// that provider's settings
+fn migrate_provider_settings(migrating: &mut Migrating) -> Result<(), ConfigError> {
+    migrating.rename("GOOSE_PROVIDER__TYPE", "GOOSE_PROVIDER");
+    migrating.rename("GOOSE_PROVIDER__MODEL", "GOOSE_MODEL");
+
+    let Some(provider) = migrating.provider() else {
+        return Ok(());
+    };
+    let host = provider
+        .config_keys
+        .iter()
+        .find(|key| key.name.ends_with("_HOST"))
+        .map(|key| key.name.clone());
+    let api_key = provider
+        .config_keys
+        .iter()
+        .find(|key| key.secret && key.required)
+        .map(|key| key.name.clone());
+    if let Some(host) = host {
+        migrating.rename("GOOSE_PROVIDER__HOST", &host);
+    }
+    if let Some(api_key) = api_key {
+        migrating.move_to_secrets("GOOSE_PROVIDER__API_KEY", &api_key)?;
+    }
+    Ok(())
+}
+
+/// Secrets are only read from the secret store, so ones written to the config file by hand were
+/// ignored; references to secrets, like op:// references, stay in the file
+fn move_secrets(migrating: &mut Migrating) -> Result<(), ConfigError> {
+    let secrets: Vec<String> = migrating
+        .providers
+        .iter()
+        .flat_map(|provider| provider.config_keys.iter())
+        .filter(|key| key.secret)
+        .map(|key| key.name.clone())
+        .filter(|name| {
+            migrating
+                .values
+                .get(name)
+                .is_some_and(|value| !is_reference(value) && !value.to_string().contains("${"))
+        })
+        .collect();
+    for name in secrets {
+        migrating.move_to_secrets(&name, &name)?;
+    }
+    Ok(())
+}
+
+/// Apply the migrations the config file is missing
+///
+/// Does nothing if there is no config file yet, it is already current, or none of the
+/// migrations change it. A file from a newer version of goose is left alone.
+pub fn migrate(
+    config: &Config,
+    providers: &[ProviderMetadata],
+) -> Result<Option<MigrationReport>, ConfigError> {
+    if !config.exists() {
+        return Ok(None);
+    }
+    let values = config.load_global_values()?;
+    let from_version = values
+        .get(CONFIG_VERSION_KEY)
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    let to_version = current_version();
+    if from_version >= to_version {
+        return Ok(None);
+    }
+
+    let mut migrating = Migrating {
+        config,
+        providers,
+        values,
+        changed: false,
+    };
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > from_version) {
+        migrating.changed = false;
+        (migration.migrate)(&mut migrating)?;
+        if migrating.changed {
+            applied.push(migration.description);
+        }
+    }
+
+    // A file the migrations didn't change is left as it is, rather than written on every run
+    // just to record the version
+    if applied.is_empty() {
+        return Ok(None);
+    }
+
+    // Back up the file as it was, which the migrations only changed in memory so far
+    let path = PathBuf::from(config.path());
+    let backup = path.with_extension(format!("yaml.v{}.bak", from_version));
+    std::fs::copy(&path, &backup)?;
+
+    let mut values = migrating.values;
+    values.insert(CONFIG_VERSION_KEY.to_string(), Value::from(to_version));
+    config.save_values(values)?;
+
+    Ok(Some(MigrationReport {
+        from_version,
+        to_version,
+        applied,
+        backup,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::ConfigKey;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn providers() -> Vec<ProviderMetadata> {
+        vec![ProviderMetadata::new(
+            "openai",
+            "OpenAI",
+            "",
+            "gpt-4o",
+            vec![],
+            "",
+            vec![
+                ConfigKey::new("OPENAI_API_KEY", true, true, None),
+                ConfigKey::new("OPENAI_HOST", true, false, Some("https://api.openai.com")),
+                ConfigKey::new("OPENAI_CUSTOM_HEADERS", false, true, None),
+            ],
+        )]
+    }
+
+    fn config_file(dir: &TempDir, yaml: &str) -> Config {
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, yaml).unwrap();
+        Config::new(&path, "test")
+            .unwrap()
+            .with_encrypted_secrets("test")
+    }
+
+    #[test]
+    fn test_migrate_old_settings() {
+        let dir = TempDir::new().unwrap();
+        let yaml = "GOOSE_PROVIDER__TYPE: openai\n\
+                    GOOSE_PROVIDER__MODEL: gpt-4o\n\
+                    GOOSE_PROVIDER__HOST: https://openai.internal\n\
+                    GOOSE_PROVIDER__API_KEY: sk-old\n\
+                    OPENAI_CUSTOM_HEADERS: op://Private/OpenAI/headers\n";
+        let config = config_file(&dir, yaml);
+
+        let report = migrate(&config, &providers()).unwrap().unwrap();
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.to_version, current_version());
+        assert_eq!(report.applied, vec![MIGRATIONS[0].description]);
+        assert_eq!(std::fs::read_to_string(report.backup).unwrap(), yaml);
+
+        let values = config.load_global_values().unwrap();
+        assert_eq!(
+            values,
+            HashMap::from([
+                ("GOOSE_PROVIDER".to_string(), json!("openai")),
+                ("GOOSE_MODEL".to_string(), json!("gpt-4o")),
+                ("OPENAI_HOST".to_string(), json!("https://openai.internal")),
+                (
+                    "OPENAI_CUSTOM_HEADERS".to_string(),
+                    json!("op://Private/OpenAI/headers")
+                ),
+                (CONFIG_VERSION_KEY.to_string(), json!(current_version())),
+            ])
+        );
+        assert_eq!(
+            config.get_secret::<String>("OPENAI_API_KEY").unwrap(),
+            "sk-old"
+        );
+
+        // Migrated configs are left alone
+        assert_eq!(migrate(&config, &providers()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_migrate_secrets() {
+        let dir = TempDir::new().unwrap();
+        let config = config_file(
+            &dir,
+            "GOOSE_PROVIDER: openai\nOPENAI_API_KEY: sk-plain\nOPENAI_HOST: https://api.openai.com\n",
+        );
+        config
+            .set_secret("OPENAI_API_KEY", json!("sk-stored"))
+            .unwrap();
+
+        let report = migrate(&config, &providers()).unwrap().unwrap();
+        assert_eq!(report.applied, vec![MIGRATIONS[1].description]);
+        let values = config.load_global_values().unwrap();
+        assert!(!values.contains_key("OPENAI_API_KEY"));
+        // A secret already in the store wins over the copy in the file
+        assert_eq!(
+            config.get_secret::<String>("OPENAI_API_KEY").unwrap(),
+            "sk-stored"
+        );
+    }
+
+    #[test]
+    fn test_migrate_current_config() {
+        let dir = TempDir::new().unwrap();
+        let config = config_file(&dir, "GOOSE_PROVIDER: openai\n");
+
+        // Nothing is written, not even the version, nor backed up
+        assert_eq!(migrate(&config, &providers()).unwrap(), None);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.yaml")).unwrap(),
+            "GOOSE_PROVIDER: openai\n"
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let newer = config_file(&dir, &format!("{}: 1000\n", CONFIG_VERSION_KEY));
+        assert_eq!(migrate(&newer, &providers()).unwrap(), None);
+    }
+}
@@ -1,6 +1,13 @@
 mod base;
 mod experiments;
 pub mod extensions;
+pub mod migrations;
+pub mod owner;
+pub mod reload;
+pub mod schema;
+pub mod secret_store;
+mod secrets_file;
+pub mod settings;
 
 pub use crate::agents::ExtensionConfig;
 pub use base::{Config, ConfigError, APP_STRATEGY};
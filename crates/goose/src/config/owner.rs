@@ -0,0 +1,81 @@
+//! The owner of what goose keeps in stores shared with other users
+//!
+//! Sessions in a shared session store are kept under an owner id, so one user's goose doesn't
+//! see or overwrite another's. The id is `GOOSE_OWNER_ID` when it is set, which users set to
+//! the same value on each of their machines to share their sessions between them, and
+//! otherwise one made up for this machine the first time it is needed.
+use etcetera::{choose_app_strategy, AppStrategy};
+use once_cell::sync::OnceCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::{Config, APP_STRATEGY};
+
+/// The id of this user in shared stores
+pub fn owner_id() -> String {
+    if let Ok(owner) = Config::global().get_param::<String>("GOOSE_OWNER_ID") {
+        if !owner.trim().is_empty() {
+            return owner.trim().to_string();
+        }
+    }
+    machine_id()
+}
+
+/// The id made up for this machine, which GOOSE_OWNER_ID doesn't change
+pub fn machine_id() -> String {
+    static MACHINE_ID: OnceCell<String> = OnceCell::new();
+    MACHINE_ID
+        .get_or_init(|| {
+            kept_id(&machine_id_path()).unwrap_or_else(|e| {
+                tracing::warn!("Failed to keep an id for this machine: {}", e);
+                uuid::Uuid::new_v4().to_string()
+            })
+        })
+        .clone()
+}
+
+fn machine_id_path() -> PathBuf {
+    choose_app_strategy(APP_STRATEGY.clone())
+        .map(|strategy| strategy.data_dir())
+        .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+        .join("machine_id")
+}
+
+/// The id kept in `path`, made up and saved there if there isn't one yet
+fn kept_id(path: &Path) -> std::io::Result<String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(mut file) => file.write_all(uuid::Uuid::new_v4().to_string().as_bytes())?,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    // Read back, in case another goose made the file first
+    let id = std::fs::read_to_string(path)?.trim().to_string();
+    if id.is_empty() {
+        return Err(std::io::Error::other(format!(
+            "{} is empty, delete it to make a new id",
+            path.display()
+        )));
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_id_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("goose").join("machine_id");
+        let id = kept_id(&path).unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(kept_id(&path).unwrap(), id);
+    }
+}
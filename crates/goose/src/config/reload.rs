@@ -0,0 +1,167 @@
+//! Hot reloading of configuration changes into running sessions
+//!
+//! [`watch_config`] polls the config files (the global one and the project's) and publishes
+//! a [`ConfigReloadEvent`] listing the keys that changed. Sessions apply the changes that
+//! don't interrupt their work with [`apply_config_reload`] between turns: switching the
+//! provider, model or temperature, and enabling or disabling extensions. Anything else takes
+//! effect the next time it is read, or when goose restarts.
+use once_cell::sync::{Lazy, OnceCell};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use super::base::Config;
+use super::extensions::ExtensionManager;
+use crate::agents::{normalize, Agent};
+use crate::model::ModelConfig;
+
+/// How often the config files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Keys that select the provider and model, a change to any of them recreates the provider
+const PROVIDER_KEYS: &[&str] = &["GOOSE_PROVIDER", "GOOSE_MODEL", "GOOSE_TEMPERATURE"];
+
+static CONFIG_RELOAD_EVENTS: Lazy<broadcast::Sender<ConfigReloadEvent>> =
+    Lazy::new(|| broadcast::channel(16).0);
+
+/// Published when the config files change
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigReloadEvent {
+    /// The top level keys that were added, changed or removed, sorted
+    pub changed_keys: Vec<String>,
+}
+
+/// Subscribe to config reloads
+pub fn config_reload_events() -> broadcast::Receiver<ConfigReloadEvent> {
+    CONFIG_RELOAD_EVENTS.subscribe()
+}
+
+/// Start watching the global config for changes, if it isn't being watched already
+///
+/// This needs to be called from within a tokio runtime.
+pub fn watch_config() {
+    static WATCHING: OnceCell<()> = OnceCell::new();
+    WATCHING.get_or_init(|| {
+        tokio::spawn(async {
+            let config = Config::global();
+            let mut values = config.load_values().unwrap_or_default();
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                // A file that fails to parse is likely being edited, wait for the next save
+                let Ok(current) = config.load_values() else {
+                    continue;
+                };
+                let changed_keys = changed_keys(&values, &current);
+                if !changed_keys.is_empty() {
+                    tracing::info!("Config reloaded, changed keys: {:?}", changed_keys);
+                    let _ = CONFIG_RELOAD_EVENTS.send(ConfigReloadEvent { changed_keys });
+                    values = current;
+                }
+            }
+        });
+    });
+}
+
+fn changed_keys(old: &HashMap<String, Value>, new: &HashMap<String, Value>) -> Vec<String> {
+    let mut keys: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// Apply a config reload to an agent, returning a description of each change made, or of
+/// the failure if it couldn't be made
+///
+/// Changes that fail, such as a provider that can't be created from a half finished edit,
+/// leave that part of the agent as it was.
+pub async fn apply_config_reload(
+    agent: &mut dyn Agent,
+    event: &ConfigReloadEvent,
+) -> Vec<Result<String, String>> {
+    let mut changes = Vec::new();
+    let config = Config::global();
+
+    if event
+        .changed_keys
+        .iter()
+        .any(|key| PROVIDER_KEYS.contains(&key.as_str()))
+    {
+        let provider: Result<String, _> = config.get_param("GOOSE_PROVIDER");
+        let model: Result<String, _> = config.get_param("GOOSE_MODEL");
+        changes.push(match (provider, model) {
+            (Ok(provider), Ok(model)) => {
+                match crate::providers::create(&provider, ModelConfig::new(model.clone())) {
+                    Ok(created) => {
+                        agent.update_provider(created).await;
+                        Ok(format!("switched to {} with model {}", provider, model))
+                    }
+                    Err(e) => Err(format!(
+                        "failed to switch to {} with model {}: {}",
+                        provider, model, e
+                    )),
+                }
+            }
+            _ => {
+                Err("failed to switch provider, GOOSE_PROVIDER and GOOSE_MODEL must be set".into())
+            }
+        });
+    }
+
+    if event.changed_keys.iter().any(|key| key == "extensions") {
+        let loaded: HashSet<String> = agent.list_extensions().await.into_iter().collect();
+        // Extensions that aren't in the config, e.g. added on the command line, are left alone
+        for entry in ExtensionManager::get_all().unwrap_or_default() {
+            let name = entry.config.name();
+            let is_loaded = loaded.contains(&normalize(entry.config.key()));
+            if entry.enabled && !is_loaded {
+                changes.push(match agent.add_extension(entry.config).await {
+                    Ok(()) => Ok(format!("enabled extension {}", name)),
+                    Err(e) => Err(format!("failed to enable extension {}: {}", name, e)),
+                });
+            } else if !entry.enabled && is_loaded {
+                agent.remove_extension(&entry.config.key()).await;
+                changes.push(Ok(format!("disabled extension {}", name)));
+            }
+        }
+    }
+
+    for change in &changes {
+        match change {
+            Ok(change) => tracing::info!("Config reload {}", change),
+            Err(e) => tracing::warn!("Config reload {}", e),
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_changed_keys() {
+        let old = HashMap::from([
+            ("GOOSE_MODEL".to_string(), json!("gpt-4o")),
+            ("GOOSE_PROVIDER".to_string(), json!("openai")),
+            ("removed".to_string(), json!(true)),
+        ]);
+        let new = HashMap::from([
+            ("GOOSE_MODEL".to_string(), json!("gpt-4o-mini")),
+            ("GOOSE_PROVIDER".to_string(), json!("openai")),
+            ("added".to_string(), json!(1)),
+        ]);
+        assert_eq!(
+            changed_keys(&old, &new),
+            vec!["GOOSE_MODEL", "added", "removed"]
+        );
+        assert!(changed_keys(&new, &new).is_empty());
+    }
+}
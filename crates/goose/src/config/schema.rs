@@ -0,0 +1,252 @@
+//! A JSON Schema for the config file
+//!
+//! The schema is built from [`SETTINGS`], the structs the object settings deserialize to and
+//! each provider's [`ConfigKey`]s, so editors can complete and check `config.yaml`, e.g. with a
+//! `# yaml-language-server: $schema=...` comment pointing at the output of
+//! `goose config schema`.
+use std::collections::HashMap;
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+use super::settings::{
+    find_setting, provider_setting_type, Setting, PROVIDER_SETTING_SUFFIXES, SETTINGS,
+};
+use super::ExtensionEntry;
+use crate::agents::approval::{ApprovalRule, APPROVAL_RULES_CONFIG_KEY};
+use crate::agents::policy::{PolicyEntry, TOOL_POLICY_CONFIG_KEY};
+use crate::model::pricing::{ModelPricing, PRICING_CONFIG_KEY};
+use crate::model::{ModelOverrides, MODELS_CONFIG_KEY};
+use crate::persona::{Persona, PERSONAS_CONFIG_KEY};
+use crate::providers::base::{ConfigKey, ProviderMetadata};
+use crate::schedule::{ScheduledJob, SCHEDULES_CONFIG_KEY};
+use crate::usage::alerts::{CostAlert, COST_ALERTS_CONFIG_KEY};
+
+const EXTENSIONS_CONFIG_KEY: &str = "extensions";
+const EXPERIMENTS_CONFIG_KEY: &str = "experiments";
+
+/// The JSON Schema of the config file, with the settings of the given providers
+pub fn config_schema(providers: &[ProviderMetadata]) -> Value {
+    let mut generator = SchemaSettings::draft07().into_generator();
+    let mut properties = Map::new();
+    for setting in SETTINGS {
+        let mut schema = setting_schema(setting, &mut generator);
+        schema["description"] = json!(setting.description);
+        properties.insert(setting.key.to_string(), schema);
+    }
+    for provider in providers {
+        for config_key in &provider.config_keys {
+            // Keys shared by several providers, like GCP_PROJECT_ID, are described once
+            if !properties.contains_key(&config_key.name) {
+                properties.insert(
+                    config_key.name.clone(),
+                    provider_key_schema(provider, config_key),
+                );
+            }
+        }
+    }
+
+    let suffixes: Vec<_> = PROVIDER_SETTING_SUFFIXES
+        .iter()
+        .map(|suffix| suffix.trim_start_matches('_'))
+        .collect();
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "goose configuration",
+        "type": "object",
+        "properties": properties,
+        "patternProperties": {
+            format!("^[A-Z0-9_]+_({})$", suffixes.join("|")): {
+                "description": "A provider's network setting, e.g. OPENAI_PROXY"
+            }
+        },
+        "definitions": generator.definitions(),
+    })
+}
+
+fn setting_schema(setting: &Setting, generator: &mut SchemaGenerator) -> Value {
+    let schema = match setting.key {
+        EXTENSIONS_CONFIG_KEY => generator.subschema_for::<HashMap<String, ExtensionEntry>>(),
+        EXPERIMENTS_CONFIG_KEY => generator.subschema_for::<HashMap<String, bool>>(),
+        PRICING_CONFIG_KEY => generator.subschema_for::<HashMap<String, ModelPricing>>(),
+        COST_ALERTS_CONFIG_KEY => generator.subschema_for::<Vec<CostAlert>>(),
+        APPROVAL_RULES_CONFIG_KEY => generator.subschema_for::<Vec<ApprovalRule>>(),
+        TOOL_POLICY_CONFIG_KEY => generator.subschema_for::<HashMap<String, PolicyEntry>>(),
+        MODELS_CONFIG_KEY => generator.subschema_for::<HashMap<String, ModelOverrides>>(),
+        SCHEDULES_CONFIG_KEY => generator.subschema_for::<HashMap<String, ScheduledJob>>(),
+        PERSONAS_CONFIG_KEY => generator.subschema_for::<HashMap<String, Persona>>(),
+        _ => return json!({"type": setting.kind.json_type()}),
+    };
+    serde_json::to_value(schema).expect("schemas serialize to JSON")
+}
+
+fn provider_key_schema(provider: &ProviderMetadata, config_key: &ConfigKey) -> Value {
+    let mut description = format!("A setting of the {} provider", provider.display_name);
+    if config_key.secret {
+        description.push_str(", kept in the secret store unless it is an op:// reference");
+    }
+    let mut schema = json!({
+        "type": provider_setting_type(config_key).json_type(),
+        "description": description,
+    });
+    if let Some(default) = &config_key.default {
+        schema["default"] = json!(default);
+    }
+    schema
+}
+
+/// Check a value is valid for its key before it is saved
+///
+/// Keys goose doesn't read are accepted, as they may be read by another version of goose.
+pub fn validate_setting(
+    key: &str,
+    value: &Value,
+    providers: &[ProviderMetadata],
+) -> Result<(), String> {
+    fn deserializes<T: DeserializeOwned>(key: &str, value: &Value) -> Result<(), String> {
+        serde_json::from_value::<T>(value.clone())
+            .map(|_| ())
+            .map_err(|e| format!("{} is invalid: {}", key, e))
+    }
+
+    let kind = match key {
+        EXTENSIONS_CONFIG_KEY => {
+            return deserializes::<HashMap<String, ExtensionEntry>>(key, value)
+        }
+        EXPERIMENTS_CONFIG_KEY => return deserializes::<HashMap<String, bool>>(key, value),
+        PRICING_CONFIG_KEY => return deserializes::<HashMap<String, ModelPricing>>(key, value),
+        COST_ALERTS_CONFIG_KEY => return deserializes::<Vec<CostAlert>>(key, value),
+        APPROVAL_RULES_CONFIG_KEY => return deserializes::<Vec<ApprovalRule>>(key, value),
+        TOOL_POLICY_CONFIG_KEY => return deserializes::<HashMap<String, PolicyEntry>>(key, value),
+        MODELS_CONFIG_KEY => return deserializes::<HashMap<String, ModelOverrides>>(key, value),
+        SCHEDULES_CONFIG_KEY => return deserializes::<HashMap<String, ScheduledJob>>(key, value),
+        PERSONAS_CONFIG_KEY => return deserializes::<HashMap<String, Persona>>(key, value),
+        _ => match find_setting(key) {
+            Some(setting) => setting.kind,
+            None => match providers
+                .iter()
+                .flat_map(|provider| provider.config_keys.iter())
+                .find(|config_key| config_key.name == key)
+            {
+                Some(config_key) => provider_setting_type(config_key),
+                None => return Ok(()),
+            },
+        },
+    };
+    if kind.matches(value) {
+        Ok(())
+    } else {
+        Err(format!("{} must be of type {}", key, kind.json_type()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn providers() -> Vec<ProviderMetadata> {
+        vec![ProviderMetadata::new(
+            "openai",
+            "OpenAI",
+            "",
+            "gpt-4o",
+            vec![],
+            "",
+            vec![
+                ConfigKey::new("OPENAI_API_KEY", true, true, None),
+                ConfigKey::new("OPENAI_HOST", true, false, Some("https://api.openai.com")),
+                ConfigKey::new("OPENAI_TIMEOUT", false, false, Some("600")),
+            ],
+        )]
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = config_schema(&providers());
+        let properties = &schema["properties"];
+
+        assert_eq!(properties["GOOSE_TEMPERATURE"]["type"], "number");
+        assert_eq!(properties["OPENAI_HOST"]["type"], "string");
+        assert_eq!(
+            properties["OPENAI_HOST"]["default"],
+            "https://api.openai.com"
+        );
+        assert_eq!(properties["OPENAI_TIMEOUT"]["type"], "integer");
+
+        // Object settings are described by the structs they deserialize to
+        let entry = properties["extensions"]["additionalProperties"]["$ref"]
+            .as_str()
+            .unwrap()
+            .trim_start_matches("#/definitions/");
+        assert!(schema["definitions"][entry].is_object());
+        assert_eq!(
+            properties["cost_alerts"]["items"]["$ref"],
+            "#/definitions/CostAlert"
+        );
+        assert!(schema["definitions"]["AlertPeriod"].is_object());
+    }
+
+    #[test]
+    fn test_validate_setting() {
+        let providers = providers();
+        let valid = [
+            ("GOOSE_TEMPERATURE", json!(0.2)),
+            ("GOOSE_SESSION_MAX_TOKENS", json!(100000)),
+            ("OPENAI_TIMEOUT", json!(600)),
+            ("OPENAI_TIMEOUT", json!("600")),
+            ("GCP_MAX_RETRIES", json!("5")),
+            ("cost_alerts", json!([{"period": "day", "threshold": 5.0}])),
+            ("approval_rules", json!([{"tool": "developer__shell"}])),
+            (
+                "tool_policy",
+                json!({"developer": "ask", "developer__shell": {"allow": [{"command": "ls*"}]}}),
+            ),
+            (
+                "extensions",
+                json!({"developer": {"enabled": true, "type": "builtin", "name": "developer"}}),
+            ),
+            (
+                "models",
+                json!({"llama": {"temperature": 0.2, "stop": ["<|eot_id|>"]}}),
+            ),
+            (
+                "schedules",
+                json!({"standup": {"cron": "0 9 * * 1-5", "prompt": "Summarize the commits"}}),
+            ),
+            (
+                "personas",
+                json!({"reviewer": {"temperature": 0.1, "tool_policy": {"*": "ask"}}}),
+            ),
+            ("SOME_OTHER_TOOL_SETTING", json!([1, 2])),
+        ];
+        for (key, value) in valid {
+            assert!(validate_setting(key, &value, &providers).is_ok(), "{}", key);
+        }
+
+        let invalid = [
+            ("GOOSE_TEMPERATURE", json!("warm")),
+            ("GOOSE_SESSION_MAX_TOKENS", json!(1.5)),
+            ("OPENAI_HOST", json!(443)),
+            ("OPENAI_TIMEOUT", json!("ten minutes")),
+            ("GCP_MAX_RETRIES", json!("5.5")),
+            ("cost_alerts", json!([{"period": "year", "threshold": 5.0}])),
+            ("approval_rules", json!([{"arguments": "rm"}])),
+            ("tool_policy", json!({"developer": "sometimes"})),
+            ("extensions", json!({"developer": {"enabled": true}})),
+            ("models", json!({"llama": {"temperature": "low"}})),
+            ("schedules", json!({"standup": {"cron": "0 9 * * 1-5"}})),
+            (
+                "personas",
+                json!({"reviewer": {"tool_policy": {"*": "maybe"}}}),
+            ),
+        ];
+        for (key, value) in invalid {
+            assert!(
+                validate_setting(key, &value, &providers).is_err(),
+                "{}",
+                key
+            );
+        }
+    }
+}
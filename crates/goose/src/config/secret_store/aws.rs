@@ -0,0 +1,433 @@
+//! Secrets kept in AWS Secrets Manager or Systems Manager Parameter Store
+//!
+//! With Secrets Manager all of goose's secrets are one secret, `goose` unless
+//! `GOOSE_AWS_SECRET_ID` names another, holding a JSON object. With Parameter Store each
+//! secret is a `SecureString` parameter under `/goose`, or the path in `GOOSE_AWS_SSM_PATH`,
+//! e.g. `/goose/OPENAI_API_KEY`.
+//!
+//! Requests are signed with the standard AWS credential chain, in `AWS_REGION`, so an
+//! instance or task role is all goose-server needs when it runs on AWS.
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use super::{block_on, Cache, SecretStore, DEFAULT_CACHE_TTL};
+use crate::config::{Config, ConfigError};
+use crate::providers::sigv4::SigV4Signer;
+
+const DEFAULT_SECRET_ID: &str = "goose";
+const DEFAULT_SSM_PATH: &str = "/goose";
+
+#[derive(Debug, Clone)]
+pub enum AwsSecretsBackend {
+    /// One Secrets Manager secret holding all the secrets as a JSON object
+    SecretsManager { secret_id: String },
+    /// One `SecureString` parameter per secret, under a path
+    ParameterStore { path: String },
+}
+
+pub struct AwsSecretStore {
+    backend: AwsSecretsBackend,
+    region: Option<String>,
+    endpoint: Option<String>,
+    signer: Option<SigV4Signer>,
+    cache: Cache,
+}
+
+impl AwsSecretStore {
+    pub fn new(backend: AwsSecretsBackend) -> Self {
+        Self {
+            backend,
+            region: None,
+            endpoint: None,
+            signer: None,
+            cache: Cache::new(DEFAULT_CACHE_TTL),
+        }
+    }
+
+    /// Use a region other than the default one from the AWS config
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Send requests to an endpoint other than the service's regional one, such as a VPC
+    /// endpoint
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Sign requests with the given signer instead of the default credential chain
+    pub fn with_signer(mut self, signer: SigV4Signer) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// The `aws-secrets-manager` store, with its settings from the config
+    pub fn secrets_manager(config: &Config) -> Self {
+        let secret_id = config
+            .get_param("GOOSE_AWS_SECRET_ID")
+            .unwrap_or_else(|_| DEFAULT_SECRET_ID.to_string());
+        Self::from_config(
+            config,
+            AwsSecretsBackend::SecretsManager { secret_id },
+            "AWS_ENDPOINT_URL_SECRETS_MANAGER",
+        )
+    }
+
+    /// The `aws-ssm` store, with its settings from the config
+    pub fn parameter_store(config: &Config) -> Self {
+        let path = config
+            .get_param("GOOSE_AWS_SSM_PATH")
+            .unwrap_or_else(|_| DEFAULT_SSM_PATH.to_string());
+        Self::from_config(
+            config,
+            AwsSecretsBackend::ParameterStore { path },
+            "AWS_ENDPOINT_URL_SSM",
+        )
+    }
+
+    fn from_config(config: &Config, backend: AwsSecretsBackend, endpoint_key: &str) -> Self {
+        let optional = |key: &str| config.get_param::<String>(key).ok();
+        Self {
+            backend,
+            region: optional("AWS_REGION"),
+            endpoint: optional(endpoint_key).or_else(|| optional("AWS_ENDPOINT_URL")),
+            signer: None,
+            cache: Cache::from_config(config),
+        }
+    }
+
+    /// The service to sign for and the prefix of its JSON API's targets
+    fn service(&self) -> (&'static str, &'static str) {
+        match self.backend {
+            AwsSecretsBackend::SecretsManager { .. } => ("secretsmanager", "secretsmanager"),
+            AwsSecretsBackend::ParameterStore { .. } => ("ssm", "AmazonSSM"),
+        }
+    }
+
+    async fn signer(&self) -> Result<SigV4Signer, ConfigError> {
+        match &self.signer {
+            Some(signer) => Ok(signer.clone()),
+            None => SigV4Signer::from_env(self.service().0, self.region.clone())
+                .await
+                .map_err(|e| ConfigError::SecretStoreError(e.to_string())),
+        }
+    }
+
+    /// Call an action of the service's JSON API, returning None if what it acts on doesn't
+    /// exist
+    async fn call(
+        &self,
+        client: &Client,
+        signer: &SigV4Signer,
+        action: &str,
+        payload: Value,
+    ) -> Result<Option<Value>, ConfigError> {
+        let (service, target) = self.service();
+        let url = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => format!("https://{}.{}.amazonaws.com/", service, signer.region()),
+        };
+        let request = client
+            .post(url)
+            .header("X-Amz-Target", format!("{}.{}", target, action))
+            .header(CONTENT_TYPE, "application/x-amz-json-1.1")
+            .body(payload.to_string());
+        let response = signer
+            .sign(request)
+            .await
+            .map_err(|e| ConfigError::SecretStoreError(e.to_string()))?
+            .send()
+            .await
+            .map_err(|e| {
+                ConfigError::SecretStoreError(format!("{} request failed: {}", service, e))
+            })?;
+
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        let body: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+        if status.is_success() {
+            return Ok(Some(body));
+        }
+        // Error types are sometimes qualified with a namespace, e.g. `aws.ssm#ParameterNotFound`
+        let error_type = body["__type"].as_str().unwrap_or_default();
+        if error_type.ends_with("ResourceNotFoundException")
+            || error_type.ends_with("ParameterNotFound")
+        {
+            return Ok(None);
+        }
+        let message = body["Message"]
+            .as_str()
+            .or_else(|| body["message"].as_str())
+            .unwrap_or(&text);
+        Err(ConfigError::SecretStoreError(format!(
+            "{} {} returned {}: {} {}",
+            service, action, status, error_type, message
+        )))
+    }
+
+    async fn read(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        let client = Client::new();
+        let signer = self.signer().await?;
+        match &self.backend {
+            AwsSecretsBackend::SecretsManager { secret_id } => {
+                let Some(body) = self
+                    .call(
+                        &client,
+                        &signer,
+                        "GetSecretValue",
+                        json!({"SecretId": secret_id}),
+                    )
+                    .await?
+                else {
+                    return Ok(HashMap::new());
+                };
+                let secret = body["SecretString"].as_str().unwrap_or("{}");
+                serde_json::from_str(secret).map_err(|_| {
+                    ConfigError::SecretStoreError(format!(
+                        "secret {} must hold a JSON object of secrets",
+                        secret_id
+                    ))
+                })
+            }
+            AwsSecretsBackend::ParameterStore { path } => {
+                let prefix = parameter_prefix(path);
+                let mut values = HashMap::new();
+                let mut next_token: Option<String> = None;
+                loop {
+                    let mut payload = json!({
+                        "Path": prefix.trim_end_matches('/'),
+                        "WithDecryption": true,
+                        "Recursive": false,
+                    });
+                    if let Some(token) = next_token.take() {
+                        payload["NextToken"] = json!(token);
+                    }
+                    let body = self
+                        .call(&client, &signer, "GetParametersByPath", payload)
+                        .await?
+                        .unwrap_or_default();
+                    for parameter in body["Parameters"].as_array().into_iter().flatten() {
+                        let (Some(name), Some(value)) =
+                            (parameter["Name"].as_str(), parameter["Value"].as_str())
+                        else {
+                            continue;
+                        };
+                        let key = name.strip_prefix(&prefix).unwrap_or(name);
+                        // Parsed like environment variables, so non-string secrets round trip
+                        let value = serde_json::from_str(value)
+                            .unwrap_or_else(|_| Value::String(value.to_string()));
+                        values.insert(key.to_string(), value);
+                    }
+                    match body["NextToken"].as_str() {
+                        Some(token) => next_token = Some(token.to_string()),
+                        None => return Ok(values),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn write(
+        &self,
+        values: &HashMap<String, Value>,
+        current: &HashMap<String, Value>,
+    ) -> Result<(), ConfigError> {
+        let client = Client::new();
+        let signer = self.signer().await?;
+        match &self.backend {
+            AwsSecretsBackend::SecretsManager { secret_id } => {
+                let secret = serde_json::to_string(values)?;
+                let put = json!({"SecretId": secret_id, "SecretString": secret});
+                if self
+                    .call(&client, &signer, "PutSecretValue", put)
+                    .await?
+                    .is_none()
+                {
+                    let create = json!({"Name": secret_id, "SecretString": secret});
+                    self.call(&client, &signer, "CreateSecret", create).await?;
+                }
+            }
+            AwsSecretsBackend::ParameterStore { path } => {
+                // Only the parameters that changed are written, each one is a separate request
+                let prefix = parameter_prefix(path);
+                for (key, value) in values {
+                    if current.get(key) == Some(value) {
+                        continue;
+                    }
+                    let value = match value {
+                        Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    };
+                    let put = json!({
+                        "Name": format!("{}{}", prefix, key),
+                        "Value": value,
+                        "Type": "SecureString",
+                        "Overwrite": true,
+                    });
+                    self.call(&client, &signer, "PutParameter", put).await?;
+                }
+                for key in current.keys().filter(|key| !values.contains_key(*key)) {
+                    let delete = json!({"Name": format!("{}{}", prefix, key)});
+                    self.call(&client, &signer, "DeleteParameter", delete)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SecretStore for AwsSecretStore {
+    fn load(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        self.cache.get(|| block_on(self.read()))
+    }
+
+    fn save(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        let current = match self.backend {
+            AwsSecretsBackend::ParameterStore { .. } => self.load()?,
+            AwsSecretsBackend::SecretsManager { .. } => HashMap::new(),
+        };
+        block_on(self.write(values, &current))?;
+        self.cache.set(values);
+        Ok(())
+    }
+}
+
+/// The parameter path with a leading and trailing slash, e.g. `/goose/`
+fn parameter_prefix(path: &str) -> String {
+    format!("/{}/", path.trim_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use wiremock::matchers::{body_json, header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn store(server: &MockServer, backend: AwsSecretsBackend) -> AwsSecretStore {
+        let service = match backend {
+            AwsSecretsBackend::SecretsManager { .. } => "secretsmanager",
+            AwsSecretsBackend::ParameterStore { .. } => "ssm",
+        };
+        let credentials = Credentials::new("AKIDEXAMPLE", "secret", None, None, "test");
+        AwsSecretStore::new(backend)
+            .with_endpoint(server.uri())
+            .with_signer(SigV4Signer::new(credentials, "us-east-1", service))
+    }
+
+    fn target(target: &str) -> wiremock::matchers::HeaderExactMatcher {
+        header("X-Amz-Target", target)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_secrets_manager() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(target("secretsmanager.GetSecretValue"))
+            .and(body_json(json!({"SecretId": "goose"})))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "__type": "ResourceNotFoundException",
+                "Message": "Secrets Manager can't find the specified secret."
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("secretsmanager.PutSecretValue"))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(json!({"__type": "ResourceNotFoundException"})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("secretsmanager.CreateSecret"))
+            .and(body_json(json!({
+                "Name": "goose",
+                "SecretString": "{\"SAMBANOVA_API_KEY\":\"sn-key\"}"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"Name": "goose"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = store(
+            &server,
+            AwsSecretsBackend::SecretsManager {
+                secret_id: "goose".to_string(),
+            },
+        );
+        assert!(store.load().unwrap().is_empty());
+        let values = HashMap::from([("SAMBANOVA_API_KEY".to_string(), json!("sn-key"))]);
+        store.save(&values).unwrap();
+        assert_eq!(store.load().unwrap(), values);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_parameter_store() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(target("AmazonSSM.GetParametersByPath"))
+            .and(body_json(
+                json!({"Path": "/teams/ml", "WithDecryption": true, "Recursive": false}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "Parameters": [{"Name": "/teams/ml/OPENAI_API_KEY", "Value": "sk-key"}],
+                "NextToken": "page-2"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("AmazonSSM.GetParametersByPath"))
+            .and(body_json(json!({
+                "Path": "/teams/ml",
+                "WithDecryption": true,
+                "Recursive": false,
+                "NextToken": "page-2"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "Parameters": [{"Name": "/teams/ml/SAMBANOVA_API_KEY", "Value": "sn-key"}]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("AmazonSSM.PutParameter"))
+            .and(body_json(json!({
+                "Name": "/teams/ml/OPENAI_API_KEY",
+                "Value": "sk-rotated",
+                "Type": "SecureString",
+                "Overwrite": true
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"Version": 2})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("AmazonSSM.DeleteParameter"))
+            .and(body_json(json!({"Name": "/teams/ml/SAMBANOVA_API_KEY"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = store(
+            &server,
+            AwsSecretsBackend::ParameterStore {
+                path: "teams/ml".to_string(),
+            },
+        );
+        assert_eq!(
+            store.load().unwrap(),
+            HashMap::from([
+                ("OPENAI_API_KEY".to_string(), json!("sk-key")),
+                ("SAMBANOVA_API_KEY".to_string(), json!("sn-key")),
+            ])
+        );
+        let values = HashMap::from([("OPENAI_API_KEY".to_string(), json!("sk-rotated"))]);
+        store.save(&values).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! Secrets kept in GCP Secret Manager
+//!
+//! All of goose's secrets are one secret, `goose` unless `GOOSE_GCP_SECRET_ID` names
+//! another, holding a JSON object. The secret is in the project in `GOOSE_GCP_SECRETS_PROJECT`,
+//! or `GCP_PROJECT_ID` as used by the Vertex AI provider, and requests are authenticated with
+//! Application Default Credentials.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use super::{block_on, Cache, SecretStore, DEFAULT_CACHE_TTL};
+use crate::config::{Config, ConfigError};
+use crate::providers::gcpauth::GcpAuth;
+
+const DEFAULT_ENDPOINT: &str = "https://secretmanager.googleapis.com";
+const DEFAULT_SECRET_ID: &str = "goose";
+
+pub struct GcpSecretStore {
+    project: String,
+    secret_id: String,
+    endpoint: String,
+    access_token: Option<String>,
+    cache: Cache,
+}
+
+impl GcpSecretStore {
+    pub fn new(project: impl Into<String>, secret_id: impl Into<String>) -> Self {
+        Self {
+            project: project.into(),
+            secret_id: secret_id.into(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            access_token: None,
+            cache: Cache::new(DEFAULT_CACHE_TTL),
+        }
+    }
+
+    /// Send requests to an endpoint other than the global one, such as a regional endpoint
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Authenticate with the given access token instead of Application Default Credentials
+    pub fn with_access_token(mut self, access_token: impl Into<String>) -> Self {
+        self.access_token = Some(access_token.into());
+        self
+    }
+
+    /// Create the store from the `GOOSE_GCP_*` settings
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let optional = |key: &str| config.get_param::<String>(key).ok();
+        let project = optional("GOOSE_GCP_SECRETS_PROJECT")
+            .or_else(|| optional("GCP_PROJECT_ID"))
+            .ok_or_else(|| {
+                ConfigError::SecretStoreError(
+                    "set GOOSE_GCP_SECRETS_PROJECT or GCP_PROJECT_ID to use the gcp secret store"
+                        .into(),
+                )
+            })?;
+        let mut store = Self::new(
+            project,
+            optional("GOOSE_GCP_SECRET_ID").unwrap_or_else(|| DEFAULT_SECRET_ID.to_string()),
+        );
+        if let Some(endpoint) = optional("GOOSE_GCP_SECRETS_ENDPOINT") {
+            store = store.with_endpoint(endpoint);
+        }
+        store.cache = Cache::from_config(config);
+        Ok(store)
+    }
+
+    fn secret_url(&self) -> String {
+        format!(
+            "{}/v1/projects/{}/secrets/{}",
+            self.endpoint.trim_end_matches('/'),
+            self.project,
+            self.secret_id
+        )
+    }
+
+    async fn authorization(&self) -> Result<String, ConfigError> {
+        if let Some(token) = &self.access_token {
+            return Ok(format!("Bearer {}", token));
+        }
+        // Not GcpAuth::new, which reads GCP_CREDENTIALS_JSON through the config, and so this
+        // store while it is being loaded
+        let auth = GcpAuth::from_application_default()
+            .await
+            .map_err(|e| ConfigError::SecretStoreError(e.to_string()))?;
+        let token = auth
+            .get_token()
+            .await
+            .map_err(|e| ConfigError::SecretStoreError(e.to_string()))?;
+        Ok(token.to_string())
+    }
+
+    /// Send a request, returning None if the secret doesn't exist
+    async fn send(&self, request: RequestBuilder) -> Result<Option<Value>, ConfigError> {
+        let response = request
+            .header(AUTHORIZATION, self.authorization().await?)
+            .send()
+            .await
+            .map_err(|e| {
+                ConfigError::SecretStoreError(format!("secret manager request failed: {}", e))
+            })?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(ConfigError::SecretStoreError(format!(
+                "secret manager returned {} for secret {}: {}",
+                status,
+                self.secret_id,
+                body["error"]["message"].as_str().unwrap_or_default()
+            )));
+        }
+        Ok(Some(body))
+    }
+
+    async fn read(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        let client = Client::new();
+        let url = format!("{}/versions/latest:access", self.secret_url());
+        let Some(body) = self.send(client.get(url)).await? else {
+            return Ok(HashMap::new());
+        };
+        let data = STANDARD
+            .decode(body["payload"]["data"].as_str().unwrap_or_default())
+            .map_err(|e| ConfigError::SecretStoreError(format!("invalid secret payload: {}", e)))?;
+        serde_json::from_slice(&data).map_err(|_| {
+            ConfigError::SecretStoreError(format!(
+                "secret {} must hold a JSON object of secrets",
+                self.secret_id
+            ))
+        })
+    }
+
+    async fn write(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        let client = Client::new();
+        let payload = json!({"payload": {"data": STANDARD.encode(serde_json::to_vec(values)?)}});
+        let add_version = || {
+            client
+                .post(format!("{}:addVersion", self.secret_url()))
+                .json(&payload)
+        };
+        if self.send(add_version()).await?.is_some() {
+            return Ok(());
+        }
+
+        // The first save creates the secret, replicated automatically
+        let create = client
+            .post(format!(
+                "{}/v1/projects/{}/secrets",
+                self.endpoint.trim_end_matches('/'),
+                self.project
+            ))
+            .query(&[("secretId", &self.secret_id)])
+            .json(&json!({"replication": {"automatic": {}}}));
+        self.send(create).await?;
+        self.send(add_version()).await?;
+        Ok(())
+    }
+}
+
+impl SecretStore for GcpSecretStore {
+    fn load(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        self.cache.get(|| block_on(self.read()))
+    }
+
+    fn save(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        block_on(self.write(values))?;
+        self.cache.set(values);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET_PATH: &str = "/v1/projects/my-project/secrets/goose";
+
+    fn store(server: &MockServer) -> GcpSecretStore {
+        GcpSecretStore::new("my-project", "goose")
+            .with_endpoint(server.uri())
+            .with_access_token("ya29.token")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_read_latest_version() {
+        let server = MockServer::start().await;
+        let data = STANDARD.encode(r#"{"SAMBANOVA_API_KEY":"sn-key"}"#);
+        Mock::given(method("GET"))
+            .and(path(format!("{}/versions/latest:access", SECRET_PATH)))
+            .and(header("authorization", "Bearer ya29.token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "projects/123/secrets/goose/versions/4",
+                "payload": {"data": data}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = store(&server);
+        let expected = HashMap::from([("SAMBANOVA_API_KEY".to_string(), json!("sn-key"))]);
+        assert_eq!(store.load().unwrap(), expected);
+        // Cached, the secret is only accessed once
+        assert_eq!(store.load().unwrap(), expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_first_save_creates_secret() {
+        let server = MockServer::start().await;
+        let values = HashMap::from([("OPENAI_API_KEY".to_string(), json!("sk-key"))]);
+        let payload =
+            json!({"payload": {"data": STANDARD.encode(r#"{"OPENAI_API_KEY":"sk-key"}"#)}});
+        Mock::given(method("POST"))
+            .and(path(format!("{}:addVersion", SECRET_PATH)))
+            .and(body_json(&payload))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                "error": {"code": 404, "message": "Secret [goose] not found"}
+            })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/projects/my-project/secrets"))
+            .and(query_param("secretId", "goose"))
+            .and(body_json(json!({"replication": {"automatic": {}}})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("{}:addVersion", SECRET_PATH)))
+            .and(body_json(&payload))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = store(&server);
+        store.save(&values).unwrap();
+        assert_eq!(store.load().unwrap(), values);
+    }
+}
@@ -0,0 +1,180 @@
+//! Stores for secrets other than the system keyring
+//!
+//! Secrets are kept in the keyring by default. Setting `GOOSE_SECRET_STORE` selects another
+//! store instead:
+//! - `file`: an encrypted file next to the config file, see `GOOSE_SECRETS_PASSPHRASE`
+//! - `vault`: a HashiCorp Vault KV v2 secret, see [`VaultStore`]
+//! - `aws-secrets-manager`: an AWS Secrets Manager secret, see [`AwsSecretStore`]
+//! - `aws-ssm`: AWS Systems Manager Parameter Store parameters, see [`AwsSecretStore`]
+//! - `gcp`: a GCP Secret Manager secret, see [`GcpSecretStore`]
+//!
+//! Secrets from the remote stores are cached for `GOOSE_SECRET_STORE_TTL` seconds, a minute
+//! by default, and are then loaded again on the next read. If that fails the cached secrets
+//! keep being used, so a brief outage of the secret manager doesn't stop goose.
+//!
+//! Whichever store is used, a config value or secret can also be a 1Password reference such
+//! as `op://Private/OpenAI/credential`, which is read with the 1Password CLI when used.
+//!
+//! Applications embedding goose can also provide their own [`SecretStore`] with
+//! [`Config::with_secret_store`].
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::base::{Config, ConfigError};
+
+mod aws;
+mod gcp;
+mod onepassword;
+mod vault;
+
+pub use aws::{AwsSecretStore, AwsSecretsBackend};
+pub use gcp::GcpSecretStore;
+#[cfg(test)]
+pub(crate) use onepassword::OP_PROGRAM;
+pub(crate) use onepassword::{is_reference, resolve_references};
+pub use vault::{VaultAuth, VaultSettings, VaultStore};
+
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// A store that keeps all of goose's secrets together, as a map from key to value
+pub trait SecretStore: Send + Sync {
+    /// Load all the secrets, returning an empty map if none have been saved yet
+    fn load(&self) -> Result<HashMap<String, Value>, ConfigError>;
+
+    /// Replace all the secrets
+    fn save(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError>;
+}
+
+/// Create the secret store named by `GOOSE_SECRET_STORE`, with its settings from the config
+pub(crate) fn create(name: &str, config: &Config) -> Result<Box<dyn SecretStore>, ConfigError> {
+    match name {
+        "vault" => Ok(Box::new(VaultStore::from_config(config)?)),
+        "aws-secrets-manager" => Ok(Box::new(AwsSecretStore::secrets_manager(config))),
+        "aws-ssm" => Ok(Box::new(AwsSecretStore::parameter_store(config))),
+        "gcp" => Ok(Box::new(GcpSecretStore::from_config(config)?)),
+        _ => Err(ConfigError::SecretStoreError(format!(
+            "unknown GOOSE_SECRET_STORE {}, expected one of keyring, file, vault, \
+             aws-secrets-manager, aws-ssm, gcp",
+            name
+        ))),
+    }
+}
+
+/// Run a blocking HTTP exchange on its own thread
+///
+/// Config reads are synchronous but are often made from async code, where reqwest's blocking
+/// client can't be used, so the stores that call out to a secret manager do it through this.
+/// The session stores use it for the same reason with their database clients.
+pub(crate) fn off_runtime<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(f)
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Run a future to completion on its own thread and runtime, for stores built on async
+/// clients, which the session archive is too
+pub(crate) fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    off_runtime(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start a runtime for the secret store")
+            .block_on(future)
+    })
+}
+
+/// Secrets loaded from a remote store, reused until they are older than the TTL
+struct Cache {
+    ttl: Duration,
+    loaded: Mutex<Option<(Instant, HashMap<String, Value>)>>,
+}
+
+impl Cache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            loaded: Mutex::new(None),
+        }
+    }
+
+    fn from_config(config: &Config) -> Self {
+        Self::new(
+            config
+                .get_param::<u64>("GOOSE_SECRET_STORE_TTL")
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_CACHE_TTL),
+        )
+    }
+
+    /// The cached secrets, loading them first if they have expired
+    ///
+    /// When loading fails the expired secrets are returned instead, and loading is tried
+    /// again once they expire again.
+    fn get(
+        &self,
+        load: impl FnOnce() -> Result<HashMap<String, Value>, ConfigError>,
+    ) -> Result<HashMap<String, Value>, ConfigError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some((at, values)) = loaded.as_ref() {
+            if at.elapsed() < self.ttl {
+                return Ok(values.clone());
+            }
+        }
+        match (load(), loaded.as_mut()) {
+            (Ok(values), _) => {
+                *loaded = Some((Instant::now(), values.clone()));
+                Ok(values)
+            }
+            (Err(e), Some((at, values))) => {
+                tracing::warn!("Failed to refresh secrets, using cached ones: {}", e);
+                *at = Instant::now();
+                Ok(values.clone())
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    fn set(&self, values: &HashMap<String, Value>) {
+        *self.loaded.lock().unwrap() = Some((Instant::now(), values.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_refreshes_and_falls_back() {
+        let cache = Cache::new(Duration::ZERO);
+        let first = HashMap::from([("KEY".to_string(), json!("first"))]);
+        let second = HashMap::from([("KEY".to_string(), json!("second"))]);
+
+        assert!(cache
+            .get(|| Err(ConfigError::SecretStoreError("down".into())))
+            .is_err());
+        assert_eq!(cache.get(|| Ok(first.clone())).unwrap(), first);
+        // Expired immediately, so loaded again, keeping the old secrets when that fails
+        assert_eq!(cache.get(|| Ok(second.clone())).unwrap(), second);
+        assert_eq!(
+            cache
+                .get(|| Err(ConfigError::SecretStoreError("down".into())))
+                .unwrap(),
+            second
+        );
+
+        let cache = Cache::new(Duration::from_secs(60));
+        cache.set(&first);
+        assert_eq!(cache.get(|| Ok(second.clone())).unwrap(), first);
+    }
+}
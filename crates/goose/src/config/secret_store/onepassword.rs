@@ -0,0 +1,139 @@
+//! 1Password secret references in config values
+//!
+//! Any config value or secret can be a reference like `op://Private/OpenAI/credential`
+//! instead of the secret itself, which is then read with the 1Password CLI (`op read`) when
+//! it is used. The CLI has to be installed and signed in, e.g. through the 1Password app's
+//! CLI integration, which may ask to approve the first read.
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::config::ConfigError;
+
+pub const REFERENCE_PREFIX: &str = "op://";
+
+/// References already read, so the CLI (and any approval prompt) runs once per reference
+static RESOLVED: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether a value is a 1Password secret reference
+pub fn is_reference(value: &Value) -> bool {
+    value
+        .as_str()
+        .is_some_and(|value| value.starts_with(REFERENCE_PREFIX))
+}
+
+/// Replace the secret references in a value, including those nested in lists and maps, with
+/// the secrets they refer to
+pub(crate) fn resolve_references(value: Value) -> Result<Value, ConfigError> {
+    resolve_with(value, &op_read)
+}
+
+fn resolve_with(
+    value: Value,
+    read: &impl Fn(&str) -> Result<String, ConfigError>,
+) -> Result<Value, ConfigError> {
+    Ok(match value {
+        Value::String(s) if s.starts_with(REFERENCE_PREFIX) => Value::String(read(&s)?),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| resolve_with(value, read))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| Ok((key, resolve_with(value, read)?)))
+                .collect::<Result<_, ConfigError>>()?,
+        ),
+        value => value,
+    })
+}
+
+#[cfg(not(test))]
+fn op_program() -> PathBuf {
+    PathBuf::from("op")
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The CLI tests run in place of `op`, so they never read from a real 1Password account
+    pub(crate) static OP_PROGRAM: std::cell::RefCell<PathBuf> =
+        std::cell::RefCell::new(PathBuf::from("goose-test-op-not-installed"));
+}
+
+#[cfg(test)]
+fn op_program() -> PathBuf {
+    OP_PROGRAM.with(|program| program.borrow().clone())
+}
+
+fn op_read(reference: &str) -> Result<String, ConfigError> {
+    if let Some(secret) = RESOLVED.lock().unwrap().get(reference) {
+        return Ok(secret.clone());
+    }
+
+    let output = Command::new(op_program())
+        .args(["read", "--no-newline", reference])
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ConfigError::SecretStoreError(format!(
+                "the 1Password CLI (op) is needed to read {}, install it from \
+                 https://developer.1password.com/docs/cli",
+                reference
+            )),
+            _ => ConfigError::SecretStoreError(format!("failed to run op: {}", e)),
+        })?;
+    if !output.status.success() {
+        return Err(ConfigError::SecretStoreError(format!(
+            "op read {} failed: {}",
+            reference,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let secret = String::from_utf8(output.stdout)
+        .map_err(|_| ConfigError::SecretStoreError(format!("{} is not valid UTF-8", reference)))?;
+    RESOLVED
+        .lock()
+        .unwrap()
+        .insert(reference.to_string(), secret.clone());
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve_references() {
+        let read = |reference: &str| match reference {
+            "op://Private/OpenAI/credential" => Ok("sk-key".to_string()),
+            _ => Err(ConfigError::SecretStoreError(format!(
+                "\"{}\" isn't an item",
+                reference
+            ))),
+        };
+
+        let value = json!({
+            "api_key": "op://Private/OpenAI/credential",
+            "headers": ["X-Team=ml", "op://Private/OpenAI/credential"],
+            "url": "https://api.openai.com/op://not-a-reference",
+            "timeout": 600
+        });
+        assert_eq!(
+            resolve_with(value, &read).unwrap(),
+            json!({
+                "api_key": "sk-key",
+                "headers": ["X-Team=ml", "sk-key"],
+                "url": "https://api.openai.com/op://not-a-reference",
+                "timeout": 600
+            })
+        );
+        assert!(resolve_with(json!("op://Private/Missing/credential"), &read).is_err());
+        assert!(is_reference(&json!("op://Private/OpenAI/credential")));
+        assert!(!is_reference(&json!("sk-key")));
+    }
+}
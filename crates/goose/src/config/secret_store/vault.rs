@@ -0,0 +1,341 @@
+//! Secrets kept in a HashiCorp Vault KV v2 secret
+//!
+//! All of goose's secrets are fields of one secret, `goose` in the `secret` mount unless
+//! `GOOSE_VAULT_PATH` and `GOOSE_VAULT_MOUNT` say otherwise, so they can be managed and
+//! audited together. The server is read from `VAULT_ADDR` (and `VAULT_NAMESPACE` on Vault
+//! Enterprise), and goose logs in with `VAULT_TOKEN` or, failing that, an AppRole from
+//! `VAULT_ROLE_ID` and `VAULT_SECRET_ID`.
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{off_runtime, Cache, SecretStore, DEFAULT_CACHE_TTL};
+use crate::config::{Config, ConfigError};
+
+const DEFAULT_MOUNT: &str = "secret";
+const DEFAULT_PATH: &str = "goose";
+const DEFAULT_APPROLE_MOUNT: &str = "approle";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub enum VaultAuth {
+    /// A token used as is
+    Token(String),
+    /// An AppRole to log in with, logging in again when its token expires
+    AppRole {
+        mount: String,
+        role_id: String,
+        secret_id: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct VaultSettings {
+    pub address: String,
+    pub namespace: Option<String>,
+    pub auth: VaultAuth,
+    /// The mount of the KV v2 secrets engine
+    pub mount: String,
+    /// The path of the secret within the mount
+    pub path: String,
+}
+
+pub struct VaultStore {
+    settings: VaultSettings,
+    token: Mutex<Option<String>>,
+    cache: Cache,
+}
+
+impl VaultStore {
+    pub fn new(settings: VaultSettings) -> Self {
+        Self::with_cache(settings, Cache::new(DEFAULT_CACHE_TTL))
+    }
+
+    fn with_cache(settings: VaultSettings, cache: Cache) -> Self {
+        Self {
+            settings,
+            token: Mutex::new(None),
+            cache,
+        }
+    }
+
+    /// Create the store from the `VAULT_*` and `GOOSE_VAULT_*` settings
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let optional = |key: &str| config.get_param::<String>(key).ok();
+        let address = optional("VAULT_ADDR").ok_or_else(|| {
+            ConfigError::SecretStoreError("set VAULT_ADDR to use the vault secret store".into())
+        })?;
+        let auth = match (
+            optional("VAULT_TOKEN"),
+            optional("VAULT_ROLE_ID"),
+            optional("VAULT_SECRET_ID"),
+        ) {
+            (Some(token), _, _) => VaultAuth::Token(token),
+            (None, Some(role_id), Some(secret_id)) => VaultAuth::AppRole {
+                mount: optional("GOOSE_VAULT_APPROLE_MOUNT")
+                    .unwrap_or_else(|| DEFAULT_APPROLE_MOUNT.to_string()),
+                role_id,
+                secret_id,
+            },
+            _ => {
+                return Err(ConfigError::SecretStoreError(
+                    "set VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID, to log in to vault"
+                        .into(),
+                ))
+            }
+        };
+        let settings = VaultSettings {
+            address,
+            namespace: optional("VAULT_NAMESPACE"),
+            auth,
+            mount: optional("GOOSE_VAULT_MOUNT").unwrap_or_else(|| DEFAULT_MOUNT.to_string()),
+            path: optional("GOOSE_VAULT_PATH").unwrap_or_else(|| DEFAULT_PATH.to_string()),
+        };
+        Ok(Self::with_cache(settings, Cache::from_config(config)))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/v1/{}",
+            self.settings.address.trim_end_matches('/'),
+            path.trim_matches('/')
+        )
+    }
+
+    fn secret_url(&self) -> String {
+        self.url(&format!(
+            "{}/data/{}",
+            self.settings.mount.trim_matches('/'),
+            self.settings.path.trim_matches('/')
+        ))
+    }
+
+    fn with_namespace(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.settings.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    fn token(&self, client: &Client) -> Result<String, ConfigError> {
+        let mut token = self.token.lock().unwrap();
+        if let Some(token) = token.as_ref() {
+            return Ok(token.clone());
+        }
+        let (mount, role_id, secret_id) = match &self.settings.auth {
+            VaultAuth::Token(token) => return Ok(token.clone()),
+            VaultAuth::AppRole {
+                mount,
+                role_id,
+                secret_id,
+            } => (mount, role_id, secret_id),
+        };
+
+        let request = client
+            .post(self.url(&format!("auth/{}/login", mount.trim_matches('/'))))
+            .json(&json!({"role_id": role_id, "secret_id": secret_id}));
+        let body: Value = check(self.with_namespace(request).send().map_err(request_error)?)?
+            .json()
+            .map_err(request_error)?;
+        let client_token = body
+            .pointer("/auth/client_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                ConfigError::SecretStoreError("vault AppRole login returned no token".into())
+            })?;
+        *token = Some(client_token.to_string());
+        Ok(client_token.to_string())
+    }
+
+    /// Send a request with the token, logging in again once if an AppRole token has expired
+    fn send(
+        &self,
+        client: &Client,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, ConfigError> {
+        let send = |token: String| {
+            self.with_namespace(request())
+                .header("X-Vault-Token", token)
+                .send()
+                .map_err(request_error)
+        };
+        let response = send(self.token(client)?)?;
+        if response.status() == StatusCode::FORBIDDEN
+            && matches!(self.settings.auth, VaultAuth::AppRole { .. })
+        {
+            *self.token.lock().unwrap() = None;
+            return send(self.token(client)?);
+        }
+        Ok(response)
+    }
+
+    fn read(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        let client = client()?;
+        let response = self.send(&client, || client.get(self.secret_url()))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(HashMap::new());
+        }
+        let body: Value = check(response)?.json().map_err(request_error)?;
+        match body.pointer("/data/data") {
+            Some(data) => Ok(serde_json::from_value(data.clone())?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    fn write(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        let client = client()?;
+        let response = self.send(&client, || {
+            client
+                .post(self.secret_url())
+                .json(&json!({ "data": values }))
+        })?;
+        check(response)?;
+        Ok(())
+    }
+}
+
+impl SecretStore for VaultStore {
+    fn load(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        self.cache.get(|| off_runtime(|| self.read()))
+    }
+
+    fn save(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        off_runtime(|| self.write(values))?;
+        self.cache.set(values);
+        Ok(())
+    }
+}
+
+fn client() -> Result<Client, ConfigError> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(request_error)
+}
+
+fn request_error(error: reqwest::Error) -> ConfigError {
+    ConfigError::SecretStoreError(format!("vault request failed: {}", error))
+}
+
+/// Turn an error status into an error with the messages vault returned
+fn check(response: Response) -> Result<Response, ConfigError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = response.url().to_string();
+    let errors = response
+        .json::<Value>()
+        .ok()
+        .and_then(|body| body.get("errors").cloned())
+        .and_then(|errors| serde_json::from_value::<Vec<String>>(errors).ok())
+        .unwrap_or_default();
+    Err(ConfigError::SecretStoreError(format!(
+        "vault returned {} for {}: {}",
+        status,
+        url,
+        errors.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn settings(server: &MockServer, auth: VaultAuth) -> VaultSettings {
+        VaultSettings {
+            address: server.uri(),
+            namespace: Some("team".to_string()),
+            auth,
+            mount: "kv".to_string(),
+            path: "apps/goose".to_string(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_approle_login_and_kv_v2_read_write() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/auth/approle/login"))
+            .and(header("X-Vault-Namespace", "team"))
+            .and(body_json(json!({"role_id": "role", "secret_id": "secret"})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"auth": {"client_token": "s.token"}})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/kv/data/apps/goose"))
+            .and(header("X-Vault-Token", "s.token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": {
+                    "data": {"SAMBANOVA_API_KEY": "sn-key"},
+                    "metadata": {"version": 3}
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/kv/data/apps/goose"))
+            .and(header("X-Vault-Token", "s.token"))
+            .and(body_json(
+                json!({"data": {"SAMBANOVA_API_KEY": "sn-rotated"}}),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = VaultStore::new(settings(
+            &server,
+            VaultAuth::AppRole {
+                mount: "approle".to_string(),
+                role_id: "role".to_string(),
+                secret_id: "secret".to_string(),
+            },
+        ));
+        let values = store.load().unwrap();
+        assert_eq!(values.get("SAMBANOVA_API_KEY"), Some(&json!("sn-key")));
+
+        let rotated = HashMap::from([("SAMBANOVA_API_KEY".to_string(), json!("sn-rotated"))]);
+        store.save(&rotated).unwrap();
+        // Served from the cache, the GET is only expected once
+        assert_eq!(store.load().unwrap(), rotated);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_missing_secret_and_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/kv/data/apps/goose"))
+            .and(header("X-Vault-Token", "good"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({"errors": []})))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/kv/data/apps/goose"))
+            .and(header("X-Vault-Token", "bad"))
+            .respond_with(
+                ResponseTemplate::new(403).set_body_json(json!({"errors": ["permission denied"]})),
+            )
+            .mount(&server)
+            .await;
+
+        let store = VaultStore::new(settings(&server, VaultAuth::Token("good".to_string())));
+        assert!(store.load().unwrap().is_empty());
+
+        let store = VaultStore::new(settings(&server, VaultAuth::Token("bad".to_string())));
+        assert!(matches!(
+            store.load(),
+            Err(ConfigError::SecretStoreError(msg)) if msg.contains("permission denied")
+        ));
+    }
+}
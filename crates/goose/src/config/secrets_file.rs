@@ -0,0 +1,205 @@
+//! Secrets stored in a passphrase encrypted file, for systems without a usable keyring such
+//! as headless Linux machines and containers
+//!
+//! The key is derived from the passphrase with Argon2id and the secrets are encrypted with
+//! XChaCha20-Poly1305. The passphrase is read from `GOOSE_SECRETS_PASSPHRASE`.
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::base::ConfigError;
+use super::secret_store::SecretStore;
+
+pub const PASSPHRASE_ENV: &str = "GOOSE_SECRETS_PASSPHRASE";
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// The file contents, with the binary fields base64 encoded
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub(crate) struct SecretsFile {
+    path: PathBuf,
+    passphrase: Option<String>,
+    /// Deriving the key is deliberately slow, so it is kept for the salt it was derived with
+    key: Mutex<Option<(Vec<u8>, Key)>>,
+}
+
+impl SecretsFile {
+    /// A secrets file at the path, using the given passphrase or the one from the environment
+    pub fn new(path: PathBuf, passphrase: Option<String>) -> Self {
+        Self {
+            path,
+            passphrase,
+            key: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a passphrase is available to encrypt and decrypt the file with
+    pub fn has_passphrase(&self) -> bool {
+        self.passphrase().is_ok()
+    }
+
+    fn passphrase(&self) -> Result<String, ConfigError> {
+        self.passphrase
+            .clone()
+            .or_else(|| std::env::var(PASSPHRASE_ENV).ok())
+            .filter(|passphrase| !passphrase.is_empty())
+            .ok_or_else(|| {
+                ConfigError::SecretsFileError(format!(
+                    "set {} to store secrets in {}",
+                    PASSPHRASE_ENV,
+                    self.path.display()
+                ))
+            })
+    }
+
+    fn key(&self, salt: &[u8]) -> Result<Key, ConfigError> {
+        let mut cached = self.key.lock().unwrap();
+        if let Some((cached_salt, key)) = cached.as_ref() {
+            if cached_salt == salt {
+                return Ok(*key);
+            }
+        }
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(self.passphrase()?.as_bytes(), salt, &mut key)
+            .map_err(|e| ConfigError::SecretsFileError(format!("key derivation failed: {}", e)))?;
+        *cached = Some((salt.to_vec(), key));
+        Ok(key)
+    }
+
+    pub fn load(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+        let file: EncryptedFile = serde_json::from_slice(&std::fs::read(&self.path)?)?;
+        if file.version != FORMAT_VERSION {
+            return Err(ConfigError::SecretsFileError(format!(
+                "unsupported version {} in {}",
+                file.version,
+                self.path.display()
+            )));
+        }
+        let salt = decode(&file.salt)?;
+        let nonce = decode(&file.nonce)?;
+        if nonce.len() != 24 {
+            return Err(ConfigError::SecretsFileError("invalid nonce".to_string()));
+        }
+        let plaintext = XChaCha20Poly1305::new(&self.key(&salt)?)
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                decode(&file.ciphertext)?.as_ref(),
+            )
+            .map_err(|_| {
+                ConfigError::SecretsFileError(format!(
+                    "failed to decrypt {}, check {}",
+                    self.path.display(),
+                    PASSPHRASE_ENV
+                ))
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    pub fn save(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        // Keep the salt of an existing file so the cached key can be reused
+        let salt = match self.key.lock().unwrap().as_ref() {
+            Some((salt, _)) => salt.clone(),
+            None => {
+                let mut salt = vec![0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+        };
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&self.key(&salt)?)
+            .encrypt(&nonce, serde_json::to_vec(values)?.as_ref())
+            .map_err(|_| ConfigError::SecretsFileError("encryption failed".to_string()))?;
+        let file = EncryptedFile {
+            version: FORMAT_VERSION,
+            salt: STANDARD.encode(&salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| ConfigError::DirectoryError(e.to_string()))?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut out = options.open(&self.path)?;
+        out.write_all(&serde_json::to_vec(&file)?)?;
+        Ok(())
+    }
+}
+
+impl SecretStore for SecretsFile {
+    fn load(&self) -> Result<HashMap<String, Value>, ConfigError> {
+        SecretsFile::load(self)
+    }
+
+    fn save(&self, values: &HashMap<String, Value>) -> Result<(), ConfigError> {
+        SecretsFile::save(self, values)
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, ConfigError> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| ConfigError::SecretsFileError(format!("invalid encoding: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_secrets_round_trip() -> Result<(), ConfigError> {
+        let dir = tempdir()?;
+        let path = dir.path().join("secrets.enc");
+        let secrets = SecretsFile::new(path.clone(), Some("correct horse".to_string()));
+        assert!(secrets.load()?.is_empty());
+
+        let values = HashMap::from([("OPENAI_API_KEY".to_string(), json!("sk-test"))]);
+        secrets.save(&values)?;
+        assert!(!std::fs::read_to_string(&path)?.contains("sk-test"));
+
+        // A fresh instance has to derive the key again
+        let reopened = SecretsFile::new(path.clone(), Some("correct horse".to_string()));
+        assert_eq!(reopened.load()?, values);
+
+        let wrong = SecretsFile::new(path, Some("battery staple".to_string()));
+        assert!(matches!(
+            wrong.load(),
+            Err(ConfigError::SecretsFileError(msg)) if msg.contains("failed to decrypt")
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,778 @@
+//! The settings goose reads from its config files, for validating them
+//!
+//! Provider settings come from each provider's [`ConfigKey`]s. Providers also read the network
+//! settings in [`PROVIDER_SETTING_SUFFIXES`] under their own prefix, e.g. `OPENAI_PROXY`,
+//! falling back to the `GOOSE_` ones listed here.
+use serde_json::Value;
+
+use crate::providers::base::{ConfigKey, ProviderMetadata};
+
+/// The type of value a setting takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Object,
+    Array,
+}
+
+impl SettingType {
+    /// The JSON Schema type of values of this type
+    pub fn json_type(&self) -> &'static str {
+        match self {
+            SettingType::String => "string",
+            SettingType::Integer => "integer",
+            SettingType::Number => "number",
+            SettingType::Boolean => "boolean",
+            SettingType::Object => "object",
+            SettingType::Array => "array",
+        }
+    }
+
+    /// Whether a value is of this type, counting a string holding an integer as one, as
+    /// `goose configure` and the environment give them
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            SettingType::String => value.is_string(),
+            SettingType::Integer => {
+                value.is_i64()
+                    || value.is_u64()
+                    || value
+                        .as_str()
+                        .is_some_and(|value| value.trim().parse::<i64>().is_ok())
+            }
+            SettingType::Number => value.is_number(),
+            SettingType::Boolean => value.is_boolean(),
+            SettingType::Object => value.is_object(),
+            SettingType::Array => value.is_array(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Setting {
+    pub key: &'static str,
+    pub kind: SettingType,
+    pub description: &'static str,
+}
+
+const fn setting(key: &'static str, kind: SettingType, description: &'static str) -> Setting {
+    Setting {
+        key,
+        kind,
+        description,
+    }
+}
+
+use SettingType::*;
+
+/// The settings that aren't specific to a provider
+pub const SETTINGS: &[Setting] = &[
+    setting(
+        "config_version",
+        Integer,
+        "The version of the config file layout, updated by goose as it migrates the file",
+    ),
+    setting("GOOSE_PROVIDER", String, "The provider to use, e.g. openai"),
+    setting("GOOSE_MODEL", String, "The model to use from the provider"),
+    setting(
+        "GOOSE_TEMPERATURE",
+        Number,
+        "The sampling temperature for the model",
+    ),
+    setting(
+        "GOOSE_MODE",
+        String,
+        "How tools are approved: auto, approve, smart_approve, chat or dry_run",
+    ),
+    setting("GOOSE_AGENT", String, "The agent version to use"),
+    setting(
+        "GOOSE_SUBAGENTS",
+        Boolean,
+        "Let the agent delegate scoped tasks to subagents",
+    ),
+    setting(
+        "GOOSE_SUBAGENT_MAX_TURNS",
+        Integer,
+        "The most turns a subagent can be given, 25 by default",
+    ),
+    setting(
+        "GOOSE_SUBAGENT_CONCURRENCY",
+        Integer,
+        "How many subagents spawned together run at once, 3 by default",
+    ),
+    setting(
+        "GOOSE_CRITIC",
+        Boolean,
+        "Have a critic review the agent's answers, which it then revises",
+    ),
+    setting(
+        "GOOSE_CRITIC_MAX_ITERATIONS",
+        Integer,
+        "The most times a turn the agent revises its answer for the critic, 2 by default",
+    ),
+    setting(
+        "GOOSE_CRITIC_MODEL",
+        String,
+        "A model for the critic, instead of the agent's own",
+    ),
+    setting(
+        "GOOSE_CRITIC_PROVIDER",
+        String,
+        "The provider of GOOSE_CRITIC_MODEL, GOOSE_PROVIDER by default",
+    ),
+    setting(
+        "GOOSE_MAX_TURNS",
+        Integer,
+        "The most completions the agent can make for one reply",
+    ),
+    setting(
+        "GOOSE_MAX_TOOL_CALLS",
+        Integer,
+        "The most tools the agent can call for one reply",
+    ),
+    setting(
+        "GOOSE_MAX_RUN_SECONDS",
+        Integer,
+        "The most seconds the agent can spend on one reply",
+    ),
+    setting(
+        "GOOSE_CLI_MIN_PRIORITY",
+        Number,
+        "The minimum priority of tool output shown by the CLI, from 0.0 to 1.0",
+    ),
+    setting(
+        "GOOSE_SYSTEM_PROMPT_FILE_PATH",
+        String,
+        "A file with a system prompt to use instead of the default one",
+    ),
+    setting(
+        "GOOSE_SYSTEM_PROMPT_APPEND",
+        String,
+        "Instructions to add to the end of the system prompt",
+    ),
+    setting(
+        "GOOSE_SESSION_MAX_TOKENS",
+        Integer,
+        "The most tokens a session can use",
+    ),
+    setting(
+        "GOOSE_SESSION_MAX_COST",
+        Number,
+        "The most a session can cost, in USD",
+    ),
+    setting(
+        "GOOSE_DAILY_MAX_TOKENS",
+        Integer,
+        "The most tokens all sessions can use in a day",
+    ),
+    setting(
+        "GOOSE_DAILY_MAX_COST",
+        Number,
+        "The most all sessions can cost in a day, in USD",
+    ),
+    setting(
+        "GOOSE_BUDGET_WARN_THRESHOLD",
+        Number,
+        "The fraction of a budget at which to warn, from 0.0 to 1.0",
+    ),
+    setting(
+        "GOOSE_AUTO_COMPACT",
+        Boolean,
+        "Summarize older turns when a conversation nears the context limit",
+    ),
+    setting(
+        "GOOSE_AUTO_COMPACT_THRESHOLD",
+        Number,
+        "The fraction of the context limit at which to summarize, from 0.1 to 1.0",
+    ),
+    setting(
+        "GOOSE_AUTO_COMPACT_KEEP_RECENT",
+        Integer,
+        "The number of recent messages never summarized",
+    ),
+    setting(
+        "GOOSE_TITLE_MODEL",
+        String,
+        "A smaller model to write session descriptions with, instead of the session's model",
+    ),
+    setting(
+        "GOOSE_TITLE_PROVIDER",
+        String,
+        "The provider of GOOSE_TITLE_MODEL, if it isn't GOOSE_PROVIDER",
+    ),
+    setting(
+        "GOOSE_IMAGE_MAX_DIMENSION",
+        Integer,
+        "The largest width or height of images sent to the provider, in pixels",
+    ),
+    setting(
+        "GOOSE_PROMPT_COMPRESSION",
+        Boolean,
+        "Compress older messages in long conversations",
+    ),
+    setting(
+        "GOOSE_PROMPT_COMPRESSION_KEEP_RECENT",
+        Integer,
+        "The number of recent messages left uncompressed",
+    ),
+    setting(
+        "GOOSE_PROMPT_COMPRESSION_MIN_CHARS",
+        Integer,
+        "The shortest message that is compressed, in characters",
+    ),
+    setting(
+        "GOOSE_PROMPT_COMPRESSION_RATIO",
+        Number,
+        "The target size of compressed messages, as a fraction of the original",
+    ),
+    setting(
+        "GOOSE_REDACTION",
+        Boolean,
+        "Mask secrets in messages before they are sent to the provider, on by default",
+    ),
+    setting(
+        "GOOSE_REDACT_PATTERNS",
+        Array,
+        "Extra regexes for secrets to mask",
+    ),
+    setting(
+        "GOOSE_PROXY",
+        String,
+        "The proxy for provider requests, e.g. http://proxy:8080",
+    ),
+    setting(
+        "GOOSE_NO_PROXY",
+        String,
+        "Hosts to reach without the proxy, comma separated",
+    ),
+    setting(
+        "GOOSE_CA_CERT",
+        String,
+        "A PEM file of extra CA certificates to trust",
+    ),
+    setting(
+        "GOOSE_CLIENT_CERT",
+        String,
+        "A PEM client certificate for mutual TLS",
+    ),
+    setting(
+        "GOOSE_CLIENT_KEY",
+        String,
+        "The PEM key of the client certificate",
+    ),
+    setting(
+        "GOOSE_REQUEST_COMPRESSION",
+        String,
+        "Compress request bodies: gzip or zstd",
+    ),
+    setting(
+        "GOOSE_CUSTOM_HEADERS",
+        String,
+        "Headers sent with every provider request, as NAME=value,NAME2=value2",
+    ),
+    setting(
+        "GOOSE_SECRET_STORE",
+        String,
+        "Where secrets are kept: keyring, file, vault, aws-secrets-manager, aws-ssm or gcp",
+    ),
+    setting(
+        "GOOSE_SECRET_STORE_TTL",
+        Integer,
+        "How long secrets from a secret manager are cached, in seconds",
+    ),
+    setting(
+        "GOOSE_SESSION_STORE",
+        String,
+        "Where sessions are saved: sqlite or postgres",
+    ),
+    setting(
+        "GOOSE_SESSION_STORE_URL",
+        String,
+        "The connection string of the postgres session store",
+    ),
+    setting(
+        "GOOSE_OWNER_ID",
+        String,
+        "Who sessions in a shared session store belong to, by default this machine",
+    ),
+    setting(
+        "GOOSE_SESSION_ENCRYPTION",
+        Boolean,
+        "Encrypt saved sessions with a key kept in the keyring",
+    ),
+    setting(
+        "GOOSE_SESSION_MAX_COUNT",
+        Integer,
+        "The most saved sessions to keep, removing the oldest",
+    ),
+    setting(
+        "GOOSE_SESSION_MAX_AGE_DAYS",
+        Integer,
+        "Remove saved sessions not updated for this many days",
+    ),
+    setting(
+        "GOOSE_SESSION_MAX_SIZE_MB",
+        Integer,
+        "The most disk space saved sessions may take, removing the oldest",
+    ),
+    setting(
+        "GOOSE_SESSION_ARCHIVE_URL",
+        String,
+        "An s3:// bucket and prefix to move old sessions to",
+    ),
+    setting(
+        "GOOSE_SESSION_ARCHIVE_ENDPOINT",
+        String,
+        "The endpoint of an S3 compatible store for the session archive, such as MinIO",
+    ),
+    setting(
+        "GOOSE_SESSION_ARCHIVE_REGION",
+        String,
+        "The region of the session archive's bucket",
+    ),
+    setting(
+        "GOOSE_SESSION_ARCHIVE_AFTER_DAYS",
+        Integer,
+        "Archive sessions not updated for this many days, 7 by default",
+    ),
+    setting(
+        "GOOSE_RECORD_SESSIONS",
+        Boolean,
+        "Record the completions and tool results of sessions to replay them",
+    ),
+    setting(
+        "GOOSE_CHECKPOINTS",
+        Boolean,
+        "Snapshot the working directory's git repository as each turn starts, to roll back to",
+    ),
+    setting("VAULT_ADDR", String, "The address of the Vault server"),
+    setting("VAULT_NAMESPACE", String, "The Vault Enterprise namespace"),
+    setting(
+        "VAULT_TOKEN",
+        String,
+        "The token to authenticate to Vault with",
+    ),
+    setting(
+        "VAULT_ROLE_ID",
+        String,
+        "The AppRole role ID to log in to Vault with",
+    ),
+    setting(
+        "VAULT_SECRET_ID",
+        String,
+        "The AppRole secret ID to log in to Vault with",
+    ),
+    setting(
+        "GOOSE_VAULT_APPROLE_MOUNT",
+        String,
+        "The path of the Vault AppRole auth method",
+    ),
+    setting(
+        "GOOSE_VAULT_MOUNT",
+        String,
+        "The path of the Vault KV v2 secrets engine",
+    ),
+    setting(
+        "GOOSE_VAULT_PATH",
+        String,
+        "The path of goose's secret in Vault",
+    ),
+    setting(
+        "AWS_REGION",
+        String,
+        "The AWS region of the secret store, and of Bedrock",
+    ),
+    setting("AWS_ENDPOINT_URL", String, "An endpoint for AWS requests"),
+    setting(
+        "AWS_ENDPOINT_URL_SECRETS_MANAGER",
+        String,
+        "An endpoint for AWS Secrets Manager requests",
+    ),
+    setting(
+        "AWS_ENDPOINT_URL_SSM",
+        String,
+        "An endpoint for AWS Systems Manager requests",
+    ),
+    setting(
+        "GOOSE_AWS_SECRET_ID",
+        String,
+        "The AWS Secrets Manager secret goose's secrets are kept in",
+    ),
+    setting(
+        "GOOSE_AWS_SSM_PATH",
+        String,
+        "The Parameter Store path goose's secrets are kept under",
+    ),
+    setting(
+        "GOOSE_GCP_SECRETS_PROJECT",
+        String,
+        "The GCP project of goose's Secret Manager secret",
+    ),
+    setting(
+        "GOOSE_GCP_SECRET_ID",
+        String,
+        "The GCP Secret Manager secret goose's secrets are kept in",
+    ),
+    setting(
+        "GOOSE_GCP_SECRETS_ENDPOINT",
+        String,
+        "An endpoint for GCP Secret Manager requests",
+    ),
+    setting(
+        "GCP_MAX_RETRIES",
+        Integer,
+        "How many times Vertex AI requests are retried",
+    ),
+    setting(
+        "GCP_INITIAL_RETRY_INTERVAL_MS",
+        Integer,
+        "The wait before the first Vertex AI retry, in milliseconds",
+    ),
+    setting(
+        "GCP_BACKOFF_MULTIPLIER",
+        Number,
+        "How much the wait grows between Vertex AI retries",
+    ),
+    setting(
+        "GCP_MAX_RETRY_INTERVAL_MS",
+        Integer,
+        "The longest wait between Vertex AI retries, in milliseconds",
+    ),
+    setting("extensions", Object, "The extensions goose can use, by key"),
+    setting(
+        "experiments",
+        Object,
+        "Experimental features, enabled or not",
+    ),
+    setting(
+        "pricing",
+        Object,
+        "Token prices by model, in USD per million tokens",
+    ),
+    setting("cost_alerts", Array, "Spend thresholds to be alerted at"),
+    setting(
+        "approval_rules",
+        Array,
+        "Tool calls that always wait for the user's approval",
+    ),
+    setting(
+        "tool_policy",
+        Object,
+        "Tool calls to allow, ask about or refuse, by extension or tool",
+    ),
+    setting(
+        "models",
+        Object,
+        "Parameters for individual models, like temperature, max_tokens and stop",
+    ),
+    setting(
+        "schedules",
+        Object,
+        "Prompts to run unattended on a cron schedule, by job name",
+    ),
+    setting(
+        "personas",
+        Object,
+        "Named bundles of instructions, model settings and tool policy to start sessions in",
+    ),
+    setting(
+        "GOOSE_PERSONA",
+        String,
+        "The persona sessions start in when none is chosen",
+    ),
+    setting(
+        "GOOSE_MEMORY",
+        Boolean,
+        "Remember facts and preferences from sessions and recall them in later ones",
+    ),
+    setting(
+        "GOOSE_MEMORY_RECALL_LIMIT",
+        Integer,
+        "The most memories to add to the system prompt of a reply",
+    ),
+    setting(
+        "GOOSE_MEMORY_MIN_SCORE",
+        Number,
+        "How similar to the user's message a memory must be to be recalled, from 0 to 1",
+    ),
+    setting(
+        "GOOSE_EMBEDDING_PROVIDER",
+        String,
+        "Where memories and indexed documents are embedded: openai, or ollama to keep them on the machine",
+    ),
+    setting(
+        "GOOSE_EMBEDDING_MODEL",
+        String,
+        "The model to embed memories and indexed documents with",
+    ),
+    setting(
+        "GOOSE_KNOWLEDGE",
+        String,
+        "How indexed documents reach the model: prompt to add relevant passages to the system prompt, tool to let it search them",
+    ),
+    setting(
+        "GOOSE_KNOWLEDGE_TOP_K",
+        Integer,
+        "The most indexed passages to add to the system prompt or return from a search",
+    ),
+    setting(
+        "GOOSE_KNOWLEDGE_MIN_SCORE",
+        Number,
+        "How similar to the user's message a passage must be to be added to the system prompt, from 0 to 1",
+    ),
+    setting(
+        "GOOSE_VECTOR_STORE",
+        String,
+        "Where memories and indexed documents keep their embeddings: sqlite, qdrant or pgvector",
+    ),
+    setting(
+        "GOOSE_VECTOR_STORE_URL",
+        String,
+        "The URL of the Qdrant server or the connection string of the pgvector database",
+    ),
+    setting(
+        "GOOSE_VECTOR_STORE_API_KEY",
+        String,
+        "The API key of the Qdrant server",
+    ),
+    setting(
+        "GOOSE_CODE_INDEX",
+        Boolean,
+        "Whether to index the definitions in the project and let the model search them",
+    ),
+    setting(
+        "GOOSE_TOOL_OUTPUT_MAX_TOKENS",
+        Integer,
+        "Tool outputs with more tokens than this are cut or summarized, 0 to always send them whole",
+    ),
+    setting(
+        "GOOSE_TOOL_OUTPUT_MODE",
+        String,
+        "How tool outputs over GOOSE_TOOL_OUTPUT_MAX_TOKENS are shortened: truncate or summarize",
+    ),
+    setting(
+        "GOOSE_TOOL_OUTPUT_LIMITS",
+        Object,
+        "Output limits in tokens for some tools or extensions, in place of GOOSE_TOOL_OUTPUT_MAX_TOKENS",
+    ),
+    setting(
+        "GOOSE_TOOL_TIMEOUTS",
+        Object,
+        "Timeouts in seconds for some tools or extensions, in place of their extension's timeout",
+    ),
+    setting(
+        "GOOSE_TOOL_MAX_RETRIES",
+        Integer,
+        "How many times a tool call that failed on the way to the tool is tried again, 0 to never retry",
+    ),
+    setting(
+        "GOOSE_TOOL_RETRIES",
+        Object,
+        "Retries for some tools or extensions, in place of GOOSE_TOOL_MAX_RETRIES",
+    ),
+    setting(
+        "GOOSE_TOOL_CONCURRENCY",
+        Integer,
+        "How many of the tool calls the model asks for in one turn run at once, 4 by default",
+    ),
+    setting(
+        "GOOSE_PARALLEL_EXTENSIONS",
+        Array,
+        "Local extensions whose tools may run at once, rather than one call at a time",
+    ),
+    setting(
+        "GOOSE_AUDIT_LOG",
+        Boolean,
+        "Record every tool call, with its arguments, approval and outcome, in an append-only audit log",
+    ),
+    setting(
+        "GOOSE_TOOL_OVERRIDES",
+        Object,
+        "New names, descriptions and argument descriptions for tools, by the names they are listed with",
+    ),
+    setting(
+        "GOOSE_SHELL_SANDBOX",
+        String,
+        "Where the developer extension runs shell commands: host (the default), docker or podman",
+    ),
+    setting(
+        "GOOSE_SHELL_SANDBOX_IMAGE",
+        String,
+        "The container image shell commands run in when GOOSE_SHELL_SANDBOX is docker or podman",
+    ),
+    setting(
+        "GOOSE_SHELL_SANDBOX_NETWORK",
+        Boolean,
+        "Whether sandboxed shell commands can reach the network; off by default",
+    ),
+    setting(
+        "GOOSE_FILE_ALLOW",
+        Array,
+        "Directories the developer extension's file tools may read and write, the project's by default",
+    ),
+    setting(
+        "GOOSE_FILE_DENY",
+        Array,
+        "Paths the developer extension's file tools may not use, even within GOOSE_FILE_ALLOW",
+    ),
+    setting(
+        "GOOSE_WEB_FETCH",
+        Boolean,
+        "Give the agent the platform__fetch_url tool to read web pages as Markdown",
+    ),
+    setting(
+        "GOOSE_WEB_FETCH_MAX_BYTES",
+        Integer,
+        "The most bytes platform__fetch_url downloads from a page, 5 MiB by default",
+    ),
+    setting(
+        "GOOSE_WEB_FETCH_PRIVATE",
+        Boolean,
+        "Let platform__fetch_url fetch pages from this machine or a private network",
+    ),
+    setting(
+        "GOOSE_ROUTER_MODEL",
+        String,
+        "A smaller model that simple questions are sent to instead of GOOSE_MODEL",
+    ),
+    setting(
+        "GOOSE_ROUTER_PROVIDER",
+        String,
+        "The provider of GOOSE_ROUTER_MODEL, the session's provider by default",
+    ),
+    setting(
+        "GOOSE_ROUTER_CLASSIFIER",
+        String,
+        "How turns the routing rules can't classify are decided: model (ask the small model) or rules",
+    ),
+    setting(
+        "GOOSE_WORKER_MODEL",
+        String,
+        "A cheaper model that makes the completions after tool results, with GOOSE_MODEL as the lead",
+    ),
+    setting(
+        "GOOSE_WORKER_PROVIDER",
+        String,
+        "The provider of GOOSE_WORKER_MODEL, the session's provider by default",
+    ),
+    setting(
+        "GOOSE_COMPARE_MODELS",
+        String,
+        "The provider/model pairs /compare answers with, separated by commas",
+    ),
+    setting(
+        "GOOSE_EXTENSION_HEARTBEAT",
+        Integer,
+        "Seconds between pings to local extensions, restarting those that stop answering, 0 to turn off",
+    ),
+    setting(
+        "GOOSE_SAMPLING",
+        String,
+        "Whether extensions' requests for completions are answered: ask, allow or deny",
+    ),
+];
+
+/// Settings each provider reads under its own prefix, e.g. `OPENAI_PROXY` or
+/// `SAMBANOVA_OAUTH_CLIENT_ID`
+pub const PROVIDER_SETTING_SUFFIXES: &[&str] = &[
+    "_HOST",
+    "_TIMEOUT",
+    "_PROXY",
+    "_NO_PROXY",
+    "_CA_CERT",
+    "_CLIENT_CERT",
+    "_CLIENT_KEY",
+    "_REQUEST_COMPRESSION",
+    "_CUSTOM_HEADERS",
+    "_OAUTH_TOKEN_URL",
+    "_OAUTH_CLIENT_ID",
+    "_OAUTH_CLIENT_SECRET",
+    "_OAUTH_SCOPE",
+    // Extra API keys to rotate through, e.g. `OPENAI_API_KEY_BACKUPS`
+    "_BACKUPS",
+];
+
+/// Find a setting that isn't specific to a provider
+pub fn find_setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| setting.key == key)
+}
+
+/// The type of a provider's setting; they are strings apart from the timeouts in seconds
+pub fn provider_setting_type(config_key: &ConfigKey) -> SettingType {
+    if config_key.name.ends_with("_TIMEOUT") {
+        SettingType::Integer
+    } else {
+        SettingType::String
+    }
+}
+
+/// Whether goose reads a key from its config, either as one of [`SETTINGS`] or as a provider
+/// setting
+pub fn is_known_setting(key: &str, providers: &[ProviderMetadata]) -> bool {
+    if find_setting(key).is_some() {
+        return true;
+    }
+    let provider_keys = || {
+        providers
+            .iter()
+            .flat_map(|provider| provider.config_keys.iter())
+            .map(|config_key| config_key.name.as_str())
+    };
+    if provider_keys().any(|name| name == key) {
+        return true;
+    }
+    // A provider setting under a prefix of one of the provider's keys, so OPENAI_PROXY
+    // is known from OPENAI_API_KEY and AZURE_OPENAI_PROXY from AZURE_OPENAI_ENDPOINT
+    PROVIDER_SETTING_SUFFIXES.iter().any(|suffix| {
+        key.strip_suffix(suffix).is_some_and(|prefix| {
+            provider_keys().any(|name| {
+                name == prefix
+                    || name
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('_'))
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_known_setting() {
+        let providers = vec![ProviderMetadata::new(
+            "azure_openai",
+            "Azure OpenAI",
+            "",
+            "gpt-4o",
+            vec![],
+            "",
+            vec![
+                ConfigKey::new("AZURE_OPENAI_ENDPOINT", true, false, None),
+                ConfigKey::new("AZURE_OPENAI_API_KEY", true, true, None),
+            ],
+        )];
+
+        for key in [
+            "GOOSE_MODEL",
+            "extensions",
+            "AZURE_OPENAI_ENDPOINT",
+            "AZURE_OPENAI_PROXY",
+            "AZURE_OPENAI_API_KEY_BACKUPS",
+        ] {
+            assert!(is_known_setting(key, &providers), "{}", key);
+        }
+        for key in [
+            "GOOSE_MODLE",
+            "AZURE_OPENAI_ENDPOINTS",
+            "AZURE_OPEN_PROXY",
+            "OPENAI_PROXY",
+        ] {
+            assert!(!is_known_setting(key, &providers), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use etcetera::{choose_app_strategy, AppStrategy};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use super::EmbeddingProvider;
+use crate::providers::errors::ProviderError;
+
+/// Embedding vectors persisted in a local SQLite database, keyed by model and content hash
+///
+/// Content is identified by its blake3 hash, so re-embedding unchanged text is free
+/// no matter where it came from.
+pub struct EmbeddingCache {
+    conn: Mutex<Connection>,
+}
+
+impl EmbeddingCache {
+    /// Open (creating if needed) the cache database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open an in-memory cache, which is discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS embeddings (
+                model TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                vector BLOB NOT NULL,
+                PRIMARY KEY (model, content_hash)
+            );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Default location of the cache database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("embeddings.db")
+    }
+
+    /// The hash identifying a piece of content in the cache
+    pub fn content_hash(text: &str) -> String {
+        blake3::hash(text.as_bytes()).to_hex().to_string()
+    }
+
+    pub fn get(&self, model: &str, text: &str) -> Result<Option<Vec<f32>>> {
+        let conn = self.conn.lock().unwrap();
+        let vector = conn
+            .query_row(
+                "SELECT vector FROM embeddings WHERE model = ?1 AND content_hash = ?2",
+                params![model, Self::content_hash(text)],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?;
+        Ok(vector.map(|bytes| decode_vector(&bytes)))
+    }
+
+    pub fn put(&self, model: &str, text: &str, vector: &[f32]) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO embeddings (model, content_hash, vector) VALUES (?1, ?2, ?3)",
+            params![model, Self::content_hash(text), encode_vector(vector)],
+        )?;
+        Ok(())
+    }
+
+    /// Number of cached vectors across all models
+    pub fn len(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM embeddings", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Remove all cached vectors for a model
+    pub fn clear_model(&self, model: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM embeddings WHERE model = ?1", params![model])?;
+        Ok(())
+    }
+}
+
+pub(crate) fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+pub(crate) fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// Wraps an embedding provider so only texts missing from the cache are sent to it
+///
+/// Cache errors are logged and treated as misses, the cache never fails a request the
+/// provider could have served.
+pub struct CachedEmbeddingProvider<P> {
+    inner: P,
+    cache: Arc<EmbeddingCache>,
+}
+
+impl<P: EmbeddingProvider> CachedEmbeddingProvider<P> {
+    pub fn new(inner: P, cache: Arc<EmbeddingCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<P: EmbeddingProvider> EmbeddingProvider for CachedEmbeddingProvider<P> {
+    fn model_name(&self) -> String {
+        self.inner.model_name()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let model = self.inner.model_name();
+        let mut vectors: Vec<Option<Vec<f32>>> = texts
+            .iter()
+            .map(|text| {
+                self.cache.get(&model, text).unwrap_or_else(|e| {
+                    warn!("Failed to read embedding cache: {}", e);
+                    None
+                })
+            })
+            .collect();
+
+        // Embed each distinct missing text once
+        let mut missing: Vec<String> = Vec::new();
+        let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, text) in texts.iter().enumerate() {
+            if vectors[i].is_none() {
+                let entry = positions.entry(text.as_str()).or_default();
+                if entry.is_empty() {
+                    missing.push(text.clone());
+                }
+                entry.push(i);
+            }
+        }
+
+        if !missing.is_empty() {
+            let embedded = self.inner.embed(&missing).await?;
+            if embedded.len() != missing.len() {
+                return Err(ProviderError::ExecutionError(format!(
+                    "Expected {} embeddings but got {}",
+                    missing.len(),
+                    embedded.len()
+                )));
+            }
+            for (text, vector) in missing.iter().zip(embedded) {
+                if let Err(e) = self.cache.put(&model, text, &vector) {
+                    warn!("Failed to write embedding cache: {}", e);
+                }
+                for &i in &positions[text.as_str()] {
+                    vectors[i] = Some(vector.clone());
+                }
+            }
+        }
+
+        Ok(vectors.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+
+    /// Embeds text as its length, counting how many texts it was asked for
+    struct CountingEmbedder {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for CountingEmbedder {
+        fn model_name(&self) -> String {
+            "test-embedding".to_string()
+        }
+
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+            self.calls.fetch_add(texts.len(), Ordering::SeqCst);
+            Ok(texts
+                .iter()
+                .map(|text| vec![text.len() as f32, 1.0])
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_cache_roundtrip() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("embeddings.db");
+
+        let cache = EmbeddingCache::open(&path)?;
+        cache.put("model-a", "hello", &[0.5, -1.25, 3.0])?;
+        drop(cache);
+
+        let cache = EmbeddingCache::open(&path)?;
+        assert_eq!(cache.get("model-a", "hello")?, Some(vec![0.5, -1.25, 3.0]));
+        // Vectors are specific to the model that produced them
+        assert_eq!(cache.get("model-b", "hello")?, None);
+        assert_eq!(cache.get("model-a", "hello!")?, None);
+
+        cache.clear_model("model-a")?;
+        assert!(cache.is_empty()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_provider_only_embeds_misses() -> Result<()> {
+        let cache = Arc::new(EmbeddingCache::open_in_memory()?);
+        let provider = CachedEmbeddingProvider::new(
+            CountingEmbedder {
+                calls: AtomicUsize::new(0),
+            },
+            cache.clone(),
+        );
+
+        let texts = vec!["a".to_string(), "bb".to_string(), "a".to_string()];
+        let vectors = provider.embed(&texts).await?;
+        assert_eq!(
+            vectors,
+            vec![vec![1.0, 1.0], vec![2.0, 1.0], vec![1.0, 1.0]]
+        );
+        assert_eq!(provider.inner().calls.load(Ordering::SeqCst), 2);
+
+        let texts = vec!["bb".to_string(), "ccc".to_string()];
+        let vectors = provider.embed(&texts).await?;
+        assert_eq!(vectors, vec![vec![2.0, 1.0], vec![3.0, 1.0]]);
+        assert_eq!(provider.inner().calls.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len()?, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! Text embeddings, used to index and search content by meaning
+//!
+//! Text is embedded by OpenAI, or a local Ollama when GOOSE_EMBEDDING_PROVIDER is `ollama`.
+pub mod cache;
+pub mod ollama;
+pub mod openai;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::providers::errors::ProviderError;
+
+pub use cache::{CachedEmbeddingProvider, EmbeddingCache};
+pub use ollama::OllamaEmbeddings;
+pub use openai::OpenAiEmbeddings;
+
+/// Config key naming the provider text is embedded by
+pub const EMBEDDING_PROVIDER_CONFIG_KEY: &str = "GOOSE_EMBEDDING_PROVIDER";
+
+/// The configured embedding model, with its vectors cached in the data dir
+pub fn from_config() -> Result<Box<dyn EmbeddingProvider>> {
+    let cache = Arc::new(EmbeddingCache::open(&EmbeddingCache::default_path())?);
+    let provider: String = Config::global()
+        .get_param(EMBEDDING_PROVIDER_CONFIG_KEY)
+        .unwrap_or_else(|_| "openai".to_string());
+    match provider.as_str() {
+        "openai" => Ok(Box::new(CachedEmbeddingProvider::new(
+            OpenAiEmbeddings::from_config()?,
+            cache,
+        ))),
+        "ollama" => Ok(Box::new(CachedEmbeddingProvider::new(
+            OllamaEmbeddings::from_config()?,
+            cache,
+        ))),
+        other => Err(anyhow!(
+            "There is no embedding provider {}, set {} to openai or ollama",
+            other,
+            EMBEDDING_PROVIDER_CONFIG_KEY
+        )),
+    }
+}
+
+/// A model that turns text into vectors
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Name of the embedding model, vectors from different models can't be compared
+    fn model_name(&self) -> String;
+
+    /// Embed each of the texts, returning one vector per text in the same order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError>;
+}
+
+/// How alike two vectors are in direction, from -1 to 1, 0 for vectors of different lengths
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::Duration;
+
+use super::openai::parse_embeddings;
+use super::EmbeddingProvider;
+use crate::config::Config;
+use crate::providers::errors::ProviderError;
+use crate::providers::http::HttpClient;
+use crate::providers::middleware;
+use crate::providers::ollama::{base_url, OLLAMA_HOST};
+use crate::providers::utils::handle_response_openai_compat;
+
+pub const OLLAMA_DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// Embeddings from a local Ollama, so the text embedded doesn't leave the machine
+///
+/// It uses the host of the Ollama provider, and the model in GOOSE_EMBEDDING_MODEL.
+pub struct OllamaEmbeddings {
+    client: HttpClient,
+    host: String,
+    model: String,
+}
+
+impl OllamaEmbeddings {
+    pub fn from_config() -> Result<Self> {
+        let config = Config::global();
+        let host: String = config
+            .get_param("OLLAMA_HOST")
+            .unwrap_or_else(|_| OLLAMA_HOST.to_string());
+        let model: String = config
+            .get_param("GOOSE_EMBEDDING_MODEL")
+            .unwrap_or_else(|_| OLLAMA_DEFAULT_EMBEDDING_MODEL.to_string());
+        Ok(Self {
+            client: HttpClient::new("OLLAMA", Duration::from_secs(600))?,
+            host,
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    fn model_name(&self) -> String {
+        // Kept apart from an OpenAI model of the same name
+        format!("ollama/{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let url = base_url(&self.host)?
+            .join("v1/embeddings")
+            .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
+        let payload = json!({"model": self.model, "input": texts});
+        let response = middleware::send(self.client.post_json(url, &payload)).await?;
+        parse_embeddings(handle_response_openai_compat(response).await?, texts.len())
+    }
+}
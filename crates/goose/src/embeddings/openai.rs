@@ -0,0 +1,119 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::EmbeddingProvider;
+use crate::config::Config;
+use crate::providers::api_keys::ApiKeys;
+use crate::providers::errors::ProviderError;
+use crate::providers::http::HttpClient;
+use crate::providers::utils::handle_response_openai_compat;
+
+pub const OPEN_AI_DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Embeddings from OpenAI, or a host with an OpenAI compatible embeddings endpoint
+///
+/// It uses the key and host of the OpenAI provider, and the model in GOOSE_EMBEDDING_MODEL.
+pub struct OpenAiEmbeddings {
+    client: HttpClient,
+    host: String,
+    api_keys: ApiKeys,
+    model: String,
+}
+
+impl OpenAiEmbeddings {
+    pub fn from_config() -> Result<Self> {
+        let config = Config::global();
+        let host: String = config
+            .get_param("OPENAI_HOST")
+            .unwrap_or_else(|_| "https://api.openai.com".to_string());
+        let model: String = config
+            .get_param("GOOSE_EMBEDDING_MODEL")
+            .unwrap_or_else(|_| OPEN_AI_DEFAULT_EMBEDDING_MODEL.to_string());
+        let timeout_secs: u64 = config.get_param("OPENAI_TIMEOUT").unwrap_or(600);
+        Ok(Self {
+            client: HttpClient::new("OPENAI", Duration::from_secs(timeout_secs))?,
+            host,
+            api_keys: ApiKeys::from_config("OPENAI_API_KEY")?,
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbeddings {
+    fn model_name(&self) -> String {
+        self.model.clone()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let url = url::Url::parse(&self.host)
+            .and_then(|host| host.join("v1/embeddings"))
+            .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
+        let payload = json!({"model": self.model, "input": texts});
+        let response = self
+            .api_keys
+            .send(|key| {
+                self.client
+                    .post_json(url.clone(), &payload)
+                    .header("Authorization", format!("Bearer {}", key))
+            })
+            .await?;
+        parse_embeddings(handle_response_openai_compat(response).await?, texts.len())
+    }
+}
+
+/// The vectors in an embeddings response, in the order of the inputs
+pub(super) fn parse_embeddings(
+    response: Value,
+    count: usize,
+) -> Result<Vec<Vec<f32>>, ProviderError> {
+    let data = response
+        .get("data")
+        .and_then(Value::as_array)
+        .ok_or_else(|| ProviderError::RequestFailed("No data in the response".to_string()))?;
+    let mut vectors = vec![Vec::new(); count];
+    for item in data {
+        let index = item.get("index").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let vector = item
+            .get("embedding")
+            .and_then(Value::as_array)
+            .ok_or_else(|| ProviderError::RequestFailed("No embedding in the data".into()))?
+            .iter()
+            .map(|value| value.as_f64().unwrap_or_default() as f32)
+            .collect();
+        if let Some(slot) = vectors.get_mut(index) {
+            *slot = vector;
+        }
+    }
+    if vectors.iter().any(Vec::is_empty) {
+        return Err(ProviderError::RequestFailed(format!(
+            "Expected {} embeddings in the response",
+            count
+        )));
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_embeddings() {
+        let response = json!({"data": [
+            {"index": 1, "embedding": [0.5, 1.0]},
+            {"index": 0, "embedding": [0.25, -1.0]},
+        ]});
+        assert_eq!(
+            parse_embeddings(response, 2).unwrap(),
+            vec![vec![0.25, -1.0], vec![0.5, 1.0]]
+        );
+        let short = json!({"data": [{"index": 0, "embedding": [0.5]}]});
+        assert!(parse_embeddings(short, 2).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+/// A passage of a document, by the lines it spans
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// The first line of the passage, counting from 1
+    pub start_line: usize,
+    /// The last line of the passage, inclusive
+    pub end_line: usize,
+    pub text: String,
+}
+
+/// Split `text` into passages of up to about `max_chars`, each starting with the last
+/// `overlap` lines of the one before so a passage split in two is still found whole
+///
+/// Passages end at a blank line where there is one in their second half, so paragraphs and
+/// code blocks are kept together where they fit. A single line longer than `max_chars` is a
+/// passage of its own.
+pub fn chunk_text(text: &str, max_chars: usize, overlap: usize) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        while start < lines.len() && lines[start].trim().is_empty() {
+            start += 1;
+        }
+        if start >= lines.len() {
+            break;
+        }
+        let mut end = start;
+        let mut size = 0;
+        while end < lines.len() && (end == start || size + lines[end].len() < max_chars) {
+            size += lines[end].len() + 1;
+            end += 1;
+        }
+        if end < lines.len() {
+            let half = start + (end - start) / 2;
+            if let Some(blank) = (half..end).rev().find(|&i| lines[i].trim().is_empty()) {
+                if blank > start {
+                    end = blank;
+                }
+            }
+        }
+        let at_end = end >= lines.len();
+        while lines[end - 1].trim().is_empty() {
+            end -= 1;
+        }
+
+        chunks.push(Chunk {
+            start_line: start + 1,
+            end_line: end,
+            text: lines[start..end].join("\n"),
+        });
+        if at_end {
+            break;
+        }
+        // Always move on by at least a line, however much overlap is asked for
+        start = end.saturating_sub(overlap).max(start + 1);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text() {
+        let text = "# Setup\nInstall the tools.\n\nRun the build.\nThen the tests.\n\n# Deploy\nPush the tag.";
+        let chunks = chunk_text(text, 40, 0);
+        assert_eq!(chunks[0].text, "# Setup\nInstall the tools.");
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 2));
+        // Every line is in a passage, at the line it is on
+        let covered: Vec<usize> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.start_line..=chunk.end_line)
+            .collect();
+        for line in [1, 2, 4, 5, 7, 8] {
+            assert!(covered.contains(&line), "line {} is missing", line);
+        }
+        assert_eq!(chunks.last().unwrap().end_line, 8);
+
+        // Passages repeat the end of the one before
+        let chunks = chunk_text("a\nb\nc\nd\ne\nf", 4, 1);
+        assert_eq!(chunks[0].text, "a\nb");
+        assert_eq!(chunks[1].text, "b\nc");
+
+        let long = "x".repeat(100);
+        let chunks = chunk_text(&long, 10, 2);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunk_text("\n\n", 10, 0).is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::Result;
+use chrono::Utc;
+use etcetera::{choose_app_strategy, AppStrategy};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::chunk::Chunk;
+use super::Passage;
+use crate::vector_store::{
+    collection_name, store_changed, SqliteVectorStore, VectorPoint, VectorScope, VectorStore,
+};
+
+const COLLECTION: &str = "knowledge";
+
+/// What is indexed from local documents in a local SQLite database, with the passages and
+/// their embeddings kept in a [`VectorStore`] under the user's [`VectorScope`]
+pub struct KnowledgeIndex {
+    conn: Mutex<Connection>,
+    vectors: Arc<dyn VectorStore>,
+    scope: VectorScope,
+}
+
+/// A passage as it is kept in the vector store
+#[derive(Serialize, Deserialize)]
+struct StoredPassage {
+    path: PathBuf,
+    start_line: usize,
+    end_line: usize,
+    content: String,
+}
+
+impl KnowledgeIndex {
+    /// Open (creating if needed) the index database at the given path
+    pub fn open(path: &Path, vectors: Arc<dyn VectorStore>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?, vectors, VectorScope::current())
+    }
+
+    /// Open an in-memory index with in-memory vectors, which are discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(
+            Connection::open_in_memory()?,
+            Arc::new(SqliteVectorStore::open_in_memory()?),
+            VectorScope::new("local", "local"),
+        )
+    }
+
+    pub(crate) fn with_connection(
+        conn: Connection,
+        vectors: Arc<dyn VectorStore>,
+        scope: VectorScope,
+    ) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sources (
+                path TEXT PRIMARY KEY,
+                added TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS files (
+                path TEXT PRIMARY KEY,
+                source TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                model TEXT NOT NULL,
+                chunks INTEGER NOT NULL DEFAULT 0
+            );
+            DROP TABLE IF EXISTS chunks;",
+        )?;
+        // The passages used to be kept in the index, in the table dropped above
+        let has_chunk_counts: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('files') WHERE name = 'chunks'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_chunk_counts {
+            conn.execute_batch("ALTER TABLE files ADD COLUMN chunks INTEGER NOT NULL DEFAULT 0;")?;
+        }
+        // Clearing the hashes has every file embedded again by the next reindex
+        if store_changed(&conn, vectors.as_ref(), &scope)? {
+            conn.execute_batch("UPDATE files SET content_hash = '';")?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            vectors,
+            scope,
+        })
+    }
+
+    /// Default location of the index database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("knowledge.db")
+    }
+
+    pub fn add_source(&self, path: &Path) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO sources (path, added) VALUES (?1, ?2)",
+            params![path.to_string_lossy(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a source and the files indexed from it, returning whether it was a source
+    pub async fn remove_source(&self, path: &Path) -> Result<bool> {
+        for file in self.files(path)? {
+            self.remove_file(&file).await?;
+        }
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM sources WHERE path = ?1",
+            params![path.to_string_lossy()],
+        )? > 0)
+    }
+
+    /// The files and directories added to the index, in the order they were added
+    pub fn sources(&self) -> Result<Vec<PathBuf>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT path FROM sources ORDER BY added")?;
+        let sources = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(sources)
+    }
+
+    /// The files indexed from `source`
+    pub fn files(&self, source: &Path) -> Result<Vec<PathBuf>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT path FROM files WHERE source = ?1")?;
+        let files = statement
+            .query_map(params![source.to_string_lossy()], |row| {
+                row.get::<_, String>(0)
+            })?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(files)
+    }
+
+    /// Whether `path` is indexed as it is now, from its content hash and the embedding model
+    pub fn is_current(&self, path: &Path, content_hash: &str, model: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let indexed: Option<(String, String)> = conn
+            .query_row(
+                "SELECT content_hash, model FROM files WHERE path = ?1",
+                params![path.to_string_lossy()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(indexed
+            .is_some_and(|(hash, indexed_model)| hash == content_hash && indexed_model == model))
+    }
+
+    /// Replace the passages indexed for a file
+    pub async fn put_file(
+        &self,
+        path: &Path,
+        source: &Path,
+        content_hash: &str,
+        model: &str,
+        chunks: &[(Chunk, Vec<f32>)],
+    ) -> Result<()> {
+        let previous = self.indexed(path)?;
+        let points = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, (chunk, vector))| {
+                let passage = StoredPassage {
+                    path: path.to_path_buf(),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    content: chunk.text.clone(),
+                };
+                Ok(VectorPoint {
+                    id: self.chunk_id(path, i),
+                    vector: vector.clone(),
+                    payload: serde_json::to_value(passage)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.vectors.upsert(&self.collection(model), points).await?;
+        // Drop the passages the file no longer has
+        if let Some((previous_model, previous_chunks)) = previous {
+            let kept = if previous_model == model {
+                chunks.len()
+            } else {
+                0
+            };
+            let stale: Vec<String> = (kept..previous_chunks)
+                .map(|i| self.chunk_id(path, i))
+                .collect();
+            if !stale.is_empty() {
+                self.vectors
+                    .delete(&self.collection(&previous_model), &stale)
+                    .await?;
+            }
+        }
+
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO files (path, source, content_hash, model, chunks)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                path.to_string_lossy(),
+                source.to_string_lossy(),
+                content_hash,
+                model,
+                chunks.len() as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub async fn remove_file(&self, path: &Path) -> Result<()> {
+        let Some((model, chunks)) = self.indexed(path)? else {
+            return Ok(());
+        };
+        let ids: Vec<String> = (0..chunks).map(|i| self.chunk_id(path, i)).collect();
+        self.vectors.delete(&self.collection(&model), &ids).await?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM files WHERE path = ?1",
+            params![path.to_string_lossy()],
+        )?;
+        Ok(())
+    }
+
+    /// The collection of the user's passages embedded by `model`
+    fn collection(&self, model: &str) -> String {
+        collection_name(COLLECTION, &self.scope, model)
+    }
+
+    /// The id of the `index`th passage of a file in the vector store, where the same path on
+    /// the user's other machines has passages too
+    fn chunk_id(&self, path: &Path, index: usize) -> String {
+        format!("{}:{}#{}", self.scope.machine, path.display(), index)
+    }
+
+    /// The model a file was embedded by and how many passages it has, None if it isn't indexed
+    fn indexed(&self, path: &Path) -> Result<Option<(String, usize)>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT model, chunks FROM files WHERE path = ?1",
+                params![path.to_string_lossy()],
+                |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)),
+            )
+            .optional()?)
+    }
+
+    /// Number of indexed passages
+    pub fn len(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 =
+            conn.query_row("SELECT COALESCE(SUM(chunks), 0) FROM files", [], |row| {
+                row.get(0)
+            })?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The passages embedded by `model` most like `vector`, the closest first
+    pub async fn search(&self, model: &str, vector: &[f32], limit: usize) -> Result<Vec<Passage>> {
+        let points = self
+            .vectors
+            .search(&self.collection(model), vector, limit)
+            .await?;
+        points
+            .into_iter()
+            .map(|point| {
+                let passage: StoredPassage = serde_json::from_value(point.payload)?;
+                Ok(Passage {
+                    path: passage.path,
+                    start_line: passage.start_line,
+                    end_line: passage.end_line,
+                    content: passage.content,
+                    score: point.score,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,540 @@
+//! Local documents goose can draw on, indexed by meaning
+//!
+//! Files and directories added to the index are split into passages and embedded. What was
+//! indexed is kept in `knowledge.db` in the data dir, and the passages with their embeddings in
+//! the [`crate::vector_store`]. Directories are walked the way git would, skipping files
+//! that `.gitignore` or `.gooseignore` exclude, and reindexing only embeds the files that
+//! changed. With `GOOSE_KNOWLEDGE` set to `prompt` the passages most like the user's message
+//! are added to the system prompt of every reply, and with `tool` the agent is given the
+//! `platform__search_knowledge` tool to search them when it needs to. Either way each passage
+//! comes with the file and lines it is from.
+mod chunk;
+mod index;
+
+pub use chunk::{chunk_text, Chunk};
+pub use index::KnowledgeIndex;
+
+use anyhow::{anyhow, Result};
+use indoc::indoc;
+use mcp_core::role::Role;
+use mcp_core::{Content, Tool, ToolError, ToolResult};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::embeddings::{self, EmbeddingCache, EmbeddingProvider};
+use crate::message::Message;
+use crate::vector_store;
+
+pub const SEARCH_KNOWLEDGE_TOOL: &str = "platform__search_knowledge";
+
+/// About how long a passage is, in characters
+const CHUNK_CHARS: usize = 1500;
+
+/// Lines each passage repeats from the end of the one before
+const CHUNK_OVERLAP_LINES: usize = 2;
+
+/// Larger files are taken not to be documents, like logs and data dumps
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Passages sent to the embedding model in one request
+const EMBED_BATCH_SIZE: usize = 64;
+
+const DEFAULT_TOP_K: usize = 5;
+
+const DEFAULT_MIN_SCORE: f32 = 0.3;
+
+/// How passages reach the model, from `GOOSE_KNOWLEDGE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetrievalMode {
+    /// The passages most like the user's message are added to the system prompt
+    Prompt,
+    /// The agent searches the passages with [`SEARCH_KNOWLEDGE_TOOL`]
+    Tool,
+}
+
+impl RetrievalMode {
+    /// The configured mode, None when the index isn't used
+    pub fn from_config() -> Option<Self> {
+        let mode: String = Config::global().get_param("GOOSE_KNOWLEDGE").ok()?;
+        match mode.as_str() {
+            "prompt" => Some(RetrievalMode::Prompt),
+            "tool" => Some(RetrievalMode::Tool),
+            "off" | "" => None,
+            other => {
+                tracing::warn!(
+                    "Unknown GOOSE_KNOWLEDGE mode {}, expected prompt or tool",
+                    other
+                );
+                None
+            }
+        }
+    }
+}
+
+/// A passage found in the index
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Passage {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content: String,
+    /// How similar the passage is to the query, from -1 to 1
+    pub score: f32,
+}
+
+impl Passage {
+    /// Where the passage is from, as `path:start-end`
+    pub fn source(&self) -> String {
+        format!(
+            "{}:{}-{}",
+            self.path.display(),
+            self.start_line,
+            self.end_line
+        )
+    }
+}
+
+/// What indexing did to the files of the sources
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexReport {
+    /// Files embedded, as they were new or changed
+    pub indexed: usize,
+    pub unchanged: usize,
+    /// Files that were indexed but are gone or now ignored
+    pub removed: usize,
+    /// Files that aren't text, or are too large to index
+    pub skipped: usize,
+}
+
+impl IndexReport {
+    fn add(&mut self, other: IndexReport) {
+        self.indexed += other.indexed;
+        self.unchanged += other.unchanged;
+        self.removed += other.removed;
+        self.skipped += other.skipped;
+    }
+}
+
+/// The document index with the embedding model its vectors come from
+pub struct Knowledge {
+    index: KnowledgeIndex,
+    embedder: Box<dyn EmbeddingProvider>,
+}
+
+impl Knowledge {
+    pub fn new(index: KnowledgeIndex, embedder: Box<dyn EmbeddingProvider>) -> Self {
+        Self { index, embedder }
+    }
+
+    /// The index in the data dir, embedded with the configured embedding model
+    pub fn open() -> Result<Self> {
+        let embedder = embeddings::from_config()?;
+        let vectors = vector_store::from_config(Config::global())?;
+        let index = KnowledgeIndex::open(&KnowledgeIndex::default_path(), vectors)?;
+        Ok(Self::new(index, embedder))
+    }
+
+    pub fn sources(&self) -> Result<Vec<PathBuf>> {
+        self.index.sources()
+    }
+
+    /// Add a file or directory to the index and index it
+    pub async fn add_source(&self, path: &Path) -> Result<IndexReport> {
+        let source = path
+            .canonicalize()
+            .map_err(|e| anyhow!("Can't index {}: {}", path.display(), e))?;
+        self.index.add_source(&source)?;
+        self.index_source(&source).await
+    }
+
+    /// Remove a file or directory and its passages from the index, returning whether it was
+    /// in the index
+    pub async fn remove_source(&self, path: &Path) -> Result<bool> {
+        let source = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.index.remove_source(&source).await
+    }
+
+    /// Bring the index up to date with the files of every source
+    pub async fn reindex(&self) -> Result<IndexReport> {
+        let mut report = IndexReport::default();
+        for source in self.index.sources()? {
+            report.add(self.index_source(&source).await?);
+        }
+        Ok(report)
+    }
+
+    async fn index_source(&self, source: &Path) -> Result<IndexReport> {
+        let model = self.embedder.model_name();
+        let mut report = IndexReport::default();
+        let mut seen = HashSet::new();
+        for path in walk(source) {
+            let Some(text) = read_document(&path) else {
+                report.skipped += 1;
+                continue;
+            };
+            seen.insert(path.clone());
+            let content_hash = EmbeddingCache::content_hash(&text);
+            if self.index.is_current(&path, &content_hash, &model)? {
+                report.unchanged += 1;
+                continue;
+            }
+
+            let chunks = chunk_text(&text, CHUNK_CHARS, CHUNK_OVERLAP_LINES);
+            // The file's name often says what a passage is about when the passage doesn't
+            let texts: Vec<String> = chunks
+                .iter()
+                .map(|chunk| format!("{}\n{}", path.display(), chunk.text))
+                .collect();
+            let mut vectors = Vec::with_capacity(texts.len());
+            for batch in texts.chunks(EMBED_BATCH_SIZE) {
+                vectors.extend(self.embedder.embed(batch).await?);
+            }
+            let embedded: Vec<(Chunk, Vec<f32>)> = chunks.into_iter().zip(vectors).collect();
+            self.index
+                .put_file(&path, source, &content_hash, &model, &embedded)
+                .await?;
+            report.indexed += 1;
+        }
+
+        for path in self.index.files(source)? {
+            if !seen.contains(&path) {
+                self.index.remove_file(&path).await?;
+                report.removed += 1;
+            }
+        }
+        Ok(report)
+    }
+
+    /// The passages most like `query`, the closest first
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<Passage>> {
+        let vector = self
+            .embedder
+            .embed(&[query.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("The embedding model returned no vector"))?;
+        self.index
+            .search(&self.embedder.model_name(), &vector, limit)
+            .await
+    }
+}
+
+/// The files under `source` to index, or `source` itself if it is a file
+pub(crate) fn walk(source: &Path) -> Vec<PathBuf> {
+    ignore::WalkBuilder::new(source)
+        .require_git(false)
+        .add_custom_ignore_filename(".gooseignore")
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// The text of a file, None if it is too large or isn't text
+fn read_document(path: &Path) -> Option<String> {
+    let size = std::fs::metadata(path).ok()?.len();
+    if size > MAX_FILE_BYTES {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.iter().take(8000).any(|&byte| byte == 0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The shared index, if `GOOSE_KNOWLEDGE` is set and the index could be opened
+pub fn global() -> Option<&'static Knowledge> {
+    static KNOWLEDGE: OnceCell<Option<Knowledge>> = OnceCell::new();
+    KNOWLEDGE
+        .get_or_init(|| {
+            RetrievalMode::from_config()?;
+            match Knowledge::open() {
+                Ok(knowledge) => Some(knowledge),
+                Err(e) => {
+                    tracing::warn!("Failed to open the knowledge index: {}", e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// The section of the system prompt with the passages relevant to the user's last message, in
+/// the `prompt` mode
+///
+/// Failing to search is logged rather than failing the reply.
+pub async fn retrieval_prompt(messages: &[Message]) -> Option<String> {
+    if RetrievalMode::from_config()? != RetrievalMode::Prompt {
+        return None;
+    }
+    let knowledge = global()?;
+    let query = messages
+        .iter()
+        .rev()
+        .find(|message| message.role == Role::User)
+        .map(Message::as_concat_text)
+        .filter(|text| !text.trim().is_empty())?;
+
+    let min_score = Config::global()
+        .get_param("GOOSE_KNOWLEDGE_MIN_SCORE")
+        .unwrap_or(DEFAULT_MIN_SCORE);
+    let passages: Vec<Passage> = match knowledge.search(&query, top_k()).await {
+        Ok(passages) => passages
+            .into_iter()
+            .filter(|passage| passage.score >= min_score)
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to search the knowledge index: {}", e);
+            return None;
+        }
+    };
+    if passages.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "\n\n# Relevant documents\n\nThese passages from the user's documents may help with \
+        their message. Say which file you drew on when you use one.\n\n{}",
+        format_passages(&passages)
+    ))
+}
+
+/// The number of passages to retrieve, from `GOOSE_KNOWLEDGE_TOP_K`
+fn top_k() -> usize {
+    Config::global()
+        .get_param("GOOSE_KNOWLEDGE_TOP_K")
+        .unwrap_or(DEFAULT_TOP_K)
+}
+
+fn format_passages(passages: &[Passage]) -> String {
+    passages
+        .iter()
+        .map(|passage| format!("## {}\n\n{}\n", passage.source(), passage.content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether the agent is offered [`search_knowledge_tool`], in the `tool` mode
+pub fn search_tool_enabled() -> bool {
+    RetrievalMode::from_config() == Some(RetrievalMode::Tool)
+}
+
+pub fn search_knowledge_tool() -> Tool {
+    Tool::new(
+        SEARCH_KNOWLEDGE_TOOL.to_string(),
+        indoc! {r#"
+            Search the user's indexed documents, such as design docs, runbooks and notes, for
+            the passages most relevant to a query.
+
+            Each passage comes with the file and lines it is from. Search by what you are
+            looking for in plain words, and cite the files you use in your answer.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "required": ["query"],
+            "properties": {
+                "query": {"type": "string", "description": "What to look for"},
+                "limit": {
+                    "type": "integer",
+                    "description": format!("Optional number of passages to return, {} by default", top_k())
+                }
+            }
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    query: String,
+    limit: Option<usize>,
+}
+
+/// Run a [`SEARCH_KNOWLEDGE_TOOL`] call
+pub async fn search_knowledge(arguments: Value) -> ToolResult<Vec<Content>> {
+    let arguments: SearchArguments = serde_json::from_value(arguments)
+        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+    let knowledge = global().ok_or_else(|| {
+        ToolError::ExecutionError("The knowledge index couldn't be opened".to_string())
+    })?;
+    let limit = arguments.limit.unwrap_or_else(top_k);
+    let passages = knowledge
+        .search(&arguments.query, limit)
+        .await
+        .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+    if passages.is_empty() {
+        return Ok(vec![Content::text(
+            "No documents are indexed, the user can add them with `goose knowledge add`",
+        )]);
+    }
+    Ok(vec![Content::text(format_passages(&passages))])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::errors::ProviderError;
+    use async_trait::async_trait;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    /// Embeds text by which of a few words it mentions, counting the texts it embeds
+    #[derive(Default)]
+    struct TopicEmbedder {
+        embedded: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for TopicEmbedder {
+        fn model_name(&self) -> String {
+            "topics".to_string()
+        }
+
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+            self.embedded.fetch_add(texts.len(), Ordering::SeqCst);
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    ["deploy", "database", "oncall"]
+                        .iter()
+                        .map(|word| if text.contains(word) { 1.0 } else { 0.1 })
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_and_search() -> Result<()> {
+        let dir = tempdir()?;
+        let docs = dir.path().join("docs");
+        fs::create_dir_all(docs.join("drafts"))?;
+        fs::write(
+            docs.join("deploy.md"),
+            "# Deploying\n\nTag the release to deploy it.",
+        )?;
+        fs::write(
+            docs.join("database.md"),
+            "# Database\n\nMigrations run on start.",
+        )?;
+        fs::write(docs.join("drafts/oncall.md"), "Oncall rotates weekly.")?;
+        fs::write(docs.join(".gooseignore"), "drafts/\n")?;
+        fs::write(docs.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 0])?;
+
+        let embedder = TopicEmbedder::default();
+        let embedded = embedder.embedded.clone();
+        let knowledge = Knowledge::new(KnowledgeIndex::open_in_memory()?, Box::new(embedder));
+        let report = knowledge.add_source(&docs).await?;
+        assert_eq!(report.indexed, 2);
+        assert_eq!(report.skipped, 1);
+
+        let passages = knowledge.search("how do I deploy?", 1).await?;
+        let deploy = docs.canonicalize()?.join("deploy.md");
+        assert_eq!(passages[0].path, deploy);
+        assert_eq!(passages[0].source(), format!("{}:1-3", deploy.display()));
+        assert!(knowledge
+            .search("oncall", 5)
+            .await?
+            .iter()
+            .all(|p| !p.path.ends_with("oncall.md")));
+
+        // Only what changed is embedded again
+        let before = embedded.load(Ordering::SeqCst);
+        fs::write(
+            docs.join("database.md"),
+            "# Database\n\nBack it up nightly.",
+        )?;
+        fs::remove_file(docs.join("deploy.md"))?;
+        let report = knowledge.reindex().await?;
+        assert_eq!(
+            (report.indexed, report.unchanged, report.removed),
+            (1, 0, 1)
+        );
+        assert_eq!(embedded.load(Ordering::SeqCst), before + 1);
+
+        assert!(knowledge.remove_source(&docs).await?);
+        assert!(knowledge.sources()?.is_empty());
+        assert!(knowledge.search("database", 5).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_switching_vector_store_reindexes() -> Result<()> {
+        let dir = tempdir()?;
+        let docs = dir.path().join("docs");
+        fs::create_dir_all(&docs)?;
+        fs::write(docs.join("deploy.md"), "Tag the release to deploy it.")?;
+        let index_path = dir.path().join("knowledge.db");
+        let open = |vectors: &str| -> Result<Knowledge> {
+            let vectors = Arc::new(vector_store::SqliteVectorStore::open(
+                &dir.path().join(vectors),
+            )?);
+            Ok(Knowledge::new(
+                KnowledgeIndex::open(&index_path, vectors)?,
+                Box::new(TopicEmbedder::default()),
+            ))
+        };
+
+        open("first.db")?.add_source(&docs).await?;
+        assert_eq!(open("first.db")?.reindex().await?.unchanged, 1);
+        // A new store has none of the passages, so they are all embedded again
+        let knowledge = open("second.db")?;
+        assert!(knowledge.search("deploy", 5).await?.is_empty());
+        assert_eq!(knowledge.reindex().await?.indexed, 1);
+        assert_eq!(knowledge.search("deploy", 5).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_owners_keep_their_own_passages() -> Result<()> {
+        let dir = tempdir()?;
+        let docs = dir.path().join("docs");
+        fs::create_dir_all(&docs)?;
+        fs::write(docs.join("deploy.md"), "Tag the release to deploy it.")?;
+        let vectors: Arc<dyn vector_store::VectorStore> =
+            Arc::new(vector_store::SqliteVectorStore::open_in_memory()?);
+        let open = |owner: &str, machine: &str| -> Result<Knowledge> {
+            let index = KnowledgeIndex::with_connection(
+                rusqlite::Connection::open_in_memory()?,
+                vectors.clone(),
+                vector_store::VectorScope::new(owner, machine),
+            )?;
+            Ok(Knowledge::new(index, Box::new(TopicEmbedder::default())))
+        };
+
+        let laptop = open("alice", "laptop")?;
+        laptop.add_source(&docs).await?;
+        // Another user of the store doesn't find Alice's passages
+        assert!(open("bob", "laptop")?.search("deploy", 5).await?.is_empty());
+        // and the same path on Alice's other machine has passages of its own
+        let desktop = open("alice", "desktop")?;
+        desktop.add_source(&docs).await?;
+        desktop.remove_source(&docs).await?;
+        assert_eq!(laptop.search("deploy", 5).await?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_passages() {
+        let passages = vec![Passage {
+            path: PathBuf::from("/docs/deploy.md"),
+            start_line: 3,
+            end_line: 9,
+            content: "Tag the release.".to_string(),
+            score: 0.8,
+        }];
+        assert_eq!(
+            format_passages(&passages),
+            "## /docs/deploy.md:3-9\n\nTag the release.\n"
+        );
+    }
+}
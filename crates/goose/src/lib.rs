@@ -1,11 +1,31 @@
 pub mod agents;
+pub mod audit;
+pub mod code_index;
+pub mod compaction;
 pub mod config;
+pub mod embeddings;
+pub mod knowledge;
+pub mod memory;
 pub mod memory_condense;
 pub mod message;
 pub mod model;
+pub mod persona;
+pub mod prompt_compression;
 pub mod prompt_template;
 pub mod providers;
+pub mod recipe;
+pub mod redaction;
+pub mod schedule;
 pub mod session;
 pub mod token_counter;
+pub mod tool_arguments;
+pub mod tool_names;
+pub mod tool_output;
+pub mod tool_overrides;
+pub mod tool_retries;
+pub mod tool_timeouts;
 pub mod tracing;
 pub mod truncate;
+pub mod usage;
+pub mod vector_store;
+pub mod web_fetch;
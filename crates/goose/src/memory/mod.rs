@@ -0,0 +1,645 @@
+//! Long-term memory, the facts and preferences goose keeps from one session to the next
+//!
+//! When `GOOSE_MEMORY` is on, the durable facts about the user and their work are extracted from
+//! each session once it ends and stored with their embeddings. Every reply then recalls the
+//! memories most like the user's last message into the system prompt. Memories are kept in
+//! `memories.db` in the data dir, their embeddings in the [`crate::vector_store`], and can be
+//! listed, edited and deleted from the CLI and server.
+mod store;
+
+pub use store::MemoryStore;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use mcp_core::role::Role;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::embeddings::{self, EmbeddingProvider};
+use crate::message::Message;
+use crate::providers::base::Provider;
+use crate::redaction::Redactor;
+use crate::session;
+use crate::vector_store;
+
+/// Memories at least this similar to a new one are taken to say the same thing
+const DUPLICATE_SIMILARITY: f32 = 0.9;
+
+const DEFAULT_RECALL_LIMIT: usize = 5;
+
+const DEFAULT_MIN_SCORE: f32 = 0.3;
+
+/// The longest a message can be in the conversation memories are extracted from
+const MAX_MESSAGE_CHARS: usize = 2000;
+
+const EXTRACT_PROMPT: &str = r#"Read the conversation between a user and their AI agent below, and list what is worth remembering about the user for future, unrelated sessions: facts about them, their projects and environment, and their preferences for how the agent should work. Leave out anything only relevant to this conversation's task, and anything secret.
+
+Reply with only a JSON array, one object per memory, each with a "kind" of "fact" or "preference" and a "content" of one short sentence, for example:
+[{"kind": "preference", "content": "Prefers pnpm over npm for JavaScript projects"}]
+Reply with [] if there is nothing worth remembering."#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryKind {
+    /// Something true about the user or their work
+    Fact,
+    /// How the user wants goose to work
+    Preference,
+}
+
+impl MemoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryKind::Fact => "fact",
+            MemoryKind::Preference => "preference",
+        }
+    }
+
+    /// The kind named `name`, a fact for anything but "preference"
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "preference" => MemoryKind::Preference,
+            _ => MemoryKind::Fact,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    pub id: String,
+    pub kind: MemoryKind,
+    pub content: String,
+    /// The session the memory was extracted from, none for memories added by hand
+    pub session: Option<String>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+/// A memory as the model writes it when extracting them
+#[derive(Debug, Deserialize)]
+struct Extracted {
+    #[serde(default = "default_kind")]
+    kind: String,
+    content: String,
+}
+
+fn default_kind() -> String {
+    MemoryKind::Fact.as_str().to_string()
+}
+
+/// The memory store with the embedding model its vectors come from
+pub struct LongTermMemory {
+    store: MemoryStore,
+    embedder: Box<dyn EmbeddingProvider>,
+}
+
+impl LongTermMemory {
+    pub fn new(store: MemoryStore, embedder: Box<dyn EmbeddingProvider>) -> Self {
+        Self { store, embedder }
+    }
+
+    /// Whether `GOOSE_MEMORY` is on
+    pub fn enabled() -> bool {
+        Config::global()
+            .get_param::<bool>("GOOSE_MEMORY")
+            .unwrap_or(false)
+    }
+
+    /// The memories in the data dir, embedded with the configured embedding model
+    ///
+    /// This opens them whether or not `GOOSE_MEMORY` is on, so they can still be managed.
+    pub fn open() -> Result<Self> {
+        let embedder = embeddings::from_config()?;
+        let vectors = vector_store::from_config(Config::global())?;
+        let store = MemoryStore::open(&MemoryStore::default_path(), vectors)?;
+        Ok(Self::new(store, embedder))
+    }
+
+    pub fn list(&self) -> Result<Vec<Memory>> {
+        self.store.list()
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Memory>> {
+        self.store.get(id)
+    }
+
+    /// Remove a memory, returning whether there was one with that id
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        self.store.delete(id).await
+    }
+
+    pub async fn add(
+        &self,
+        kind: MemoryKind,
+        content: &str,
+        session: Option<String>,
+    ) -> Result<Memory> {
+        let now = Utc::now();
+        let memory = Memory {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            content: content.trim().to_string(),
+            session,
+            created: now,
+            updated: now,
+        };
+        let vector = self.embed_one(&memory.content).await?;
+        self.store
+            .put(&memory, &self.embedder.model_name(), &vector)
+            .await?;
+        Ok(memory)
+    }
+
+    /// Change what a memory says, returning it as changed or None if there is no such memory
+    pub async fn edit(
+        &self,
+        id: &str,
+        kind: Option<MemoryKind>,
+        content: &str,
+    ) -> Result<Option<Memory>> {
+        let Some(memory) = self.store.get(id)? else {
+            return Ok(None);
+        };
+        let memory = Memory {
+            kind: kind.unwrap_or(memory.kind),
+            content: content.trim().to_string(),
+            updated: Utc::now(),
+            ..memory
+        };
+        let vector = self.embed_one(&memory.content).await?;
+        self.store
+            .put(&memory, &self.embedder.model_name(), &vector)
+            .await?;
+        Ok(Some(memory))
+    }
+
+    /// The memories most like `query`, the closest first, with their similarity
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<(Memory, f32)>> {
+        self.reembed_outdated().await?;
+        let vector = self.embed_one(query).await?;
+        self.store
+            .search(&self.embedder.model_name(), &vector, limit)
+            .await
+    }
+
+    /// The memories relevant to `query`, as many as `GOOSE_MEMORY_RECALL_LIMIT` that are at
+    /// least as similar as `GOOSE_MEMORY_MIN_SCORE`
+    pub async fn recall(&self, query: &str) -> Result<Vec<Memory>> {
+        let config = Config::global();
+        let limit = config
+            .get_param("GOOSE_MEMORY_RECALL_LIMIT")
+            .unwrap_or(DEFAULT_RECALL_LIMIT);
+        let min_score = config
+            .get_param("GOOSE_MEMORY_MIN_SCORE")
+            .unwrap_or(DEFAULT_MIN_SCORE);
+        Ok(self
+            .search(query, limit)
+            .await?
+            .into_iter()
+            .filter(|(_, score)| *score >= min_score)
+            .map(|(memory, _)| memory)
+            .collect())
+    }
+
+    /// Have the model extract what is worth remembering from `messages`, storing the memories
+    /// that aren't already known and returning them
+    pub async fn remember(
+        &self,
+        provider: &dyn Provider,
+        messages: &[Message],
+        session: Option<String>,
+    ) -> Result<Vec<Memory>> {
+        let conversation = transcript(messages);
+        if conversation.is_empty() {
+            return Ok(Vec::new());
+        }
+        let prompt = format!("{}\n\n# Conversation\n{}", EXTRACT_PROMPT, conversation);
+        let (reply, usage) = provider
+            .complete(
+                "You extract long-term memories from conversations, replying only with JSON",
+                &[Message::user().with_text(prompt)],
+                &[],
+            )
+            .await?;
+        let provider_name: String = Config::global()
+            .get_param("GOOSE_PROVIDER")
+            .unwrap_or_else(|_| "unknown".to_string());
+        crate::usage::record(&provider_name, session.clone(), &usage);
+
+        let mut remembered = Vec::new();
+        for extracted in parse_extracted(&reply.as_concat_text())? {
+            let content = Redactor::global()
+                .redact(extracted.content.trim())
+                .to_string();
+            if content.is_empty() || self.is_known(&content).await? {
+                continue;
+            }
+            let kind = MemoryKind::parse(&extracted.kind);
+            remembered.push(self.add(kind, &content, session.clone()).await?);
+        }
+        Ok(remembered)
+    }
+
+    /// Extract memories from the messages of a session added since it was last remembered, if
+    /// there are at least `min_new` of them
+    pub async fn remember_session(
+        &self,
+        session_file: &Path,
+        provider: &dyn Provider,
+        min_new: usize,
+    ) -> Result<Vec<Memory>> {
+        let messages = session::read_messages(session_file)?;
+        let start = session::read_metadata(session_file)?
+            .remembered_messages
+            .min(messages.len());
+        if messages.len() - start < min_new.max(1) {
+            return Ok(Vec::new());
+        }
+        let session_id = session_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
+        let remembered = self
+            .remember(provider, &messages[start..], session_id)
+            .await?;
+        // The session went on while the model was asked, so only the count is changed in
+        // its metadata as it is now
+        let mut metadata = session::read_metadata(session_file)?;
+        metadata.remembered_messages = metadata.remembered_messages.max(messages.len());
+        session::update_metadata(session_file, &metadata).await?;
+        Ok(remembered)
+    }
+
+    /// Whether a memory already says about the same as `content`
+    async fn is_known(&self, content: &str) -> Result<bool> {
+        let closest = self.search(content, 1).await?;
+        Ok(closest
+            .first()
+            .is_some_and(|(_, score)| *score >= DUPLICATE_SIMILARITY))
+    }
+
+    /// Embed again the memories embedded by another model, after the embedding model changed
+    async fn reembed_outdated(&self) -> Result<()> {
+        let model = self.embedder.model_name();
+        let outdated = self.store.outdated(&model)?;
+        if outdated.is_empty() {
+            return Ok(());
+        }
+        let texts: Vec<String> = outdated.iter().map(|m| m.content.clone()).collect();
+        let vectors = self.embedder.embed(&texts).await?;
+        for (memory, vector) in outdated.iter().zip(vectors) {
+            self.store.put(memory, &model, &vector).await?;
+        }
+        Ok(())
+    }
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>> {
+        self.embedder
+            .embed(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("The embedding model returned no vector"))
+    }
+}
+
+/// The shared long-term memory, if `GOOSE_MEMORY` is on and the memories could be opened
+pub fn global() -> Option<&'static LongTermMemory> {
+    static MEMORY: OnceCell<Option<LongTermMemory>> = OnceCell::new();
+    MEMORY
+        .get_or_init(|| {
+            if !LongTermMemory::enabled() {
+                return None;
+            }
+            match LongTermMemory::open() {
+                Ok(memory) => Some(memory),
+                Err(e) => {
+                    tracing::warn!("Failed to open long-term memory: {}", e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// The section of the system prompt with the memories relevant to the user's last message,
+/// None when memory is off or nothing relevant is remembered
+///
+/// Failing to recall is logged rather than failing the reply.
+pub async fn recall_prompt(messages: &[Message]) -> Option<String> {
+    let memory = global()?;
+    let query = messages
+        .iter()
+        .rev()
+        .find(|message| message.role == Role::User)
+        .map(Message::as_concat_text)
+        .filter(|text| !text.trim().is_empty())?;
+    match memory.recall(&query).await {
+        Ok(memories) => format_memories(&memories),
+        Err(e) => {
+            tracing::warn!("Failed to recall memories: {}", e);
+            None
+        }
+    }
+}
+
+fn format_memories(memories: &[Memory]) -> Option<String> {
+    if memories.is_empty() {
+        return None;
+    }
+    let mut prompt = "\n\n# Memories\n\nYou remember these from earlier sessions with the user. \
+        Use them where they are relevant, and go with what the user says now where they differ.\n"
+        .to_string();
+    for memory in memories {
+        prompt.push_str(&format!(
+            "- ({}) {}\n",
+            memory.kind.as_str(),
+            memory.content
+        ));
+    }
+    Some(prompt)
+}
+
+/// The text of the conversation, each message after who sent it
+fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let text = message.as_concat_text();
+            let text = text.trim();
+            if text.is_empty() {
+                return None;
+            }
+            let role = match message.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            let text: String = text.chars().take(MAX_MESSAGE_CHARS).collect();
+            Some(format!("{}: {}", role, text))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The memories in the model's reply, which may be wrapped in a code fence
+fn parse_extracted(reply: &str) -> Result<Vec<Extracted>> {
+    let reply = reply.trim();
+    let json = match (reply.find('['), reply.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err(anyhow!("Expected a JSON array of memories, got: {}", reply)),
+    };
+    serde_json::from_str(json).map_err(|e| anyhow!("Failed to parse the memories: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelConfig;
+    use crate::providers::base::{ProviderMetadata, ProviderUsage, Usage};
+    use crate::providers::errors::ProviderError;
+    use async_trait::async_trait;
+    use mcp_core::tool::Tool;
+    use std::sync::Arc;
+
+    /// Embeds text by which of a few words it mentions, so texts on the same topic match
+    struct TopicEmbedder;
+
+    #[async_trait]
+    impl EmbeddingProvider for TopicEmbedder {
+        fn model_name(&self) -> String {
+            "topics".to_string()
+        }
+
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    ["rust", "pnpm", "tabs"]
+                        .iter()
+                        .map(|word| if text.contains(word) { 1.0 } else { 0.0 })
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    fn memory() -> LongTermMemory {
+        LongTermMemory::new(
+            MemoryStore::open_in_memory().unwrap(),
+            Box::new(TopicEmbedder),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_edit_delete() -> Result<()> {
+        let memory = memory();
+        let first = memory
+            .add(MemoryKind::Fact, "Works on a Rust CLI", None)
+            .await?;
+        let second = memory
+            .add(MemoryKind::Preference, "Uses pnpm", Some("s1".into()))
+            .await?;
+        assert_eq!(memory.list()?.len(), 2);
+
+        let edited = memory
+            .edit(&first.id, None, "Indents with tabs")
+            .await?
+            .unwrap();
+        assert_eq!(edited.kind, MemoryKind::Fact);
+        assert_eq!(edited.created, first.created);
+        // The edit is embedded again, so it is found by its new content
+        let found = memory.search("tabs or spaces?", 1).await?;
+        assert_eq!(found[0].0.id, first.id);
+        assert!(memory.edit("missing", None, "x").await?.is_none());
+
+        assert!(memory.delete(&second.id).await?);
+        assert!(!memory.delete(&second.id).await?);
+        assert_eq!(memory.get(&second.id)?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_reembeds_outdated() -> Result<()> {
+        let store = MemoryStore::open_in_memory()?;
+        let now = Utc::now();
+        let old = Memory {
+            id: "old".to_string(),
+            kind: MemoryKind::Preference,
+            content: "Prefers pnpm".to_string(),
+            session: None,
+            created: now,
+            updated: now,
+        };
+        store
+            .put(&old, "retired-model", &[0.0, 0.0, 0.0, 1.0])
+            .await?;
+        let memory = LongTermMemory::new(store, Box::new(TopicEmbedder));
+
+        let found = memory.search("install it with pnpm", 5).await?;
+        assert_eq!(found.len(), 1);
+        assert!(found[0].1 > 0.99);
+        assert!(memory.store.outdated("topics")?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_owners_keep_their_own_memories() -> Result<()> {
+        let vectors: Arc<dyn vector_store::VectorStore> =
+            Arc::new(vector_store::SqliteVectorStore::open_in_memory()?);
+        let open = |owner: &str, machine: &str| -> Result<LongTermMemory> {
+            let store = MemoryStore::with_connection(
+                rusqlite::Connection::open_in_memory()?,
+                vectors.clone(),
+                vector_store::VectorScope::new(owner, machine),
+            )?;
+            Ok(LongTermMemory::new(store, Box::new(TopicEmbedder)))
+        };
+
+        let laptop = open("alice", "laptop")?;
+        let memory = laptop
+            .add(MemoryKind::Preference, "Uses pnpm", None)
+            .await?;
+        // Another user of the store doesn't find Alice's memories
+        assert!(open("bob", "laptop")?.search("pnpm", 5).await?.is_empty());
+        // and her other machines find them, though they aren't in their database
+        let found = open("alice", "desktop")?.search("pnpm", 5).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, memory);
+        Ok(())
+    }
+
+    /// Replies to every request with the same memories
+    struct ExtractingProvider;
+
+    #[async_trait]
+    impl Provider for ExtractingProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            ModelConfig::new("test-model".to_string())
+        }
+
+        async fn complete(
+            &self,
+            _system: &str,
+            _messages: &[Message],
+            _tools: &[Tool],
+        ) -> Result<(Message, ProviderUsage), ProviderError> {
+            let reply = r#"[{"kind": "preference", "content": "Installs packages with pnpm"},
+                {"kind": "fact", "content": "Maintains a Rust crate"}]"#;
+            Ok((
+                Message::assistant().with_text(reply),
+                ProviderUsage::new("test-model".to_string(), Usage::default()),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remember_skips_known() -> Result<()> {
+        let memory = memory();
+        memory
+            .add(MemoryKind::Preference, "Always use pnpm", None)
+            .await?;
+        let messages = vec![
+            Message::user().with_text("Add a dependency to my Rust crate, with pnpm for the docs"),
+            Message::assistant().with_text("Done"),
+        ];
+        let remembered = memory
+            .remember(&ExtractingProvider, &messages, Some("s1".into()))
+            .await?;
+        assert_eq!(remembered.len(), 1);
+        assert_eq!(remembered[0].content, "Maintains a Rust crate");
+        assert_eq!(remembered[0].session.as_deref(), Some("s1"));
+        assert_eq!(memory.list()?.len(), 2);
+
+        assert!(memory
+            .remember(&ExtractingProvider, &[], None)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
+    /// Renames the session while the memories are extracted from it
+    struct RenamingProvider(std::path::PathBuf);
+
+    #[async_trait]
+    impl Provider for RenamingProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            ModelConfig::new("test-model".to_string())
+        }
+
+        async fn complete(
+            &self,
+            system: &str,
+            messages: &[Message],
+            tools: &[Tool],
+        ) -> Result<(Message, ProviderUsage), ProviderError> {
+            let mut metadata = session::read_metadata(&self.0).unwrap();
+            metadata.description = "Renamed".to_string();
+            session::update_metadata(&self.0, &metadata).await.unwrap();
+            ExtractingProvider.complete(system, messages, tools).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remember_session_keeps_metadata() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let session_file = dir.path().join("s1.jsonl");
+        let messages = vec![
+            Message::user().with_text("Add a dependency to my Rust crate"),
+            Message::assistant().with_text("Done"),
+        ];
+        session::storage::save_messages_with_metadata(
+            &session_file,
+            &session::SessionMetadata::default(),
+            &messages,
+        )?;
+
+        let memory = memory();
+        let provider = RenamingProvider(session_file.clone());
+        let remembered = memory.remember_session(&session_file, &provider, 0).await?;
+        assert_eq!(remembered.len(), 2);
+        let metadata = session::read_metadata(&session_file)?;
+        assert_eq!(metadata.description, "Renamed");
+        assert_eq!(metadata.remembered_messages, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_extracted() {
+        let reply = "```json\n[{\"kind\": \"preference\", \"content\": \"Uses tabs\"}, {\"content\": \"Lives in Oslo\"}]\n```";
+        let extracted = parse_extracted(reply).unwrap();
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(
+            MemoryKind::parse(&extracted[0].kind),
+            MemoryKind::Preference
+        );
+        assert_eq!(MemoryKind::parse(&extracted[1].kind), MemoryKind::Fact);
+        assert!(parse_extracted("[]").unwrap().is_empty());
+        assert!(parse_extracted("Nothing to remember").is_err());
+    }
+
+    #[test]
+    fn test_format_memories() {
+        assert_eq!(format_memories(&[]), None);
+        let now = Utc::now();
+        let prompt = format_memories(&[Memory {
+            id: "a".to_string(),
+            kind: MemoryKind::Preference,
+            content: "Uses pnpm".to_string(),
+            session: None,
+            created: now,
+            updated: now,
+        }])
+        .unwrap();
+        assert!(prompt.contains("# Memories"));
+        assert!(prompt.ends_with("- (preference) Uses pnpm\n"));
+    }
+}
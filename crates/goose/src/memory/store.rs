@@ -0,0 +1,243 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use etcetera::{choose_app_strategy, AppStrategy};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::{Memory, MemoryKind};
+use crate::vector_store::{
+    collection_name, store_changed, SqliteVectorStore, VectorPoint, VectorScope, VectorStore,
+};
+
+const COLLECTION: &str = "memories";
+
+/// Memories in a local SQLite database, with their embeddings kept in a [`VectorStore`] under
+/// the user's [`VectorScope`]
+pub struct MemoryStore {
+    conn: Mutex<Connection>,
+    vectors: Arc<dyn VectorStore>,
+    scope: VectorScope,
+}
+
+impl MemoryStore {
+    /// Open (creating if needed) the memory database at the given path
+    pub fn open(path: &Path, vectors: Arc<dyn VectorStore>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?, vectors, VectorScope::current())
+    }
+
+    /// Open an in-memory store with in-memory vectors, which are discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(
+            Connection::open_in_memory()?,
+            Arc::new(SqliteVectorStore::open_in_memory()?),
+            VectorScope::new("local", "local"),
+        )
+    }
+
+    pub(crate) fn with_connection(
+        conn: Connection,
+        vectors: Arc<dyn VectorStore>,
+        scope: VectorScope,
+    ) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS memories (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                content TEXT NOT NULL,
+                session TEXT,
+                created TEXT NOT NULL,
+                updated TEXT NOT NULL,
+                model TEXT NOT NULL
+            );",
+        )?;
+        // The vectors used to be kept with the memories
+        let has_vectors: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('memories') WHERE name = 'vector'",
+            [],
+            |row| row.get(0),
+        )?;
+        if has_vectors {
+            conn.execute_batch("ALTER TABLE memories DROP COLUMN vector;")?;
+        }
+        // Clearing the model has the memories embedded again on the next search
+        if store_changed(&conn, vectors.as_ref(), &scope)? {
+            conn.execute_batch("UPDATE memories SET model = '';")?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            vectors,
+            scope,
+        })
+    }
+
+    /// Default location of the memory database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("memories.db")
+    }
+
+    /// Save `memory` with its embedding from `model`, replacing the memory with the same id
+    pub async fn put(&self, memory: &Memory, model: &str, vector: &[f32]) -> Result<()> {
+        let previous_model = self.model_of(&memory.id)?;
+        self.vectors
+            .upsert(
+                &collection_name(COLLECTION, &self.scope, model),
+                vec![VectorPoint {
+                    id: memory.id.clone(),
+                    vector: vector.to_vec(),
+                    payload: serde_json::to_value(memory)?,
+                }],
+            )
+            .await?;
+        {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO memories
+                    (id, kind, content, session, created, updated, model)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    memory.id,
+                    memory.kind.as_str(),
+                    memory.content,
+                    memory.session,
+                    memory.created.to_rfc3339(),
+                    memory.updated.to_rfc3339(),
+                    model
+                ],
+            )?;
+        }
+        if let Some(previous_model) = previous_model.filter(|previous| previous != model) {
+            self.vectors
+                .delete(
+                    &collection_name(COLLECTION, &self.scope, &previous_model),
+                    std::slice::from_ref(&memory.id),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The model a memory was embedded by, None if there is no such memory
+    fn model_of(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT model FROM memories WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Memory>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT id, kind, content, session, created, updated FROM memories WHERE id = ?1",
+                params![id],
+                read_memory,
+            )
+            .optional()?)
+    }
+
+    /// Every memory, the most recently updated first
+    pub fn list(&self) -> Result<Vec<Memory>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT id, kind, content, session, created, updated FROM memories
+                ORDER BY updated DESC",
+        )?;
+        let memories = statement
+            .query_map([], read_memory)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(memories)
+    }
+
+    /// Remove a memory, returning whether there was one with that id
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let Some(model) = self.model_of(id)? else {
+            return Ok(false);
+        };
+        self.vectors
+            .delete(
+                &collection_name(COLLECTION, &self.scope, &model),
+                &[id.to_string()],
+            )
+            .await?;
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM memories WHERE id = ?1", params![id])? > 0)
+    }
+
+    /// The memories embedded by another model than `model`, which can't be compared with its
+    /// vectors until they are embedded again
+    pub fn outdated(&self, model: &str) -> Result<Vec<Memory>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT id, kind, content, session, created, updated FROM memories
+                WHERE model != ?1",
+        )?;
+        let memories = statement
+            .query_map(params![model], read_memory)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(memories)
+    }
+
+    /// The memories most like `vector` from `model`, the closest first, with their similarity
+    pub async fn search(
+        &self,
+        model: &str,
+        vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<(Memory, f32)>> {
+        let points = self
+            .vectors
+            .search(
+                &collection_name(COLLECTION, &self.scope, model),
+                vector,
+                limit,
+            )
+            .await?;
+        let mut scored = Vec::with_capacity(points.len());
+        for point in points {
+            // The user's memories from their other machines are only in the vector store
+            let memory = match self.get(&point.id)? {
+                Some(memory) => memory,
+                None => match serde_json::from_value(point.payload) {
+                    Ok(memory) => memory,
+                    Err(_) => continue,
+                },
+            };
+            scored.push((memory, point.score));
+        }
+        Ok(scored)
+    }
+}
+
+fn read_memory(row: &Row) -> rusqlite::Result<Memory> {
+    let timestamp = |index: usize| -> rusqlite::Result<DateTime<Utc>> {
+        let text: String = row.get(index)?;
+        DateTime::parse_from_rfc3339(&text)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    index,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })
+    };
+    Ok(Memory {
+        id: row.get(0)?,
+        kind: MemoryKind::parse(&row.get::<_, String>(1)?),
+        content: row.get(2)?,
+        session: row.get(3)?,
+        created: timestamp(4)?,
+        updated: timestamp(5)?,
+    })
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 /// Messages which represent the content sent back and forth to LLM provider
 ///
@@ -16,8 +16,11 @@ use mcp_core::role::Role;
 use mcp_core::tool::ToolCall;
 use serde_json::Value;
 
+pub mod attachment;
 mod tool_result_serde;
 
+pub use attachment::{AttachmentContent, AttachmentPart};
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolRequest {
@@ -81,6 +84,7 @@ pub enum MessageContent {
     ToolConfirmationRequest(ToolConfirmationRequest),
     Thinking(ThinkingContent),
     RedactedThinking(RedactedThinkingContent),
+    Attachment(AttachmentContent),
 }
 
 impl MessageContent {
@@ -137,6 +141,11 @@ impl MessageContent {
     pub fn redacted_thinking<S: Into<String>>(data: S) -> Self {
         MessageContent::RedactedThinking(RedactedThinkingContent { data: data.into() })
     }
+
+    pub fn attachment(attachment: AttachmentContent) -> Self {
+        MessageContent::Attachment(attachment)
+    }
+
     pub fn as_tool_request(&self) -> Option<&ToolRequest> {
         if let MessageContent::ToolRequest(ref tool_request) = self {
             Some(tool_request)
@@ -252,8 +261,18 @@ pub struct Message {
     pub role: Role,
     pub created: i64,
     pub content: Vec<MessageContent>,
+    /// Values integrations attach to correlate the message with other systems, such as tags,
+    /// its source or a ticket ID. They are kept with the session and never sent to the model.
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
 }
 
+/// Metadata of a message or session, kept in key order so it serializes the same each time
+pub type Metadata = BTreeMap<String, Value>;
+
+/// The metadata key of a note the agent wrote to the model, naming what the note is for
+pub const AGENT_NOTE: &str = "goose_note";
+
 impl Message {
     /// Create a new user message with the current timestamp
     pub fn user() -> Self {
@@ -261,6 +280,7 @@ impl Message {
             role: Role::User,
             created: Utc::now().timestamp(),
             content: Vec::new(),
+            metadata: Metadata::new(),
         }
     }
 
@@ -270,9 +290,31 @@ impl Message {
             role: Role::Assistant,
             created: Utc::now().timestamp(),
             content: Vec::new(),
+            metadata: Metadata::new(),
         }
     }
 
+    /// A note from the agent to the model, such as a reviewer's critique of its answer
+    ///
+    /// Notes are sent as user messages, as that is what a model replies to, but they aren't a
+    /// turn the user started, see [`Message::is_agent_note`].
+    pub fn agent_note<S: Into<String>>(kind: &str, text: S) -> Self {
+        Message::user()
+            .with_text(text)
+            .with_metadata(AGENT_NOTE, kind)
+    }
+
+    /// Whether the message is a note from the agent rather than from the user
+    pub fn is_agent_note(&self) -> bool {
+        self.metadata.contains_key(AGENT_NOTE)
+    }
+
+    /// Set a metadata value of the message
+    pub fn with_metadata<S: Into<String>, V: Into<Value>>(mut self, key: S, value: V) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
     /// Add any MessageContent to the message
     pub fn with_content(mut self, content: MessageContent) -> Self {
         self.content.push(content);
@@ -334,6 +376,11 @@ impl Message {
         self.with_content(MessageContent::redacted_thinking(data))
     }
 
+    /// Add a file attachment to the message
+    pub fn with_attachment(self, attachment: AttachmentContent) -> Self {
+        self.with_content(MessageContent::attachment(attachment))
+    }
+
     /// Get the concatenated text content of the message, separated by newlines
     pub fn as_concat_text(&self) -> String {
         self.content
@@ -475,6 +522,44 @@ mod tests {
         assert_eq!(tool_call["error"], "Execution failed: Something went wrong");
     }
 
+    #[test]
+    fn test_attachment_serialization() {
+        let message = Message::user()
+            .with_text("What's the total?")
+            .with_attachment(AttachmentContent::from_bytes(
+                "sales.csv",
+                "text/csv",
+                b"north,10",
+            ));
+
+        let value = serde_json::to_value(&message).unwrap();
+        let attachment = &value["content"][1];
+        assert_eq!(attachment["type"], "attachment");
+        assert_eq!(attachment["name"], "sales.csv");
+        assert_eq!(attachment["mimeType"], "text/csv");
+        assert!(attachment.get("path").is_none());
+
+        let parsed: Message = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, message);
+    }
+
+    #[test]
+    fn test_metadata_serialization() {
+        let message = Message::user().with_text("Hello");
+        let value = serde_json::to_value(&message).unwrap();
+        assert!(value.get("metadata").is_none());
+
+        let message = message
+            .with_metadata("ticket", "OPS-1234")
+            .with_metadata("tags", json!(["triage", "billing"]));
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!(value["metadata"]["ticket"], "OPS-1234");
+        assert_eq!(value["metadata"]["tags"][1], "billing");
+
+        let parsed: Message = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, message);
+    }
+
     #[test]
     fn test_deserialization() {
         // Create a JSON string with our new format
@@ -0,0 +1,322 @@
+//! Files attached to a message, such as a CSV to analyze or a PDF to summarize
+//!
+//! An attachment is kept in the session as a path, or as its bytes when it didn't come from a
+//! file, and is read when a request is built. Each provider format asks for it with
+//! [`AttachmentContent::render`], giving the types its API reads natively. Those are sent as
+//! they are, text files are inlined, and what is too big or can't be read by the model is
+//! reduced to an excerpt or a note of where the file is, so the agent can open it with its tools.
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+
+/// The most characters of a text file inlined whole; longer files are cut to this many
+pub const MAX_INLINE_TEXT: usize = 100_000;
+
+/// The largest file sent natively, in bytes
+pub const MAX_NATIVE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Image types the image content of most providers accepts
+pub const IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Types that are text although they aren't `text/*`
+const TEXT_APPLICATION_TYPES: &[&str] = &[
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/toml",
+    "application/javascript",
+    "application/sql",
+    "application/x-sh",
+];
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentContent {
+    /// The file name, as shown to the model
+    pub name: String,
+    pub mime_type: String,
+    /// Where the file is, for attachments read from disk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// The contents base64 encoded, for attachments that aren't read from disk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+/// An attachment as a provider request includes it
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachmentPart {
+    /// The file's text, or an excerpt of it or a note about it
+    Text(String),
+    /// The file as it is, of a type the provider reads natively
+    File { mime_type: String, data: String },
+}
+
+impl AttachmentContent {
+    /// Attach a file, which is read each time it is sent
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(anyhow!("No such file {}", path.display()));
+        }
+        let path = path.canonicalize()?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mime_type = match mime_type_for_extension(&path) {
+            Some(mime_type) => mime_type.to_string(),
+            None => sniff_mime_type(&path)?.to_string(),
+        };
+        Ok(Self {
+            name,
+            mime_type,
+            path: Some(path),
+            data: None,
+        })
+    }
+
+    /// Attach contents that aren't in a file
+    pub fn from_bytes<S: Into<String>, T: Into<String>>(
+        name: S,
+        mime_type: T,
+        bytes: &[u8],
+    ) -> Self {
+        Self {
+            name: name.into(),
+            mime_type: mime_type.into(),
+            path: None,
+            data: Some(base64::prelude::BASE64_STANDARD.encode(bytes)),
+        }
+    }
+
+    /// The attached contents
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        match (&self.data, &self.path) {
+            (Some(data), _) => Ok(base64::prelude::BASE64_STANDARD.decode(data)?),
+            (None, Some(path)) => Ok(std::fs::read(path)?),
+            (None, None) => Err(anyhow!("The attachment {} has no contents", self.name)),
+        }
+    }
+
+    /// Whether the attachment is a text file, which any model can read
+    pub fn is_text(&self) -> bool {
+        self.mime_type.starts_with("text/")
+            || TEXT_APPLICATION_TYPES.contains(&self.mime_type.as_str())
+            || self.mime_type.ends_with("+json")
+            || self.mime_type.ends_with("+xml")
+    }
+
+    /// The attachment as it should be sent to a provider that reads the `native` types itself
+    pub fn render(&self, native: &[&str]) -> AttachmentPart {
+        let bytes = match self.bytes() {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return AttachmentPart::Text(format!(
+                    "[The attached file {} could not be read: {}]",
+                    self.name, e
+                ))
+            }
+        };
+
+        if native.contains(&self.mime_type.as_str()) && bytes.len() <= MAX_NATIVE_BYTES {
+            return AttachmentPart::File {
+                mime_type: self.mime_type.clone(),
+                data: base64::prelude::BASE64_STANDARD.encode(&bytes),
+            };
+        }
+
+        match std::str::from_utf8(&bytes) {
+            Ok(text) if self.is_text() => AttachmentPart::Text(self.inline_text(text)),
+            _ => AttachmentPart::Text(format!(
+                "[The attached file {} ({}, {} bytes) can't be shown here{}]",
+                self.name,
+                self.mime_type,
+                bytes.len(),
+                self.location()
+            )),
+        }
+    }
+
+    /// The attachment as text, for providers that read no files natively
+    pub fn render_text(&self) -> String {
+        match self.render(&[]) {
+            AttachmentPart::Text(text) => text,
+            AttachmentPart::File { .. } => unreachable!("no types are native"),
+        }
+    }
+
+    /// The text of a file, cut at a line end if it is longer than [`MAX_INLINE_TEXT`]
+    fn inline_text(&self, text: &str) -> String {
+        let shown = match text.char_indices().nth(MAX_INLINE_TEXT) {
+            Some((end, _)) => {
+                let end = text[..end].rfind('\n').map_or(end, |newline| newline + 1);
+                &text[..end]
+            }
+            None => text,
+        };
+        let mut inlined = format!(
+            "<attachment name=\"{}\" type=\"{}\">\n{}",
+            self.name, self.mime_type, shown
+        );
+        if !inlined.ends_with('\n') {
+            inlined.push('\n');
+        }
+        inlined.push_str("</attachment>");
+        if shown.len() < text.len() {
+            inlined.push_str(&format!(
+                "\n[Only the first {} of {} lines of {} are shown{}]",
+                shown.lines().count(),
+                text.lines().count(),
+                self.name,
+                self.location()
+            ));
+        }
+        inlined
+    }
+
+    fn location(&self) -> String {
+        match &self.path {
+            Some(path) => format!(", the whole file is at {}", path.display()),
+            None => String::new(),
+        }
+    }
+}
+
+fn mime_type_for_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    Some(match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "json" | "jsonl" | "ipynb" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "js" | "mjs" | "ts" | "tsx" | "jsx" => "application/javascript",
+        "sql" => "application/sql",
+        "sh" | "bash" | "zsh" => "application/x-sh",
+        "py" => "text/x-python",
+        "rs" => "text/x-rust",
+        "go" => "text/x-go",
+        "java" | "kt" => "text/x-java",
+        "c" | "h" | "cpp" | "hpp" | "cc" => "text/x-c",
+        "rb" => "text/x-ruby",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        _ => return None,
+    })
+}
+
+/// The type of a file without a known extension, by whether its start is text
+fn sniff_mime_type(path: &Path) -> Result<&'static str> {
+    use std::io::Read;
+
+    let mut start = Vec::with_capacity(8192);
+    std::fs::File::open(path)?
+        .take(8192)
+        .read_to_end(&mut start)?;
+    let is_text = match std::str::from_utf8(&start) {
+        Ok(_) => true,
+        // The read may have cut a character in two
+        Err(e) => e.error_len().is_none(),
+    };
+    Ok(if is_text && !start.contains(&0) {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let csv = dir.path().join("sales.csv");
+        std::fs::write(&csv, "region,total\nnorth,10\n")?;
+        let attachment = AttachmentContent::from_path(&csv)?;
+        assert_eq!(attachment.name, "sales.csv");
+        assert_eq!(attachment.mime_type, "text/csv");
+        assert!(attachment.is_text());
+        assert_eq!(attachment.bytes()?, b"region,total\nnorth,10\n");
+
+        let notes = dir.path().join("NOTES");
+        std::fs::write(&notes, "remember the milk")?;
+        assert_eq!(
+            AttachmentContent::from_path(&notes)?.mime_type,
+            "text/plain"
+        );
+        let blob = dir.path().join("blob");
+        std::fs::write(&blob, [0u8, 159, 146, 150])?;
+        assert_eq!(
+            AttachmentContent::from_path(&blob)?.mime_type,
+            "application/octet-stream"
+        );
+
+        assert!(AttachmentContent::from_path(dir.path().join("missing.csv")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() {
+        let csv = AttachmentContent::from_bytes("sales.csv", "text/csv", b"region,total\nnorth,10");
+        assert_eq!(
+            csv.render(IMAGE_TYPES),
+            AttachmentPart::Text(
+                "<attachment name=\"sales.csv\" type=\"text/csv\">\nregion,total\nnorth,10\n</attachment>"
+                    .to_string()
+            )
+        );
+
+        let pdf = AttachmentContent::from_bytes("report.pdf", "application/pdf", b"%PDF-1.7");
+        assert_eq!(
+            pdf.render(&["application/pdf"]),
+            AttachmentPart::File {
+                mime_type: "application/pdf".to_string(),
+                data: "JVBERi0xLjc=".to_string(),
+            }
+        );
+        match pdf.render(IMAGE_TYPES) {
+            AttachmentPart::Text(note) => assert_eq!(
+                note,
+                "[The attached file report.pdf (application/pdf, 8 bytes) can't be shown here]"
+            ),
+            part => panic!("Expected a note, got {:?}", part),
+        }
+    }
+
+    #[test]
+    fn test_render_long_text() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("big.log");
+        let line = "x".repeat(99) + "\n";
+        std::fs::write(&path, line.repeat(MAX_INLINE_TEXT / 100 + 50))?;
+        let attachment = AttachmentContent::from_path(&path)?;
+
+        let AttachmentPart::Text(text) = attachment.render(&[]) else {
+            panic!("Expected text");
+        };
+        assert!(text.len() < MAX_INLINE_TEXT + 500);
+        assert!(text.contains(&format!("{}</attachment>", line)));
+        assert!(text.ends_with(&format!(
+            "[Only the first {} of {} lines of big.log are shown, the whole file is at {}]",
+            MAX_INLINE_TEXT / 100,
+            MAX_INLINE_TEXT / 100 + 50,
+            path.canonicalize()?.display()
+        )));
+        Ok(())
+    }
+}
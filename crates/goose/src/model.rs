@@ -1,11 +1,74 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub mod pricing;
+pub mod registry;
+
 const DEFAULT_CONTEXT_LIMIT: usize = 128_000;
 
 // Tokenizer names, used to infer from model name
 pub const GPT_4O_TOKENIZER: &str = "Xenova--gpt-4o";
 pub const CLAUDE_TOKENIZER: &str = "Xenova--claude-tokenizer";
 
+/// Config key holding parameters for individual models, keyed by model name:
+///
+/// ```yaml
+/// models:
+///   Meta-Llama-3.3-70B-Instruct:
+///     temperature: 0.2
+///     max_tokens: 4096
+///     stop: ["<|eot_id|>"]
+/// ```
+///
+/// They are applied whenever that model is used, over the global settings like
+/// GOOSE_TEMPERATURE.
+pub const MODELS_CONFIG_KEY: &str = "models";
+
+tokio::task_local! {
+    static OUTPUT_LIMIT: i32;
+}
+
+/// Run `future` with the completions it makes generating at most `tokens`, or the model's own
+/// limit when that is lower
+pub async fn with_output_limit<F: Future>(tokens: i32, future: F) -> F::Output {
+    OUTPUT_LIMIT.scope(tokens, future).await
+}
+
+/// The lower of `limit` and the limit of the completions made now, if either is set
+fn within_output_limit(limit: Option<i32>) -> Option<i32> {
+    match (limit, OUTPUT_LIMIT.try_with(|tokens| *tokens).ok()) {
+        (Some(limit), Some(scoped)) => Some(limit.min(scoped)),
+        (limit, scoped) => limit.or(scoped),
+    }
+}
+
+/// Parameters set for one model in the `models` config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ModelOverrides {
+    /// Sampling temperature
+    pub temperature: Option<f32>,
+    /// Maximum tokens to generate
+    pub max_tokens: Option<i32>,
+    /// Context limit, for models the built-in registry doesn't know
+    pub context_limit: Option<usize>,
+    /// Sequences that end generation
+    pub stop: Option<Vec<String>>,
+}
+
+impl ModelOverrides {
+    /// The overrides configured for a model, if any
+    pub fn from_config(model_name: &str) -> Self {
+        crate::config::Config::global()
+            .get_param::<HashMap<String, ModelOverrides>>(MODELS_CONFIG_KEY)
+            .ok()
+            .and_then(|mut models| models.remove(model_name))
+            .unwrap_or_default()
+    }
+}
+
 /// Configuration for model-specific settings and limits
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
@@ -25,6 +88,9 @@ pub struct ModelConfig {
     pub toolshim: bool,
     /// Model to use for toolshim (optional as a default exists)
     pub toolshim_model: Option<String>,
+    /// Optional sequences that end generation
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
 }
 
 impl ModelConfig {
@@ -34,6 +100,8 @@ impl ModelConfig {
     /// 1. Explicit context_limit if provided in config
     /// 2. Model-specific default based on model name
     /// 3. Global default (128_000) (in get_context_limit)
+    ///
+    /// The model's entry in the `models` config, if there is one, is applied last.
     pub fn new(model_name: String) -> Self {
         let context_limit = Self::get_model_specific_limit(&model_name);
         let tokenizer_name = Self::infer_tokenizer_name(&model_name);
@@ -44,15 +112,22 @@ impl ModelConfig {
 
         let toolshim_model = std::env::var("GOOSE_TOOLSHIM_OLLAMA_MODEL").ok();
 
+        let temperature = crate::config::Config::global()
+            .get_param("GOOSE_TEMPERATURE")
+            .ok();
+
+        let overrides = ModelOverrides::from_config(&model_name);
         Self {
             model_name,
             tokenizer_name: tokenizer_name.to_string(),
             context_limit,
-            temperature: None,
+            temperature,
             max_tokens: None,
             toolshim,
             toolshim_model,
+            stop: None,
         }
+        .with_overrides(overrides)
     }
 
     fn infer_tokenizer_name(model_name: &str) -> &'static str {
@@ -66,23 +141,7 @@ impl ModelConfig {
 
     /// Get model-specific context limit based on model name
     fn get_model_specific_limit(model_name: &str) -> Option<usize> {
-        // Implement some sensible defaults
-        match model_name {
-            // OpenAI models, https://platform.openai.com/docs/models#models-overview
-            name if name.contains("gpt-4o") => Some(128_000),
-            name if name.contains("gpt-4-turbo") => Some(128_000),
-            name if name.contains("o1-mini") || name.contains("o1-preview") => Some(128_000),
-            name if name.contains("o1") => Some(200_000),
-            name if name.contains("o3-mini") => Some(200_000),
-
-            // Anthropic models, https://docs.anthropic.com/en/docs/about-claude/models
-            name if name.contains("claude-3") => Some(200_000),
-
-            // Meta Llama models, https://github.com/meta-llama/llama-models/tree/main?tab=readme-ov-file#llama-models-1
-            name if name.contains("llama3.2") => Some(128_000),
-            name if name.contains("llama3.3") => Some(128_000),
-            _ => None,
-        }
+        registry::lookup(model_name).map(|limits| limits.context_limit)
     }
 
     /// Set an explicit context limit
@@ -108,6 +167,22 @@ impl ModelConfig {
         self
     }
 
+    /// Set the stop sequences
+    pub fn with_stop(mut self, stop: Option<Vec<String>>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Apply the parameters set for this model, keeping the current values of those it
+    /// doesn't set
+    pub fn with_overrides(mut self, overrides: ModelOverrides) -> Self {
+        self.temperature = overrides.temperature.or(self.temperature);
+        self.max_tokens = overrides.max_tokens.or(self.max_tokens);
+        self.context_limit = overrides.context_limit.or(self.context_limit);
+        self.stop = overrides.stop.or(self.stop);
+        self
+    }
+
     /// Set whether to interpret tool calls
     pub fn with_toolshim(mut self, toolshim: bool) -> Self {
         self.toolshim = toolshim;
@@ -130,6 +205,36 @@ impl ModelConfig {
     pub fn context_limit(&self) -> usize {
         self.context_limit.unwrap_or(DEFAULT_CONTEXT_LIMIT)
     }
+
+    /// Get the maximum number of output tokens for the current model
+    /// An explicit max_tokens takes precedence over the registry default
+    pub fn max_output_tokens(&self) -> Option<i32> {
+        within_output_limit(self.max_tokens.or_else(|| {
+            registry::lookup(&self.model_name)
+                .and_then(|limits| limits.max_output_tokens)
+                .map(|tokens| tokens as i32)
+        }))
+    }
+
+    /// The max tokens to ask a completion made now for, lowered by [`with_output_limit`]
+    pub fn output_limit(&self) -> Option<i32> {
+        within_output_limit(self.max_tokens)
+    }
+
+    /// Get the knowledge cutoff (YYYY-MM) for the current model, if known
+    pub fn knowledge_cutoff(&self) -> Option<&'static str> {
+        registry::lookup(&self.model_name).and_then(|limits| limits.knowledge_cutoff)
+    }
+
+    /// Get the image limits of the current model's vendor, if known
+    pub fn image_limits(&self) -> Option<crate::providers::images::ImageLimits> {
+        registry::image_limits(&self.model_name)
+    }
+
+    /// Get the features supported by the current model
+    pub fn capabilities(&self) -> registry::ModelCapabilities {
+        registry::capabilities(&self.model_name)
+    }
 }
 
 #[cfg(test)]
@@ -167,6 +272,53 @@ mod tests {
         assert_eq!(config.context_limit, Some(50_000));
     }
 
+    #[test]
+    fn test_model_config_overrides() {
+        let config = ModelConfig::new("test-model".to_string())
+            .with_temperature(Some(0.7))
+            .with_context_limit(Some(50_000))
+            .with_overrides(ModelOverrides {
+                temperature: Some(0.2),
+                max_tokens: Some(4096),
+                context_limit: None,
+                stop: Some(vec!["<|eot_id|>".to_string()]),
+            });
+
+        assert_eq!(config.temperature, Some(0.2));
+        assert_eq!(config.max_tokens, Some(4096));
+        assert_eq!(config.context_limit, Some(50_000));
+        assert_eq!(config.stop, Some(vec!["<|eot_id|>".to_string()]));
+    }
+
+    #[test]
+    fn test_model_config_registry_defaults() {
+        let config = ModelConfig::new("claude-3-opus-latest".to_string());
+        assert_eq!(config.max_output_tokens(), Some(4_096));
+        assert_eq!(config.knowledge_cutoff(), Some("2023-08"));
+
+        // Explicit max_tokens overrides the registry
+        let config = config.with_max_tokens(Some(1_000));
+        assert_eq!(config.max_output_tokens(), Some(1_000));
+
+        let config = ModelConfig::new("unknown-model".to_string());
+        assert_eq!(config.max_output_tokens(), None);
+        assert_eq!(config.knowledge_cutoff(), None);
+    }
+
+    #[tokio::test]
+    async fn test_with_output_limit() {
+        let config = ModelConfig::new("claude-3-opus-latest".to_string());
+        let limited = with_output_limit(100, async {
+            (config.max_output_tokens(), config.output_limit())
+        })
+        .await;
+        assert_eq!(limited, (Some(100), Some(100)));
+        let config = config.with_max_tokens(Some(50));
+        let limited = with_output_limit(100, async { config.output_limit() }).await;
+        assert_eq!(limited, Some(50));
+        assert_eq!(ModelConfig::new("gpt-4o".to_string()).output_limit(), None);
+    }
+
     #[test]
     fn test_model_config_tool_interpretation() {
         // Test without env vars - should be false
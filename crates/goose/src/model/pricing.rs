@@ -0,0 +1,207 @@
+//! Per-model token prices used to estimate the cost of a completion
+//!
+//! Prices are in USD per million tokens. The built-in table can be extended or
+//! overridden with a `pricing` map in the config file, keyed by model name pattern:
+//!
+//! ```yaml
+//! pricing:
+//!   my-finetuned-gpt-4o:
+//!     input: 3.75
+//!     output: 15.0
+//!     cached_input: 1.875
+//! ```
+//!
+//! Patterns are matched as case-insensitive substrings of the model name, the same way
+//! as the [registry](super::registry).
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::providers::base::Usage;
+
+/// Config key holding price overrides
+pub const PRICING_CONFIG_KEY: &str = "pricing";
+
+/// Token prices for a model, in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ModelPricing {
+    /// Price of uncached input tokens
+    pub input: f64,
+    /// Price of output tokens
+    pub output: f64,
+    /// Price of input tokens read from the prompt cache, defaults to the input price
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+}
+
+impl ModelPricing {
+    /// Compute the cost in USD of the given usage
+    ///
+    /// Returns None if the usage carries no token counts at all
+    pub fn cost(&self, usage: &Usage) -> Option<f64> {
+        if usage.input_tokens.is_none() && usage.output_tokens.is_none() {
+            return None;
+        }
+
+        let input = usage.input_tokens.unwrap_or(0).max(0) as f64;
+        let output = usage.output_tokens.unwrap_or(0).max(0) as f64;
+        // Cached tokens are a subset of the input tokens
+        let cached = (usage.cached_input_tokens.unwrap_or(0).max(0) as f64).min(input);
+
+        let cost = (input - cached) * self.input
+            + cached * self.cached_input.unwrap_or(self.input)
+            + output * self.output;
+        Some(cost / 1_000_000.0)
+    }
+}
+
+/// Ordered list of (pattern, input, output, cached input) prices in USD per million
+/// tokens, most specific patterns first
+const MODEL_PRICES: &[(&str, f64, f64, Option<f64>)] = &[
+    // OpenAI, https://openai.com/api/pricing
+    ("gpt-4o-mini", 0.15, 0.60, Some(0.075)),
+    ("gpt-4o", 2.50, 10.00, Some(1.25)),
+    ("gpt-4-turbo", 10.00, 30.00, None),
+    ("gpt-3.5-turbo", 0.50, 1.50, None),
+    ("o1-mini", 1.10, 4.40, Some(0.55)),
+    ("o1-preview", 15.00, 60.00, Some(7.50)),
+    ("o1", 15.00, 60.00, Some(7.50)),
+    ("o3-mini", 1.10, 4.40, Some(0.55)),
+    // Anthropic, https://www.anthropic.com/pricing#anthropic-api
+    ("claude-3-7-sonnet", 3.00, 15.00, Some(0.30)),
+    ("claude-3-5-sonnet", 3.00, 15.00, Some(0.30)),
+    ("claude-3-5-haiku", 0.80, 4.00, Some(0.08)),
+    ("claude-3-opus", 15.00, 75.00, Some(1.50)),
+    ("claude-3-haiku", 0.25, 1.25, Some(0.03)),
+    // Google, https://ai.google.dev/pricing
+    ("gemini-2.0-flash-lite", 0.075, 0.30, None),
+    ("gemini-2.0-flash", 0.10, 0.40, Some(0.025)),
+    ("gemini-1.5-pro", 1.25, 5.00, Some(0.3125)),
+    ("gemini-1.5-flash", 0.075, 0.30, Some(0.01875)),
+    // DeepSeek, https://api-docs.deepseek.com/quick_start/pricing
+    ("deepseek-r1", 0.55, 2.19, Some(0.14)),
+    ("deepseek-reasoner", 0.55, 2.19, Some(0.14)),
+    ("deepseek-chat", 0.27, 1.10, Some(0.07)),
+];
+
+/// Look up the built-in price for a model by name
+pub fn builtin_pricing(model_name: &str) -> Option<ModelPricing> {
+    let model_name = model_name.to_lowercase();
+    MODEL_PRICES
+        .iter()
+        .find(|(pattern, ..)| model_name.contains(pattern))
+        .map(|&(_, input, output, cached_input)| ModelPricing {
+            input,
+            output,
+            cached_input,
+        })
+}
+
+/// Find the override that applies to a model, preferring the longest matching pattern
+fn find_override(
+    overrides: &HashMap<String, ModelPricing>,
+    model_name: &str,
+) -> Option<ModelPricing> {
+    let model_name = model_name.to_lowercase();
+    overrides
+        .iter()
+        .filter(|(pattern, _)| model_name.contains(&pattern.to_lowercase()))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, pricing)| *pricing)
+}
+
+/// Look up the price for a model, with config overrides taking precedence over the
+/// built-in table
+///
+/// Returns None for models we have no price for, such as locally hosted ones
+pub fn lookup(model_name: &str) -> Option<ModelPricing> {
+    let overrides: HashMap<String, ModelPricing> = Config::global()
+        .get_param(PRICING_CONFIG_KEY)
+        .unwrap_or_default();
+    find_override(&overrides, model_name).or_else(|| builtin_pricing(model_name))
+}
+
+/// Compute the cost in USD of a completion, if the model's price is known
+pub fn cost(model_name: &str, usage: &Usage) -> Option<f64> {
+    lookup(model_name).and_then(|pricing| pricing.cost(usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_builtin_pricing() {
+        let mini = builtin_pricing("gpt-4o-mini-2024-07-18").unwrap();
+        assert!(approx_eq(mini.input, 0.15));
+
+        let flash_lite = builtin_pricing("gemini-2.0-flash-lite-preview-02-05").unwrap();
+        assert!(approx_eq(flash_lite.input, 0.075));
+
+        assert_eq!(builtin_pricing("qwen2.5"), None);
+    }
+
+    #[test]
+    fn test_cost() {
+        let pricing = ModelPricing {
+            input: 3.0,
+            output: 15.0,
+            cached_input: Some(0.3),
+        };
+
+        let usage = Usage::new(Some(1_000_000), Some(100_000), Some(1_100_000));
+        assert!(approx_eq(pricing.cost(&usage).unwrap(), 4.5));
+
+        let usage = usage.with_cached_input_tokens(Some(500_000));
+        assert!(approx_eq(pricing.cost(&usage).unwrap(), 1.5 + 0.15 + 1.5));
+
+        assert_eq!(pricing.cost(&Usage::default()), None);
+    }
+
+    #[test]
+    fn test_cached_price_defaults_to_input() {
+        let pricing = ModelPricing {
+            input: 10.0,
+            output: 30.0,
+            cached_input: None,
+        };
+        let usage =
+            Usage::new(Some(1_000_000), Some(0), None).with_cached_input_tokens(Some(1_000_000));
+        assert!(approx_eq(pricing.cost(&usage).unwrap(), 10.0));
+    }
+
+    #[test]
+    fn test_find_override_prefers_longest_pattern() {
+        let overrides = HashMap::from([
+            (
+                "gpt-4o".to_string(),
+                ModelPricing {
+                    input: 1.0,
+                    output: 1.0,
+                    cached_input: None,
+                },
+            ),
+            (
+                "GPT-4o-Mini".to_string(),
+                ModelPricing {
+                    input: 2.0,
+                    output: 2.0,
+                    cached_input: None,
+                },
+            ),
+        ]);
+
+        let pricing = find_override(&overrides, "gpt-4o-mini").unwrap();
+        assert!(approx_eq(pricing.input, 2.0));
+        let pricing = find_override(&overrides, "gpt-4o-2024-08-06").unwrap();
+        assert!(approx_eq(pricing.input, 1.0));
+        assert_eq!(find_override(&overrides, "claude-3-opus"), None);
+    }
+}
@@ -0,0 +1,254 @@
+//! Registry of known model limits and capabilities
+//!
+//! Maps model name patterns to their context window, maximum output tokens,
+//! knowledge cutoff and supported features, so providers don't need to hardcode
+//! these values or guess them.
+//! Patterns are matched as case-insensitive substrings of the model name, and the
+//! first match wins, so more specific patterns must come before more general ones.
+
+use serde::{Deserialize, Serialize};
+
+use crate::providers::images::{ImageLimits, ANTHROPIC_IMAGE_LIMITS, GOOGLE_IMAGE_LIMITS};
+
+/// Known limits for a model or family of models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelLimits {
+    /// Maximum number of tokens in the context window (input + output)
+    pub context_limit: usize,
+    /// Maximum number of tokens the model can generate in a single response
+    pub max_output_tokens: Option<usize>,
+    /// Knowledge cutoff of the training data, formatted as YYYY-MM
+    pub knowledge_cutoff: Option<&'static str>,
+}
+
+/// Ordered list of (pattern, context limit, max output tokens, knowledge cutoff),
+/// most specific patterns first
+const MODEL_LIMITS: &[(&str, usize, Option<usize>, Option<&str>)] = &[
+    // OpenAI models, https://platform.openai.com/docs/models#models-overview
+    ("gpt-4o-mini", 128_000, Some(16_384), Some("2023-10")),
+    ("gpt-4o", 128_000, Some(16_384), Some("2023-10")),
+    ("gpt-4-turbo", 128_000, Some(4_096), Some("2023-12")),
+    ("gpt-3.5-turbo", 16_385, Some(4_096), Some("2021-09")),
+    ("o1-mini", 128_000, Some(65_536), Some("2023-10")),
+    ("o1-preview", 128_000, Some(32_768), Some("2023-10")),
+    ("o1", 200_000, Some(100_000), Some("2023-10")),
+    ("o3-mini", 200_000, Some(100_000), Some("2023-10")),
+    // Anthropic models, https://docs.anthropic.com/en/docs/about-claude/models
+    ("claude-3-7-sonnet", 200_000, Some(8_192), Some("2024-11")),
+    ("claude-3-5-sonnet", 200_000, Some(8_192), Some("2024-04")),
+    ("claude-3-5-haiku", 200_000, Some(8_192), Some("2024-07")),
+    ("claude-3-opus", 200_000, Some(4_096), Some("2023-08")),
+    ("claude-3-haiku", 200_000, Some(4_096), Some("2023-08")),
+    ("claude-3", 200_000, Some(4_096), None),
+    // Google Gemini models, https://ai.google.dev/gemini-api/docs/models/gemini
+    ("gemini-2.0-flash", 1_048_576, Some(8_192), Some("2024-08")),
+    ("gemini-1.5-pro", 2_097_152, Some(8_192), None),
+    ("gemini-1.5-flash", 1_048_576, Some(8_192), None),
+    // Meta Llama models, https://github.com/meta-llama/llama-models/tree/main?tab=readme-ov-file#llama-models-1
+    ("llama3.3", 128_000, None, Some("2023-12")),
+    ("llama-3.3", 128_000, None, Some("2023-12")),
+    ("llama3.2", 128_000, None, Some("2023-12")),
+    ("llama-3.2", 128_000, None, Some("2023-12")),
+    ("llama3.1", 128_000, None, Some("2023-12")),
+    ("llama-3.1", 128_000, None, Some("2023-12")),
+    // DeepSeek models, https://api-docs.deepseek.com/quick_start/pricing
+    ("deepseek-r1", 128_000, Some(8_192), None),
+];
+
+/// Look up the known limits for a model by name
+///
+/// Returns None if the model doesn't match any known pattern
+pub fn lookup(model_name: &str) -> Option<ModelLimits> {
+    let model_name = model_name.to_lowercase();
+    MODEL_LIMITS
+        .iter()
+        .find(|(pattern, ..)| model_name.contains(pattern))
+        .map(
+            |&(_, context_limit, max_output_tokens, knowledge_cutoff)| ModelLimits {
+                context_limit,
+                max_output_tokens,
+                knowledge_cutoff,
+            },
+        )
+}
+
+/// Features a model supports, used by the agent to degrade gracefully when a
+/// model lacks one (e.g. prompting for tool calls instead of native function calling)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// Native tool (function) calling
+    pub supports_tools: bool,
+    /// Image inputs
+    pub supports_vision: bool,
+    /// Streaming responses
+    pub supports_streaming: bool,
+    /// Constraining output to a JSON schema
+    pub supports_structured_output: bool,
+}
+
+impl Default for ModelCapabilities {
+    /// Models we know nothing about are assumed to handle tools and images, which
+    /// is how goose treated every model before capabilities were tracked
+    fn default() -> Self {
+        Self {
+            supports_tools: true,
+            supports_vision: true,
+            supports_streaming: true,
+            supports_structured_output: false,
+        }
+    }
+}
+
+/// Ordered list of (pattern, tools, vision, streaming, structured output),
+/// most specific patterns first
+const MODEL_CAPABILITIES: &[(&str, bool, bool, bool, bool)] = &[
+    // OpenAI models
+    ("o1-mini", false, false, true, false),
+    ("o1-preview", false, false, true, false),
+    ("o1", true, true, true, true),
+    ("o3-mini", true, false, true, true),
+    ("gpt-4o", true, true, true, true),
+    ("gpt-4-turbo", true, true, true, false),
+    ("gpt-3.5-turbo", true, false, true, false),
+    // Anthropic models
+    ("claude-3-5-haiku", true, false, true, false),
+    ("claude", true, true, true, false),
+    // Google models
+    ("gemini", true, true, true, true),
+    ("gemma", false, false, true, false),
+    // Meta Llama models
+    ("llama3.2-vision", false, true, true, false),
+    ("llama", true, false, true, false),
+    // DeepSeek models, the reasoning models don't support function calling
+    ("deepseek-r1", false, false, true, false),
+    ("deepseek-reasoner", false, false, true, false),
+    ("deepseek", true, false, true, false),
+    // Qwen models
+    ("qwen", true, false, true, false),
+];
+
+/// Look up the known capabilities for a model by name
+///
+/// Returns the permissive defaults if the model doesn't match any known pattern
+pub fn capabilities(model_name: &str) -> ModelCapabilities {
+    let model_name = model_name.to_lowercase();
+    MODEL_CAPABILITIES
+        .iter()
+        .find(|(pattern, ..)| model_name.contains(pattern))
+        .map(
+            |&(
+                _,
+                supports_tools,
+                supports_vision,
+                supports_streaming,
+                supports_structured_output,
+            )| {
+                ModelCapabilities {
+                    supports_tools,
+                    supports_vision,
+                    supports_streaming,
+                    supports_structured_output,
+                }
+            },
+        )
+        .unwrap_or_default()
+}
+
+/// Ordered list of (pattern, image limits) for models that are also served through other
+/// vendors' APIs, such as Claude behind an OpenAI compatible router. The vendor prefixes are
+/// how OpenRouter (`anthropic/...`) and Bedrock (`anthropic.`) name them.
+const MODEL_IMAGE_LIMITS: &[(&str, ImageLimits)] = &[
+    ("claude", ANTHROPIC_IMAGE_LIMITS),
+    ("anthropic/", ANTHROPIC_IMAGE_LIMITS),
+    ("anthropic.", ANTHROPIC_IMAGE_LIMITS),
+    ("gemini", GOOGLE_IMAGE_LIMITS),
+    ("google/", GOOGLE_IMAGE_LIMITS),
+];
+
+/// Look up the image limits of the model's own vendor, whichever API serves it
+///
+/// Returns None if the model doesn't match any known pattern, and the API's own limits apply
+pub fn image_limits(model_name: &str) -> Option<ImageLimits> {
+    let model_name = model_name.to_lowercase();
+    MODEL_IMAGE_LIMITS
+        .iter()
+        .find(|(pattern, _)| model_name.contains(pattern))
+        .map(|&(_, limits)| limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_prefers_specific_patterns() {
+        let mini = lookup("gpt-4o-mini").unwrap();
+        assert_eq!(mini.max_output_tokens, Some(16_384));
+
+        let o1_mini = lookup("o1-mini-2024-09-12").unwrap();
+        assert_eq!(o1_mini.context_limit, 128_000);
+
+        let o1 = lookup("o1").unwrap();
+        assert_eq!(o1.context_limit, 200_000);
+
+        let opus = lookup("claude-3-opus-latest").unwrap();
+        assert_eq!(opus.max_output_tokens, Some(4_096));
+
+        let sonnet = lookup("claude-3-5-sonnet-latest").unwrap();
+        assert_eq!(sonnet.max_output_tokens, Some(8_192));
+        assert_eq!(sonnet.knowledge_cutoff, Some("2024-04"));
+    }
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        let limits = lookup("Meta-Llama-3.3-70B-Instruct").unwrap();
+        assert_eq!(limits.context_limit, 128_000);
+    }
+
+    #[test]
+    fn test_lookup_unknown_model() {
+        assert_eq!(lookup("unknown-model"), None);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let o1_mini = capabilities("o1-mini");
+        assert!(!o1_mini.supports_tools);
+        assert!(!o1_mini.supports_vision);
+
+        let gpt = capabilities("gpt-4o-2024-08-06");
+        assert!(gpt.supports_tools);
+        assert!(gpt.supports_vision);
+        assert!(gpt.supports_structured_output);
+
+        let sonnet = capabilities("claude-3-5-sonnet-latest");
+        assert!(sonnet.supports_vision);
+        assert!(!capabilities("claude-3-5-haiku-latest").supports_vision);
+
+        assert!(!capabilities("deepseek-r1:14b").supports_tools);
+        assert!(capabilities("Meta-Llama-3.3-70B-Instruct").supports_tools);
+        assert!(!capabilities("Meta-Llama-3.3-70B-Instruct").supports_vision);
+    }
+
+    #[test]
+    fn test_image_limits() {
+        assert_eq!(
+            image_limits("anthropic/claude-3.5-sonnet"),
+            Some(ANTHROPIC_IMAGE_LIMITS)
+        );
+        assert_eq!(
+            image_limits("goose-claude-3-7-sonnet"),
+            Some(ANTHROPIC_IMAGE_LIMITS)
+        );
+        assert_eq!(
+            image_limits("google/gemini-2.0-flash-001"),
+            Some(GOOGLE_IMAGE_LIMITS)
+        );
+        assert_eq!(image_limits("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_capabilities_unknown_model() {
+        assert_eq!(capabilities("unknown-model"), ModelCapabilities::default());
+    }
+}
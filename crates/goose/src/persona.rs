@@ -0,0 +1,143 @@
+//! Personas, named ways of working that a session can start in
+//!
+//! A persona bundles instructions for the system prompt, the model and its sampling settings,
+//! and a tool policy to add to the user's. Personas are a `personas` map in the config file, keyed
+//! by name:
+//!
+//! ```yaml
+//! personas:
+//!   careful-reviewer:
+//!     description: Reviews changes without making any
+//!     instructions: Review the code for bugs and risky changes. Don't edit any files.
+//!     model: claude-3-7-sonnet-latest
+//!     temperature: 0.1
+//!     tool_policy:
+//!       developer__text_editor: never
+//!       "*": ask
+//!   rapid-prototyper:
+//!     instructions: Get something working quickly, and leave polish for later.
+//!     temperature: 0.8
+//!     routing:
+//!       small_model: gpt-4o-mini
+//! ```
+//!
+//! A session starts in the persona it is asked to, or else the one in GOOSE_PERSONA. Its
+//! sampling settings apply over the model's in `models`, and its tool policy applies where it
+//! is stricter than the `tool_policy` config, so a persona can't loosen the user's policy. Its
+//! `routing` sends simple questions to a smaller model, as GOOSE_ROUTER_MODEL does for every
+//! session.
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::agents::policy::PolicyEntry;
+use crate::agents::router::RoutingSettings;
+use crate::config::{Config, ConfigError};
+use crate::model::{ModelConfig, ModelOverrides};
+
+/// Config key holding the personas
+pub const PERSONAS_CONFIG_KEY: &str = "personas";
+
+/// Config key naming the persona sessions start in when none is asked for
+pub const DEFAULT_PERSONA_CONFIG_KEY: &str = "GOOSE_PERSONA";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Persona {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Added to the system prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// The provider to use instead of GOOSE_PROVIDER
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// The model to use instead of GOOSE_MODEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Sampling settings for the model
+    #[serde(flatten)]
+    pub sampling: ModelOverrides,
+    /// Which model answers each turn, over the GOOSE_ROUTER_* config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingSettings>,
+    /// Tool policy entries, which apply where they are stricter than the `tool_policy` config
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tool_policy: HashMap<String, PolicyEntry>,
+}
+
+impl Persona {
+    /// The persona named `name`
+    pub fn named(name: &str) -> Result<Self> {
+        personas()?.remove(name).ok_or_else(|| {
+            anyhow!(
+                "There is no persona named {}, add it under '{}' in the config file",
+                name,
+                PERSONAS_CONFIG_KEY
+            )
+        })
+    }
+
+    /// The name of the persona to start a session in, `name` if given and otherwise the
+    /// default persona, if there is one
+    pub fn selected(name: Option<String>) -> Result<Option<String>> {
+        if name.is_some() {
+            return Ok(name);
+        }
+        match Config::global().get_param::<String>(DEFAULT_PERSONA_CONFIG_KEY) {
+            Ok(name) => Ok(Some(name)),
+            Err(ConfigError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The config for `model` with the persona's sampling settings
+    pub fn model_config(&self, model: String) -> ModelConfig {
+        ModelConfig::new(model).with_overrides(self.sampling.clone())
+    }
+}
+
+/// The configured personas, by name
+pub fn personas() -> Result<BTreeMap<String, Persona>> {
+    match Config::global().get_param(PERSONAS_CONFIG_KEY) {
+        Ok(personas) => Ok(personas),
+        Err(ConfigError::NotFound(_)) => Ok(BTreeMap::new()),
+        Err(e) => Err(anyhow!("Failed to read the personas: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::policy::PolicyAction;
+
+    #[test]
+    fn test_persona_config() {
+        let personas: BTreeMap<String, Persona> = serde_yaml::from_str(
+            r#"
+            careful-reviewer:
+              instructions: Don't edit any files.
+              model: gpt-4o
+              temperature: 0.1
+              tool_policy:
+                "*": ask
+            rapid-prototyper:
+              max_tokens: 2048
+            "#,
+        )
+        .unwrap();
+        let reviewer = &personas["careful-reviewer"];
+        assert_eq!(
+            reviewer.tool_policy["*"],
+            PolicyEntry::Action(PolicyAction::Ask)
+        );
+        assert_eq!(reviewer.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            reviewer.model_config("gpt-4o".to_string()).temperature,
+            Some(0.1)
+        );
+
+        let prototyper = personas["rapid-prototyper"].model_config("gpt-4o-mini".to_string());
+        assert_eq!(prototyper.max_tokens, Some(2048));
+    }
+}
@@ -0,0 +1,281 @@
+//! Extractive compression of older conversation turns before they are sent to the provider
+//!
+//! Long sessions accumulate large tool outputs (file contents, command output, search
+//! results) that the model rarely needs verbatim once it has acted on them. When enabled
+//! with `GOOSE_PROMPT_COMPRESSION=true`, bulky text in all but the most recent messages is
+//! reduced to its most salient lines: the start and end of the text, lines that look like
+//! errors, warnings or locations, and the most information dense of the remaining lines.
+//! Dropped lines are replaced with a marker so the model knows content was elided.
+//!
+//! Only the copy sent to the provider is compressed, the session history keeps the full text.
+use std::collections::HashSet;
+
+use mcp_core::Content;
+
+use crate::config::Config;
+use crate::message::{Message, MessageContent};
+
+const DEFAULT_KEEP_RECENT: usize = 6;
+const DEFAULT_MIN_CHARS: usize = 2000;
+const DEFAULT_RATIO: f64 = 0.3;
+
+/// Lines always kept at the start and end of compressed text
+const CONTEXT_LINES: usize = 3;
+
+/// Words that mark a line as worth keeping regardless of its score
+const SALIENT_MARKERS: &[&str] = &[
+    "error",
+    "warning",
+    "fail",
+    "panic",
+    "exception",
+    "traceback",
+    "denied",
+    "not found",
+    "todo",
+    "fixme",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptCompression {
+    /// Number of most recent messages that are never compressed
+    pub keep_recent: usize,
+    /// Text shorter than this many characters is left untouched
+    pub min_chars: usize,
+    /// Target fraction of the original characters to keep
+    pub ratio: f64,
+}
+
+impl Default for PromptCompression {
+    fn default() -> Self {
+        Self {
+            keep_recent: DEFAULT_KEEP_RECENT,
+            min_chars: DEFAULT_MIN_CHARS,
+            ratio: DEFAULT_RATIO,
+        }
+    }
+}
+
+impl PromptCompression {
+    /// Read the compression settings, returning None unless compression is enabled
+    ///
+    /// - GOOSE_PROMPT_COMPRESSION: set to true to enable
+    /// - GOOSE_PROMPT_COMPRESSION_KEEP_RECENT: messages left uncompressed (default 6)
+    /// - GOOSE_PROMPT_COMPRESSION_MIN_CHARS: minimum size of text to compress (default 2000)
+    /// - GOOSE_PROMPT_COMPRESSION_RATIO: fraction of the text to keep (default 0.3)
+    pub fn from_config() -> Option<Self> {
+        let config = Config::global();
+        if !config
+            .get_param::<bool>("GOOSE_PROMPT_COMPRESSION")
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        Some(Self {
+            keep_recent: config
+                .get_param("GOOSE_PROMPT_COMPRESSION_KEEP_RECENT")
+                .unwrap_or(DEFAULT_KEEP_RECENT),
+            min_chars: config
+                .get_param("GOOSE_PROMPT_COMPRESSION_MIN_CHARS")
+                .unwrap_or(DEFAULT_MIN_CHARS),
+            ratio: config
+                .get_param::<f64>("GOOSE_PROMPT_COMPRESSION_RATIO")
+                .unwrap_or(DEFAULT_RATIO)
+                .clamp(0.05, 1.0),
+        })
+    }
+
+    /// Compress the text and tool output of all but the most recent messages
+    pub fn compress_messages(&self, messages: &[Message]) -> Vec<Message> {
+        let cutoff = messages.len().saturating_sub(self.keep_recent);
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                if i >= cutoff {
+                    return message.clone();
+                }
+                let mut message = message.clone();
+                for content in message.content.iter_mut() {
+                    match content {
+                        MessageContent::Text(text) => {
+                            if let Some(compressed) = self.compress_text(&text.text) {
+                                text.text = compressed;
+                            }
+                        }
+                        MessageContent::ToolResponse(response) => {
+                            if let Ok(contents) = response.tool_result.as_mut() {
+                                for item in contents.iter_mut() {
+                                    if let Content::Text(text) = item {
+                                        if let Some(compressed) = self.compress_text(&text.text) {
+                                            text.text = compressed;
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                message
+            })
+            .collect()
+    }
+
+    /// Reduce text to its most salient lines, or None if it is too short to be worth it
+    pub fn compress_text(&self, text: &str) -> Option<String> {
+        if text.len() < self.min_chars {
+            return None;
+        }
+
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.len() <= CONTEXT_LINES * 2 + 1 {
+            return None;
+        }
+
+        let budget = (text.len() as f64 * self.ratio) as usize;
+        let mut keep = vec![false; lines.len()];
+        let mut used = 0;
+
+        // Always keep the surrounding context and anything that looks important
+        for (i, line) in lines.iter().enumerate() {
+            if i < CONTEXT_LINES || i >= lines.len() - CONTEXT_LINES || is_salient(line) {
+                keep[i] = true;
+                used += line.len() + 1;
+            }
+        }
+
+        // Fill the rest of the budget with the most information dense lines, skipping repeats
+        let mut seen = HashSet::new();
+        let mut candidates: Vec<(usize, f64)> = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, line)| !keep[*i] && seen.insert(line.trim()))
+            .map(|(i, line)| (i, density(line)))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (i, _) in candidates {
+            if used + lines[i].len() + 1 > budget {
+                continue;
+            }
+            keep[i] = true;
+            used += lines[i].len() + 1;
+        }
+
+        let mut compressed = String::with_capacity(used);
+        let mut omitted = 0;
+        for (line, kept) in lines.iter().zip(&keep) {
+            if *kept {
+                if omitted > 0 {
+                    compressed.push_str(&omitted_marker(omitted));
+                    omitted = 0;
+                }
+                compressed.push_str(line);
+                compressed.push('\n');
+            } else {
+                omitted += 1;
+            }
+        }
+        if omitted > 0 {
+            compressed.push_str(&omitted_marker(omitted));
+        }
+
+        // Only use the compressed text if it actually saves something
+        (compressed.len() < text.len()).then_some(compressed)
+    }
+}
+
+fn omitted_marker(count: usize) -> String {
+    format!(
+        "[... {} line{} omitted ...]\n",
+        count,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+fn is_salient(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    SALIENT_MARKERS.iter().any(|marker| lower.contains(marker))
+}
+
+/// Score a line by how many distinct words it carries, favouring lines with identifiers,
+/// paths and numbers over blank lines, separators and boilerplate
+fn density(line: &str) -> f64 {
+    let words: Vec<&str> = line
+        .split(|c: char| !c.is_alphanumeric() && c != '_' && c != '/' && c != '.')
+        .filter(|word| word.len() > 1)
+        .collect();
+    if words.is_empty() {
+        return 0.0;
+    }
+    let distinct: HashSet<&str> = words.iter().copied().collect();
+    let technical = words
+        .iter()
+        .filter(|word| {
+            word.contains('/')
+                || word.contains('_')
+                || word.chars().any(|c| c.is_ascii_digit())
+                || word.chars().skip(1).any(|c| c.is_uppercase())
+        })
+        .count();
+    distinct.len() as f64 + technical as f64 * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long_output() -> String {
+        let mut lines = vec!["running 120 tests".to_string()];
+        for i in 0..100 {
+            lines.push(format!("test module_{i}::checks_value ... ok"));
+        }
+        lines.push("error[E0308]: mismatched types in src/config.rs:42".to_string());
+        for _ in 0..50 {
+            lines.push("----------------------------------------".to_string());
+        }
+        lines.push("test result: FAILED. 119 passed; 1 failed".to_string());
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_compress_text_keeps_salient_lines() {
+        let compression = PromptCompression::default();
+        let text = long_output();
+        let compressed = compression.compress_text(&text).unwrap();
+
+        assert!(compressed.len() < text.len() / 2);
+        assert!(compressed.starts_with("running 120 tests\n"));
+        assert!(compressed.contains("error[E0308]: mismatched types in src/config.rs:42"));
+        assert!(compressed.contains("test result: FAILED. 119 passed; 1 failed"));
+        assert!(compressed.contains("omitted ...]"));
+    }
+
+    #[test]
+    fn test_short_text_is_untouched() {
+        let compression = PromptCompression::default();
+        assert_eq!(compression.compress_text("just a short answer"), None);
+    }
+
+    #[test]
+    fn test_recent_messages_are_not_compressed() {
+        let compression = PromptCompression {
+            keep_recent: 1,
+            ..Default::default()
+        };
+        let text = long_output();
+        let messages = vec![
+            Message::user().with_tool_response("1", Ok(vec![Content::text(text.clone())])),
+            Message::assistant().with_text(text.clone()),
+        ];
+
+        let compressed = compression.compress_messages(&messages);
+        let MessageContent::ToolResponse(response) = &compressed[0].content[0] else {
+            panic!("expected a tool response");
+        };
+        let output = response.tool_result.as_ref().unwrap()[0].as_text().unwrap();
+        assert!(output.len() < text.len());
+        assert_eq!(compressed[1].as_concat_text(), text);
+    }
+}
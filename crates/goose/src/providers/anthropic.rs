@@ -1,14 +1,16 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use axum::http::HeaderMap;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use serde_json::Value;
 use std::time::Duration;
 
+use super::api_keys::ApiKeys;
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
 use super::errors::ProviderError;
 use super::formats::anthropic::{create_request, get_usage, response_to_message};
-use super::utils::{emit_debug_trace, get_model};
+use super::http::HttpClient;
+use super::utils::{emit_debug_trace, get_model, handle_response_openai_compat, parse_model_list};
 use crate::message::Message;
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
@@ -27,9 +29,10 @@ pub const ANTHROPIC_DOC_URL: &str = "https://docs.anthropic.com/en/docs/about-cl
 #[derive(serde::Serialize)]
 pub struct AnthropicProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
-    api_key: String,
+    #[serde(skip)]
+    api_keys: ApiKeys,
     model: ModelConfig,
 }
 
@@ -43,19 +46,17 @@ impl Default for AnthropicProvider {
 impl AnthropicProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("ANTHROPIC_API_KEY")?;
+        let api_keys = ApiKeys::from_config("ANTHROPIC_API_KEY")?;
         let host: String = config
             .get_param("ANTHROPIC_HOST")
             .unwrap_or_else(|_| "https://api.anthropic.com".to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("ANTHROPIC", Duration::from_secs(600))?;
 
         Ok(Self {
             client,
             host,
-            api_key,
+            api_keys,
             model,
         })
     }
@@ -68,11 +69,13 @@ impl AnthropicProvider {
         })?;
 
         let response = self
-            .client
-            .post(url)
-            .headers(headers)
-            .json(&payload)
-            .send()
+            .api_keys
+            .send(|key| {
+                self.client
+                    .post_json(url.clone(), &payload)
+                    .headers(headers.clone())
+                    .header("x-api-key", key)
+            })
             .await?;
 
         let status = response.status();
@@ -158,7 +161,6 @@ impl Provider for AnthropicProvider {
         let payload = create_request(&self.model, system, messages, tools)?;
 
         let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert("x-api-key", self.api_key.parse().unwrap());
         headers.insert("anthropic-version", "2023-06-01".parse().unwrap());
 
         let is_thinking_enabled = std::env::var("CLAUDE_THINKING_ENABLED").is_ok();
@@ -186,4 +188,24 @@ impl Provider for AnthropicProvider {
         emit_debug_trace(&self.model, &payload, &response, &usage);
         Ok((message, ProviderUsage::new(model, usage)))
     }
+
+    async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+        let base_url = url::Url::parse(&self.host)
+            .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
+        let url = base_url.join("v1/models?limit=1000").map_err(|e| {
+            ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
+        })?;
+
+        let response = self
+            .api_keys
+            .send(|key| {
+                self.client
+                    .get(url.clone())
+                    .header("x-api-key", key)
+                    .header("anthropic-version", "2023-06-01")
+            })
+            .await?;
+        let data = handle_response_openai_compat(response).await?;
+        Ok(Some(parse_model_list(&data, "data", "id")?))
+    }
 }
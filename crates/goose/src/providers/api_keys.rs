@@ -0,0 +1,233 @@
+//! API keys with backups, so rotating a key doesn't take down long running sessions
+//!
+//! Providers read their key from `{NAME}` (e.g. `OPENAI_API_KEY`) as before, and optional
+//! backup keys from `{NAME}_BACKUPS`, either a list or a comma separated string. When the
+//! provider rejects a key with a 401 or 403, the key is marked bad and the request is
+//! retried with the next one, which is then used for all later requests. Each switch is
+//! logged and published to subscribers of [`key_rotation_events`].
+use once_cell::sync::Lazy;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::broadcast;
+
+use super::errors::ProviderError;
+use super::middleware;
+use crate::config::Config;
+
+static KEY_ROTATION_EVENTS: Lazy<broadcast::Sender<KeyRotationEvent>> =
+    Lazy::new(|| broadcast::channel(16).0);
+
+/// Published when a provider switches to a backup key after its current key was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyRotationEvent {
+    /// The config key the keys were read from, e.g. "OPENAI_API_KEY"
+    pub key_name: String,
+    /// The status the provider rejected the key with
+    pub status: u16,
+    /// Position of the rejected key, 0 being the primary key
+    pub rejected_index: usize,
+    /// Position of the key now in use
+    pub next_index: usize,
+}
+
+/// Subscribe to key rotations across all providers
+pub fn key_rotation_events() -> broadcast::Receiver<KeyRotationEvent> {
+    KEY_ROTATION_EVENTS.subscribe()
+}
+
+/// A provider's primary API key followed by its backups, tried in order
+pub struct ApiKeys {
+    name: String,
+    keys: Vec<String>,
+    current: AtomicUsize,
+}
+
+impl std::fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeys")
+            .field("name", &self.name)
+            .field("keys", &self.keys.len())
+            .field("current", &self.current.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl ApiKeys {
+    pub fn new(name: impl Into<String>, keys: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            keys,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    /// Load the primary key from the named secret and any backups from `{name}_BACKUPS`
+    pub fn from_config(name: &str) -> anyhow::Result<Self> {
+        let config = Config::global();
+        let mut keys = vec![config.get_secret::<String>(name)?];
+        if let Ok(backups) = config.get_secret::<Value>(&format!("{name}_BACKUPS")) {
+            keys.extend(parse_backups(backups));
+        }
+        Ok(Self::new(name, keys))
+    }
+
+    /// The key currently in use
+    pub fn current(&self) -> Option<&str> {
+        self.keys
+            .get(self.current.load(Ordering::SeqCst))
+            .map(String::as_str)
+    }
+
+    /// Send the request built with the current key, switching to the next key and
+    /// retrying if it is rejected
+    ///
+    /// Once there are no backups left the rejection is returned as is, so the last key is
+    /// never marked bad and a transient auth failure doesn't leave the provider without one.
+    pub async fn send(
+        &self,
+        request: impl Fn(&str) -> RequestBuilder,
+    ) -> Result<Response, ProviderError> {
+        loop {
+            let index = self.current.load(Ordering::SeqCst);
+            let key = self.keys.get(index).ok_or_else(|| {
+                ProviderError::Authentication(format!("{} is not configured", self.name))
+            })?;
+            let response = middleware::send(request(key)).await?;
+            let status = response.status();
+            if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+                && self.reject(index, status)
+            {
+                continue;
+            }
+            return Ok(response);
+        }
+    }
+
+    /// Mark the key at index bad, returning whether there is another key to try
+    fn reject(&self, index: usize, status: StatusCode) -> bool {
+        let next_index = index + 1;
+        if next_index >= self.keys.len() {
+            return false;
+        }
+        // Concurrent requests may all be rejected with the same key, only the first switches
+        if self
+            .current
+            .compare_exchange(index, next_index, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            tracing::warn!(
+                key_name = %self.name,
+                status = status.as_u16(),
+                "{} key {} was rejected, switching to backup key {}",
+                self.name,
+                index,
+                next_index
+            );
+            let _ = KEY_ROTATION_EVENTS.send(KeyRotationEvent {
+                key_name: self.name.clone(),
+                status: status.as_u16(),
+                rejected_index: index,
+                next_index,
+            });
+        }
+        true
+    }
+}
+
+fn parse_backups(value: Value) -> Vec<String> {
+    match value {
+        Value::Array(keys) => keys
+            .into_iter()
+            .filter_map(|key| key.as_str().map(str::to_string))
+            .collect(),
+        Value::String(keys) => keys
+            .split(',')
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn server_accepting(key: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("Authorization", format!("Bearer {key}").as_str()))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_rejected_key_rotates_to_backup() {
+        let server = server_accepting("backup").await;
+        let client = reqwest::Client::new();
+        let keys = ApiKeys::new(
+            "ROTATION_TEST_API_KEY",
+            vec!["revoked".to_string(), "backup".to_string()],
+        );
+        let mut events = key_rotation_events();
+
+        let send = || keys.send(|key| client.post(server.uri()).bearer_auth(key));
+        assert_eq!(send().await.unwrap().status(), StatusCode::OK);
+        assert_eq!(keys.current(), Some("backup"));
+        assert_eq!(send().await.unwrap().status(), StatusCode::OK);
+
+        // Only the first request tried the revoked key
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        let event = loop {
+            let event = events.recv().await.unwrap();
+            if event.key_name == "ROTATION_TEST_API_KEY" {
+                break event;
+            }
+        };
+        assert_eq!(
+            event,
+            KeyRotationEvent {
+                key_name: "ROTATION_TEST_API_KEY".to_string(),
+                status: 401,
+                rejected_index: 0,
+                next_index: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_last_key_is_kept_when_rejected() {
+        let server = server_accepting("other").await;
+        let client = reqwest::Client::new();
+        let keys = ApiKeys::new("SINGLE_TEST_API_KEY", vec!["only".to_string()]);
+
+        let response = keys
+            .send(|key| client.post(server.uri()).bearer_auth(key))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(keys.current(), Some("only"));
+    }
+
+    #[test]
+    fn test_parse_backups() {
+        assert_eq!(
+            parse_backups(json!("key-2, key-3,")),
+            vec!["key-2".to_string(), "key-3".to_string()]
+        );
+        assert_eq!(
+            parse_backups(json!(["key-2", "key-3"])),
+            vec!["key-2".to_string(), "key-3".to_string()]
+        );
+    }
+}
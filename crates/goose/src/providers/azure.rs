@@ -1,12 +1,13 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
 use serde_json::Value;
 use std::time::Duration;
 
+use super::azureauth::{AzureAuth, DEFAULT_AUTHORITY_HOST};
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
 use super::errors::ProviderError;
 use super::formats::openai::{create_request, get_usage, response_to_message};
+use super::http::HttpClient;
 use super::utils::{emit_debug_trace, get_model, handle_response_openai_compat, ImageFormat};
 use crate::message::Message;
 use crate::model::ModelConfig;
@@ -21,9 +22,10 @@ pub const AZURE_OPENAI_KNOWN_MODELS: &[&str] = &["gpt-4o", "gpt-4o-mini", "gpt-4
 #[derive(Debug, serde::Serialize)]
 pub struct AzureProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     endpoint: String,
-    api_key: String,
+    #[serde(skip)]
+    auth: AzureAuth,
     deployment_name: String,
     api_version: String,
     model: ModelConfig,
@@ -39,21 +41,19 @@ impl Default for AzureProvider {
 impl AzureProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("AZURE_OPENAI_API_KEY")?;
+        let auth = AzureAuth::from_config()?;
         let endpoint: String = config.get_param("AZURE_OPENAI_ENDPOINT")?;
         let deployment_name: String = config.get_param("AZURE_OPENAI_DEPLOYMENT_NAME")?;
         let api_version: String = config
             .get_param("AZURE_OPENAI_API_VERSION")
             .unwrap_or_else(|_| AZURE_DEFAULT_API_VERSION.to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("AZURE_OPENAI", Duration::from_secs(600))?;
 
         Ok(Self {
             client,
             endpoint,
-            api_key,
+            auth,
             deployment_name,
             api_version,
             model,
@@ -82,11 +82,8 @@ impl AzureProvider {
         base_url.set_query(Some(&format!("api-version={}", self.api_version)));
 
         let response: reqwest::Response = self
-            .client
-            .post(base_url)
-            .header("api-key", &self.api_key)
-            .json(&payload)
-            .send()
+            .auth
+            .send(|| self.client.post_json(base_url.clone(), &payload))
             .await?;
 
         handle_response_openai_compat(response).await
@@ -111,6 +108,16 @@ impl Provider for AzureProvider {
                 ConfigKey::new("AZURE_OPENAI_ENDPOINT", true, false, None),
                 ConfigKey::new("AZURE_OPENAI_DEPLOYMENT_NAME", true, false, None),
                 ConfigKey::new("AZURE_OPENAI_API_VERSION", true, false, Some("2024-10-21")),
+                ConfigKey::new("AZURE_OPENAI_AUTH_TYPE", false, false, Some("api_key")),
+                ConfigKey::new("AZURE_TENANT_ID", false, false, None),
+                ConfigKey::new("AZURE_CLIENT_ID", false, false, None),
+                ConfigKey::new("AZURE_CLIENT_SECRET", false, true, None),
+                ConfigKey::new(
+                    "AZURE_AUTHORITY_HOST",
+                    false,
+                    false,
+                    Some(DEFAULT_AUTHORITY_HOST),
+                ),
             ],
         )
     }
@@ -0,0 +1,514 @@
+//! Authentication for Azure OpenAI, with either an API key or Microsoft Entra ID tokens
+//!
+//! `AZURE_OPENAI_AUTH_TYPE` picks the method:
+//! - `api_key` (default): the `AZURE_OPENAI_API_KEY` secret, sent as the api-key header, with
+//!   backup keys from `AZURE_OPENAI_API_KEY_BACKUPS`
+//! - `client_secret`: a service principal, from `AZURE_TENANT_ID`, `AZURE_CLIENT_ID` and
+//!   the `AZURE_CLIENT_SECRET` secret
+//! - `managed_identity`: the identity of the Azure VM, container or app service goose runs
+//!   on, optionally a user assigned one selected with `AZURE_CLIENT_ID`
+//! - `device_code`: interactive sign in on another device, for the public client app
+//!   `AZURE_CLIENT_ID` in `AZURE_TENANT_ID` (defaults to "organizations")
+//!
+//! Entra ID tokens are cached and refreshed shortly before they expire.
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+use super::api_keys::ApiKeys;
+use super::client_credentials::ClientCredentials;
+use super::errors::ProviderError;
+use super::http::HttpClient;
+use super::middleware;
+use crate::config::Config;
+
+pub(crate) const DEFAULT_AUTHORITY_HOST: &str = "https://login.microsoftonline.com";
+const COGNITIVE_SERVICES_RESOURCE: &str = "https://cognitiveservices.azure.com";
+const COGNITIVE_SERVICES_SCOPE: &str = "https://cognitiveservices.azure.com/.default";
+const IMDS_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Tokens are refreshed this long before they expire
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Managed identity endpoints return this as a string
+    #[serde(default, deserialize_with = "deserialize_seconds")]
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    message: String,
+    #[serde(deserialize_with = "deserialize_seconds")]
+    expires_in: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_seconds")]
+    interval: Option<u64>,
+}
+
+fn deserialize_seconds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_u64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+#[derive(Debug)]
+enum EntraCredential {
+    ManagedIdentity {
+        endpoint: String,
+        /// Secret header value required by app service identity endpoints
+        identity_header: Option<String>,
+        client_id: Option<String>,
+    },
+    DeviceCode {
+        authority: String,
+        client_id: String,
+        /// Obtained from the first sign in, so later tokens don't need user interaction
+        refresh_token: Mutex<Option<String>>,
+    },
+}
+
+#[derive(Debug)]
+enum AuthMethod {
+    ApiKey(ApiKeys),
+    ClientSecret(ClientCredentials),
+    Entra {
+        credential: EntraCredential,
+        client: HttpClient,
+        cached_token: RwLock<Option<CachedToken>>,
+    },
+}
+
+/// The credentials requests to Azure OpenAI are authenticated with
+#[derive(Debug)]
+pub struct AzureAuth(AuthMethod);
+
+impl AzureAuth {
+    pub fn from_config() -> anyhow::Result<Self> {
+        let config = Config::global();
+        let auth_type: String = config
+            .get_param("AZURE_OPENAI_AUTH_TYPE")
+            .unwrap_or_else(|_| "api_key".to_string());
+        let authority_host: String = config
+            .get_param("AZURE_AUTHORITY_HOST")
+            .unwrap_or_else(|_| DEFAULT_AUTHORITY_HOST.to_string());
+        let client_id: Option<String> = config.get_param("AZURE_CLIENT_ID").ok();
+        // Custom headers are meant for the OpenAI endpoint, not for Entra ID
+        let client_for_auth = || {
+            HttpClient::new("AZURE_OPENAI", Duration::from_secs(30))
+                .map(|client| client.with_headers(HeaderMap::new()))
+        };
+
+        match auth_type.as_str() {
+            "api_key" => Ok(Self(AuthMethod::ApiKey(ApiKeys::from_config(
+                "AZURE_OPENAI_API_KEY",
+            )?))),
+            "client_secret" => {
+                let tenant_id: String = config.get_param("AZURE_TENANT_ID")?;
+                let client_id =
+                    client_id.ok_or_else(|| anyhow::anyhow!("AZURE_CLIENT_ID is required"))?;
+                Ok(Self(AuthMethod::ClientSecret(ClientCredentials::new(
+                    format!("{authority_host}/{tenant_id}/oauth2/v2.0/token"),
+                    client_id,
+                    config.get_secret("AZURE_CLIENT_SECRET")?,
+                    Some(COGNITIVE_SERVICES_SCOPE.to_string()),
+                    client_for_auth()?,
+                ))))
+            }
+            "managed_identity" => {
+                // App service and container apps expose their own endpoint, VMs use IMDS
+                let identity_endpoint = std::env::var("IDENTITY_ENDPOINT").ok();
+                let identity_header = std::env::var("IDENTITY_HEADER").ok();
+                Ok(Self::entra(
+                    EntraCredential::ManagedIdentity {
+                        endpoint: identity_endpoint.unwrap_or_else(|| IMDS_ENDPOINT.to_string()),
+                        identity_header,
+                        client_id,
+                    },
+                    client_for_auth()?,
+                ))
+            }
+            "device_code" => {
+                let tenant_id: String = config
+                    .get_param("AZURE_TENANT_ID")
+                    .unwrap_or_else(|_| "organizations".to_string());
+                let client_id =
+                    client_id.ok_or_else(|| anyhow::anyhow!("AZURE_CLIENT_ID is required"))?;
+                Ok(Self::entra(
+                    EntraCredential::DeviceCode {
+                        authority: format!("{authority_host}/{tenant_id}"),
+                        client_id,
+                        refresh_token: Mutex::new(None),
+                    },
+                    client_for_auth()?,
+                ))
+            }
+            other => Err(anyhow::anyhow!(
+                "Unknown AZURE_OPENAI_AUTH_TYPE '{other}', expected api_key, client_secret, managed_identity or device_code"
+            )),
+        }
+    }
+
+    fn entra(credential: EntraCredential, client: HttpClient) -> Self {
+        Self(AuthMethod::Entra {
+            credential,
+            client,
+            cached_token: RwLock::new(None),
+        })
+    }
+
+    /// Send an authenticated request, moving to a backup API key if the current one is rejected
+    pub async fn send(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, ProviderError> {
+        match &self.0 {
+            AuthMethod::ApiKey(api_keys) => {
+                api_keys.send(|key| request().header("api-key", key)).await
+            }
+            _ => middleware::send(self.apply(request()).await?).await,
+        }
+    }
+
+    /// Add the authentication header to a request
+    async fn apply(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        match &self.0 {
+            AuthMethod::ApiKey(api_keys) => Ok(request.header(
+                "api-key",
+                api_keys.current().ok_or_else(|| {
+                    ProviderError::Authentication("AZURE_OPENAI_API_KEY is not configured".into())
+                })?,
+            )),
+            AuthMethod::ClientSecret(credentials) => {
+                Ok(request.bearer_auth(credentials.token().await?))
+            }
+            AuthMethod::Entra {
+                credential,
+                client,
+                cached_token,
+            } => Ok(request.bearer_auth(entra_token(credential, client, cached_token).await?)),
+        }
+    }
+}
+
+/// Get the cached Entra ID token, requesting a new one once it is near expiry
+async fn entra_token(
+    credential: &EntraCredential,
+    client: &HttpClient,
+    cached_token: &RwLock<Option<CachedToken>>,
+) -> Result<String, ProviderError> {
+    if let Some(cached) = cached_token.read().await.as_ref() {
+        if cached.expires_at > Instant::now() {
+            return Ok(cached.access_token.clone());
+        }
+    }
+
+    let mut cached_token = cached_token.write().await;
+    if let Some(cached) = cached_token.as_ref() {
+        if cached.expires_at > Instant::now() {
+            return Ok(cached.access_token.clone());
+        }
+    }
+
+    let token = credential.request_token(client).await?;
+    let lifetime = Duration::from_secs(token.expires_in.unwrap_or(3600));
+    let access_token = token.access_token.clone();
+    *cached_token = Some(CachedToken {
+        access_token: token.access_token,
+        expires_at: Instant::now() + lifetime.saturating_sub(REFRESH_MARGIN),
+    });
+    Ok(access_token)
+}
+
+impl EntraCredential {
+    async fn request_token(&self, client: &HttpClient) -> Result<TokenResponse, ProviderError> {
+        match self {
+            EntraCredential::ManagedIdentity {
+                endpoint,
+                identity_header,
+                client_id,
+            } => {
+                let api_version = if identity_header.is_some() {
+                    "2019-08-01"
+                } else {
+                    "2018-02-01"
+                };
+                let mut query = vec![
+                    ("api-version", api_version),
+                    ("resource", COGNITIVE_SERVICES_RESOURCE),
+                ];
+                if let Some(client_id) = client_id {
+                    query.push(("client_id", client_id.as_str()));
+                }
+                let mut request = client.get(endpoint).query(&query);
+                request = match identity_header {
+                    Some(header) => request.header("X-IDENTITY-HEADER", header),
+                    None => request.header("Metadata", "true"),
+                };
+                parse_token_response(request.send().await?).await
+            }
+            EntraCredential::DeviceCode {
+                authority,
+                client_id,
+                refresh_token,
+            } => {
+                let mut refresh_token = refresh_token.lock().await;
+                let token = match refresh_token.as_deref() {
+                    Some(current) => {
+                        let params = [
+                            ("grant_type", "refresh_token"),
+                            ("client_id", client_id.as_str()),
+                            ("refresh_token", current),
+                            ("scope", COGNITIVE_SERVICES_SCOPE),
+                        ];
+                        let url = format!("{authority}/oauth2/v2.0/token");
+                        match parse_token_response(client.post(url).form(&params).send().await?)
+                            .await
+                        {
+                            Ok(token) => token,
+                            // The refresh token was revoked or expired, sign in again
+                            Err(_) => device_code_sign_in(client, authority, client_id).await?,
+                        }
+                    }
+                    None => device_code_sign_in(client, authority, client_id).await?,
+                };
+                if let Some(new_refresh_token) = &token.refresh_token {
+                    *refresh_token = Some(new_refresh_token.clone());
+                }
+                Ok(token)
+            }
+        }
+    }
+}
+
+/// Ask the user to sign in on another device, then wait for them to finish
+async fn device_code_sign_in(
+    client: &HttpClient,
+    authority: &str,
+    client_id: &str,
+) -> Result<TokenResponse, ProviderError> {
+    let scope = format!("{COGNITIVE_SERVICES_SCOPE} offline_access");
+    let response = client
+        .post(format!("{authority}/oauth2/v2.0/devicecode"))
+        .form(&[("client_id", client_id), ("scope", scope.as_str())])
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(ProviderError::Authentication(format!(
+            "Device code request failed: {body}"
+        )));
+    }
+    let device_code: DeviceCodeResponse = response
+        .json()
+        .await
+        .map_err(|e| ProviderError::Authentication(format!("Invalid device code response: {e}")))?;
+
+    // The message tells the user which URL to visit and the code to enter
+    eprintln!("{}", device_code.message);
+
+    let mut interval = Duration::from_secs(device_code.interval.unwrap_or(5));
+    let deadline = Instant::now() + Duration::from_secs(device_code.expires_in.unwrap_or(900));
+    let token_url = format!("{authority}/oauth2/v2.0/token");
+    let params = [
+        ("grant_type", DEVICE_CODE_GRANT),
+        ("client_id", client_id),
+        ("device_code", device_code.device_code.as_str()),
+    ];
+
+    while Instant::now() < deadline {
+        tokio::time::sleep(interval).await;
+        let response = client.post(&token_url).form(&params).send().await?;
+        if response.status().is_success() {
+            return parse_token_response(response).await;
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<TokenErrorResponse>(&body) {
+            Ok(error) if error.error == "authorization_pending" => {}
+            Ok(error) if error.error == "slow_down" => interval += Duration::from_secs(5),
+            Ok(error) => {
+                return Err(ProviderError::Authentication(format!(
+                    "Device code sign in failed: {}",
+                    error.error_description.unwrap_or(error.error)
+                )))
+            }
+            Err(_) => {
+                return Err(ProviderError::Authentication(format!(
+                    "Device code sign in failed: {body}"
+                )))
+            }
+        }
+    }
+    Err(ProviderError::Authentication(
+        "Device code sign in timed out".to_string(),
+    ))
+}
+
+async fn parse_token_response(response: reqwest::Response) -> Result<TokenResponse, ProviderError> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(ProviderError::Authentication(format!(
+            "Entra ID token request failed with status {status}: {body}"
+        )));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| ProviderError::Authentication(format!("Invalid token response: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_client() -> HttpClient {
+        HttpClient::with_client(reqwest::Client::new(), Duration::from_secs(5))
+    }
+
+    async fn authorization(auth: &AzureAuth) -> String {
+        let request = auth
+            .apply(reqwest::Client::new().get("http://localhost"))
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        request.headers()["authorization"]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_api_key() {
+        let auth = AzureAuth(AuthMethod::ApiKey(ApiKeys::new(
+            "AZURE_OPENAI_API_KEY",
+            vec!["secret".to_string()],
+        )));
+        let request = auth
+            .apply(reqwest::Client::new().get("http://localhost"))
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["api-key"], "secret");
+    }
+
+    #[tokio::test]
+    async fn test_managed_identity_token_is_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/metadata/identity/oauth2/token"))
+            .and(header("Metadata", "true"))
+            .and(query_param("resource", COGNITIVE_SERVICES_RESOURCE))
+            .and(query_param("client_id", "user-assigned"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "mi-token",
+                "expires_in": "3599",
+                "token_type": "Bearer"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let auth = AzureAuth::entra(
+            EntraCredential::ManagedIdentity {
+                endpoint: format!("{}/metadata/identity/oauth2/token", server.uri()),
+                identity_header: None,
+                client_id: Some("user-assigned".to_string()),
+            },
+            test_client(),
+        );
+        assert_eq!(authorization(&auth).await, "Bearer mi-token");
+        assert_eq!(authorization(&auth).await, "Bearer mi-token");
+    }
+
+    #[tokio::test]
+    async fn test_device_code_flow_then_refresh() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/tenant/oauth2/v2.0/devicecode"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "device_code": "device-123",
+                "user_code": "ABCD-EFGH",
+                "verification_uri": "https://microsoft.com/devicelogin",
+                "expires_in": 900,
+                "interval": 0,
+                "message": "To sign in, enter the code ABCD-EFGH"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // The user hasn't finished signing in on the first poll
+        Mock::given(method("POST"))
+            .and(path("/tenant/oauth2/v2.0/token"))
+            .and(body_string_contains("device_code=device-123"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "authorization_pending"
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/tenant/oauth2/v2.0/token"))
+            .and(body_string_contains("device_code=device-123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "device-token",
+                "refresh_token": "refresh-1",
+                "expires_in": 30
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/tenant/oauth2/v2.0/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=refresh-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "refreshed-token",
+                "refresh_token": "refresh-2",
+                "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let auth = AzureAuth::entra(
+            EntraCredential::DeviceCode {
+                authority: format!("{}/tenant", server.uri()),
+                client_id: "public-client".to_string(),
+                refresh_token: Mutex::new(None),
+            },
+            test_client(),
+        );
+        assert_eq!(authorization(&auth).await, "Bearer device-token");
+        // The first token expires within the refresh margin, so the refresh token is used
+        assert_eq!(authorization(&auth).await, "Bearer refreshed-token");
+    }
+}
@@ -1,12 +1,30 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+use super::dedupe;
 use super::errors::ProviderError;
+use super::http::with_request_timeout;
 use crate::message::Message;
+use crate::model::pricing;
+use crate::model::registry::ModelCapabilities;
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
+use std::fmt;
+use std::time::Duration;
 use utoipa::ToSchema;
 
+/// How long a health check waits for the provider before reporting it unreachable
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fragments of request errors that mean the provider's host couldn't be reached
+const UNREACHABLE_ERRORS: &[&str] = &[
+    "error sending request",
+    "connection refused",
+    "dns error",
+    "failed to lookup address",
+    "timed out",
+];
+
 /// Metadata about a provider's configuration requirements and capabilities
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProviderMetadata {
@@ -84,11 +102,15 @@ impl ConfigKey {
 pub struct ProviderUsage {
     pub model: String,
     pub usage: Usage,
+    /// Estimated cost in USD, None if the model's price is unknown
+    #[serde(default)]
+    pub cost: Option<f64>,
 }
 
 impl ProviderUsage {
     pub fn new(model: String, usage: Usage) -> Self {
-        Self { model, usage }
+        let cost = pricing::cost(&model, &usage);
+        Self { model, usage, cost }
     }
 }
 
@@ -97,6 +119,9 @@ pub struct Usage {
     pub input_tokens: Option<i32>,
     pub output_tokens: Option<i32>,
     pub total_tokens: Option<i32>,
+    /// Input tokens read from the provider's prompt cache, included in input_tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input_tokens: Option<i32>,
 }
 
 impl Usage {
@@ -109,6 +134,70 @@ impl Usage {
             input_tokens,
             output_tokens,
             total_tokens,
+            cached_input_tokens: None,
+        }
+    }
+
+    pub fn with_cached_input_tokens(mut self, cached_input_tokens: Option<i32>) -> Self {
+        self.cached_input_tokens = cached_input_tokens;
+        self
+    }
+}
+
+/// The outcome of a provider health check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status", content = "message", rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// The provider accepted the credentials and serves the configured model
+    Healthy,
+    /// The provider rejected the API key or other credentials
+    InvalidCredentials(String),
+    /// The provider's host couldn't be reached
+    Unreachable(String),
+    /// The provider doesn't serve the configured model
+    ModelNotFound(String),
+    /// The check failed for another reason
+    Unhealthy(String),
+}
+
+impl HealthStatus {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, HealthStatus::Healthy)
+    }
+
+    /// Classify the error a provider request failed with
+    pub fn from_error(error: &ProviderError) -> Self {
+        let message = error.to_string();
+        let lower = message.to_lowercase();
+        if matches!(error, ProviderError::Authentication(_)) {
+            HealthStatus::InvalidCredentials(message)
+        } else if lower.contains("model")
+            && ["not found", "not_found", "does not exist", "unknown model"]
+                .iter()
+                .any(|fragment| lower.contains(fragment))
+        {
+            HealthStatus::ModelNotFound(message)
+        } else if UNREACHABLE_ERRORS
+            .iter()
+            .any(|fragment| lower.contains(fragment))
+        {
+            HealthStatus::Unreachable(message)
+        } else {
+            HealthStatus::Unhealthy(message)
+        }
+    }
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthStatus::Healthy => write!(f, "Provider is reachable and the model is available"),
+            HealthStatus::InvalidCredentials(message) => {
+                write!(f, "Invalid API key or credentials: {}", message)
+            }
+            HealthStatus::Unreachable(message) => write!(f, "Host unreachable: {}", message),
+            HealthStatus::ModelNotFound(message) => write!(f, "Model not found: {}", message),
+            HealthStatus::Unhealthy(message) => write!(f, "{}", message),
         }
     }
 }
@@ -143,8 +232,85 @@ pub trait Provider: Send + Sync {
         tools: &[Tool],
     ) -> Result<(Message, ProviderUsage), ProviderError>;
 
+    /// Generate the next message like [`Provider::complete`], but with a deadline that
+    /// overrides the provider's configured timeout
+    ///
+    /// The timeout applies to each HTTP request and bounds the whole call, including any
+    /// retries, so short classification calls fail fast and long reasoning runs can take
+    /// longer than usual.
+    async fn complete_with_timeout(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+        timeout: Duration,
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        let completion = with_request_timeout(timeout, self.complete(system, messages, tools));
+        tokio::time::timeout(timeout, completion)
+            .await
+            .map_err(|_| {
+                ProviderError::RequestFailed(format!(
+                    "Request timed out after {} seconds",
+                    timeout.as_secs_f64()
+                ))
+            })?
+    }
+
+    /// Generate the next message like [`Provider::complete`], with requests that carry an
+    /// idempotency key so gateways that support it don't bill or execute a retried turn twice
+    async fn complete_deduplicated(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        let model = self.get_model_config().model_name;
+        let key = dedupe::idempotency_key(&model, system, messages, tools);
+        dedupe::with_idempotency_key(key, self.complete(system, messages, tools)).await
+    }
+
     /// Get the model config from the provider
     fn get_model_config(&self) -> ModelConfig;
+
+    /// Get the features the configured model supports through this provider
+    ///
+    /// Defaults to the model registry, providers should override this when their
+    /// API restricts what the model can otherwise do
+    fn capabilities(&self) -> ModelCapabilities {
+        self.get_model_config().capabilities()
+    }
+
+    /// Fetch the models currently offered by the provider's model listing API
+    ///
+    /// Returns None if the provider has no listing API, in which case callers
+    /// should fall back to the known models in the provider metadata
+    async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+        Ok(None)
+    }
+
+    /// Check that the provider is reachable, accepts its credentials and serves the
+    /// configured model, so configuration problems surface at setup time
+    ///
+    /// Pings the model listing API where there is one, and only falls back to a minimal
+    /// completion when the provider has no listing or doesn't list the model, since
+    /// listings often leave out aliases the API accepts.
+    async fn health_check(&self) -> HealthStatus {
+        let model = self.get_model_config().model_name;
+        match with_request_timeout(HEALTH_CHECK_TIMEOUT, self.fetch_supported_models()).await {
+            Ok(Some(models)) if models.contains(&model) => return HealthStatus::Healthy,
+            Ok(_) => {}
+            Err(e) => return HealthStatus::from_error(&e),
+        }
+
+        let messages = [Message::user().with_text("Reply with OK")];
+        match self
+            .complete_with_timeout("", &messages, &[], HEALTH_CHECK_TIMEOUT)
+            .await
+        {
+            Ok(_) => HealthStatus::Healthy,
+            Err(e) => HealthStatus::from_error(&e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -179,4 +345,123 @@ mod tests {
 
         Ok(())
     }
+
+    struct SlowProvider;
+
+    #[async_trait]
+    impl Provider for SlowProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        async fn complete(
+            &self,
+            _system: &str,
+            _messages: &[Message],
+            _tools: &[Tool],
+        ) -> Result<(Message, ProviderUsage), ProviderError> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok((
+                Message::assistant().with_text("done"),
+                ProviderUsage::new("slow".to_string(), Usage::default()),
+            ))
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            ModelConfig::new("slow".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_complete_with_timeout() {
+        let provider = SlowProvider;
+
+        let result = provider
+            .complete_with_timeout("", &[], &[], Duration::from_millis(10))
+            .await;
+        assert!(matches!(result, Err(ProviderError::RequestFailed(_))));
+
+        let (message, _) = provider
+            .complete_with_timeout("", &[], &[], Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(message.as_concat_text(), "done");
+    }
+
+    /// Lists one model and fails completions for any other
+    struct ListingProvider {
+        model: String,
+    }
+
+    #[async_trait]
+    impl Provider for ListingProvider {
+        fn metadata() -> ProviderMetadata {
+            ProviderMetadata::empty()
+        }
+
+        async fn complete(
+            &self,
+            _system: &str,
+            _messages: &[Message],
+            _tools: &[Tool],
+        ) -> Result<(Message, ProviderUsage), ProviderError> {
+            Err(ProviderError::RequestFailed(format!(
+                "The model `{}` does not exist",
+                self.model
+            )))
+        }
+
+        fn get_model_config(&self) -> ModelConfig {
+            ModelConfig::new(self.model.clone())
+        }
+
+        async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+            Ok(Some(vec!["listed".to_string()]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let provider = ListingProvider {
+            model: "listed".to_string(),
+        };
+        assert_eq!(provider.health_check().await, HealthStatus::Healthy);
+
+        let provider = ListingProvider {
+            model: "unlisted".to_string(),
+        };
+        assert!(matches!(
+            provider.health_check().await,
+            HealthStatus::ModelNotFound(_)
+        ));
+    }
+
+    #[test]
+    fn test_health_status_from_error() {
+        assert!(matches!(
+            HealthStatus::from_error(&ProviderError::Authentication("bad key".to_string())),
+            HealthStatus::InvalidCredentials(_)
+        ));
+        assert!(matches!(
+            HealthStatus::from_error(&ProviderError::ExecutionError(
+                "error sending request for url (https://api.example.com/v1/models)".to_string()
+            )),
+            HealthStatus::Unreachable(_)
+        ));
+        assert!(matches!(
+            HealthStatus::from_error(&ProviderError::ServerError("overloaded".to_string())),
+            HealthStatus::Unhealthy(_)
+        ));
+    }
+
+    #[test]
+    fn test_provider_usage_cost() {
+        let usage = Usage::new(Some(1_000_000), Some(1_000_000), Some(2_000_000));
+        let provider_usage = ProviderUsage::new("gpt-4o-2024-08-06".to_string(), usage.clone());
+        let cost = provider_usage.cost.unwrap();
+        assert!((cost - 12.5).abs() < 1e-9);
+
+        let provider_usage = ProviderUsage::new("some-local-model".to_string(), usage);
+        assert_eq!(provider_usage.cost, None);
+    }
 }
@@ -18,6 +18,8 @@ use crate::providers::utils::emit_debug_trace;
 use super::formats::bedrock::{
     from_bedrock_message, from_bedrock_usage, to_bedrock_message, to_bedrock_tool_config,
 };
+use super::formats::outgoing;
+use super::images::BEDROCK_IMAGE_LIMITS;
 
 pub const BEDROCK_DOC_LINK: &str =
     "https://docs.aws.amazon.com/bedrock/latest/userguide/models-supported.html";
@@ -104,6 +106,8 @@ impl Provider for BedrockProvider {
         tools: &[Tool],
     ) -> Result<(Message, ProviderUsage), ProviderError> {
         let model_name = &self.model.model_name;
+        let image_limits = self.model.image_limits().unwrap_or(BEDROCK_IMAGE_LIMITS);
+        let (system, messages) = outgoing(system, messages, &image_limits);
 
         let mut request = self
             .client
@@ -0,0 +1,219 @@
+//! OAuth2 client credentials authentication, for gateways that issue short lived access
+//! tokens from a token endpoint instead of accepting a static API key
+//!
+//! Configured under the provider's key prefix:
+//! - `{PREFIX}_OAUTH_TOKEN_URL`: the token endpoint, enables client credentials auth
+//! - `{PREFIX}_OAUTH_CLIENT_ID` / `{PREFIX}_OAUTH_CLIENT_SECRET` (a secret)
+//! - `{PREFIX}_OAUTH_SCOPE`: optional space separated scopes to request
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use super::errors::ProviderError;
+use super::http::HttpClient;
+use crate::config::Config;
+
+/// Tokens are refreshed this long before they expire, so in-flight requests don't race expiry
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Lifetime assumed for tokens issued without an expires_in
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+/// Fetches and caches access tokens using the OAuth2 client credentials grant
+#[derive(Debug)]
+pub struct ClientCredentials {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    client: HttpClient,
+    cached_token: RwLock<Option<CachedToken>>,
+}
+
+impl ClientCredentials {
+    pub fn new(
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        scope: Option<String>,
+        client: HttpClient,
+    ) -> Self {
+        Self {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+            client,
+            cached_token: RwLock::new(None),
+        }
+    }
+
+    /// Load the client credentials for a provider's config prefix (e.g. "OPENAI"),
+    /// returning None if no token endpoint is configured
+    pub fn from_config(prefix: &str) -> anyhow::Result<Option<Self>> {
+        let config = Config::global();
+        let Ok(token_url) = config.get_param::<String>(&format!("{prefix}_OAUTH_TOKEN_URL")) else {
+            return Ok(None);
+        };
+        let client_id: String = config.get_param(&format!("{prefix}_OAUTH_CLIENT_ID"))?;
+        let client_secret: String = config.get_secret(&format!("{prefix}_OAUTH_CLIENT_SECRET"))?;
+        let scope: Option<String> = config.get_param(&format!("{prefix}_OAUTH_SCOPE")).ok();
+        // The provider's custom headers are for its API and shouldn't reach the token endpoint
+        let client =
+            HttpClient::new(prefix, Duration::from_secs(30))?.with_headers(HeaderMap::new());
+        Ok(Some(Self::new(
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+            client,
+        )))
+    }
+
+    /// Get a valid access token, requesting a new one if the cached token is near expiry
+    pub async fn token(&self) -> Result<String, ProviderError> {
+        if let Some(cached) = self.cached_token.read().await.as_ref() {
+            if cached.expires_at > Instant::now() {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        let mut cached_token = self.cached_token.write().await;
+        // Another task may have refreshed the token while we waited for the lock
+        if let Some(cached) = cached_token.as_ref() {
+            if cached.expires_at > Instant::now() {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        let token = self.request_token().await?;
+        let access_token = token.access_token.clone();
+        *cached_token = Some(token);
+        Ok(access_token)
+    }
+
+    async fn request_token(&self) -> Result<CachedToken, ProviderError> {
+        let mut params = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.scope {
+            params.push(("scope", scope.as_str()));
+        }
+
+        let response = self
+            .client
+            .post(&self.token_url)
+            .form(&params)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ProviderError::Authentication(format!(
+                "Token request to {} failed with status {}: {}",
+                self.token_url, status, body
+            )));
+        }
+
+        let token: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| ProviderError::Authentication(format!("Invalid token response: {}", e)))?;
+        let lifetime = token
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+        Ok(CachedToken {
+            access_token: token.access_token,
+            expires_at: Instant::now() + lifetime.saturating_sub(REFRESH_MARGIN),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn credentials(server: &MockServer) -> ClientCredentials {
+        ClientCredentials::new(
+            format!("{}/oauth/token", server.uri()),
+            "goose".to_string(),
+            "secret".to_string(),
+            Some("llm.read".to_string()),
+            HttpClient::with_client(reqwest::Client::new(), Duration::from_secs(5)),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_token_is_cached_until_near_expiry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .and(body_string_contains("scope=llm.read"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "token-1",
+                "token_type": "Bearer",
+                "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let credentials = credentials(&server);
+        assert_eq!(credentials.token().await.unwrap(), "token-1");
+        assert_eq!(credentials.token().await.unwrap(), "token-1");
+    }
+
+    #[tokio::test]
+    async fn test_short_lived_token_is_refreshed() {
+        let server = MockServer::start().await;
+        // Expiring within the refresh margin means every call needs a new token
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "short-lived",
+                "expires_in": 30
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let credentials = credentials(&server);
+        credentials.token().await.unwrap();
+        credentials.token().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_token_request_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("invalid_client"))
+            .mount(&server)
+            .await;
+
+        let err = credentials(&server).token().await.unwrap_err();
+        assert!(
+            matches!(err, ProviderError::Authentication(msg) if msg.contains("invalid_client"))
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::time::Duration;
@@ -8,6 +8,8 @@ use std::time::Duration;
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
 use super::errors::ProviderError;
 use super::formats::databricks::{create_request, get_usage, response_to_message};
+use super::http::HttpClient;
+use super::middleware;
 use super::oauth;
 use super::utils::{get_model, ImageFormat};
 use crate::config::ConfigError;
@@ -63,7 +65,7 @@ impl DatabricksAuth {
 #[derive(Debug, serde::Serialize)]
 pub struct DatabricksProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
     auth: DatabricksAuth,
     model: ModelConfig,
@@ -98,9 +100,7 @@ impl DatabricksProvider {
 
         let host = host?;
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("DATABRICKS", Duration::from_secs(600))?;
 
         // If we find a databricks token we prefer that
         if let Ok(api_key) = config.get_secret("DATABRICKS_TOKEN") {
@@ -148,13 +148,12 @@ impl DatabricksProvider {
         })?;
 
         let auth_header = self.ensure_auth_header().await?;
-        let response = self
-            .client
-            .post(url)
-            .header("Authorization", auth_header)
-            .json(&payload)
-            .send()
-            .await?;
+        let response = middleware::send(
+            self.client
+                .post_json(url, &payload)
+                .header("Authorization", auth_header),
+        )
+        .await?;
 
         let status = response.status();
         let payload: Option<Value> = response.json().await.ok();
@@ -0,0 +1,68 @@
+//! Idempotency keys for completions
+//!
+//! Each logical completion is identified by a key derived from the model and the full
+//! request, and sent as an `Idempotency-Key` header, so gateways that support it can drop
+//! a retried request, such as a UI retry racing the original, instead of billing and
+//! executing the same turn twice.
+use std::future::Future;
+
+use crate::message::Message;
+use mcp_core::tool::Tool;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+tokio::task_local! {
+    static IDEMPOTENCY_KEY: String;
+}
+
+/// The idempotency key of the completion being made by the current task, if any
+pub fn current_idempotency_key() -> Option<String> {
+    IDEMPOTENCY_KEY.try_with(|key| key.clone()).ok()
+}
+
+/// Derive the idempotency key for a completion request
+pub fn idempotency_key(model: &str, system: &str, messages: &[Message], tools: &[Tool]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for part in [
+        model.to_string(),
+        system.to_string(),
+        serde_json::to_string(messages).unwrap_or_default(),
+        serde_json::to_string(tools).unwrap_or_default(),
+    ] {
+        // Length prefixes keep the boundaries between parts unambiguous
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Run a completion whose requests carry `key`
+pub async fn with_idempotency_key<F: Future>(key: String, completion: F) -> F::Output {
+    IDEMPOTENCY_KEY.scope(key, completion).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_key() {
+        let messages = vec![Message::user().with_text("hello")];
+        let key = idempotency_key("gpt-4o", "system", &messages, &[]);
+        assert_eq!(key, idempotency_key("gpt-4o", "system", &messages, &[]));
+        assert_ne!(
+            key,
+            idempotency_key("gpt-4o-mini", "system", &messages, &[])
+        );
+        assert_ne!(key, idempotency_key("gpt-4o", "system2", &messages, &[]));
+        assert_ne!(key, idempotency_key("gpt-4o", "system", &[], &[]));
+    }
+
+    #[tokio::test]
+    async fn test_requests_carry_the_key() {
+        let key =
+            with_idempotency_key("key".to_string(), async { current_idempotency_key() }).await;
+        assert_eq!(key.as_deref(), Some("key"));
+        assert!(current_idempotency_key().is_none());
+    }
+}
@@ -1,7 +1,8 @@
 use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, Serialize, Deserialize)]
 pub enum ProviderError {
     #[error("Authentication error: {0}")]
     Authentication(String),
@@ -1,7 +1,9 @@
-use crate::message::{Message, MessageContent};
+use crate::message::{AttachmentPart, Message, MessageContent};
 use crate::model::ModelConfig;
 use crate::providers::base::Usage;
 use crate::providers::errors::ProviderError;
+use crate::providers::formats::outgoing;
+use crate::providers::images::ANTHROPIC_IMAGE_LIMITS;
 use anyhow::{anyhow, Result};
 use mcp_core::content::Content;
 use mcp_core::role::Role;
@@ -9,6 +11,15 @@ use mcp_core::tool::{Tool, ToolCall};
 use serde_json::{json, Value};
 use std::collections::HashSet;
 
+/// Attachment types Anthropic's API reads itself, as images or documents
+const ANTHROPIC_FILE_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+];
+
 /// Convert internal Message format to Anthropic's API message specification
 pub fn format_messages(messages: &[Message]) -> Vec<Value> {
     let mut anthropic_messages = Vec::new();
@@ -74,6 +85,22 @@ pub fn format_messages(messages: &[Message]) -> Vec<Value> {
                     }));
                 }
                 MessageContent::Image(_) => continue, // Anthropic doesn't support image content yet
+                MessageContent::Attachment(attachment) => {
+                    content.push(match attachment.render(ANTHROPIC_FILE_TYPES) {
+                        AttachmentPart::Text(text) => json!({
+                            "type": "text",
+                            "text": text
+                        }),
+                        AttachmentPart::File { mime_type, data } => json!({
+                            "type": if mime_type == "application/pdf" { "document" } else { "image" },
+                            "source": {
+                                "type": "base64",
+                                "media_type": mime_type,
+                                "data": data
+                            }
+                        }),
+                    });
+                }
             }
         }
 
@@ -247,7 +274,13 @@ pub fn get_usage(data: &Value) -> Result<Usage> {
 
         let total_tokens = output_tokens.map(|o| total_input_tokens as i32 + o);
 
-        Ok(Usage::new(input_tokens, output_tokens, total_tokens))
+        let cached_input_tokens = usage
+            .get("cache_read_input_tokens")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as i32);
+
+        Ok(Usage::new(input_tokens, output_tokens, total_tokens)
+            .with_cached_input_tokens(cached_input_tokens))
     } else {
         tracing::debug!(
             "Failed to get usage data: {}",
@@ -265,6 +298,8 @@ pub fn create_request(
     messages: &[Message],
     tools: &[Tool],
 ) -> Result<Value> {
+    let (system, messages) = outgoing(system, messages, &ANTHROPIC_IMAGE_LIMITS);
+    let (system, messages): (&str, &[Message]) = (&system, &messages);
     let anthropic_messages = format_messages(messages);
     let tool_specs = format_tools(tools);
     let system_spec = format_system(system);
@@ -275,8 +310,8 @@ pub fn create_request(
     }
 
     // https://docs.anthropic.com/en/docs/about-claude/models/all-models#model-comparison-table
-    // Claude 3.7 supports max output tokens up to 8192
-    let max_tokens = model_config.max_tokens.unwrap_or(8192);
+    // Default to the model's known output limit, Claude 3.7 supports max output tokens up to 8192
+    let max_tokens = model_config.max_output_tokens().unwrap_or(8192);
     let mut payload = json!({
         "model": model_config.model_name,
         "messages": anthropic_messages,
@@ -310,6 +345,13 @@ pub fn create_request(
         }
     }
 
+    if let Some(stop) = &model_config.stop {
+        payload
+            .as_object_mut()
+            .unwrap()
+            .insert("stop_sequences".to_string(), json!(stop));
+    }
+
     // Add thinking parameters for claude-3-7-sonnet model
     let is_thinking_enabled = std::env::var("CLAUDE_THINKING_ENABLED").is_ok();
     if model_config.model_name.starts_with("claude-3-7-sonnet-") && is_thinking_enabled {
@@ -339,6 +381,7 @@ pub fn create_request(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::AttachmentContent;
     use serde_json::json;
 
     #[test]
@@ -374,6 +417,7 @@ mod tests {
         assert_eq!(usage.input_tokens, Some(24)); // 12 + 12 + 0
         assert_eq!(usage.output_tokens, Some(15));
         assert_eq!(usage.total_tokens, Some(39)); // 24 + 15
+        assert_eq!(usage.cached_input_tokens, Some(0));
 
         Ok(())
     }
@@ -515,6 +559,35 @@ mod tests {
         assert_eq!(spec[2]["content"][0]["text"], "How are you?");
     }
 
+    #[test]
+    fn test_attachment_to_anthropic_spec() {
+        let messages = vec![Message::user()
+            .with_text("Summarize these")
+            .with_attachment(AttachmentContent::from_bytes(
+                "notes.md",
+                "text/markdown",
+                b"# Notes",
+            ))
+            .with_attachment(AttachmentContent::from_bytes(
+                "report.pdf",
+                "application/pdf",
+                b"%PDF-1.7",
+            ))];
+
+        let spec = format_messages(&messages);
+
+        let content = spec[0]["content"].as_array().unwrap();
+        assert_eq!(content.len(), 3);
+        assert_eq!(content[1]["type"], "text");
+        assert_eq!(
+            content[1]["text"],
+            "<attachment name=\"notes.md\" type=\"text/markdown\">\n# Notes\n</attachment>"
+        );
+        assert_eq!(content[2]["type"], "document");
+        assert_eq!(content[2]["source"]["media_type"], "application/pdf");
+        assert_eq!(content[2]["source"]["data"], "JVBERi0xLjc=");
+    }
+
     #[test]
     fn test_tools_to_anthropic_spec() {
         let tools = vec![
@@ -34,6 +34,9 @@ pub fn to_bedrock_message_content(content: &MessageContent) -> Result<bedrock::C
         MessageContent::Image(_) => {
             bail!("Image content is not supported by Bedrock provider yet")
         }
+        MessageContent::Attachment(attachment) => {
+            bedrock::ContentBlock::Text(attachment.render_text())
+        }
         MessageContent::Thinking(_) => {
             // Thinking blocks are not supported in Bedrock - skip
             bedrock::ContentBlock::Text("".to_string())
@@ -212,6 +215,7 @@ pub fn from_bedrock_message(message: &bedrock::Message) -> Result<Message> {
         role,
         content,
         created,
+        metadata: Default::default(),
     })
 }
 
@@ -269,6 +273,7 @@ pub fn from_bedrock_usage(usage: &bedrock::TokenUsage) -> Usage {
         input_tokens: Some(usage.input_tokens),
         output_tokens: Some(usage.output_tokens),
         total_tokens: Some(usage.total_tokens),
+        cached_input_tokens: None,
     }
 }
 
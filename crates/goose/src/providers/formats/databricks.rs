@@ -2,6 +2,9 @@ use crate::message::{Message, MessageContent};
 use crate::model::ModelConfig;
 use crate::providers::base::Usage;
 use crate::providers::errors::ProviderError;
+use crate::providers::formats::openai::format_attachment;
+use crate::providers::formats::outgoing;
+use crate::providers::images::ANTHROPIC_IMAGE_LIMITS;
 use crate::providers::utils::{
     convert_image, detect_image_path, is_valid_function_name, load_image_file,
     sanitize_function_name, ImageFormat,
@@ -188,6 +191,10 @@ pub fn format_messages(messages: &[Message], image_format: &ImageFormat) -> Vec<
                         }
                     }));
                 }
+                MessageContent::Attachment(attachment) => {
+                    has_multiple_content = true;
+                    content_array.push(format_attachment(attachment, image_format));
+                }
             }
         }
 
@@ -338,6 +345,7 @@ pub fn response_to_message(response: Value) -> anyhow::Result<Message> {
         role: Role::Assistant,
         created: chrono::Utc::now().timestamp(),
         content,
+        metadata: Default::default(),
     })
 }
 
@@ -421,6 +429,8 @@ pub fn create_request(
     tools: &[Tool],
     image_format: &ImageFormat,
 ) -> anyhow::Result<Value, Error> {
+    let (system, messages) = outgoing(system, messages, &ANTHROPIC_IMAGE_LIMITS);
+    let (system, messages): (&str, &[Message]) = (&system, &messages);
     if model_config.model_name.starts_with("o1-mini") {
         return Err(anyhow!(
             "o1-mini model is not currently supported since Goose uses tool calling and o1-mini does not support it. Please use o1 or o3 models instead."
@@ -500,7 +510,7 @@ pub fn create_request(
 
         // For Claude models with thinking enabled, we need to add max_tokens + budget_tokens
         // Default to 8192 (Claude max output) + budget if not specified
-        let max_completion_tokens = model_config.max_tokens.unwrap_or(8192);
+        let max_completion_tokens = model_config.output_limit().unwrap_or(8192);
         payload.as_object_mut().unwrap().insert(
             "max_tokens".to_string(),
             json!(max_completion_tokens + budget_tokens),
@@ -520,7 +530,7 @@ pub fn create_request(
             .unwrap()
             .insert("temperature".to_string(), json!(2));
     } else {
-        // o1, o3 models currently don't support temperature
+        // o1, o3 models currently don't support temperature or stop sequences
         if !is_o1 && !is_o3 {
             if let Some(temp) = model_config.temperature {
                 payload
@@ -528,10 +538,16 @@ pub fn create_request(
                     .unwrap()
                     .insert("temperature".to_string(), json!(temp));
             }
+            if let Some(stop) = &model_config.stop {
+                payload
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("stop".to_string(), json!(stop));
+            }
         }
 
         // o1 models use max_completion_tokens instead of max_tokens
-        if let Some(tokens) = model_config.max_tokens {
+        if let Some(tokens) = model_config.output_limit() {
             let key = if is_o1 || is_o3 {
                 "max_completion_tokens"
             } else {
@@ -953,6 +969,7 @@ mod tests {
             max_tokens: Some(1024),
             toolshim: false,
             toolshim_model: None,
+            stop: None,
         };
         let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
         let obj = request.as_object().unwrap();
@@ -985,6 +1002,7 @@ mod tests {
             max_tokens: Some(1024),
             toolshim: false,
             toolshim_model: None,
+            stop: None,
         };
         let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
         let obj = request.as_object().unwrap();
@@ -1018,6 +1036,7 @@ mod tests {
             max_tokens: Some(1024),
             toolshim: false,
             toolshim_model: None,
+            stop: None,
         };
         let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
         let obj = request.as_object().unwrap();
@@ -1,7 +1,9 @@
-use crate::message::{Message, MessageContent};
+use crate::message::{AttachmentPart, Message, MessageContent};
 use crate::model::ModelConfig;
 use crate::providers::base::Usage;
 use crate::providers::errors::ProviderError;
+use crate::providers::formats::outgoing;
+use crate::providers::images::GOOGLE_IMAGE_LIMITS;
 use crate::providers::utils::{is_valid_function_name, sanitize_function_name};
 use anyhow::Result;
 use mcp_core::content::Content;
@@ -10,6 +12,9 @@ use mcp_core::tool::{Tool, ToolCall};
 use rand::{distributions::Alphanumeric, Rng};
 use serde_json::{json, Map, Value};
 
+/// Attachment types Gemini reads itself as inline data
+const GOOGLE_FILE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/webp", "application/pdf"];
+
 /// Convert internal Message format to Google's API message specification
 pub fn format_messages(messages: &[Message]) -> Vec<Value> {
     messages
@@ -104,6 +109,17 @@ pub fn format_messages(messages: &[Message]) -> Vec<Value> {
                             }
                         }
                     }
+                    MessageContent::Attachment(attachment) => {
+                        parts.push(match attachment.render(GOOGLE_FILE_TYPES) {
+                            AttachmentPart::Text(text) => json!({"text": text}),
+                            AttachmentPart::File { mime_type, data } => json!({
+                                "inline_data": {
+                                    "mime_type": mime_type,
+                                    "data": data,
+                                }
+                            }),
+                        });
+                    }
 
                     _ => {}
                 }
@@ -207,6 +223,7 @@ pub fn response_to_message(response: Value) -> Result<Message> {
             role,
             created,
             content,
+            metadata: Default::default(),
         });
     }
     let candidate = candidate.unwrap();
@@ -250,6 +267,7 @@ pub fn response_to_message(response: Value) -> Result<Message> {
         role,
         created,
         content,
+        metadata: Default::default(),
     })
 }
 
@@ -286,6 +304,8 @@ pub fn create_request(
     messages: &[Message],
     tools: &[Tool],
 ) -> Result<Value> {
+    let (system, messages) = outgoing(system, messages, &GOOGLE_IMAGE_LIMITS);
+    let (system, messages): (&str, &[Message]) = (&system, &messages);
     let mut payload = Map::new();
     payload.insert(
         "system_instruction".to_string(),
@@ -302,9 +322,12 @@ pub fn create_request(
     if let Some(temp) = model_config.temperature {
         generation_config.insert("temperature".to_string(), json!(temp));
     }
-    if let Some(tokens) = model_config.max_tokens {
+    if let Some(tokens) = model_config.output_limit() {
         generation_config.insert("maxOutputTokens".to_string(), json!(tokens));
     }
+    if let Some(stop) = &model_config.stop {
+        generation_config.insert("stopSequences".to_string(), json!(stop));
+    }
     if !generation_config.is_empty() {
         payload.insert("generationConfig".to_string(), json!(generation_config));
     }
@@ -315,6 +338,7 @@ pub fn create_request(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::AttachmentContent;
     use serde_json::json;
 
     fn set_up_text_message(text: &str, role: Role) -> Message {
@@ -322,6 +346,7 @@ mod tests {
             role,
             created: 0,
             content: vec![MessageContent::text(text.to_string())],
+            metadata: Default::default(),
         }
     }
 
@@ -330,6 +355,7 @@ mod tests {
             role: Role::User,
             created: 0,
             content: vec![MessageContent::tool_request(id.to_string(), Ok(tool_call))],
+            metadata: Default::default(),
         }
     }
 
@@ -341,6 +367,7 @@ mod tests {
                 id.to_string(),
                 Ok(tool_response),
             )],
+            metadata: Default::default(),
         }
     }
 
@@ -383,6 +410,26 @@ mod tests {
         assert_eq!(payload[1]["parts"][0]["text"], "World");
     }
 
+    #[test]
+    fn test_message_to_google_spec_attachment() {
+        let messages = vec![Message::user()
+            .with_text("What's in the report?")
+            .with_attachment(AttachmentContent::from_bytes(
+                "report.pdf",
+                "application/pdf",
+                b"%PDF-1.7",
+            ))];
+        let payload = format_messages(&messages);
+        assert_eq!(
+            payload[0]["parts"][1]["inline_data"]["mime_type"],
+            "application/pdf"
+        );
+        assert_eq!(
+            payload[0]["parts"][1]["inline_data"]["data"],
+            "JVBERi0xLjc="
+        );
+    }
+
     #[test]
     fn test_message_to_google_spec_tool_request_message() {
         let arguments = json!({
@@ -1,6 +1,34 @@
+use std::borrow::Cow;
+
+use super::images::{fit_messages, ImageLimits};
+use crate::message::Message;
+use crate::redaction::Redactor;
+
 pub mod anthropic;
 pub mod bedrock;
 pub mod databricks;
 pub mod gcpvertexai;
 pub mod google;
 pub mod openai;
+
+/// The system prompt and messages as a request sends them, with secrets the agent came across in
+/// files or command output masked and images fitted to `limits`
+pub fn outgoing<'a>(
+    system: &'a str,
+    messages: &'a [Message],
+    limits: &ImageLimits,
+) -> (Cow<'a, str>, Cow<'a, [Message]>) {
+    (
+        Redactor::global().redact(system),
+        outgoing_messages(messages, limits),
+    )
+}
+
+/// The messages as a request sends them, with secrets masked and images fitted to `limits`
+pub fn outgoing_messages<'a>(messages: &'a [Message], limits: &ImageLimits) -> Cow<'a, [Message]> {
+    let messages = Redactor::global().redact_messages(messages);
+    match fit_messages(&messages, limits) {
+        Cow::Owned(fitted) => Cow::Owned(fitted),
+        Cow::Borrowed(_) => messages,
+    }
+}
@@ -1,12 +1,16 @@
-use crate::message::{Message, MessageContent};
+use crate::message::attachment::IMAGE_TYPES;
+use crate::message::{AttachmentContent, AttachmentPart, Message, MessageContent};
 use crate::model::ModelConfig;
 use crate::providers::base::Usage;
 use crate::providers::errors::ProviderError;
+use crate::providers::formats::outgoing;
+use crate::providers::images::OPENAI_IMAGE_LIMITS;
 use crate::providers::utils::{
     convert_image, detect_image_path, is_valid_function_name, load_image_file,
     sanitize_function_name, ImageFormat,
 };
 use anyhow::{anyhow, Error};
+use mcp_core::content::ImageContent;
 use mcp_core::ToolError;
 use mcp_core::{Content, Role, Tool, ToolCall};
 use serde_json::{json, Value};
@@ -22,6 +26,7 @@ pub fn format_messages(messages: &[Message], image_format: &ImageFormat) -> Vec<
         });
 
         let mut output = Vec::new();
+        let mut attachment_parts = Vec::new();
 
         for content in &message.content {
             match content {
@@ -151,9 +156,23 @@ pub fn format_messages(messages: &[Message], image_format: &ImageFormat) -> Vec<
                     // Handle direct image content
                     converted["content"] = json!([convert_image(image, image_format)]);
                 }
+                MessageContent::Attachment(attachment) => {
+                    attachment_parts.push(format_attachment(attachment, image_format));
+                }
             }
         }
 
+        // Attachments follow the message's text, which is sent as a plain string when alone
+        if !attachment_parts.is_empty() {
+            let mut parts = match converted["content"].take() {
+                Value::Null => Vec::new(),
+                Value::Array(parts) => parts,
+                text => vec![json!({"type": "text", "text": text})],
+            };
+            parts.extend(attachment_parts);
+            converted["content"] = json!(parts);
+        }
+
         if converted.get("content").is_some() || converted.get("tool_calls").is_some() {
             output.insert(0, converted);
         }
@@ -163,6 +182,22 @@ pub fn format_messages(messages: &[Message], image_format: &ImageFormat) -> Vec<
     messages_spec
 }
 
+/// Convert an attachment to a content part, as an image if it is one or as text otherwise
+///   OpenAI compatible endpoints differ in which files they accept, but all take images
+pub fn format_attachment(attachment: &AttachmentContent, image_format: &ImageFormat) -> Value {
+    match attachment.render(IMAGE_TYPES) {
+        AttachmentPart::Text(text) => json!({"type": "text", "text": text}),
+        AttachmentPart::File { mime_type, data } => convert_image(
+            &ImageContent {
+                mime_type,
+                data,
+                annotations: None,
+            },
+            image_format,
+        ),
+    }
+}
+
 /// Convert internal Tool format to OpenAI's API tool specification
 pub fn format_tools(tools: &[Tool]) -> anyhow::Result<Vec<Value>> {
     let mut tool_names = std::collections::HashSet::new();
@@ -249,6 +284,7 @@ pub fn response_to_message(response: Value) -> anyhow::Result<Message> {
         role: Role::Assistant,
         created: chrono::Utc::now().timestamp(),
         content,
+        metadata: Default::default(),
     })
 }
 
@@ -276,7 +312,14 @@ pub fn get_usage(data: &Value) -> Result<Usage, ProviderError> {
             _ => None,
         });
 
-    Ok(Usage::new(input_tokens, output_tokens, total_tokens))
+    let cached_input_tokens = usage
+        .get("prompt_tokens_details")
+        .and_then(|details| details.get("cached_tokens"))
+        .and_then(|v| v.as_i64())
+        .map(|v| v as i32);
+
+    Ok(Usage::new(input_tokens, output_tokens, total_tokens)
+        .with_cached_input_tokens(cached_input_tokens))
 }
 
 /// Validates and fixes tool schemas to ensure they have proper parameter structure.
@@ -332,6 +375,10 @@ pub fn create_request(
     tools: &[Tool],
     image_format: &ImageFormat,
 ) -> anyhow::Result<Value, Error> {
+    // OpenAI compatible routers often serve other vendors' models, which take their own limits
+    let image_limits = model_config.image_limits().unwrap_or(OPENAI_IMAGE_LIMITS);
+    let (system, messages) = outgoing(system, messages, &image_limits);
+    let (system, messages): (&str, &[Message]) = (&system, &messages);
     if model_config.model_name.starts_with("o1-mini") {
         return Err(anyhow!(
             "o1-mini model is not currently supported since Goose uses tool calling and o1-mini does not support it. Please use o1 or o3 models instead."
@@ -397,7 +444,7 @@ pub fn create_request(
             .unwrap()
             .insert("tools".to_string(), json!(tools_spec));
     }
-    // o1, o3 models currently don't support temperature
+    // o1, o3 models currently don't support temperature or stop sequences
     if !is_o1 && !is_o3 {
         if let Some(temp) = model_config.temperature {
             payload
@@ -405,10 +452,16 @@ pub fn create_request(
                 .unwrap()
                 .insert("temperature".to_string(), json!(temp));
         }
+        if let Some(stop) = &model_config.stop {
+            payload
+                .as_object_mut()
+                .unwrap()
+                .insert("stop".to_string(), json!(stop));
+        }
     }
 
     // o1 models use max_completion_tokens instead of max_tokens
-    if let Some(tokens) = model_config.max_tokens {
+    if let Some(tokens) = model_config.output_limit() {
         let key = if is_o1 || is_o3 {
             "max_completion_tokens"
         } else {
@@ -709,6 +762,45 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_format_messages_with_attachments() -> anyhow::Result<()> {
+        let message = Message::user()
+            .with_attachment(AttachmentContent::from_bytes(
+                "sales.csv",
+                "text/csv",
+                b"region,total",
+            ))
+            .with_text("Analyze this")
+            .with_attachment(AttachmentContent::from_bytes(
+                "report.pdf",
+                "application/pdf",
+                b"%PDF-1.7",
+            ));
+        let spec = format_messages(&[message], &ImageFormat::OpenAi);
+
+        // The text comes first wherever it is, and PDFs are described rather than sent
+        let content = spec[0]["content"].as_array().unwrap();
+        assert_eq!(content.len(), 3);
+        assert_eq!(content[0], json!({"type": "text", "text": "Analyze this"}));
+        assert!(content[1]["text"]
+            .as_str()
+            .unwrap()
+            .contains("region,total"));
+        assert!(content[2]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("[The attached file report.pdf"));
+
+        let image = Message::user().with_attachment(AttachmentContent::from_bytes(
+            "chart.png",
+            "image/png",
+            &[0x89, 0x50, 0x4E, 0x47],
+        ));
+        let spec = format_messages(&[image], &ImageFormat::OpenAi);
+        assert_eq!(spec[0]["content"][0]["type"], "image_url");
+        Ok(())
+    }
+
     #[test]
     fn test_response_to_message_text() -> anyhow::Result<()> {
         let response = json!({
@@ -737,6 +829,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_usage_with_cached_tokens() -> anyhow::Result<()> {
+        let response = json!({
+            "usage": {
+                "prompt_tokens": 2006,
+                "completion_tokens": 300,
+                "total_tokens": 2306,
+                "prompt_tokens_details": {
+                    "cached_tokens": 1920
+                }
+            }
+        });
+
+        let usage = get_usage(&response)?;
+        assert_eq!(usage.input_tokens, Some(2006));
+        assert_eq!(usage.output_tokens, Some(300));
+        assert_eq!(usage.total_tokens, Some(2306));
+        assert_eq!(usage.cached_input_tokens, Some(1920));
+
+        Ok(())
+    }
+
     #[test]
     fn test_response_to_message_valid_toolrequest() -> anyhow::Result<()> {
         let response: Value = serde_json::from_str(OPENAI_TOOL_USE_RESPONSE)?;
@@ -828,6 +942,7 @@ mod tests {
             max_tokens: Some(1024),
             toolshim: false,
             toolshim_model: None,
+            stop: None,
         };
         let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
         let obj = request.as_object().unwrap();
@@ -849,6 +964,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_create_request_stop_sequences() -> anyhow::Result<()> {
+        let stop = vec!["<|eot_id|>".to_string()];
+        let model_config = ModelConfig::new("gpt-4o".to_string()).with_stop(Some(stop.clone()));
+        let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
+        assert_eq!(request["stop"], json!(stop));
+
+        // Reasoning models reject stop sequences
+        let model_config = ModelConfig::new("o1".to_string()).with_stop(Some(stop));
+        let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
+        assert!(request.get("stop").is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_create_request_o1_default() -> anyhow::Result<()> {
         // Test default medium reasoning effort for O1 model
@@ -860,6 +990,7 @@ mod tests {
             max_tokens: Some(1024),
             toolshim: false,
             toolshim_model: None,
+            stop: None,
         };
         let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
         let obj = request.as_object().unwrap();
@@ -893,6 +1024,7 @@ mod tests {
             max_tokens: Some(1024),
             toolshim: false,
             toolshim_model: None,
+            stop: None,
         };
         let request = create_request(&model_config, "system", &[], &[], &ImageFormat::OpenAi)?;
         let obj = request.as_object().unwrap();
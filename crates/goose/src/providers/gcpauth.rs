@@ -1,12 +1,31 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{env, fmt, io};
 use tokio::sync::RwLock;
 
+use crate::config::Config;
+
+/// Base URL of the GCP metadata server, available to workloads running on GCP
+const METADATA_BASE_URL: &str = "http://metadata.google.internal";
+
+/// Path of the default service account's access token on the metadata server
+const METADATA_TOKEN_PATH: &str = "/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// OAuth scope requested for all tokens
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// How long before expiry the background refresh replaces the cached token
+const BACKGROUND_REFRESH_AHEAD: Duration = Duration::from_secs(300);
+
+/// Minimum wait between background refresh attempts, including after a failure
+const BACKGROUND_REFRESH_MIN_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Represents errors that can occur during GCP authentication.
 ///
 /// This enum encompasses various error conditions that might arise during
@@ -49,7 +68,8 @@ impl fmt::Display for AuthToken {
 /// Represents the types of Application Default Credentials (ADC) supported.
 ///
 /// GCP supports multiple credential types for authentication. This enum
-/// represents the two main types: authorized user and service account.
+/// represents the credential files ADC can point at (authorized user, service
+/// account and workload identity federation), plus the metadata server.
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum AdcCredentials {
@@ -57,6 +77,9 @@ enum AdcCredentials {
     AuthorizedUser(AuthorizedUserCredentials),
     /// Credentials for a service account
     ServiceAccount(ServiceAccountCredentials),
+    /// Workload identity federation credentials, exchanging a token from an external
+    /// identity provider (Kubernetes, GitHub Actions, Azure, any OIDC issuer) for a GCP one
+    ExternalAccount(ExternalAccountCredentials),
     /// Credentials for the GCP native default account
     DefaultAccount(TokenResponse),
 }
@@ -92,11 +115,73 @@ struct ServiceAccountCredentials {
     token_uri: String,
 }
 
+/// Credentials for workload identity federation.
+///
+/// These credentials are generated with `gcloud iam workload-identity-pools
+/// create-cred-config` and describe where to read the external subject token
+/// from, rather than containing a secret themselves.
+#[derive(Debug, Deserialize)]
+struct ExternalAccountCredentials {
+    /// Full resource name of the workload identity pool provider
+    audience: String,
+    /// Type of the external token, e.g. `urn:ietf:params:oauth:token-type:jwt`
+    subject_token_type: String,
+    /// URI of the Security Token Service
+    #[serde(default = "default_sts_token_url")]
+    token_url: String,
+    /// URI to impersonate a service account with the federated token, if configured
+    service_account_impersonation_url: Option<String>,
+    /// Where to read the external subject token from
+    credential_source: CredentialSource,
+}
+
+/// The source of the external subject token for workload identity federation.
+///
+/// Either a file (e.g. a projected Kubernetes service account token) or a URL
+/// (e.g. a local metadata endpoint) holding the token.
+#[derive(Debug, Deserialize)]
+struct CredentialSource {
+    /// Path to a file containing the subject token
+    file: Option<String>,
+    /// URL returning the subject token
+    url: Option<String>,
+    /// Headers to send with the URL request
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// How the token is encoded in the file or response, plain text if absent
+    format: Option<CredentialSourceFormat>,
+}
+
+/// Format of a subject token source.
+#[derive(Debug, Deserialize)]
+struct CredentialSourceFormat {
+    /// Either "text" or "json"
+    #[serde(rename = "type")]
+    format_type: String,
+    /// For JSON sources, the field holding the token
+    subject_token_field_name: Option<String>,
+}
+
+/// Response structure for service account impersonation requests.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImpersonationResponse {
+    /// The impersonated service account's access token
+    access_token: String,
+    /// When the token expires
+    expire_time: DateTime<Utc>,
+}
+
 /// Returns the default OAuth 2.0 token endpoint.
 fn default_token_uri() -> String {
     "https://oauth2.googleapis.com/token".to_string()
 }
 
+/// Returns the default Security Token Service endpoint.
+fn default_sts_token_url() -> String {
+    "https://sts.googleapis.com/v1/token".to_string()
+}
+
 /// A trait that defines operations for interacting with the filesystem.
 ///
 /// This trait provides an abstraction over filesystem operations, primarily
@@ -158,16 +243,33 @@ impl AdcCredentials {
     /// https://cloud.google.com/docs/authentication/application-default-credentials#personal
     ///
     /// Attempts to load credentials in the following order:
-    /// 1. GOOGLE_APPLICATION_CREDENTIALS environment variable
-    /// 2. Default gcloud credentials path (~/.config/gcloud/application_default_credentials.json)
-    /// 3. Metadata server if running in GCP
+    /// 1. GCP_CREDENTIALS_JSON from the goose config or keyring
+    /// 2. GOOGLE_APPLICATION_CREDENTIALS environment variable
+    /// 3. Default gcloud credentials path (~/.config/gcloud/application_default_credentials.json)
+    /// 4. Metadata server if running in GCP
     async fn load() -> Result<Self, AuthError> {
-        Self::load_impl(
-            &RealFilesystemOps,
-            &RealEnvOps,
-            "http://metadata.google.internal",
-        )
-        .await
+        if let Some(creds) = Self::load_from_config()? {
+            return Ok(creds);
+        }
+
+        Self::load_impl(&RealFilesystemOps, &RealEnvOps, METADATA_BASE_URL).await
+    }
+
+    /// Loads a credentials file saved in the goose config, for users who
+    /// configure a service account key through goose rather than gcloud.
+    fn load_from_config() -> Result<Option<Self>, AuthError> {
+        let Ok(value) = Config::global().get_secret::<serde_json::Value>("GCP_CREDENTIALS_JSON")
+        else {
+            return Ok(None);
+        };
+
+        // The JSON may be stored either as an object or as a string containing it
+        let creds = match value {
+            serde_json::Value::String(content) => serde_json::from_str(&content),
+            value => serde_json::from_value(value),
+        }
+        .map_err(|e| AuthError::Credentials(format!("Invalid GCP_CREDENTIALS_JSON: {}", e)))?;
+        Ok(Some(creds))
     }
 
     async fn load_impl(
@@ -240,11 +342,22 @@ impl AdcCredentials {
     }
 
     async fn load_from_metadata_server(base_url: &str) -> Result<Self, AuthError> {
+        let token_response = Self::fetch_metadata_token(base_url).await?;
+
+        // Note: When using metadata server, we have access to the OAuth2 access token
+        // that can be used to authenticate applications.
+        Ok(AdcCredentials::DefaultAccount(TokenResponse {
+            token_type: token_response.token_type,
+            access_token: token_response.access_token,
+            expires_in: token_response.expires_in,
+        }))
+    }
+
+    async fn fetch_metadata_token(base_url: &str) -> Result<TokenResponse, AuthError> {
         let client = reqwest::Client::new();
-        let metadata_path = "/computeMetadata/v1/instance/service-accounts/default/token";
 
         let response = client
-            .get(format!("{}{}", base_url, metadata_path))
+            .get(format!("{}{}", base_url, METADATA_TOKEN_PATH))
             .header("Metadata-Flavor", "Google")
             .send()
             .await
@@ -259,18 +372,10 @@ impl AdcCredentials {
         }
 
         // Get the identity token and credentials from metadata server
-        let token_response = response
+        response
             .json::<TokenResponse>()
             .await
-            .map_err(|e| AuthError::Credentials(format!("Invalid metadata response: {}", e)))?;
-
-        // Note: When using metadata server, we have access to the OAuth2 access token
-        // that can be used to authenticate applications.
-        Ok(AdcCredentials::DefaultAccount(TokenResponse {
-            token_type: token_response.token_type,
-            access_token: token_response.access_token,
-            expires_in: token_response.expires_in,
-        }))
+            .map_err(|e| AuthError::Credentials(format!("Invalid metadata response: {}", e)))
     }
 }
 
@@ -361,6 +466,22 @@ impl GcpAuth {
         })
     }
 
+    /// Creates a GCP authentication handler from the application default credentials only,
+    /// without GCP_CREDENTIALS_JSON from the goose config, for the secret store the config
+    /// itself is read through.
+    pub async fn from_application_default() -> Result<Self, AuthError> {
+        Ok(Self {
+            credentials: AdcCredentials::load_impl(
+                &RealFilesystemOps,
+                &RealEnvOps,
+                METADATA_BASE_URL,
+            )
+            .await?,
+            client: reqwest::Client::new(),
+            cached_token: Arc::new(RwLock::new(None)),
+        })
+    }
+
     /// Retrieves a valid authentication token.
     ///
     /// This method implements an efficient token management strategy:
@@ -393,13 +514,81 @@ impl GcpAuth {
         }
 
         // Get new token
+        let cached = self.fetch_token(token_guard.is_none()).await?;
+        let auth_token = cached.token.clone();
+        *token_guard = Some(cached);
+
+        Ok(auth_token)
+    }
+
+    /// Keeps the cached token fresh from a background task.
+    ///
+    /// The token is replaced a few minutes before it expires, so requests find a
+    /// valid token in the cache instead of waiting on a token exchange. Failed
+    /// refreshes are retried, and requests still refresh the token themselves if
+    /// the background refresh falls behind. The task stops once the handler is dropped.
+    pub fn start_background_refresh(self: &Arc<Self>) {
+        let auth = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let Some(auth) = auth.upgrade() else {
+                    return;
+                };
+                let delay = match auth.refresh_ahead_of_expiry().await {
+                    Ok(expires_at) => expires_at
+                        .saturating_duration_since(Instant::now())
+                        .saturating_sub(BACKGROUND_REFRESH_AHEAD),
+                    Err(e) => {
+                        tracing::warn!("Background GCP token refresh failed: {}", e);
+                        Duration::ZERO
+                    }
+                };
+                drop(auth);
+                tokio::time::sleep(delay.max(BACKGROUND_REFRESH_MIN_INTERVAL)).await;
+            }
+        });
+    }
+
+    /// Replaces the cached token if it is missing or about to expire.
+    ///
+    /// # Returns
+    /// * `Result<Instant, AuthError>` - When the cached token expires
+    async fn refresh_ahead_of_expiry(&self) -> Result<Instant, AuthError> {
+        let initial = match self.cached_token.read().await.as_ref() {
+            Some(cached) if cached.expires_at > Instant::now() + BACKGROUND_REFRESH_AHEAD => {
+                return Ok(cached.expires_at);
+            }
+            cached => cached.is_none(),
+        };
+
+        // The lock isn't held during the exchange, requests keep using the current token
+        let cached = self.fetch_token(initial).await?;
+        let expires_at = cached.expires_at;
+        *self.cached_token.write().await = Some(cached);
+        Ok(expires_at)
+    }
+
+    /// Obtains a new token from the credentials.
+    ///
+    /// # Arguments
+    /// * `initial` - Whether this is the first token, which the metadata server
+    ///   credentials already hold from when they were loaded
+    ///
+    /// # Returns
+    /// * `Result<CachedToken, AuthError>` - The new token and its expiration time
+    async fn fetch_token(&self, initial: bool) -> Result<CachedToken, AuthError> {
         let token_response = match &self.credentials {
             AdcCredentials::ServiceAccount(creds) => self.get_service_account_token(creds).await?,
             AdcCredentials::AuthorizedUser(creds) => self.get_authorized_user_token(creds).await?,
-            AdcCredentials::DefaultAccount(creds) => self.get_default_access_token(creds).await?,
+            AdcCredentials::ExternalAccount(creds) => {
+                self.get_external_account_token(creds).await?
+            }
+            AdcCredentials::DefaultAccount(creds) => {
+                self.get_default_access_token(creds, initial).await?
+            }
         };
 
-        let auth_token = AuthToken {
+        let token = AuthToken {
             token_type: if token_response.token_type.is_empty() {
                 "Bearer".to_string()
             } else {
@@ -413,12 +602,7 @@ impl GcpAuth {
                 token_response.expires_in.saturating_sub(30), // 30 second buffer
             );
 
-        *token_guard = Some(CachedToken {
-            token: auth_token.clone(),
-            expires_at,
-        });
-
-        Ok(auth_token)
+        Ok(CachedToken { token, expires_at })
     }
 
     /// Creates a JWT token for service account authentication.
@@ -437,7 +621,7 @@ impl GcpAuth {
         let claims = JwtClaims {
             iss: creds.client_email.clone(),
             sub: creds.client_email.clone(),
-            scope: "https://www.googleapis.com/auth/cloud-platform".to_string(),
+            scope: CLOUD_PLATFORM_SCOPE.to_string(),
             aud: creds.token_uri.clone(),
             iat: now,
             exp: now + 3600, // 1 hours validity
@@ -508,7 +692,7 @@ impl GcpAuth {
         let params = [
             ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
             ("assertion", &jwt),
-            ("scope", "https://www.googleapis.com/auth/cloud-platform"),
+            ("scope", CLOUD_PLATFORM_SCOPE),
         ];
 
         self.exchange_token(&creds.token_uri, &params).await
@@ -530,24 +714,165 @@ impl GcpAuth {
             ("client_secret", creds.client_secret.as_str()),
             ("refresh_token", creds.refresh_token.as_str()),
             ("grant_type", "refresh_token"),
-            ("scope", "https://www.googleapis.com/auth/cloud-platform"),
+            ("scope", CLOUD_PLATFORM_SCOPE),
         ];
 
         self.exchange_token(&creds.token_uri, &params).await
     }
 
+    /// Gets a token using workload identity federation.
+    ///
+    /// Reads the external subject token, exchanges it with the Security Token
+    /// Service for a federated token, and uses that to impersonate a service
+    /// account if the credentials name one.
+    ///
+    /// # Arguments
+    /// * `creds` - External account credentials
+    ///
+    /// # Returns
+    /// * `Result<TokenResponse>` - The token response
+    async fn get_external_account_token(
+        &self,
+        creds: &ExternalAccountCredentials,
+    ) -> Result<TokenResponse, AuthError> {
+        let subject_token = self.read_subject_token(&creds.credential_source).await?;
+        let params = [
+            (
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:token-exchange",
+            ),
+            ("audience", creds.audience.as_str()),
+            ("scope", CLOUD_PLATFORM_SCOPE),
+            (
+                "requested_token_type",
+                "urn:ietf:params:oauth:token-type:access_token",
+            ),
+            ("subject_token", subject_token.as_str()),
+            ("subject_token_type", creds.subject_token_type.as_str()),
+        ];
+        let federated = self.exchange_token(&creds.token_url, &params).await?;
+
+        let Some(impersonation_url) = &creds.service_account_impersonation_url else {
+            return Ok(federated);
+        };
+
+        let response = self
+            .client
+            .post(impersonation_url)
+            .bearer_auth(&federated.access_token)
+            .json(&serde_json::json!({ "scope": [CLOUD_PLATFORM_SCOPE] }))
+            .send()
+            .await
+            .map_err(|e| AuthError::TokenExchange(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(AuthError::TokenExchange(format!(
+                "Service account impersonation failed with status {}: {}",
+                status, error_text
+            )));
+        }
+
+        let impersonated = response
+            .json::<ImpersonationResponse>()
+            .await
+            .map_err(|e| AuthError::TokenExchange(format!("Invalid response: {}", e)))?;
+        let expires_in = (impersonated.expire_time - Utc::now()).num_seconds().max(0) as u64;
+        Ok(TokenResponse {
+            access_token: impersonated.access_token,
+            expires_in,
+            token_type: "Bearer".to_string(),
+        })
+    }
+
+    /// Reads the external subject token for workload identity federation.
+    ///
+    /// # Arguments
+    /// * `source` - Where the token is stored and how it is formatted
+    ///
+    /// # Returns
+    /// * `Result<String>` - The subject token
+    async fn read_subject_token(&self, source: &CredentialSource) -> Result<String, AuthError> {
+        let content = match (&source.file, &source.url) {
+            (Some(file), _) => tokio::fs::read_to_string(file).await.map_err(|e| {
+                AuthError::Credentials(format!("Failed to read subject token from {}: {}", file, e))
+            })?,
+            (None, Some(url)) => {
+                let mut request = self.client.get(url);
+                for (name, value) in &source.headers {
+                    request = request.header(name, value);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| {
+                        AuthError::Credentials(format!(
+                            "Failed to read subject token from {}: {}",
+                            url, e
+                        ))
+                    })?;
+                response
+                    .text()
+                    .await
+                    .map_err(|e| AuthError::Credentials(e.to_string()))?
+            }
+            (None, None) => {
+                return Err(AuthError::Credentials(
+                    "Only file and url credential sources are supported for external accounts"
+                        .to_string(),
+                ))
+            }
+        };
+
+        match &source.format {
+            Some(format) if format.format_type == "json" => {
+                let field = format
+                    .subject_token_field_name
+                    .as_deref()
+                    .unwrap_or("access_token");
+                let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| {
+                    AuthError::Credentials(format!("Invalid subject token JSON: {}", e))
+                })?;
+                value
+                    .get(field)
+                    .and_then(|token| token.as_str())
+                    .map(|token| token.to_string())
+                    .ok_or_else(|| {
+                        AuthError::Credentials(format!("Subject token JSON has no {} field", field))
+                    })
+            }
+            _ => Ok(content.trim().to_string()),
+        }
+    }
+
     /// Gets a token directly from the GCP metadata endpoint.
     ///
+    /// The token fetched when the credentials were loaded is used first, later
+    /// tokens are requested from the metadata server as they expire.
+    ///
     /// # Arguments
     /// * `creds` - Default Access Token Response
+    /// * `initial` - Whether this is the first token requested
     ///
     /// # Returns
     /// * `Result<TokenResponse>` - The token response
     async fn get_default_access_token(
         &self,
         creds: &TokenResponse,
+        initial: bool,
     ) -> Result<TokenResponse, AuthError> {
-        Ok(creds.clone())
+        if initial {
+            return Ok(creds.clone());
+        }
+
+        AdcCredentials::fetch_metadata_token(METADATA_BASE_URL)
+            .await
+            .map_err(|e| AuthError::TokenExchange(e.to_string()))
     }
 }
 
@@ -556,7 +881,7 @@ mod tests {
     use super::*;
     use mockall::predicate::eq;
     use tokio::time::sleep;
-    use wiremock::matchers::{header, method, path};
+    use wiremock::matchers::{body_string_contains, header, method, path};
     // Only import what we need
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
@@ -1112,4 +1437,99 @@ iXVBc2YmAuU8hiOFUPxtyQfNzG5fQ0rhJSewdtyWxIadJSLj6fsK+AEsNQ==
         .await;
         assert!(matches!(result, Err(AuthError::Credentials(_))));
     }
+
+    #[tokio::test]
+    async fn test_external_account_token_with_impersonation() {
+        let mock_server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let token_file = dir.path().join("token.json");
+        std::fs::write(&token_file, r#"{"id_token": "k8s-subject-token"}"#).unwrap();
+
+        Mock::given(method("POST"))
+            .and(path("/v1/token"))
+            .and(body_string_contains("subject_token=k8s-subject-token"))
+            .and(body_string_contains(
+                "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Atoken-exchange",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "federated_token",
+                "issued_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(
+                "/v1/projects/-/serviceAccounts/goose@example.iam.gserviceaccount.com:generateAccessToken",
+            ))
+            .and(header("Authorization", "Bearer federated_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "accessToken": "impersonated_token",
+                "expireTime": (Utc::now() + chrono::Duration::hours(1)).to_rfc3339(),
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials: AdcCredentials = serde_json::from_value(serde_json::json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/pool/providers/k8s",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": format!("{}/v1/token", mock_server.uri()),
+            "service_account_impersonation_url": format!(
+                "{}/v1/projects/-/serviceAccounts/goose@example.iam.gserviceaccount.com:generateAccessToken",
+                mock_server.uri()
+            ),
+            "credential_source": {
+                "file": token_file.to_string_lossy(),
+                "format": {"type": "json", "subject_token_field_name": "id_token"}
+            }
+        }))
+        .unwrap();
+
+        let auth = create_test_auth_with_creds(credentials).await;
+        let token = auth.get_token().await.unwrap();
+        assert_eq!(token.token_value, "impersonated_token");
+        // Cached, so neither exchange is repeated
+        let token = auth.get_token().await.unwrap();
+        assert_eq!(token.token_value, "impersonated_token");
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_fills_cache() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "background_token",
+                "expires_in": 3600,
+                "token_type": "Bearer",
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let auth = Arc::new(
+            create_test_auth_with_creds(AdcCredentials::AuthorizedUser(
+                AuthorizedUserCredentials {
+                    token_uri: format!("{}/token", mock_server.uri()),
+                    ..mock_authorized_user()
+                },
+            ))
+            .await,
+        );
+        auth.start_background_refresh();
+
+        for _ in 0..50 {
+            if auth.cached_token.read().await.is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        // The request finds the token already cached and doesn't exchange again
+        let token = auth.get_token().await.unwrap();
+        assert_eq!(token.token_value, "background_token");
+    }
 }
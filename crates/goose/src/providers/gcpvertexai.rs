@@ -1,8 +1,9 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use serde_json::Value;
 use tokio::time::sleep;
 use url::Url;
@@ -19,6 +20,8 @@ use crate::providers::formats::gcpvertexai::{
 
 use crate::providers::formats::gcpvertexai::GcpLocation::Iowa;
 use crate::providers::gcpauth::GcpAuth;
+use crate::providers::http::HttpClient;
+use crate::providers::middleware;
 use crate::providers::utils::emit_debug_trace;
 use mcp_core::tool::Tool;
 
@@ -103,10 +106,10 @@ impl RetryConfig {
 pub struct GcpVertexAIProvider {
     /// HTTP client for making API requests
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     /// GCP authentication handler
     #[serde(skip)]
-    auth: GcpAuth,
+    auth: Arc<GcpAuth>,
     /// Base URL for the Vertex AI API
     host: String,
     /// GCP project identifier
@@ -150,11 +153,13 @@ impl GcpVertexAIProvider {
         let location = Self::determine_location(config)?;
         let host = format!("https://{}-aiplatform.googleapis.com", location);
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
-            .build()?;
+        let client = HttpClient::new("GCP", Duration::from_secs(DEFAULT_TIMEOUT_SECS))?;
 
-        let auth = GcpAuth::new().await?;
+        let auth = Arc::new(GcpAuth::new().await?);
+        // Outside a tokio runtime tokens are still refreshed when requests need them
+        if tokio::runtime::Handle::try_current().is_ok() {
+            auth.start_background_refresh();
+        }
 
         // Load optional retry configuration from environment
         let retry_config = Self::load_retry_config(config);
@@ -307,14 +312,14 @@ impl GcpVertexAIProvider {
                 .map_err(|e| ProviderError::Authentication(e.to_string()))?;
 
             // Make the request
-            let response = self
+            let request = self
                 .client
-                .post(url.clone())
-                .json(payload)
-                .header("Authorization", auth_header)
-                .send()
-                .await
-                .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+                .post_json(url.clone(), payload)
+                .header("Authorization", auth_header);
+            let response = middleware::send(request).await.map_err(|e| match e {
+                ProviderError::ExecutionError(e) => ProviderError::RequestFailed(e),
+                e => e,
+            })?;
 
             let status = response.status();
 
@@ -450,6 +455,7 @@ impl Provider for GcpVertexAIProvider {
             vec![
                 ConfigKey::new("GCP_PROJECT_ID", true, false, None),
                 ConfigKey::new("GCP_LOCATION", true, false, Some(Iowa.to_string().as_str())),
+                ConfigKey::new("GCP_CREDENTIALS_JSON", false, true, None),
                 ConfigKey::new(
                     "GCP_MAX_RETRIES",
                     false,
@@ -589,7 +595,7 @@ mod tests {
         assert!(metadata
             .known_models
             .contains(&"gemini-1.5-pro-002".to_string()));
-        // Should contain the original 2 config keys plus 4 new retry-related ones
-        assert_eq!(metadata.config_keys.len(), 6);
+        // Project and location, the optional credentials JSON, and 4 retry-related keys
+        assert_eq!(metadata.config_keys.len(), 7);
     }
 }
@@ -1,15 +1,16 @@
+use super::api_keys::ApiKeys;
 use super::errors::ProviderError;
 use crate::message::Message;
 use crate::model::ModelConfig;
 use crate::providers::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
 use crate::providers::formats::google::{create_request, get_usage, response_to_message};
+use crate::providers::http::HttpClient;
 use crate::providers::utils::{
     emit_debug_trace, handle_response_google_compat, unescape_json_values,
 };
 use anyhow::Result;
 use async_trait::async_trait;
 use mcp_core::tool::Tool;
-use reqwest::Client;
 use serde_json::Value;
 use std::time::Duration;
 use url::Url;
@@ -32,9 +33,10 @@ pub const GOOGLE_DOC_URL: &str = "https://ai.google/get-started/our-models/";
 #[derive(Debug, serde::Serialize)]
 pub struct GoogleProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
-    api_key: String,
+    #[serde(skip)]
+    api_keys: ApiKeys,
     model: ModelConfig,
 }
 
@@ -48,19 +50,17 @@ impl Default for GoogleProvider {
 impl GoogleProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("GOOGLE_API_KEY")?;
+        let api_keys = ApiKeys::from_config("GOOGLE_API_KEY")?;
         let host: String = config
             .get_param("GOOGLE_HOST")
             .unwrap_or_else(|_| GOOGLE_API_HOST.to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("GOOGLE", Duration::from_secs(600))?;
 
         Ok(Self {
             client,
             host,
-            api_key,
+            api_keys,
             model,
         })
     }
@@ -71,8 +71,8 @@ impl GoogleProvider {
 
         let url = base_url
             .join(&format!(
-                "v1beta/models/{}:generateContent?key={}",
-                self.model.model_name, self.api_key
+                "v1beta/models/{}:generateContent",
+                self.model.model_name
             ))
             .map_err(|e| {
                 ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
@@ -84,11 +84,14 @@ impl GoogleProvider {
 
         loop {
             let response = self
-                .client
-                .post(url.clone()) // Clone the URL for each retry
-                .header("CONTENT_TYPE", "application/json")
-                .json(&payload)
-                .send()
+                .api_keys
+                .send(|key| {
+                    let mut url = url.clone(); // Clone the URL for each retry
+                    url.query_pairs_mut().append_pair("key", key);
+                    self.client
+                        .post_json(url, &payload)
+                        .header("CONTENT_TYPE", "application/json")
+                })
                 .await;
 
             match response {
@@ -113,12 +116,7 @@ impl GoogleProvider {
                         Err(err) => return Err(err), // Other errors
                     }
                 }
-                Err(err) => {
-                    return Err(ProviderError::RequestFailed(format!(
-                        "Request failed: {}",
-                        err
-                    )));
-                }
+                Err(err) => return Err(err),
             }
         }
     }
@@ -1,13 +1,15 @@
+use super::api_keys::ApiKeys;
 use super::errors::ProviderError;
 use crate::message::Message;
 use crate::model::ModelConfig;
 use crate::providers::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
 use crate::providers::formats::openai::{create_request, get_usage, response_to_message};
+use crate::providers::http::HttpClient;
 use crate::providers::utils::get_model;
 use anyhow::Result;
 use async_trait::async_trait;
 use mcp_core::Tool;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use serde_json::Value;
 use std::time::Duration;
 use url::Url;
@@ -21,9 +23,10 @@ pub const GROQ_DOC_URL: &str = "https://console.groq.com/docs/models";
 #[derive(serde::Serialize)]
 pub struct GroqProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
-    api_key: String,
+    #[serde(skip)]
+    api_keys: ApiKeys,
     model: ModelConfig,
 }
 
@@ -37,19 +40,17 @@ impl Default for GroqProvider {
 impl GroqProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("GROQ_API_KEY")?;
+        let api_keys = ApiKeys::from_config("GROQ_API_KEY")?;
         let host: String = config
             .get_param("GROQ_HOST")
             .unwrap_or_else(|_| GROQ_API_HOST.to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("GROQ", Duration::from_secs(600))?;
 
         Ok(Self {
             client,
             host,
-            api_key,
+            api_keys,
             model,
         })
     }
@@ -62,11 +63,12 @@ impl GroqProvider {
         })?;
 
         let response = self
-            .client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&payload)
-            .send()
+            .api_keys
+            .send(|key| {
+                self.client
+                    .post_json(url.clone(), &payload)
+                    .header("Authorization", format!("Bearer {}", key))
+            })
             .await?;
 
         let status = response.status();
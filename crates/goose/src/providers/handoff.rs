@@ -0,0 +1,124 @@
+//! Carrying a conversation over to another model
+//!
+//! Messages are kept in goose's own format and each provider encodes them for its API, so most
+//! of a conversation can go to any model as it is. Two parts of an answer only hold for the
+//! model that wrote it: thinking blocks, which carry a signature its API checks, and tool call
+//! ids, which each API limits to its own characters and length. After the model of a session
+//! changes, the messages from before the change are sent without their thinking, and with
+//! their tool call ids rewritten to letters, digits, `_` and `-`, which every provider accepts.
+//! A message that was only thinking is left out, as APIs refuse empty messages.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::message::{Message, MessageContent};
+
+/// The longest tool call id every provider accepts
+const MAX_ID_LEN: usize = 64;
+
+/// Make the first `before` messages, which were written before the session switched models,
+/// fit the model it switched to
+pub fn hand_off(messages: &mut Vec<Message>, before: usize) {
+    let before = before.min(messages.len());
+    for message in &mut messages[..before] {
+        message.content.retain(|content| {
+            !matches!(
+                content,
+                MessageContent::Thinking(_) | MessageContent::RedactedThinking(_)
+            )
+        });
+        for content in &mut message.content {
+            let id = match content {
+                MessageContent::ToolRequest(request) => &mut request.id,
+                MessageContent::ToolResponse(response) => &mut response.id,
+                MessageContent::ToolConfirmationRequest(confirmation) => &mut confirmation.id,
+                _ => continue,
+            };
+            *id = portable_id(id);
+        }
+    }
+    let mut index = 0;
+    messages.retain(|message| {
+        index += 1;
+        index > before || !message.content.is_empty()
+    });
+}
+
+/// `id` with the characters some providers reject replaced, and shortened to fit them
+///
+/// The same id always becomes the same, so requests still match their responses.
+fn portable_id(id: &str) -> String {
+    let cleaned: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if cleaned.is_empty() {
+        return "call".to_string();
+    }
+    if cleaned.len() <= MAX_ID_LEN {
+        return cleaned;
+    }
+    // Ids can share a long prefix, so the end is a hash of the whole id
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    let hash = format!("{:016x}", hasher.finish());
+    format!("{}_{}", &cleaned[..MAX_ID_LEN - hash.len() - 1], hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::{Content, ToolCall};
+    use serde_json::json;
+
+    #[test]
+    fn test_hand_off() {
+        let call = ToolCall::new("developer__shell", json!({"command": "ls"}));
+        let before = Message::assistant()
+            .with_thinking("The user wants a listing", "c2lnbmF0dXJl")
+            .with_tool_request("toolu:01/abc", Ok(call.clone()));
+        let response =
+            Message::user().with_tool_response("toolu:01/abc", Ok(vec![Content::text("a.txt")]));
+        let thinking = Message::assistant().with_thinking("Nothing to add", "c2lnbmF0dXJl");
+        // Written in the same second as the switch, which the order still tells apart
+        let after = Message::assistant()
+            .with_thinking("Now for the sizes", "c2lnbmF0dXJl")
+            .with_tool_request("toolu:02", Ok(call));
+        let mut messages = vec![before, response, thinking, after];
+
+        hand_off(&mut messages, 3);
+        // The message that was only thinking is left out
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].content.len(), 1);
+        let MessageContent::ToolRequest(request) = &messages[0].content[0] else {
+            panic!("expected the tool request to be kept");
+        };
+        assert_eq!(request.id, "toolu_01_abc");
+        let MessageContent::ToolResponse(response) = &messages[1].content[0] else {
+            panic!("expected a tool response");
+        };
+        assert_eq!(response.id, request.id);
+        // What the new model wrote stays as it is
+        assert_eq!(messages[2].content.len(), 2);
+        let MessageContent::ToolRequest(request) = &messages[2].content[1] else {
+            panic!("expected a tool request");
+        };
+        assert_eq!(request.id, "toolu:02");
+    }
+
+    #[test]
+    fn test_portable_id() {
+        assert_eq!(portable_id("call_abc-123"), "call_abc-123");
+        assert_eq!(portable_id(""), "call");
+        let long = "x".repeat(100);
+        let other = format!("{}y", "x".repeat(99));
+        assert_eq!(portable_id(&long).len(), MAX_ID_LEN);
+        assert_eq!(portable_id(&long), portable_id(&long));
+        assert_ne!(portable_id(&long), portable_id(&other));
+    }
+}
@@ -0,0 +1,382 @@
+//! Fitting images to the size limits of provider APIs
+//!
+//! Screenshots from a large or high density display easily exceed what a provider accepts, in
+//! either pixels or bytes, and the request fails. Before a request is built, images larger than
+//! the provider's [`ImageLimits`] are scaled down and re-encoded, as JPEG unless they have
+//! transparency, which keeps them PNG. Images that carry EXIF metadata are re-encoded too, so
+//! camera details and locations aren't uploaded. Images already within the limits are sent as
+//! they are.
+//!
+//! `GOOSE_IMAGE_MAX_DIMENSION` lowers the largest width or height sent to any provider, which
+//! cuts the tokens each image costs.
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use std::sync::Mutex;
+
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageOutputFormat};
+use mcp_core::content::ImageContent;
+use mcp_core::Content;
+use once_cell::sync::Lazy;
+
+use crate::config::Config;
+use crate::message::attachment::IMAGE_TYPES;
+use crate::message::{AttachmentContent, Message, MessageContent};
+
+/// The largest images a provider accepts, or gains detail from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageLimits {
+    /// The most pixels on the longer side
+    pub max_dimension: u32,
+    /// The most bytes of an encoded image
+    pub max_bytes: usize,
+}
+
+/// Anthropic resizes anything larger than 1568 pixels, and rejects images over 5 MB
+pub const ANTHROPIC_IMAGE_LIMITS: ImageLimits = ImageLimits {
+    max_dimension: 1568,
+    max_bytes: 5 * 1024 * 1024,
+};
+
+/// OpenAI fits images into 2048 pixels for its high detail mode, and takes up to 20 MB
+pub const OPENAI_IMAGE_LIMITS: ImageLimits = ImageLimits {
+    max_dimension: 2048,
+    max_bytes: 20 * 1024 * 1024,
+};
+
+/// Gemini scales images to 3072 pixels, and inline data is limited to 20 MB per request, which
+/// several images share
+pub const GOOGLE_IMAGE_LIMITS: ImageLimits = ImageLimits {
+    max_dimension: 3072,
+    max_bytes: 7 * 1024 * 1024,
+};
+
+/// Bedrock's Converse API takes images up to 8000 pixels and 3.75 MB, for models whose vendor
+/// doesn't set lower limits
+pub const BEDROCK_IMAGE_LIMITS: ImageLimits = ImageLimits {
+    max_dimension: 8000,
+    max_bytes: 3840 * 1024,
+};
+
+impl Default for ImageLimits {
+    /// Limits every provider accepts, for images whose destination isn't known
+    fn default() -> Self {
+        ANTHROPIC_IMAGE_LIMITS
+    }
+}
+
+impl ImageLimits {
+    /// These limits, lowered to `GOOSE_IMAGE_MAX_DIMENSION` if that is set
+    fn configured(&self) -> Self {
+        static MAX_DIMENSION: Lazy<Option<u32>> = Lazy::new(|| {
+            Config::global()
+                .get_param::<u32>("GOOSE_IMAGE_MAX_DIMENSION")
+                .ok()
+                .filter(|max| *max >= MIN_DIMENSION)
+        });
+        Self {
+            max_dimension: MAX_DIMENSION
+                .map_or(self.max_dimension, |max| max.min(self.max_dimension)),
+            ..*self
+        }
+    }
+}
+
+/// Images aren't scaled below this to meet a byte limit, or by `GOOSE_IMAGE_MAX_DIMENSION`
+const MIN_DIMENSION: u32 = 256;
+
+/// JPEG qualities tried in turn until an image is small enough
+const JPEG_QUALITIES: &[u8] = &[85, 70, 55];
+
+/// Images fitted recently, by a hash of their data and the limits they were fitted to
+///
+/// Each request sends the whole conversation, so without this the same screenshots would be
+/// decoded and encoded again on every turn.
+type FittedImages = HashMap<(u64, ImageLimits), Option<ImageContent>>;
+static FITTED: Lazy<Mutex<FittedImages>> = Lazy::new(|| Mutex::new(HashMap::new()));
+const MAX_FITTED: usize = 64;
+
+/// The messages with their images, in content, tool results and attachments, fitted to limits
+pub fn fit_messages<'a>(messages: &'a [Message], limits: &ImageLimits) -> Cow<'a, [Message]> {
+    let limits = limits.configured();
+    let mut fitted: Option<Vec<Message>> = None;
+    for (i, message) in messages.iter().enumerate() {
+        for (j, content) in message.content.iter().enumerate() {
+            if let Some(content) = fit_content(content, &limits) {
+                fitted.get_or_insert_with(|| messages.to_vec())[i].content[j] = content;
+            }
+        }
+    }
+    match fitted {
+        Some(messages) => Cow::Owned(messages),
+        None => Cow::Borrowed(messages),
+    }
+}
+
+/// The content with its images fitted, or None if it is fine as it is
+fn fit_content(content: &MessageContent, limits: &ImageLimits) -> Option<MessageContent> {
+    match content {
+        MessageContent::Image(image) => fit_image(image, limits).map(MessageContent::Image),
+        MessageContent::ToolResponse(response) => {
+            let contents = response.tool_result.as_ref().ok()?;
+            let mut fitted: Option<Vec<Content>> = None;
+            for (i, content) in contents.iter().enumerate() {
+                if let Content::Image(image) = content {
+                    if let Some(image) = fit_image(image, limits) {
+                        fitted.get_or_insert_with(|| contents.clone())[i] = Content::Image(image);
+                    }
+                }
+            }
+            let mut response = response.clone();
+            response.tool_result = Ok(fitted?);
+            Some(MessageContent::ToolResponse(response))
+        }
+        MessageContent::Attachment(attachment)
+            if IMAGE_TYPES.contains(&attachment.mime_type.as_str()) =>
+        {
+            let image = ImageContent {
+                data: base64::prelude::BASE64_STANDARD.encode(attachment.bytes().ok()?),
+                mime_type: attachment.mime_type.clone(),
+                annotations: None,
+            };
+            let image = fit_image(&image, limits)?;
+            Some(MessageContent::Attachment(AttachmentContent {
+                mime_type: image.mime_type,
+                data: Some(image.data),
+                ..attachment.clone()
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// The image scaled and re-encoded to fit the limits, or None if it already does
+///
+/// Images that can't be decoded are left for the provider to accept or reject.
+pub fn fit_image(image: &ImageContent, limits: &ImageLimits) -> Option<ImageContent> {
+    let mut hasher = DefaultHasher::new();
+    image.data.hash(&mut hasher);
+    let key = (hasher.finish(), *limits);
+    if let Some(fitted) = FITTED.lock().unwrap().get(&key) {
+        return fitted.clone();
+    }
+
+    let fitted = fit_image_uncached(image, limits);
+    let mut cache = FITTED.lock().unwrap();
+    if cache.len() >= MAX_FITTED {
+        cache.clear();
+    }
+    cache.insert(key, fitted.clone());
+    fitted
+}
+
+fn fit_image_uncached(image: &ImageContent, limits: &ImageLimits) -> Option<ImageContent> {
+    let bytes = base64::prelude::BASE64_STANDARD.decode(&image.data).ok()?;
+    let (width, height) = image::io::Reader::new(Cursor::new(&bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+    if width.max(height) <= limits.max_dimension
+        && bytes.len() <= limits.max_bytes
+        && !has_exif(&bytes)
+    {
+        return None;
+    }
+
+    let mut decoded = image::load_from_memory(&bytes).ok()?;
+    if width.max(height) > limits.max_dimension {
+        decoded = decoded.resize(
+            limits.max_dimension,
+            limits.max_dimension,
+            FilterType::Lanczos3,
+        );
+    }
+    let (mime_type, encoded) = loop {
+        if let Some(encoded) = encode(&decoded, limits.max_bytes) {
+            break encoded;
+        }
+        // Still too many bytes at the lowest quality, so give up some pixels
+        let dimension = decoded.width().max(decoded.height()) * 3 / 4;
+        if dimension < MIN_DIMENSION {
+            return None;
+        }
+        decoded = decoded.resize(dimension, dimension, FilterType::Lanczos3);
+    };
+
+    Some(ImageContent {
+        data: base64::prelude::BASE64_STANDARD.encode(encoded),
+        mime_type: mime_type.to_string(),
+        annotations: image.annotations.clone(),
+    })
+}
+
+/// The image encoded within `max_bytes`, as PNG if it has transparency and JPEG otherwise
+fn encode(image: &DynamicImage, max_bytes: usize) -> Option<(&'static str, Vec<u8>)> {
+    if is_transparent(image) {
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .ok()?;
+        return (png.len() <= max_bytes).then_some(("image/png", png));
+    }
+
+    let rgb = image.to_rgb8();
+    JPEG_QUALITIES.iter().find_map(|quality| {
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, *quality)
+            .encode_image(&rgb)
+            .ok()?;
+        (jpeg.len() <= max_bytes).then_some(("image/jpeg", jpeg))
+    })
+}
+
+fn is_transparent(image: &DynamicImage) -> bool {
+    image.color().has_alpha() && image.to_rgba8().pixels().any(|pixel| pixel[3] < u8::MAX)
+}
+
+/// Whether an encoded image carries EXIF metadata, in a JPEG APP1 segment, a PNG eXIf chunk or
+/// a WebP EXIF chunk, all of which come before the image data
+fn has_exif(bytes: &[u8]) -> bool {
+    let header = &bytes[..bytes.len().min(128 * 1024)];
+    [&b"Exif\0\0"[..], b"eXIf", b"EXIF"]
+        .iter()
+        .any(|marker| header.windows(marker.len()).any(|window| window == *marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn encoded(image: DynamicImage, format: ImageOutputFormat, mime_type: &str) -> ImageContent {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        ImageContent {
+            data: base64::prelude::BASE64_STANDARD.encode(bytes),
+            mime_type: mime_type.to_string(),
+            annotations: None,
+        }
+    }
+
+    fn dimensions(image: &ImageContent) -> (u32, u32) {
+        let bytes = base64::prelude::BASE64_STANDARD
+            .decode(&image.data)
+            .unwrap();
+        image::load_from_memory(&bytes)
+            .unwrap()
+            .to_rgba8()
+            .dimensions()
+    }
+
+    /// A noisy image, which compresses about as badly as a photo
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let v = (x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)) as u8;
+            Rgb([v, v.wrapping_mul(3), v.wrapping_add(y as u8)])
+        }))
+    }
+
+    const SMALL: ImageLimits = ImageLimits {
+        max_dimension: 400,
+        max_bytes: 1024 * 1024,
+    };
+
+    #[test]
+    fn test_fit_image() {
+        // Within the limits, the image is sent as it is
+        let small = encoded(noise(300, 200), ImageOutputFormat::Png, "image/png");
+        assert_eq!(fit_image(&small, &SMALL), None);
+
+        // Larger, it is scaled down keeping its aspect ratio, and becomes a JPEG
+        let screenshot = encoded(noise(1600, 1000), ImageOutputFormat::Png, "image/png");
+        let fitted = fit_image(&screenshot, &SMALL).unwrap();
+        assert_eq!(fitted.mime_type, "image/jpeg");
+        assert_eq!(dimensions(&fitted), (400, 250));
+
+        // Transparency is kept
+        let icon = DynamicImage::ImageRgba8(RgbaImage::from_pixel(800, 800, Rgba([0, 0, 0, 0])));
+        let fitted =
+            fit_image(&encoded(icon, ImageOutputFormat::Png, "image/png"), &SMALL).unwrap();
+        assert_eq!(fitted.mime_type, "image/png");
+        assert_eq!(dimensions(&fitted), (400, 400));
+
+        // Too many bytes at full size, it is scaled further
+        let tight = ImageLimits {
+            max_dimension: 1000,
+            max_bytes: 60 * 1024,
+        };
+        let fitted = fit_image(&screenshot, &tight).unwrap();
+        let (width, _) = dimensions(&fitted);
+        assert!(width < 1000);
+        assert!(
+            base64::prelude::BASE64_STANDARD
+                .decode(&fitted.data)
+                .unwrap()
+                .len()
+                <= tight.max_bytes
+        );
+    }
+
+    #[test]
+    fn test_fit_image_strips_exif() {
+        let small = encoded(noise(64, 64), ImageOutputFormat::Jpeg(90), "image/jpeg");
+        let mut bytes = base64::prelude::BASE64_STANDARD
+            .decode(&small.data)
+            .unwrap();
+        let exif = [
+            &[0xFF, 0xE1, 0x00, 0x10][..],
+            b"Exif\0\0",
+            &[0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08],
+        ]
+        .concat();
+        bytes.splice(2..2, exif);
+        let with_exif = ImageContent {
+            data: base64::prelude::BASE64_STANDARD.encode(&bytes),
+            ..small
+        };
+
+        let fitted = fit_image(&with_exif, &SMALL).unwrap();
+        let fitted_bytes = base64::prelude::BASE64_STANDARD
+            .decode(&fitted.data)
+            .unwrap();
+        assert!(!has_exif(&fitted_bytes));
+        assert_eq!(dimensions(&fitted), (64, 64));
+    }
+
+    #[test]
+    fn test_fit_messages() {
+        let screenshot = encoded(noise(1200, 800), ImageOutputFormat::Png, "image/png");
+        let messages = vec![
+            Message::user().with_text("What's on my screen?"),
+            Message::user().with_tool_response(
+                "call_1",
+                Ok(vec![
+                    Content::text("Took a screenshot"),
+                    Content::Image(screenshot.clone()),
+                ]),
+            ),
+        ];
+
+        let fitted = fit_messages(&messages, &SMALL);
+        let MessageContent::ToolResponse(response) = &fitted[1].content[0] else {
+            panic!("Expected a tool response");
+        };
+        let contents = response.tool_result.as_ref().unwrap();
+        assert_eq!(contents[0], Content::text("Took a screenshot"));
+        let Content::Image(image) = &contents[1] else {
+            panic!("Expected an image");
+        };
+        assert_eq!(dimensions(image), (400, 267));
+
+        let text_only = &messages[..1];
+        assert!(matches!(fit_messages(text_only, &SMALL), Cow::Borrowed(_)));
+    }
+}
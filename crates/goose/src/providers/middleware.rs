@@ -0,0 +1,133 @@
+//! Middleware for the HTTP requests providers send to their model APIs
+//!
+//! Embedding applications can register [`HttpMiddleware`] to change requests before they are
+//! sent, such as adding organization specific signing or redacting payload fields, and to
+//! inspect or replace responses, such as for mirroring traffic, without changing each provider.
+//! It runs for the completion and model listing requests of the providers that go through
+//! [`send`]: those built on reqwest, which is all of them but Bedrock, whose requests the AWS
+//! SDK sends. The requests that fetch auth tokens don't go through it, so credentials aren't
+//! exposed to it.
+//!
+//! Request hooks run in the order the middleware was registered and response hooks in the
+//! reverse order, so the first middleware registered sees the request last before it is
+//! sent and the response last before the provider handles it.
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use reqwest::{Request, RequestBuilder, Response};
+use std::sync::{Arc, RwLock};
+
+use super::errors::ProviderError;
+
+static MIDDLEWARE: Lazy<RwLock<Vec<Arc<dyn HttpMiddleware>>>> =
+    Lazy::new(|| RwLock::new(Vec::new()));
+
+#[async_trait]
+pub trait HttpMiddleware: Send + Sync {
+    /// Modify a request before it is sent, returning an error stops it from being sent
+    async fn on_request(&self, _request: &mut Request) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    /// Inspect a response before the provider handles it
+    ///
+    /// The response is passed by value so its body can be read, in which case return a new
+    /// response with the same body (e.g. built from an `http::Response`) in its place.
+    async fn on_response(&self, response: Response) -> Result<Response, ProviderError> {
+        Ok(response)
+    }
+}
+
+/// Add middleware that runs for all provider requests made from now on
+pub fn register_middleware(middleware: Arc<dyn HttpMiddleware>) {
+    MIDDLEWARE.write().unwrap().push(middleware);
+}
+
+/// Send a provider request through the registered middleware
+pub async fn send(request: RequestBuilder) -> Result<Response, ProviderError> {
+    let middleware = MIDDLEWARE.read().unwrap().clone();
+    if middleware.is_empty() {
+        return Ok(request.send().await?);
+    }
+
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    for middleware in &middleware {
+        middleware.on_request(&mut request).await?;
+    }
+    let mut response = client.execute(request).await?;
+    for middleware in middleware.iter().rev() {
+        response = middleware.on_response(response).await?;
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use serde_json::{json, Value};
+    use std::sync::Mutex;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TEST_PATH: &str = "/middleware-test";
+
+    /// Signs and redacts requests to the test path, recording the responses it sees.
+    /// Middleware is global, so it leaves requests made by other tests alone
+    struct TestMiddleware {
+        statuses: Mutex<Vec<u16>>,
+    }
+
+    #[async_trait]
+    impl HttpMiddleware for TestMiddleware {
+        async fn on_request(&self, request: &mut Request) -> Result<(), ProviderError> {
+            if request.url().path() != TEST_PATH {
+                return Ok(());
+            }
+            request
+                .headers_mut()
+                .insert("x-org-signature", HeaderValue::from_static("signed"));
+            let body = request.body().and_then(|body| body.as_bytes());
+            let mut payload: Value = serde_json::from_slice(body.unwrap_or_default())
+                .map_err(|e| ProviderError::ExecutionError(e.to_string()))?;
+            payload["user"] = json!("[redacted]");
+            *request.body_mut() = Some(payload.to_string().into());
+            Ok(())
+        }
+
+        async fn on_response(&self, response: Response) -> Result<Response, ProviderError> {
+            if response.url().path() == TEST_PATH {
+                self.statuses
+                    .lock()
+                    .unwrap()
+                    .push(response.status().as_u16());
+            }
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_modifies_requests_and_sees_responses() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(TEST_PATH))
+            .and(header("x-org-signature", "signed"))
+            .and(body_json(json!({"model": "gpt-4o", "user": "[redacted]"})))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let middleware = Arc::new(TestMiddleware {
+            statuses: Mutex::new(Vec::new()),
+        });
+        register_middleware(middleware.clone());
+
+        let request = reqwest::Client::new()
+            .post(format!("{}{}", server.uri(), TEST_PATH))
+            .json(&json!({"model": "gpt-4o", "user": "alice@example.com"}));
+        let response = send(request).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(*middleware.statuses.lock().unwrap(), vec![200]);
+    }
+}
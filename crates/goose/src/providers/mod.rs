@@ -1,20 +1,29 @@
 pub mod anthropic;
+pub mod api_keys;
 pub mod azure;
+mod azureauth;
 pub mod base;
 pub mod bedrock;
+pub mod client_credentials;
 pub mod databricks;
+pub mod dedupe;
 pub mod errors;
 mod factory;
 pub mod formats;
-mod gcpauth;
+pub(crate) mod gcpauth;
 pub mod gcpvertexai;
 pub mod google;
 pub mod groq;
+pub mod handoff;
+pub mod http;
+pub mod images;
+pub mod middleware;
 pub mod oauth;
 pub mod ollama;
 pub mod openai;
 pub mod openrouter;
 pub mod sambanova;
+pub mod sigv4;
 pub mod toolshim;
 pub mod utils;
 
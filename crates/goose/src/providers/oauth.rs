@@ -11,6 +11,8 @@ use std::{collections::HashMap, fs, net::SocketAddr, path::PathBuf, sync::Arc};
 use tokio::sync::{oneshot, Mutex as TokioMutex};
 use url::Url;
 
+use super::http::shared_client;
+
 lazy_static! {
     static ref OAUTH_MUTEX: TokioMutex<()> = TokioMutex::new(());
 }
@@ -100,7 +102,7 @@ async fn get_workspace_endpoints(host: &str) -> Result<OidcEndpoints> {
         .join("oidc/.well-known/oauth-authorization-server")
         .expect("Invalid OIDC URL");
 
-    let client = reqwest::Client::new();
+    let client = shared_client("DATABRICKS")?;
     let resp = client.get(oidc_url.clone()).send().await?;
 
     if !resp.status().is_success() {
@@ -244,7 +246,7 @@ impl OAuthFlow {
             ("client_id", &self.client_id),
         ];
 
-        let client = reqwest::Client::new();
+        let client = shared_client("DATABRICKS")?;
         let resp = client
             .post(&self.endpoints.token_endpoint)
             .header("Content-Type", "application/x-www-form-urlencoded")
@@ -273,7 +275,7 @@ impl OAuthFlow {
 
         tracing::debug!("Refreshing token using refresh_token");
 
-        let client = reqwest::Client::new();
+        let client = shared_client("DATABRICKS")?;
         let resp = client
             .post(&self.endpoints.token_endpoint)
             .header("Content-Type", "application/x-www-form-urlencoded")
@@ -1,13 +1,14 @@
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
 use super::errors::ProviderError;
-use super::utils::{get_model, handle_response_openai_compat};
+use super::http::HttpClient;
+use super::middleware;
+use super::utils::{get_model, handle_response_openai_compat, parse_model_list};
 use crate::message::Message;
 use crate::model::ModelConfig;
 use crate::providers::formats::openai::{create_request, get_usage, response_to_message};
 use anyhow::Result;
 use async_trait::async_trait;
 use mcp_core::tool::Tool;
-use reqwest::Client;
 use serde_json::Value;
 use std::time::Duration;
 use url::Url;
@@ -22,7 +23,7 @@ pub const OLLAMA_DOC_URL: &str = "https://ollama.com/library";
 #[derive(serde::Serialize)]
 pub struct OllamaProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
     model: ModelConfig,
 }
@@ -41,9 +42,7 @@ impl OllamaProvider {
             .get_param("OLLAMA_HOST")
             .unwrap_or_else(|_| OLLAMA_HOST.to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("OLLAMA", Duration::from_secs(600))?;
 
         Ok(Self {
             client,
@@ -54,25 +53,7 @@ impl OllamaProvider {
 
     /// Get the base URL for Ollama API calls
     fn get_base_url(&self) -> Result<Url, ProviderError> {
-        // OLLAMA_HOST is sometimes just the 'host' or 'host:port' without a scheme
-        let base = if self.host.starts_with("http://") || self.host.starts_with("https://") {
-            self.host.clone()
-        } else {
-            format!("http://{}", self.host)
-        };
-
-        let mut base_url = Url::parse(&base)
-            .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
-
-        // Set the default port if missing
-        let explicit_default_port = self.host.ends_with(":80") || self.host.ends_with(":443");
-        if base_url.port().is_none() && !explicit_default_port {
-            base_url.set_port(Some(OLLAMA_DEFAULT_PORT)).map_err(|_| {
-                ProviderError::RequestFailed("Failed to set default port".to_string())
-            })?;
-        }
-
-        Ok(base_url)
+        base_url(&self.host)
     }
 
     async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
@@ -83,12 +64,35 @@ impl OllamaProvider {
             ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
         })?;
 
-        let response = self.client.post(url).json(&payload).send().await?;
+        let response = middleware::send(self.client.post_json(url, &payload)).await?;
 
         handle_response_openai_compat(response).await
     }
 }
 
+/// The base URL for Ollama API calls to `host`, the value of OLLAMA_HOST
+pub fn base_url(host: &str) -> Result<Url, ProviderError> {
+    // OLLAMA_HOST is sometimes just the 'host' or 'host:port' without a scheme
+    let base = if host.starts_with("http://") || host.starts_with("https://") {
+        host.to_string()
+    } else {
+        format!("http://{}", host)
+    };
+
+    let mut base_url = Url::parse(&base)
+        .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
+
+    // Set the default port if missing
+    let explicit_default_port = host.ends_with(":80") || host.ends_with(":443");
+    if base_url.port().is_none() && !explicit_default_port {
+        base_url
+            .set_port(Some(OLLAMA_DEFAULT_PORT))
+            .map_err(|_| ProviderError::RequestFailed("Failed to set default port".to_string()))?;
+    }
+
+    Ok(base_url)
+}
+
 #[async_trait]
 impl Provider for OllamaProvider {
     fn metadata() -> ProviderMetadata {
@@ -145,4 +149,15 @@ impl Provider for OllamaProvider {
         super::utils::emit_debug_trace(&self.model, &payload, &response, &usage);
         Ok((message, ProviderUsage::new(model, usage)))
     }
+
+    async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+        let base_url = self.get_base_url()?;
+        let url = base_url.join("api/tags").map_err(|e| {
+            ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
+        })?;
+
+        let response = middleware::send(self.client.get(url)).await?;
+        let data = handle_response_openai_compat(response).await?;
+        Ok(Some(parse_model_list(&data, "models", "name")?))
+    }
 }
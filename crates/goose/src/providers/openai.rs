@@ -1,14 +1,20 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{RequestBuilder, Response};
 use serde_json::Value;
-use std::collections::HashMap;
 use std::time::Duration;
 
+use super::api_keys::ApiKeys;
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
+use super::client_credentials::ClientCredentials;
 use super::errors::ProviderError;
 use super::formats::openai::{create_request, get_usage, response_to_message};
-use super::utils::{emit_debug_trace, get_model, handle_response_openai_compat, ImageFormat};
+use super::http::HttpClient;
+use super::middleware;
+use super::utils::{
+    emit_debug_trace, expand_path_template, get_model, handle_response_openai_compat,
+    parse_model_list, ImageFormat, MODEL_PLACEHOLDER,
+};
 use crate::message::Message;
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
@@ -27,14 +33,18 @@ pub const OPEN_AI_DOC_URL: &str = "https://platform.openai.com/docs/models";
 #[derive(Debug, serde::Serialize)]
 pub struct OpenAiProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
+    /// May contain a `{model}` placeholder for gateways with per model paths
     base_path: String,
-    api_key: String,
+    #[serde(skip)]
+    api_keys: ApiKeys,
     organization: Option<String>,
     project: Option<String>,
     model: ModelConfig,
-    custom_headers: Option<HashMap<String, String>>,
+    /// Used instead of the API key when the host issues tokens from an OAuth2 endpoint
+    #[serde(skip)]
+    oauth: Option<ClientCredentials>,
 }
 
 impl Default for OpenAiProvider {
@@ -47,7 +57,12 @@ impl Default for OpenAiProvider {
 impl OpenAiProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("OPENAI_API_KEY")?;
+        let oauth = ClientCredentials::from_config("OPENAI")?;
+        let api_keys = match oauth {
+            Some(_) => ApiKeys::from_config("OPENAI_API_KEY")
+                .unwrap_or_else(|_| ApiKeys::new("OPENAI_API_KEY", Vec::new())),
+            None => ApiKeys::from_config("OPENAI_API_KEY")?,
+        };
         let host: String = config
             .get_param("OPENAI_HOST")
             .unwrap_or_else(|_| "https://api.openai.com".to_string());
@@ -56,38 +71,54 @@ impl OpenAiProvider {
             .unwrap_or_else(|_| "v1/chat/completions".to_string());
         let organization: Option<String> = config.get_param("OPENAI_ORGANIZATION").ok();
         let project: Option<String> = config.get_param("OPENAI_PROJECT").ok();
-        let custom_headers: Option<HashMap<String, String>> = config
-            .get_secret("OPENAI_CUSTOM_HEADERS")
-            .ok()
-            .map(parse_custom_headers);
         let timeout_secs: u64 = config.get_param("OPENAI_TIMEOUT").unwrap_or(600);
-        let client = Client::builder()
-            .timeout(Duration::from_secs(timeout_secs))
-            .build()?;
+        let client = HttpClient::new("OPENAI", Duration::from_secs(timeout_secs))?;
 
         Ok(Self {
             client,
             host,
             base_path,
-            api_key,
+            api_keys,
             organization,
             project,
             model,
-            custom_headers,
+            oauth,
         })
     }
 
     async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
         let base_url = url::Url::parse(&self.host)
             .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
-        let url = base_url.join(&self.base_path).map_err(|e| {
+        let path = expand_path_template(&self.base_path, &self.model.model_name);
+        let url = base_url.join(&path).map_err(|e| {
             ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
         })?;
 
-        let mut request = self
-            .client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key));
+        let response = self
+            .send(|| self.client.post_json(url.clone(), &payload))
+            .await?;
+
+        handle_response_openai_compat(response).await
+    }
+
+    /// Send a request authorized with the OAuth token if configured, otherwise with the
+    /// current API key, moving to a backup key if it is rejected
+    async fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Response, ProviderError> {
+        match &self.oauth {
+            Some(oauth) => {
+                let token = oauth.token().await?;
+                middleware::send(self.add_headers(request(), &token)).await
+            }
+            None => {
+                self.api_keys
+                    .send(|key| self.add_headers(request(), key))
+                    .await
+            }
+        }
+    }
+
+    fn add_headers(&self, mut request: RequestBuilder, token: &str) -> RequestBuilder {
+        request = request.header("Authorization", format!("Bearer {}", token));
 
         // Add organization header if present
         if let Some(org) = &self.organization {
@@ -99,15 +130,16 @@ impl OpenAiProvider {
             request = request.header("OpenAI-Project", project);
         }
 
-        if let Some(custom_headers) = &self.custom_headers {
-            for (key, value) in custom_headers {
-                request = request.header(key, value);
-            }
-        }
-
-        let response = request.json(&payload).send().await?;
+        request
+    }
 
-        handle_response_openai_compat(response).await
+    /// The model listing endpoint sits next to the completions endpoint, so derive it
+    /// from the configured base path to support OpenAI compatible hosts with a prefix
+    fn models_path(&self) -> String {
+        match self.base_path.strip_suffix("chat/completions") {
+            Some(prefix) => format!("{}models", prefix),
+            None => "v1/models".to_string(),
+        }
     }
 }
 
@@ -132,6 +164,10 @@ impl Provider for OpenAiProvider {
                 ConfigKey::new("OPENAI_PROJECT", false, false, None),
                 ConfigKey::new("OPENAI_CUSTOM_HEADERS", false, true, None),
                 ConfigKey::new("OPENAI_TIMEOUT", false, false, Some("600")),
+                ConfigKey::new("OPENAI_OAUTH_TOKEN_URL", false, false, None),
+                ConfigKey::new("OPENAI_OAUTH_CLIENT_ID", false, false, None),
+                ConfigKey::new("OPENAI_OAUTH_CLIENT_SECRET", false, true, None),
+                ConfigKey::new("OPENAI_OAUTH_SCOPE", false, false, None),
             ],
         )
     }
@@ -169,15 +205,166 @@ impl Provider for OpenAiProvider {
         emit_debug_trace(&self.model, &payload, &response, &usage);
         Ok((message, ProviderUsage::new(model, usage)))
     }
+
+    async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+        // Gateways that route on the model in the path don't have a listing alongside it
+        if self.base_path.contains(MODEL_PLACEHOLDER) {
+            return Ok(None);
+        }
+
+        let base_url = url::Url::parse(&self.host)
+            .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
+        let url = base_url.join(&self.models_path()).map_err(|e| {
+            ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
+        })?;
+
+        let response = self.send(|| self.client.get(url.clone())).await?;
+        let data = handle_response_openai_compat(response).await?;
+        Ok(Some(parse_model_list(&data, "data", "id")?))
+    }
 }
 
-fn parse_custom_headers(s: String) -> HashMap<String, String> {
-    s.split(',')
-        .filter_map(|header| {
-            let mut parts = header.splitn(2, '=');
-            let key = parts.next().map(|s| s.trim().to_string())?;
-            let value = parts.next().map(|s| s.trim().to_string())?;
-            Some((key, value))
-        })
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn test_provider(host: String, base_path: &str) -> OpenAiProvider {
+        OpenAiProvider {
+            client: HttpClient::with_client(reqwest::Client::new(), Duration::from_secs(600)),
+            host,
+            base_path: base_path.to_string(),
+            api_keys: ApiKeys::new("OPENAI_API_KEY", vec!["test-key".to_string()]),
+            organization: None,
+            project: None,
+            model: ModelConfig::new(OPEN_AI_DEFAULT_MODEL.to_string()),
+            oauth: None,
+        }
+    }
+
+    #[test]
+    fn test_models_path() {
+        let host = "https://api.openai.com".to_string();
+        assert_eq!(
+            test_provider(host.clone(), "v1/chat/completions").models_path(),
+            "v1/models"
+        );
+        assert_eq!(
+            test_provider(host.clone(), "openai/v1/chat/completions").models_path(),
+            "openai/v1/models"
+        );
+        assert_eq!(test_provider(host, "custom").models_path(), "v1/models");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_supported_models() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(header("Authorization", "Bearer test-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "object": "list",
+                "data": [
+                    {"id": "gpt-4o-mini", "object": "model"},
+                    {"id": "gpt-4o", "object": "model"}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let provider = test_provider(mock_server.uri(), "v1/chat/completions");
+        let models = provider.fetch_supported_models().await.unwrap();
+        assert_eq!(
+            models,
+            Some(vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_path_template() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/openai/deployments/gpt-4o/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hello!"},
+                    "finish_reason": "stop"
+                }],
+                "model": "gpt-4o",
+                "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = test_provider(
+            mock_server.uri(),
+            "openai/deployments/{model}/chat/completions",
+        );
+        let (message, usage) = provider
+            .complete("system", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(message.as_concat_text(), "Hello!");
+        assert_eq!(usage.model, "gpt-4o");
+        assert_eq!(provider.fetch_supported_models().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_supported_models_unauthorized() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": {"message": "Invalid API key", "type": "invalid_request_error"}
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let provider = test_provider(mock_server.uri(), "v1/chat/completions");
+        let result = provider.fetch_supported_models().await;
+        assert!(matches!(result, Err(ProviderError::Authentication(_))));
+    }
+
+    #[tokio::test]
+    async fn test_oauth_token_replaces_api_key() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "gateway-token",
+                "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(header("Authorization", "Bearer gateway-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "object": "list",
+                "data": [{"id": "gpt-4o", "object": "model"}]
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let mut provider = test_provider(mock_server.uri(), "v1/chat/completions");
+        provider.oauth = Some(ClientCredentials::new(
+            format!("{}/oauth/token", mock_server.uri()),
+            "goose".to_string(),
+            "secret".to_string(),
+            None,
+            HttpClient::with_client(reqwest::Client::new(), Duration::from_secs(5)),
+        ));
+        for _ in 0..2 {
+            let models = provider.fetch_supported_models().await.unwrap();
+            assert_eq!(models, Some(vec!["gpt-4o".to_string()]));
+        }
+    }
 }
@@ -1,11 +1,12 @@
 use anyhow::{Error, Result};
 use async_trait::async_trait;
-use reqwest::Client;
 use serde_json::{json, Value};
 use std::time::Duration;
 
+use super::api_keys::ApiKeys;
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
 use super::errors::ProviderError;
+use super::http::HttpClient;
 use super::utils::{
     emit_debug_trace, get_model, handle_response_google_compat, handle_response_openai_compat,
     is_google_model,
@@ -26,9 +27,10 @@ pub const OPENROUTER_DOC_URL: &str = "https://openrouter.ai/models";
 #[derive(serde::Serialize)]
 pub struct OpenRouterProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
-    api_key: String,
+    #[serde(skip)]
+    api_keys: ApiKeys,
     model: ModelConfig,
 }
 
@@ -42,19 +44,17 @@ impl Default for OpenRouterProvider {
 impl OpenRouterProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("OPENROUTER_API_KEY")?;
+        let api_keys = ApiKeys::from_config("OPENROUTER_API_KEY")?;
         let host: String = config
             .get_param("OPENROUTER_HOST")
             .unwrap_or_else(|_| "https://openrouter.ai".to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()?;
+        let client = HttpClient::new("OPENROUTER", Duration::from_secs(600))?;
 
         Ok(Self {
             client,
             host,
-            api_key,
+            api_keys,
             model,
         })
     }
@@ -67,14 +67,14 @@ impl OpenRouterProvider {
         })?;
 
         let response = self
-            .client
-            .post(url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("HTTP-Referer", "https://block.github.io/goose")
-            .header("X-Title", "Goose")
-            .json(&payload)
-            .send()
+            .api_keys
+            .send(|key| {
+                self.client
+                    .post_json(url.clone(), &payload)
+                    .header("Authorization", format!("Bearer {}", key))
+                    .header("HTTP-Referer", "https://block.github.io/goose")
+                    .header("X-Title", "Goose")
+            })
             .await?;
 
         if is_google_model(&payload) {
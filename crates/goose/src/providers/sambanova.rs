@@ -1,14 +1,18 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::RequestBuilder;
 use serde_json::Value;
-use std::collections::HashMap;
 use std::time::Duration;
 
+use super::api_keys::ApiKeys;
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage};
 use super::errors::ProviderError;
 use super::formats::openai::{create_request, get_usage, response_to_message};
-use super::utils::{emit_debug_trace, get_model, handle_response_openai_compat, ImageFormat};
+use super::http::HttpClient;
+use super::utils::{
+    emit_debug_trace, expand_path_template, get_model, handle_response_openai_compat,
+    parse_model_list, ImageFormat,
+};
 use crate::message::Message;
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
@@ -21,12 +25,13 @@ pub const SAMBANOVA_DOC_URL: &str = "https://api.sambanova.ai";
 #[derive(Debug, serde::Serialize)]
 pub struct SambanovaProvider {
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     host: String,
+    /// May contain a `{model}` placeholder for gateways with per model paths
     base_path: String,
-    api_key: String,
+    #[serde(skip)]
+    api_keys: ApiKeys,
     model: ModelConfig,
-    custom_headers: Option<HashMap<String, String>>,
 }
 
 impl Default for SambanovaProvider {
@@ -39,54 +44,44 @@ impl Default for SambanovaProvider {
 impl SambanovaProvider {
     pub fn from_env(model: ModelConfig) -> Result<Self> {
         let config = crate::config::Config::global();
-        let api_key: String = config.get_secret("SAMBANOVA_API_KEY")?;
+        let api_keys = ApiKeys::from_config("SAMBANOVA_API_KEY")?;
         let host: String = config
             .get_param("SAMBANOVA_HOST")
             .unwrap_or_else(|_| "https://api.sambanova.ai".to_string());
         let base_path: String = config
             .get_param("SAMBANOVA_BASE_PATH")
             .unwrap_or_else(|_| "v1".to_string());
-        let custom_headers: Option<HashMap<String, String>> = config
-            .get_secret("SAMBANOVA_CUSTOM_HEADERS")
-            .ok()
-            .map(parse_custom_headers);
         let timeout_secs: u64 = config.get_param("SAMBANOVA_TIMEOUT").unwrap_or(600);
-        let client = Client::builder()
-            .timeout(Duration::from_secs(timeout_secs))
-            .build()?;
+        let client = HttpClient::new("SAMBANOVA", Duration::from_secs(timeout_secs))?;
 
         Ok(Self {
             client,
             host,
             base_path,
-            api_key,
+            api_keys,
             model,
-            custom_headers,
         })
     }
 
     async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
         let base_url = url::Url::parse(&self.host)
             .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
-        let url = base_url.join(&self.base_path).map_err(|e| {
+        let path = expand_path_template(&self.base_path, &self.model.model_name);
+        let url = base_url.join(&path).map_err(|e| {
             ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
         })?;
 
-        let mut request = self
-            .client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key));
-
-        if let Some(custom_headers) = &self.custom_headers {
-            for (key, value) in custom_headers {
-                request = request.header(key, value);
-            }
-        }
-
-        let response = request.json(&payload).send().await?;
+        let response = self
+            .api_keys
+            .send(|key| self.add_headers(self.client.post_json(url.clone(), &payload), key))
+            .await?;
 
         handle_response_openai_compat(response).await
     }
+
+    fn add_headers(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request.header("Authorization", format!("Bearer {}", api_key))
+    }
 }
 
 #[async_trait]
@@ -145,15 +140,19 @@ impl Provider for SambanovaProvider {
         emit_debug_trace(&self.model, &payload, &response, &usage);
         Ok((message, ProviderUsage::new(model, usage)))
     }
-}
 
-fn parse_custom_headers(s: String) -> HashMap<String, String> {
-    s.split(',')
-        .filter_map(|header| {
-            let mut parts = header.splitn(2, '=');
-            let key = parts.next().map(|s| s.trim().to_string())?;
-            let value = parts.next().map(|s| s.trim().to_string())?;
-            Some((key, value))
-        })
-        .collect()
+    async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+        let base_url = url::Url::parse(&self.host)
+            .map_err(|e| ProviderError::RequestFailed(format!("Invalid base URL: {e}")))?;
+        let url = base_url.join("v1/models").map_err(|e| {
+            ProviderError::RequestFailed(format!("Failed to construct endpoint URL: {e}"))
+        })?;
+
+        let response = self
+            .api_keys
+            .send(|key| self.add_headers(self.client.get(url.clone()), key))
+            .await?;
+        let data = handle_response_openai_compat(response).await?;
+        Ok(Some(parse_model_list(&data, "data", "id")?))
+    }
 }
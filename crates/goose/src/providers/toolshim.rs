@@ -31,6 +31,8 @@
 //!
 
 use super::errors::ProviderError;
+use super::http::HttpClient;
+use super::middleware;
 use super::ollama::OLLAMA_DEFAULT_PORT;
 use super::ollama::OLLAMA_HOST;
 use crate::message::{Message, MessageContent};
@@ -38,7 +40,6 @@ use crate::model::ModelConfig;
 use crate::providers::formats::openai::create_request;
 use anyhow::Result;
 use mcp_core::tool::{Tool, ToolCall};
-use reqwest::Client;
 use serde_json::{json, Value};
 use std::time::Duration;
 use uuid::Uuid;
@@ -62,16 +63,14 @@ pub trait ToolInterpreter {
 
 /// Ollama-specific implementation of the ToolInterpreter trait
 pub struct OllamaInterpreter {
-    client: Client,
+    client: HttpClient,
     base_url: String,
 }
 
 impl OllamaInterpreter {
     pub fn new() -> Result<Self, ProviderError> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(600))
-            .build()
-            .expect("Failed to create HTTP client");
+        let client = HttpClient::new("OLLAMA", Duration::from_secs(600))
+            .map_err(|e| ProviderError::ExecutionError(e.to_string()))?;
 
         let base_url = Self::get_ollama_base_url()?;
 
@@ -161,7 +160,7 @@ impl OllamaInterpreter {
 
         // tracing::warn!("payload: {}", serde_json::to_string_pretty(&payload).unwrap_or_default());
 
-        let response = self.client.post(&url).json(&payload).send().await?;
+        let response = middleware::send(self.client.post_json(&url, &payload)).await?;
 
         if !response.status().is_success() {
             let status = response.status();
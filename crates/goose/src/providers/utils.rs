@@ -1,6 +1,7 @@
 use super::base::Usage;
 use super::errors::GoogleErrorCode;
 use crate::model::ModelConfig;
+use crate::redaction::Redactor;
 use anyhow::Result;
 use base64::Engine;
 use regex::Regex;
@@ -11,6 +12,7 @@ use std::io::Read;
 use std::path::Path;
 
 use crate::providers::errors::{OpenAIError, ProviderError};
+use crate::providers::images::{fit_image, ImageLimits};
 use mcp_core::content::ImageContent;
 
 #[derive(serde::Deserialize)]
@@ -203,6 +205,43 @@ pub fn get_model(data: &Value) -> String {
     }
 }
 
+/// Extract the model names from a model listing response, e.g. `{"data": [{"id": "gpt-4o"}]}`
+/// for OpenAI compatible endpoints or `{"models": [{"name": "qwen2.5"}]}` for Ollama.
+/// The names are returned sorted so they display consistently.
+pub fn parse_model_list(
+    data: &Value,
+    list_key: &str,
+    name_key: &str,
+) -> Result<Vec<String>, ProviderError> {
+    let entries = data
+        .get(list_key)
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| {
+            ProviderError::RequestFailed(format!(
+                "Model list response is missing the '{}' array",
+                list_key
+            ))
+        })?;
+
+    let mut models: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| entry.get(name_key).and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+        .collect();
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+/// Placeholder for the model name in configurable endpoint paths
+pub const MODEL_PLACEHOLDER: &str = "{model}";
+
+/// Expand an endpoint path template, for gateways that route on per model paths like
+/// `openai/deployments/{model}/chat/completions`. Paths without a placeholder are unchanged.
+pub fn expand_path_template(template: &str, model: &str) -> String {
+    template.replace(MODEL_PLACEHOLDER, model)
+}
+
 /// Check if a file is actually an image by examining its magic bytes
 fn is_image_file(path: &Path) -> bool {
     if let Ok(mut file) = std::fs::File::open(path) {
@@ -281,11 +320,13 @@ pub fn load_image_file(path: &str) -> Result<ImageContent, ProviderError> {
     // Convert to base64
     let data = base64::prelude::BASE64_STANDARD.encode(&bytes);
 
-    Ok(ImageContent {
+    let image = ImageContent {
         mime_type: mime_type.to_string(),
         data,
         annotations: None,
-    })
+    };
+    // The provider isn't known here, so fit the image to what any of them accepts
+    Ok(fit_image(&image, &ImageLimits::default()).unwrap_or(image))
 }
 
 pub fn unescape_json_values(value: &Value) -> Value {
@@ -323,10 +364,19 @@ pub fn emit_debug_trace(
     response: &Value,
     usage: &Usage,
 ) {
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return;
+    }
+    // Secrets can come back in responses too, e.g. when the model repeats a tool's output
+    let redactor = Redactor::global();
+    let mut payload = payload.clone();
+    redactor.redact_value(&mut payload);
+    let mut response = response.clone();
+    redactor.redact_value(&mut response);
     tracing::debug!(
         model_config = %serde_json::to_string_pretty(model_config).unwrap_or_default(),
-        input = %serde_json::to_string_pretty(payload).unwrap_or_default(),
-        output = %serde_json::to_string_pretty(response).unwrap_or_default(),
+        input = %serde_json::to_string_pretty(&payload).unwrap_or_default(),
+        output = %serde_json::to_string_pretty(&response).unwrap_or_default(),
         input_tokens = ?usage.input_tokens.unwrap_or_default(),
         output_tokens = ?usage.output_tokens.unwrap_or_default(),
         total_tokens = ?usage.total_tokens.unwrap_or_default(),
@@ -543,4 +593,40 @@ mod tests {
             assert_eq!(result, expected_status);
         }
     }
+
+    #[test]
+    fn test_parse_model_list() {
+        let openai = json!({
+            "object": "list",
+            "data": [
+                {"id": "gpt-4o-mini", "object": "model"},
+                {"id": "gpt-4o", "object": "model"},
+                {"object": "model"}
+            ]
+        });
+        assert_eq!(
+            parse_model_list(&openai, "data", "id").unwrap(),
+            vec!["gpt-4o", "gpt-4o-mini"]
+        );
+
+        let ollama = json!({"models": [{"name": "qwen2.5:latest"}, {"name": "llama3.2"}]});
+        assert_eq!(
+            parse_model_list(&ollama, "models", "name").unwrap(),
+            vec!["llama3.2", "qwen2.5:latest"]
+        );
+
+        assert!(parse_model_list(&json!({"error": "nope"}), "data", "id").is_err());
+    }
+
+    #[test]
+    fn test_expand_path_template() {
+        assert_eq!(
+            expand_path_template("openai/deployments/{model}/chat/completions", "gpt-4o"),
+            "openai/deployments/gpt-4o/chat/completions"
+        );
+        assert_eq!(
+            expand_path_template("v1/chat/completions", "gpt-4o"),
+            "v1/chat/completions"
+        );
+    }
 }
@@ -0,0 +1,407 @@
+//! Success criteria that are checked when a recipe run is done, not only told to the agent
+//!
+//! A criterion is text, which the agent is asked to check itself, or a check:
+//!
+//! ```yaml
+//! success_criteria:
+//!   - The findings are grouped by severity
+//!   - command: cargo audit
+//!   - file: audit.md
+//!     matches: "RUSTSEC-\\d{4}-\\d{4}"
+//!   - matches: "(?i)\\d+ vulnerable dependencies"
+//! ```
+//!
+//! A `command` has to exit with 0 and a `file` has to exist, both in the working directory of
+//! the run. `matches` is a regex that the output of the command or the contents of the file
+//! has to match, or with neither, the agent's last answer. The checks run once the agent is
+//! done with the last step, and those that fail are sent back to it to keep going, up to
+//! [`MAX_CHECK_ROUNDS`] times.
+//!
+//! Parameters are filled into a command quoted for the shell, so a value is always one
+//! argument. A `file` is a relative path that can't lead out of the working directory. Commands
+//! run without asking, so only recipes loaded from a local file run them: for a recipe from a
+//! URL or given to the server, the agent is told the command like a text criterion, and runs it
+//! with its own tools, which ask as any other call does.
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+use std::process::Stdio;
+use std::time::Duration;
+
+use super::Recipe;
+use crate::schedule::shell;
+
+/// The most times failed checks are sent back to the agent
+pub const MAX_CHECK_ROUNDS: usize = 3;
+
+/// How long a check's command can run before it fails
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How much of a command's output is sent back with a failed check, from the end
+const MAX_OUTPUT_LINES: usize = 40;
+const MAX_OUTPUT_CHARS: usize = 4000;
+
+/// What has to be true for the task to be done
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum SuccessCriterion {
+    /// Told to the agent, which checks it itself
+    Text(String),
+    /// Run when the agent is done
+    Check(SuccessCheck),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SuccessCheck {
+    /// A shell command that has to exit with 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// A path that has to exist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// A regex that the output of the command, the contents of the file, or else the agent's
+    /// last answer has to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<String>,
+}
+
+impl SuccessCriterion {
+    /// The text of the criterion that is filled in with parameters
+    pub(super) fn texts(&self) -> Vec<&str> {
+        match self {
+            SuccessCriterion::Text(text) => vec![text.as_str()],
+            SuccessCriterion::Check(check) => check
+                .command
+                .iter()
+                .chain(&check.file)
+                .chain(&check.matches)
+                .map(String::as_str)
+                .collect(),
+        }
+    }
+
+    /// The criterion filled in with `render`, and a command with `render_command`, which quotes
+    /// the values for the shell
+    pub(super) fn render(
+        &self,
+        render: impl Fn(&str) -> Result<String>,
+        render_command: impl Fn(&str) -> Result<String>,
+    ) -> Result<Self> {
+        let render_option = |text: &Option<String>| text.as_deref().map(&render).transpose();
+        Ok(match self {
+            SuccessCriterion::Text(text) => SuccessCriterion::Text(render(text)?),
+            SuccessCriterion::Check(check) => SuccessCriterion::Check(SuccessCheck {
+                command: check.command.as_deref().map(render_command).transpose()?,
+                file: render_option(&check.file)?,
+                matches: render_option(&check.matches)?,
+            }),
+        })
+    }
+
+    /// The criterion as the agent is told it
+    pub fn describe(&self) -> String {
+        match self {
+            SuccessCriterion::Text(text) => text.clone(),
+            SuccessCriterion::Check(check) => check.describe(),
+        }
+    }
+}
+
+impl SuccessCheck {
+    pub(super) fn validate(&self) -> Result<()> {
+        match (&self.command, &self.file, &self.matches) {
+            (Some(_), Some(_), _) => {
+                return Err(anyhow!("A success check has a command or a file, not both"))
+            }
+            (None, None, None) => {
+                return Err(anyhow!(
+                    "A success check needs a command, a file or a regex to match"
+                ))
+            }
+            _ => {}
+        }
+        if let Some(pattern) = &self.matches {
+            Regex::new(pattern)
+                .map_err(|e| anyhow!("The success check regex {} is invalid: {}", pattern, e))?;
+        }
+        if let Some(file) = &self.file {
+            check_path(file).map_err(|e| anyhow!(e))?;
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let regex = self.matches.as_deref();
+        match (&self.command, &self.file) {
+            (Some(command), _) => match regex {
+                Some(regex) => format!(
+                    "`{}` exits with 0 and its output matches the regex `{}`",
+                    command, regex
+                ),
+                None => format!("`{}` exits with 0", command),
+            },
+            (None, Some(file)) => match regex {
+                Some(regex) => format!("{} exists and matches the regex `{}`", file, regex),
+                None => format!("{} exists", file),
+            },
+            (None, None) => format!(
+                "Your last answer matches the regex `{}`",
+                regex.unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Run the check in `working_dir`, failing with what went wrong
+    pub async fn run(&self, working_dir: &Path, answer: &str) -> Result<(), String> {
+        let regex = match &self.matches {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        let matches = |text: &str| regex.as_ref().is_none_or(|regex| regex.is_match(text));
+
+        if let Some(command) = &self.command {
+            let mut shell = shell(command);
+            shell
+                .current_dir(working_dir)
+                .stdin(Stdio::null())
+                .kill_on_drop(true);
+            let output = match tokio::time::timeout(COMMAND_TIMEOUT, shell.output()).await {
+                Ok(Ok(output)) => output,
+                Ok(Err(e)) => return Err(format!("`{}` couldn't be run: {}", command, e)),
+                Err(_) => {
+                    return Err(format!(
+                        "`{}` didn't finish within {} minutes",
+                        command,
+                        COMMAND_TIMEOUT.as_secs() / 60
+                    ))
+                }
+            };
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            if !output.status.success() {
+                let status = output
+                    .status
+                    .code()
+                    .map(|code| format!("exited with {}", code))
+                    .unwrap_or_else(|| "was stopped".to_string());
+                return Err(format!("`{}` {}:\n{}", command, status, tail(&text)));
+            }
+            if !matches(&text) {
+                return Err(format!(
+                    "The output of `{}` doesn't match the regex `{}`:\n{}",
+                    command,
+                    self.matches.as_deref().unwrap_or_default(),
+                    tail(&text)
+                ));
+            }
+        } else if let Some(file) = &self.file {
+            check_path(file)?;
+            let path = working_dir.join(file);
+            if !path.exists() {
+                return Err(format!("{} doesn't exist", file));
+            }
+            if regex.is_some() {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{} couldn't be read: {}", file, e))?;
+                if !matches(&contents) {
+                    return Err(format!(
+                        "{} doesn't match the regex `{}`",
+                        file,
+                        self.matches.as_deref().unwrap_or_default()
+                    ));
+                }
+            }
+        } else if !matches(answer) {
+            return Err(format!(
+                "Your last answer doesn't match the regex `{}`",
+                self.matches.as_deref().unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Recipe {
+    /// The success criteria that are checks that run, which are those with a command only when
+    /// the recipe was loaded from a local file
+    pub fn checks(&self) -> impl Iterator<Item = &SuccessCheck> {
+        self.success_criteria
+            .iter()
+            .filter_map(|criterion| match criterion {
+                SuccessCriterion::Check(check) if check.command.is_none() || self.dir.is_some() => {
+                    Some(check)
+                }
+                SuccessCriterion::Check(_) | SuccessCriterion::Text(_) => None,
+            })
+    }
+
+    /// What is wrong with each check that fails, run in `working_dir` after the agent ended
+    /// with `answer`
+    pub async fn failed_checks(&self, working_dir: &Path, answer: &str) -> Vec<String> {
+        if self.dir.is_none() {
+            for criterion in &self.success_criteria {
+                if let SuccessCriterion::Check(SuccessCheck {
+                    command: Some(command),
+                    ..
+                }) = criterion
+                {
+                    tracing::warn!(
+                        "Didn't run `{}`, as only recipes loaded from a local file run commands",
+                        command
+                    );
+                }
+            }
+        }
+        let mut failed = Vec::new();
+        for check in self.checks() {
+            if let Err(failure) = check.run(working_dir, answer).await {
+                failed.push(failure);
+            }
+        }
+        failed
+    }
+}
+
+/// The message that sends the checks that failed back to the agent
+pub fn retry_message(failed: &[String]) -> String {
+    let failed: Vec<String> = failed
+        .iter()
+        .map(|failure| format!("- {}", failure.replace('\n', "\n  ")))
+        .collect();
+    format!(
+        "The task isn't done yet, as these checks of its success criteria fail:\n{}\n\n\
+        Fix what makes them fail and keep going until they pass.",
+        failed.join("\n")
+    )
+}
+
+/// `value` quoted as one argument for the shell that runs checks
+pub(super) fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Check that the `file` of a check is a path in the working directory
+fn check_path(file: &str) -> Result<(), String> {
+    let relative = Path::new(file)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !relative {
+        return Err(format!(
+            "The file of a check has to be a path in the working directory, not {}",
+            file
+        ));
+    }
+    Ok(())
+}
+
+/// The end of a command's output, which is where its errors usually are
+fn tail(output: &str) -> String {
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    let mut tail = lines[lines.len().saturating_sub(MAX_OUTPUT_LINES)..].join("\n");
+    if tail.len() > MAX_OUTPUT_CHARS {
+        let mut start = tail.len() - MAX_OUTPUT_CHARS;
+        while !tail.is_char_boundary(start) {
+            start += 1;
+        }
+        tail = tail[start..].to_string();
+    }
+    if tail.len() < output.trim_end().len() {
+        format!("...\n{}", tail)
+    } else {
+        tail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(yaml: &str) -> SuccessCheck {
+        match serde_yaml::from_str(yaml).unwrap() {
+            SuccessCriterion::Check(check) => check,
+            SuccessCriterion::Text(text) => panic!("expected a check, got {}", text),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let criterion: SuccessCriterion =
+            serde_yaml::from_str("Every dependency is listed").unwrap();
+        assert_eq!(
+            criterion,
+            SuccessCriterion::Text("Every dependency is listed".to_string())
+        );
+        assert_eq!(
+            check("{file: audit.md, matches: CVE}").describe(),
+            "audit.md exists and matches the regex `CVE`"
+        );
+        // A misspelled field isn't taken as a check that passes
+        assert!(serde_yaml::from_str::<SuccessCriterion>("{comand: cargo test}").is_err());
+
+        assert!(check("{command: make, file: out}").validate().is_err());
+        assert!(check("{}").validate().is_err());
+        assert!(check("{matches: '(unclosed'}").validate().is_err());
+        assert!(check("{matches: done}").validate().is_ok());
+        assert!(check("{file: /etc/passwd}").validate().is_err());
+        assert!(check("{file: ../secrets.txt}").validate().is_err());
+        assert!(check("{file: ./out/report.md}").validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("audit.md"), "RUSTSEC-2024-0001\n").unwrap();
+        let run = |yaml: &str| {
+            let check = check(yaml);
+            let dir = dir.path().to_path_buf();
+            async move { check.run(&dir, "All 3 tests pass").await }
+        };
+
+        assert!(run("{file: audit.md}").await.is_ok());
+        assert!(run("{file: audit.md, matches: 'RUSTSEC-\\d{4}'}")
+            .await
+            .is_ok());
+        assert_eq!(
+            run("{file: missing.md}").await,
+            Err("missing.md doesn't exist".to_string())
+        );
+        assert!(run("{file: audit.md, matches: CVE}").await.is_err());
+        assert!(run("{matches: '\\d+ tests pass'}").await.is_ok());
+        assert!(run("{matches: failed}").await.is_err());
+        assert!(run("{file: ../audit.md}").await.is_err());
+
+        if cfg!(unix) {
+            assert!(run("{command: test -f audit.md}").await.is_ok());
+            assert!(run("{command: 'echo ok', matches: ^ok}").await.is_ok());
+            let failed = run("{command: 'echo broken; exit 2'}").await.unwrap_err();
+            assert_eq!(failed, "`echo broken; exit 2` exited with 2:\nbroken");
+        }
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        if cfg!(unix) {
+            assert_eq!(shell_quote("a.md"), "'a.md'");
+            assert_eq!(shell_quote("x'; rm -rf ~; '"), "'x'\\''; rm -rf ~; '\\'''");
+        }
+    }
+
+    #[test]
+    fn test_tail() {
+        let output: String = (1..=100).map(|n| format!("line {}\n", n)).collect();
+        let cut = tail(&output);
+        assert!(cut.starts_with("...\nline 61\n"));
+        assert!(cut.ends_with("line 100"));
+        assert_eq!(tail("short\n"), "short");
+    }
+}
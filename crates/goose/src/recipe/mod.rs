@@ -0,0 +1,747 @@
+//! Recipes, repeatable tasks defined in YAML
+//!
+//! A recipe says what to do, with which tools, and when it is done, so a task can be run the
+//! same way again and shared instead of living in someone's shell history:
+//!
+//! ```yaml
+//! name: dependency-audit
+//! description: Audit a project's dependencies for known vulnerabilities
+//! parameters:
+//!   - key: manifest
+//!     description: The dependency manifest to audit
+//!     default: Cargo.toml
+//! instructions: You are auditing the dependencies listed in {{ manifest }}. Don't change them.
+//! prompt: List the dependencies in {{ manifest }} that have known vulnerabilities
+//! extensions: [developer]
+//! tools: [fetch__fetch]
+//! success_criteria:
+//!   - Every vulnerable dependency is listed with its advisory
+//! steps:
+//!   - Suggest the smallest upgrade that fixes each vulnerability
+//!   - Save the findings in audit.md
+//! ```
+//!
+//! The prompt is sent first and each step after the agent is done with the one before, all in
+//! one session. Parameters are filled into the instructions, prompt, steps and criteria as
+//! `{{ key }}`, and every parameter without a default has to be given a value. When a recipe
+//! lists `extensions` or `tools`, the agent can only use the tools of those extensions and the
+//! tools named, Goose's own `platform` tools included. The instructions and the success criteria are added to the system prompt, and
+//! a `system_prompt` replaces the built-in one.
+//!
+//! Parameters are strings unless they have a `type`. An `enum` takes one of its `options`, and
+//! a `file` takes a path and is filled in with the contents of the file, or with nothing when
+//! the path is empty. The default of a `file` is a path in the recipe's own directory, so only
+//! recipes loaded from a local file can have one other than the empty path:
+//!
+//! ```yaml
+//! parameters:
+//!   - key: severity
+//!     type: enum
+//!     options: [low, high, critical]
+//!     default: high
+//!   - key: policy
+//!     type: file
+//!     default: ""
+//! ```
+//!
+//! With a `response`, each answer is a value of its JSON schema, which the agent checks and
+//! gives with the `platform__final_output` tool. A program running the recipe reads the value
+//! of the last answer as the result:
+//!
+//! ```yaml
+//! response:
+//!   json_schema:
+//!     type: object
+//!     required: [vulnerable]
+//!     properties:
+//!       vulnerable: {type: array, items: {type: string}}
+//! ```
+//!
+//! Success criteria can also be checks, a command that has to exit with 0, a file that has to
+//! exist, or a regex the output has to match, which are run once the agent is done and sent
+//! back to it while they fail. See [`checks`].
+//!
+//! The text is a [minijinja](https://docs.rs/minijinja) template, so it can use conditionals
+//! like `{% if policy %}` and filters, and include shared templates with
+//! `{% include "common.md" %}`. Included templates are read from next to the recipe, or given
+//! inline under `templates`.
+use anyhow::{anyhow, Result};
+use minijinja::{Environment, UndefinedBehavior};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+use crate::agents::final_output::FinalOutput;
+
+mod checks;
+use checks::shell_quote;
+pub use checks::{retry_message, SuccessCheck, SuccessCriterion, MAX_CHECK_ROUNDS};
+
+/// The templates a template includes, imports or extends, by name
+static INCLUDES: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\{%-?\s*(?:include|import|from|extends)\s+["']([^"']+)["']"#)
+        .expect("the pattern is valid")
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Recipe {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Values to fill into the recipe when it is run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<RecipeParameter>,
+    /// Added to the system prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// Replaces the built-in system prompt while the recipe runs. It is a template of the
+    /// extensions and the time like the built-in one, and isn't filled in with parameters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// The first message sent
+    pub prompt: String,
+    /// The extensions whose tools the agent may use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<String>>,
+    /// Other tools the agent may use, by their full names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// What has to be true for the task to be done
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub success_criteria: Vec<SuccessCriterion>,
+    /// Messages sent after the prompt, one at a time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+    /// The form of the result, for a program that runs the recipe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<RecipeResponse>,
+    /// Templates the recipe's text can include, by name. Loading a recipe adds the files next
+    /// to it that it includes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, String>,
+    /// The directory of the recipe's file, where the defaults of its file parameters are read
+    /// from, when it was loaded from one
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+/// What a recipe run ends with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RecipeResponse {
+    /// The JSON schema the agent's answer has to match, given as the final output
+    pub json_schema: Value,
+}
+
+/// What a parameter's value is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    /// Any text
+    #[default]
+    String,
+    /// One of the parameter's `options`
+    Enum,
+    /// The path of a text file, whose contents are filled in
+    File,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RecipeParameter {
+    /// The name the parameter is filled in by, as `{{ key }}`
+    pub key: String,
+    #[serde(rename = "type", default)]
+    pub kind: ParameterType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The value when none is given, which makes the parameter optional
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// The values an enum parameter can take
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+}
+
+impl RecipeParameter {
+    /// What to fill in for `value`
+    fn resolve(&self, value: &str) -> Result<String> {
+        match self.kind {
+            ParameterType::String => Ok(value.to_string()),
+            ParameterType::Enum if self.options.iter().any(|option| option == value) => {
+                Ok(value.to_string())
+            }
+            ParameterType::Enum => Err(anyhow!(
+                "{} is one of {}, not {}",
+                self.key,
+                self.options.join(", "),
+                value
+            )),
+            // No path leaves an optional file out
+            ParameterType::File if value.is_empty() => Ok(String::new()),
+            ParameterType::File => std::fs::read_to_string(value)
+                .map_err(|e| anyhow!("Failed to read {} for {}: {}", value, self.key, e)),
+        }
+    }
+
+    /// What to fill in for the parameter's `default`, reading a file default from `dir`, the
+    /// recipe's directory
+    ///
+    /// The recipe's author chose the default, so it can't read files outside the recipe's
+    /// directory, and a recipe that isn't a local file can't read any.
+    fn resolve_default(&self, default: &str, dir: Option<&Path>) -> Result<String> {
+        if self.kind != ParameterType::File || default.is_empty() {
+            return self.resolve(default);
+        }
+        let dir = dir.ok_or_else(|| {
+            anyhow!(
+                "The default of {} is a file, which only recipes loaded from a local file can \
+                have, give {} a value",
+                self.key,
+                self.key
+            )
+        })?;
+        check_default_path(&self.key, default)?;
+        let path = dir.join(default);
+        // A link in the directory can't lead outside it either
+        let inside = path
+            .canonicalize()
+            .and_then(|path| Ok(path.starts_with(dir.canonicalize()?)))
+            .map_err(|e| anyhow!("Failed to read {} for {}: {}", default, self.key, e))?;
+        if !inside {
+            return Err(anyhow!(
+                "The default of {} has to be a file in the recipe's directory, not {}",
+                self.key,
+                default
+            ));
+        }
+        self.resolve(&path.to_string_lossy())
+    }
+
+    /// A value to check the templates with
+    fn placeholder(&self) -> String {
+        match self.kind {
+            ParameterType::Enum => self.options.first().cloned().unwrap_or_default(),
+            ParameterType::String | ParameterType::File => self.key.clone(),
+        }
+    }
+}
+
+impl Recipe {
+    /// Parse a recipe from YAML, or JSON
+    ///
+    /// The templates it includes have to be in its `templates`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let recipe: Recipe = serde_yaml::from_str(text)?;
+        recipe.validate()?;
+        Ok(recipe)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("The recipe has no name"));
+        }
+        let mut keys = Vec::new();
+        for parameter in &self.parameters {
+            let key = parameter.key.as_str();
+            let valid = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(anyhow!(
+                    "The parameter key '{}' isn't valid, use letters, digits and underscores",
+                    key
+                ));
+            }
+            if keys.contains(&key) {
+                return Err(anyhow!("The parameter {} is declared twice", key));
+            }
+            keys.push(key);
+            match parameter.kind {
+                ParameterType::Enum if parameter.options.is_empty() => {
+                    return Err(anyhow!("The enum parameter {} has no options", key));
+                }
+                ParameterType::Enum => {
+                    if let Some(default) = &parameter.default {
+                        parameter.resolve(default)?;
+                    }
+                }
+                _ if !parameter.options.is_empty() => {
+                    return Err(anyhow!("Only enum parameters have options, not {}", key));
+                }
+                ParameterType::File => {
+                    if let Some(default) = parameter.default.as_deref().filter(|d| !d.is_empty()) {
+                        check_default_path(key, default)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(response) = &self.response {
+            FinalOutput::new(response.json_schema.clone())?;
+        }
+        if let Some(system_prompt) = &self.system_prompt {
+            Environment::new()
+                .template_from_str(system_prompt)
+                .map_err(|e| anyhow!("The system prompt is invalid: {}", e))?;
+        }
+        // Check the templates with a placeholder for each parameter
+        let placeholders = self
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.key.clone(), parameter.placeholder()))
+            .collect();
+        let filled = self.fill(&placeholders)?;
+        for check in filled.checks() {
+            check.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Read a recipe from a file, or from an http(s) URL, with the templates it includes from
+    /// next to it
+    pub async fn load(source: &str) -> Result<Self> {
+        let mut recipe: Recipe = serde_yaml::from_str(&read_source(source, None).await?)
+            .map_err(|e| anyhow!("The recipe {} is invalid: {}", source, e))?;
+
+        let mut pending: Vec<String> = recipe.texts().flat_map(included).collect();
+        while let Some(name) = pending.pop() {
+            if recipe.templates.contains_key(&name) {
+                continue;
+            }
+            let template = read_source(source, Some(&name)).await?;
+            pending.extend(included(&template));
+            recipe.templates.insert(name, template);
+        }
+
+        recipe
+            .validate()
+            .map_err(|e| anyhow!("The recipe {} is invalid: {}", source, e))?;
+        if !is_url(source) {
+            recipe.dir = Path::new(source)
+                .parent()
+                .map(|dir| match dir.as_os_str().is_empty() {
+                    true => PathBuf::from("."),
+                    false => dir.to_path_buf(),
+                });
+        }
+        Ok(recipe)
+    }
+
+    /// The recipe with `values` filled in for its parameters
+    ///
+    /// Parameters without a value take their default, and it is an error to leave out one
+    /// without a default, to give a value for a parameter the recipe doesn't have, or one an
+    /// enum doesn't list. File parameters are filled in with the contents of the file, or
+    /// nothing when the path is empty.
+    pub fn with_parameters(&self, values: &HashMap<String, String>) -> Result<Self> {
+        if let Some(unknown) = values
+            .keys()
+            .find(|key| !self.parameters.iter().any(|p| p.key == **key))
+        {
+            return Err(anyhow!(
+                "The recipe {} has no parameter {}",
+                self.name,
+                unknown
+            ));
+        }
+        let mut filled = BTreeMap::new();
+        let mut missing = Vec::new();
+        for parameter in &self.parameters {
+            let value = match (values.get(&parameter.key), &parameter.default) {
+                (Some(value), _) => parameter.resolve(value)?,
+                (None, Some(default)) => parameter.resolve_default(default, self.dir.as_deref())?,
+                (None, None) => {
+                    missing.push(parameter.key.as_str());
+                    continue;
+                }
+            };
+            filled.insert(parameter.key.clone(), value);
+        }
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "The recipe {} needs a value for {}",
+                self.name,
+                missing.join(", ")
+            ));
+        }
+        self.fill(&filled)
+    }
+
+    /// The text of the recipe that is filled in
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        self.instructions
+            .iter()
+            .chain(std::iter::once(&self.prompt))
+            .map(String::as_str)
+            .chain(
+                self.success_criteria
+                    .iter()
+                    .flat_map(SuccessCriterion::texts),
+            )
+            .chain(self.steps.iter().map(String::as_str))
+    }
+
+    fn fill(&self, values: &BTreeMap<String, String>) -> Result<Self> {
+        let mut environment = Environment::new();
+        environment.set_undefined_behavior(UndefinedBehavior::Strict);
+        for (name, template) in &self.templates {
+            environment
+                .add_template(name, template)
+                .map_err(|e| anyhow!("The template {} is invalid: {}", name, e))?;
+        }
+        let render_with = |template: &str, values: &BTreeMap<String, String>| {
+            environment
+                .render_str(template, values)
+                .map_err(|e| anyhow!("Failed to fill in '{}': {}", template, e))
+        };
+        let render = |template: &str| render_with(template, values);
+        // A command is run by the shell, where each value has to stay one argument
+        let quoted: BTreeMap<String, String> = values
+            .iter()
+            .map(|(key, value)| (key.clone(), shell_quote(value)))
+            .collect();
+        Ok(Self {
+            instructions: self.instructions.as_deref().map(render).transpose()?,
+            prompt: render(&self.prompt)?,
+            success_criteria: self
+                .success_criteria
+                .iter()
+                .map(|criterion| {
+                    criterion.render(render, |template| render_with(template, &quoted))
+                })
+                .collect::<Result<_>>()?,
+            steps: self
+                .steps
+                .iter()
+                .map(|step| render(step))
+                .collect::<Result<_>>()?,
+            ..self.clone()
+        })
+    }
+
+    /// The messages to send, the prompt and then the steps
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.prompt.as_str()).chain(self.steps.iter().map(String::as_str))
+    }
+
+    /// What the recipe adds to the system prompt
+    pub fn system_prompt_additions(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(instructions) = &self.instructions {
+            parts.push(instructions.clone());
+        }
+        if !self.success_criteria.is_empty() {
+            let criteria: Vec<String> = self
+                .success_criteria
+                .iter()
+                .map(|criterion| format!("- {}", criterion.describe()))
+                .collect();
+            parts.push(format!(
+                "The task is done when all of these are true. Check each of them before you say \
+                you are done, and say which aren't met if you can't meet them:\n{}",
+                criteria.join("\n")
+            ));
+        }
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+
+    /// Whether the recipe lets the agent use the tool `name`
+    pub fn allows_tool(&self, name: &str) -> bool {
+        if self.extensions.is_none() && self.tools.is_none() {
+            return true;
+        }
+        let extension = name.split_once("__").map(|(extension, _)| extension);
+        self.extensions
+            .iter()
+            .flatten()
+            .any(|allowed| Some(allowed.as_str()) == extension)
+            || self.tools.iter().flatten().any(|allowed| allowed == name)
+    }
+}
+
+/// The names of the templates `template` includes
+fn included(template: &str) -> Vec<String> {
+    INCLUDES
+        .captures_iter(template)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// Whether `source` is a URL rather than a local file
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Check that the default `path` of the file parameter `key` is in the recipe's directory
+fn check_default_path(key: &str, path: &str) -> Result<()> {
+    let relative = Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !relative {
+        return Err(anyhow!(
+            "The default of {} has to be a path in the recipe's directory, not {}",
+            key,
+            path
+        ));
+    }
+    Ok(())
+}
+
+/// Read the recipe at `source`, or the template `name` next to it
+async fn read_source(source: &str, name: Option<&str>) -> Result<String> {
+    if let Some(name) = name {
+        // Includes stay next to the recipe, so a recipe from a URL can't read local files
+        let relative = Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !relative {
+            return Err(anyhow!(
+                "The recipe {} includes {}, which isn't a path next to it",
+                source,
+                name
+            ));
+        }
+    }
+    if is_url(source) {
+        let mut url = reqwest::Url::parse(source)?;
+        if let Some(name) = name {
+            url = url.join(name)?;
+        }
+        Ok(reqwest::get(url).await?.error_for_status()?.text().await?)
+    } else {
+        let path = match name {
+            Some(name) => Path::new(source).with_file_name(name),
+            None => Path::new(source).to_path_buf(),
+        };
+        std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUDIT: &str = r#"
+name: dependency-audit
+parameters:
+  - key: manifest
+    default: Cargo.toml
+  - key: report
+instructions: You are auditing {{ manifest }}.
+prompt: List the vulnerable dependencies in {{ manifest }}
+extensions: [developer]
+tools: [fetch__fetch]
+success_criteria:
+  - "{{ report }} lists every vulnerable dependency"
+  - file: "{{ report }}"
+steps:
+  - Save the findings in {{ report }}
+"#;
+
+    #[test]
+    fn test_with_parameters() -> Result<()> {
+        let recipe = Recipe::parse(AUDIT)?;
+        assert!(recipe.with_parameters(&HashMap::new()).is_err());
+        let values = HashMap::from([("report".to_string(), "audit.md".to_string())]);
+        let filled = recipe.with_parameters(&values)?;
+        assert_eq!(
+            filled.messages().collect::<Vec<_>>(),
+            vec![
+                "List the vulnerable dependencies in Cargo.toml",
+                "Save the findings in audit.md"
+            ]
+        );
+        let system_prompt = filled.system_prompt_additions().unwrap();
+        assert!(system_prompt.starts_with("You are auditing Cargo.toml."));
+        assert!(system_prompt
+            .ends_with("- audit.md lists every vulnerable dependency\n- audit.md exists"));
+        assert_eq!(
+            filled.checks().next().unwrap().file.as_deref(),
+            Some("audit.md")
+        );
+
+        let unknown = HashMap::from([("branch".to_string(), "main".to_string())]);
+        assert!(recipe.with_parameters(&unknown).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_checks() -> Result<()> {
+        let mut recipe = Recipe::parse(
+            "name: a\nprompt: Audit {{ report }}\nparameters: [{key: report}]\n\
+            success_criteria: [{command: 'test -f {{ report }}'}]",
+        )?;
+        let values = HashMap::from([("report".to_string(), "a.md; rm x".to_string())]);
+        let filled = recipe.with_parameters(&values)?;
+        assert_eq!(filled.prompt, "Audit a.md; rm x");
+        // Only a recipe from a local file runs its commands
+        assert_eq!(filled.checks().count(), 0);
+        assert!(filled
+            .system_prompt_additions()
+            .unwrap()
+            .contains("exits with 0"));
+
+        recipe.dir = Some(PathBuf::from("."));
+        let filled = recipe.with_parameters(&values)?;
+        if cfg!(unix) {
+            assert_eq!(
+                filled.checks().next().unwrap().command.as_deref(),
+                Some("test -f 'a.md; rm x'")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // A template naming a parameter the recipe doesn't declare
+        assert!(Recipe::parse("name: a\nprompt: Audit {{ manifest }}").is_err());
+        assert!(Recipe::parse("name: a\nprompt: Audit {{ manifest").is_err());
+        assert!(Recipe::parse("name: ''\nprompt: Audit").is_err());
+        assert!(Recipe::parse("name: a").is_err());
+        assert!(Recipe::parse("name: a\nprompt: Audit\nparameters:\n  - key: a-b").is_err());
+        // The system prompt is filled in with the extensions when it is used, not the parameters
+        assert!(Recipe::parse("name: a\nprompt: Audit\nsystem_prompt: '{{ extensions }}'").is_ok());
+        assert!(Recipe::parse("name: a\nprompt: Audit\nsystem_prompt: '{% if %}'").is_err());
+        assert!(
+            Recipe::parse("name: a\nprompt: Audit\nresponse:\n  json_schema: {type: list}")
+                .is_err()
+        );
+        assert!(
+            Recipe::parse("name: a\nprompt: Audit\nsuccess_criteria:\n  - matches: '('").is_err()
+        );
+    }
+
+    #[test]
+    fn test_typed_parameters() -> Result<()> {
+        let recipe = Recipe::parse(
+            r#"
+name: review
+parameters:
+  - key: severity
+    type: enum
+    options: [low, high]
+    default: high
+  - key: policy
+    type: file
+    default: ""
+prompt: "Report {{ severity }} issues{% if policy %} breaking this policy: {{ policy }}{% endif %}"
+"#,
+        )?;
+        let prompt = |values: &[(&str, &str)]| -> Result<String> {
+            let values = values
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            Ok(recipe.with_parameters(&values)?.prompt)
+        };
+        assert_eq!(prompt(&[])?, "Report high issues");
+        assert_eq!(prompt(&[("severity", "low")])?, "Report low issues");
+        assert!(prompt(&[("severity", "medium")]).is_err());
+
+        let dir = tempfile::tempdir()?;
+        let policy = dir.path().join("policy.md");
+        std::fs::write(&policy, "no unwrap")?;
+        assert_eq!(
+            prompt(&[("policy", policy.to_str().unwrap())])?,
+            "Report high issues breaking this policy: no unwrap"
+        );
+        assert!(prompt(&[("policy", "missing.md")]).is_err());
+
+        // Enums need options, and their defaults have to be one of them
+        let enum_recipe = |options: &str| {
+            Recipe::parse(&format!(
+                "name: a\nprompt: Audit\nparameters:\n  - key: level\n    type: enum\n{}",
+                options
+            ))
+        };
+        assert!(enum_recipe("    options: [low]\n    default: low").is_ok());
+        assert!(enum_recipe("    options: [low]\n    default: high").is_err());
+        assert!(enum_recipe("    default: low").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_defaults() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let recipes = dir.path().join("recipes");
+        std::fs::create_dir(&recipes)?;
+        std::fs::write(recipes.join("policy.md"), "no unwrap")?;
+        std::fs::write(dir.path().join("secret"), "hunter2")?;
+        let file_recipe = |default: &str| {
+            format!(
+                "name: a\nprompt: '{{{{ policy }}}}'\nparameters:\n  - key: policy\n    type: file\n    default: {}",
+                default
+            )
+        };
+
+        // The default is read from the recipe's directory
+        let source = recipes.join("review.yaml");
+        std::fs::write(&source, file_recipe("policy.md"))?;
+        let recipe = Recipe::load(source.to_str().unwrap()).await?;
+        assert_eq!(recipe.with_parameters(&HashMap::new())?.prompt, "no unwrap");
+
+        // and can't be outside it
+        assert!(Recipe::parse(&file_recipe("../secret")).is_err());
+        assert!(Recipe::parse(&file_recipe("/etc/passwd")).is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("secret"), recipes.join("link.md"))?;
+            std::fs::write(&source, file_recipe("link.md"))?;
+            let recipe = Recipe::load(source.to_str().unwrap()).await?;
+            assert!(recipe.with_parameters(&HashMap::new()).is_err());
+        }
+
+        // A recipe that isn't a local file has no directory to read a default from
+        let recipe = Recipe::parse(&file_recipe("policy.md"))?;
+        assert!(recipe.with_parameters(&HashMap::new()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_includes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("recipe.yaml"),
+            "name: a\nparameters:\n  - key: team\nprompt: '{% include \"intro.md\" %} Audit'",
+        )?;
+        std::fs::write(
+            dir.path().join("intro.md"),
+            "{% include 'team.md' %} You work for {{ team }}.",
+        )?;
+        std::fs::write(dir.path().join("team.md"), "Hello.")?;
+        let source = dir.path().join("recipe.yaml");
+        let recipe = Recipe::load(source.to_str().unwrap()).await?;
+        assert_eq!(recipe.templates.len(), 2);
+        let values = HashMap::from([("team".to_string(), "platform".to_string())]);
+        assert_eq!(
+            recipe.with_parameters(&values)?.prompt,
+            "Hello. You work for platform. Audit"
+        );
+
+        // Includes can't reach outside the recipe's directory
+        std::fs::write(
+            dir.path().join("escape.yaml"),
+            "name: a\nprompt: '{% include \"../secret\" %}'",
+        )?;
+        let escape = dir.path().join("escape.yaml");
+        assert!(Recipe::load(escape.to_str().unwrap()).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_allows_tool() -> Result<()> {
+        let recipe = Recipe::parse(AUDIT)?;
+        assert!(recipe.allows_tool("developer__shell"));
+        assert!(recipe.allows_tool("fetch__fetch"));
+        assert!(!recipe.allows_tool("fetch__fetch_all"));
+        assert!(!recipe.allows_tool("github__create_issue"));
+
+        let open = Recipe::parse("name: a\nprompt: Audit")?;
+        assert!(open.allows_tool("github__create_issue"));
+        Ok(())
+    }
+}
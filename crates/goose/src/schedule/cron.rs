@@ -0,0 +1,250 @@
+//! Cron expressions, for when scheduled jobs run
+//!
+//! An expression has the five standard fields: minute, hour, day of the month, month and day of
+//! the week. Each field is `*`, a number, a range like `1-5` or a list of those like `1,15`, any
+//! of them with a step like `*/15` or `9-17/2`. Months and days of the week can be given by the
+//! first three letters of their names, and Sunday is either 0 or 7. `@hourly`, `@daily`,
+//! `@weekly`, `@monthly` and `@yearly` stand for the usual expressions.
+//!
+//! As in cron, when both day fields are restricted a day matches if either of them does.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use std::str::FromStr;
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead to look for a matching time, so that `0 0 30 2 *` doesn't loop forever
+const SEARCH_YEARS: i64 = 5;
+
+/// A parsed cron expression, with the values each field matches as bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(anyhow!(
+                "A cron expression has 5 fields, minute, hour, day, month and weekday, but '{}' has {}",
+                expression,
+                fields.len()
+            ));
+        };
+
+        let mut weekday_bits = parse_field(weekdays, 0, 7, WEEKDAYS, 0)?;
+        // Sunday is both 0 and 7
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59, &[], 0)?,
+            hours: parse_field(hours, 0, 23, &[], 0)?,
+            days: parse_field(days, 1, 31, &[], 0)?,
+            months: parse_field(months, 1, 12, MONTHS, 1)?,
+            weekdays: weekday_bits,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+}
+
+/// The values a field matches, as bits
+///
+/// `names` are alternatives to the numbers, the first standing for `first_name`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], first_name: u32) -> Result<u64> {
+    let value = |value: &str| -> Result<u32> {
+        let number = match names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+        {
+            Some(index) => index as u32 + first_name,
+            None => value
+                .parse()
+                .map_err(|_| anyhow!("'{}' in the cron field '{}' isn't a number", value, field))?,
+        };
+        if !(min..=max).contains(&number) {
+            return Err(anyhow!(
+                "{} in the cron field '{}' is outside {}-{}",
+                number,
+                field,
+                min,
+                max
+            ));
+        }
+        Ok(number)
+    };
+
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| anyhow!("Invalid step in the cron field '{}'", field))?;
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `5/15` runs from 5 on
+                None if step.is_some() => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(anyhow!(
+                "The range '{}' in a cron field is backwards",
+                range
+            ));
+        }
+        for number in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << number;
+        }
+    }
+    Ok(bits)
+}
+
+fn matches(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        expression.parse()
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = matches(self.days, date.day());
+        let weekday = matches(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first time after `after` the schedule matches, in `after`'s time zone
+    ///
+    /// Times a daylight saving change skips over don't happen, and are passed over. Returns
+    /// None if the schedule never matches, like on February 30th.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = after.timezone();
+        let start = after.naive_local();
+        let mut time =
+            start.date().and_hms_opt(start.hour(), start.minute(), 0)? + Duration::minutes(1);
+        let limit = time + Duration::days(366 * SEARCH_YEARS);
+        while time < limit {
+            let date = time.date();
+            if !matches(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                time = midnight(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.day_matches(date) {
+                time = midnight(date.succ_opt()?);
+            } else if !matches(self.hours, time.hour()) {
+                time = date.and_hms_opt(time.hour(), 0, 0)? + Duration::hours(1);
+            } else if !matches(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else if let Some(next) = timezone.from_local_datetime(&time).earliest() {
+                return Some(next);
+            } else {
+                time += Duration::minutes(1);
+            }
+        }
+        None
+    }
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).expect("midnight exists")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        let after = DateTime::parse_from_rfc3339(after)
+            .unwrap()
+            .with_timezone(&Utc);
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(&after)
+            .map(|next| next.format("%Y-%m-%d %H:%M %a").to_string())
+    }
+
+    #[test]
+    fn test_next_after() {
+        let monday = "2025-03-03T10:30:00Z";
+        assert_eq!(next("* * * * *", monday).unwrap(), "2025-03-03 10:31 Mon");
+        assert_eq!(
+            next("*/15 * * * *", monday).unwrap(),
+            "2025-03-03 10:45 Mon"
+        );
+        assert_eq!(next("@hourly", monday).unwrap(), "2025-03-03 11:00 Mon");
+        assert_eq!(next("@daily", monday).unwrap(), "2025-03-04 00:00 Tue");
+        assert_eq!(
+            next("0 9 * * MON-FRI", monday).unwrap(),
+            "2025-03-04 09:00 Tue"
+        );
+        assert_eq!(next("30 10 * * 1", monday).unwrap(), "2025-03-10 10:30 Mon");
+        assert_eq!(next("0 0 * * 7", monday).unwrap(), "2025-03-09 00:00 Sun");
+        assert_eq!(
+            next("0 12 1 jan,jul *", monday).unwrap(),
+            "2025-07-01 12:00 Tue"
+        );
+        assert_eq!(next("0 0 29 2 *", monday).unwrap(), "2028-02-29 00:00 Tue");
+        assert_eq!(next("0 0 30 2 *", monday), None);
+
+        // Either day field can match when both are restricted
+        assert_eq!(
+            next("0 0 15 * FRI", monday).unwrap(),
+            "2025-03-07 00:00 Fri"
+        );
+        assert_eq!(next("0 0 4 * FRI", monday).unwrap(), "2025-03-04 00:00 Tue");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * foo *",
+            "@sometimes",
+        ] {
+            assert!(CronSchedule::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
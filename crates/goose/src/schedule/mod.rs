@@ -0,0 +1,333 @@
+//! Jobs that run a prompt unattended on a schedule
+//!
+//! Jobs are configured as a `schedules` map in the config file, keyed by the job's name:
+//!
+//! ```yaml
+//! schedules:
+//!   dependency-report:
+//!     cron: "0 9 * * MON"
+//!     prompt: List the outdated dependencies of this repository and what upgrading them takes
+//!     working_dir: /home/me/src/app
+//!     model: gpt-4o-mini
+//!     notify:
+//!       webhook: https://hooks.example.com/goose
+//!       only_failures: true
+//! ```
+//!
+//! `goose schedule start` runs each job whenever its [cron expression](cron) next matches,
+//! as a headless run saved as a session named after the job and when it started. Runs that
+//! would have happened while the scheduler wasn't running aren't made up for, and a job still
+//! running when it is due again isn't started twice.
+pub mod cron;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, Utc};
+use etcetera::{choose_app_strategy, AppStrategy};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::Config;
+use cron::CronSchedule;
+
+/// Config key holding the scheduled jobs
+pub const SCHEDULES_CONFIG_KEY: &str = "schedules";
+
+/// How long a webhook has to answer a notification, so one that hangs doesn't hold up the job
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A prompt to run on a schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduledJob {
+    /// When to run, as a cron expression like `0 9 * * MON-FRI`, in local time
+    pub cron: String,
+    /// The instructions to run
+    pub prompt: String,
+    /// The directory to run in, the scheduler's own if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+    /// The provider to use instead of GOOSE_PROVIDER
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// The model to use instead of GOOSE_MODEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The goose mode to run in instead of GOOSE_MODE. No one is there to approve tool calls,
+    /// so a run that asks for approval fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Who to tell how the runs went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify: Option<JobNotification>,
+    /// Whether the scheduler runs the job
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl ScheduledJob {
+    pub fn schedule(&self) -> Result<CronSchedule> {
+        CronSchedule::parse(&self.cron)
+    }
+
+    /// When the job is due next after `after`, None if never
+    pub fn next_run(&self, after: &DateTime<Local>) -> Result<Option<DateTime<Local>>> {
+        Ok(self.schedule()?.next_after(after))
+    }
+}
+
+/// The configured jobs, by name
+pub fn jobs() -> Result<BTreeMap<String, ScheduledJob>> {
+    match Config::global().get_param(SCHEDULES_CONFIG_KEY) {
+        Ok(jobs) => Ok(jobs),
+        Err(crate::config::ConfigError::NotFound(_)) => Ok(BTreeMap::new()),
+        Err(e) => Err(anyhow!("Failed to read the scheduled jobs: {}", e)),
+    }
+}
+
+/// The name of the session a run of `job` started at `started` is saved as
+pub fn session_name(job: &str, started: &DateTime<Utc>) -> String {
+    format!(
+        "{}-{}",
+        job,
+        started.with_timezone(&Local).format("%Y%m%d-%H%M%S")
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A run of a scheduled job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRun {
+    /// The session the run is saved as
+    pub session: String,
+    pub started: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    /// Why the run failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl JobRun {
+    pub fn start(job: &str) -> Self {
+        let started = Utc::now();
+        Self {
+            session: session_name(job, &started),
+            started,
+            finished: None,
+            status: RunStatus::Running,
+            error: None,
+        }
+    }
+
+    /// Mark the run done, failed if there is an `error`
+    pub fn finish(&mut self, error: Option<String>) {
+        self.finished = Some(Utc::now());
+        self.status = match error {
+            Some(_) => RunStatus::Failed,
+            None => RunStatus::Succeeded,
+        };
+        self.error = error;
+    }
+}
+
+/// The last run of each job, kept in the data dir
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScheduleState {
+    pub last_runs: BTreeMap<String, JobRun>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl ScheduleState {
+    pub fn load() -> Result<Self> {
+        let path = choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("schedule_state.json");
+        Self::load_from(&path)
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        let mut state = if path.exists() {
+            serde_json::from_str::<ScheduleState>(&std::fs::read_to_string(path)?)?
+        } else {
+            ScheduleState::default()
+        };
+        state.path = path.to_path_buf();
+        Ok(state)
+    }
+
+    /// Save `run` as the last run of `job`
+    pub fn record(&mut self, job: &str, run: &JobRun) -> Result<()> {
+        self.last_runs.insert(job.to_string(), run.clone());
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// How to tell someone a job ran
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct JobNotification {
+    /// A shell command to run after each run, with the job's name, the run's status and its
+    /// session in GOOSE_JOB, GOOSE_JOB_STATUS and GOOSE_SESSION
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// A URL the run is POSTed to as JSON, which has 30 seconds to answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+    /// Only notify of runs that failed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub only_failures: bool,
+}
+
+#[derive(Serialize)]
+struct Notification<'a> {
+    job: &'a str,
+    #[serde(flatten)]
+    run: &'a JobRun,
+}
+
+impl JobNotification {
+    /// Tell the configured command and webhook how `run` of `job` went
+    pub async fn send(&self, job: &str, run: &JobRun) -> Result<()> {
+        if self.only_failures && run.status != RunStatus::Failed {
+            return Ok(());
+        }
+        let status = serde_json::to_value(run.status)?;
+        let status = status.as_str().unwrap_or_default();
+
+        if let Some(command) = &self.command {
+            let output = shell(command)
+                .env("GOOSE_JOB", job)
+                .env("GOOSE_JOB_STATUS", status)
+                .env("GOOSE_SESSION", &run.session)
+                .output()
+                .await?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "The notification command failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            reqwest::Client::new()
+                .post(webhook)
+                .timeout(WEBHOOK_TIMEOUT)
+                .json(&Notification { job, run })
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_job_config() {
+        let jobs: BTreeMap<String, ScheduledJob> = serde_yaml::from_str(
+            r#"
+            standup:
+              cron: "0 9 * * MON-FRI"
+              prompt: Summarize yesterday's commits
+              model: gpt-4o-mini
+              notify:
+                command: notify-send "$GOOSE_JOB $GOOSE_JOB_STATUS"
+            cleanup:
+              cron: "@weekly"
+              prompt: Remove the stale branches
+              enabled: false
+            "#,
+        )
+        .unwrap();
+        let standup = &jobs["standup"];
+        assert!(standup.enabled);
+        assert_eq!(standup.model.as_deref(), Some("gpt-4o-mini"));
+        assert!(!standup.notify.as_ref().unwrap().only_failures);
+        assert!(!jobs["cleanup"].enabled);
+
+        let friday = DateTime::parse_from_rfc3339("2025-03-07T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Local);
+        let next = standup.next_run(&friday).unwrap().unwrap();
+        assert!(next > friday);
+        assert_eq!(next.format("%a %H:%M").to_string(), "Mon 09:00");
+    }
+
+    #[tokio::test]
+    async fn test_state_and_notification() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("schedule_state.json");
+
+        let mut run = JobRun::start("standup");
+        assert!(run.session.starts_with("standup-"));
+        run.finish(Some("The provider isn't configured".to_string()));
+        ScheduleState::load_from(&path)?.record("standup", &run)?;
+        let state = ScheduleState::load_from(&path)?;
+        assert_eq!(state.last_runs["standup"], run);
+        assert_eq!(run.status, RunStatus::Failed);
+
+        let notified = dir.path().join("notified");
+        let notification = JobNotification {
+            command: Some(format!(
+                "echo \"$GOOSE_JOB $GOOSE_JOB_STATUS\" > {}",
+                notified.display()
+            )),
+            ..Default::default()
+        };
+        notification.send("standup", &run).await?;
+        assert_eq!(std::fs::read_to_string(&notified)?.trim(), "standup failed");
+
+        // Successful runs aren't reported to those only interested in failures
+        std::fs::remove_file(&notified)?;
+        run.finish(None);
+        let only_failures = JobNotification {
+            only_failures: true,
+            ..notification
+        };
+        only_failures.send("standup", &run).await?;
+        assert!(!notified.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+//! Archiving old sessions to S3 or a compatible object store
+//!
+//! With `GOOSE_SESSION_ARCHIVE_URL` set to a bucket and prefix like `s3://team-goose/sessions`,
+//! sessions not updated for `GOOSE_SESSION_ARCHIVE_AFTER_DAYS` (7 by default) are compressed
+//! with zstd and uploaded as `<prefix>/<owner id>/<session id>-<random id>.jsonl.zst`, so
+//! sessions of other users and machines with the same id are kept apart. Their session file is
+//! then cut down to its metadata, marked with [`SessionMetadata::archived`], so they're still
+//! listed, and the messages are downloaded again the next time the session is resumed, which
+//! removes the archived copy. Purging an archived session removes the archived copy too.
+//!
+//! `GOOSE_SESSION_ARCHIVE_ENDPOINT` points at another S3 compatible store, such as MinIO or
+//! `https://storage.googleapis.com` for Google Cloud Storage, which are addressed with path
+//! style URLs. Requests are signed with the standard AWS credential chain, in
+//! `GOOSE_SESSION_ARCHIVE_REGION` or the default AWS region; stores other than S3 take HMAC
+//! keys through `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::storage::{self, SessionMetadata};
+use super::store::{self, SavedSession};
+use crate::config::owner::owner_id;
+use crate::config::secret_store::block_on;
+use crate::config::Config;
+use crate::providers::sigv4::SigV4Signer;
+
+const DEFAULT_ARCHIVE_AFTER_DAYS: u64 = 7;
+
+/// Where a session's messages were archived
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedSession {
+    /// The object holding the session, like
+    /// `s3://team-goose/sessions/<owner id>/20250101_120000-<random id>.jsonl.zst`
+    pub url: String,
+    /// When the session was archived, as a Unix timestamp
+    pub archived_at: i64,
+}
+
+/// A bucket that old sessions are moved to
+pub struct SessionArchive {
+    bucket: String,
+    prefix: String,
+    region: Option<String>,
+    endpoint: Option<String>,
+    signer: Option<SigV4Signer>,
+    client: Client,
+}
+
+impl SessionArchive {
+    /// An archive at a URL like `s3://bucket/prefix`
+    pub fn new(url: &str) -> Result<Self> {
+        let path = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| anyhow!("The session archive must be an s3:// URL, got {}", url))?;
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if bucket.is_empty() {
+            return Err(anyhow!("The session archive URL {} has no bucket", url));
+        }
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            region: None,
+            endpoint: None,
+            signer: None,
+            client: Client::new(),
+        })
+    }
+
+    /// Use a region other than the default one from the AWS config
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Send requests to an S3 compatible store instead of S3, using path style URLs
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Sign requests with the given signer instead of the default credential chain
+    pub fn with_signer(mut self, signer: SigV4Signer) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// The archive set with `GOOSE_SESSION_ARCHIVE_URL`, if any
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Ok(url) = config.get_param::<String>("GOOSE_SESSION_ARCHIVE_URL") else {
+            return Ok(None);
+        };
+        let mut archive = Self::new(&url)?;
+        if let Ok(region) = config.get_param::<String>("GOOSE_SESSION_ARCHIVE_REGION") {
+            archive = archive.with_region(region);
+        }
+        if let Ok(endpoint) = config.get_param::<String>("GOOSE_SESSION_ARCHIVE_ENDPOINT") {
+            archive = archive.with_endpoint(endpoint);
+        }
+        Ok(Some(archive))
+    }
+
+    /// A new key for archiving a session, which no other archived session has
+    fn key(&self, owner: &str, session_id: &str) -> String {
+        let name = format!(
+            "{}/{}-{}.jsonl.zst",
+            owner,
+            session_id,
+            uuid::Uuid::new_v4().simple()
+        );
+        match self.prefix.as_str() {
+            "" => name,
+            prefix => format!("{}/{}", prefix, name),
+        }
+    }
+
+    /// The key of an archived session, if it is in this archive's bucket
+    fn archived_key<'a>(&self, archived: &'a ArchivedSession) -> Result<&'a str> {
+        archived
+            .url
+            .strip_prefix(&format!("s3://{}/", self.bucket))
+            .ok_or_else(|| {
+                anyhow!(
+                    "The session was archived to {}, outside of s3://{}",
+                    archived.url,
+                    self.bucket
+                )
+            })
+    }
+
+    fn object_url(&self, region: &str, key: &str) -> String {
+        match &self.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), self.bucket, key),
+            None => format!(
+                "https://{}.s3.{}.amazonaws.com/{}",
+                self.bucket, region, key
+            ),
+        }
+    }
+
+    async fn signer(&self) -> Result<SigV4Signer> {
+        let signer = match &self.signer {
+            Some(signer) => signer.clone(),
+            // Stores other than S3 ignore the region, but it is part of the signature
+            None if self.endpoint.is_some() && self.region.is_none() => {
+                SigV4Signer::from_env("s3", Some("us-east-1".to_string())).await?
+            }
+            None => SigV4Signer::from_env("s3", self.region.clone()).await?,
+        };
+        Ok(signer.with_s3_settings())
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let signer = self.signer().await?;
+        let request = self
+            .client
+            .put(self.object_url(signer.region(), key))
+            .header("content-type", "application/zstd")
+            .body(body);
+        let response = signer.sign(request).await?.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Uploading {} to the session archive returned {}: {}",
+                key,
+                response.status(),
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let signer = self.signer().await?;
+        let request = self.client.delete(self.object_url(signer.region(), key));
+        let response = signer.sign(request).await?.send().await?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(anyhow!(
+                "Deleting {} from the session archive returned {}: {}",
+                key,
+                response.status(),
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let signer = self.signer().await?;
+        let request = self.client.get(self.object_url(signer.region(), key));
+        let response = signer.sign(request).await?.send().await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.bytes().await?.to_vec())),
+            status => Err(anyhow!(
+                "Downloading {} from the session archive returned {}: {}",
+                key,
+                status,
+                response.text().await.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// Upload a session and cut its file down to its metadata
+    ///
+    /// The store's copy of the session is also removed, so only the archive holds its messages
+    /// until it's resumed. A copy saved since under the same id, from another machine, is kept.
+    pub fn archive(&self, session_file: &Path) -> Result<ArchivedSession> {
+        let mut metadata = storage::read_metadata(session_file)?;
+        if let Some(archived) = metadata.archived {
+            return Ok(archived);
+        }
+        let session_id = session_file
+            .file_stem()
+            .ok_or_else(|| anyhow!("Invalid session path {}", session_file.display()))?
+            .to_string_lossy()
+            .to_string();
+        let modified = fs::metadata(session_file)?.modified()?;
+        // The file is uploaded as it is, so encrypted sessions stay encrypted in the archive
+        let contents = zstd::encode_all(&fs::read(session_file)?[..], 0)?;
+        let key = self.key(&owner_id(), &session_id);
+        block_on(self.put(&key, contents))?;
+
+        let archived = ArchivedSession {
+            url: format!("s3://{}/{}", self.bucket, key),
+            archived_at: Utc::now().timestamp(),
+        };
+        metadata.archived = Some(archived.clone());
+        storage::write_session_file(session_file, &metadata, &[])?;
+        if let Some((session_id, store)) =
+            storage::stored_session_id(session_file).zip(store::global())
+        {
+            let saved_by = DateTime::<Utc>::from(modified) + store::SAVE_SLACK;
+            if let Err(e) = store.delete(&session_id, Some(saved_by)) {
+                tracing::warn!("Failed to delete archived session from the store: {}", e);
+            }
+        }
+        Ok(archived)
+    }
+
+    /// Download an archived session, writing its file and saving it to the store again
+    ///
+    /// The archived copy is removed once the session is restored, as it is archived anew the
+    /// next time.
+    pub fn restore(&self, session_file: &Path) -> Result<SavedSession> {
+        let metadata = storage::read_metadata(session_file)?;
+        let archived = metadata
+            .archived
+            .ok_or_else(|| anyhow!("{} isn't archived", session_file.display()))?;
+        let key = self.archived_key(&archived)?;
+        let contents = block_on(self.get(key))?
+            .ok_or_else(|| anyhow!("The archived session {} is missing", archived.url))?;
+        fs::write(session_file, zstd::decode_all(&contents[..])?)?;
+
+        let saved = SavedSession {
+            metadata: storage::read_metadata(session_file)?,
+            messages: storage::read_messages(session_file)?,
+        };
+        storage::save_messages_with_metadata(session_file, &saved.metadata, &saved.messages)?;
+        if let Err(e) = block_on(self.delete(key)) {
+            tracing::warn!(
+                "Failed to remove the restored session from the archive: {}",
+                e
+            );
+        }
+        Ok(saved)
+    }
+
+    /// Remove the archived copy of a session, for when the session is purged
+    pub fn remove(&self, archived: &ArchivedSession) -> Result<()> {
+        block_on(self.delete(self.archived_key(archived)?))
+    }
+
+    /// Archive the sessions in the session dir not updated for `after`, returning their ids
+    pub fn archive_old_sessions(&self, after: Duration) -> Result<Vec<String>> {
+        let mut archived = Vec::new();
+        for (session_id, session_file) in storage::list_sessions()? {
+            let modified = session_file.metadata().and_then(|m| m.modified())?;
+            if SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default()
+                < after
+                || storage::is_archived(&session_file)
+            {
+                continue;
+            }
+            match self.archive(&session_file) {
+                Ok(_) => archived.push(session_id),
+                Err(e) => tracing::warn!("Failed to archive session {}: {}", session_id, e),
+            }
+        }
+        Ok(archived)
+    }
+}
+
+/// How long sessions are kept before they're archived, `GOOSE_SESSION_ARCHIVE_AFTER_DAYS`
+pub fn archive_after(config: &Config) -> Duration {
+    let days = config
+        .get_param("GOOSE_SESSION_ARCHIVE_AFTER_DAYS")
+        .unwrap_or(DEFAULT_ARCHIVE_AFTER_DAYS);
+    Duration::from_secs(days * 24 * 60 * 60)
+}
+
+/// The archive configured by `GOOSE_SESSION_ARCHIVE_URL`, if any
+pub fn global() -> Option<&'static SessionArchive> {
+    static ARCHIVE: OnceCell<Option<SessionArchive>> = OnceCell::new();
+    ARCHIVE
+        .get_or_init(|| match SessionArchive::from_config(Config::global()) {
+            Ok(archive) => archive,
+            Err(e) => {
+                tracing::warn!("Failed to set up the session archive: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Restore an archived session from the configured archive
+pub fn restore(session_file: &Path, metadata: &SessionMetadata) -> Result<SavedSession> {
+    match global() {
+        Some(archive) => archive.restore(session_file),
+        None => Err(anyhow!(
+            "The session was archived to {}, set GOOSE_SESSION_ARCHIVE_URL to restore it",
+            metadata
+                .archived
+                .as_ref()
+                .map(|archived| archived.url.as_str())
+                .unwrap_or_default()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use aws_credential_types::Credentials;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    /// Keeps what is uploaded and serves it back
+    #[derive(Clone, Default)]
+    struct Bucket(Arc<Mutex<Option<Vec<u8>>>>);
+
+    impl Respond for Bucket {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let mut object = self.0.lock().unwrap();
+            match request.method.as_str() {
+                "PUT" => {
+                    *object = Some(request.body.clone());
+                    ResponseTemplate::new(200)
+                }
+                "DELETE" => {
+                    *object = None;
+                    ResponseTemplate::new(204)
+                }
+                _ => match object.as_ref() {
+                    Some(body) => ResponseTemplate::new(200).set_body_bytes(body.clone()),
+                    None => ResponseTemplate::new(404),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_key() -> Result<()> {
+        let archive = SessionArchive::new("s3://team-goose/sessions/")?;
+        let key = archive.key("alice", "a");
+        assert!(key.starts_with("sessions/alice/a-"));
+        assert!(key.ends_with(".jsonl.zst"));
+        // Archiving a session again never overwrites what was archived before
+        assert_ne!(archive.key("alice", "a"), key);
+        assert_eq!(
+            archive.object_url("eu-west-1", "sessions/a.jsonl.zst"),
+            "https://team-goose.s3.eu-west-1.amazonaws.com/sessions/a.jsonl.zst"
+        );
+        let archive = SessionArchive::new("s3://team-goose")?.with_endpoint("http://minio:9000/");
+        assert!(archive.key("alice", "a").starts_with("alice/a-"));
+        assert_eq!(
+            archive.object_url("us-east-1", "a.jsonl.zst"),
+            "http://minio:9000/team-goose/a.jsonl.zst"
+        );
+        assert!(SessionArchive::new("gs://team-goose").is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_archive_and_restore() -> Result<()> {
+        let server = MockServer::start().await;
+        let bucket = Bucket::default();
+        let object = r"^/team-goose/sessions/[^/]+/a-[0-9a-f]{32}\.jsonl\.zst$";
+        Mock::given(path_regex(object))
+            .and(method("PUT"))
+            .respond_with(bucket.clone())
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path_regex(object))
+            .and(method("GET"))
+            .respond_with(bucket.clone())
+            .mount(&server)
+            .await;
+        Mock::given(path_regex(object))
+            .and(method("DELETE"))
+            .respond_with(bucket.clone())
+            .expect(1)
+            .mount(&server)
+            .await;
+        let credentials = Credentials::new("AKIDEXAMPLE", "secret", None, None, "test");
+        let archive = SessionArchive::new("s3://team-goose/sessions")?
+            .with_endpoint(server.uri())
+            .with_signer(SigV4Signer::new(credentials, "us-east-1", "s3"));
+
+        let dir = tempdir()?;
+        let session_file = dir.path().join("a.jsonl");
+        let messages = vec![
+            Message::user().with_text("List the files"),
+            Message::assistant().with_text("The directory is empty"),
+        ];
+        let metadata = SessionMetadata {
+            description: "Listing files".to_string(),
+            ..Default::default()
+        };
+        storage::save_messages_with_metadata(&session_file, &metadata, &messages)?;
+
+        let archived = archive.archive(&session_file)?;
+        assert!(archived.url.starts_with("s3://team-goose/sessions/"));
+        // The session is still listed with its description, without its messages
+        let stub = storage::read_metadata(&session_file)?;
+        assert_eq!(stub.description, "Listing files");
+        assert_eq!(stub.archived, Some(archived));
+        assert!(storage::read_messages(&session_file)?.is_empty());
+        // Archiving again leaves the archived copy alone
+        archive.archive(&session_file)?;
+
+        let restored = archive.restore(&session_file)?;
+        assert_eq!(restored.messages, messages);
+        assert_eq!(restored.metadata.archived, None);
+        assert_eq!(storage::read_messages(&session_file)?, messages);
+        // The archived copy is removed once the session is restored
+        assert!(bucket.0.lock().unwrap().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! Checkpoints of the working directory at each turn, to roll a session back to
+//!
+//! With `GOOSE_CHECKPOINTS=true`, the agent snapshots the files of the working directory's git
+//! repository as each turn starts, before answering it. A snapshot is a commit of every file git
+//! doesn't ignore, written through an index of its own, so the repository's index, branches and
+//! stashes are left as they are. Snapshots are kept under `refs/goose/checkpoints/` so git
+//! doesn't garbage collect them.
+//!
+//! Rolling back to turn N puts the files back as they were when turn N was done, removing the
+//! files created since, and removes the later turns from the session. The files as they were
+//! before the rollback are kept too, under `refs/goose/checkpoints/<session>/before-rollback`.
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use super::storage::{
+    read_metadata, resume, rewind, save_messages_with_metadata, update_metadata, RevisionKind,
+};
+use super::store::SavedSession;
+use crate::compaction::starts_turn;
+use crate::message::Message;
+
+/// Config key enabling checkpoints
+pub const CHECKPOINTS_CONFIG_KEY: &str = "GOOSE_CHECKPOINTS";
+
+/// A snapshot of the files in the session's repository
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The number of turns done when the snapshot was taken
+    pub turn: usize,
+    /// The number of messages in the session then
+    pub message_count: usize,
+    /// The root of the repository the snapshot is of
+    pub repository: PathBuf,
+    /// The snapshot's commit
+    pub commit: String,
+    /// When the snapshot was taken, as a Unix timestamp
+    pub created: i64,
+}
+
+/// Snapshot the working directory as the turn `messages` ends with starts
+///
+/// Returns None if the last message doesn't start a turn or the working directory isn't in a git
+/// repository. A checkpoint taken for a turn again, such as when its reply is retried, replaces
+/// the ones taken for it and the turns after it.
+pub async fn checkpoint_turn(
+    session_file: &Path,
+    working_dir: &Path,
+    messages: &[Message],
+) -> Result<Option<Checkpoint>> {
+    let Some((_, before)) = messages.split_last().filter(|(last, _)| starts_turn(last)) else {
+        return Ok(None);
+    };
+    let Ok(repository) = git(working_dir, None, &["rev-parse", "--show-toplevel"]).await else {
+        return Ok(None);
+    };
+    let repository = PathBuf::from(repository);
+    let turn = before.iter().filter(|message| starts_turn(message)).count();
+
+    let commit = snapshot(
+        &repository,
+        &format!("goose checkpoint after turn {}", turn),
+    )
+    .await?;
+    git(
+        &repository,
+        None,
+        &["update-ref", &checkpoint_ref(session_file, turn), &commit],
+    )
+    .await?;
+
+    let checkpoint = Checkpoint {
+        turn,
+        message_count: before.len(),
+        repository,
+        commit,
+        created: Utc::now().timestamp(),
+    };
+    let mut metadata = read_metadata(session_file)?;
+    metadata.checkpoints.retain(|existing| existing.turn < turn);
+    metadata.checkpoints.push(checkpoint.clone());
+    update_metadata(session_file, &metadata).await?;
+    Ok(Some(checkpoint))
+}
+
+/// Put the files back as they were when `turn` was done, and remove the turns after it
+///
+/// The removed messages are recorded in the session's revisions.
+pub async fn rollback(session_file: &Path, turn: usize) -> Result<SavedSession> {
+    let mut saved = resume(session_file)?;
+    let checkpoint = saved
+        .metadata
+        .checkpoints
+        .iter()
+        .find(|checkpoint| checkpoint.turn == turn)
+        .cloned()
+        .ok_or_else(|| {
+            let turns: Vec<_> = saved
+                .metadata
+                .checkpoints
+                .iter()
+                .map(|checkpoint| checkpoint.turn.to_string())
+                .collect();
+            if turns.is_empty() {
+                anyhow!("The session has no checkpoints")
+            } else {
+                anyhow!(
+                    "There is no checkpoint after turn {}, only after turns {}",
+                    turn,
+                    turns.join(", ")
+                )
+            }
+        })?;
+    let repository = &checkpoint.repository;
+
+    let current = snapshot(
+        repository,
+        &format!("goose checkpoint before rolling back to turn {}", turn),
+    )
+    .await?;
+    git(
+        repository,
+        None,
+        &[
+            "update-ref",
+            &checkpoint_ref(session_file, "before-rollback"),
+            &current,
+        ],
+    )
+    .await?;
+    restore(repository, &checkpoint.commit, &current).await?;
+
+    for later in saved.metadata.checkpoints.iter().filter(|c| c.turn > turn) {
+        let reference = checkpoint_ref(session_file, later.turn);
+        if let Err(e) = git(&later.repository, None, &["update-ref", "-d", &reference]).await {
+            tracing::warn!("Failed to delete checkpoint {}: {}", reference, e);
+        }
+    }
+    saved.metadata.checkpoints.retain(|c| c.turn <= turn);
+    if checkpoint.message_count < saved.messages.len() {
+        rewind(&mut saved, checkpoint.message_count, RevisionKind::Rollback);
+    }
+    save_messages_with_metadata(session_file, &saved.metadata, &saved.messages)?;
+    Ok(saved)
+}
+
+fn checkpoint_ref(session_file: &Path, name: impl std::fmt::Display) -> String {
+    let session = session_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("refs/goose/checkpoints/{}/{}", session, name)
+}
+
+/// An index file of our own, removed when dropped
+struct TempIndex(PathBuf);
+
+impl TempIndex {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("goose-checkpoint-{}.index", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempIndex {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Commit every file in `repository` that git doesn't ignore, without touching its index
+async fn snapshot(repository: &Path, message: &str) -> Result<String> {
+    let index = TempIndex::new();
+    // Starting from the repository's index saves hashing the files that didn't change
+    let repository_index = git(repository, None, &["rev-parse", "--git-path", "index"]).await?;
+    let repository_index = repository.join(repository_index);
+    if repository_index.exists() {
+        fs::copy(&repository_index, &index.0)?;
+    }
+    git(repository, Some(&index.0), &["add", "--all"]).await?;
+    let tree = git(repository, Some(&index.0), &["write-tree"]).await?;
+    git(repository, None, &["commit-tree", &tree, "-m", message]).await
+}
+
+/// Put the files of `repository` back as they are in `commit`, from the snapshot `current`
+async fn restore(repository: &Path, commit: &str, current: &str) -> Result<()> {
+    let created = git(
+        repository,
+        None,
+        &[
+            "diff-tree",
+            "-r",
+            "-z",
+            "--name-only",
+            "--no-renames",
+            "--diff-filter=A",
+            commit,
+            current,
+        ],
+    )
+    .await?;
+    for path in created.split('\0').filter(|path| !path.is_empty()) {
+        match fs::remove_file(repository.join(path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+
+    let index = TempIndex::new();
+    git(repository, Some(&index.0), &["read-tree", commit]).await?;
+    git(
+        repository,
+        Some(&index.0),
+        &["checkout-index", "--all", "--force"],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn git(dir: &Path, index: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    command
+        .current_dir(dir)
+        .args(args)
+        // Snapshots are committed without the user's identity, which may not be set up
+        .env("GIT_AUTHOR_NAME", "goose")
+        .env("GIT_AUTHOR_EMAIL", "goose@localhost")
+        .env("GIT_COMMITTER_NAME", "goose")
+        .env("GIT_COMMITTER_EMAIL", "goose@localhost");
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    let output = command.output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::storage::SessionMetadata;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_checkpoint_and_rollback() -> Result<()> {
+        let sessions = tempdir()?;
+        let session_file = sessions.path().join("refactor.jsonl");
+        let workspace = tempdir()?;
+        let repository = workspace.path();
+        git(repository, None, &["init", "--quiet"]).await?;
+        fs::write(repository.join("lib.rs"), "fn main() {}")?;
+
+        let mut messages = vec![Message::user().with_text("Rename main to run")];
+        save_messages_with_metadata(&session_file, &SessionMetadata::default(), &messages)?;
+        let first = checkpoint_turn(&session_file, repository, &messages)
+            .await?
+            .unwrap();
+        assert_eq!((first.turn, first.message_count), (0, 0));
+
+        fs::write(repository.join("lib.rs"), "fn run() {}")?;
+        messages.push(Message::assistant().with_text("Renamed it"));
+        messages.push(Message::user().with_text("Add a test"));
+        save_messages_with_metadata(&session_file, &read_metadata(&session_file)?, &messages)?;
+        let second = checkpoint_turn(&session_file, repository, &messages)
+            .await?
+            .unwrap();
+        assert_eq!((second.turn, second.message_count), (1, 2));
+
+        fs::remove_file(repository.join("lib.rs"))?;
+        fs::create_dir(repository.join("tests"))?;
+        fs::write(repository.join("tests/run.rs"), "#[test] fn runs() {}")?;
+        messages.push(Message::assistant().with_text("Moved it to a test"));
+        save_messages_with_metadata(&session_file, &read_metadata(&session_file)?, &messages)?;
+
+        // Back to when the rename was done
+        let saved = rollback(&session_file, 1).await?;
+        assert_eq!(
+            fs::read_to_string(repository.join("lib.rs"))?,
+            "fn run() {}"
+        );
+        assert!(!repository.join("tests/run.rs").exists());
+        assert_eq!(saved.messages, messages[..2]);
+        assert_eq!(saved.metadata.revisions[0].kind, RevisionKind::Rollback);
+
+        let saved = rollback(&session_file, 0).await?;
+        assert_eq!(
+            fs::read_to_string(repository.join("lib.rs"))?,
+            "fn main() {}"
+        );
+        assert!(saved.messages.is_empty());
+        assert_eq!(saved.metadata.checkpoints, vec![first]);
+        assert!(rollback(&session_file, 1).await.is_err());
+
+        // Not in a repository
+        let elsewhere = tempdir()?;
+        assert_eq!(
+            checkpoint_turn(&session_file, elsewhere.path(), &messages[..1]).await?,
+            None
+        );
+        Ok(())
+    }
+}
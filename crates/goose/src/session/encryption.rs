@@ -0,0 +1,311 @@
+//! Encryption of saved sessions, enabled with `GOOSE_SESSION_ENCRYPTION=true`
+//!
+//! Messages and tool output often hold source code and secrets, so session files and the
+//! content columns of the session store can be kept encrypted. Each line of a session file and
+//! each stored message is encrypted on its own with XChaCha20-Poly1305, under a random key kept
+//! as the `GOOSE_SESSION_KEY` secret in the keyring or whichever secret backend is configured.
+//! Each is encrypted under its record id, the session and the line or message it is, so it can't
+//! be moved into another session or place without failing to decrypt. Encrypted text is marked
+//! with [`PREFIX`], so sessions saved before encryption was enabled still load, and are encrypted
+//! the next time they are saved. As a session file is saved whole, one with encrypted lines
+//! can't have plaintext ones, see [`check_lines`].
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use once_cell::sync::Lazy;
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::{Config, ConfigError};
+
+/// The start of encrypted text, followed by the base64 of the nonce and the ciphertext
+pub const PREFIX: &str = "goose-encrypted:v2:";
+
+/// The secret the key is kept in
+pub const KEY_SECRET: &str = "GOOSE_SESSION_KEY";
+
+const NONCE_LEN: usize = 24;
+
+#[derive(Clone)]
+pub struct SessionCipher {
+    key: Key,
+}
+
+impl SessionCipher {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key: key.into() }
+    }
+
+    /// A cipher with a new random key
+    pub fn generate() -> Self {
+        Self {
+            key: XChaCha20Poly1305::generate_key(&mut OsRng),
+        }
+    }
+
+    /// The cipher `GOOSE_SESSION_ENCRYPTION` asks for, if any
+    ///
+    /// The key is created the first time, and it is an error if it can't be kept, as sessions
+    /// encrypted with it could never be read again.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        if !config
+            .get_param::<bool>("GOOSE_SESSION_ENCRYPTION")
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+        match config.get_secret::<String>(KEY_SECRET) {
+            Ok(key) => Ok(Some(Self::parse_key(&key)?)),
+            Err(ConfigError::NotFound(_)) => {
+                // Only one goose makes the key, the others wait for it and read the same key
+                let _lock = KeyLock::acquire(&key_lock_path(config))?;
+                match config.get_secret::<String>(KEY_SECRET) {
+                    Ok(key) => return Ok(Some(Self::parse_key(&key)?)),
+                    Err(ConfigError::NotFound(_)) => {}
+                    Err(e) => return Err(anyhow!("Failed to read the session key: {}", e)),
+                }
+                let cipher = Self::generate();
+                config.set_secret(KEY_SECRET, STANDARD.encode(cipher.key).into())?;
+                // Read back, so sessions are only ever encrypted with the key that was kept
+                let key = config
+                    .get_secret::<String>(KEY_SECRET)
+                    .map_err(|e| anyhow!("Failed to read the new session key back: {}", e))?;
+                Ok(Some(Self::parse_key(&key)?))
+            }
+            Err(e) => Err(anyhow!("Failed to read the session key: {}", e)),
+        }
+    }
+
+    fn parse_key(key: &str) -> Result<Self> {
+        let key: [u8; 32] = STANDARD
+            .decode(key.trim())?
+            .try_into()
+            .map_err(|_| anyhow!("{} must be 32 bytes, base64 encoded", KEY_SECRET))?;
+        Ok(Self::new(key))
+    }
+
+    /// Encrypt `plaintext` as the record `record_id`, see [`record_id`]
+    pub fn encrypt(&self, record_id: &str, plaintext: &str) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: record_id.as_bytes(),
+        };
+        let ciphertext = XChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, payload)
+            .expect("encrypting in memory doesn't fail");
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        format!("{}{}", PREFIX, STANDARD.encode(sealed))
+    }
+
+    /// Decrypt text from [`SessionCipher::encrypt`] for the same `record_id`, returning text that
+    /// isn't encrypted as it is
+    pub fn decrypt<'a>(&self, record_id: &str, text: &'a str) -> Result<Cow<'a, str>> {
+        let Some(encoded) = text.strip_prefix(PREFIX) else {
+            return Ok(Cow::Borrowed(text));
+        };
+        let sealed = STANDARD.decode(encoded.trim_end())?;
+        if sealed.len() < NONCE_LEN {
+            return Err(anyhow!("The encrypted session data is cut short"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: record_id.as_bytes(),
+        };
+        let plaintext = XChaCha20Poly1305::new(&self.key)
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                anyhow!(
+                    "Failed to decrypt {}, it was encrypted with another {} or for another record",
+                    record_id,
+                    KEY_SECRET
+                )
+            })?;
+        Ok(Cow::Owned(String::from_utf8(plaintext)?))
+    }
+}
+
+/// The id of a part of a session, such as its `metadata` or a message's index, that the part is
+/// encrypted under
+pub fn record_id(session_id: &str, part: impl Display) -> String {
+    format!("{}/{}", session_id, part)
+}
+
+/// Whether text was encrypted by a [`SessionCipher`]
+pub fn is_encrypted(text: &str) -> bool {
+    text.starts_with(PREFIX)
+}
+
+/// Check that a session file's lines are all encrypted or none are
+///
+/// A plaintext line among encrypted ones wasn't saved by goose, and would be read without being
+/// authenticated.
+pub fn check_lines<S: AsRef<str>>(lines: &[S]) -> Result<()> {
+    let encrypted = lines
+        .iter()
+        .filter(|line| is_encrypted(line.as_ref()))
+        .count();
+    if encrypted > 0 && encrypted < lines.len() {
+        return Err(anyhow!(
+            "The session has plaintext lines among its encrypted ones, and won't be read"
+        ));
+    }
+    Ok(())
+}
+
+/// `plaintext` encrypted with `cipher` as the record `record_id`, or as it is without one
+pub fn seal(cipher: Option<&SessionCipher>, record_id: &str, plaintext: String) -> String {
+    match cipher {
+        Some(cipher) => cipher.encrypt(record_id, &plaintext),
+        None => plaintext,
+    }
+}
+
+/// `text` decrypted with `cipher`, failing if it is encrypted and there is no cipher
+pub fn open<'a>(
+    cipher: Option<&SessionCipher>,
+    record_id: &str,
+    text: &'a str,
+) -> Result<Cow<'a, str>> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(record_id, text),
+        None if is_encrypted(text) => Err(anyhow!(
+            "The session is encrypted, set GOOSE_SESSION_ENCRYPTION to read it"
+        )),
+        None => Ok(Cow::Borrowed(text)),
+    }
+}
+
+/// Held by the goose making the session key, as secret stores can't create a key only if there
+/// is none yet
+struct KeyLock(PathBuf);
+
+/// How long a goose waits for another to make the key
+const KEY_LOCK_WAIT: Duration = Duration::from_secs(10);
+
+impl KeyLock {
+    fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let started = SystemTime::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(_) => return Ok(Self(path.to_path_buf())),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+            // A lock older than the wait was left by a goose that exited while making the key
+            let stale = fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > KEY_LOCK_WAIT);
+            if stale {
+                let _ = fs::remove_file(path);
+                continue;
+            }
+            if started.elapsed().unwrap_or_default() > KEY_LOCK_WAIT {
+                return Err(anyhow!(
+                    "Timed out waiting for another goose to make the session key, delete {} if none is running",
+                    path.display()
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for KeyLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn key_lock_path(config: &Config) -> PathBuf {
+    Path::new(&config.path()).with_extension("session_key.lock")
+}
+
+/// The cipher for sessions saved by this process, see [`SessionCipher::from_config`]
+pub fn global() -> Result<Option<&'static SessionCipher>> {
+    static CIPHER: Lazy<Result<Option<SessionCipher>, String>> =
+        Lazy::new(|| SessionCipher::from_config(Config::global()).map_err(|e| e.to_string()));
+    match &*CIPHER {
+        Ok(cipher) => Ok(cipher.as_ref()),
+        Err(e) => Err(anyhow!("{}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let cipher = SessionCipher::generate();
+        let line = r#"{"role":"user","content":[{"type":"text","text":"AKIA..."}]}"#;
+        let sealed = cipher.encrypt("a/0", line);
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("AKIA"));
+        // Each encryption uses a new nonce
+        assert_ne!(sealed, cipher.encrypt("a/0", line));
+        assert_eq!(cipher.decrypt("a/0", &sealed)?, line);
+
+        // Text saved before encryption was enabled is read as it is
+        assert_eq!(cipher.decrypt("a/0", line)?, line);
+        assert_eq!(open(None, "a/0", line)?, line);
+        assert!(open(None, "a/0", &sealed).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let sealed = SessionCipher::new([1; 32]).encrypt("a/0", "hello");
+        assert!(SessionCipher::new([2; 32]).decrypt("a/0", &sealed).is_err());
+        assert!(SessionCipher::new([1; 32])
+            .decrypt("a/0", &sealed[..PREFIX.len() + 8])
+            .is_err());
+    }
+
+    #[test]
+    fn test_bound_to_record() -> Result<()> {
+        let cipher = SessionCipher::new([1; 32]);
+        let sealed = cipher.encrypt(&record_id("a", 0), "hello");
+        assert!(cipher.decrypt(&record_id("a", 1), &sealed).is_err());
+        assert!(cipher.decrypt(&record_id("b", 0), &sealed).is_err());
+        assert_eq!(cipher.decrypt(&record_id("a", 0), &sealed)?, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_lines() {
+        let sealed = SessionCipher::generate().encrypt("a/0", "hello");
+        assert!(check_lines(&[sealed.as_str(), sealed.as_str()]).is_ok());
+        assert!(check_lines(&["{}", "{}"]).is_ok());
+        assert!(check_lines(&[sealed.as_str(), "{}"]).is_err());
+        assert!(check_lines(&["{}", sealed.as_str()]).is_err());
+    }
+
+    #[test]
+    fn test_key_lock_waits() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.session_key.lock");
+        let lock = KeyLock::acquire(&path)?;
+        let waiting = {
+            let path = path.clone();
+            std::thread::spawn(move || KeyLock::acquire(&path).map(|_| SystemTime::now()))
+        };
+        std::thread::sleep(Duration::from_millis(200));
+        let released = SystemTime::now();
+        drop(lock);
+        let acquired = waiting.join().unwrap()?;
+        assert!(acquired >= released);
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+//! Export and import of sessions
+//!
+//! Sessions are exported as JSONL in one of two formats:
+//! - [`ExportFormat::Goose`]: the session file format, the metadata on the first line and then
+//!   a message per line, which imports back without losing anything
+//! - [`ExportFormat::OpenAi`]: the OpenAI chat fine-tuning format, a line per session with its
+//!   messages as they are sent to OpenAI models, so transcripts can be used as a dataset
+//!
+//! Sessions can also be exported as a transcript to read, in [`ExportFormat::Markdown`] or
+//! [`ExportFormat::Html`], see [`super::transcript`].
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use super::storage::SessionMetadata;
+use super::store::SavedSession;
+use super::transcript;
+use crate::message::Message;
+use crate::providers::formats::openai::format_messages;
+use crate::providers::utils::ImageFormat;
+use mcp_core::role::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Goose,
+    OpenAi,
+    Markdown,
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "goose" => Ok(ExportFormat::Goose),
+            "openai" => Ok(ExportFormat::OpenAi),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(anyhow!(
+                "Unknown export format '{}', expected one of goose, openai, markdown, html",
+                s
+            )),
+        }
+    }
+}
+
+/// Write a session to `writer` in the given format
+///
+/// Returns false if nothing was written, as a session without a reply from the model makes no
+/// fine-tuning example.
+pub fn export_session(
+    session: &SavedSession,
+    format: ExportFormat,
+    writer: &mut impl Write,
+) -> Result<bool> {
+    match format {
+        ExportFormat::Goose => {
+            serde_json::to_writer(&mut *writer, &session.metadata)?;
+            writeln!(writer)?;
+            for message in &session.messages {
+                serde_json::to_writer(&mut *writer, message)?;
+                writeln!(writer)?;
+            }
+            Ok(true)
+        }
+        ExportFormat::OpenAi => match fine_tuning_example(&session.messages) {
+            Some(example) => {
+                serde_json::to_writer(&mut *writer, &example)?;
+                writeln!(writer)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        },
+        ExportFormat::Markdown => {
+            transcript::write_markdown(session, writer)?;
+            Ok(true)
+        }
+        ExportFormat::Html => {
+            transcript::write_html(session, writer)?;
+            Ok(true)
+        }
+    }
+}
+
+/// A session's messages as an example for OpenAI chat fine-tuning, if the model replied
+fn fine_tuning_example(messages: &[Message]) -> Option<Value> {
+    if !messages
+        .iter()
+        .any(|message| message.role == Role::Assistant)
+    {
+        return None;
+    }
+    Some(json!({"messages": format_messages(messages, &ImageFormat::OpenAi)}))
+}
+
+/// Read a session exported in the goose format
+///
+/// Session files can be imported as they are, including old ones that start without metadata.
+pub fn import_session(reader: impl BufRead) -> Result<SavedSession> {
+    let mut metadata = None;
+    let mut messages = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if metadata.is_none() && messages.is_empty() {
+            if let Ok(first) = serde_json::from_str::<SessionMetadata>(&line) {
+                metadata = Some(first);
+                continue;
+            }
+            if serde_json::from_str::<Value>(&line)
+                .is_ok_and(|value| value.get("messages").is_some())
+            {
+                return Err(anyhow!(
+                    "This is a fine-tuning export, which can't be imported; export the session in \
+                     the goose format to move it"
+                ));
+            }
+        }
+        let message = serde_json::from_str::<Message>(&line)
+            .map_err(|e| anyhow!("Line {} is not a message: {}", number + 1, e))?;
+        messages.push(message);
+    }
+
+    let mut metadata = metadata.unwrap_or_default();
+    metadata.message_count = messages.len();
+    Ok(SavedSession { metadata, messages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::tool::ToolCall;
+    use mcp_core::Content;
+    use std::path::PathBuf;
+
+    fn session() -> SavedSession {
+        let mut metadata = SessionMetadata::new(PathBuf::from("/tmp/project"));
+        metadata.description = "Listing files".to_string();
+        metadata.accumulated_cost = Some(0.02);
+        metadata
+            .custom
+            .insert("ticket".to_string(), json!("OPS-1234"));
+        SavedSession {
+            metadata,
+            messages: vec![
+                Message::user()
+                    .with_text("List the files")
+                    .with_metadata("source", "slack")
+                    .with_metadata("tags", json!(["triage"])),
+                Message::assistant().with_tool_request(
+                    "call_1",
+                    Ok(ToolCall::new("developer__shell", json!({"command": "ls"}))),
+                ),
+                Message::user().with_tool_response("call_1", Ok(vec![Content::text("main.rs")])),
+                Message::assistant().with_text("There is one file, main.rs"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_goose_round_trip() -> Result<()> {
+        let session = session();
+        let mut exported = Vec::new();
+        assert!(export_session(
+            &session,
+            ExportFormat::Goose,
+            &mut exported
+        )?);
+
+        let imported = import_session(exported.as_slice())?;
+        assert_eq!(imported.messages, session.messages);
+        assert_eq!(imported.metadata.description, "Listing files");
+        assert_eq!(imported.metadata.working_dir, session.metadata.working_dir);
+        assert_eq!(imported.metadata.accumulated_cost, Some(0.02));
+        assert_eq!(imported.metadata.message_count, 4);
+        assert_eq!(imported.metadata.custom["ticket"], "OPS-1234");
+        assert_eq!(imported.messages[0].metadata["source"], "slack");
+        Ok(())
+    }
+
+    #[test]
+    fn test_openai_export() -> Result<()> {
+        let mut exported = Vec::new();
+        assert!(export_session(
+            &session(),
+            ExportFormat::OpenAi,
+            &mut exported
+        )?);
+        let lines: Vec<Value> = exported
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<serde_json::Result<_>>()?;
+        assert_eq!(lines.len(), 1);
+
+        let messages = lines[0]["messages"].as_array().unwrap();
+        let roles: Vec<_> = messages
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "assistant"]);
+        assert_eq!(
+            messages[1]["tool_calls"][0]["function"]["name"],
+            "developer__shell"
+        );
+        assert_eq!(messages[2]["tool_call_id"], "call_1");
+        assert_eq!(messages[2]["content"], "main.rs");
+
+        // Sessions the model never replied to are left out of datasets
+        let unanswered = SavedSession {
+            metadata: SessionMetadata::default(),
+            messages: vec![Message::user().with_text("Hello")],
+        };
+        let mut exported = Vec::new();
+        assert!(!export_session(
+            &unanswered,
+            ExportFormat::OpenAi,
+            &mut exported
+        )?);
+        assert!(exported.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let example = r#"{"messages": [{"role": "user", "content": "Hello"}]}"#;
+        assert!(import_session(example.as_bytes())
+            .unwrap_err()
+            .to_string()
+            .contains("fine-tuning"));
+
+        let message = serde_json::to_string(&Message::user().with_text("Hello")).unwrap();
+        let broken = format!("{}\n{{\"role\": \"user\"", message);
+        assert!(import_session(broken.as_bytes())
+            .unwrap_err()
+            .to_string()
+            .starts_with("Line 2"));
+    }
+}
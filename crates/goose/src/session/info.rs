@@ -1,7 +1,7 @@
 use anyhow::Result;
 use serde::Serialize;
 
-use crate::session::{self, SessionMetadata};
+use crate::session::{self, store, SessionMetadata};
 
 #[derive(Serialize)]
 pub struct SessionInfo {
@@ -19,7 +19,7 @@ pub fn get_session_info() -> Result<Vec<SessionInfo>> {
             return Err(anyhow::anyhow!("Failed to list sessions"));
         }
     };
-    let session_infos = sessions
+    let mut session_infos: Vec<SessionInfo> = sessions
         .into_iter()
         .map(|(id, path)| {
             // Get last modified time as string
@@ -45,5 +45,29 @@ pub fn get_session_info() -> Result<Vec<SessionInfo>> {
         })
         .collect();
 
+    // Sessions from other machines sharing the session store have no file here until resumed
+    if let Some(store) = store::global() {
+        match store.list() {
+            Ok(stored) => {
+                for stored in stored {
+                    if session_infos.iter().any(|info| info.id == stored.id) {
+                        continue;
+                    }
+                    let path = session::get_path(session::Identifier::Name(stored.id.clone()));
+                    session_infos.push(SessionInfo {
+                        id: stored.id,
+                        path: path.to_string_lossy().to_string(),
+                        modified: stored
+                            .updated_at
+                            .format("%Y-%m-%d %H:%M:%S UTC")
+                            .to_string(),
+                        metadata: stored.metadata,
+                    });
+                }
+            }
+            Err(e) => tracing::warn!("Failed to list sessions in the session store: {}", e),
+        }
+    }
+
     Ok(session_infos)
 }
@@ -1,11 +1,24 @@
+pub mod archive;
+pub mod checkpoint;
+pub mod encryption;
+pub mod export;
 pub mod info;
+pub mod pause;
+pub mod replay;
+pub mod retention;
+pub mod stats;
 pub mod storage;
+pub mod store;
+pub mod transcript;
 
 // Re-export common session types and functions
 pub use storage::{
-    ensure_session_dir, generate_description, generate_session_id, get_most_recent_session,
-    get_path, list_sessions, persist_messages, read_messages, read_metadata, update_metadata,
-    Identifier, SessionMetadata,
+    delete_turn, edit_message, ensure_session_dir, fork, generate_description, generate_session_id,
+    get_most_recent_session, get_path, list_sessions, persist_messages, read_messages,
+    read_metadata, resume, search, session_exists, undo_turn, update_metadata, ForkPoint,
+    Identifier, Revision, RevisionKind, SessionMetadata,
 };
+pub use store::{SavedSession, SearchHit, SearchQuery, SessionStore, StoredSession};
 
 pub use info::{get_session_info, SessionInfo};
+pub use stats::SessionStats;
@@ -0,0 +1,112 @@
+//! Pausing a run of the agent between tool calls, to resume it later
+//!
+//! A run can stop once the results of its tool calls are in, before it asks the model what to do
+//! next. Everything the run needs to go on is then in the session's messages, so pausing only
+//! marks the session with [`SessionMetadata::paused`]. Resuming the session, even from another
+//! process days later, sends the messages back to the agent as they are and the run picks up
+//! with its next step.
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::storage::{read_metadata, update_metadata, SessionMetadata};
+use crate::message::{Message, MessageContent};
+
+/// A run of the agent that was paused between tool calls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PausedRun {
+    /// When the run was paused, as a Unix timestamp
+    pub paused_at: i64,
+    /// The number of messages in the session when it was paused
+    pub message_count: usize,
+}
+
+/// Whether a run can pause right after `message`, which holds the results of tool calls
+pub fn can_pause_after(message: &Message) -> bool {
+    !message.content.is_empty()
+        && message
+            .content
+            .iter()
+            .all(|content| matches!(content, MessageContent::ToolResponse(_)))
+}
+
+/// Mark the session's run as paused after the last of `messages`
+pub async fn pause(session_file: &Path, messages: &[Message]) -> Result<PausedRun> {
+    if !messages.last().is_some_and(can_pause_after) {
+        return Err(anyhow!(
+            "A run can only be paused once the results of its tool calls are in"
+        ));
+    }
+    let paused = PausedRun {
+        paused_at: Utc::now().timestamp(),
+        message_count: messages.len(),
+    };
+    let metadata = SessionMetadata {
+        paused: Some(paused.clone()),
+        ..read_metadata(session_file)?
+    };
+    update_metadata(session_file, &metadata).await?;
+    Ok(paused)
+}
+
+/// Clear the session's paused run, returning it if the run can go on from `messages`
+///
+/// A run can't go on once the session has moved past where it was paused, such as when the
+/// user sent another message or undid the turn.
+pub async fn take_paused(session_file: &Path, messages: &[Message]) -> Result<Option<PausedRun>> {
+    let mut metadata = read_metadata(session_file)?;
+    let Some(paused) = metadata.paused.take() else {
+        return Ok(None);
+    };
+    update_metadata(session_file, &metadata).await?;
+    let resumable =
+        paused.message_count == messages.len() && messages.last().is_some_and(can_pause_after);
+    Ok(resumable.then_some(paused))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::storage::save_messages_with_metadata;
+    use mcp_core::tool::ToolCall;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_pause_and_resume() -> Result<()> {
+        let dir = tempdir()?;
+        let session_file = dir.path().join("paused.jsonl");
+        let mut messages = vec![
+            Message::user().with_text("Migrate the tests to pytest"),
+            Message::assistant().with_tool_request(
+                "1",
+                Ok(ToolCall::new(
+                    "developer__shell",
+                    json!({"command": "ls tests"}),
+                )),
+            ),
+        ];
+        save_messages_with_metadata(&session_file, &SessionMetadata::default(), &messages)?;
+
+        // Not while the tools are running
+        assert!(pause(&session_file, &messages).await.is_err());
+
+        messages.push(Message::user().with_tool_response("1", Ok(vec![])));
+        save_messages_with_metadata(&session_file, &read_metadata(&session_file)?, &messages)?;
+        let paused = pause(&session_file, &messages).await?;
+        assert_eq!(paused.message_count, 3);
+        assert_eq!(read_metadata(&session_file)?.paused, Some(paused.clone()));
+
+        assert_eq!(take_paused(&session_file, &messages).await?, Some(paused));
+        assert_eq!(read_metadata(&session_file)?.paused, None);
+        assert_eq!(take_paused(&session_file, &messages).await?, None);
+
+        // The session moved on since the pause
+        pause(&session_file, &messages).await?;
+        messages.push(Message::user().with_text("Actually, stop"));
+        assert_eq!(take_paused(&session_file, &messages).await?, None);
+        assert_eq!(read_metadata(&session_file)?.paused, None);
+        Ok(())
+    }
+}
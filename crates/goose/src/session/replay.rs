@@ -0,0 +1,363 @@
+//! Recording sessions to replay them deterministically
+//!
+//! With `GOOSE_RECORD_SESSIONS=true`, every completion the provider returns and every result an
+//! extension's tool returns is appended to the session's recording, kept next to the session
+//! file under `recordings/`. A replay re-drives the agent loop from the recording instead:
+//! completions come from [`ReplayProvider`] and tool calls are answered with the results
+//! recorded for them, so nothing goes over the network and no tool runs. Replays show how the
+//! agent got to its answers, and tests can replay a recorded session to check that the agent
+//! still makes the same calls after a change.
+//!
+//! Completions are matched to requests by the messages sent, falling back to the order they
+//! were recorded in, and tool results by the tool and its arguments. A request the recording has
+//! no answer for means the replay diverged from it, and gets an error.
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use mcp_core::tool::{Tool, ToolCall};
+use mcp_core::{Content, ToolError, ToolResult};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::encryption::{self, SessionCipher};
+use crate::message::Message;
+use crate::model::registry::ModelCapabilities;
+use crate::model::ModelConfig;
+use crate::providers::base::{Provider, ProviderMetadata, ProviderUsage};
+use crate::providers::errors::ProviderError;
+
+/// Config key enabling the recording of sessions
+pub const RECORD_SESSIONS_CONFIG_KEY: &str = "GOOSE_RECORD_SESSIONS";
+
+type Completion = Result<(Message, ProviderUsage), ProviderError>;
+type RecordedToolResult = (ToolCall, ToolResult<Vec<Content>>);
+
+/// An event in a session's recording, stored one per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    /// Recording started, with the model the completions come from
+    Started { model: ModelConfig },
+    /// The provider answered a request
+    Completion {
+        /// The [`request_key`] of the messages sent
+        request: String,
+        result: Completion,
+    },
+    /// A tool call returned
+    ToolResult {
+        tool_call: ToolCall,
+        result: ToolResult<Vec<Content>>,
+    },
+}
+
+/// Where the recording of the session in `session_file` is kept
+pub fn recording_path(session_file: &Path) -> PathBuf {
+    let name = session_file.file_name().unwrap_or_default();
+    session_file
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("recordings")
+        .join(name)
+}
+
+/// Identify a request by the messages sent, leaving out when they were created, which differs
+/// between a recorded run and its replay
+pub fn request_key(messages: &[Message]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for message in messages {
+        let part = serde_json::to_string(&(&message.role, &message.content)).unwrap_or_default();
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+fn recording_record_id(path: &Path) -> String {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    encryption::record_id("recordings", name)
+}
+
+/// Appends events to a session's recording
+pub struct Recorder {
+    file: Mutex<File>,
+    cipher: Option<&'static SessionCipher>,
+    /// The recording's name, which its events are encrypted under
+    record_id: String,
+}
+
+impl Recorder {
+    /// Record to `path`, after what was recorded there before, starting with the model used
+    pub fn create(path: &Path, model: ModelConfig) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let recorder = Self {
+            file: Mutex::new(OpenOptions::new().create(true).append(true).open(path)?),
+            cipher: encryption::global()?,
+            record_id: recording_record_id(path),
+        };
+        recorder.record(&RecordedEvent::Started { model });
+        Ok(recorder)
+    }
+
+    /// Append `event`, logging rather than failing the run if it can't be written
+    pub fn record(&self, event: &RecordedEvent) {
+        let line = match serde_json::to_string(event) {
+            Ok(line) => encryption::seal(self.cipher, &self.record_id, line),
+            Err(e) => {
+                tracing::warn!("Failed to serialize a recorded event: {}", e);
+                return;
+            }
+        };
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{}", line) {
+            tracing::warn!("Failed to write to the session recording: {}", e);
+        }
+    }
+}
+
+/// A session's recording, handing out each recorded answer once
+pub struct Recording {
+    model: ModelConfig,
+    completions: Mutex<Vec<Option<(String, Completion)>>>,
+    tool_results: Mutex<Vec<Option<RecordedToolResult>>>,
+}
+
+impl Recording {
+    /// Read the recording at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let cipher = encryption::global()?;
+        let record_id = recording_record_id(path);
+        let events = BufReader::new(File::open(path)?)
+            .lines()
+            .map(|line| {
+                let line = line?;
+                Ok(serde_json::from_str(&encryption::open(
+                    cipher, &record_id, &line,
+                )?)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(events)
+    }
+
+    pub fn new(events: Vec<RecordedEvent>) -> Result<Self> {
+        let mut model = None;
+        let mut completions = Vec::new();
+        let mut tool_results = Vec::new();
+        for event in events {
+            match event {
+                RecordedEvent::Started { model: started } => {
+                    model.get_or_insert(started);
+                }
+                RecordedEvent::Completion { request, result } => {
+                    completions.push(Some((request, result)))
+                }
+                RecordedEvent::ToolResult { tool_call, result } => {
+                    tool_results.push(Some((tool_call, result)))
+                }
+            }
+        }
+        Ok(Self {
+            model: model.ok_or_else(|| anyhow!("The recording doesn't say which model it used"))?,
+            completions: Mutex::new(completions),
+            tool_results: Mutex::new(tool_results),
+        })
+    }
+
+    /// The recorded answer to a request with `messages`
+    pub fn completion(&self, messages: &[Message]) -> Completion {
+        let request = request_key(messages);
+        let mut completions = self.completions.lock().unwrap();
+        let index = completions
+            .iter()
+            .position(|completion| completion.as_ref().is_some_and(|(key, _)| *key == request))
+            .or_else(|| completions.iter().position(Option::is_some))
+            .ok_or_else(|| {
+                ProviderError::ExecutionError(
+                    "The replay asked for a completion past the end of the recording".to_string(),
+                )
+            })?;
+        completions[index].take().map(|(_, result)| result).unwrap()
+    }
+
+    /// Whether the recording has a result for `tool_call` that wasn't replayed yet
+    pub fn has_tool_result(&self, tool_call: &ToolCall) -> bool {
+        self.tool_results
+            .lock()
+            .unwrap()
+            .iter()
+            .flatten()
+            .any(|(recorded, _)| recorded == tool_call)
+    }
+
+    /// The recorded result of `tool_call`
+    pub fn tool_result(&self, tool_call: &ToolCall) -> ToolResult<Vec<Content>> {
+        let mut tool_results = self.tool_results.lock().unwrap();
+        let recorded = tool_results
+            .iter_mut()
+            .find(|result| {
+                result
+                    .as_ref()
+                    .is_some_and(|(recorded, _)| recorded == tool_call)
+            })
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                ToolError::ExecutionError(format!(
+                    "The replay diverged from the recording, which has no result for this call to {}",
+                    tool_call.name
+                ))
+            })?;
+        recorded.1
+    }
+
+    /// The numbers of recorded completions and tool results that weren't replayed
+    pub fn unused(&self) -> (usize, usize) {
+        (
+            self.completions.lock().unwrap().iter().flatten().count(),
+            self.tool_results.lock().unwrap().iter().flatten().count(),
+        )
+    }
+}
+
+/// Records the completions of the provider it wraps
+pub struct RecordingProvider {
+    inner: Arc<Box<dyn Provider>>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Arc<Box<dyn Provider>>, recorder: Arc<Recorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        let result = self.inner.complete(system, messages, tools).await;
+        self.recorder.record(&RecordedEvent::Completion {
+            request: request_key(messages),
+            result: result.clone(),
+        });
+        result
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        self.inner.capabilities()
+    }
+
+    async fn fetch_supported_models(&self) -> Result<Option<Vec<String>>, ProviderError> {
+        self.inner.fetch_supported_models().await
+    }
+}
+
+/// Answers completions from a recording
+pub struct ReplayProvider {
+    recording: Arc<Recording>,
+}
+
+impl ReplayProvider {
+    pub fn new(recording: Arc<Recording>) -> Self {
+        Self { recording }
+    }
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        _system: &str,
+        messages: &[Message],
+        _tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        self.recording.completion(messages)
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.recording.model.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::Usage;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn completion(text: &str) -> Completion {
+        Ok((
+            Message::assistant().with_text(text),
+            ProviderUsage::new("gpt-4o".to_string(), Usage::default()),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() -> Result<()> {
+        let dir = tempdir()?;
+        let path = recording_path(&dir.path().join("session.jsonl"));
+        let first = [Message::user().with_text("List the tests")];
+        let second = [Message::user().with_text("Run them")];
+        let list = ToolCall::new("developer__shell", json!({"command": "ls tests"}));
+
+        let recorder = Recorder::create(&path, ModelConfig::new("gpt-4o".to_string()))?;
+        for (messages, text) in [(&first, "Listing"), (&second, "Running")] {
+            recorder.record(&RecordedEvent::Completion {
+                request: request_key(messages),
+                result: completion(text),
+            });
+        }
+        recorder.record(&RecordedEvent::ToolResult {
+            tool_call: list.clone(),
+            result: Ok(vec![Content::text("test_replay.rs")]),
+        });
+        drop(recorder);
+
+        let recording = Arc::new(Recording::load(&path)?);
+        let provider = ReplayProvider::new(recording.clone());
+        assert_eq!(provider.get_model_config().model_name, "gpt-4o");
+
+        // Completions are matched by their messages, whatever order they are asked for in
+        let (message, _) = provider.complete("", &second, &[]).await?;
+        assert_eq!(message.as_concat_text(), "Running");
+        let (message, _) = provider
+            .complete("", &[Message::user().with_text("Something else")], &[])
+            .await?;
+        assert_eq!(message.as_concat_text(), "Listing");
+        assert!(provider.complete("", &first, &[]).await.is_err());
+
+        assert!(recording.has_tool_result(&list));
+        assert_eq!(recording.unused(), (0, 1));
+        let other = ToolCall::new("developer__shell", json!({"command": "rm -rf tests"}));
+        assert!(recording.tool_result(&other).is_err());
+        assert_eq!(
+            recording.tool_result(&list)?,
+            vec![Content::text("test_replay.rs")]
+        );
+        assert!(recording.tool_result(&list).is_err());
+        Ok(())
+    }
+}
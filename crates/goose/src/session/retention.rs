@@ -0,0 +1,279 @@
+//! Limits on how many saved sessions are kept
+//!
+//! Session files are never removed by goose itself unless a retention policy is set, with any of
+//! `GOOSE_SESSION_MAX_COUNT`, `GOOSE_SESSION_MAX_AGE_DAYS` and `GOOSE_SESSION_MAX_SIZE_MB`. The
+//! oldest sessions past a limit are then removed, from the session dir and the session store,
+//! by [`purge`], which [`start_cleanup`] runs in the background every few hours.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use super::archive;
+use super::replay;
+use super::storage::{self, ensure_session_dir};
+use super::store;
+use crate::config::Config;
+
+/// Sessions updated this recently are kept whatever the limits, as they may still be running
+const ACTIVE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Which sessions to keep; sessions past any of the limits are removed, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// The most sessions to keep
+    pub max_sessions: Option<usize>,
+    /// Remove sessions not updated for this many days
+    pub max_age_days: Option<u64>,
+    /// The most disk space the session files may take, in megabytes
+    pub max_size_mb: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// The policy set with `GOOSE_SESSION_MAX_COUNT`, `GOOSE_SESSION_MAX_AGE_DAYS` and
+    /// `GOOSE_SESSION_MAX_SIZE_MB`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_sessions: config.get_param("GOOSE_SESSION_MAX_COUNT").ok(),
+            max_age_days: config.get_param("GOOSE_SESSION_MAX_AGE_DAYS").ok(),
+            max_size_mb: config.get_param("GOOSE_SESSION_MAX_SIZE_MB").ok(),
+        }
+    }
+
+    /// Whether every session is kept
+    pub fn is_unlimited(&self) -> bool {
+        self.max_sessions.is_none() && self.max_age_days.is_none() && self.max_size_mb.is_none()
+    }
+}
+
+/// A session removed, or that would be removed, by [`purge`]
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgedSession {
+    pub id: String,
+    pub updated_at: DateTime<Utc>,
+    /// The size of the session file in bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeReport {
+    pub sessions: Vec<PurgedSession>,
+    /// The disk space freed in bytes
+    pub freed: u64,
+}
+
+#[derive(Debug, Clone)]
+struct SessionFile {
+    id: String,
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Remove the sessions past the limits of `policy`, or with `dry_run`, only list them
+///
+/// Sessions updated in the last hour are always kept, as they may still be running.
+pub fn purge(policy: &RetentionPolicy, dry_run: bool) -> Result<PurgeReport> {
+    let mut files = session_files()?;
+    files.sort_by_key(|file| Reverse(file.modified));
+
+    let mut report = PurgeReport::default();
+    for file in expired(files, policy, SystemTime::now()) {
+        if !dry_run {
+            // An archived session's messages are in the archive, which are removed first so
+            // they aren't left there without the session that points to them
+            if let Some(archived) = storage::read_metadata(&file.path)
+                .ok()
+                .and_then(|metadata| metadata.archived)
+            {
+                let removed = match archive::global() {
+                    Some(archive) => archive.remove(&archived),
+                    None => Err(anyhow!(
+                        "set GOOSE_SESSION_ARCHIVE_URL to remove {}",
+                        archived.url
+                    )),
+                };
+                if let Err(e) = removed {
+                    tracing::warn!("Keeping archived session {}: {}", file.id, e);
+                    continue;
+                }
+            }
+            fs::remove_file(&file.path)?;
+            let recording = replay::recording_path(&file.path);
+            if recording.exists() {
+                fs::remove_file(&recording)?;
+            }
+            // Only the store's copy of this file, a session saved since under the same id on
+            // another machine is kept
+            if let Some(store) = store::global() {
+                let saved_by = DateTime::<Utc>::from(file.modified) + store::SAVE_SLACK;
+                if let Err(e) = store.delete(&file.id, Some(saved_by)) {
+                    tracing::warn!("Failed to delete session {} from the store: {}", file.id, e);
+                }
+            }
+        }
+        report.freed += file.size;
+        report.sessions.push(PurgedSession {
+            id: file.id,
+            updated_at: file.modified.into(),
+            size: file.size,
+        });
+    }
+    Ok(report)
+}
+
+/// Start cleaning up old sessions every few hours, if it isn't running already
+///
+/// Closed sessions are moved to the session archive if one is configured, see
+/// [`super::archive`], and then those past the retention policy are purged. The policy is read
+/// again each time, so changes to it apply without a restart. This needs to be called from
+/// within a tokio runtime.
+pub fn start_cleanup() {
+    static CLEANING: OnceCell<()> = OnceCell::new();
+    CLEANING.get_or_init(|| {
+        tokio::spawn(async {
+            loop {
+                if let Some(archive) = archive::global() {
+                    let after = archive::archive_after(Config::global());
+                    match tokio::task::spawn_blocking(move || archive.archive_old_sessions(after))
+                        .await
+                    {
+                        Ok(Ok(archived)) if !archived.is_empty() => {
+                            tracing::info!("Archived {} old sessions", archived.len())
+                        }
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => tracing::warn!("Failed to archive old sessions: {}", e),
+                        Err(e) => tracing::warn!("Failed to archive old sessions: {}", e),
+                    }
+                }
+                let policy = RetentionPolicy::from_config(Config::global());
+                if !policy.is_unlimited() {
+                    match tokio::task::spawn_blocking(move || purge(&policy, false)).await {
+                        Ok(Ok(report)) if !report.sessions.is_empty() => tracing::info!(
+                            "Removed {} old sessions, freeing {} bytes",
+                            report.sessions.len(),
+                            report.freed
+                        ),
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => tracing::warn!("Failed to remove old sessions: {}", e),
+                        Err(e) => tracing::warn!("Failed to remove old sessions: {}", e),
+                    }
+                }
+                tokio::time::sleep(CLEANUP_INTERVAL).await;
+            }
+        });
+    });
+}
+
+fn session_files() -> Result<Vec<SessionFile>> {
+    let session_dir = ensure_session_dir()?;
+    Ok(fs::read_dir(&session_dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            if path.extension()? != "jsonl" {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            Some(SessionFile {
+                id: path.file_stem()?.to_string_lossy().to_string(),
+                modified: metadata.modified().ok()?,
+                size: metadata.len(),
+                path,
+            })
+        })
+        .collect())
+}
+
+/// The sessions past the limits, from files sorted most recently updated first
+fn expired(files: Vec<SessionFile>, policy: &RetentionPolicy, now: SystemTime) -> Vec<SessionFile> {
+    let max_age = policy.max_age_days.map(|days| DAY * days as u32);
+    let max_size = policy.max_size_mb.map(|mb| mb * 1024 * 1024);
+    let (mut kept, mut kept_size) = (0, 0);
+    let mut expired = Vec::new();
+    for file in files {
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        let over = policy.max_sessions.is_some_and(|max| kept >= max)
+            || max_age.is_some_and(|max| age > max)
+            || max_size.is_some_and(|max| kept_size + file.size > max);
+        if over && age > ACTIVE_WINDOW {
+            expired.push(file);
+        } else {
+            kept += 1;
+            kept_size += file.size;
+        }
+    }
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: &str, days_ago: u32, size: u64, now: SystemTime) -> SessionFile {
+        SessionFile {
+            id: id.to_string(),
+            path: PathBuf::from(format!("{}.jsonl", id)),
+            modified: now - DAY * days_ago,
+            size,
+        }
+    }
+
+    fn expired_ids(files: &[SessionFile], policy: RetentionPolicy, now: SystemTime) -> Vec<String> {
+        expired(files.to_vec(), &policy, now)
+            .into_iter()
+            .map(|file| file.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_expired() {
+        let now = SystemTime::now();
+        let mb = 1024 * 1024;
+        let files = [
+            file("running", 0, 3 * mb, now),
+            file("yesterday", 1, mb, now),
+            file("last_week", 7, mb, now),
+            file("last_year", 365, mb, now),
+        ];
+
+        assert!(expired_ids(&files, RetentionPolicy::default(), now).is_empty());
+        let policy = RetentionPolicy {
+            max_sessions: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired_ids(&files, policy, now),
+            vec!["last_week", "last_year"]
+        );
+        let policy = RetentionPolicy {
+            max_age_days: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(expired_ids(&files, policy, now), vec!["last_year"]);
+        let policy = RetentionPolicy {
+            max_size_mb: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(expired_ids(&files, policy, now), vec!["last_year"]);
+
+        // A session that may still be running is kept even past every limit
+        let policy = RetentionPolicy {
+            max_sessions: Some(0),
+            max_age_days: Some(0),
+            max_size_mb: Some(0),
+        };
+        assert_eq!(
+            expired_ids(&files, policy, now),
+            vec!["yesterday", "last_week", "last_year"]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Statistics on where a session's time and money went
+//!
+//! The stats are computed from the messages each time a session is saved and kept in its
+//! metadata, so listing sessions doesn't have to read them, see [`SessionMetadata::stats`].
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use super::storage::{self, SessionMetadata};
+use super::store::argument_paths;
+use crate::compaction::starts_turn;
+use crate::message::{Message, MessageContent};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionStats {
+    /// The number of messages the user sent, each starting a turn
+    pub turns: usize,
+    /// The number of calls to each tool
+    pub tool_calls: BTreeMap<String, usize>,
+    /// The number of tool calls that returned an error
+    pub failed_tool_calls: usize,
+    /// Tokens summed over every completion in the session
+    pub tokens: Option<i64>,
+    /// Estimated cost in USD summed over every completion in the session
+    pub cost: Option<f64>,
+    /// Seconds from the first message to the last
+    pub duration_secs: i64,
+    /// Seconds from the start of each turn to its last message, summed, leaving out the time
+    /// the session sat waiting for the user
+    pub active_secs: i64,
+    /// The files and directories given to tools, sorted
+    pub files_touched: Vec<String>,
+}
+
+impl SessionStats {
+    pub fn new(metadata: &SessionMetadata, messages: &[Message]) -> Self {
+        let mut stats = Self {
+            tokens: metadata.accumulated_tokens,
+            cost: metadata.accumulated_cost,
+            ..Default::default()
+        };
+        let mut files = BTreeSet::new();
+        let mut turn_start = None;
+        for (i, message) in messages.iter().enumerate() {
+            if starts_turn(message) {
+                stats.turns += 1;
+                turn_start = Some(message.created);
+            }
+            let turn_ends = messages.get(i + 1).is_none_or(starts_turn);
+            if let Some(start) = turn_start.filter(|_| turn_ends) {
+                stats.active_secs += (message.created - start).max(0);
+            }
+
+            for content in &message.content {
+                match content {
+                    MessageContent::ToolRequest(request) => {
+                        if let Ok(call) = &request.tool_call {
+                            *stats.tool_calls.entry(call.name.clone()).or_default() += 1;
+                            files.extend(argument_paths(&call.arguments));
+                        }
+                    }
+                    MessageContent::ToolResponse(response) if response.tool_result.is_err() => {
+                        stats.failed_tool_calls += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let (Some(first), Some(last)) = (messages.first(), messages.last()) {
+            stats.duration_secs = (last.created - first.created).max(0);
+        }
+        stats.files_touched = files.into_iter().collect();
+        stats
+    }
+
+    /// The total number of tool calls
+    pub fn total_tool_calls(&self) -> usize {
+        self.tool_calls.values().sum()
+    }
+}
+
+/// The stats of a saved session from its messages as they are now, including sessions saved
+/// before stats were kept
+pub fn session_stats(session_file: &Path) -> Result<SessionStats> {
+    let saved = storage::resume(session_file)?;
+    Ok(SessionStats::new(&saved.metadata, &saved.messages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::tool::ToolCall;
+    use mcp_core::ToolError;
+    use serde_json::json;
+
+    #[test]
+    fn test_session_stats() {
+        let at = |message: Message, created: i64| Message { created, ..message };
+        let messages = vec![
+            at(Message::user().with_text("Fix the build"), 1000),
+            at(
+                Message::assistant().with_tool_request(
+                    "call_1",
+                    Ok(ToolCall::new(
+                        "developer__text_editor",
+                        json!({"command": "view", "path": "src/main.rs"}),
+                    )),
+                ),
+                1010,
+            ),
+            at(
+                Message::user().with_tool_response("call_1", Ok(vec![])),
+                1011,
+            ),
+            at(
+                Message::assistant().with_tool_request(
+                    "call_2",
+                    Ok(ToolCall::new(
+                        "developer__shell",
+                        json!({"command": "cargo build"}),
+                    )),
+                ),
+                1020,
+            ),
+            at(
+                Message::user().with_tool_response(
+                    "call_2",
+                    Err(ToolError::ExecutionError("exit code 101".to_string())),
+                ),
+                1050,
+            ),
+            at(
+                Message::assistant().with_text("It needs a newer compiler"),
+                1060,
+            ),
+            // The user came back an hour later
+            at(Message::user().with_text("Check the lib too"), 4660),
+            at(
+                Message::assistant().with_tool_request(
+                    "call_3",
+                    Ok(ToolCall::new(
+                        "developer__text_editor",
+                        json!({"command": "view", "path": "src/lib.rs"}),
+                    )),
+                ),
+                4670,
+            ),
+            at(
+                Message::user().with_tool_response("call_3", Ok(vec![])),
+                4680,
+            ),
+        ];
+        let metadata = SessionMetadata {
+            accumulated_tokens: Some(4200),
+            accumulated_cost: Some(0.03),
+            ..Default::default()
+        };
+
+        let stats = SessionStats::new(&metadata, &messages);
+        assert_eq!(stats.turns, 2);
+        assert_eq!(stats.tool_calls["developer__text_editor"], 2);
+        assert_eq!(stats.tool_calls["developer__shell"], 1);
+        assert_eq!(stats.total_tool_calls(), 3);
+        assert_eq!(stats.failed_tool_calls, 1);
+        assert_eq!(stats.tokens, Some(4200));
+        assert_eq!(stats.cost, Some(0.03));
+        assert_eq!(stats.duration_secs, 3680);
+        assert_eq!(stats.active_secs, 80);
+        assert_eq!(stats.files_touched, vec!["src/lib.rs", "src/main.rs"]);
+
+        assert_eq!(SessionStats::new(&metadata, &[]).duration_secs, 0);
+    }
+}
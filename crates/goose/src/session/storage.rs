@@ -1,7 +1,16 @@
-use crate::message::Message;
+use super::archive::{self, ArchivedSession};
+use super::checkpoint::Checkpoint;
+use super::encryption;
+use super::pause::PausedRun;
+use super::stats::SessionStats;
+use super::store::{self, SavedSession, SearchHit, SearchQuery};
+use crate::compaction::{self, ConversationSummary};
+use crate::config::Config;
+use crate::message::{Message, MessageContent, Metadata};
+use crate::model::ModelConfig;
 use crate::providers::base::Provider;
 use anyhow::Result;
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
 use etcetera::{choose_app_strategy, AppStrategy, AppStrategyArgs};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
@@ -23,10 +32,90 @@ pub struct SessionMetadata {
     pub working_dir: PathBuf,
     /// A short description of the session, typically 3 words or less
     pub description: String,
+    /// A sentence on what the session is about, written with the description
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+    /// The number of turns the description was written from
+    #[serde(skip_serializing_if = "is_zero")]
+    pub described_turns: usize,
     /// Number of messages in the session
     pub message_count: usize,
     /// The total number of tokens used in the session. Retrieved from the provider's last usage.
     pub total_tokens: Option<i32>,
+    /// Tokens summed over every completion in the session
+    pub accumulated_tokens: Option<i64>,
+    /// Estimated cost in USD summed over every completion in the session
+    pub accumulated_cost: Option<f64>,
+    /// The session this one was forked from, if it is a fork
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkPoint>,
+    /// The sessions forked from this one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<String>,
+    /// The synopsis sent in place of the first messages, once the session has been compacted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compaction: Option<ConversationSummary>,
+    /// Indexes of the messages the user pinned, which are never summarized away
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pinned_messages: Vec<usize>,
+    /// Values integrations attach to correlate the session with other systems
+    #[serde(skip_serializing_if = "Metadata::is_empty")]
+    pub custom: Metadata,
+    /// Edits and deletions of the session's turns, oldest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<Revision>,
+    /// Statistics on the session as of its last save
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<SessionStats>,
+    /// Where the messages are while the session is archived, see [`super::archive`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived: Option<ArchivedSession>,
+    /// The agent's run, when it was paused between tool calls, see [`super::pause`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<PausedRun>,
+    /// Snapshots of the working directory taken as turns started, see [`super::checkpoint`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checkpoints: Vec<Checkpoint>,
+    /// The number of messages long-term memories were extracted from, see [`crate::memory`]
+    #[serde(skip_serializing_if = "is_zero")]
+    pub remembered_messages: usize,
+}
+
+/// A change that rewound a session to one of its turns, with the messages it replaced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub kind: RevisionKind,
+    /// The index of the first message changed or removed
+    pub message_index: usize,
+    /// When the change was made, as a Unix timestamp
+    pub timestamp: i64,
+    /// The messages from `message_index` on as they were before the change
+    pub original: Vec<Message>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionKind {
+    /// The user rewrote the message that started the turn
+    Edit,
+    /// The turn was removed
+    Delete,
+    /// The agent's reply to the last turn was removed
+    Undo,
+    /// The session and its files were rolled back to a turn's checkpoint
+    Rollback,
+}
+
+/// Where a fork branched off its parent session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForkPoint {
+    pub session_id: String,
+    /// The fork started with the parent's messages before this one
+    pub message_index: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
 }
 
 // Custom deserializer to handle old sessions without working_dir
@@ -38,18 +127,63 @@ impl<'de> Deserialize<'de> for SessionMetadata {
         #[derive(Deserialize)]
         struct Helper {
             description: String,
+            #[serde(default)]
+            summary: String,
+            #[serde(default)]
+            described_turns: usize,
             message_count: usize,
             total_tokens: Option<i32>,
             working_dir: Option<PathBuf>,
+            #[serde(default)]
+            accumulated_tokens: Option<i64>,
+            #[serde(default)]
+            accumulated_cost: Option<f64>,
+            #[serde(default)]
+            forked_from: Option<ForkPoint>,
+            #[serde(default)]
+            forks: Vec<String>,
+            #[serde(default)]
+            compaction: Option<ConversationSummary>,
+            #[serde(default)]
+            pinned_messages: Vec<usize>,
+            #[serde(default)]
+            custom: Metadata,
+            #[serde(default)]
+            revisions: Vec<Revision>,
+            #[serde(default)]
+            stats: Option<SessionStats>,
+            #[serde(default)]
+            archived: Option<ArchivedSession>,
+            #[serde(default)]
+            paused: Option<PausedRun>,
+            #[serde(default)]
+            checkpoints: Vec<Checkpoint>,
+            #[serde(default)]
+            remembered_messages: usize,
         }
 
         let helper = Helper::deserialize(deserializer)?;
 
         Ok(SessionMetadata {
             description: helper.description,
+            summary: helper.summary,
+            described_turns: helper.described_turns,
             message_count: helper.message_count,
             total_tokens: helper.total_tokens,
             working_dir: helper.working_dir.unwrap_or_else(get_home_dir),
+            accumulated_tokens: helper.accumulated_tokens,
+            accumulated_cost: helper.accumulated_cost,
+            forked_from: helper.forked_from,
+            forks: helper.forks,
+            compaction: helper.compaction,
+            pinned_messages: helper.pinned_messages,
+            custom: helper.custom,
+            revisions: helper.revisions,
+            stats: helper.stats,
+            archived: helper.archived,
+            paused: helper.paused,
+            checkpoints: helper.checkpoints,
+            remembered_messages: helper.remembered_messages,
         })
     }
 }
@@ -59,8 +193,23 @@ impl SessionMetadata {
         Self {
             working_dir,
             description: String::new(),
+            summary: String::new(),
+            described_turns: 0,
             message_count: 0,
             total_tokens: None,
+            accumulated_tokens: None,
+            accumulated_cost: None,
+            forked_from: None,
+            forks: Vec::new(),
+            compaction: None,
+            pinned_messages: Vec::new(),
+            custom: Metadata::new(),
+            revisions: Vec::new(),
+            stats: None,
+            archived: None,
+            paused: None,
+            checkpoints: Vec::new(),
+            remembered_messages: 0,
         }
     }
 }
@@ -157,6 +306,307 @@ pub fn list_sessions() -> Result<Vec<(String, PathBuf)>> {
     Ok(entries)
 }
 
+/// The id the lines of a session file are encrypted under, its name
+fn record_session_id(session_file: &Path) -> String {
+    session_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The id of a session in the session store
+///
+/// Only sessions in the session dir are kept in the store, under their name, which is also
+/// the id their usage is recorded under.
+pub(super) fn stored_session_id(session_file: &Path) -> Option<String> {
+    let session_dir = ensure_session_dir().ok()?;
+    if session_file.parent()? != session_dir {
+        return None;
+    }
+    Some(session_file.file_stem()?.to_string_lossy().to_string())
+}
+
+/// Whether a session has been saved, either to its file or to the session store
+pub fn session_exists(session_file: &Path) -> bool {
+    session_file.exists()
+        || stored_session_id(session_file)
+            .zip(store::global())
+            .is_some_and(|(session_id, store)| store.contains(&session_id).unwrap_or(false))
+}
+
+/// Load a session to continue it where it left off
+///
+/// Sessions in the session store are loaded from it, and their file is rewritten if it is
+/// missing or differs, as it does when goose stopped while writing it. A file saved after the
+/// store's copy is kept instead, as the store missed the last save, and is saved to the store.
+/// Archived sessions are downloaded from the archive. Other sessions are read from their file.
+pub fn resume(session_file: &Path) -> Result<SavedSession> {
+    if let Some((session_id, store)) = stored_session_id(session_file).zip(store::global()) {
+        let saved = store.resume(&session_id).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load session from the session store: {}", e);
+            None
+        });
+        if let Some(saved) = saved {
+            if read_messages(session_file).ok().as_ref() == Some(&saved.messages) {
+                return Ok(saved);
+            }
+            // An archived file holds no messages, a copy in the store was saved since on
+            // another machine
+            if is_archived(session_file)
+                || !file_saved_after_store(session_file, store, &session_id)
+            {
+                write_session_file(session_file, &saved.metadata, &saved.messages)?;
+                return Ok(saved);
+            }
+            let local = SavedSession {
+                metadata: read_metadata(session_file)?,
+                messages: read_messages(session_file)?,
+            };
+            if let Err(e) = store.save(&session_id, &local.metadata, &local.messages) {
+                tracing::warn!("Failed to save session to the session store: {}", e);
+            }
+            return Ok(local);
+        }
+    }
+
+    let metadata = read_metadata(session_file)?;
+    if metadata.archived.is_some() {
+        return archive::restore(session_file, &metadata);
+    }
+    Ok(SavedSession {
+        metadata,
+        messages: read_messages(session_file)?,
+    })
+}
+
+/// Whether a session file was cut down to its metadata by [`archive`]
+pub(super) fn is_archived(session_file: &Path) -> bool {
+    read_metadata(session_file).is_ok_and(|metadata| metadata.archived.is_some())
+}
+
+/// Whether a session file was written after the store's copy of the session was saved
+///
+/// Sessions are saved to their file first, so a store's copy is a little newer than the file
+/// it was saved with.
+fn file_saved_after_store(
+    session_file: &Path,
+    store: &dyn store::SessionStore,
+    session_id: &str,
+) -> bool {
+    let modified = match std::fs::metadata(session_file).and_then(|m| m.modified()) {
+        Ok(modified) => DateTime::<Utc>::from(modified),
+        Err(_) => return false,
+    };
+    match store.updated_at(session_id) {
+        // The stores keep milliseconds
+        Ok(Some(updated_at)) => modified.timestamp_millis() > updated_at.timestamp_millis(),
+        Ok(None) => true,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to check the session store's copy of the session: {}",
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Fork a session into a new one that starts with the messages before `message_index`
+///
+/// The fork gets its own copy of those messages, so continuing either session leaves the other
+/// as it was. The sessions are linked through [`SessionMetadata::forked_from`] and
+/// [`SessionMetadata::forks`]. A fork point within the agent's reply to a turn is moved back to
+/// just after the user's message, so the fork never ends with a tool call without its result.
+pub fn fork(session_file: &Path, message_index: usize, fork_file: &Path) -> Result<SavedSession> {
+    let session_id = session_name(session_file)?;
+    let fork_id = session_name(fork_file)?;
+    if session_exists(fork_file) {
+        return Err(anyhow::anyhow!(
+            "A session named {} already exists",
+            fork_id
+        ));
+    }
+    let mut parent = resume(session_file)?;
+    if message_index > parent.messages.len() {
+        return Err(anyhow::anyhow!(
+            "Session {} has {} messages, can't fork it at message {}",
+            session_id,
+            parent.messages.len(),
+            message_index
+        ));
+    }
+
+    let message_index = turn_boundary(&parent.messages, message_index);
+    let messages = parent.messages[..message_index].to_vec();
+    let mut metadata = SessionMetadata::new(parent.metadata.working_dir.clone());
+    metadata.description = parent.metadata.description.clone();
+    metadata.message_count = messages.len();
+    // Pins and a synopsis of the shared messages still apply to the fork
+    metadata.pinned_messages = pins_before(&parent.metadata, message_index);
+    metadata.compaction = parent
+        .metadata
+        .compaction
+        .clone()
+        .filter(|summary| summary.message_count < message_index);
+    metadata.forked_from = Some(ForkPoint {
+        session_id,
+        message_index,
+    });
+    save_messages_with_metadata(fork_file, &metadata, &messages)?;
+
+    parent.metadata.forks.push(fork_id);
+    save_messages_with_metadata(session_file, &parent.metadata, &parent.messages)?;
+    Ok(SavedSession { metadata, messages })
+}
+
+/// The last point at or before `message_index` that is the start of a turn, or just after the
+/// message that started one
+fn turn_boundary(messages: &[Message], message_index: usize) -> usize {
+    let mut index = message_index;
+    while index > 0
+        && index < messages.len()
+        && !compaction::starts_turn(&messages[index])
+        && !compaction::starts_turn(&messages[index - 1])
+    {
+        index -= 1;
+    }
+    index
+}
+
+/// Replace the text of the user message at `message_index`, removing the replies that followed
+///
+/// The edited message keeps its attachments and metadata, so the turn can be regenerated by
+/// replying to it. The replaced messages are recorded in [`SessionMetadata::revisions`].
+pub fn edit_message(session_file: &Path, message_index: usize, text: &str) -> Result<SavedSession> {
+    let mut saved = resume(session_file)?;
+    check_turn(session_file, &saved.messages, message_index)?;
+
+    let mut edited = saved.messages[message_index].clone();
+    edited
+        .content
+        .retain(|content| !matches!(content, MessageContent::Text(_)));
+    edited.content.insert(0, MessageContent::text(text));
+    edited.created = chrono::Utc::now().timestamp();
+
+    // The pin on the edited message is still the user's
+    let pinned_messages = pins_before(&saved.metadata, message_index + 1);
+    rewind(&mut saved, message_index, RevisionKind::Edit);
+    saved.messages.push(edited);
+    saved.metadata.pinned_messages = pinned_messages;
+    saved.metadata.message_count = saved.messages.len();
+    save_messages_with_metadata(session_file, &saved.metadata, &saved.messages)?;
+    Ok(saved)
+}
+
+/// Remove the turn starting at `message_index` and every message after it
+///
+/// The session continues from the turn before, so the next reply regenerates the conversation
+/// from there. The removed messages are recorded in [`SessionMetadata::revisions`].
+pub fn delete_turn(session_file: &Path, message_index: usize) -> Result<SavedSession> {
+    let mut saved = resume(session_file)?;
+    check_turn(session_file, &saved.messages, message_index)?;
+
+    rewind(&mut saved, message_index, RevisionKind::Delete);
+    save_messages_with_metadata(session_file, &saved.metadata, &saved.messages)?;
+    Ok(saved)
+}
+
+/// Remove the agent's reply to the last turn, its tool calls and their results
+///
+/// The session ends with the user's message again, so a bad generation can be dropped and
+/// answered anew. The removed messages are recorded in [`SessionMetadata::revisions`].
+pub fn undo_turn(session_file: &Path) -> Result<SavedSession> {
+    let mut saved = resume(session_file)?;
+    let reply_index = saved
+        .messages
+        .iter()
+        .rposition(compaction::starts_turn)
+        .map(|index| index + 1)
+        .filter(|&index| index < saved.messages.len())
+        .ok_or_else(|| anyhow::anyhow!("There is no reply to undo"))?;
+
+    rewind(&mut saved, reply_index, RevisionKind::Undo);
+    save_messages_with_metadata(session_file, &saved.metadata, &saved.messages)?;
+    Ok(saved)
+}
+
+fn check_turn(session_file: &Path, messages: &[Message], message_index: usize) -> Result<()> {
+    match messages.get(message_index) {
+        Some(message) if compaction::starts_turn(message) => Ok(()),
+        Some(_) => Err(anyhow::anyhow!(
+            "Message {} of session {} isn't one the user wrote",
+            message_index,
+            session_name(session_file)?
+        )),
+        None => Err(anyhow::anyhow!(
+            "Session {} has {} messages, there is no message {}",
+            session_name(session_file)?,
+            messages.len(),
+            message_index
+        )),
+    }
+}
+
+/// Cut a session's messages at `message_index`, recording what was cut
+pub(super) fn rewind(saved: &mut SavedSession, message_index: usize, kind: RevisionKind) {
+    let original = saved.messages.split_off(message_index);
+    let metadata = &mut saved.metadata;
+    metadata.pinned_messages = pins_before(metadata, message_index);
+    // A synopsis that covers the cut messages no longer matches the conversation
+    metadata.compaction = metadata
+        .compaction
+        .take()
+        .filter(|summary| summary.message_count <= message_index);
+    metadata.revisions.push(Revision {
+        kind,
+        message_index,
+        timestamp: chrono::Utc::now().timestamp(),
+        original,
+    });
+    metadata.message_count = saved.messages.len();
+}
+
+fn pins_before(metadata: &SessionMetadata, message_index: usize) -> Vec<usize> {
+    metadata
+        .pinned_messages
+        .iter()
+        .copied()
+        .filter(|&index| index < message_index)
+        .collect()
+}
+
+/// The name of a session, that of its file without the extension
+fn session_name(session_file: &Path) -> Result<String> {
+    session_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow::anyhow!("No session name in {}", session_file.display()))
+}
+
+/// Search past sessions by their messages, the tools they called and the files those touched
+///
+/// Session files that aren't in the session store yet, e.g. from before it existed, are added to
+/// it first so they are searched too.
+pub fn search(query: &SearchQuery) -> Result<Vec<SearchHit>> {
+    if encryption::global()?.is_some() {
+        return Err(anyhow::anyhow!(
+            "Encrypted sessions can't be searched, as they aren't indexed"
+        ));
+    }
+    let store = store::global()
+        .ok_or_else(|| anyhow::anyhow!("The session store couldn't be opened to search"))?;
+    for (session_id, path) in list_sessions()? {
+        if store.contains(&session_id)? {
+            continue;
+        }
+        match read_metadata(&path).and_then(|metadata| Ok((metadata, read_messages(&path)?))) {
+            Ok((metadata, messages)) => store.save(&session_id, &metadata, &messages)?,
+            Err(e) => tracing::warn!("Skipping session {} in search: {}", session_id, e),
+        }
+    }
+    store.search(query)
+}
+
 /// Generate a session ID using timestamp format (yyyymmdd_hhmmss)
 pub fn generate_session_id() -> String {
     Local::now().format("%Y%m%d_%H%M%S").to_string()
@@ -174,13 +624,21 @@ pub fn read_messages(session_file: &Path) -> Result<Vec<Message>> {
         .truncate(false)
         .open(session_file)?;
 
+    let cipher = encryption::global()?;
+    let session_id = record_session_id(session_file);
     let reader = io::BufReader::new(file);
-    let mut lines = reader.lines();
+    let lines = reader.lines().collect::<io::Result<Vec<_>>>()?;
+    encryption::check_lines(&lines)?;
+    let mut lines = lines.into_iter();
     let mut messages = Vec::new();
 
     // Read the first line as metadata or create default if empty/missing
     if let Some(line) = lines.next() {
-        let line = line?;
+        let line = encryption::open(
+            cipher,
+            &encryption::record_id(&session_id, "metadata"),
+            &line,
+        )?;
         // Try to parse as metadata, but if it fails, treat it as a message
         if let Ok(_metadata) = serde_json::from_str::<SessionMetadata>(&line) {
             // Metadata successfully parsed, continue with the rest of the lines as messages
@@ -191,8 +649,12 @@ pub fn read_messages(session_file: &Path) -> Result<Vec<Message>> {
     }
 
     // Read the rest of the lines as messages
-    for line in lines {
-        messages.push(serde_json::from_str::<Message>(&line?)?);
+    for (index, line) in lines.enumerate() {
+        messages.push(serde_json::from_str::<Message>(&encryption::open(
+            cipher,
+            &encryption::record_id(&session_id, index),
+            &line,
+        )?)?);
     }
 
     Ok(messages)
@@ -212,7 +674,19 @@ pub fn read_metadata(session_file: &Path) -> Result<SessionMetadata> {
 
     // Read just the first line
     if reader.read_line(&mut first_line)? > 0 {
+        // Plaintext metadata is only read from a session without encrypted lines
+        let cipher = encryption::global()?;
+        if cipher.is_some() && !encryption::is_encrypted(&first_line) {
+            let mut lines = vec![first_line.clone()];
+            lines.extend(reader.lines().collect::<io::Result<Vec<_>>>()?);
+            encryption::check_lines(&lines)?;
+        }
         // Try to parse as metadata
+        let first_line = encryption::open(
+            cipher,
+            &encryption::record_id(&record_session_id(session_file), "metadata"),
+            &first_line,
+        )?;
         match serde_json::from_str::<SessionMetadata>(&first_line) {
             Ok(metadata) => Ok(metadata),
             Err(_) => {
@@ -235,46 +709,94 @@ pub async fn persist_messages(
     messages: &[Message],
     provider: Option<Arc<Box<dyn Provider>>>,
 ) -> Result<()> {
-    // Count user messages
-    let user_message_count = messages
-        .iter()
-        .filter(|m| m.role == mcp_core::role::Role::User && !m.as_concat_text().trim().is_empty())
-        .count();
+    let turns = count_turns(messages);
 
-    // Check if we need to update the description (after 1st or 3rd user message)
+    // Check if we need to update the description (after 1st or 3rd turn)
+    let metadata = read_metadata(session_file)?;
     match provider {
-        Some(provider) if user_message_count < 4 => {
+        Some(provider)
+            if DESCRIBE_AFTER_TURNS.contains(&turns) && metadata.described_turns < turns =>
+        {
             //generate_description is responsible for writing the messages
             generate_description(session_file, messages, provider.as_ref().as_ref()).await
         }
         _ => {
-            // Read existing metadata
-            let metadata = read_metadata(session_file)?;
             // Write the file with metadata and messages
             save_messages_with_metadata(session_file, &metadata, messages)
         }
     }
 }
 
+/// The number of turns the user started, tool results are sent as user messages too
+fn count_turns(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .filter(|m| compaction::starts_turn(m))
+        .count()
+}
+
+/// The numbers of turns after which the session's description is written, so it is there from
+/// the start and then reflects what the conversation turned out to be about
+const DESCRIBE_AFTER_TURNS: [usize; 2] = [1, 3];
+
 /// Write messages to a session file with the provided metadata
 ///
 /// Overwrites the file with metadata as the first line, followed by all messages in JSONL format.
+/// The metadata's stats are brought up to date with the messages. Sessions in the session dir
+/// are also saved to the session store.
 pub fn save_messages_with_metadata(
     session_file: &Path,
     metadata: &SessionMetadata,
     messages: &[Message],
 ) -> Result<()> {
+    let metadata = &SessionMetadata {
+        stats: Some(SessionStats::new(metadata, messages)),
+        ..metadata.clone()
+    };
+    write_session_file(session_file, metadata, messages)?;
+
+    if let Some(session_id) = stored_session_id(session_file) {
+        if let Some(store) = store::global() {
+            if let Err(e) = store.save(&session_id, metadata, messages) {
+                tracing::warn!("Failed to save session to the session store: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn write_session_file(
+    session_file: &Path,
+    metadata: &SessionMetadata,
+    messages: &[Message],
+) -> Result<()> {
+    let cipher = encryption::global()?;
+    let session_id = record_session_id(session_file);
     let file = File::create(session_file).expect("The path specified does not exist");
     let mut writer = io::BufWriter::new(file);
 
     // Write metadata as the first line
-    serde_json::to_writer(&mut writer, &metadata)?;
-    writeln!(writer)?;
+    writeln!(
+        writer,
+        "{}",
+        encryption::seal(
+            cipher,
+            &encryption::record_id(&session_id, "metadata"),
+            serde_json::to_string(metadata)?
+        )
+    )?;
 
     // Write all messages
-    for message in messages {
-        serde_json::to_writer(&mut writer, &message)?;
-        writeln!(writer)?;
+    for (index, message) in messages.iter().enumerate() {
+        writeln!(
+            writer,
+            "{}",
+            encryption::seal(
+                cipher,
+                &encryption::record_id(&session_id, index),
+                serde_json::to_string(message)?
+            )
+        )?;
     }
 
     writer.flush()?;
@@ -283,20 +805,22 @@ pub fn save_messages_with_metadata(
 
 /// Generate a description for the session using the provider
 ///
-/// This function is called when appropriate to generate a short description
-/// of the session based on the conversation history.
+/// This function is called when appropriate to generate a short description and a one sentence
+/// summary of the session based on the conversation history. They are written by the model set
+/// with `GOOSE_TITLE_MODEL` if there is one, as any small model writes them well.
 pub async fn generate_description(
     session_file: &Path,
     messages: &[Message],
     provider: &dyn Provider,
 ) -> Result<()> {
-    // Create a special message asking for a 3-word description
-    let mut description_prompt = "Based on the conversation so far, provide a concise description of this session in 4 words or less. This will be used for finding the session later in a UI with limited space - reply *ONLY* with the description".to_string();
+    // Create a special message asking for a title and a summary
+    let mut description_prompt = "Based on the conversation so far, provide a concise description of this session in 4 words or less, and on the next line a single sentence summarizing what the session is about. These will be used for finding the session later in a UI with limited space - reply *ONLY* with the two lines".to_string();
 
-    // get context from messages so far, limiting each message to 300 chars
+    // get context from the messages that started turns so far
+    let turns = count_turns(messages);
     let context: Vec<String> = messages
         .iter()
-        .filter(|m| m.role == mcp_core::role::Role::User)
+        .filter(|m| compaction::starts_turn(m))
         .take(3) // Use up to first 3 user messages for context
         .map(|m| m.as_concat_text())
         .collect();
@@ -309,28 +833,88 @@ pub async fn generate_description(
         );
     }
 
-    // Generate the description
+    let title_provider = title_provider();
+    let (provider_name, provider) = match &title_provider {
+        Some((name, title_provider)) => (name.clone(), title_provider.as_ref() as &dyn Provider),
+        None => (
+            Config::global()
+                .get_param("GOOSE_PROVIDER")
+                .unwrap_or_else(|_| "unknown".to_string()),
+            provider,
+        ),
+    };
+
+    // Read current metadata
+    let mut metadata = read_metadata(session_file)?;
+
+    // Generate the description, the messages are saved even if that fails
     let message = Message::user().with_text(&description_prompt);
-    let result = provider
+    match provider
         .complete(
-            "Reply with only a description in four words or less",
+            "Reply with only a description in four words or less and a one sentence summary",
             &[message],
             &[],
         )
-        .await?;
-
-    let description = result.0.as_concat_text();
-
-    // Read current metadata
-    let mut metadata = read_metadata(session_file)?;
-
-    // Update description
-    metadata.description = description;
+        .await
+    {
+        Ok((reply, usage)) => {
+            let session_id = session_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string());
+            crate::usage::record(&provider_name, session_id, &usage);
+            let (description, summary) = parse_description(&reply.as_concat_text());
+            metadata.description = description;
+            metadata.summary = summary;
+            metadata.described_turns = turns;
+        }
+        Err(e) => tracing::warn!("Failed to describe the session: {}", e),
+    }
 
     // Update the file with the new metadata and existing messages
     save_messages_with_metadata(session_file, &metadata, messages)
 }
 
+/// The provider for `GOOSE_TITLE_MODEL`, from `GOOSE_TITLE_PROVIDER` or else `GOOSE_PROVIDER`
+fn title_provider() -> Option<(String, Box<dyn Provider + Send + Sync>)> {
+    let config = Config::global();
+    let model: String = config.get_param("GOOSE_TITLE_MODEL").ok()?;
+    let provider: String = config
+        .get_param("GOOSE_TITLE_PROVIDER")
+        .or_else(|_| config.get_param("GOOSE_PROVIDER"))
+        .ok()?;
+    match crate::providers::create(&provider, ModelConfig::new(model)) {
+        Ok(created) => Some((provider, created)),
+        Err(e) => {
+            tracing::warn!("Failed to create the provider for session titles: {}", e);
+            None
+        }
+    }
+}
+
+/// Split the model's reply into the description and the summary, without the quotes and
+/// labels models tend to add
+fn parse_description(reply: &str) -> (String, String) {
+    let mut lines = reply
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = match line.split_once(':') {
+                Some((label, rest))
+                    if ["title", "description", "summary"]
+                        .contains(&label.trim_matches('*').trim().to_lowercase().as_str()) =>
+                {
+                    rest
+                }
+                _ => line,
+            };
+            line.trim_matches(|c: char| c == '"' || c == '*' || c.is_whitespace())
+        })
+        .filter(|line| !line.is_empty());
+    let description = lines.next().unwrap_or_default().to_string();
+    let summary = lines.collect::<Vec<_>>().join(" ");
+    (description, summary)
+}
+
 /// Update only the metadata in a session file, preserving all messages
 pub async fn update_metadata(session_file: &Path, metadata: &SessionMetadata) -> Result<()> {
     // Read all messages from the file
@@ -342,6 +926,7 @@ pub async fn update_metadata(session_file: &Path, metadata: &SessionMetadata) ->
 
 #[cfg(test)]
 mod tests {
+    use super::store::SessionStore;
     use super::*;
     use crate::message::MessageContent;
     use tempfile::tempdir;
@@ -382,6 +967,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_file_saved_after_store() -> Result<()> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("session.jsonl");
+        let store = store::SqliteSessionStore::open_in_memory()?;
+        let metadata = SessionMetadata::default();
+        let messages = vec![Message::user().with_text("Hello")];
+
+        // Saved the usual way, the file first
+        write_session_file(&file_path, &metadata, &messages)?;
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        store.save("session", &metadata, &messages)?;
+        assert!(!file_saved_after_store(&file_path, &store, "session"));
+
+        // The store missed the last save
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        write_session_file(&file_path, &metadata, &messages)?;
+        assert!(file_saved_after_store(&file_path, &store, "session"));
+        Ok(())
+    }
+
     #[test]
     fn test_empty_file() -> Result<()> {
         let dir = tempdir()?;
@@ -394,6 +1000,206 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fork() -> Result<()> {
+        let dir = tempdir()?;
+        let parent_file = dir.path().join("parent.jsonl");
+        let fork_file = dir.path().join("fork.jsonl");
+        let messages = vec![
+            Message::user().with_text("Write a parser"),
+            Message::assistant().with_text("Using nom"),
+            Message::user().with_text("Looks good"),
+        ];
+        let mut metadata = SessionMetadata::new(dir.path().to_path_buf());
+        metadata.description = "Writing a parser".to_string();
+        metadata.pinned_messages = vec![0, 2];
+        save_messages_with_metadata(&parent_file, &metadata, &messages)?;
+
+        let forked = fork(&parent_file, 1, &fork_file)?;
+        assert_eq!(forked.messages, messages[..1]);
+        let fork_metadata = read_metadata(&fork_file)?;
+        assert_eq!(fork_metadata.description, "Writing a parser");
+        assert_eq!(fork_metadata.pinned_messages, vec![0]);
+        assert_eq!(
+            fork_metadata.forked_from,
+            Some(ForkPoint {
+                session_id: "parent".to_string(),
+                message_index: 1
+            })
+        );
+
+        // The parent keeps its messages and links to the fork
+        assert_eq!(read_messages(&parent_file)?, messages);
+        assert_eq!(read_metadata(&parent_file)?.forks, vec!["fork"]);
+
+        // Continuing the fork leaves the parent alone
+        let mut branch = forked.messages;
+        branch.push(Message::assistant().with_text("Using a hand-written parser"));
+        save_messages_with_metadata(&fork_file, &fork_metadata, &branch)?;
+        assert_eq!(read_messages(&parent_file)?, messages);
+
+        assert!(fork(&parent_file, 1, &fork_file).is_err());
+        assert!(fork(&parent_file, 4, &dir.path().join("other.jsonl")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fork_keeps_tool_calls_whole() -> Result<()> {
+        let dir = tempdir()?;
+        let parent_file = dir.path().join("parent.jsonl");
+        let messages = vec![
+            Message::user().with_text("List the files"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(mcp_core::tool::ToolCall::new(
+                    "developer__shell",
+                    serde_json::json!({"command": "ls"}),
+                )),
+            ),
+            Message::user().with_tool_response("call_1", Ok(vec![])),
+            Message::assistant().with_text("The directory is empty"),
+            Message::user().with_text("Thanks"),
+        ];
+        save_messages_with_metadata(&parent_file, &SessionMetadata::default(), &messages)?;
+
+        // Forking between the tool call and its result, or after it, forks after the question
+        for (at, name) in [(2, "a"), (3, "b")] {
+            let forked = fork(
+                &parent_file,
+                at,
+                &dir.path().join(format!("{}.jsonl", name)),
+            )?;
+            assert_eq!(forked.messages, messages[..1]);
+            assert_eq!(forked.metadata.forked_from.unwrap().message_index, 1);
+        }
+        let forked = fork(&parent_file, 4, &dir.path().join("c.jsonl"))?;
+        assert_eq!(forked.messages, messages[..4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_and_delete_turns() -> Result<()> {
+        let dir = tempdir()?;
+        let session_file = dir.path().join("parser.jsonl");
+        let messages = vec![
+            Message::user().with_text("Write a parser"),
+            Message::assistant().with_text("Using nom"),
+            Message::user()
+                .with_text("Add tests")
+                .with_metadata("ticket", "PARSE-7"),
+            Message::assistant().with_text("Added three tests"),
+        ];
+        let mut metadata = SessionMetadata::new(dir.path().to_path_buf());
+        metadata.pinned_messages = vec![0, 2];
+        save_messages_with_metadata(&session_file, &metadata, &messages)?;
+
+        // Only messages the user wrote can be edited
+        assert!(edit_message(&session_file, 1, "Use pest").is_err());
+        assert!(edit_message(&session_file, 4, "Use pest").is_err());
+
+        let edited = edit_message(&session_file, 2, "Add property tests")?;
+        assert_eq!(edited.messages.len(), 3);
+        assert_eq!(edited.messages[2].as_concat_text(), "Add property tests");
+        assert_eq!(edited.messages[2].metadata["ticket"], "PARSE-7");
+        assert_eq!(read_messages(&session_file)?, edited.messages);
+
+        let metadata = read_metadata(&session_file)?;
+        assert_eq!(metadata.pinned_messages, vec![0, 2]);
+        assert_eq!(metadata.revisions.len(), 1);
+        assert_eq!(metadata.revisions[0].kind, RevisionKind::Edit);
+        assert_eq!(metadata.revisions[0].original, messages[2..]);
+
+        let deleted = delete_turn(&session_file, 2)?;
+        assert_eq!(deleted.messages, messages[..2]);
+        let metadata = read_metadata(&session_file)?;
+        assert_eq!(metadata.pinned_messages, vec![0]);
+        assert_eq!(metadata.message_count, 2);
+        assert_eq!(metadata.revisions[1].kind, RevisionKind::Delete);
+        assert_eq!(metadata.revisions[1].original, edited.messages[2..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_turn() -> Result<()> {
+        let dir = tempdir()?;
+        let session_file = dir.path().join("undo.jsonl");
+        let messages = vec![
+            Message::user().with_text("Write a parser"),
+            Message::assistant().with_text("Using nom"),
+            Message::user().with_text("Add tests"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(mcp_core::tool::ToolCall::new(
+                    "developer__shell",
+                    serde_json::json!({"command": "rm -rf tests"}),
+                )),
+            ),
+            Message::user().with_tool_response("call_1", Ok(vec![])),
+            Message::assistant().with_text("I removed the tests"),
+        ];
+        save_messages_with_metadata(
+            &session_file,
+            &SessionMetadata::new(dir.path().to_path_buf()),
+            &messages,
+        )?;
+
+        let undone = undo_turn(&session_file)?;
+        assert_eq!(undone.messages, messages[..3]);
+        assert_eq!(read_messages(&session_file)?, messages[..3]);
+        let revision = &read_metadata(&session_file)?.revisions[0];
+        assert_eq!(revision.kind, RevisionKind::Undo);
+        assert_eq!(revision.message_index, 3);
+        assert_eq!(revision.original, messages[3..]);
+
+        // The last turn has no reply left to undo
+        assert!(undo_turn(&session_file).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_turns_skips_tool_results() {
+        let messages = vec![
+            Message::user().with_text("List the files"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(mcp_core::tool::ToolCall::new(
+                    "developer__shell",
+                    serde_json::json!({"command": "ls"}),
+                )),
+            ),
+            Message::user().with_tool_response("call_1", Ok(vec![])),
+            Message::assistant().with_text("There is a.txt"),
+            Message::user().with_text("Open it"),
+        ];
+        assert_eq!(count_turns(&messages), 2);
+    }
+
+    #[test]
+    fn test_parse_description() {
+        assert_eq!(
+            parse_description(
+                "Parser for config files\nWriting a nom parser for the TOML config.\n"
+            ),
+            (
+                "Parser for config files".to_string(),
+                "Writing a nom parser for the TOML config.".to_string()
+            )
+        );
+        assert_eq!(
+            parse_description(
+                "**Title:** \"Flaky test triage\"\n\nSummary: Finding why the login test fails."
+            ),
+            (
+                "Flaky test triage".to_string(),
+                "Finding why the login test fails.".to_string()
+            )
+        );
+        assert_eq!(
+            parse_description("Listing files"),
+            ("Listing files".to_string(), String::new())
+        );
+    }
+
     #[test]
     fn test_generate_session_id() {
         let id = generate_session_id();
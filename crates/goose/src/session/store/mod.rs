@@ -0,0 +1,414 @@
+//! Stores that persist sessions alongside their session files
+//!
+//! Sessions are saved to a SQLite database in the goose data dir by default. Setting
+//! `GOOSE_SESSION_STORE` selects another store instead:
+//! - `sqlite`: the local database, see [`SqliteSessionStore`]
+//! - `postgres`: a PostgreSQL database at `GOOSE_SESSION_STORE_URL`, see
+//!   [`PostgresSessionStore`], so every goose-server serving a team shares its sessions
+//!
+//! Resuming a session loads it from the store, so it can continue on another machine than the
+//! one it started on, or after goose stopped while writing its session file. The stores also
+//! index the messages, so past sessions can be found with [`SessionStore::search`], unless
+//! sessions are encrypted, in which case nothing is indexed, see [`super::encryption`].
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mcp_core::role::Role;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::encryption::{self, SessionCipher};
+use super::storage::SessionMetadata;
+use crate::config::Config;
+use crate::message::{Message, MessageContent};
+
+mod postgres;
+mod sqlite;
+
+pub(crate) use self::postgres::tls_connector;
+pub use self::postgres::PostgresSessionStore;
+pub use sqlite::SqliteSessionStore;
+
+/// A session as it was last saved
+#[derive(Debug, Clone)]
+pub struct SavedSession {
+    pub metadata: SessionMetadata,
+    pub messages: Vec<Message>,
+}
+
+/// A saved session, without its messages
+#[derive(Debug, Clone)]
+pub struct StoredSession {
+    pub id: String,
+    pub metadata: SessionMetadata,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What to search past sessions for; messages must match all of what is given
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Words in the messages, in any order
+    pub text: Option<String>,
+    /// A tool the assistant called, e.g. `developer__shell` or just `shell`
+    pub tool: Option<String>,
+    /// A file path given to a tool, or part of one, e.g. `src/main.rs`
+    pub path: Option<String>,
+    /// The most hits to return
+    pub limit: usize,
+}
+
+/// A message that matched a search
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub session_id: String,
+    pub description: String,
+    /// The position of the message in the session
+    pub message_index: usize,
+    pub role: String,
+    /// Part of the message around what matched, which is wrapped in [`SNIPPET_START`] and
+    /// [`SNIPPET_END`]
+    pub snippet: String,
+}
+
+pub const SNIPPET_START: &str = "[";
+pub const SNIPPET_END: &str = "]";
+
+/// How long after its file a session is saved to the store, at most
+pub const SAVE_SLACK: Duration = Duration::from_secs(60);
+
+/// A store for the full state of sessions, by session id
+pub trait SessionStore: Send + Sync {
+    /// Save the whole state of a session, replacing what was saved before
+    fn save(
+        &self,
+        session_id: &str,
+        metadata: &SessionMetadata,
+        messages: &[Message],
+    ) -> Result<()>;
+
+    /// Load a session to continue it where it left off, or None if it was never saved
+    fn resume(&self, session_id: &str) -> Result<Option<SavedSession>>;
+
+    /// Whether a session has been saved
+    fn contains(&self, session_id: &str) -> Result<bool>;
+
+    /// When a session was last saved, or None if it never was
+    fn updated_at(&self, session_id: &str) -> Result<Option<DateTime<Utc>>>;
+
+    /// Delete a session and its messages, returning whether it was deleted
+    ///
+    /// With `updated_before`, a session updated since then is kept, as it isn't the copy the
+    /// caller means to delete but one another goose saved under the same id.
+    fn delete(&self, session_id: &str, updated_before: Option<DateTime<Utc>>) -> Result<bool>;
+
+    /// List the saved sessions, most recently updated first
+    fn list(&self) -> Result<Vec<StoredSession>>;
+
+    /// Find the messages matching a query, best matches first
+    fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>>;
+}
+
+/// Create the session store configured by `GOOSE_SESSION_STORE`
+pub fn from_config(config: &Config) -> Result<Box<dyn SessionStore>> {
+    let name = config
+        .get_param::<String>("GOOSE_SESSION_STORE")
+        .unwrap_or_else(|_| "sqlite".to_string());
+    // The same cipher as the session files, so a new key isn't made for each
+    let cipher = encryption::global()?.cloned();
+    match name.as_str() {
+        "sqlite" => Ok(Box::new(
+            SqliteSessionStore::open(&SqliteSessionStore::default_path())?.with_cipher(cipher),
+        )),
+        "postgres" => {
+            let url: String = config.get_param("GOOSE_SESSION_STORE_URL").map_err(|_| {
+                anyhow::anyhow!("GOOSE_SESSION_STORE_URL must be set to use the postgres store")
+            })?;
+            let ca_cert: Option<String> = config.get_param("GOOSE_CA_CERT").ok();
+            Ok(Box::new(
+                PostgresSessionStore::connect(&url, ca_cert.as_deref().map(Path::new))?
+                    .with_cipher(cipher),
+            ))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Unknown GOOSE_SESSION_STORE {}, expected one of sqlite, postgres",
+            name
+        )),
+    }
+}
+
+/// How long to wait before trying to open a store that failed to open again
+const REOPEN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The shared store configured by `GOOSE_SESSION_STORE`
+///
+/// Returns None if the store couldn't be opened, in which case sessions are only kept in their
+/// session files until it opens, as a database that was unreachable is tried again later.
+pub fn global() -> Option<&'static dyn SessionStore> {
+    static STORE: OnceCell<Box<dyn SessionStore>> = OnceCell::new();
+    static FAILED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+    if let Some(store) = STORE.get() {
+        return Some(store.as_ref());
+    }
+
+    let mut failed_at = FAILED_AT.lock().unwrap();
+    if failed_at.is_some_and(|at| at.elapsed() < REOPEN_INTERVAL) {
+        return None;
+    }
+    match STORE.get_or_try_init(|| from_config(Config::global())) {
+        Ok(store) => Some(store.as_ref()),
+        Err(e) => {
+            tracing::warn!("Failed to open session store: {}", e);
+            *failed_at = Some(Instant::now());
+            None
+        }
+    }
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+    }
+}
+
+/// Tool arguments that name files or directories
+const PATH_ARGUMENTS: &[&str] = &[
+    "path",
+    "paths",
+    "file",
+    "file_path",
+    "filename",
+    "directory",
+    "cwd",
+    "working_dir",
+];
+
+/// The searchable parts of a message, each as space separated text
+#[derive(Debug, Default, PartialEq)]
+struct SearchFields {
+    /// The text of the message, the tools' arguments and the text of their results
+    text: String,
+    tools: String,
+    paths: String,
+}
+
+impl SearchFields {
+    fn new(message: &Message) -> Self {
+        let mut text = Vec::new();
+        let mut tools = Vec::new();
+        let mut paths = Vec::new();
+        for content in &message.content {
+            match content {
+                MessageContent::Text(content) => text.push(content.text.clone()),
+                MessageContent::ToolRequest(request) => {
+                    if let Ok(call) = &request.tool_call {
+                        tools.push(call.name.clone());
+                        argument_strings(&call.arguments, None, &mut text, &mut paths);
+                    }
+                }
+                MessageContent::ToolResponse(response) => {
+                    if let Ok(contents) = &response.tool_result {
+                        text.extend(
+                            contents
+                                .iter()
+                                .filter_map(|c| c.as_text())
+                                .map(String::from),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            text: text.join(" "),
+            tools: tools.join(" "),
+            paths: paths.join(" "),
+        }
+    }
+}
+
+/// The paths in tool arguments
+pub(super) fn argument_paths(arguments: &Value) -> Vec<String> {
+    let mut paths = Vec::new();
+    argument_strings(arguments, None, &mut Vec::new(), &mut paths);
+    paths
+}
+
+/// Collect the strings in tool arguments, and separately those that are paths
+fn argument_strings(
+    value: &Value,
+    key: Option<&str>,
+    text: &mut Vec<String>,
+    paths: &mut Vec<String>,
+) {
+    match value {
+        Value::String(s) => {
+            if key.is_some_and(|key| PATH_ARGUMENTS.contains(&key)) {
+                paths.push(s.clone());
+            }
+            text.push(s.clone());
+        }
+        Value::Array(values) => {
+            for value in values {
+                argument_strings(value, key, text, paths);
+            }
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                argument_strings(value, Some(key), text, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The number of leading messages that are already stored
+///
+/// Sessions mostly grow a message at a time, so the stores only rewrite the messages from the
+/// first one that changed. Messages stored before encryption was turned on or off count as
+/// changed, so they are saved the way the rest are.
+fn unchanged_messages(
+    cipher: Option<&SessionCipher>,
+    session_id: &str,
+    stored: &[String],
+    contents: &[String],
+) -> usize {
+    stored
+        .iter()
+        .zip(contents)
+        .enumerate()
+        .take_while(|(idx, (stored, content))| {
+            encryption::is_encrypted(stored) == cipher.is_some()
+                && encryption::open(cipher, &encryption::record_id(session_id, idx), stored)
+                    .is_ok_and(|stored| stored == **content)
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageContent;
+    use mcp_core::tool::ToolCall;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    pub(super) fn conversation() -> Vec<Message> {
+        vec![
+            Message::user().with_text("List the files"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(ToolCall::new("developer__shell", json!({"command": "ls"}))),
+            ),
+            Message::user().with_tool_response("call_1", Ok(vec![])),
+            Message::assistant().with_text("The directory is empty"),
+        ]
+    }
+
+    #[test]
+    fn test_search_fields() {
+        let request = Message::assistant()
+            .with_text("Let me look")
+            .with_tool_request(
+                "call_1",
+                Ok(ToolCall::new(
+                    "developer__text_editor",
+                    json!({"command": "view", "path": "/tmp/project/src/main.rs"}),
+                )),
+            );
+        let fields = SearchFields::new(&request);
+        assert_eq!(fields.tools, "developer__text_editor");
+        assert_eq!(fields.paths, "/tmp/project/src/main.rs");
+        assert!(fields.text.starts_with("Let me look "));
+        assert!(fields.text.contains("/tmp/project/src/main.rs"));
+
+        let response = Message::user()
+            .with_tool_response("call_1", Ok(vec![mcp_core::Content::text("fn main() {}")]));
+        assert_eq!(SearchFields::new(&response).text, "fn main() {}");
+    }
+
+    /// Exercise a store with a session id it hasn't seen before
+    pub(super) fn check_store(store: &dyn SessionStore, session_id: &str) -> Result<()> {
+        assert!(store.resume(session_id)?.is_none());
+        assert!(!store.contains(session_id)?);
+        assert!(store.updated_at(session_id)?.is_none());
+
+        let mut metadata = SessionMetadata::new(PathBuf::from("/tmp/project"));
+        metadata.description = "Listing files".to_string();
+        metadata.accumulated_tokens = Some(1200);
+        metadata.accumulated_cost = Some(0.01);
+        let mut messages = conversation();
+        store.save(session_id, &metadata, &messages[..2])?;
+        store.save(session_id, &metadata, &messages)?;
+        let search = |query: SearchQuery| -> Result<Vec<usize>> {
+            let hits = store.search(&SearchQuery {
+                limit: 100,
+                ..query
+            })?;
+            // A shared database may have other sessions with the same messages
+            Ok(hits
+                .into_iter()
+                .filter(|hit| hit.session_id == session_id)
+                .map(|hit| hit.message_index)
+                .collect())
+        };
+        let shell = || SearchQuery {
+            tool: Some("shell".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            search(SearchQuery {
+                text: Some("files list".to_string()),
+                ..Default::default()
+            })?,
+            vec![0]
+        );
+        assert_eq!(search(shell())?, vec![1]);
+
+        let before = Utc::now() - chrono::Duration::minutes(1);
+        assert!(store.updated_at(session_id)?.is_some_and(|at| at > before));
+        let saved = store.resume(session_id)?.unwrap();
+        assert_eq!(saved.messages, messages);
+        assert_eq!(saved.metadata.working_dir, metadata.working_dir);
+        assert_eq!(saved.metadata.description, "Listing files");
+        assert_eq!(saved.metadata.accumulated_tokens, Some(1200));
+        assert_eq!(saved.metadata.accumulated_cost, Some(0.01));
+        assert!(matches!(
+            saved.messages[1].content[0],
+            MessageContent::ToolRequest(_)
+        ));
+
+        // Messages dropped or replaced, e.g. by truncation, are replaced in the store too
+        messages.truncate(1);
+        messages.push(Message::assistant().with_text("Which directory?"));
+        store.save(session_id, &metadata, &messages)?;
+        assert_eq!(store.resume(session_id)?.unwrap().messages, messages);
+        assert!(store.contains(session_id)?);
+        assert!(search(shell())?.is_empty());
+
+        let listed = store.list()?;
+        assert_eq!(listed[0].id, session_id);
+        assert_eq!(listed[0].metadata.description, "Listing files");
+
+        // A session saved after the copy being deleted is kept
+        let updated_at = store.updated_at(session_id)?.unwrap();
+        let before = updated_at - chrono::Duration::seconds(1);
+        assert!(!store.delete(session_id, Some(before))?);
+        assert!(store.contains(session_id)?);
+
+        // Deleting a session takes its messages out of the search index too
+        assert!(store.delete(session_id, Some(updated_at))?);
+        assert!(!store.contains(session_id)?);
+        assert!(store.resume(session_id)?.is_none());
+        assert!(search(SearchQuery {
+            text: Some("directory".to_string()),
+            ..Default::default()
+        })?
+        .is_empty());
+        assert!(!store.delete(session_id, None)?);
+        Ok(())
+    }
+}
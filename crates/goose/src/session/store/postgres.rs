@@ -0,0 +1,468 @@
+use ::postgres::Client;
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::{ClientConfig, RootCertStore};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use super::super::encryption::{self, SessionCipher};
+use super::{
+    role_name, unchanged_messages, SavedSession, SearchFields, SearchHit, SearchQuery,
+    SessionStore, StoredSession, SNIPPET_END, SNIPPET_START,
+};
+use crate::config::secret_store::off_runtime;
+use crate::message::Message;
+use crate::session::storage::SessionMetadata;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS goose_sessions (
+        owner TEXT NOT NULL,
+        id TEXT NOT NULL,
+        working_dir TEXT NOT NULL,
+        description TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        created_at BIGINT NOT NULL,
+        updated_at BIGINT NOT NULL,
+        PRIMARY KEY (owner, id)
+    );
+    CREATE TABLE IF NOT EXISTS goose_session_messages (
+        owner TEXT NOT NULL,
+        session_id TEXT NOT NULL,
+        idx BIGINT NOT NULL,
+        role TEXT NOT NULL,
+        created BIGINT NOT NULL,
+        content TEXT NOT NULL,
+        PRIMARY KEY (owner, session_id, idx),
+        FOREIGN KEY (owner, session_id) REFERENCES goose_sessions(owner, id) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS idx_goose_sessions_updated
+        ON goose_sessions(owner, updated_at);
+    ALTER TABLE goose_session_messages ADD COLUMN IF NOT EXISTS search_text TEXT,
+        ADD COLUMN IF NOT EXISTS search_tools TEXT,
+        ADD COLUMN IF NOT EXISTS search_paths TEXT;
+    CREATE INDEX IF NOT EXISTS idx_goose_session_messages_search ON goose_session_messages
+        USING GIN (to_tsvector('simple', coalesce(search_text, '')));";
+
+/// Sessions persisted in a PostgreSQL database, shared by every goose pointed at it
+///
+/// Each goose only sees the sessions of its owner, see [`crate::config::owner`], so sessions
+/// with the same id from different users or machines are kept apart. The tables are prefixed
+/// with `goose_` so the database can be shared with other applications. The connection is
+/// made again if it drops, e.g. when the server restarts.
+pub struct PostgresSessionStore {
+    url: String,
+    owner: String,
+    tls: MakeRustlsConnect,
+    /// Only taken when the store is dropped
+    client: Mutex<Option<Client>>,
+    cipher: Option<SessionCipher>,
+}
+
+impl PostgresSessionStore {
+    /// Connect to the database at a connection string like `postgres://goose@db.internal/goose`,
+    /// creating the tables if needed
+    ///
+    /// TLS is used when the server supports it, or always with `sslmode=require`, trusting the
+    /// public root certificates and those in the PEM file `ca_cert`.
+    pub fn connect(url: &str, ca_cert: Option<&Path>) -> Result<Self> {
+        let tls = tls_connector(ca_cert)?;
+        let client = off_runtime(|| -> Result<Client> {
+            let mut client = Client::connect(url, tls.clone())?;
+            client.batch_execute(SCHEMA)?;
+            index_unindexed_messages(&mut client)?;
+            Ok(client)
+        })?;
+        Ok(Self {
+            url: url.to_string(),
+            owner: crate::config::owner::owner_id(),
+            tls,
+            client: Mutex::new(Some(client)),
+            cipher: None,
+        })
+    }
+
+    /// Keep sessions under another owner than this user's
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = owner.into();
+        self
+    }
+
+    /// Encrypt the sessions saved from now on, which also leaves them out of the search index
+    pub fn with_cipher(mut self, cipher: Option<SessionCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Run queries on their own thread, as the client can't block an async runtime's thread
+    fn with_client<T: Send>(&self, f: impl FnOnce(&mut Client) -> Result<T> + Send) -> Result<T> {
+        off_runtime(|| {
+            let mut client = self.client.lock().unwrap();
+            let client = client
+                .as_mut()
+                .expect("the client is kept until the store is dropped");
+            if client.is_closed() {
+                *client = Client::connect(&self.url, self.tls.clone())?;
+            }
+            f(client)
+        })
+    }
+}
+
+impl Drop for PostgresSessionStore {
+    fn drop(&mut self) {
+        // Closing the connection blocks on the client's runtime too
+        if let Some(client) = self.client.get_mut().ok().and_then(Option::take) {
+            off_runtime(|| drop(client));
+        }
+    }
+}
+
+/// Fill in the search columns of messages saved before they were added
+fn index_unindexed_messages(client: &mut Client) -> Result<()> {
+    let rows = client.query(
+        "SELECT owner, session_id, idx, content FROM goose_session_messages
+        WHERE search_text IS NULL",
+        &[],
+    )?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let mut tx = client.transaction()?;
+    let update = tx.prepare(
+        "UPDATE goose_session_messages SET search_text = $4, search_tools = $5, search_paths = $6
+        WHERE owner = $1 AND session_id = $2 AND idx = $3",
+    )?;
+    for row in rows {
+        let (owner, session_id, idx): (String, String, i64) = (row.get(0), row.get(1), row.get(2));
+        let content: &str = row.get(3);
+        if encryption::is_encrypted(content) {
+            continue;
+        }
+        let fields = SearchFields::new(&serde_json::from_str(content)?);
+        tx.execute(
+            &update,
+            &[
+                &owner,
+                &session_id,
+                &idx,
+                &fields.text,
+                &fields.tools,
+                &fields.paths,
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub(crate) fn tls_connector(ca_cert: Option<&Path>) -> Result<MakeRustlsConnect> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(path) = ca_cert {
+        for cert in CertificateDer::pem_file_iter(path)? {
+            roots.add(cert?)?;
+        }
+    }
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    Ok(MakeRustlsConnect::new(config))
+}
+
+impl SessionStore for PostgresSessionStore {
+    fn save(
+        &self,
+        session_id: &str,
+        metadata: &SessionMetadata,
+        messages: &[Message],
+    ) -> Result<()> {
+        let contents = messages
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        let working_dir = metadata.working_dir.to_string_lossy().to_string();
+        let cipher = self.cipher.as_ref();
+        let metadata_json = encryption::seal(
+            cipher,
+            &encryption::record_id(session_id, "metadata"),
+            serde_json::to_string(metadata)?,
+        );
+        // The description is also in the metadata, where it is encrypted with the rest
+        let description = match cipher {
+            Some(_) => "",
+            None => metadata.description.as_str(),
+        };
+        let now = Utc::now().timestamp_millis();
+        let owner = &self.owner;
+
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            tx.execute(
+                "INSERT INTO goose_sessions
+                    (owner, id, working_dir, description, metadata, created_at, updated_at)
+                VALUES ($6, $1, $2, $3, $4, $5, $5)
+                ON CONFLICT (owner, id) DO UPDATE SET working_dir = excluded.working_dir,
+                    description = excluded.description, metadata = excluded.metadata,
+                    updated_at = excluded.updated_at",
+                &[
+                    &session_id,
+                    &working_dir,
+                    &description,
+                    &metadata_json,
+                    &now,
+                    owner,
+                ],
+            )?;
+
+            let stored: Vec<String> = tx
+                .query(
+                    "SELECT content FROM goose_session_messages
+                    WHERE owner = $2 AND session_id = $1 ORDER BY idx",
+                    &[&session_id, owner],
+                )?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            let unchanged = unchanged_messages(cipher, session_id, &stored, &contents);
+            tx.execute(
+                "DELETE FROM goose_session_messages
+                WHERE owner = $3 AND session_id = $1 AND idx >= $2",
+                &[&session_id, &(unchanged as i64), owner],
+            )?;
+            let insert = tx.prepare(
+                "INSERT INTO goose_session_messages
+                    (owner, session_id, idx, role, created, content, search_text, search_tools,
+                    search_paths)
+                VALUES ($9, $1, $2, $3, $4, $5, $6, $7, $8)",
+            )?;
+            for (idx, (message, content)) in messages.iter().zip(&contents).enumerate() {
+                if idx < unchanged {
+                    continue;
+                }
+                // Encrypted messages get empty search columns, so they are never indexed
+                let fields = match cipher {
+                    Some(_) => SearchFields::default(),
+                    None => SearchFields::new(message),
+                };
+                tx.execute(
+                    &insert,
+                    &[
+                        &session_id,
+                        &(idx as i64),
+                        &role_name(&message.role),
+                        &message.created,
+                        &encryption::seal(
+                            cipher,
+                            &encryption::record_id(session_id, idx),
+                            content.clone(),
+                        ),
+                        &fields.text,
+                        &fields.tools,
+                        &fields.paths,
+                        owner,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn resume(&self, session_id: &str) -> Result<Option<SavedSession>> {
+        let owner = &self.owner;
+        let (metadata, contents) = self.with_client(|client| {
+            let metadata = client
+                .query_opt(
+                    "SELECT metadata FROM goose_sessions WHERE owner = $2 AND id = $1",
+                    &[&session_id, owner],
+                )?
+                .map(|row| row.get::<_, String>(0));
+            let contents: Vec<String> = client
+                .query(
+                    "SELECT content FROM goose_session_messages
+                    WHERE owner = $2 AND session_id = $1 ORDER BY idx",
+                    &[&session_id, owner],
+                )?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            Ok((metadata, contents))
+        })?;
+        let Some(metadata) = metadata else {
+            return Ok(None);
+        };
+
+        let cipher = self.cipher.as_ref();
+        let messages = contents
+            .iter()
+            .enumerate()
+            .map(|(idx, content)| {
+                let record_id = encryption::record_id(session_id, idx);
+                Ok(serde_json::from_str(&encryption::open(
+                    cipher, &record_id, content,
+                )?)?)
+            })
+            .collect::<Result<Vec<Message>>>()?;
+        Ok(Some(SavedSession {
+            metadata: serde_json::from_str(&encryption::open(
+                cipher,
+                &encryption::record_id(session_id, "metadata"),
+                &metadata,
+            )?)?,
+            messages,
+        }))
+    }
+
+    fn contains(&self, session_id: &str) -> Result<bool> {
+        self.with_client(|client| {
+            let found = client.query_opt(
+                "SELECT 1 FROM goose_sessions WHERE owner = $2 AND id = $1",
+                &[&session_id, &self.owner],
+            )?;
+            Ok(found.is_some())
+        })
+    }
+
+    fn updated_at(&self, session_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let updated_at = self.with_client(|client| {
+            Ok(client
+                .query_opt(
+                    "SELECT updated_at FROM goose_sessions WHERE owner = $2 AND id = $1",
+                    &[&session_id, &self.owner],
+                )?
+                .map(|row| row.get::<_, i64>(0)))
+        })?;
+        Ok(updated_at.and_then(|at| Utc.timestamp_millis_opt(at).single()))
+    }
+
+    fn delete(&self, session_id: &str, updated_before: Option<DateTime<Utc>>) -> Result<bool> {
+        let updated_before = updated_before.map(|before| before.timestamp_millis());
+        self.with_client(|client| {
+            let deleted = client.execute(
+                "DELETE FROM goose_sessions WHERE owner = $2 AND id = $1
+                    AND ($3::BIGINT IS NULL OR updated_at <= $3)",
+                &[&session_id, &self.owner, &updated_before],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn list(&self) -> Result<Vec<StoredSession>> {
+        let rows = self.with_client(|client| {
+            let rows = client
+                .query(
+                    "SELECT id, metadata, updated_at FROM goose_sessions WHERE owner = $1
+                    ORDER BY updated_at DESC",
+                    &[&self.owner],
+                )?
+                .iter()
+                .map(|row| {
+                    (
+                        row.get::<_, String>(0),
+                        row.get::<_, String>(1),
+                        row.get::<_, i64>(2),
+                    )
+                })
+                .collect::<Vec<_>>();
+            Ok(rows)
+        })?;
+        rows.into_iter()
+            .map(|(id, metadata, updated_at)| {
+                let metadata = serde_json::from_str(&encryption::open(
+                    self.cipher.as_ref(),
+                    &encryption::record_id(&id, "metadata"),
+                    &metadata,
+                )?)?;
+                Ok(StoredSession {
+                    id,
+                    metadata,
+                    updated_at: Utc
+                        .timestamp_millis_opt(updated_at)
+                        .single()
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let text = query.text.as_deref().unwrap_or("").trim();
+        let tool = query.tool.as_deref().unwrap_or("").trim();
+        let path = query.path.as_deref().unwrap_or("").trim();
+        if text.is_empty() && tool.is_empty() && path.is_empty() {
+            return Ok(Vec::new());
+        }
+        let headline = format!(
+            "StartSel={}, StopSel={}, MinWords=8, MaxWords=16",
+            SNIPPET_START, SNIPPET_END
+        );
+
+        self.with_client(|client| {
+            let rows = client.query(
+                "SELECT m.session_id, s.description, m.idx, m.role,
+                    ts_headline('simple', coalesce(m.search_text, ''), q, $5)
+                FROM goose_session_messages m
+                JOIN goose_sessions s ON s.owner = m.owner AND s.id = m.session_id,
+                    plainto_tsquery('simple', $1) q
+                WHERE m.owner = $6
+                    AND ($1 = '' OR to_tsvector('simple', coalesce(m.search_text, '')) @@ q)
+                    AND ($2 = '' OR strpos(lower(m.search_tools), lower($2)) > 0)
+                    AND ($3 = '' OR strpos(lower(m.search_paths), lower($3)) > 0)
+                ORDER BY ts_rank(to_tsvector('simple', coalesce(m.search_text, '')), q) DESC,
+                    s.updated_at DESC, m.idx
+                LIMIT $4",
+                &[
+                    &text,
+                    &tool,
+                    &path,
+                    &(query.limit as i64),
+                    &headline,
+                    &self.owner,
+                ],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| SearchHit {
+                    session_id: row.get(0),
+                    description: row.get(1),
+                    message_index: row.get::<_, i64>(2) as usize,
+                    role: row.get(3),
+                    snippet: row.get(4),
+                })
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::check_store;
+    use super::*;
+
+    // Needs a database to write to, e.g.
+    // GOOSE_TEST_POSTGRES_URL="postgres://postgres@localhost/goose_test?sslmode=disable"
+    #[tokio::test]
+    async fn test_save_and_resume() -> Result<()> {
+        let Ok(url) = std::env::var("GOOSE_TEST_POSTGRES_URL") else {
+            println!("Skipping the postgres session store test - GOOSE_TEST_POSTGRES_URL not set");
+            return Ok(());
+        };
+        // Used from the runtime's thread, as the agent and server do
+        let store = PostgresSessionStore::connect(&url, None)?.with_owner("alice");
+        let session_id = uuid::Uuid::new_v4().to_string();
+        check_store(&store, &session_id)?;
+
+        // Another owner's session with the same id is kept apart
+        let other = PostgresSessionStore::connect(&url, None)?.with_owner("bob");
+        store.save(&session_id, &SessionMetadata::default(), &[])?;
+        assert!(!other.contains(&session_id)?);
+        assert!(other.resume(&session_id)?.is_none());
+        assert!(!other.delete(&session_id, None)?);
+        assert!(store.delete(&session_id, None)?);
+        Ok(())
+    }
+}
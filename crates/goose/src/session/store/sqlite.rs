@@ -0,0 +1,550 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use etcetera::{choose_app_strategy, AppStrategy};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::super::encryption::{self, SessionCipher};
+use super::{
+    role_name, unchanged_messages, SavedSession, SearchFields, SearchHit, SearchQuery,
+    SessionStore, StoredSession, SNIPPET_END, SNIPPET_START,
+};
+use crate::message::Message;
+use crate::session::storage::SessionMetadata;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS sessions (
+        id TEXT PRIMARY KEY,
+        working_dir TEXT NOT NULL,
+        description TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        idx INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        created INTEGER NOT NULL,
+        content TEXT NOT NULL,
+        UNIQUE (session_id, idx)
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS message_search USING fts5(text, tools, paths);
+    CREATE TRIGGER IF NOT EXISTS messages_unindex AFTER DELETE ON messages BEGIN
+        DELETE FROM message_search WHERE rowid = old.id;
+    END;
+    CREATE INDEX IF NOT EXISTS idx_sessions_updated ON sessions(updated_at);";
+
+/// The version of [`SCHEMA`], kept in the database's `user_version`
+const SCHEMA_VERSION: i64 = 1;
+
+/// Sessions persisted in a local SQLite database
+///
+/// Each save is a transaction, so unlike a session file that is being rewritten, the database
+/// always holds a complete copy of the session as of its last message.
+pub struct SqliteSessionStore {
+    conn: Mutex<Connection>,
+    cipher: Option<SessionCipher>,
+}
+
+impl SqliteSessionStore {
+    /// Open (creating if needed) the session database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open an in-memory session database, which is discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        let tx = conn.transaction()?;
+        let version: i64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            // Messages were first keyed by session and position alone; they're given ids for the
+            // search index to refer to, and indexed
+            let has_messages = tx
+                .query_row(
+                    "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages'",
+                    [],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if has_messages {
+                tx.execute_batch("ALTER TABLE messages RENAME TO messages_v0")?;
+            }
+            tx.execute_batch(SCHEMA)?;
+            if has_messages {
+                tx.execute_batch(
+                    "INSERT INTO messages (session_id, idx, role, created, content)
+                    SELECT session_id, idx, role, created, content FROM messages_v0;
+                    DROP TABLE messages_v0;",
+                )?;
+                index_all_messages(&tx)?;
+            }
+            tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        } else {
+            tx.execute_batch(SCHEMA)?;
+        }
+        tx.commit()?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            cipher: None,
+        })
+    }
+
+    /// Encrypt the sessions saved from now on, which also leaves them out of the search index
+    pub fn with_cipher(mut self, cipher: Option<SessionCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Default location of the session database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("sessions.db")
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn save(
+        &self,
+        session_id: &str,
+        metadata: &SessionMetadata,
+        messages: &[Message],
+    ) -> Result<()> {
+        let contents = messages
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        let now = Utc::now().timestamp_millis();
+        let cipher = self.cipher.as_ref();
+        // The description is also in the metadata, where it is encrypted with the rest
+        let description = match cipher {
+            Some(_) => "",
+            None => metadata.description.as_str(),
+        };
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO sessions (id, working_dir, description, metadata, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            ON CONFLICT(id) DO UPDATE SET working_dir = excluded.working_dir,
+                description = excluded.description, metadata = excluded.metadata,
+                updated_at = excluded.updated_at",
+            params![
+                session_id,
+                metadata.working_dir.to_string_lossy(),
+                description,
+                encryption::seal(
+                    cipher,
+                    &encryption::record_id(session_id, "metadata"),
+                    serde_json::to_string(metadata)?,
+                ),
+                now,
+            ],
+        )?;
+
+        let unchanged = unchanged_messages(
+            cipher,
+            session_id,
+            &message_contents(&tx, session_id)?,
+            &contents,
+        );
+        tx.execute(
+            "DELETE FROM messages WHERE session_id = ?1 AND idx >= ?2",
+            params![session_id, unchanged as i64],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO messages (session_id, idx, role, created, content)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (idx, (message, content)) in messages.iter().zip(&contents).enumerate() {
+                if idx < unchanged {
+                    continue;
+                }
+                stmt.execute(params![
+                    session_id,
+                    idx as i64,
+                    role_name(&message.role),
+                    message.created,
+                    encryption::seal(
+                        cipher,
+                        &encryption::record_id(session_id, idx),
+                        content.clone()
+                    )
+                ])?;
+                if cipher.is_none() {
+                    index_message(&tx, tx.last_insert_rowid(), message)?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn resume(&self, session_id: &str) -> Result<Option<SavedSession>> {
+        let conn = self.conn.lock().unwrap();
+        let metadata: Option<String> = conn
+            .query_row(
+                "SELECT metadata FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(metadata) = metadata else {
+            return Ok(None);
+        };
+
+        let cipher = self.cipher.as_ref();
+        let messages = message_contents(&conn, session_id)?
+            .iter()
+            .enumerate()
+            .map(|(idx, content)| {
+                let record_id = encryption::record_id(session_id, idx);
+                Ok(serde_json::from_str(&encryption::open(
+                    cipher, &record_id, content,
+                )?)?)
+            })
+            .collect::<Result<Vec<Message>>>()?;
+        Ok(Some(SavedSession {
+            metadata: serde_json::from_str(&encryption::open(
+                cipher,
+                &encryption::record_id(session_id, "metadata"),
+                &metadata,
+            )?)?,
+            messages,
+        }))
+    }
+
+    fn contains(&self, session_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let found = conn
+            .query_row(
+                "SELECT 1 FROM sessions WHERE id = ?1",
+                params![session_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    fn updated_at(&self, session_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.conn.lock().unwrap();
+        let updated_at: Option<i64> = conn
+            .query_row(
+                "SELECT updated_at FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(updated_at.and_then(|at| Utc.timestamp_millis_opt(at).single()))
+    }
+
+    fn delete(&self, session_id: &str, updated_before: Option<DateTime<Utc>>) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        // The messages are deleted with the session, and taken out of the index by a trigger
+        let deleted = conn.execute(
+            "DELETE FROM sessions WHERE id = ?1 AND (?2 IS NULL OR updated_at <= ?2)",
+            params![
+                session_id,
+                updated_before.map(|before| before.timestamp_millis())
+            ],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn list(&self) -> Result<Vec<StoredSession>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT id, metadata, updated_at FROM sessions ORDER BY updated_at DESC")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(id, metadata, updated_at)| {
+                let metadata = serde_json::from_str(&encryption::open(
+                    self.cipher.as_ref(),
+                    &encryption::record_id(&id, "metadata"),
+                    &metadata,
+                )?)?;
+                Ok(StoredSession {
+                    id,
+                    metadata,
+                    updated_at: Utc
+                        .timestamp_millis_opt(updated_at)
+                        .single()
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let Some(expression) = match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.lock().unwrap();
+        // The snippet is from the text, where the tools' arguments are too
+        let mut stmt = conn.prepare(
+            "SELECT m.session_id, s.description, m.idx, m.role,
+                snippet(message_search, 0, ?2, ?3, '…', 16)
+            FROM message_search
+            JOIN messages m ON m.id = message_search.rowid
+            JOIN sessions s ON s.id = m.session_id
+            WHERE message_search MATCH ?1
+            ORDER BY rank
+            LIMIT ?4",
+        )?;
+        let hits = stmt.query_map(
+            params![expression, SNIPPET_START, SNIPPET_END, query.limit as i64],
+            |row| {
+                Ok(SearchHit {
+                    session_id: row.get(0)?,
+                    description: row.get(1)?,
+                    message_index: row.get::<_, i64>(2)? as usize,
+                    role: row.get(3)?,
+                    snippet: row.get(4)?,
+                })
+            },
+        )?;
+        Ok(hits.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+/// Add a message to the search index, under the id of its row in `messages`
+fn index_message(conn: &Connection, id: i64, message: &Message) -> rusqlite::Result<()> {
+    let fields = SearchFields::new(message);
+    conn.execute(
+        "INSERT INTO message_search (rowid, text, tools, paths) VALUES (?1, ?2, ?3, ?4)",
+        params![id, fields.text, fields.tools, fields.paths],
+    )?;
+    Ok(())
+}
+
+fn index_all_messages(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT id, content FROM messages")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, content) in rows {
+        index_message(conn, id, &serde_json::from_str(&content)?)?;
+    }
+    Ok(())
+}
+
+/// An FTS5 query for messages that match everything in the query
+///
+/// Each word is quoted, so punctuation in it (e.g. `main.rs`) isn't taken as query syntax.
+fn match_expression(query: &SearchQuery) -> Option<String> {
+    fn phrase(column: &str, words: &str) -> String {
+        format!("{} : \"{}\"", column, words.replace('"', "\"\""))
+    }
+    let searchable = |s: &&str| s.chars().any(char::is_alphanumeric);
+
+    let mut terms: Vec<String> = query
+        .text
+        .iter()
+        .flat_map(|text| text.split_whitespace())
+        .filter(searchable)
+        .map(|word| phrase("text", word))
+        .collect();
+    terms.extend(
+        query
+            .tool
+            .as_deref()
+            .filter(searchable)
+            .map(|tool| phrase("tools", tool)),
+    );
+    terms.extend(
+        query
+            .path
+            .as_deref()
+            .filter(searchable)
+            .map(|path| phrase("paths", path)),
+    );
+    (!terms.is_empty()).then(|| terms.join(" AND "))
+}
+
+/// The serialized messages of a session, in order
+fn message_contents(conn: &Connection, session_id: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt =
+        conn.prepare("SELECT content FROM messages WHERE session_id = ?1 ORDER BY idx")?;
+    let rows = stmt.query_map(params![session_id], |row| row.get(0))?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{check_store, conversation};
+    use super::*;
+    use mcp_core::tool::ToolCall;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_resume() -> Result<()> {
+        check_store(&SqliteSessionStore::open_in_memory()?, "20250101_120000")
+    }
+
+    #[test]
+    fn test_sessions_survive_reopen() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("sessions.db");
+
+        let messages = conversation();
+        let store = SqliteSessionStore::open(&path)?;
+        store.save("a", &SessionMetadata::default(), &messages)?;
+        drop(store);
+
+        let store = SqliteSessionStore::open(&path)?;
+        assert_eq!(store.resume("a")?.unwrap().messages, messages);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_paths() -> Result<()> {
+        let store = SqliteSessionStore::open_in_memory()?;
+        let messages = vec![
+            Message::user().with_text("What's in main.rs?"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(ToolCall::new(
+                    "developer__text_editor",
+                    json!({"command": "view", "path": "/tmp/project/src/main.rs"}),
+                )),
+            ),
+        ];
+        store.save("a", &SessionMetadata::default(), &messages)?;
+
+        let hits = store.search(&SearchQuery {
+            path: Some("src/main.rs".to_string()),
+            limit: 10,
+            ..Default::default()
+        })?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_index, 1);
+        // Words are matched as they are, not as query syntax
+        let hits = store.search(&SearchQuery {
+            text: Some("main.rs? \"".to_string()),
+            limit: 10,
+            ..Default::default()
+        })?;
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].snippet, "What's in [main.rs]?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unindexed_database() -> Result<()> {
+        // A database from before the search index
+        let dir = tempdir()?;
+        let path = dir.path().join("sessions.db");
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "CREATE TABLE sessions (id TEXT PRIMARY KEY, working_dir TEXT NOT NULL,
+                description TEXT NOT NULL, metadata TEXT NOT NULL, created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL);
+            CREATE TABLE messages (session_id TEXT NOT NULL REFERENCES sessions(id)
+                ON DELETE CASCADE, idx INTEGER NOT NULL, role TEXT NOT NULL,
+                created INTEGER NOT NULL, content TEXT NOT NULL, PRIMARY KEY (session_id, idx));",
+        )?;
+        let messages = conversation();
+        conn.execute(
+            "INSERT INTO sessions VALUES ('a', '/tmp', '', ?1, 0, 0)",
+            params![serde_json::to_string(&SessionMetadata::default())?],
+        )?;
+        for (idx, message) in messages.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO messages VALUES ('a', ?1, ?2, ?3, ?4)",
+                params![
+                    idx as i64,
+                    role_name(&message.role),
+                    message.created,
+                    serde_json::to_string(message)?
+                ],
+            )?;
+        }
+        drop(conn);
+
+        let store = SqliteSessionStore::open(&path)?;
+        assert_eq!(store.resume("a")?.unwrap().messages, messages);
+        let hits = store.search(&SearchQuery {
+            text: Some("empty".to_string()),
+            limit: 10,
+            ..Default::default()
+        })?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_index, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted_sessions() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("sessions.db");
+        let messages = conversation();
+        let metadata = SessionMetadata {
+            description: "Listing files".to_string(),
+            ..Default::default()
+        };
+        SqliteSessionStore::open(&path)?.save("a", &metadata, &messages[..2])?;
+
+        // Turning encryption on encrypts the messages saved before too
+        let cipher = SessionCipher::generate();
+        let store = SqliteSessionStore::open(&path)?.with_cipher(Some(cipher.clone()));
+        store.save("a", &metadata, &messages)?;
+        let saved = store.resume("a")?.unwrap();
+        assert_eq!(saved.messages, messages);
+        assert_eq!(saved.metadata.description, "Listing files");
+
+        let conn = store.conn.lock().unwrap();
+        let contents = message_contents(&conn, "a")?;
+        assert_eq!(contents.len(), messages.len());
+        assert!(contents
+            .iter()
+            .all(|content| encryption::is_encrypted(content)));
+        let (description, metadata): (String, String) = conn.query_row(
+            "SELECT description, metadata FROM sessions WHERE id = 'a'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(description, "");
+        assert!(encryption::is_encrypted(&metadata));
+        drop(conn);
+
+        let hits = store.search(&SearchQuery {
+            text: Some("files".to_string()),
+            limit: 10,
+            ..Default::default()
+        })?;
+        assert!(hits.is_empty());
+
+        // Without the key the sessions can't be read
+        assert!(SqliteSessionStore::open(&path)?.resume("a").is_err());
+        assert!(SqliteSessionStore::open(&path)?
+            .with_cipher(Some(SessionCipher::generate()))
+            .resume("a")
+            .is_err());
+        Ok(())
+    }
+}
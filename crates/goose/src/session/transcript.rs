@@ -0,0 +1,426 @@
+//! Transcripts of sessions to read and share, as Markdown or as a standalone HTML page
+//!
+//! A transcript shows the conversation turn by turn: what the user wrote and attached, and what
+//! goose replied, with the tools it called, their output and any images. The session's
+//! description, summary, tokens and cost come first. Long tool output is cut so a transcript
+//! stays readable when it is pasted into a pull request.
+use anyhow::Result;
+use mcp_core::content::Content;
+use mcp_core::resource::ResourceContents;
+use mcp_core::role::Role;
+use std::io::Write;
+
+use super::storage::SessionMetadata;
+use super::store::SavedSession;
+use crate::message::{AttachmentContent, Message, MessageContent};
+
+/// The most lines of a tool's output a transcript shows
+pub const MAX_OUTPUT_LINES: usize = 100;
+
+/// A run of messages shown under one heading
+struct Turn<'a> {
+    role: Role,
+    created: i64,
+    blocks: Vec<Block<'a>>,
+}
+
+enum Block<'a> {
+    Text(&'a str),
+    Thinking(&'a str),
+    ToolCall { name: String, arguments: String },
+    ToolOutput(String),
+    ToolError(String),
+    Image { mime_type: &'a str, data: &'a str },
+    Attachment(&'a AttachmentContent),
+}
+
+/// Group the messages into turns, with tool results shown in the reply that requested them
+fn turns(messages: &[Message]) -> Vec<Turn<'_>> {
+    let mut turns: Vec<Turn> = Vec::new();
+    for message in messages {
+        let role = if message.is_tool_response() && !message.content.iter().any(is_visible_text) {
+            Role::Assistant
+        } else {
+            message.role.clone()
+        };
+        if turns.last().is_none_or(|turn| turn.role != role) {
+            turns.push(Turn {
+                role,
+                created: message.created,
+                blocks: Vec::new(),
+            });
+        }
+        let blocks = &mut turns.last_mut().unwrap().blocks;
+        for content in &message.content {
+            blocks.extend(content_blocks(content));
+        }
+    }
+    turns.retain(|turn| !turn.blocks.is_empty());
+    turns
+}
+
+fn is_visible_text(content: &MessageContent) -> bool {
+    matches!(content, MessageContent::Text(text) if !text.text.trim().is_empty())
+}
+
+fn content_blocks(content: &MessageContent) -> Vec<Block<'_>> {
+    match content {
+        MessageContent::Text(text) if !text.text.trim().is_empty() => vec![Block::Text(&text.text)],
+        MessageContent::Image(image) => vec![Block::Image {
+            mime_type: &image.mime_type,
+            data: &image.data,
+        }],
+        MessageContent::Thinking(thinking) => vec![Block::Thinking(&thinking.thinking)],
+        MessageContent::Attachment(attachment) => vec![Block::Attachment(attachment)],
+        MessageContent::ToolRequest(request) => vec![match &request.tool_call {
+            Ok(call) => Block::ToolCall {
+                name: call.name.clone(),
+                arguments: serde_json::to_string_pretty(&call.arguments).unwrap_or_default(),
+            },
+            Err(e) => Block::ToolError(e.to_string()),
+        }],
+        MessageContent::ToolResponse(response) => match &response.tool_result {
+            Ok(contents) => {
+                let mut blocks = Vec::new();
+                let mut output = Vec::new();
+                for content in contents {
+                    match content {
+                        Content::Text(text) => output.push(text.text.as_str()),
+                        Content::Resource(resource) => match &resource.resource {
+                            ResourceContents::TextResourceContents { text, .. } => {
+                                output.push(text.as_str())
+                            }
+                            ResourceContents::BlobResourceContents { .. } => {}
+                        },
+                        Content::Image(image) => blocks.push(Block::Image {
+                            mime_type: &image.mime_type,
+                            data: &image.data,
+                        }),
+                    }
+                }
+                if !output.is_empty() {
+                    blocks.insert(0, Block::ToolOutput(cut_output(&output.join("\n"))));
+                }
+                blocks
+            }
+            Err(e) => vec![Block::ToolError(e.to_string())],
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// `output` with at most [`MAX_OUTPUT_LINES`] lines, noting how many were left out
+fn cut_output(output: &str) -> String {
+    let lines = output.lines().count();
+    if lines <= MAX_OUTPUT_LINES {
+        return output.trim_end().to_string();
+    }
+    let mut cut: String = output
+        .lines()
+        .take(MAX_OUTPUT_LINES)
+        .collect::<Vec<_>>()
+        .join("\n");
+    cut.push_str(&format!("\n... {} more lines", lines - MAX_OUTPUT_LINES));
+    cut
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Goose",
+    }
+}
+
+fn timestamp(created: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(created, 0)
+        .filter(|_| created > 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+}
+
+fn title(metadata: &SessionMetadata) -> &str {
+    if metadata.description.trim().is_empty() {
+        "Goose session"
+    } else {
+        metadata.description.trim()
+    }
+}
+
+/// The facts about the session listed under its title
+fn details(metadata: &SessionMetadata, messages: &[Message]) -> Vec<(&'static str, String)> {
+    let mut details = vec![
+        (
+            "Working directory",
+            metadata.working_dir.display().to_string(),
+        ),
+        ("Messages", messages.len().to_string()),
+    ];
+    if let Some(tokens) = metadata
+        .accumulated_tokens
+        .or(metadata.total_tokens.map(i64::from))
+    {
+        details.push(("Tokens", tokens.to_string()));
+    }
+    if let Some(cost) = metadata.accumulated_cost {
+        details.push(("Cost", format!("${:.2}", cost)));
+    }
+    details
+}
+
+/// Write the session as Markdown
+pub fn write_markdown(session: &SavedSession, writer: &mut impl Write) -> Result<()> {
+    let metadata = &session.metadata;
+    writeln!(writer, "# {}\n", title(metadata))?;
+    if !metadata.summary.is_empty() {
+        writeln!(writer, "{}\n", metadata.summary)?;
+    }
+    for (name, value) in details(metadata, &session.messages) {
+        writeln!(writer, "- **{}:** {}", name, value)?;
+    }
+
+    for turn in turns(&session.messages) {
+        match timestamp(turn.created) {
+            Some(time) => writeln!(writer, "\n## {} · {}\n", role_name(&turn.role), time)?,
+            None => writeln!(writer, "\n## {}\n", role_name(&turn.role))?,
+        }
+        for block in turn.blocks {
+            match block {
+                Block::Text(text) => writeln!(writer, "{}\n", text.trim())?,
+                Block::Thinking(thinking) => writeln!(
+                    writer,
+                    "<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>\n",
+                    thinking.trim()
+                )?,
+                Block::ToolCall { name, arguments } => writeln!(
+                    writer,
+                    "**Tool call:** `{}`\n\n{}\n",
+                    name,
+                    fenced(&arguments, "json")
+                )?,
+                Block::ToolOutput(output) => writeln!(
+                    writer,
+                    "<details>\n<summary>Output</summary>\n\n{}\n\n</details>\n",
+                    fenced(&output, "")
+                )?,
+                Block::ToolError(error) => writeln!(writer, "**Tool error:** {}\n", error)?,
+                Block::Image { mime_type, data } => {
+                    writeln!(writer, "![image](data:{};base64,{})\n", mime_type, data)?
+                }
+                Block::Attachment(attachment) => writeln!(
+                    writer,
+                    "**Attached:** `{}` ({})\n",
+                    attachment.name, attachment.mime_type
+                )?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `text` in a code block, fenced with more backticks than any run of them in the text
+fn fenced(text: &str, language: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, text, fence)
+}
+
+const STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Helvetica,Arial,sans-serif;max-width:52rem;margin:2rem auto;padding:0 1rem;color:#1f2328;line-height:1.5}\
+header dl{display:grid;grid-template-columns:max-content auto;gap:.25rem 1rem;color:#59636e}\
+header dd{margin:0}\
+section{border-top:1px solid #d1d9e0;padding:.5rem 0}\
+h2{font-size:1rem;margin:.5rem 0}\
+h2 time{font-weight:normal;color:#59636e;margin-left:.5rem}\
+.text{white-space:pre-wrap}\
+pre{background:#f6f8fa;padding:.75rem;border-radius:6px;overflow-x:auto}\
+.tool{font-weight:600}\
+.error{color:#d1242f}\
+img{max-width:100%}";
+
+/// Write the session as an HTML page that needs nothing else to be shown
+pub fn write_html(session: &SavedSession, writer: &mut impl Write) -> Result<()> {
+    let metadata = &session.metadata;
+    let title = escape(title(metadata));
+    writeln!(writer, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>", title, STYLE)?;
+    writeln!(writer, "<header>\n<h1>{}</h1>", title)?;
+    if !metadata.summary.is_empty() {
+        writeln!(writer, "<p>{}</p>", escape(&metadata.summary))?;
+    }
+    writeln!(writer, "<dl>")?;
+    for (name, value) in details(metadata, &session.messages) {
+        writeln!(writer, "<dt>{}</dt><dd>{}</dd>", name, escape(&value))?;
+    }
+    writeln!(writer, "</dl>\n</header>")?;
+
+    for turn in turns(&session.messages) {
+        writeln!(writer, "<section>")?;
+        match timestamp(turn.created) {
+            Some(time) => writeln!(
+                writer,
+                "<h2>{}<time>{}</time></h2>",
+                role_name(&turn.role),
+                time
+            )?,
+            None => writeln!(writer, "<h2>{}</h2>", role_name(&turn.role))?,
+        }
+        for block in turn.blocks {
+            match block {
+                Block::Text(text) => writeln!(writer, "{}", text_html(text.trim()))?,
+                Block::Thinking(thinking) => writeln!(
+                    writer,
+                    "<details><summary>Thinking</summary>{}</details>",
+                    text_html(thinking.trim())
+                )?,
+                Block::ToolCall { name, arguments } => writeln!(
+                    writer,
+                    "<p class=\"tool\">Tool call: <code>{}</code></p>\n<pre><code>{}</code></pre>",
+                    escape(&name),
+                    escape(&arguments)
+                )?,
+                Block::ToolOutput(output) => writeln!(
+                    writer,
+                    "<details><summary>Output</summary><pre><code>{}</code></pre></details>",
+                    escape(&output)
+                )?,
+                Block::ToolError(error) => writeln!(
+                    writer,
+                    "<p class=\"error\">Tool error: {}</p>",
+                    escape(&error)
+                )?,
+                Block::Image { mime_type, data } => writeln!(
+                    writer,
+                    "<img alt=\"image\" src=\"data:{};base64,{}\">",
+                    escape(mime_type),
+                    escape(data)
+                )?,
+                Block::Attachment(attachment) => writeln!(
+                    writer,
+                    "<p class=\"tool\">Attached: <code>{}</code> ({})</p>",
+                    escape(&attachment.name),
+                    escape(&attachment.mime_type)
+                )?,
+            }
+        }
+        writeln!(writer, "</section>")?;
+    }
+    writeln!(writer, "</body>\n</html>")?;
+    Ok(())
+}
+
+/// Message text as HTML, keeping its line breaks and showing fenced code blocks as code
+fn text_html(text: &str) -> String {
+    let mut html = String::new();
+    for (index, part) in text.split("```").enumerate() {
+        if part.trim().is_empty() {
+            continue;
+        }
+        if index % 2 == 1 {
+            // The first line of a code block names its language
+            let code = part.split_once('\n').map_or(part, |(_, code)| code);
+            html.push_str(&format!(
+                "<pre><code>{}</code></pre>",
+                escape(code.trim_end())
+            ));
+        } else {
+            html.push_str(&format!(
+                "<div class=\"text\">{}</div>",
+                escape(part.trim())
+            ));
+        }
+    }
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::tool::ToolCall;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn session() -> SavedSession {
+        let mut metadata = SessionMetadata::new(PathBuf::from("/tmp/project"));
+        metadata.description = "Listing files".to_string();
+        metadata.accumulated_tokens = Some(1200);
+        metadata.accumulated_cost = Some(0.0234);
+        SavedSession {
+            metadata,
+            messages: vec![
+                Message::user().with_text("List the files"),
+                Message::assistant()
+                    .with_text("Let me look")
+                    .with_tool_request(
+                        "call_1",
+                        Ok(ToolCall::new("developer__shell", json!({"command": "ls"}))),
+                    ),
+                Message::user().with_tool_response(
+                    "call_1",
+                    Ok(vec![
+                        Content::text("main.rs\n<lib>.rs"),
+                        Content::image("aGk=", "image/png"),
+                    ]),
+                ),
+                Message::assistant().with_text("There are two files"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_markdown() -> Result<()> {
+        let mut markdown = Vec::new();
+        write_markdown(&session(), &mut markdown)?;
+        let markdown = String::from_utf8(markdown)?;
+
+        assert!(markdown.starts_with("# Listing files\n"));
+        assert!(markdown.contains("- **Tokens:** 1200\n- **Cost:** $0.02\n"));
+        // The tool result is part of goose's reply, not a message from the user
+        assert_eq!(markdown.matches("\n## User").count(), 1);
+        assert_eq!(markdown.matches("\n## Goose").count(), 1);
+        assert!(markdown.contains(
+            "**Tool call:** `developer__shell`\n\n```json\n{\n  \"command\": \"ls\"\n}\n```"
+        ));
+        assert!(markdown.contains("```\nmain.rs\n<lib>.rs\n```"));
+        assert!(markdown.contains("![image](data:image/png;base64,aGk=)"));
+        assert!(markdown.trim_end().ends_with("There are two files"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_html() -> Result<()> {
+        let mut html = Vec::new();
+        write_html(&session(), &mut html)?;
+        let html = String::from_utf8(html)?;
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Listing files</title>"));
+        assert!(html.contains("<pre><code>main.rs\n&lt;lib&gt;.rs</code></pre>"));
+        assert!(html.contains("src=\"data:image/png;base64,aGk=\""));
+        assert_eq!(html.matches("<section>").count(), 2);
+        assert!(html.trim_end().ends_with("</html>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cut_output() {
+        let output = (1..=MAX_OUTPUT_LINES + 5)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let cut = cut_output(&output);
+        assert!(cut.ends_with(&format!("{}\n... 5 more lines", MAX_OUTPUT_LINES)));
+        assert_eq!(fenced("a ``` b", "md"), "````md\na ``` b\n````");
+    }
+}
@@ -5,7 +5,12 @@ use std::fs;
 use std::path::Path;
 use tokenizers::tokenizer::Tokenizer;
 
-use crate::message::Message;
+use crate::message::attachment::IMAGE_TYPES;
+use crate::message::{AttachmentContent, Message, MessageContent};
+
+/// About what an image at the largest size providers take costs, with Anthropic at
+/// (width * height) / 750 for 1092x1092
+const ATTACHED_IMAGE_TOKENS: usize = 1_600;
 
 // The embedded directory with all possible tokenizer files.
 // If one of them doesn’t exist, we’ll download it at startup.
@@ -163,6 +168,15 @@ impl TokenCounter {
         func_token_count
     }
 
+    /// Text files count as the text that is inlined, and images as what a full size image costs,
+    /// other files as the note that takes their place where they aren't read natively
+    fn count_attachment_tokens(&self, attachment: &AttachmentContent) -> usize {
+        if IMAGE_TYPES.contains(&attachment.mime_type.as_str()) {
+            return ATTACHED_IMAGE_TOKENS;
+        }
+        self.count_tokens(&attachment.render_text())
+    }
+
     pub fn count_chat_tokens(
         &self,
         system_prompt: &str,
@@ -195,6 +209,8 @@ impl TokenCounter {
                     num_tokens += self.count_tokens(&text);
                 } else if let Some(tool_response_text) = content.as_tool_response_text() {
                     num_tokens += self.count_tokens(&tool_response_text);
+                } else if let MessageContent::Attachment(attachment) = content {
+                    num_tokens += self.count_attachment_tokens(attachment);
                 } else {
                     // unsupported content type such as image - pass
                     continue;
@@ -278,6 +294,7 @@ mod tests {
                 content: vec![MessageContent::text(
                     "What's the weather like in San Francisco?",
                 )],
+                metadata: Default::default(),
             },
             Message {
                 role: Role::Assistant,
@@ -285,11 +302,13 @@ mod tests {
                 content: vec![MessageContent::text(
                     "Looks like it's 60 degrees Fahrenheit in San Francisco.",
                 )],
+                metadata: Default::default(),
             },
             Message {
                 role: Role::User,
                 created: 2,
                 content: vec![MessageContent::text("How about New York?")],
+                metadata: Default::default(),
             },
         ];
 
@@ -323,6 +342,29 @@ mod tests {
         assert_eq!(token_count_with_tools, 124);
     }
 
+    #[test]
+    fn test_count_chat_tokens_with_attachments() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let text = Message::user().with_text("Analyze this");
+        let csv = text.clone().with_attachment(AttachmentContent::from_bytes(
+            "prices.csv",
+            "text/csv",
+            "date,price\n2024-01-01,100\n2024-01-02,102\n".as_bytes(),
+        ));
+        let image = text.clone().with_attachment(AttachmentContent::from_bytes(
+            "chart.png",
+            "image/png",
+            &[0u8; 16],
+        ));
+
+        let base = counter.count_chat_tokens("", std::slice::from_ref(&text), &[]);
+        assert!(counter.count_chat_tokens("", &[csv], &[]) > base + 10);
+        assert_eq!(
+            counter.count_chat_tokens("", &[image], &[]),
+            base + ATTACHED_IMAGE_TOKENS
+        );
+    }
+
     #[test]
     #[should_panic]
     fn test_panic_if_provided_tokenizer_doesnt_exist() {
@@ -0,0 +1,238 @@
+//! Checking the arguments the model gives a tool against the tool's schema
+//!
+//! Models get the types of arguments wrong in a few common ways: a number or boolean as a
+//! string, an array or object as a string of JSON, or null for an argument they mean to leave
+//! out. Those are fixed before the call, going by the tool's input schema. Arguments that still
+//! don't match are refused without calling the tool, with where and how they are wrong, so the
+//! model can fix the call.
+use jsonschema::Validator;
+use serde_json::{Map, Value};
+
+/// The most problems with the arguments listed back to the model
+const MAX_ERRORS: usize = 10;
+
+/// A tool's input schema, compiled once to check the arguments of each call against
+pub struct ToolSchema {
+    schema: Value,
+    /// None when the schema itself is invalid, leaving the tool to check the arguments
+    validator: Option<Validator>,
+}
+
+impl ToolSchema {
+    pub fn new(schema: Value) -> Self {
+        let validator = jsonschema::validator_for(&schema)
+            .inspect_err(|e| {
+                tracing::debug!("Not checking arguments against an invalid schema: {}", e);
+            })
+            .ok();
+        Self { schema, validator }
+    }
+
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// The arguments with the common mistakes fixed, or what is wrong with them, one problem
+    /// a line
+    pub fn conform(&self, mut arguments: Value) -> Result<Value, String> {
+        coerce(&self.schema, &mut arguments);
+        let Some(validator) = &self.validator else {
+            return Ok(arguments);
+        };
+        let errors: Vec<String> = validator
+            .iter_errors(&arguments)
+            .take(MAX_ERRORS)
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                if path.is_empty() {
+                    format!("- {}", error)
+                } else {
+                    format!("- at {}: {}", path, error)
+                }
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(arguments)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+/// The types a schema allows, empty when it doesn't say
+fn types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(kind)) => vec![kind.as_str()],
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_of(value: &Value, kind: &str) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+/// `value` as one of `kinds`, if it is a common mistake for one
+fn coerced(value: &Value, kinds: &[&str]) -> Option<Value> {
+    kinds.iter().find_map(|&kind| match (kind, value) {
+        ("integer", Value::String(text)) => text.trim().parse::<i64>().ok().map(Value::from),
+        ("number", Value::String(text)) => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .map(Value::from),
+        ("boolean", Value::String(text)) => match text.trim().to_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ("array" | "object", Value::String(text)) => serde_json::from_str::<Value>(text)
+            .ok()
+            .filter(|parsed| is_of(parsed, kind)),
+        ("string", Value::Number(_) | Value::Bool(_)) => Some(Value::String(value.to_string())),
+        _ => None,
+    })
+}
+
+/// Fix the common mistakes in `value` for `schema`, and within its properties and items
+fn coerce(schema: &Value, value: &mut Value) {
+    let kinds = types(schema);
+    if !kinds.is_empty() && !kinds.iter().any(|kind| is_of(value, kind)) {
+        if value.is_null() && kinds.contains(&"object") {
+            // A tool without arguments is often called with none at all
+            *value = Value::Object(Map::new());
+        } else if let Some(fixed) = coerced(value, &kinds) {
+            *value = fixed;
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                return;
+            };
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|required| required.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            // Null for an argument that can be left out means to leave it out
+            object.retain(|name, value| {
+                !value.is_null()
+                    || required.contains(&name.as_str())
+                    || properties
+                        .get(name)
+                        .is_none_or(|property| types(property).contains(&"null"))
+            });
+            for (name, value) in object.iter_mut() {
+                if let Some(property) = properties.get(name) {
+                    coerce(property, value);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item) = schema.get("items") {
+                for value in items.iter_mut() {
+                    coerce(item, value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["path"],
+            "properties": {
+                "path": {"type": "string"},
+                "line": {"type": "integer"},
+                "recursive": {"type": "boolean"},
+                "ratio": {"type": "number"},
+                "globs": {"type": "array", "items": {"type": "string"}},
+                "options": {
+                    "type": "object",
+                    "properties": {"depth": {"type": "integer"}}
+                },
+                "note": {"type": ["string", "null"]}
+            }
+        })
+    }
+
+    #[test]
+    fn test_conform_fixes_common_mistakes() {
+        let arguments = json!({
+            "path": "/repo/src",
+            "line": "42",
+            "recursive": "True",
+            "ratio": " 0.5",
+            "globs": "[\"*.rs\", 7]",
+            "options": {"depth": "2"},
+            "note": null,
+        });
+        assert_eq!(
+            ToolSchema::new(schema()).conform(arguments),
+            Ok(json!({
+                "path": "/repo/src",
+                "line": 42,
+                "recursive": true,
+                "ratio": 0.5,
+                "globs": ["*.rs", "7"],
+                "options": {"depth": 2},
+                "note": null,
+            }))
+        );
+
+        // Optional arguments given as null are left out
+        assert_eq!(
+            ToolSchema::new(schema()).conform(json!({"path": "/repo", "line": null})),
+            Ok(json!({"path": "/repo"}))
+        );
+        assert_eq!(
+            ToolSchema::new(json!({"type": "object", "properties": {}})).conform(Value::Null),
+            Ok(json!({}))
+        );
+    }
+
+    #[test]
+    fn test_conform_reports_what_is_wrong() {
+        let errors = ToolSchema::new(schema())
+            .conform(json!({"line": "forty-two", "recursive": 1}))
+            .unwrap_err();
+        let errors: Vec<&str> = errors.lines().collect();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.contains(&"- \"path\" is a required property"));
+        assert!(errors.contains(&"- at /line: \"forty-two\" is not of type \"integer\""));
+        assert!(errors.contains(&"- at /recursive: 1 is not of type \"boolean\""));
+
+        // A required argument given as null is kept, to be reported
+        assert!(ToolSchema::new(schema())
+            .conform(json!({"path": null}))
+            .is_err());
+    }
+
+    #[test]
+    fn test_conform_without_a_valid_schema() {
+        let arguments = json!({"line": "42"});
+        assert_eq!(
+            ToolSchema::new(json!({"type": 12})).conform(arguments.clone()),
+            Ok(arguments)
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! The names the model knows the tools of extensions by
+//!
+//! Each tool is named after its extension, as `developer__shell`, so tools of the same name in
+//! two extensions stay apart. Names can still meet: an extension `git` with a tool `hub__clone`
+//! and an extension `git__hub` with a tool `clone` are both `git__hub__clone`, and an extension
+//! can give a tool the name of one of Goose's own. The tool named first keeps the name and the
+//! others get their extension's name after it, as `git__hub__clone_git_hub`, rather than one
+//! hiding the other, so their names don't change with which other extensions are added. A call
+//! by any of those names goes back to the extension and the tool's name on its server.
+use std::collections::{HashMap, HashSet};
+
+/// Where the model's calls to a tool go
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolRoute {
+    /// The name of the extension, as its tools are prefixed
+    pub extension: String,
+    /// The name of the tool on the extension's server
+    pub tool: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ToolNames {
+    /// Names of tools that aren't an extension's
+    reserved: HashSet<String>,
+    routes: HashMap<String, ToolRoute>,
+}
+
+impl ToolNames {
+    /// Names for the tools of extensions, keeping `reserved` for Goose's own tools
+    pub fn reserving<'a>(reserved: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            reserved: reserved.into_iter().map(str::to_string).collect(),
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Name the tool `tool` of the extension `extension`, with the extension's name after the
+    /// name when a tool named before has it, and a number after that if it is taken too
+    pub fn name(&mut self, extension: &str, tool: &str) -> String {
+        let prefixed = format!("{}__{}", extension, tool);
+        let mut name = prefixed.clone();
+        if self.is_taken(&name) {
+            let disambiguated = format!("{}_{}", prefixed, extension.replace("__", "_"));
+            name = disambiguated.clone();
+            let mut number = 2;
+            while self.is_taken(&name) {
+                name = format!("{}_{}", disambiguated, number);
+                number += 1;
+            }
+        }
+        self.routes.insert(
+            name.clone(),
+            ToolRoute {
+                extension: extension.to_string(),
+                tool: tool.to_string(),
+            },
+        );
+        name
+    }
+
+    fn is_taken(&self, name: &str) -> bool {
+        self.reserved.contains(name) || self.routes.contains_key(name)
+    }
+
+    /// Where calls to the tool the model knows as `name` go, if it was named
+    pub fn route(&self, name: &str) -> Option<&ToolRoute> {
+        self.routes.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(extension: &str, tool: &str) -> Option<ToolRoute> {
+        Some(ToolRoute {
+            extension: extension.to_string(),
+            tool: tool.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_names_that_meet() {
+        let mut names = ToolNames::reserving(["platform__read_resource"]);
+        assert_eq!(names.name("git", "hub__clone"), "git__hub__clone");
+        assert_eq!(names.name("git__hub", "clone"), "git__hub__clone_git_hub");
+        assert_eq!(
+            names.name("git", "hub__clone_git_hub"),
+            "git__hub__clone_git_hub_git"
+        );
+        assert_eq!(
+            names.name("platform", "read_resource"),
+            "platform__read_resource_platform"
+        );
+        assert_eq!(names.name("github", "clone"), "github__clone");
+
+        assert_eq!(
+            names.route("git__hub__clone").cloned(),
+            route("git", "hub__clone")
+        );
+        assert_eq!(
+            names.route("git__hub__clone_git_hub").cloned(),
+            route("git__hub", "clone")
+        );
+        assert_eq!(
+            names.route("platform__read_resource_platform").cloned(),
+            route("platform", "read_resource")
+        );
+        assert_eq!(names.route("platform__read_resource"), None);
+        assert_eq!(names.route("github__push"), None);
+    }
+}
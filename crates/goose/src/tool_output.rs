@@ -0,0 +1,630 @@
+//! Keeping oversized tool output out of the context
+//!
+//! A tool can return far more than the model needs: a build log, a large file, every match of
+//! a search. When the text of a tool result has more than `GOOSE_TOOL_OUTPUT_MAX_TOKENS`
+//! tokens (10000 by default, 0 to keep every output whole), its middle is cut so only its
+//! start and end are sent, with a marker saying what was left out. With
+//! `GOOSE_TOOL_OUTPUT_MODE=summarize`, the model is asked to summarize it instead.
+//! `GOOSE_TOOL_OUTPUT_LIMITS` gives some tools or extensions a limit of their own, such as a
+//! lower one for a chatty build tool.
+//!
+//! Either way the full output is kept for the rest of the session, and the marker names it,
+//! so the agent can read the lines that were cut, page through it from a character offset, or
+//! search it with the `platform__read_tool_output` tool, which the agent has whenever outputs
+//! can be cut.
+use indoc::indoc;
+use mcp_core::{Content, Tool, ToolError, ToolResult};
+use regex::RegexBuilder;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::message::Message;
+use crate::providers::base::Provider;
+use crate::token_counter::TokenCounter;
+
+pub const READ_TOOL_OUTPUT_TOOL: &str = "platform__read_tool_output";
+
+const DEFAULT_MAX_TOKENS: usize = 10_000;
+
+/// The most full outputs kept, the oldest are dropped first
+const MAX_STORED_OUTPUTS: usize = 20;
+
+/// Lines returned by a read of a stored output when not asked for a number
+const DEFAULT_READ_LINES: usize = 200;
+
+/// The most characters a read of a stored output returns, however long its lines are
+const MAX_READ_CHARS: usize = 40_000;
+
+/// How much of an output the model is given to summarize, in multiples of the limit
+const SUMMARY_INPUT_FACTOR: usize = 4;
+
+/// What to do with an output over the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedOutput {
+    /// Keep the start and end of the output
+    Truncate,
+    /// Have the model summarize it
+    Summarize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolOutputLimit {
+    /// Text outputs with more tokens than this are cut or summarized, 0 to keep them whole
+    pub max_tokens: usize,
+    pub mode: OversizedOutput,
+    /// The limits of the tools, by prefixed tool name or extension name, in place of
+    /// `max_tokens`
+    pub tool_limits: HashMap<String, usize>,
+}
+
+impl ToolOutputLimit {
+    pub fn new(max_tokens: usize, mode: OversizedOutput) -> Self {
+        Self {
+            max_tokens,
+            mode,
+            tool_limits: HashMap::new(),
+        }
+    }
+
+    /// Give the tools or extensions `tool_limits` names limits of their own
+    pub fn with_tool_limits(mut self, tool_limits: HashMap<String, usize>) -> Self {
+        self.tool_limits = tool_limits;
+        self
+    }
+
+    /// Read the limit from `GOOSE_TOOL_OUTPUT_MAX_TOKENS`, `GOOSE_TOOL_OUTPUT_LIMITS` and
+    /// `GOOSE_TOOL_OUTPUT_MODE`, None if outputs are kept whole
+    pub fn from_config() -> Option<Self> {
+        let config = Config::global();
+        let max_tokens = config
+            .get_param("GOOSE_TOOL_OUTPUT_MAX_TOKENS")
+            .unwrap_or(DEFAULT_MAX_TOKENS);
+        let tool_limits: HashMap<String, usize> = config
+            .get_param("GOOSE_TOOL_OUTPUT_LIMITS")
+            .unwrap_or_default();
+        if max_tokens == 0 && tool_limits.values().all(|&limit| limit == 0) {
+            return None;
+        }
+        let mode = match config
+            .get_param::<String>("GOOSE_TOOL_OUTPUT_MODE")
+            .as_deref()
+        {
+            Ok("summarize") => OversizedOutput::Summarize,
+            Ok("truncate") | Err(_) => OversizedOutput::Truncate,
+            Ok(other) => {
+                tracing::warn!(
+                    "Unknown GOOSE_TOOL_OUTPUT_MODE {}, expected truncate or summarize",
+                    other
+                );
+                OversizedOutput::Truncate
+            }
+        };
+        Some(Self::new(max_tokens, mode).with_tool_limits(tool_limits))
+    }
+
+    /// The limit of the tool `tool_name`, or None if its outputs are kept whole
+    ///
+    /// A limit for the tool itself comes first, then one for its extension.
+    pub fn max_tokens_for(&self, tool_name: &str) -> Option<usize> {
+        let extension = tool_name.split_once("__").map(|(extension, _)| extension);
+        let max_tokens = self
+            .tool_limits
+            .get(tool_name)
+            .or_else(|| extension.and_then(|extension| self.tool_limits.get(extension)))
+            .copied()
+            .unwrap_or(self.max_tokens);
+        (max_tokens > 0).then_some(max_tokens)
+    }
+
+    /// Cut or summarize the text outputs over the limit, keeping each in full in `store`
+    ///
+    /// Summarizing falls back to cutting if the provider fails, and its usage is recorded as
+    /// the session `session_id`'s.
+    pub async fn apply(
+        &self,
+        tool_name: &str,
+        contents: &mut [Content],
+        store: &ToolOutputStore,
+        counter: &TokenCounter,
+        provider: &dyn Provider,
+        session_id: Option<&str>,
+    ) {
+        let Some(max_tokens) = self.max_tokens_for(tool_name) else {
+            return;
+        };
+        for content in contents.iter_mut() {
+            let Content::Text(text) = content else {
+                continue;
+            };
+            // A token is at least a byte, so shorter text needn't be tokenized
+            if text.text.len() <= max_tokens {
+                continue;
+            }
+            let tokens = counter.count_tokens(&text.text);
+            if tokens <= max_tokens {
+                continue;
+            }
+
+            let id = store.put(text.text.clone());
+            let cut = cut_middle(&text.text, tokens, max_tokens);
+            let summary = match self.mode {
+                OversizedOutput::Truncate => None,
+                OversizedOutput::Summarize => {
+                    let input = cut_middle(&text.text, tokens, max_tokens * SUMMARY_INPUT_FACTOR);
+                    summarize(provider, tool_name, &input.text, session_id)
+                        .await
+                        .map_err(|e| tracing::warn!("Failed to summarize tool output: {}", e))
+                        .ok()
+                }
+            };
+            text.text = match summary {
+                Some(summary) => format!(
+                    "{}\n[This summarizes an output of {} lines and {} tokens, too long to send in full. The full output is kept as {}: call {} with that id to read its lines, page through it from an offset, or search it.]",
+                    summary, cut.total_lines, tokens, id, READ_TOOL_OUTPUT_TOOL
+                ),
+                None => cut.with_marker(&id, tokens),
+            };
+        }
+    }
+}
+
+/// The start and end of a text, from cutting out its middle
+#[derive(Debug, Clone, PartialEq)]
+struct Cut {
+    text: String,
+    head: String,
+    tail: String,
+    /// The last line that is kept at the start, and the first at the end, counting from 1
+    head_lines: usize,
+    tail_start: usize,
+    total_lines: usize,
+}
+
+impl Cut {
+    fn with_marker(&self, id: &str, tokens: usize) -> String {
+        // Without line breaks near the cut, it can be within a line
+        let cut = if self.tail_start > self.head_lines + 1 {
+            format!(
+                "lines {} to {} of {} were cut",
+                self.head_lines + 1,
+                self.tail_start - 1,
+                self.total_lines
+            )
+        } else {
+            format!(
+                "part of line {} of {} was cut",
+                self.tail_start, self.total_lines
+            )
+        };
+        format!(
+            "{}\n[... {}, as the output has {} tokens. The full output is kept as {}: call {} with that id to read those lines, page through it from offset {}, or search it ...]\n{}",
+            self.head.trim_end_matches('\n'),
+            cut,
+            tokens,
+            id,
+            READ_TOOL_OUTPUT_TOOL,
+            self.head.chars().count(),
+            self.tail
+        )
+    }
+}
+
+/// Cut the middle out of `text`, which has `tokens` tokens, to keep about `max_tokens` of
+/// them: two thirds from the start and one third from the end, at line breaks where there
+/// are some nearby
+fn cut_middle(text: &str, tokens: usize, max_tokens: usize) -> Cut {
+    let total_lines = text.lines().count();
+    if tokens <= max_tokens {
+        return Cut {
+            text: text.to_string(),
+            head: text.to_string(),
+            tail: String::new(),
+            head_lines: total_lines,
+            tail_start: total_lines + 1,
+            total_lines,
+        };
+    }
+    let budget = (text.len() as f64 * max_tokens as f64 / tokens as f64) as usize;
+    let head_budget = budget * 2 / 3;
+    let tail_budget = budget - head_budget;
+
+    let mut head_end = floor_char_boundary(text, head_budget);
+    if let Some(newline) = text[..head_end].rfind('\n') {
+        if newline >= head_end / 2 {
+            head_end = newline + 1;
+        }
+    }
+    let mut tail_start = ceil_char_boundary(text, text.len() - tail_budget).max(head_end);
+    if let Some(newline) = text[tail_start..].find('\n') {
+        if newline < tail_budget / 2 {
+            tail_start += newline + 1;
+        }
+    }
+
+    let head = &text[..head_end];
+    let tail = &text[tail_start..];
+    let head_lines = head.matches('\n').count() + usize::from(!head.ends_with('\n'));
+    Cut {
+        text: format!("{}\n...\n{}", head.trim_end_matches('\n'), tail),
+        head: head.to_string(),
+        tail: tail.to_string(),
+        head_lines,
+        tail_start: text[..tail_start].matches('\n').count() + 1,
+        total_lines,
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+async fn summarize(
+    provider: &dyn Provider,
+    tool_name: &str,
+    output: &str,
+    session_id: Option<&str>,
+) -> anyhow::Result<String> {
+    let system = indoc! {"
+        You summarize the output of a tool for an AI agent that called it, as the output is too
+        long for its context. Keep what the agent may need to act on: errors, warnings and
+        failures with their messages, file paths and line numbers, names, numbers and counts,
+        and the overall result. Leave out repetitive and routine lines. Answer with the summary
+        alone, in under 300 words.
+    "};
+    let message = Message::user().with_text(format!(
+        "The output of the {} tool, with its middle cut if it was very long:\n\n{}",
+        tool_name, output
+    ));
+    let (reply, usage) = provider.complete(system, &[message], &[]).await?;
+    let provider_name: String = Config::global()
+        .get_param("GOOSE_PROVIDER")
+        .unwrap_or_else(|_| "unknown".to_string());
+    crate::usage::record(&provider_name, session_id.map(str::to_string), &usage);
+    let summary = reply.as_concat_text();
+    if summary.trim().is_empty() {
+        anyhow::bail!("The summary is empty");
+    }
+    Ok(summary.trim().to_string())
+}
+
+/// The full outputs that were cut or summarized, by the id their marker gives
+#[derive(Default)]
+pub struct ToolOutputStore {
+    outputs: Mutex<VecDeque<(String, Arc<str>)>>,
+    next: Mutex<usize>,
+}
+
+impl ToolOutputStore {
+    /// Keep an output, dropping the oldest kept if there are too many, and return its id
+    pub fn put(&self, output: String) -> String {
+        let id = {
+            let mut next = self.next.lock().unwrap();
+            *next += 1;
+            format!("tool-output-{}", next)
+        };
+        let mut outputs = self.outputs.lock().unwrap();
+        if outputs.len() >= MAX_STORED_OUTPUTS {
+            outputs.pop_front();
+        }
+        outputs.push_back((id.clone(), output.into()));
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<str>> {
+        let outputs = self.outputs.lock().unwrap();
+        outputs
+            .iter()
+            .find(|(kept, _)| kept == id)
+            .map(|(_, output)| output.clone())
+    }
+
+    /// Whether any output was kept, so the agent needs the tool to read them
+    pub fn is_empty(&self) -> bool {
+        self.outputs.lock().unwrap().is_empty()
+    }
+}
+
+pub fn read_tool_output_tool() -> Tool {
+    Tool::new(
+        READ_TOOL_OUTPUT_TOOL.to_string(),
+        indoc! {r#"
+            Read more of a tool output that was too long to return in full.
+
+            When a tool's output is cut or summarized, it says so and gives the id the
+            full output is kept as. Read the lines that were cut from start_line on, or give a
+            pattern to find the lines that match it, to see just the part you need. For output
+            without useful line breaks, such as minified JSON, page through it with offset
+            instead: each page says the offset the next one starts at.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "required": ["id"],
+            "properties": {
+                "id": {"type": "string", "description": "The id of the output, e.g. tool-output-1"},
+                "start_line": {
+                    "type": "integer",
+                    "description": "The first line to read, counting from 1, 1 by default"
+                },
+                "lines": {
+                    "type": "integer",
+                    "description": format!("How many lines to read or matches to return, {} by default", DEFAULT_READ_LINES)
+                },
+                "pattern": {
+                    "type": "string",
+                    "description": "A regular expression to find the lines that match it, case insensitive, from start_line on"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": format!("The character to read a page of up to {} characters from, counting from 0, in place of lines", MAX_READ_CHARS)
+                }
+            }
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct ReadArguments {
+    id: String,
+    start_line: Option<usize>,
+    lines: Option<usize>,
+    pattern: Option<String>,
+    offset: Option<usize>,
+}
+
+/// Run a [`READ_TOOL_OUTPUT_TOOL`] call on the outputs in `store`
+pub fn read_tool_output(store: &ToolOutputStore, arguments: Value) -> ToolResult<Vec<Content>> {
+    let arguments: ReadArguments = serde_json::from_value(arguments)
+        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+    let output = store.get(&arguments.id).ok_or_else(|| {
+        ToolError::InvalidParameters(format!(
+            "No output is kept as {}, it may be from an earlier session or too old",
+            arguments.id
+        ))
+    })?;
+    if let Some(offset) = arguments.offset {
+        if arguments.pattern.is_some() || arguments.start_line.is_some() {
+            return Err(ToolError::InvalidParameters(
+                "Give offset on its own, or start_line and pattern instead".to_string(),
+            ));
+        }
+        return Ok(vec![Content::text(read_page(
+            &arguments.id,
+            &output,
+            offset,
+        ))]);
+    }
+    let start = arguments.start_line.unwrap_or(1).max(1);
+    let count = arguments.lines.unwrap_or(DEFAULT_READ_LINES).max(1);
+    let lines: Vec<&str> = output.lines().collect();
+    let pattern = arguments
+        .pattern
+        .map(|pattern| {
+            RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| ToolError::InvalidParameters(format!("Invalid pattern: {}", e)))
+        })
+        .transpose()?;
+
+    let mut text = String::new();
+    let mut shown = 0;
+    let mut next = None;
+    for (i, line) in lines.iter().enumerate().skip(start - 1) {
+        if pattern
+            .as_ref()
+            .is_some_and(|pattern| !pattern.is_match(line))
+        {
+            continue;
+        }
+        if shown == count || text.len() + line.len() > MAX_READ_CHARS && shown > 0 {
+            next = Some(i + 1);
+            break;
+        }
+        let line = match line.char_indices().nth(MAX_READ_CHARS) {
+            Some((end, _)) => &line[..end],
+            None => line,
+        };
+        text.push_str(&format!("{}: {}\n", i + 1, line));
+        shown += 1;
+    }
+
+    let summary = match (&pattern, shown) {
+        (Some(pattern), 0) => format!(
+            "No lines from line {} of {} match {}",
+            start, arguments.id, pattern
+        ),
+        (None, 0) => format!(
+            "{} has {} lines, there are none from line {}",
+            arguments.id,
+            lines.len(),
+            start
+        ),
+        _ => format!(
+            "{} of the {} lines of {}:",
+            if pattern.is_some() {
+                format!("{} matching lines", shown)
+            } else {
+                format!("{} lines", shown)
+            },
+            lines.len(),
+            arguments.id
+        ),
+    };
+    let mut result = format!("{}\n{}", summary, text);
+    if let Some(next) = next {
+        result.push_str(&format!("[... more from line {}]", next));
+    }
+    Ok(vec![Content::text(result.trim_end().to_string())])
+}
+
+/// Up to [`MAX_READ_CHARS`] characters of the output `id` from the character `offset` on
+fn read_page(id: &str, output: &str, offset: usize) -> String {
+    let total = output.chars().count();
+    if offset >= total {
+        return format!(
+            "{} has {} characters, there are none from offset {}",
+            id, total, offset
+        );
+    }
+    let page: String = output.chars().skip(offset).take(MAX_READ_CHARS).collect();
+    let end = offset + page.chars().count();
+    let mut result = format!(
+        "Characters {} to {} of the {} of {}:\n{}",
+        offset, end, total, id, page
+    );
+    if end < total {
+        result.push_str(&format!("\n[... more from offset {}]", end));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(lines: usize) -> String {
+        (1..=lines).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_cut_middle() {
+        let text = numbered(1000);
+        // Each line is about 3 tokens
+        let cut = cut_middle(&text, 3000, 300);
+        assert!(cut.head.starts_with("line 1\n"));
+        assert!(cut.head.ends_with('\n'));
+        assert!(cut.tail.ends_with("line 1000\n"));
+        assert_eq!(cut.total_lines, 1000);
+        assert!(cut.head_lines > 50 && cut.head_lines < 80);
+        assert!(cut.tail_start > 950 && cut.tail_start < 980);
+        assert!(cut.head.len() + cut.tail.len() <= text.len() / 10 + 1);
+        assert_eq!(
+            cut.head,
+            numbered(cut.head_lines),
+            "the start is cut at a line break"
+        );
+
+        let marked = cut.with_marker("tool-output-1", 3000);
+        assert!(marked.contains(&format!(
+            "[... lines {} to {} of 1000 were cut, as the output has 3000 tokens. The full output is kept as tool-output-1",
+            cut.head_lines + 1,
+            cut.tail_start - 1
+        )));
+        assert!(marked.contains(&format!(
+            "page through it from offset {}, or search it",
+            cut.head.len()
+        )));
+    }
+
+    #[test]
+    fn test_max_tokens_for() {
+        let limit = ToolOutputLimit::new(10_000, OversizedOutput::Truncate).with_tool_limits(
+            HashMap::from([
+                ("developer".to_string(), 2_000),
+                ("developer__text_editor".to_string(), 0),
+            ]),
+        );
+        assert_eq!(limit.max_tokens_for("developer__shell"), Some(2_000));
+        assert_eq!(limit.max_tokens_for("developer__text_editor"), None);
+        assert_eq!(limit.max_tokens_for("github__list_issues"), Some(10_000));
+
+        let limit = ToolOutputLimit::new(0, OversizedOutput::Truncate)
+            .with_tool_limits(HashMap::from([("developer__shell".to_string(), 500)]));
+        assert_eq!(limit.max_tokens_for("developer__shell"), Some(500));
+        assert_eq!(limit.max_tokens_for("github__list_issues"), None);
+    }
+
+    #[test]
+    fn test_cut_middle_of_one_line() {
+        let text = "é".repeat(10_000);
+        let cut = cut_middle(&text, 10_000, 100);
+        assert_eq!(cut.head.chars().count(), 66);
+        assert_eq!(cut.tail.chars().count(), 33);
+        assert_eq!((cut.total_lines, cut.head_lines, cut.tail_start), (1, 1, 1));
+        assert!(cut
+            .with_marker("tool-output-1", 10_000)
+            .contains("[... part of line 1 of 1 was cut, as the output has 10000 tokens."));
+    }
+
+    #[test]
+    fn test_store_drops_the_oldest() {
+        let store = ToolOutputStore::default();
+        assert!(store.is_empty());
+        let first = store.put("first".to_string());
+        for i in 0..MAX_STORED_OUTPUTS {
+            store.put(format!("output {}", i));
+        }
+        assert!(store.get(&first).is_none());
+        assert_eq!(
+            store.get("tool-output-2").as_deref(),
+            Some("output 0"),
+            "ids keep counting after outputs are dropped"
+        );
+    }
+
+    #[test]
+    fn test_read_tool_output() {
+        let store = ToolOutputStore::default();
+        let id = store.put(numbered(500));
+        let read = |arguments: Value| match &read_tool_output(&store, arguments).unwrap()[0] {
+            Content::Text(text) => text.text.clone(),
+            _ => panic!("expected text"),
+        };
+
+        let text = read(json!({"id": id, "start_line": 101, "lines": 2}));
+        assert_eq!(
+            text,
+            "2 lines of the 500 lines of tool-output-1:\n101: line 101\n102: line 102\n[... more from line 103]"
+        );
+        let text = read(json!({"id": id, "pattern": "^LINE 4.0$", "lines": 3}));
+        assert_eq!(
+            text,
+            "3 matching lines of the 500 lines of tool-output-1:\n400: line 400\n410: line 410\n420: line 420\n[... more from line 430]"
+        );
+        let text = read(json!({"id": id, "start_line": 600}));
+        assert_eq!(
+            text,
+            "tool-output-1 has 500 lines, there are none from line 600"
+        );
+
+        let json = store.put(format!("[{}]", "1,".repeat(MAX_READ_CHARS)));
+        let text = read(json!({"id": json, "offset": 10}));
+        assert!(text.starts_with(&format!(
+            "Characters 10 to {} of the {} of tool-output-2:\n,1,1",
+            MAX_READ_CHARS + 10,
+            MAX_READ_CHARS * 2 + 2
+        )));
+        assert!(text.ends_with(&format!("[... more from offset {}]", MAX_READ_CHARS + 10)));
+        let text = read(json!({"id": json, "offset": MAX_READ_CHARS * 2}));
+        assert_eq!(
+            text,
+            format!(
+                "Characters {} to {} of the {} of tool-output-2:\n,]",
+                MAX_READ_CHARS * 2,
+                MAX_READ_CHARS * 2 + 2,
+                MAX_READ_CHARS * 2 + 2
+            )
+        );
+        let both = read_tool_output(&store, json!({"id": json, "offset": 0, "pattern": "1"}));
+        assert!(matches!(both, Err(ToolError::InvalidParameters(_))));
+
+        let missing = read_tool_output(&store, json!({"id": "tool-output-9"}));
+        assert!(matches!(missing, Err(ToolError::InvalidParameters(_))));
+        let invalid = read_tool_output(&store, json!({"id": id, "pattern": "("}));
+        assert!(matches!(invalid, Err(ToolError::InvalidParameters(_))));
+    }
+}
@@ -0,0 +1,216 @@
+//! Renaming the tools of extensions and rewriting how they are described to the model
+//!
+//! `GOOSE_TOOL_OVERRIDES` gives some tools, by the name they are listed with, another name, a
+//! description of their own, or descriptions of their arguments, without changing the servers
+//! they come from. The model knows a renamed tool only by its new name, and its calls by that
+//! name are turned back into calls to the listed name before anything else sees them, so tool
+//! policies, approval rules and the other settings for a tool still go by the listed name.
+use mcp_core::Tool;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::config::Config;
+
+/// The longest tool name providers take
+const MAX_NAME_LEN: usize = 64;
+
+/// How one tool is shown to the model, where it differs from what its server says
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ToolOverride {
+    /// The name the model knows the tool by
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Descriptions of the tool's arguments, by argument name
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOverrides {
+    /// By the name the tool is listed with, such as `developer__shell`
+    overrides: HashMap<String, ToolOverride>,
+}
+
+impl ToolOverrides {
+    pub fn new(overrides: HashMap<String, ToolOverride>) -> Self {
+        Self { overrides }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(
+            Config::global()
+                .get_param("GOOSE_TOOL_OVERRIDES")
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Rename and describe `tools` as the overrides say, returning the listed names of the
+    /// renamed tools by their new names
+    ///
+    /// A new name that another tool is listed with, that another tool was renamed to first, or
+    /// that is one of `reserved`, is left out with a warning, and the tool keeps its name.
+    pub fn apply<'a>(
+        &self,
+        tools: &mut [Tool],
+        reserved: impl IntoIterator<Item = &'a str>,
+    ) -> HashMap<String, String> {
+        let mut taken: HashSet<String> = reserved.into_iter().map(str::to_string).collect();
+        taken.extend(tools.iter().map(|tool| tool.name.clone()));
+        let mut renamed = HashMap::new();
+        for tool in tools.iter_mut() {
+            let Some(tool_override) = self.overrides.get(&tool.name) else {
+                continue;
+            };
+            if let Some(description) = &tool_override.description {
+                tool.description = description.clone();
+            }
+            for (argument, description) in &tool_override.arguments {
+                match tool
+                    .input_schema
+                    .get_mut("properties")
+                    .and_then(|properties| properties.get_mut(argument))
+                    .and_then(Value::as_object_mut)
+                {
+                    Some(property) => {
+                        property.insert(
+                            "description".to_string(),
+                            Value::String(description.clone()),
+                        );
+                    }
+                    None => tracing::warn!(
+                        "GOOSE_TOOL_OVERRIDES describes an argument {} that {} doesn't have",
+                        argument,
+                        tool.name
+                    ),
+                }
+            }
+
+            match &tool_override.name {
+                Some(name) if *name == tool.name => {}
+                Some(name) if !is_valid_name(name) => tracing::warn!(
+                    "Not renaming {} to {}, which isn't a valid tool name",
+                    tool.name,
+                    name
+                ),
+                Some(name) if !taken.insert(name.clone()) => tracing::warn!(
+                    "Not renaming {} to {}, which another tool already has",
+                    tool.name,
+                    name
+                ),
+                Some(name) => {
+                    renamed.insert(
+                        name.clone(),
+                        std::mem::replace(&mut tool.name, name.clone()),
+                    );
+                }
+                None => {}
+            }
+        }
+        renamed
+    }
+}
+
+/// Whether providers take `name` as the name of a tool
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tools() -> Vec<Tool> {
+        let schema = json!({
+            "type": "object",
+            "properties": {"command": {"type": "string", "description": "The command"}}
+        });
+        vec![
+            Tool::new("developer__shell", "Run a command", schema.clone()),
+            Tool::new("github__search_code", "Search code", schema.clone()),
+            Tool::new("gitlab__search_code", "Search code", schema),
+        ]
+    }
+
+    fn overrides() -> ToolOverrides {
+        serde_json::from_value::<HashMap<String, ToolOverride>>(json!({
+            "developer__shell": {
+                "name": "run",
+                "description": "Run a command in the project",
+                "arguments": {"command": "A bash command", "timeout": "Seconds"}
+            },
+            "github__search_code": {"name": "search_code"},
+            // Taken by the tool renamed before it
+            "gitlab__search_code": {"name": "search_code"},
+        }))
+        .map(ToolOverrides::new)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut tools = tools();
+        let renamed = overrides().apply(&mut tools, []);
+
+        let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(names, ["run", "search_code", "gitlab__search_code"]);
+        assert_eq!(
+            renamed,
+            HashMap::from([
+                ("run".to_string(), "developer__shell".to_string()),
+                ("search_code".to_string(), "github__search_code".to_string()),
+            ])
+        );
+        assert_eq!(tools[0].description, "Run a command in the project");
+        assert_eq!(
+            tools[0].input_schema["properties"]["command"]["description"],
+            "A bash command"
+        );
+        assert_eq!(tools[1].description, "Search code");
+    }
+
+    #[test]
+    fn test_apply_keeps_names_it_cannot_take() {
+        let overrides = ToolOverrides::new(HashMap::from([
+            (
+                "developer__shell".to_string(),
+                ToolOverride {
+                    name: Some("github__search_code".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "github__search_code".to_string(),
+                ToolOverride {
+                    name: Some("search code".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "gitlab__search_code".to_string(),
+                ToolOverride {
+                    name: Some("platform__read_resource".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ]));
+        let mut tools = tools();
+        assert!(overrides
+            .apply(&mut tools, ["platform__read_resource"])
+            .is_empty());
+        let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "developer__shell",
+                "github__search_code",
+                "gitlab__search_code"
+            ]
+        );
+    }
+}
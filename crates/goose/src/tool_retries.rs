@@ -0,0 +1,172 @@
+//! Trying tool calls again when they fail in a way that may pass
+//!
+//! A call can fail without the tool having looked at it: the client isn't connected to the
+//! server, or the server answers that it is overloaded or rate limited. Such a call is made
+//! again after a second, then two, and so on. Errors that leave it unknown whether the tool
+//! ran, such as a connection that dropped while waiting for the answer, aren't retried, since
+//! the tool may have done its work.
+//!
+//! Only calls to tools that just read are retried by default, up to `GOOSE_TOOL_MAX_RETRIES`
+//! times, 2 by default. `GOOSE_TOOL_RETRIES` gives some tools or extensions a number of their
+//! own, which holds for any of their calls, such as 3 for a server known to be flaky. Errors
+//! from the tool itself, such as a file that doesn't exist, go back to the model as they are,
+//! as trying again wouldn't change them.
+use mcp_client::client::Error as ClientError;
+use mcp_client::transport::Error as TransportError;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::Config;
+
+const DEFAULT_MAX_RETRIES: usize = 2;
+
+/// Wait before the first retry, doubled before each one after it
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolRetries {
+    /// The retries of calls to tools that just read
+    pub max_retries: usize,
+    /// The retries of the tools, by prefixed tool name or extension name, in place of
+    /// `max_retries`
+    pub tool_retries: HashMap<String, usize>,
+}
+
+impl Default for ToolRetries {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RETRIES)
+    }
+}
+
+impl ToolRetries {
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            tool_retries: HashMap::new(),
+        }
+    }
+
+    /// Give the tools or extensions `tool_retries` names a number of retries of their own
+    pub fn with_tool_retries(mut self, tool_retries: HashMap<String, usize>) -> Self {
+        self.tool_retries = tool_retries;
+        self
+    }
+
+    pub fn from_config() -> Self {
+        let config = Config::global();
+        Self::new(
+            config
+                .get_param("GOOSE_TOOL_MAX_RETRIES")
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+        )
+        .with_tool_retries(config.get_param("GOOSE_TOOL_RETRIES").unwrap_or_default())
+    }
+
+    /// How many times a call to `tool_name` is made again, going by a number for the tool
+    /// itself first, then its extension's, and otherwise none unless the call is `read_only`
+    pub fn for_tool(&self, tool_name: &str, read_only: bool) -> usize {
+        let extension = tool_name.split_once("__").map(|(extension, _)| extension);
+        self.tool_retries
+            .get(tool_name)
+            .or_else(|| extension.and_then(|extension| self.tool_retries.get(extension)))
+            .copied()
+            .unwrap_or(if read_only { self.max_retries } else { 0 })
+    }
+}
+
+/// How long to wait before the retry after `attempt` ones that failed
+pub fn retry_delay(attempt: usize) -> Duration {
+    RETRY_DELAY.saturating_mul(2u32.saturating_pow(attempt as u32))
+}
+
+/// Whether `error` surely kept the call from reaching the tool, in a way that may pass
+///
+/// A call that timed out isn't, since it may have done its work and would take as long again,
+/// nor is one whose connection failed after it may have been sent.
+pub fn is_transient(error: &ClientError) -> bool {
+    match error {
+        ClientError::Transport(error) => is_transient_transport(error),
+        ClientError::NotReady => true,
+        // The service's errors come boxed, as the client's or the transport's
+        ClientError::McpServerError { source, .. } | ClientError::ServerBoxError(source) => {
+            if let Some(error) = source.downcast_ref::<ClientError>() {
+                is_transient(error)
+            } else {
+                source
+                    .downcast_ref::<TransportError>()
+                    .is_some_and(is_transient_transport)
+            }
+        }
+        _ => false,
+    }
+}
+
+fn is_transient_transport(error: &TransportError) -> bool {
+    match error {
+        TransportError::NotConnected => true,
+        // Timed out waiting for the request, too early, rate limited, or unavailable: the
+        // server answered without handling the request
+        TransportError::HttpError { status, .. } => matches!(status, 408 | 425 | 429 | 503),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_error(status: u16) -> ClientError {
+        ClientError::McpServerError {
+            method: "tools/call".to_string(),
+            server: "jira".to_string(),
+            source: Box::new(ClientError::ServerBoxError(Box::new(
+                TransportError::HttpError {
+                    status,
+                    message: String::new(),
+                },
+            ))),
+        }
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&http_error(503)));
+        assert!(is_transient(&http_error(429)));
+        assert!(!is_transient(&http_error(401)));
+        assert!(is_transient(&ClientError::Transport(
+            TransportError::NotConnected
+        )));
+        // The tool may have run before the server failed or the connection dropped
+        assert!(!is_transient(&http_error(500)));
+        assert!(!is_transient(&http_error(504)));
+        assert!(!is_transient(&ClientError::Transport(
+            TransportError::SseConnection("connection reset".to_string())
+        )));
+        assert!(!is_transient(&ClientError::Transport(
+            TransportError::ChannelClosed
+        )));
+        // The tool answered, so it would answer the same again
+        assert!(!is_transient(&ClientError::RpcError {
+            code: -32602,
+            message: "Invalid params".to_string(),
+        }));
+        assert!(!is_transient(&ClientError::UnexpectedResponse(
+            "missing result".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_for_tool() {
+        let retries = ToolRetries::new(2).with_tool_retries(HashMap::from([
+            ("github".to_string(), 5),
+            ("github__create_issue".to_string(), 0),
+        ]));
+        assert_eq!(retries.for_tool("github__create_issue", true), 0);
+        assert_eq!(retries.for_tool("github__list_issues", false), 5);
+        assert_eq!(retries.for_tool("jira__search", true), 2);
+        // Calls that may change something run once unless their tool is given retries
+        assert_eq!(retries.for_tool("jira__create_issue", false), 0);
+        assert_eq!(retry_delay(0), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,121 @@
+//! How long each tool may run
+//!
+//! Every request to an extension waits at most the extension's `timeout`.
+//! `GOOSE_TOOL_TIMEOUTS` gives some tools or extensions a limit of their own, in seconds, such
+//! as a longer one for a slow build or a shorter one for a search that can hang. A call that
+//! runs past its limit is cancelled, which stops the server's work on it: the developer
+//! extension stops the shell command and everything it started. The model is told the call
+//! timed out, so it can go about it another way rather than the session waiting on it.
+use mcp_core::ToolError;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolTimeouts {
+    /// Seconds, by prefixed tool name or extension name
+    timeouts: HashMap<String, u64>,
+}
+
+impl ToolTimeouts {
+    pub fn new(timeouts: HashMap<String, u64>) -> Self {
+        Self { timeouts }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(
+            Config::global()
+                .get_param("GOOSE_TOOL_TIMEOUTS")
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The limit of the tool `tool_name`, one for the tool itself first, then its extension's
+    pub fn for_tool(&self, tool_name: &str) -> Option<Duration> {
+        let extension = tool_name.split_once("__").map(|(extension, _)| extension);
+        self.timeouts
+            .get(tool_name)
+            .or_else(|| extension.and_then(|extension| self.timeouts.get(extension)))
+            .map(|&seconds| Duration::from_secs(seconds))
+    }
+
+    /// The longest any tool of the extension `name` may run, which its requests wait for
+    pub fn longest_for_extension(&self, name: &str) -> Option<Duration> {
+        let prefix = format!("{}__", name);
+        self.timeouts
+            .iter()
+            .filter(|(key, _)| *key == name || key.starts_with(&prefix))
+            .map(|(_, &seconds)| Duration::from_secs(seconds))
+            .max()
+    }
+}
+
+/// Run `call` to the tool `tool_name` for at most `timeout`, dropping it once that is up
+pub async fn within<T>(
+    tool_name: &str,
+    timeout: Option<Duration>,
+    call: impl Future<Output = T>,
+) -> Result<T, ToolError> {
+    let Some(timeout) = timeout else {
+        return Ok(call.await);
+    };
+    tokio::time::timeout(timeout, call).await.map_err(|_| {
+        ToolError::ExecutionError(format!(
+            "{} didn't finish within {} seconds, so it was stopped. Try doing less in one call, \
+            or running the work in the background, rather than calling it the same way again.",
+            tool_name,
+            timeout.as_secs()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeouts() -> ToolTimeouts {
+        ToolTimeouts::new(HashMap::from([
+            ("developer".to_string(), 600),
+            ("developer__screen_capture".to_string(), 10),
+            ("github__search_code".to_string(), 900),
+        ]))
+    }
+
+    #[test]
+    fn test_for_tool() {
+        let timeouts = timeouts();
+        assert_eq!(
+            timeouts.for_tool("developer__screen_capture"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            timeouts.for_tool("developer__shell"),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(timeouts.for_tool("github__list_issues"), None);
+        assert_eq!(
+            timeouts.longest_for_extension("github"),
+            Some(Duration::from_secs(900))
+        );
+        assert_eq!(
+            timeouts.longest_for_extension("developer"),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(timeouts.longest_for_extension("jira"), None);
+    }
+
+    #[tokio::test]
+    async fn test_within() {
+        let slow = tokio::time::sleep(Duration::from_secs(60));
+        let result = within("developer__shell", Some(Duration::from_secs(2)), slow).await;
+        let Err(ToolError::ExecutionError(message)) = result else {
+            panic!("expected the call to time out");
+        };
+        assert!(message.starts_with("developer__shell didn't finish within 2 seconds"));
+
+        let quick = async { 7 };
+        assert_eq!(within("developer__shell", None, quick).await, Ok(7));
+    }
+}
@@ -0,0 +1,210 @@
+//! Spend thresholds that notify the user when they are crossed
+//!
+//! Alerts are configured as a `cost_alerts` list in the config file, each with a
+//! period and a threshold in USD:
+//!
+//! ```yaml
+//! cost_alerts:
+//!   - period: session
+//!     threshold: 5.0
+//!   - period: month
+//!     threshold: 50.0
+//! ```
+//!
+//! Session spend is tracked by the agent, daily/weekly/monthly spend comes from the
+//! [usage store](super::UsageStore). Each alert fires once, on the completion that
+//! takes the spend past its threshold.
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeZone, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::UsageStore;
+use crate::config::Config;
+
+/// Config key holding the list of alerts
+pub const COST_ALERTS_CONFIG_KEY: &str = "cost_alerts";
+
+/// The span of time spend is accumulated over for an alert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertPeriod {
+    Session,
+    Day,
+    Week,
+    Month,
+}
+
+impl AlertPeriod {
+    /// Start of the calendar period containing `now`, in local time
+    ///
+    /// Weeks start on Monday. Returns None for sessions, which aren't calendar based
+    pub fn start(&self, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+        let today = now.date_naive();
+        let first_day = match self {
+            AlertPeriod::Session => return None,
+            AlertPeriod::Day => today,
+            AlertPeriod::Week => {
+                today.checked_sub_days(Days::new(today.weekday().num_days_from_monday() as u64))?
+            }
+            AlertPeriod::Month => NaiveDate::from_ymd_opt(today.year(), today.month(), 1)?,
+        };
+        Local
+            .from_local_datetime(&first_day.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(|start| start.with_timezone(&Utc))
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            AlertPeriod::Session => "this session",
+            AlertPeriod::Day => "today",
+            AlertPeriod::Week => "this week",
+            AlertPeriod::Month => "this month",
+        }
+    }
+}
+
+/// A threshold in USD on the spend over a period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CostAlert {
+    pub period: AlertPeriod,
+    pub threshold: f64,
+}
+
+impl CostAlert {
+    /// The alert message if spend went from `before` to `after` across the threshold
+    pub fn check(&self, before: f64, after: f64) -> Option<String> {
+        (before < self.threshold && after >= self.threshold).then(|| {
+            format!(
+                "Cost alert: spend {} is ${:.2}, past the ${:.2} threshold",
+                self.period.describe(),
+                after,
+                self.threshold
+            )
+        })
+    }
+}
+
+/// The configured cost alerts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostAlerts {
+    pub alerts: Vec<CostAlert>,
+}
+
+impl CostAlerts {
+    pub fn new(alerts: Vec<CostAlert>) -> Self {
+        Self { alerts }
+    }
+
+    pub fn from_config() -> Self {
+        let alerts: Vec<CostAlert> = Config::global()
+            .get_param(COST_ALERTS_CONFIG_KEY)
+            .unwrap_or_default();
+        Self::new(alerts)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty()
+    }
+
+    /// Find the alerts crossed by a completion costing `cost`
+    ///
+    /// `session_cost` is the session's spend including the completion, and the completion
+    /// is expected to already be recorded in `store`. Calendar alerts are skipped when
+    /// there is no store to read their spend from.
+    pub fn crossed(
+        &self,
+        cost: f64,
+        session_cost: f64,
+        store: Option<&UsageStore>,
+    ) -> Result<Vec<String>> {
+        if cost <= 0.0 {
+            return Ok(Vec::new());
+        }
+
+        let now = Local::now();
+        let mut messages = Vec::new();
+        for alert in &self.alerts {
+            let spent = match (alert.period.start(now), store) {
+                (None, _) => session_cost,
+                (Some(since), Some(store)) => store.total(Some(since))?.cost,
+                (Some(_), None) => continue,
+            };
+            if let Some(message) = alert.check(spent - cost, spent) {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::{ProviderUsage, Usage};
+    use crate::usage::UsageRecord;
+    use chrono::Timelike;
+
+    fn record(cost: f64) -> UsageRecord {
+        let mut usage = ProviderUsage::new("test-model".to_string(), Usage::default());
+        usage.cost = Some(cost);
+        UsageRecord::new("test", None, &usage)
+    }
+
+    #[test]
+    fn test_period_start() {
+        // A Wednesday afternoon
+        let now = Local.with_ymd_and_hms(2025, 3, 12, 15, 30, 0).unwrap();
+        let start = |period: AlertPeriod| period.start(now).unwrap().with_timezone(&Local);
+
+        assert_eq!(AlertPeriod::Session.start(now), None);
+        assert_eq!(start(AlertPeriod::Day).date_naive().day(), 12);
+        assert_eq!(start(AlertPeriod::Day).hour(), 0);
+        assert_eq!(start(AlertPeriod::Week).date_naive().day(), 10);
+        assert_eq!(start(AlertPeriod::Month).date_naive().day(), 1);
+    }
+
+    #[test]
+    fn test_alert_fires_once_when_crossed() {
+        let alert = CostAlert {
+            period: AlertPeriod::Session,
+            threshold: 5.0,
+        };
+        assert_eq!(alert.check(3.0, 4.99), None);
+        assert_eq!(
+            alert.check(4.5, 5.25),
+            Some("Cost alert: spend this session is $5.25, past the $5.00 threshold".to_string())
+        );
+        assert_eq!(alert.check(5.25, 6.0), None);
+    }
+
+    #[test]
+    fn test_crossed_uses_store_for_calendar_periods() -> Result<()> {
+        let alerts = CostAlerts::new(vec![
+            CostAlert {
+                period: AlertPeriod::Session,
+                threshold: 1.0,
+            },
+            CostAlert {
+                period: AlertPeriod::Month,
+                threshold: 10.0,
+            },
+        ]);
+
+        let store = UsageStore::open_in_memory()?;
+        store.record(&record(9.5))?;
+        store.record(&record(0.75))?;
+
+        let crossed = alerts.crossed(0.75, 0.75, Some(&store))?;
+        assert_eq!(
+            crossed,
+            vec!["Cost alert: spend this month is $10.25, past the $10.00 threshold".to_string()]
+        );
+
+        // Without a store only the session alert can fire
+        assert!(alerts.crossed(0.75, 0.75, None)?.is_empty());
+        assert_eq!(alerts.crossed(0.5, 1.25, None)?.len(), 1);
+        Ok(())
+    }
+}
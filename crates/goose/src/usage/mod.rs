@@ -0,0 +1,18 @@
+pub mod alerts;
+pub mod store;
+
+pub use alerts::{AlertPeriod, CostAlert, CostAlerts};
+pub use store::{UsageGroupBy, UsageRecord, UsageStore, UsageSummary};
+
+use crate::providers::base::ProviderUsage;
+
+/// Record the usage of a completion made for a session, or outside of one, in the shared store
+///
+/// Failing to record is only logged, as it mustn't fail what the completion was for.
+pub fn record(provider: &str, session_id: Option<String>, usage: &ProviderUsage) {
+    if let Some(store) = UsageStore::global() {
+        if let Err(e) = store.record(&UsageRecord::new(provider, session_id, usage)) {
+            tracing::warn!("Failed to record usage: {}", e);
+        }
+    }
+}
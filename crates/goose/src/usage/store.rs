@@ -0,0 +1,467 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use etcetera::{choose_app_strategy, AppStrategy};
+use once_cell::sync::OnceCell;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::providers::base::ProviderUsage;
+
+/// A single completion's usage, as stored in the usage database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Utc>,
+    /// Name of the session the completion belonged to, if any
+    pub session_id: Option<String>,
+    pub provider: String,
+    pub model: String,
+    /// Whether the lead or the worker model made the completion, when there is a worker
+    pub role: Option<String>,
+    pub input_tokens: Option<i32>,
+    pub output_tokens: Option<i32>,
+    pub total_tokens: Option<i32>,
+    pub cached_input_tokens: Option<i32>,
+    /// Estimated cost in USD, None if the model's price is unknown
+    pub cost: Option<f64>,
+}
+
+impl UsageRecord {
+    pub fn new(provider: &str, session_id: Option<String>, usage: &ProviderUsage) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            session_id,
+            provider: provider.to_string(),
+            model: usage.model.clone(),
+            role: None,
+            input_tokens: usage.usage.input_tokens,
+            output_tokens: usage.usage.output_tokens,
+            total_tokens: usage.usage.total_tokens,
+            cached_input_tokens: usage.usage.cached_input_tokens,
+            cost: usage.cost,
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let timestamp: i64 = row.get("timestamp")?;
+        Ok(Self {
+            timestamp: Utc
+                .timestamp_millis_opt(timestamp)
+                .single()
+                .unwrap_or_default(),
+            session_id: row.get("session_id")?,
+            provider: row.get("provider")?,
+            model: row.get("model")?,
+            role: row.get("role")?,
+            input_tokens: row.get("input_tokens")?,
+            output_tokens: row.get("output_tokens")?,
+            total_tokens: row.get("total_tokens")?,
+            cached_input_tokens: row.get("cached_input_tokens")?,
+            cost: row.get("cost")?,
+        })
+    }
+}
+
+/// Dimension to aggregate usage records over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    /// Local calendar day, formatted as YYYY-MM-DD
+    Day,
+    Provider,
+    Model,
+    Session,
+    /// Lead or worker, empty for completions made without a worker model
+    Role,
+}
+
+impl UsageGroupBy {
+    fn column(&self) -> &'static str {
+        match self {
+            UsageGroupBy::Day => "date(timestamp / 1000, 'unixepoch', 'localtime')",
+            UsageGroupBy::Provider => "provider",
+            UsageGroupBy::Model => "model",
+            UsageGroupBy::Session => "COALESCE(session_id, '')",
+            UsageGroupBy::Role => "COALESCE(role, '')",
+        }
+    }
+}
+
+impl FromStr for UsageGroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "day" => Ok(UsageGroupBy::Day),
+            "provider" => Ok(UsageGroupBy::Provider),
+            "model" => Ok(UsageGroupBy::Model),
+            "session" => Ok(UsageGroupBy::Session),
+            "role" => Ok(UsageGroupBy::Role),
+            _ => Err(anyhow::anyhow!(
+                "Unknown usage grouping '{}', expected one of day, provider, model, session, role",
+                s
+            )),
+        }
+    }
+}
+
+/// Usage summed over a group of records
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageSummary {
+    /// The day, provider, model, session or role the records share, empty for the overall
+    /// total and for records without a session or role
+    pub key: String,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub total_tokens: i64,
+    /// Estimated cost in USD, counting only records with a known price
+    pub cost: f64,
+}
+
+impl UsageSummary {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            key: row.get(0)?,
+            requests: row.get(1)?,
+            input_tokens: row.get(2)?,
+            output_tokens: row.get(3)?,
+            total_tokens: row.get(4)?,
+            cost: row.get(5)?,
+        })
+    }
+}
+
+const SUMMARY_COLUMNS: &str = "COUNT(*), COALESCE(SUM(input_tokens), 0),
+    COALESCE(SUM(output_tokens), 0), COALESCE(SUM(total_tokens), 0), COALESCE(SUM(cost), 0.0)";
+
+/// Usage records persisted in a local SQLite database
+pub struct UsageStore {
+    conn: Mutex<Connection>,
+}
+
+impl UsageStore {
+    /// Open (creating if needed) the usage database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open an in-memory usage database, which is discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                session_id TEXT,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                role TEXT,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                total_tokens INTEGER,
+                cached_input_tokens INTEGER,
+                cost REAL
+            );
+            CREATE INDEX IF NOT EXISTS idx_usage_timestamp ON usage(timestamp);
+            CREATE INDEX IF NOT EXISTS idx_usage_session ON usage(session_id);",
+        )?;
+        // Databases created before roles were recorded don't have the column yet
+        if conn.prepare("SELECT role FROM usage LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE usage ADD COLUMN role TEXT", [])?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Default location of the usage database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("usage.db")
+    }
+
+    /// The shared store at the default path
+    ///
+    /// Returns None if the database couldn't be opened, in which case usage isn't recorded
+    pub fn global() -> Option<&'static UsageStore> {
+        static STORE: OnceCell<Option<UsageStore>> = OnceCell::new();
+        STORE
+            .get_or_init(|| match Self::open(&Self::default_path()) {
+                Ok(store) => Some(store),
+                Err(e) => {
+                    tracing::warn!("Failed to open usage database: {}", e);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    pub fn record(&self, record: &UsageRecord) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO usage (timestamp, session_id, provider, model, role, input_tokens,
+                output_tokens, total_tokens, cached_input_tokens, cost)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                record.timestamp.timestamp_millis(),
+                record.session_id,
+                record.provider,
+                record.model,
+                record.role,
+                record.input_tokens,
+                record.output_tokens,
+                record.total_tokens,
+                record.cached_input_tokens,
+                record.cost,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// List the records at or after `since`, oldest first
+    pub fn records_since(&self, since: DateTime<Utc>) -> Result<Vec<UsageRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT * FROM usage WHERE timestamp >= ?1 ORDER BY timestamp, id")?;
+        let records = stmt
+            .query_map(params![since.timestamp_millis()], UsageRecord::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// Sum the records at or after `since` (all records if None), grouped by a dimension
+    ///
+    /// Days are sorted chronologically, other groupings by descending cost
+    pub fn summarize(
+        &self,
+        since: Option<DateTime<Utc>>,
+        group_by: UsageGroupBy,
+    ) -> Result<Vec<UsageSummary>> {
+        let order = match group_by {
+            UsageGroupBy::Day => "1",
+            _ => "6 DESC, 5 DESC, 1",
+        };
+        let sql = format!(
+            "SELECT {column}, {SUMMARY_COLUMNS} FROM usage WHERE timestamp >= ?1
+            GROUP BY 1 ORDER BY {order}",
+            column = group_by.column(),
+        );
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let summaries = stmt
+            .query_map(
+                params![since.map(|t| t.timestamp_millis()).unwrap_or(0)],
+                UsageSummary::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(summaries)
+    }
+
+    /// Sum all the records at or after `since` (all records if None)
+    pub fn total(&self, since: Option<DateTime<Utc>>) -> Result<UsageSummary> {
+        let sql = format!("SELECT '', {SUMMARY_COLUMNS} FROM usage WHERE timestamp >= ?1");
+        let conn = self.conn.lock().unwrap();
+        let summary = conn.query_row(
+            &sql,
+            params![since.map(|t| t.timestamp_millis()).unwrap_or(0)],
+            UsageSummary::from_row,
+        )?;
+        Ok(summary)
+    }
+
+    /// List the records for a session, oldest first
+    pub fn session_records(&self, session_id: &str) -> Result<Vec<UsageRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT * FROM usage WHERE session_id = ?1 ORDER BY timestamp, id")?;
+        let records = stmt
+            .query_map(params![session_id], UsageRecord::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::Usage;
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    fn provider_usage(model: &str, total_tokens: i32) -> ProviderUsage {
+        ProviderUsage::new(
+            model.to_string(),
+            Usage::new(
+                Some(total_tokens / 2),
+                Some(total_tokens / 2),
+                Some(total_tokens),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_record_and_query() -> Result<()> {
+        let store = UsageStore::open_in_memory()?;
+
+        let mut old = UsageRecord::new("openai", None, &provider_usage("gpt-4o", 10));
+        old.timestamp = Utc::now() - Duration::days(2);
+        store.record(&old)?;
+
+        let recent = UsageRecord::new(
+            "anthropic",
+            Some("20250101_120000".to_string()),
+            &provider_usage("claude-3-5-sonnet-latest", 100),
+        );
+        store.record(&recent)?;
+
+        let since = store.records_since(Utc::now() - Duration::days(1))?;
+        assert_eq!(since.len(), 1);
+        assert_eq!(since[0].provider, "anthropic");
+        assert_eq!(since[0].total_tokens, Some(100));
+        assert_eq!(since[0].cost, recent.cost);
+        assert_eq!(
+            since[0].timestamp.timestamp_millis(),
+            recent.timestamp.timestamp_millis()
+        );
+
+        let session = store.session_records("20250101_120000")?;
+        assert_eq!(session.len(), 1);
+        assert_eq!(session[0].model, "claude-3-5-sonnet-latest");
+
+        assert_eq!(
+            store.records_since(Utc::now() - Duration::days(3))?.len(),
+            2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_records_survive_reopen() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("usage.db");
+
+        let store = UsageStore::open(&path)?;
+        store.record(&UsageRecord::new(
+            "ollama",
+            None,
+            &provider_usage("qwen2.5", 42),
+        ))?;
+        drop(store);
+
+        let store = UsageStore::open(&path)?;
+        let records = store.records_since(Utc::now() - Duration::hours(1))?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].cost, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize() -> Result<()> {
+        let store = UsageStore::open_in_memory()?;
+        let mut usage = provider_usage("gpt-4o", 1_000_000);
+        usage.cost = Some(6.25);
+        store.record(&UsageRecord::new("openai", Some("a".to_string()), &usage))?;
+        store.record(&UsageRecord::new("openai", Some("b".to_string()), &usage))?;
+        store.record(&UsageRecord::new(
+            "ollama",
+            None,
+            &provider_usage("qwen2.5", 100),
+        ))?;
+
+        let by_provider = store.summarize(None, UsageGroupBy::Provider)?;
+        assert_eq!(by_provider.len(), 2);
+        assert_eq!(by_provider[0].key, "openai");
+        assert_eq!(by_provider[0].requests, 2);
+        assert_eq!(by_provider[0].total_tokens, 2_000_000);
+        assert!((by_provider[0].cost - 12.5).abs() < 1e-9);
+        assert_eq!(by_provider[1].key, "ollama");
+        assert_eq!(by_provider[1].cost, 0.0);
+
+        let by_session = store.summarize(None, UsageGroupBy::Session)?;
+        let keys: Vec<&str> = by_session.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", ""]);
+
+        let by_day = store.summarize(Some(Utc::now() - Duration::days(7)), UsageGroupBy::Day)?;
+        assert_eq!(by_day.len(), 1);
+        assert_eq!(by_day[0].requests, 3);
+
+        let total = store.total(None)?;
+        assert_eq!(total.requests, 3);
+        assert_eq!(total.input_tokens, 1_000_050);
+
+        let future = store.total(Some(Utc::now() + Duration::days(1)))?;
+        assert_eq!(future, UsageSummary::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_by_role() -> Result<()> {
+        let store = UsageStore::open_in_memory()?;
+        for (model, role) in [
+            ("claude-3-7-sonnet-latest", Some("lead")),
+            ("claude-3-5-haiku-latest", Some("worker")),
+            ("claude-3-5-haiku-latest", Some("worker")),
+            ("gpt-4o", None),
+        ] {
+            let mut record = UsageRecord::new("anthropic", None, &provider_usage(model, 10));
+            record.role = role.map(str::to_string);
+            store.record(&record)?;
+        }
+
+        let by_role = store.summarize(None, UsageGroupBy::Role)?;
+        let mut requests: Vec<(&str, i64)> = by_role
+            .iter()
+            .map(|summary| (summary.key.as_str(), summary.requests))
+            .collect();
+        requests.sort();
+        assert_eq!(requests, vec![("", 1), ("lead", 1), ("worker", 2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_role_column_added_to_old_databases() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                session_id TEXT,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                total_tokens INTEGER,
+                cached_input_tokens INTEGER,
+                cost REAL
+            );",
+        )?;
+        let store = UsageStore::with_connection(conn)?;
+        let mut record = UsageRecord::new("openai", None, &provider_usage("gpt-4o-mini", 10));
+        record.role = Some("worker".to_string());
+        store.record(&record)?;
+        let records = store.records_since(Utc::now() - Duration::hours(1))?;
+        assert_eq!(records[0].role.as_deref(), Some("worker"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_from_str() {
+        assert_eq!(
+            "Model".parse::<UsageGroupBy>().unwrap(),
+            UsageGroupBy::Model
+        );
+        assert!("week".parse::<UsageGroupBy>().is_err());
+    }
+}
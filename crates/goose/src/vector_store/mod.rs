@@ -0,0 +1,274 @@
+//! Stores for embedding vectors, searched by similarity
+//!
+//! Long-term memory and the document index keep their vectors in the store `GOOSE_VECTOR_STORE`
+//! selects, `sqlite` by default:
+//! - `sqlite`: `vectors.db` in the goose data dir, see [`SqliteVectorStore`]
+//! - `qdrant`: a Qdrant server at `GOOSE_VECTOR_STORE_URL`, see [`QdrantVectorStore`]
+//! - `pgvector`: a PostgreSQL database with the pgvector extension at `GOOSE_VECTOR_STORE_URL`,
+//!   see [`PgVectorStore`]
+//!
+//! Vectors are kept in named collections, one per feature, owner and embedding model, as vectors
+//! from different models can't be compared, and a store shared by several users shouldn't mix
+//! their vectors. The owner is the one in [`crate::config::owner`], so a search only looks at
+//! what this user embedded.
+use anyhow::Result;
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::owner::{machine_id, owner_id};
+use crate::config::Config;
+
+mod pgvector;
+mod qdrant;
+mod sqlite;
+
+pub use self::pgvector::PgVectorStore;
+pub use qdrant::QdrantVectorStore;
+pub use sqlite::SqliteVectorStore;
+
+/// A vector to store, with what it was embedded from
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorPoint {
+    pub id: String,
+    pub vector: Vec<f32>,
+    /// Whatever the feature needs back from a search, `{}` if it has nothing to keep
+    pub payload: Value,
+}
+
+/// A stored vector found by a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredPoint {
+    pub id: String,
+    /// The cosine similarity to the vector searched for, from -1 to 1
+    pub score: f32,
+    pub payload: Value,
+}
+
+/// A store of vectors by id, in collections that are created when first written to
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    /// Where the vectors are kept, e.g. the database file or the server's URL
+    fn location(&self) -> String;
+
+    /// Save the points, replacing those with the same ids
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<()>;
+
+    /// Remove the points with these ids, ignoring those that aren't stored
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<()>;
+
+    /// The points most like `vector`, the closest first
+    async fn search(
+        &self,
+        collection: &str,
+        vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>>;
+}
+
+/// Whose vectors a feature keeps, and where the ids of the points it makes come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorScope {
+    /// The user whose collections are read and written
+    pub owner: String,
+    /// This machine, for ids such as file paths that mean something only on it
+    pub machine: String,
+}
+
+impl VectorScope {
+    pub fn new(owner: impl Into<String>, machine: impl Into<String>) -> Self {
+        Self {
+            owner: owner.into(),
+            machine: machine.into(),
+        }
+    }
+
+    /// The scope of this user on this machine
+    pub fn current() -> Self {
+        Self::new(owner_id(), machine_id())
+    }
+}
+
+/// The collection a feature keeps the owner's vectors of `model` in, e.g.
+/// `memories_5f3a9c0e1b2d4a67_text_embedding_3_small`
+///
+/// The owner is hashed, as stores limit the characters of names and names can be listed.
+pub fn collection_name(feature: &str, scope: &VectorScope, model: &str) -> String {
+    let model: String = model
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let owner = blake3::hash(scope.owner.as_bytes()).to_hex();
+    format!("{}_{}_{}", feature, &owner[..16], model)
+}
+
+/// Whether `store` isn't the one whose location was recorded in `conn` for the scope's owner,
+/// recording it
+///
+/// The features keep what they embedded in their own database, which this tells when the
+/// store or the owner was switched and everything must be embedded again into the new
+/// collections.
+pub(crate) fn store_changed(
+    conn: &Connection,
+    store: &dyn VectorStore,
+    scope: &VectorScope,
+) -> Result<bool> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS vector_store (location TEXT NOT NULL);")?;
+    let location = format!("{} for {}", store.location(), scope.owner);
+    let recorded: Option<String> = conn
+        .query_row("SELECT location FROM vector_store", [], |row| row.get(0))
+        .optional()?;
+    if recorded.as_deref() == Some(location.as_str()) {
+        return Ok(false);
+    }
+    conn.execute_batch("DELETE FROM vector_store;")?;
+    conn.execute(
+        "INSERT INTO vector_store (location) VALUES (?1)",
+        rusqlite::params![location],
+    )?;
+    Ok(true)
+}
+
+/// Open the store selected by `GOOSE_VECTOR_STORE`
+pub fn from_config(config: &Config) -> Result<Arc<dyn VectorStore>> {
+    let name = config
+        .get_param::<String>("GOOSE_VECTOR_STORE")
+        .unwrap_or_else(|_| "sqlite".to_string());
+    let url: Option<String> = config.get_param("GOOSE_VECTOR_STORE_URL").ok();
+    match name.as_str() {
+        "sqlite" => Ok(Arc::new(SqliteVectorStore::open(
+            &SqliteVectorStore::default_path(),
+        )?)),
+        "qdrant" => {
+            let api_key: Option<String> = config.get_secret("GOOSE_VECTOR_STORE_API_KEY").ok();
+            Ok(Arc::new(QdrantVectorStore::new(
+                url.as_deref().unwrap_or(qdrant::DEFAULT_URL),
+                api_key,
+            )?))
+        }
+        "pgvector" => {
+            let url = url.ok_or_else(|| {
+                anyhow::anyhow!("GOOSE_VECTOR_STORE_URL must be set to use the pgvector store")
+            })?;
+            let ca_cert: Option<String> = config.get_param("GOOSE_CA_CERT").ok();
+            Ok(Arc::new(PgVectorStore::connect(
+                &url,
+                ca_cert.as_deref().map(Path::new),
+            )?))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Unknown GOOSE_VECTOR_STORE {}, expected one of sqlite, qdrant, pgvector",
+            name
+        )),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn point(id: &str, vector: &[f32], payload: Value) -> VectorPoint {
+        VectorPoint {
+            id: id.to_string(),
+            vector: vector.to_vec(),
+            payload,
+        }
+    }
+
+    /// Checks a store the way the features use it, in collections only this check writes to
+    pub(crate) async fn check_store(store: &dyn VectorStore, prefix: &str) -> Result<()> {
+        let docs = format!("{}_docs", prefix);
+        let other = format!("{}_other", prefix);
+        store
+            .upsert(
+                &docs,
+                vec![
+                    point("a", &[1.0, 0.0, 0.0], json!({"path": "a.md"})),
+                    point("b", &[0.0, 1.0, 0.0], json!({"path": "b.md"})),
+                    point("c", &[0.7, 0.7, 0.0], json!({})),
+                ],
+            )
+            .await?;
+        store
+            .upsert(&other, vec![point("a", &[0.0, 0.0, 1.0], json!({}))])
+            .await?;
+
+        let found = store.search(&docs, &[1.0, 0.1, 0.0], 2).await?;
+        let ids: Vec<&str> = found.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["a", "c"]);
+        assert!(found[0].score > 0.99);
+        assert_eq!(found[0].payload, json!({"path": "a.md"}));
+
+        // Replacing a point moves it, and collections don't see each other's points
+        store
+            .upsert(&docs, vec![point("a", &[0.0, 1.0, 0.0], json!({"v": 2}))])
+            .await?;
+        let found = store.search(&docs, &[1.0, 0.0, 0.0], 1).await?;
+        assert_eq!(found[0].id, "c");
+        assert_eq!(store.search(&docs, &[0.0, 0.0, 1.0], 5).await?.len(), 3);
+
+        store
+            .delete(&docs, &["a".to_string(), "missing".to_string()])
+            .await?;
+        assert_eq!(store.search(&docs, &[0.0, 1.0, 0.0], 5).await?.len(), 2);
+        store
+            .delete(&docs, &["b".to_string(), "c".to_string()])
+            .await?;
+        store.delete(&other, &["a".to_string()]).await?;
+        assert!(store.search(&docs, &[0.0, 1.0, 0.0], 5).await?.is_empty());
+        assert!(store
+            .search(&format!("{}_never_written", prefix), &[1.0], 5)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_changed() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        let store = SqliteVectorStore::open_in_memory()?;
+        let alice = VectorScope::new("alice", "laptop");
+        assert!(store_changed(&conn, &store, &alice)?);
+        assert!(!store_changed(&conn, &store, &alice)?);
+        let other = QdrantVectorStore::new("http://localhost:6333", None)?;
+        assert!(store_changed(&conn, &other, &alice)?);
+        assert!(store_changed(&conn, &store, &alice)?);
+        assert!(store_changed(
+            &conn,
+            &store,
+            &VectorScope::new("bob", "laptop")
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collection_name() {
+        let alice = VectorScope::new("alice", "laptop");
+        let memories = collection_name("memories", &alice, "text-embedding-3-small");
+        assert!(memories.starts_with("memories_"));
+        assert!(memories.ends_with("_text_embedding_3_small"));
+        assert!(
+            collection_name("knowledge", &alice, "nomic/Embed:v1.5").ends_with("_nomic_embed_v1_5")
+        );
+        // Each owner has their own collections, whichever machine they are on
+        let bob = VectorScope::new("bob", "laptop");
+        assert_ne!(
+            collection_name("memories", &bob, "m"),
+            collection_name("memories", &alice, "m")
+        );
+        let alice_desktop = VectorScope::new("alice", "desktop");
+        assert_eq!(
+            collection_name("memories", &alice_desktop, "m"),
+            collection_name("memories", &alice, "m")
+        );
+    }
+}
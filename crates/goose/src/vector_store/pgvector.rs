@@ -0,0 +1,182 @@
+use ::postgres::Client;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use super::{ScoredPoint, VectorPoint, VectorStore};
+use crate::config::secret_store::off_runtime;
+use crate::session::store::tls_connector;
+
+const SCHEMA: &str = "CREATE EXTENSION IF NOT EXISTS vector;
+    CREATE TABLE IF NOT EXISTS goose_vectors (
+        collection TEXT NOT NULL,
+        id TEXT NOT NULL,
+        embedding vector NOT NULL,
+        payload JSONB NOT NULL,
+        PRIMARY KEY (collection, id)
+    );";
+
+/// Vectors in a PostgreSQL database with the pgvector extension, shared by every goose pointed
+/// at it
+///
+/// The vectors are in one `goose_vectors` table, with the collection as a column, and are
+/// searched by cosine distance. The extension is created if it is available but not yet
+/// enabled, which needs a role allowed to create it.
+pub struct PgVectorStore {
+    url: String,
+    tls: MakeRustlsConnect,
+    /// Only taken when the store is dropped
+    client: Mutex<Option<Client>>,
+}
+
+impl PgVectorStore {
+    /// Connect to the database at a connection string like `postgres://goose@db.internal/goose`,
+    /// creating the table if needed
+    ///
+    /// TLS is used as for the postgres session store, trusting the public root certificates and
+    /// those in the PEM file `ca_cert`.
+    pub fn connect(url: &str, ca_cert: Option<&Path>) -> Result<Self> {
+        let tls = tls_connector(ca_cert)?;
+        let client = off_runtime(|| -> Result<Client> {
+            let mut client = Client::connect(url, tls.clone())?;
+            client.batch_execute(SCHEMA)?;
+            Ok(client)
+        })?;
+        Ok(Self {
+            url: url.to_string(),
+            tls,
+            client: Mutex::new(Some(client)),
+        })
+    }
+
+    /// Run queries on their own thread, as the client can't block an async runtime's thread
+    fn with_client<T: Send>(&self, f: impl FnOnce(&mut Client) -> Result<T> + Send) -> Result<T> {
+        off_runtime(|| {
+            let mut client = self.client.lock().unwrap();
+            let client = client
+                .as_mut()
+                .expect("the client is kept until the store is dropped");
+            if client.is_closed() {
+                *client = Client::connect(&self.url, self.tls.clone())?;
+            }
+            f(client)
+        })
+    }
+}
+
+impl Drop for PgVectorStore {
+    fn drop(&mut self) {
+        // Closing the connection blocks on the client's runtime too
+        if let Some(client) = self.client.get_mut().ok().and_then(Option::take) {
+            off_runtime(|| drop(client));
+        }
+    }
+}
+
+/// A vector in pgvector's text format, e.g. `[0.5,1]`, as the client has no type for vectors
+fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(f32::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+#[async_trait]
+impl VectorStore for PgVectorStore {
+    /// The connection string without its password, if it has one
+    fn location(&self) -> String {
+        match url::Url::parse(&self.url) {
+            Ok(mut url) => {
+                let _ = url.set_password(None);
+                url.to_string()
+            }
+            Err(_) => self.url.clone(),
+        }
+    }
+
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<()> {
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            let upsert = tx.prepare(
+                "INSERT INTO goose_vectors (collection, id, embedding, payload)
+                VALUES ($1, $2, $3::text::vector, $4::text::jsonb)
+                ON CONFLICT (collection, id)
+                DO UPDATE SET embedding = EXCLUDED.embedding, payload = EXCLUDED.payload",
+            )?;
+            for point in &points {
+                tx.execute(
+                    &upsert,
+                    &[
+                        &collection,
+                        &point.id,
+                        &vector_literal(&point.vector),
+                        &point.payload.to_string(),
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<()> {
+        self.with_client(|client| {
+            client.execute(
+                "DELETE FROM goose_vectors WHERE collection = $1 AND id = ANY($2)",
+                &[&collection, &ids],
+            )?;
+            Ok(())
+        })
+    }
+
+    async fn search(
+        &self,
+        collection: &str,
+        vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>> {
+        let query = vector_literal(vector);
+        let rows = self.with_client(|client| {
+            Ok(client.query(
+                "SELECT id, 1 - (embedding <=> $2::text::vector), payload::text FROM goose_vectors
+                WHERE collection = $1
+                ORDER BY embedding <=> $2::text::vector
+                LIMIT $3",
+                &[&collection, &query, &(limit as i64)],
+            )?)
+        })?;
+        rows.iter()
+            .map(|row| {
+                Ok(ScoredPoint {
+                    id: row.get(0),
+                    score: row.get::<_, f64>(1) as f32,
+                    payload: serde_json::from_str(row.get(2))?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::check_store;
+    use super::*;
+
+    #[test]
+    fn test_vector_literal() {
+        assert_eq!(vector_literal(&[0.5, 1.0, -2.25]), "[0.5,1,-2.25]");
+    }
+
+    // Needs a database with the pgvector extension, e.g.
+    // GOOSE_TEST_PGVECTOR_URL="postgres://postgres@localhost/goose_test?sslmode=disable"
+    #[tokio::test]
+    async fn test_store() -> Result<()> {
+        let Ok(url) = std::env::var("GOOSE_TEST_PGVECTOR_URL") else {
+            println!("Skipping the pgvector store test - GOOSE_TEST_PGVECTOR_URL not set");
+            return Ok(());
+        };
+        let store = PgVectorStore::connect(&url, None)?;
+        let prefix = format!("test_{}", uuid::Uuid::new_v4().simple());
+        check_store(&store, &prefix).await
+    }
+}
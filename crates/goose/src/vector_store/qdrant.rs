@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use super::{ScoredPoint, VectorPoint, VectorStore};
+
+pub const DEFAULT_URL: &str = "http://localhost:6333";
+
+/// Vectors in a Qdrant server, through its REST API
+///
+/// Collections are created with cosine distance and the length of the first vector written to
+/// them. Qdrant only takes integers and UUIDs as point ids, so points are stored under a UUID
+/// derived from their id, which is kept in the payload.
+pub struct QdrantVectorStore {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    /// The collections known to exist, so they are only checked once
+    collections: Mutex<HashSet<String>>,
+}
+
+impl QdrantVectorStore {
+    /// A client for the server at `url`, e.g. `http://localhost:6333`, authenticating with
+    /// `api_key` if it needs one
+    pub fn new(url: &str, api_key: Option<String>) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(Duration::from_secs(30)).build()?,
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+            collections: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}/{}", self.url, path));
+        match &self.api_key {
+            Some(key) => request.header("api-key", key),
+            None => request,
+        }
+    }
+
+    /// Create the collection for vectors of `size` if it doesn't exist yet
+    async fn ensure_collection(&self, collection: &str, size: usize) -> Result<()> {
+        let mut known = self.collections.lock().await;
+        if known.contains(collection) {
+            return Ok(());
+        }
+        let path = format!("collections/{}", collection);
+        let response = self.request(reqwest::Method::GET, &path).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            let response = self
+                .request(reqwest::Method::PUT, &path)
+                .json(&json!({"vectors": {"size": size, "distance": "Cosine"}}))
+                .send()
+                .await?;
+            check(response).await?;
+        } else {
+            check(response).await?;
+        }
+        known.insert(collection.to_string());
+        Ok(())
+    }
+}
+
+/// The point id Qdrant keeps a point under, a UUID from the hash of its id
+fn point_id(id: &str) -> String {
+    let hash = Sha256::digest(id.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Uuid::from_bytes(bytes).to_string()
+}
+
+/// The body of a successful response, or an error with what the server said
+async fn check(response: Response) -> Result<Value> {
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        let message = body
+            .pointer("/status/error")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| body.to_string());
+        return Err(anyhow!("Qdrant returned {}: {}", status, message));
+    }
+    Ok(body)
+}
+
+/// The points in a search response, with the ids and payloads they were upserted with
+fn parse_results(body: &Value) -> Result<Vec<ScoredPoint>> {
+    body.get("result")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("No result in the Qdrant response"))?
+        .iter()
+        .map(|hit| {
+            let payload = hit.get("payload").cloned().unwrap_or_default();
+            Ok(ScoredPoint {
+                id: payload
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow!("A Qdrant point has no id in its payload"))?
+                    .to_string(),
+                score: hit.get("score").and_then(Value::as_f64).unwrap_or_default() as f32,
+                payload: payload.get("payload").cloned().unwrap_or_else(|| json!({})),
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl VectorStore for QdrantVectorStore {
+    fn location(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<()> {
+        let Some(first) = points.first() else {
+            return Ok(());
+        };
+        self.ensure_collection(collection, first.vector.len())
+            .await?;
+        let points: Vec<Value> = points
+            .into_iter()
+            .map(|point| {
+                json!({
+                    "id": point_id(&point.id),
+                    "vector": point.vector,
+                    "payload": {"id": point.id, "payload": point.payload},
+                })
+            })
+            .collect();
+        let response = self
+            .request(
+                reqwest::Method::PUT,
+                &format!("collections/{}/points?wait=true", collection),
+            )
+            .json(&json!({ "points": points }))
+            .send()
+            .await?;
+        check(response).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let points: Vec<String> = ids.iter().map(|id| point_id(id)).collect();
+        let response = self
+            .request(
+                reqwest::Method::POST,
+                &format!("collections/{}/points/delete?wait=true", collection),
+            )
+            .json(&json!({ "points": points }))
+            .send()
+            .await?;
+        // Nothing was ever stored in a collection that doesn't exist
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        check(response).await?;
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        collection: &str,
+        vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>> {
+        let response = self
+            .request(
+                reqwest::Method::POST,
+                &format!("collections/{}/points/search", collection),
+            )
+            .json(&json!({"vector": vector, "limit": limit, "with_payload": true}))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        parse_results(&check(response).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::check_store;
+    use super::*;
+
+    #[test]
+    fn test_point_id() {
+        let id = point_id("/docs/deploy.md#0");
+        assert_eq!(id, point_id("/docs/deploy.md#0"));
+        assert_ne!(id, point_id("/docs/deploy.md#1"));
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+    }
+
+    #[test]
+    fn test_parse_results() {
+        let body = json!({"result": [
+            {"id": point_id("a"), "score": 0.9, "payload": {"id": "a", "payload": {"path": "a.md"}}},
+            {"id": point_id("b"), "score": 0.5, "payload": {"id": "b", "payload": {}}},
+        ]});
+        let points = parse_results(&body).unwrap();
+        assert_eq!(points[0].id, "a");
+        assert_eq!(points[0].payload, json!({"path": "a.md"}));
+        assert_eq!(points[1].score, 0.5);
+        assert!(parse_results(&json!({"status": "ok"})).is_err());
+    }
+
+    // Needs a Qdrant server, e.g. GOOSE_TEST_QDRANT_URL="http://localhost:6333"
+    #[tokio::test]
+    async fn test_store() -> Result<()> {
+        let Ok(url) = std::env::var("GOOSE_TEST_QDRANT_URL") else {
+            println!("Skipping the Qdrant store test - GOOSE_TEST_QDRANT_URL not set");
+            return Ok(());
+        };
+        let store = QdrantVectorStore::new(&url, None)?;
+        let prefix = format!("test_{}", uuid::Uuid::new_v4().simple());
+        check_store(&store, &prefix).await
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use etcetera::{choose_app_strategy, AppStrategy};
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::{ScoredPoint, VectorPoint, VectorStore};
+use crate::embeddings::cache::{decode_vector, encode_vector};
+use crate::embeddings::cosine_similarity;
+
+/// Vectors in a local SQLite database, searched by comparing the query with every vector of
+/// the collection, which is quick enough for the memories and documents of one user
+pub struct SqliteVectorStore {
+    conn: Mutex<Connection>,
+    location: String,
+}
+
+impl SqliteVectorStore {
+    /// Open (creating if needed) the vector database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?, path.display().to_string())
+    }
+
+    /// Open an in-memory store, which is discarded when dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?, ":memory:".to_string())
+    }
+
+    fn with_connection(conn: Connection, location: String) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS vectors (
+                collection TEXT NOT NULL,
+                id TEXT NOT NULL,
+                vector BLOB NOT NULL,
+                payload TEXT NOT NULL,
+                PRIMARY KEY (collection, id)
+            );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            location,
+        })
+    }
+
+    /// Default location of the vector database, in the goose data dir
+    pub fn default_path() -> PathBuf {
+        choose_app_strategy(crate::config::APP_STRATEGY.clone())
+            .map(|strategy| strategy.data_dir())
+            .unwrap_or_else(|_| PathBuf::from(".local/share/goose"))
+            .join("vectors.db")
+    }
+}
+
+#[async_trait]
+impl VectorStore for SqliteVectorStore {
+    fn location(&self) -> String {
+        self.location.clone()
+    }
+
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for point in points {
+            tx.execute(
+                "INSERT OR REPLACE INTO vectors (collection, id, vector, payload)
+                    VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    collection,
+                    point.id,
+                    encode_vector(&point.vector),
+                    point.payload.to_string()
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for id in ids {
+            tx.execute(
+                "DELETE FROM vectors WHERE collection = ?1 AND id = ?2",
+                params![collection, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        collection: &str,
+        vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement =
+            conn.prepare("SELECT id, vector, payload FROM vectors WHERE collection = ?1")?;
+        let mut points = statement
+            .query_map(params![collection], |row| {
+                let stored = decode_vector(&row.get::<_, Vec<u8>>(1)?);
+                Ok((
+                    row.get::<_, String>(0)?,
+                    cosine_similarity(vector, &stored),
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        points.sort_by(|(_, a, _), (_, b, _)| b.total_cmp(a));
+        points.truncate(limit);
+        points
+            .into_iter()
+            .map(|(id, score, payload)| {
+                Ok(ScoredPoint {
+                    id,
+                    score,
+                    payload: serde_json::from_str(&payload)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::check_store;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_store() -> Result<()> {
+        check_store(&SqliteVectorStore::open_in_memory()?, "test").await
+    }
+
+    #[tokio::test]
+    async fn test_persists() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("nested").join("vectors.db");
+        let point = VectorPoint {
+            id: "a".to_string(),
+            vector: vec![0.5, 0.5],
+            payload: serde_json::json!({"path": "a.md"}),
+        };
+        SqliteVectorStore::open(&path)?
+            .upsert("docs", vec![point])
+            .await?;
+        let found = SqliteVectorStore::open(&path)?
+            .search("docs", &[1.0, 1.0], 5)
+            .await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].payload["path"], "a.md");
+        Ok(())
+    }
+}
@@ -0,0 +1,597 @@
+//! Fetching web pages for the agent, without an extension
+//!
+//! With `GOOSE_WEB_FETCH` on, the agent is given the `platform__fetch_url` tool. It downloads a
+//! page over the network settings of `GOOSE_PROXY` and the like, and gives back the readable
+//! part of an HTML page as Markdown, or other text as it is. Sites are asked first through their
+//! `robots.txt`, and pages it disallows aren't fetched. Hosts on this machine or a private
+//! network, such as a router or a cloud metadata service, aren't fetched unless
+//! `GOOSE_WEB_FETCH_PRIVATE` is true, and each redirect is checked like the URL the model gave
+//! before it is followed. The addresses a host resolves to when it is connected to are checked
+//! too, so it can't resolve to a public address for the check and a private one after. Downloads stop at
+//! `GOOSE_WEB_FETCH_MAX_BYTES`, and what the model is given at the `max_length` it asks for.
+//! Pages, and the `robots.txt` of their sites, are kept for a while in memory, so reading a
+//! long page a part at a time downloads it once.
+mod readable;
+mod robots;
+
+pub use readable::{readable, ReadablePage};
+pub use robots::{Robots, ROBOTS_AGENT};
+
+use indoc::indoc;
+use mcp_core::{Content, Tool, ToolError, ToolResult};
+use once_cell::sync::OnceCell;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{CONTENT_TYPE, LOCATION, USER_AGENT};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::{Host, Url};
+
+use crate::config::Config;
+use crate::providers::http::client_builder;
+
+pub const FETCH_URL_TOOL: &str = "platform__fetch_url";
+
+/// The most of a response downloaded, unless `GOOSE_WEB_FETCH_MAX_BYTES` is set
+const DEFAULT_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// How many characters of a page the model is given, unless it asks for another number
+const DEFAULT_MAX_LENGTH: usize = 20_000;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a fetched page or `robots.txt` is kept
+const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// The most pages kept at once, the oldest going first
+const CACHE_ENTRIES: usize = 64;
+
+/// The most redirects followed from the URL the model gave
+const MAX_REDIRECTS: usize = 10;
+
+/// Whether `GOOSE_WEB_FETCH` is on
+pub fn enabled() -> bool {
+    Config::global()
+        .get_param::<bool>("GOOSE_WEB_FETCH")
+        .unwrap_or(false)
+}
+
+/// A page as the model reads it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedPage {
+    /// Where the page was found, after redirects
+    pub url: String,
+    pub title: Option<String>,
+    pub text: String,
+}
+
+/// What was fetched lately, by URL
+struct Cache<T> {
+    /// With when each was fetched, and how many were put before it
+    entries: Mutex<HashMap<String, (Instant, u64, T)>>,
+    puts: AtomicU64,
+}
+
+impl<T: Clone> Cache<T> {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            puts: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((fetched, _, value)) if fetched.elapsed() < CACHE_TTL => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&self, key: String, value: T) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= CACHE_ENTRIES && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, put, _))| *put)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        let put = self.puts.fetch_add(1, Ordering::SeqCst);
+        entries.insert(key, (Instant::now(), put, value));
+    }
+}
+
+fn pages() -> &'static Cache<FetchedPage> {
+    static PAGES: OnceCell<Cache<FetchedPage>> = OnceCell::new();
+    PAGES.get_or_init(Cache::new)
+}
+
+fn robots() -> &'static Cache<Robots> {
+    static ROBOTS: OnceCell<Cache<Robots>> = OnceCell::new();
+    ROBOTS.get_or_init(Cache::new)
+}
+
+fn client() -> ToolResult<&'static Client> {
+    static CLIENT: OnceCell<Client> = OnceCell::new();
+    CLIENT.get_or_try_init(|| {
+        client_builder("GOOSE")
+            .and_then(|builder| {
+                // Redirects are followed in fetch, which checks where each one leads
+                Ok(builder
+                    .timeout(REQUEST_TIMEOUT)
+                    .redirect(reqwest::redirect::Policy::none())
+                    .dns_resolver(Arc::new(PublicResolver))
+                    .build()?)
+            })
+            .map_err(|e| ToolError::ExecutionError(format!("Can't fetch pages: {}", e)))
+    })
+}
+
+/// Looks up the hosts the fetch client connects to, leaving out the addresses that aren't public
+/// unless `GOOSE_WEB_FETCH_PRIVATE` allows them
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            // The client sets the port of the URL on the addresses
+            let addresses = tokio::net::lookup_host((name.as_str(), 0)).await?;
+            let addresses: Vec<SocketAddr> = if allows_private() {
+                addresses.collect()
+            } else {
+                addresses
+                    .filter(|address| is_public(address.ip()))
+                    .collect()
+            };
+            if addresses.is_empty() {
+                return Err(format!(
+                    "{} has no public address, and Goose doesn't fetch pages from this machine \
+                    or a private network unless GOOSE_WEB_FETCH_PRIVATE is true",
+                    name.as_str()
+                )
+                .into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The value of the user agent header, naming Goose as `robots.txt` does
+fn user_agent() -> String {
+    format!("{}/{}", ROBOTS_AGENT, env!("CARGO_PKG_VERSION"))
+}
+
+fn max_bytes() -> usize {
+    Config::global()
+        .get_param("GOOSE_WEB_FETCH_MAX_BYTES")
+        .unwrap_or(DEFAULT_MAX_BYTES)
+}
+
+/// Whether `GOOSE_WEB_FETCH_PRIVATE` lets pages on this machine or a private network be fetched
+fn allows_private() -> bool {
+    Config::global()
+        .get_param::<bool>("GOOSE_WEB_FETCH_PRIVATE")
+        .unwrap_or(false)
+}
+
+/// Whether `ip` is an address on the internet, rather than of this machine, a private network
+/// or a link, where a fetch could reach services that trust whoever can reach them
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    // 100.64.0.0/10 is shared by carrier-grade NAT
+    let shared = first == 100 && (64..128).contains(&second);
+    // 0.0.0.0/8 is this network
+    let this_network = first == 0;
+    !(ip.is_loopback()
+        || this_network
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || shared)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    // NAT64 (64:ff9b::/96), 6to4 (2002::/16) and IPv4-compatible (::/96) addresses lead to the
+    // IPv4 address in them, which has to be public too
+    let embedded = match ip.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] | [0, 0, 0, 0, 0, 0, high, low] => Some((high, low)),
+        [0x2002, high, low, ..] => Some((high, low)),
+        _ => None,
+    }
+    .map(|(high, low)| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)));
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        || ip.is_multicast())
+        && embedded.is_none_or(is_public_v4)
+}
+
+/// Refuse `url` when its host is, or resolves to, an address that isn't public, unless
+/// `GOOSE_WEB_FETCH_PRIVATE` allows it
+async fn check_host(url: &Url) -> ToolResult<()> {
+    if allows_private() {
+        return Ok(());
+    }
+    let addresses: Vec<IpAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => {
+            let port = url.port_or_known_default().unwrap_or(80);
+            tokio::net::lookup_host((domain, port))
+                .await
+                .map_err(|e| {
+                    ToolError::ExecutionError(format!("Failed to look up {}: {}", domain, e))
+                })?
+                .map(|address| address.ip())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    match addresses.iter().find(|ip| !is_public(**ip)) {
+        Some(ip) => Err(ToolError::ExecutionError(format!(
+            "{} is on this machine or a private network ({}), which Goose doesn't fetch pages \
+            from unless GOOSE_WEB_FETCH_PRIVATE is true",
+            url.host_str().unwrap_or_default(),
+            ip
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Whether the `robots.txt` of `url`'s site lets Goose fetch it
+async fn robots_allow(url: &Url) -> ToolResult<bool> {
+    let origin = url.origin().ascii_serialization();
+    let rules = match robots().get(&origin) {
+        Some(rules) => rules,
+        None => {
+            let response = client()?
+                .get(format!("{}/robots.txt", origin))
+                .header(USER_AGENT, user_agent())
+                .send()
+                .await;
+            let rules = match response {
+                Ok(response) if response.status().is_success() => {
+                    let text = download(response, max_bytes()).await?;
+                    Robots::parse(&String::from_utf8_lossy(&text), ROBOTS_AGENT)
+                }
+                // A site that doesn't say, or can't be asked, doesn't disallow anything
+                _ => Robots::default(),
+            };
+            robots().put(origin, rules.clone());
+            rules
+        }
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    Ok(rules.allows(&path))
+}
+
+/// The body of `response`, failing once it is longer than `limit`
+async fn download(mut response: reqwest::Response, limit: usize) -> ToolResult<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| ToolError::ExecutionError(format!("Failed to download the page: {}", e)))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(ToolError::ExecutionError(format!(
+                "The page is larger than {} bytes, the most Goose downloads",
+                limit
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Send a request for `url`, following the redirects of the response, each checked as `url` is
+async fn follow(url: &Url) -> ToolResult<reqwest::Response> {
+    let mut current = url.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        if !matches!(current.scheme(), "http" | "https") {
+            return Err(ToolError::ExecutionError(format!(
+                "{} redirects to {}, which isn't an http or https URL",
+                url, current
+            )));
+        }
+        check_host(&current).await?;
+        if !robots_allow(&current).await? {
+            return Err(ToolError::ExecutionError(format!(
+                "The robots.txt of {} doesn't allow fetching {}",
+                current.host_str().unwrap_or_default(),
+                current
+            )));
+        }
+
+        let response = client()?
+            .get(current.clone())
+            .header(USER_AGENT, user_agent())
+            .send()
+            .await
+            .map_err(|e| {
+                ToolError::ExecutionError(format!("Failed to fetch {}: {}", current, e))
+            })?;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .filter(|_| response.status().is_redirection());
+        match location {
+            Some(location) => {
+                current = current.join(location).map_err(|e| {
+                    ToolError::ExecutionError(format!(
+                        "{} redirects to {}, which isn't a URL: {}",
+                        current, location, e
+                    ))
+                })?;
+            }
+            None => return Ok(response),
+        }
+    }
+    Err(ToolError::ExecutionError(format!(
+        "{} redirects more than {} times",
+        url, MAX_REDIRECTS
+    )))
+}
+
+/// Download `url` and read what it says
+pub async fn fetch(url: &Url) -> ToolResult<FetchedPage> {
+    if let Some(page) = pages().get(url.as_str()) {
+        return Ok(page);
+    }
+
+    let response = follow(url).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ToolError::ExecutionError(match status {
+            StatusCode::NOT_FOUND => format!("There is no page at {}", url),
+            status => format!("Fetching {} failed with {}", url, status),
+        }));
+    }
+    let final_url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("text/html")
+        .to_lowercase();
+    let body = download(response, max_bytes()).await?;
+
+    let page = if content_type.contains("html") {
+        let ReadablePage { title, markdown } =
+            readable(&String::from_utf8_lossy(&body), &final_url);
+        FetchedPage {
+            url: final_url.to_string(),
+            title,
+            text: markdown,
+        }
+    } else if is_text(&content_type) {
+        FetchedPage {
+            url: final_url.to_string(),
+            title: None,
+            text: String::from_utf8_lossy(&body).into_owned(),
+        }
+    } else {
+        return Err(ToolError::ExecutionError(format!(
+            "{} is {}, which isn't text Goose can read",
+            url, content_type
+        )));
+    };
+    pages().put(url.to_string(), page.clone());
+    Ok(page)
+}
+
+/// Whether a response of `content_type` is text to give as it is
+fn is_text(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || ["json", "xml", "yaml", "javascript", "csv", "markdown"]
+            .iter()
+            .any(|kind| content_type.contains(kind))
+}
+
+pub fn fetch_url_tool() -> Tool {
+    Tool::new(
+        FETCH_URL_TOOL.to_string(),
+        indoc! {r#"
+            Fetch a web page and read what it says, as Markdown.
+
+            The page's navigation, ads and other boilerplate are left out, and its headings,
+            lists, links and code kept. Other text, like JSON or plain text files, comes as it
+            is. Long pages are cut at max_length characters; to read on, fetch the page again
+            with start_index where the last part ended.
+
+            Pages that the site's robots.txt disallows aren't fetched, nor are pages on this
+            machine or a private network.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": {"type": "string", "description": "The http or https URL to fetch"},
+                "max_length": {
+                    "type": "integer",
+                    "description": format!("Optional most characters to return, {} by default", DEFAULT_MAX_LENGTH)
+                },
+                "start_index": {
+                    "type": "integer",
+                    "description": "Optional character to start from, to read on from a part that was cut, 0 by default"
+                }
+            }
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct FetchArguments {
+    url: String,
+    max_length: Option<usize>,
+    #[serde(default)]
+    start_index: usize,
+}
+
+/// Run a [`FETCH_URL_TOOL`] call
+pub async fn fetch_url(arguments: Value) -> ToolResult<Vec<Content>> {
+    let arguments: FetchArguments = serde_json::from_value(arguments)
+        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+    let url = Url::parse(&arguments.url).map_err(|e| {
+        ToolError::InvalidParameters(format!("{} isn't a URL: {}", arguments.url, e))
+    })?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ToolError::InvalidParameters(format!(
+            "Only http and https URLs can be fetched, not {}",
+            url.scheme()
+        )));
+    }
+
+    let page = fetch(&url).await?;
+    let max_length = arguments.max_length.unwrap_or(DEFAULT_MAX_LENGTH).max(1);
+    Ok(vec![Content::text(format_page(
+        &page,
+        arguments.start_index,
+        max_length,
+    ))])
+}
+
+/// The part of `page` from the character `start` that the model is given
+fn format_page(page: &FetchedPage, start: usize, max_length: usize) -> String {
+    let total = page.text.chars().count();
+    let part: String = page.text.chars().skip(start).take(max_length).collect();
+    let mut text = format!("URL: {}\n", page.url);
+    if let Some(title) = &page.title {
+        text.push_str(&format!("Title: {}\n", title));
+    }
+    text.push('\n');
+    if part.is_empty() {
+        text.push_str(&format!(
+            "Nothing from character {}, the page has {} characters",
+            start, total
+        ));
+        return text;
+    }
+    text.push_str(&part);
+    let end = start + part.chars().count();
+    if end < total {
+        text.push_str(&format!(
+            "\n\n[Cut at character {} of {}. Fetch again with start_index {} to read on.]",
+            end, total, end
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_page() {
+        let page = FetchedPage {
+            url: "https://example.com/".to_string(),
+            title: Some("Example".to_string()),
+            text: "abcdefghij".to_string(),
+        };
+        assert_eq!(
+            format_page(&page, 0, 4),
+            "URL: https://example.com/\nTitle: Example\n\nabcd\n\n\
+            [Cut at character 4 of 10. Fetch again with start_index 4 to read on.]"
+        );
+        assert_eq!(
+            format_page(&page, 8, 4),
+            "URL: https://example.com/\nTitle: Example\n\nij"
+        );
+        assert!(format_page(&page, 10, 4)
+            .ends_with("Nothing from character 10, the page has 10 characters"));
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in [
+            "93.184.216.34",
+            "2606:2800:220:1:248:1893:25c8:1946",
+            "100.128.0.1",
+            "64:ff9b::5db8:d822",
+            "2002:5db8:d822::1",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.5.4",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "0.1.2.3",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b::7f00:1",
+            "2002:a00:1::1",
+            "2002:c0a8:101::",
+            "::127.0.0.1",
+            "::10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_host() {
+        let check = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            async move { check_host(&url).await }
+        };
+        assert!(check("http://169.254.169.254/latest/meta-data/")
+            .await
+            .is_err());
+        assert!(check("http://[::1]:8080/").await.is_err());
+        assert!(check("http://localhost/").await.is_err());
+        assert!(check("https://93.184.216.34/").await.is_ok());
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = Cache::new();
+        for i in 0..=CACHE_ENTRIES {
+            cache.put(format!("https://example.com/{}", i), i);
+        }
+        assert_eq!(cache.get("https://example.com/0"), None);
+        assert_eq!(cache.get("https://example.com/1"), Some(1));
+        assert_eq!(
+            cache.get(&format!("https://example.com/{}", CACHE_ENTRIES)),
+            Some(CACHE_ENTRIES)
+        );
+    }
+}
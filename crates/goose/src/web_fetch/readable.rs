@@ -0,0 +1,520 @@
+//! The readable part of a web page, as Markdown
+//!
+//! Pages carry far more than what they say: scripts, styles, navigation, banners and footers. The
+//! page's `main` element, or else its first `article` or its `body`, is taken to be what it says.
+//! Within that, elements that are never the content, like `nav` and `aside`, are left out, and
+//! the rest becomes Markdown with its headings, lists, links and code kept.
+use url::Url;
+
+/// Elements whose contents are never what a page says
+const SKIPPED: [&str; 15] = [
+    "script", "style", "noscript", "template", "svg", "canvas", "nav", "header", "footer", "aside",
+    "form", "iframe", "button", "select", "head",
+];
+
+/// Elements with no contents or closing tag
+const VOID: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr", "param",
+];
+
+/// Elements that start a paragraph of their own
+const BLOCKS: [&str; 18] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "table",
+    "tr",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "blockquote",
+    "figure",
+    "figcaption",
+    "address",
+    "details",
+    "summary",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadablePage {
+    pub title: Option<String>,
+    pub markdown: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Open { name: String, attributes: &'a str },
+    Close(String),
+    Text(&'a str),
+}
+
+/// The readable text of the page `html`, with links made absolute against `base`
+pub fn readable(html: &str, base: &Url) -> ReadablePage {
+    let tokens = tokenize(html);
+    let title = title(&tokens);
+    let content = ["main", "article", "body"]
+        .iter()
+        .find_map(|name| element(&tokens, name))
+        .unwrap_or(&tokens[..]);
+
+    let mut markdown = Markdown::new(base);
+    for token in content {
+        markdown.push(token);
+    }
+    ReadablePage {
+        title,
+        markdown: markdown.finish(),
+    }
+}
+
+/// Split `html` into tags and the text between them, dropping comments and the source of
+/// scripts and styles
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            tokens.push(Token::Text(rest));
+            return tokens;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim().to_ascii_lowercase()));
+            continue;
+        }
+        let name_len = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let name = tag[..name_len].to_ascii_lowercase();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            // A < that doesn't start a tag, as in `a < b`
+            tokens.push(Token::Text("<"));
+            tokens.push(Token::Text(tag));
+            tokens.push(Token::Text(">"));
+            continue;
+        }
+        tokens.push(Token::Open {
+            name: name.clone(),
+            attributes: &tag[name_len..],
+        });
+
+        // The source of a script or style isn't markup, and can contain what looks like it
+        if name == "script" || name == "style" {
+            let close = format!("</{}", name);
+            let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            rest = &rest[end..];
+        }
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    tokens
+}
+
+/// The page's title, from its `title` element
+fn title(tokens: &[Token]) -> Option<String> {
+    let title: String = element(tokens, "title")?
+        .iter()
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(decode_entities(text)),
+            _ => None,
+        })
+        .collect();
+    let title = collapse_whitespace(&title).trim().to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+/// The contents of the first element named `name`
+fn element<'t, 'a>(tokens: &'t [Token<'a>], name: &str) -> Option<&'t [Token<'a>]> {
+    let start = tokens
+        .iter()
+        .position(|token| matches!(token, Token::Open { name: open, .. } if open == name))?;
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start + 1) {
+        match token {
+            Token::Open { name: open, .. } if open == name => depth += 1,
+            Token::Close(close) if close == name => {
+                if depth == 0 {
+                    return Some(&tokens[start + 1..i]);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    Some(&tokens[start + 1..])
+}
+
+/// Markdown written as the tokens of a page come
+struct Markdown<'u> {
+    base: &'u Url,
+    out: String,
+    /// The skipped elements the tokens are in
+    skipping: Vec<String>,
+    /// The links the tokens are in, with where their text starts
+    links: Vec<(Option<String>, usize)>,
+    /// The lists the tokens are in, whether each is numbered and its items so far
+    lists: Vec<(bool, usize)>,
+    /// How many `pre` elements the tokens are in, where whitespace is kept
+    preformatted: usize,
+}
+
+impl<'u> Markdown<'u> {
+    fn new(base: &'u Url) -> Self {
+        Self {
+            base,
+            out: String::new(),
+            skipping: Vec::new(),
+            links: Vec::new(),
+            lists: Vec::new(),
+            preformatted: 0,
+        }
+    }
+
+    fn push(&mut self, token: &Token) {
+        match token {
+            Token::Open { name, attributes } => {
+                if !self.skipping.is_empty() || SKIPPED.contains(&name.as_str()) {
+                    if !VOID.contains(&name.as_str()) && !attributes.trim_end().ends_with('/') {
+                        self.skipping.push(name.clone());
+                    }
+                    return;
+                }
+                self.open(name, attributes);
+            }
+            Token::Close(name) => {
+                if let Some(i) = self.skipping.iter().rposition(|open| open == name) {
+                    self.skipping.truncate(i);
+                    return;
+                }
+                if self.skipping.is_empty() {
+                    self.close(name);
+                }
+            }
+            Token::Text(text) => {
+                if self.skipping.is_empty() {
+                    self.text(text);
+                }
+            }
+        }
+    }
+
+    fn open(&mut self, name: &str, attributes: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.paragraph();
+                let level = name[1..].parse().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+            }
+            "br" => self.out.push('\n'),
+            "hr" => {
+                self.paragraph();
+                self.out.push_str("---");
+                self.paragraph();
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.paragraph();
+                }
+                self.lists.push((name == "ol", 0));
+            }
+            "li" => {
+                self.line();
+                let depth = self.lists.len().saturating_sub(1);
+                self.out.push_str(&"  ".repeat(depth));
+                match self.lists.last_mut() {
+                    Some((true, items)) => {
+                        *items += 1;
+                        self.out.push_str(&format!("{}. ", items));
+                    }
+                    _ => self.out.push_str("- "),
+                }
+            }
+            "pre" => {
+                self.paragraph();
+                self.out.push_str("```\n");
+                self.preformatted += 1;
+            }
+            "code" if self.preformatted == 0 => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" | "i" => self.out.push('*'),
+            "a" => {
+                let href = attribute(attributes, "href")
+                    .filter(|href| !href.starts_with('#') && !href.starts_with("javascript:"))
+                    .and_then(|href| self.base.join(&href).ok())
+                    .map(String::from);
+                if href.is_some() {
+                    self.out.push('[');
+                }
+                self.links.push((href, self.out.len()));
+            }
+            "td" | "th" => self.out.push(' '),
+            name if BLOCKS.contains(&name) => self.paragraph(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.paragraph(),
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.paragraph();
+                }
+            }
+            "pre" => {
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                self.out.push_str("```");
+                self.preformatted = self.preformatted.saturating_sub(1);
+                self.paragraph();
+            }
+            "code" if self.preformatted == 0 => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" | "i" => self.out.push('*'),
+            "a" => match self.links.pop() {
+                // A link around nothing, like an image, says nothing
+                Some((Some(_), start)) if self.out[start..].trim().is_empty() => {
+                    self.out.truncate(start - 1);
+                }
+                Some((Some(href), _)) => self.out.push_str(&format!("]({})", href)),
+                _ => {}
+            },
+            name if BLOCKS.contains(&name) => self.paragraph(),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = decode_entities(text);
+        if self.preformatted > 0 {
+            self.out.push_str(&text);
+            return;
+        }
+        let text = collapse_whitespace(&text);
+        if self.out.is_empty() || self.out.ends_with(['\n', ' ']) {
+            self.out.push_str(text.trim_start());
+        } else {
+            self.out.push_str(&text);
+        }
+    }
+
+    /// Start a new line, unless on one already
+    fn line(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    /// Start a new paragraph, unless at the start of one already
+    fn paragraph(&mut self) {
+        self.line();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut markdown = String::new();
+        let mut blank = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank += 1;
+                continue;
+            }
+            if !markdown.is_empty() {
+                markdown.push_str(if blank > 0 { "\n\n" } else { "\n" });
+            }
+            markdown.push_str(line);
+            blank = 0;
+        }
+        markdown
+    }
+}
+
+/// The value of the attribute `name` among a tag's `attributes`
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let key_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let key = &rest[..key_len];
+        rest = rest[key_len..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, after) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = value[1..].find(quote).map_or(value.len(), |end| end + 1);
+                        (&value[1..end], value.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = after;
+                value
+            }
+            None => "",
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+    }
+}
+
+/// Runs of whitespace as single spaces
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            collapsed.push(' ');
+            space = false;
+        }
+        collapsed.push(c);
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// `text` with its character references replaced by the characters they stand for
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end + 1])?, end + 2)));
+        match reference {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The character a reference like `amp` or `#39` names
+fn entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "middot" => '·',
+        "bull" => '•',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readable() {
+        let html = r##"<!DOCTYPE html>
+            <html><head><title>Release notes &mdash; Goose</title>
+            <style>body { color: red; }</style>
+            <script>if (a < b) { document.write("<p>nope</p>"); }</script></head>
+            <body>
+              <nav><a href="/">Home</a> <a href="/docs">Docs</a></nav>
+              <main>
+                <h1>Release   notes</h1>
+                <!-- the newest first -->
+                <p>Goose now <strong>fetches</strong> pages, see
+                  <a href="guide.html#fetch">the guide</a> &amp; <a href="#top">top</a>.</p>
+                <ul><li>Readable text</li><li>Robots &lt;respected&gt;</li></ul>
+                <pre><code>goose run
+  --text hi</code></pre>
+                <aside>Subscribe to our newsletter</aside>
+              </main>
+              <footer>&copy; 2025</footer>
+            </body></html>"##;
+        let base = Url::parse("https://example.com/notes/index.html").unwrap();
+        let page = readable(html, &base);
+        assert_eq!(page.title.as_deref(), Some("Release notes — Goose"));
+        assert_eq!(
+            page.markdown,
+            "# Release notes\n\n\
+            Goose now **fetches** pages, see [the guide](https://example.com/notes/guide.html#fetch) & top.\n\n\
+            - Readable text\n\
+            - Robots <respected>\n\n\
+            ```\ngoose run\n  --text hi\n```"
+        );
+    }
+
+    #[test]
+    fn test_attribute() {
+        let attributes = r#" class="x" data-empty href='/a?b=1&amp;c=2' target=_blank"#;
+        assert_eq!(attribute(attributes, "href").as_deref(), Some("/a?b=1&c=2"));
+        assert_eq!(attribute(attributes, "target").as_deref(), Some("_blank"));
+        assert_eq!(attribute(attributes, "data-empty").as_deref(), Some(""));
+        assert_eq!(attribute(attributes, "id"), None);
+    }
+}
@@ -0,0 +1,140 @@
+//! What a site's `robots.txt` lets Goose fetch
+//!
+//! The rules of the group naming Goose's agent are followed, or else those of the `*` group. The
+//! longest rule matching a path decides, an `Allow` winning a tie, and `*` and a closing `$` in
+//! rules match as they do for search engines. A site without one lets everything be fetched.
+
+/// The name Goose goes by in `robots.txt`
+pub const ROBOTS_AGENT: &str = "goose";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots {
+    /// Whether each rule allows the paths it matches, and its pattern
+    rules: Vec<(bool, String)>,
+}
+
+impl Robots {
+    /// The rules of `robots_txt` for `agent`
+    pub fn parse(robots_txt: &str, agent: &str) -> Self {
+        let agent = agent.to_lowercase();
+        let mut named: Option<Vec<(bool, String)>> = None;
+        let mut anyone: Option<Vec<(bool, String)>> = None;
+
+        // The agents of the group being read, and whether its rules have started
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                rule @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    // An empty Disallow allows everything, which is what no rule does
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (rule == "allow", value.to_string());
+                    if agents
+                        .iter()
+                        .any(|name| agent.contains(name.as_str()) && name != "*")
+                    {
+                        named.get_or_insert_with(Vec::new).push(rule.clone());
+                    }
+                    if agents.iter().any(|name| name == "*") {
+                        anyone.get_or_insert_with(Vec::new).push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            rules: named.or(anyone).unwrap_or_default(),
+        }
+    }
+
+    /// Whether `path`, with its query, may be fetched
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Whether the rule `pattern` matches the start of `path`
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        // Anchored, the last part has to be at the very end
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS_TXT: &str = "
+        # Everyone
+        User-agent: *
+        Disallow: /private/
+        Allow: /private/press$
+        Disallow: /*.pdf$
+
+        User-agent: Googlebot
+        User-agent: goose
+        Disallow: /search
+        Allow: /search/about
+    ";
+
+    #[test]
+    fn test_rules_for_anyone() {
+        let robots = Robots::parse(ROBOTS_TXT, "SomeBot");
+        assert!(robots.allows("/docs/intro"));
+        assert!(!robots.allows("/private/keys"));
+        assert!(robots.allows("/private/press"));
+        assert!(!robots.allows("/private/press/2024"));
+        assert!(!robots.allows("/papers/goose.pdf"));
+        assert!(robots.allows("/papers/goose.pdf?download=1"));
+        assert!(robots.allows("/search?q=goose"));
+    }
+
+    #[test]
+    fn test_rules_for_goose() {
+        let robots = Robots::parse(ROBOTS_TXT, ROBOTS_AGENT);
+        assert!(!robots.allows("/search?q=goose"));
+        assert!(robots.allows("/search/about"));
+        // Only the rules naming Goose apply to it
+        assert!(robots.allows("/private/keys"));
+
+        assert!(Robots::parse("", ROBOTS_AGENT).allows("/anything"));
+        assert!(Robots::parse("User-agent: *\nDisallow:\n", ROBOTS_AGENT).allows("/"));
+    }
+}
@@ -12,6 +12,7 @@ use goose::providers::{
     groq::GroqProvider, ollama::OllamaProvider, openai::OpenAiProvider,
     openrouter::OpenRouterProvider,
 };
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, PartialEq)]
 enum ProviderType {
@@ -123,7 +124,9 @@ async fn run_truncate_test(
         ),
     ];
 
-    let reply_stream = agent.reply(&messages, None).await?;
+    let reply_stream = agent
+        .reply(&messages, None, CancellationToken::new())
+        .await?;
     tokio::pin!(reply_stream);
 
     let mut responses = Vec::new();
@@ -63,9 +63,14 @@ pub struct ClientInfo {
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct ClientCapabilities {
-    // Add fields as needed. For now, empty capabilities are fine.
+    /// Set when the client answers `sampling/createMessage` requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingCapability>,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct SamplingCapability {}
+
 #[derive(Serialize, Deserialize)]
 pub struct InitializeParams {
     #[serde(rename = "protocolVersion")]
@@ -94,9 +99,62 @@ pub trait McpClientTrait: Send + Sync {
 
     async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, Error>;
 
+    /// Call a tool, asking the server to send `notifications/progress` with `progress_token`
+    /// while it runs
+    async fn call_tool_with_progress(
+        &self,
+        name: &str,
+        arguments: Value,
+        progress_token: &str,
+    ) -> Result<CallToolResult, Error> {
+        let _ = progress_token;
+        self.call_tool(name, arguments).await
+    }
+
     async fn list_prompts(&self, next_cursor: Option<String>) -> Result<ListPromptsResult, Error>;
 
     async fn get_prompt(&self, name: &str, arguments: Value) -> Result<GetPromptResult, Error>;
+
+    /// Check that the server still answers requests
+    async fn ping(&self) -> Result<(), Error>;
+}
+
+/// Sends `notifications/cancelled` for a request when dropped, unless its response came
+struct CancelOnDrop<S>
+where
+    S: Service<JsonRpcMessage, Response = JsonRpcMessage> + Send + 'static,
+    S::Future: Send,
+{
+    service: Option<S>,
+    id: u64,
+}
+
+impl<S> Drop for CancelOnDrop<S>
+where
+    S: Service<JsonRpcMessage, Response = JsonRpcMessage> + Send + 'static,
+    S::Future: Send,
+{
+    fn drop(&mut self) {
+        let Some(mut service) = self.service.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let notification = JsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/cancelled".to_string(),
+            params: Some(serde_json::json!({
+                "requestId": self.id,
+                "reason": "The client is no longer waiting for the response",
+            })),
+        });
+        runtime.spawn(async move {
+            if service.ready().await.is_ok() {
+                let _ = service.call(notification).await;
+            }
+        });
+    }
 }
 
 /// The MCP client is the interface for MCP operations.
@@ -128,11 +186,14 @@ where
     }
 
     /// Send a JSON-RPC request and check we don't get an error response.
+    ///
+    /// Requests can be in flight at the same time. If the returned future is dropped before the
+    /// response comes, or the request times out, the server is told to cancel it.
     async fn send_request<R>(&self, method: &str, params: Value) -> Result<R, Error>
     where
         R: for<'de> Deserialize<'de>,
     {
-        let mut service = self.service.lock().await;
+        let mut service = self.service.lock().await.clone();
         service.ready().await.map_err(|_| Error::NotReady)?;
 
         let id = self.next_id.fetch_add(1, Ordering::SeqCst);
@@ -143,6 +204,10 @@ where
             params: Some(params.clone()),
         });
 
+        let mut cancel_on_drop = CancelOnDrop {
+            service: Some(service.clone()),
+            id,
+        };
         let response_msg = service
             .call(request)
             .await
@@ -156,13 +221,17 @@ where
                 // we don't need include params because it can be really large
                 source: Box::new(e.into()),
             })?;
+        cancel_on_drop.service = None;
 
         match response_msg {
             JsonRpcMessage::Response(JsonRpcResponse {
-                id, result, error, ..
+                id: response_id,
+                result,
+                error,
+                ..
             }) => {
                 // Verify id matches
-                if id != Some(self.next_id.load(Ordering::SeqCst) - 1) {
+                if response_id != Some(id) {
                     return Err(Error::UnexpectedResponse(
                         "id mismatch for JsonRpcResponse".to_string(),
                     ));
@@ -178,8 +247,12 @@ where
                     Err(Error::UnexpectedResponse("missing result".to_string()))
                 }
             }
-            JsonRpcMessage::Error(JsonRpcError { id, error, .. }) => {
-                if id != Some(self.next_id.load(Ordering::SeqCst) - 1) {
+            JsonRpcMessage::Error(JsonRpcError {
+                id: response_id,
+                error,
+                ..
+            }) => {
+                if response_id != Some(id) {
                     return Err(Error::UnexpectedResponse(
                         "id mismatch for JsonRpcError".to_string(),
                     ));
@@ -351,6 +424,30 @@ where
         self.send_request("tools/call", params).await
     }
 
+    async fn call_tool_with_progress(
+        &self,
+        name: &str,
+        arguments: Value,
+        progress_token: &str,
+    ) -> Result<CallToolResult, Error> {
+        if !self.completed_initialization() {
+            return Err(Error::NotInitialized);
+        }
+        if self.server_capabilities.as_ref().unwrap().tools.is_none() {
+            return Err(Error::RpcError {
+                code: METHOD_NOT_FOUND,
+                message: "Server does not support 'tools' capability".to_string(),
+            });
+        }
+
+        let params = serde_json::json!({
+            "name": name,
+            "arguments": arguments,
+            "_meta": { "progressToken": progress_token },
+        });
+        self.send_request("tools/call", params).await
+    }
+
     async fn list_prompts(&self, next_cursor: Option<String>) -> Result<ListPromptsResult, Error> {
         if !self.completed_initialization() {
             return Err(Error::NotInitialized);
@@ -388,4 +485,11 @@ where
 
         self.send_request("prompts/get", params).await
     }
+
+    async fn ping(&self) -> Result<(), Error> {
+        // Servers answer with an empty result, whatever their capabilities
+        self.send_request::<Value>("ping", serde_json::json!({}))
+            .await
+            .map(|_| ())
+    }
 }
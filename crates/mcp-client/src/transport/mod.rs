@@ -1,6 +1,11 @@
 use async_trait::async_trait;
-use mcp_core::protocol::JsonRpcMessage;
+use mcp_core::protocol::{
+    ErrorData, JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, METHOD_NOT_FOUND,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client as HttpClient;
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot, RwLock};
 
@@ -29,6 +34,9 @@ pub enum Error {
     #[error("SSE connection error: {0}")]
     SseConnection(String),
 
+    #[error("Streamable HTTP error: {0}")]
+    StreamableHttp(String),
+
     #[error("HTTP error: {status} - {message}")]
     HttpError { status: u16, message: String },
 }
@@ -60,6 +68,47 @@ pub trait TransportHandle: Send + Sync + Clone + 'static {
     async fn send(&self, message: JsonRpcMessage) -> Result<JsonRpcMessage, Error>;
 }
 
+/// Answers the requests a server sends the client, such as `sampling/createMessage`, and hears
+/// its notifications, such as `notifications/progress`
+#[async_trait]
+pub trait RequestHandler: Send + Sync {
+    /// The response or error to send back for `request`
+    async fn handle(&self, request: JsonRpcRequest) -> JsonRpcMessage;
+
+    /// Hear a notification from the server, which is ignored unless overridden
+    async fn notify(&self, _notification: JsonRpcNotification) {}
+}
+
+/// The answer to a request from the server, from `handler` or else an error saying the client
+/// doesn't support its method, so that the server isn't left waiting
+pub(crate) async fn answer_request(
+    handler: Option<Arc<dyn RequestHandler>>,
+    request: JsonRpcRequest,
+) -> JsonRpcMessage {
+    match handler {
+        Some(handler) => handler.handle(request).await,
+        None => JsonRpcMessage::Error(JsonRpcError {
+            jsonrpc: "2.0".to_string(),
+            id: request.id,
+            error: ErrorData {
+                code: METHOD_NOT_FOUND,
+                message: format!("The client doesn't support {}", request.method),
+                data: None,
+            },
+        }),
+    }
+}
+
+/// Pass a notification from the server to `handler`, if there is one
+pub(crate) async fn forward_notification(
+    handler: Option<Arc<dyn RequestHandler>>,
+    notification: JsonRpcNotification,
+) {
+    if let Some(handler) = handler {
+        handler.notify(notification).await;
+    }
+}
+
 // Helper function that contains the common send implementation
 pub async fn send_message(
     sender: &mpsc::Sender<TransportMessage>,
@@ -115,13 +164,36 @@ impl PendingRequests {
         }
     }
 
+    pub async fn is_pending(&self, id: &str) -> bool {
+        self.requests.read().await.contains_key(id)
+    }
+
     pub async fn clear(&self) {
         self.requests.write().await.clear();
     }
 }
 
+/// An HTTP client that sends `headers` with each request, for the transports over HTTP
+pub(crate) fn http_client(headers: &HashMap<String, String>) -> Result<HttpClient, String> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("Invalid header {}: {}", name, e))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| format!("Invalid value for header {}: {}", name, e))?;
+        header_map.insert(name, value);
+    }
+    HttpClient::builder()
+        .default_headers(header_map)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
 pub mod stdio;
 pub use stdio::StdioTransport;
 
 pub mod sse;
 pub use sse::SseTransport;
+
+pub mod streamable_http;
+pub use streamable_http::StreamableHttpTransport;
@@ -1,6 +1,6 @@
 use crate::transport::{Error, PendingRequests, TransportMessage};
 use async_trait::async_trait;
-use eventsource_client::{Client, SSE};
+use eventsource_client::{Client, ReconnectOptions, SSE};
 use futures::TryStreamExt;
 use mcp_core::protocol::{JsonRpcMessage, JsonRpcRequest};
 use reqwest::Client as HttpClient;
@@ -11,13 +11,22 @@ use tokio::time::{timeout, Duration};
 use tracing::warn;
 use url::Url;
 
-use super::{send_message, Transport, TransportHandle};
+use super::{
+    answer_request, forward_notification, send_message, RequestHandler, Transport, TransportHandle,
+};
 
 // Timeout for the endpoint discovery
 const ENDPOINT_TIMEOUT_SECS: u64 = 5;
 
+// Wait before reconnecting a dropped stream, doubled after each attempt that fails
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// Attempts in a row that fail before the transport stops reconnecting
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
 /// The SSE-based actor that continuously:
-/// - Reads incoming events from the SSE stream.
+/// - Reads incoming events from the SSE stream, reconnecting when it drops.
 /// - Sends outgoing messages via HTTP POST (once the post endpoint is known).
 pub struct SseActor {
     /// Receives messages (requests/notifications) from the handle
@@ -26,10 +35,17 @@ pub struct SseActor {
     pending_requests: Arc<PendingRequests>,
     /// Base SSE URL
     sse_url: String,
+    /// Headers sent with the SSE request, which the HTTP client also sends with each POST
+    headers: HashMap<String, String>,
     /// For sending HTTP POST requests
     http_client: HttpClient,
     /// The discovered endpoint for POST requests (once "endpoint" SSE event arrives)
     post_endpoint: Arc<RwLock<Option<String>>>,
+    /// The initialize request and notification, sent again to the new session of a reconnected
+    /// stream
+    handshake: Arc<RwLock<Vec<JsonRpcMessage>>>,
+    /// Answers the requests the server sends on the stream
+    request_handler: Option<Arc<dyn RequestHandler>>,
 }
 
 impl SseActor {
@@ -43,70 +59,155 @@ impl SseActor {
             receiver,
             pending_requests,
             sse_url,
+            headers: HashMap::new(),
             post_endpoint,
             http_client: HttpClient::new(),
+            handshake: Arc::new(RwLock::new(Vec::new())),
+            request_handler: None,
         }
     }
 
-    /// The main entry point for the actor. Spawns two concurrent loops:
+    /// Answer the requests the server sends with `handler`
+    pub fn with_request_handler(mut self, handler: Option<Arc<dyn RequestHandler>>) -> Self {
+        self.request_handler = handler;
+        self
+    }
+
+    /// Send `headers` with the SSE request, and with each POST through `http_client`
+    pub fn with_headers(
+        mut self,
+        headers: HashMap<String, String>,
+        http_client: HttpClient,
+    ) -> Self {
+        self.headers = headers;
+        self.http_client = http_client;
+        self
+    }
+
+    /// The main entry point for the actor. Runs two concurrent loops, until either ends:
     /// 1) handle_incoming_messages (SSE events)
     /// 2) handle_outgoing_messages (sending messages via POST)
     pub async fn run(self) {
-        tokio::join!(
-            Self::handle_incoming_messages(
+        tokio::select!(
+            _ = Self::handle_incoming_messages(
                 self.sse_url.clone(),
+                self.headers.clone(),
+                self.http_client.clone(),
                 Arc::clone(&self.pending_requests),
-                Arc::clone(&self.post_endpoint)
-            ),
-            Self::handle_outgoing_messages(
+                Arc::clone(&self.post_endpoint),
+                Arc::clone(&self.handshake),
+                self.request_handler.clone(),
+            ) => {}
+            _ = Self::handle_outgoing_messages(
                 self.receiver,
                 self.http_client.clone(),
                 Arc::clone(&self.post_endpoint),
                 Arc::clone(&self.pending_requests),
-            )
+                Arc::clone(&self.handshake),
+            ) => {}
         );
     }
 
-    /// Continuously reads SSE events from `sse_url`.
-    /// - If an `endpoint` event is received, store it in `post_endpoint`.
-    /// - If a `message` event is received, parse it as `JsonRpcMessage`
-    ///   and respond to pending requests if it's a `Response`.
+    /// Reads SSE events from `sse_url`, connecting again with backoff whenever the stream
+    /// ends, until [`MAX_RECONNECT_ATTEMPTS`] in a row fail.
     async fn handle_incoming_messages(
         sse_url: String,
+        headers: HashMap<String, String>,
+        http_client: HttpClient,
         pending_requests: Arc<PendingRequests>,
         post_endpoint: Arc<RwLock<Option<String>>>,
+        handshake: Arc<RwLock<Vec<JsonRpcMessage>>>,
+        request_handler: Option<Arc<dyn RequestHandler>>,
     ) {
-        let client = match eventsource_client::ClientBuilder::for_url(&sse_url) {
-            Ok(builder) => builder.build(),
+        let mut failures = 0;
+        loop {
+            let connected = Self::read_stream(
+                &sse_url,
+                &headers,
+                &http_client,
+                &pending_requests,
+                &post_endpoint,
+                &handshake,
+                &request_handler,
+            )
+            .await;
+
+            // Requests sent on the lost stream are never answered, and messages can't be
+            // posted until the next stream gives its endpoint
+            *post_endpoint.write().await = None;
+            pending_requests.clear().await;
+
+            failures = if connected { 0 } else { failures + 1 };
+            if failures >= MAX_RECONNECT_ATTEMPTS {
+                tracing::error!(
+                    "SSE stream of {} failed {} times in a row; giving up.",
+                    sse_url,
+                    failures
+                );
+                return;
+            }
+            let delay = reconnect_delay(failures);
+            warn!(
+                "SSE stream of {} ended; reconnecting in {:?}.",
+                sse_url, delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Reads the events of one SSE stream until it ends or errors, returning whether it got as
+    /// far as the endpoint.
+    /// - If an `endpoint` event is received, store it in `post_endpoint`, after sending the
+    ///   handshake again when this stream replaces one that dropped.
+    /// - If a `message` event is received, parse it as `JsonRpcMessage`
+    ///   and respond to pending requests if it's a `Response`.
+    async fn read_stream(
+        sse_url: &str,
+        headers: &HashMap<String, String>,
+        http_client: &HttpClient,
+        pending_requests: &PendingRequests,
+        post_endpoint: &RwLock<Option<String>>,
+        handshake: &RwLock<Vec<JsonRpcMessage>>,
+        request_handler: &Option<Arc<dyn RequestHandler>>,
+    ) -> bool {
+        let client = match sse_client(sse_url, headers) {
+            Ok(client) => client,
             Err(e) => {
-                pending_requests.clear().await;
                 warn!("Failed to connect SSE client: {}", e);
-                return;
+                return false;
             }
         };
         let mut stream = client.stream();
+        let mut connected = false;
 
-        // First, wait for the "endpoint" event
         while let Ok(Some(event)) = stream.try_next().await {
             match event {
                 SSE::Event(e) if e.event_type == "endpoint" => {
                     // SSE server uses the "endpoint" event to tell us the POST URL
-                    let base_url = Url::parse(&sse_url).expect("Invalid base URL");
-                    let post_url = base_url
-                        .join(&e.data)
-                        .expect("Failed to resolve endpoint URL");
-
+                    let post_url = match Url::parse(sse_url).and_then(|base| base.join(&e.data)) {
+                        Ok(url) => url.to_string(),
+                        Err(err) => {
+                            warn!("Failed to resolve SSE endpoint URL {}: {}", e.data, err);
+                            return connected;
+                        }
+                    };
                     tracing::debug!("Discovered SSE POST endpoint: {}", post_url);
-                    *post_endpoint.write().await = Some(post_url.to_string());
-                    break;
-                }
-                _ => continue,
-            }
-        }
 
-        // Now handle subsequent events
-        while let Ok(Some(event)) = stream.try_next().await {
-            match event {
+                    // A reconnected stream is a new session on the server, which has to be
+                    // initialized before it takes other requests
+                    let handshake = handshake.read().await.clone();
+                    if !handshake.is_empty() {
+                        tracing::info!("Reconnected to {}; initializing the new session.", sse_url);
+                    }
+                    for message in &handshake {
+                        if let Err(e) = post_message(http_client, &post_url, message).await {
+                            warn!("Failed to send the handshake again: {e}");
+                        }
+                    }
+
+                    *post_endpoint.write().await = Some(post_url);
+                    connected = true;
+                }
                 SSE::Event(e) if e.event_type == "message" => {
                     // Attempt to parse the SSE data as a JsonRpcMessage
                     match serde_json::from_str::<JsonRpcMessage>(&e.data) {
@@ -126,7 +227,32 @@ impl SseActor {
                                             .await;
                                     }
                                 }
-                                _ => {} // TODO: Handle other variants (Request, etc.)
+                                JsonRpcMessage::Request(request) => {
+                                    // Answered in a task of its own, so the stream goes on
+                                    // being read while the answer is worked out
+                                    let Some(post_url) = post_endpoint.read().await.clone() else {
+                                        continue;
+                                    };
+                                    let request = request.clone();
+                                    let handler = request_handler.clone();
+                                    let http_client = http_client.clone();
+                                    tokio::spawn(async move {
+                                        let response = answer_request(handler, request).await;
+                                        if let Err(e) =
+                                            post_message(&http_client, &post_url, &response).await
+                                        {
+                                            warn!("Failed to answer the server's request: {e}");
+                                        }
+                                    });
+                                }
+                                JsonRpcMessage::Notification(notification) => {
+                                    forward_notification(
+                                        request_handler.clone(),
+                                        notification.clone(),
+                                    )
+                                    .await;
+                                }
+                                _ => {}
                             }
                         }
                         Err(err) => {
@@ -137,20 +263,19 @@ impl SseActor {
                 _ => { /* ignore other events */ }
             }
         }
-
-        // SSE stream ended or errored; signal any pending requests
-        tracing::error!("SSE stream ended or encountered an error; clearing pending requests.");
-        pending_requests.clear().await;
+        connected
     }
 
     /// Continuously receives messages from the `mpsc::Receiver`.
     /// - If it's a request, store the oneshot in `pending_requests`.
+    /// - If it's part of the handshake, keep it to send again after reconnecting.
     /// - POST the message to the discovered endpoint (once known).
     async fn handle_outgoing_messages(
         mut receiver: mpsc::Receiver<TransportMessage>,
         http_client: HttpClient,
         post_endpoint: Arc<RwLock<Option<String>>>,
         pending_requests: Arc<PendingRequests>,
+        handshake: Arc<RwLock<Vec<JsonRpcMessage>>>,
     ) {
         while let Some(transport_msg) = receiver.recv().await {
             let post_url = match post_endpoint.read().await.as_ref() {
@@ -163,17 +288,6 @@ impl SseActor {
                 }
             };
 
-            // Serialize the JSON-RPC message
-            let message_str = match serde_json::to_string(&transport_msg.message) {
-                Ok(s) => s,
-                Err(e) => {
-                    if let Some(tx) = transport_msg.response_tx {
-                        let _ = tx.send(Err(Error::Serialization(e)));
-                    }
-                    continue;
-                }
-            };
-
             // If it's a request, store the channel so we can respond later
             if let Some(response_tx) = transport_msg.response_tx {
                 if let JsonRpcMessage::Request(JsonRpcRequest { id: Some(id), .. }) =
@@ -183,29 +297,23 @@ impl SseActor {
                 }
             }
 
-            // Perform the HTTP POST
-            match http_client
-                .post(&post_url)
-                .header("Content-Type", "application/json")
-                .body(message_str)
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        let err = Error::HttpError {
-                            status: resp.status().as_u16(),
-                            message: resp.status().to_string(),
-                        };
-                        warn!("HTTP request returned error: {err}");
-                        // This doesn't directly fail the request,
-                        // because we rely on SSE to deliver the error response
-                    }
+            match &transport_msg.message {
+                JsonRpcMessage::Request(request) if request.method == "initialize" => {
+                    *handshake.write().await = vec![transport_msg.message.clone()];
                 }
-                Err(e) => {
-                    warn!("HTTP POST failed: {e}");
-                    // Similarly, SSE might eventually reveal the error
+                JsonRpcMessage::Notification(notification)
+                    if notification.method == "notifications/initialized" =>
+                {
+                    handshake.write().await.push(transport_msg.message.clone());
                 }
+                _ => {}
+            }
+
+            // Perform the HTTP POST
+            if let Err(e) = post_message(&http_client, &post_url, &transport_msg.message).await {
+                warn!("HTTP POST failed: {e}");
+                // This doesn't directly fail the request,
+                // because we rely on SSE to deliver the error response
             }
         }
 
@@ -215,6 +323,49 @@ impl SseActor {
     }
 }
 
+/// An SSE client for `sse_url` sending `headers`, which leaves reconnecting to the actor
+fn sse_client(
+    sse_url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<impl Client, eventsource_client::Error> {
+    let mut builder = eventsource_client::ClientBuilder::for_url(sse_url)?
+        .reconnect(ReconnectOptions::reconnect(false).build());
+    for (name, value) in headers {
+        builder = builder.header(name, value)?;
+    }
+    Ok(builder.build())
+}
+
+/// POST `message` to the endpoint, failing on a response that isn't a success
+async fn post_message(
+    http_client: &HttpClient,
+    post_url: &str,
+    message: &JsonRpcMessage,
+) -> Result<(), Error> {
+    let message_str = serde_json::to_string(message)?;
+    let resp = http_client
+        .post(post_url)
+        .header("Content-Type", "application/json")
+        .body(message_str)
+        .send()
+        .await
+        .map_err(|e| Error::SseConnection(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(Error::HttpError {
+            status: resp.status().as_u16(),
+            message: resp.status().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// How long to wait before reconnecting after `failures` attempts in a row failed
+fn reconnect_delay(failures: u32) -> Duration {
+    RECONNECT_DELAY
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_RECONNECT_DELAY)
+}
+
 #[derive(Clone)]
 pub struct SseTransportHandle {
     sender: mpsc::Sender<TransportMessage>,
@@ -231,6 +382,8 @@ impl TransportHandle for SseTransportHandle {
 pub struct SseTransport {
     sse_url: String,
     env: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    request_handler: Option<Arc<dyn RequestHandler>>,
 }
 
 /// The SSE transport spawns an `SseActor` on `start()`.
@@ -239,9 +392,23 @@ impl SseTransport {
         Self {
             sse_url: sse_url.into(),
             env,
+            headers: HashMap::new(),
+            request_handler: None,
         }
     }
 
+    /// Send `headers`, such as `Authorization`, with the SSE request and each message
+    pub fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Answer the requests the server sends with `handler`
+    pub fn with_request_handler(mut self, handler: Arc<dyn RequestHandler>) -> Self {
+        self.request_handler = Some(handler);
+        self
+    }
+
     /// Waits for the endpoint to be set, up to 10 attempts.
     async fn wait_for_endpoint(
         post_endpoint: Arc<RwLock<Option<String>>>,
@@ -272,6 +439,8 @@ impl Transport for SseTransport {
             std::env::set_var(key, value);
         }
 
+        let http_client = super::http_client(&self.headers).map_err(Error::SseConnection)?;
+
         // Create a channel for outgoing TransportMessages
         let (tx, rx) = mpsc::channel(32);
 
@@ -284,7 +453,9 @@ impl Transport for SseTransport {
             Arc::new(PendingRequests::new()),
             self.sse_url.clone(),
             post_endpoint,
-        );
+        )
+        .with_headers(self.headers.clone(), http_client)
+        .with_request_handler(self.request_handler.clone());
 
         // Spawn the actor task
         tokio::spawn(actor.run());
@@ -303,7 +474,7 @@ impl Transport for SseTransport {
 
     async fn close(&self) -> Result<(), Error> {
         // For SSE, you might close the stream or send a shutdown signal to the actor.
-        // Here, we do nothing special.
+        // The actor stops once every handle is dropped.
         Ok(())
     }
 }
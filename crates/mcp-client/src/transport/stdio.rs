@@ -7,13 +7,20 @@ use mcp_core::protocol::JsonRpcMessage;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::sync::{mpsc, Mutex};
 
-use super::{send_message, Error, PendingRequests, Transport, TransportHandle, TransportMessage};
+use super::{
+    answer_request, forward_notification, send_message, Error, PendingRequests, RequestHandler,
+    Transport, TransportHandle, TransportMessage,
+};
 
 /// A `StdioTransport` uses a child process's stdin/stdout as a communication channel.
 ///
 /// It uses channels for message passing and handles responses asynchronously through a background task.
 pub struct StdioActor {
     receiver: mpsc::Receiver<TransportMessage>,
+    /// Sends the answers to the server's requests out with the other messages, without keeping
+    /// the channel open once the handles are dropped
+    responder: mpsc::WeakSender<TransportMessage>,
+    request_handler: Option<Arc<dyn RequestHandler>>,
     pending_requests: Arc<PendingRequests>,
     _process: Child, // we store the process to keep it alive
     error_sender: mpsc::Sender<Error>,
@@ -26,7 +33,12 @@ impl StdioActor {
     pub async fn run(mut self) {
         use tokio::pin;
 
-        let incoming = Self::handle_incoming_messages(self.stdout, self.pending_requests.clone());
+        let incoming = Self::handle_incoming_messages(
+            self.stdout,
+            self.pending_requests.clone(),
+            self.request_handler.clone(),
+            self.responder.clone(),
+        );
         let outgoing = Self::handle_outgoing_messages(
             self.receiver,
             self.stdin,
@@ -71,7 +83,12 @@ impl StdioActor {
         self.pending_requests.clear().await;
     }
 
-    async fn handle_incoming_messages(stdout: ChildStdout, pending_requests: Arc<PendingRequests>) {
+    async fn handle_incoming_messages(
+        stdout: ChildStdout,
+        pending_requests: Arc<PendingRequests>,
+        request_handler: Option<Arc<dyn RequestHandler>>,
+        responder: mpsc::WeakSender<TransportMessage>,
+    ) {
         let mut reader = BufReader::new(stdout);
         let mut line = String::new();
         loop {
@@ -98,7 +115,29 @@ impl StdioActor {
                                     pending_requests.respond(&id.to_string(), Ok(message)).await;
                                 }
                             }
-                            _ => {} // TODO: Handle other variants (Request, etc.)
+                            JsonRpcMessage::Request(request) => {
+                                // Answered in a task of its own, as the answer can take a
+                                // completion, while responses keep coming in
+                                let request = request.clone();
+                                let handler = request_handler.clone();
+                                let responder = responder.clone();
+                                tokio::spawn(async move {
+                                    let response = answer_request(handler, request).await;
+                                    if let Some(sender) = responder.upgrade() {
+                                        let _ = sender
+                                            .send(TransportMessage {
+                                                message: response,
+                                                response_tx: None,
+                                            })
+                                            .await;
+                                    }
+                                });
+                            }
+                            JsonRpcMessage::Notification(notification) => {
+                                forward_notification(request_handler.clone(), notification.clone())
+                                    .await;
+                            }
+                            _ => {}
                         }
                     }
                     line.clear();
@@ -188,6 +227,7 @@ pub struct StdioTransport {
     command: String,
     args: Vec<String>,
     env: HashMap<String, String>,
+    request_handler: Option<Arc<dyn RequestHandler>>,
 }
 
 impl StdioTransport {
@@ -200,9 +240,16 @@ impl StdioTransport {
             command: command.into(),
             args,
             env,
+            request_handler: None,
         }
     }
 
+    /// Answer the requests the server sends with `handler`
+    pub fn with_request_handler(mut self, handler: Arc<dyn RequestHandler>) -> Self {
+        self.request_handler = Some(handler);
+        self
+    }
+
     async fn spawn_process(&self) -> Result<(Child, ChildStdin, ChildStdout, ChildStderr), Error> {
         let mut command = Command::new(&self.command);
         command
@@ -255,6 +302,8 @@ impl Transport for StdioTransport {
 
         let actor = StdioActor {
             receiver: message_rx,
+            responder: message_tx.downgrade(),
+            request_handler: self.request_handler.clone(),
             pending_requests: Arc::new(PendingRequests::new()),
             _process: process,
             error_sender: error_tx,
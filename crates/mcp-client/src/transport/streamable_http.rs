@@ -0,0 +1,507 @@
+use crate::transport::{Error, PendingRequests, TransportMessage};
+use async_trait::async_trait;
+use futures::StreamExt;
+use mcp_core::protocol::{JsonRpcMessage, JsonRpcRequest};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client as HttpClient, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::time::Duration;
+use tracing::warn;
+
+use super::{
+    answer_request, forward_notification, send_message, RequestHandler, Transport, TransportHandle,
+};
+
+// The header the server gives the session's id in, which is sent back with each request
+const SESSION_ID_HEADER: &str = "Mcp-Session-Id";
+
+// The header a stream is resumed from, with the id of the last event read
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+// Times a stream that ended before its response is resumed, waiting longer before each
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+const RESUME_DELAY: Duration = Duration::from_millis(500);
+
+/// The connection to the server's single MCP endpoint, shared by the requests in flight
+struct Connection {
+    url: String,
+    http_client: HttpClient,
+    /// Map of request-id -> oneshot sender
+    pending_requests: PendingRequests,
+    /// The session the server assigned in its response to `initialize`, if it keeps sessions
+    session_id: RwLock<Option<String>>,
+    /// The initialize request and notification, sent again when the server ends the session
+    handshake: RwLock<Vec<JsonRpcMessage>>,
+    /// Held while the session is initialized again, so requests that find it ended at the
+    /// same time start one new session between them
+    reinitializing: Mutex<()>,
+    /// Answers the requests the server sends in its responses
+    request_handler: Option<Arc<dyn RequestHandler>>,
+}
+
+impl Connection {
+    /// POST `message`, reading its response from the body, starting a new session and trying
+    /// once more when the server has ended the current one
+    async fn send(&self, message: &JsonRpcMessage) -> Result<(), Error> {
+        let session_id = self.session_id.read().await.clone();
+        let mut response = self.post(message, session_id.as_deref()).await?;
+        if response.status() == StatusCode::NOT_FOUND
+            && session_id.is_some()
+            && !is_initialize(message)
+        {
+            self.reinitialize(session_id.as_deref()).await?;
+            let session_id = self.session_id.read().await.clone();
+            response = self.post(message, session_id.as_deref()).await?;
+        }
+        self.read_response(response, request_id(message)).await
+    }
+
+    async fn post(
+        &self,
+        message: &JsonRpcMessage,
+        session_id: Option<&str>,
+    ) -> Result<Response, Error> {
+        let mut request = self
+            .http_client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json, text/event-stream")
+            .body(serde_json::to_string(message)?);
+        if let Some(session_id) = session_id {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| Error::StreamableHttp(e.to_string()))
+    }
+
+    /// Send the handshake again, unless another request did since the session `stale` ended
+    async fn reinitialize(&self, stale: Option<&str>) -> Result<(), Error> {
+        let _guard = self.reinitializing.lock().await;
+        if self.session_id.read().await.as_deref() != stale {
+            return Ok(());
+        }
+        tracing::info!("Session of {} ended; initializing a new one.", self.url);
+        *self.session_id.write().await = None;
+
+        let handshake = self.handshake.read().await.clone();
+        for message in &handshake {
+            let session_id = self.session_id.read().await.clone();
+            let response = self.post(message, session_id.as_deref()).await?;
+            // The response to the initialize request sent again has no one waiting for it, but
+            // it has to be read for the new session's id
+            self.read_response(response, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Take the session id from `response` and pass on the messages in its body, which is
+    /// empty for a notification, a JSON message or batch, or a stream of events
+    async fn read_response(&self, response: Response, id: Option<String>) -> Result<(), Error> {
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::HttpError {
+                status: status.as_u16(),
+                message: status.to_string(),
+            });
+        }
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            *self.session_id.write().await = Some(session_id.to_string());
+        }
+        if status == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if content_type.starts_with("text/event-stream") {
+            self.read_stream(response, id).await
+        } else {
+            let body = response
+                .bytes()
+                .await
+                .map_err(|e| Error::StreamableHttp(e.to_string()))?;
+            if body.is_empty() {
+                return Ok(());
+            }
+            let messages = match serde_json::from_slice::<Vec<JsonRpcMessage>>(&body) {
+                Ok(messages) => messages,
+                Err(_) => vec![serde_json::from_slice::<JsonRpcMessage>(&body)?],
+            };
+            for message in messages {
+                self.dispatch(message).await;
+            }
+            Ok(())
+        }
+    }
+
+    /// Read the events of `response`, resuming the stream from the last event while the
+    /// request `id` is still waiting for its response, up to [`MAX_RESUME_ATTEMPTS`] times
+    async fn read_stream(&self, response: Response, id: Option<String>) -> Result<(), Error> {
+        let mut last_event_id = None;
+        self.read_events(response, &mut last_event_id).await;
+
+        let Some(id) = id else {
+            return Ok(());
+        };
+        let mut attempts = 0;
+        while self.pending_requests.is_pending(&id).await {
+            // The server only lets a stream be resumed when it gives its events ids
+            let Some(event_id) = last_event_id.clone() else {
+                return Err(Error::StreamableHttp(
+                    "The stream ended before the response".to_string(),
+                ));
+            };
+            if attempts == MAX_RESUME_ATTEMPTS {
+                return Err(Error::StreamableHttp(format!(
+                    "The stream ended before the response, {} times",
+                    attempts + 1
+                )));
+            }
+            attempts += 1;
+            tokio::time::sleep(RESUME_DELAY * attempts).await;
+            match self.resume(&event_id).await {
+                Ok(response) => self.read_events(response, &mut last_event_id).await,
+                Err(e) => warn!("Failed to resume the stream of {}: {}", self.url, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// GET the stream again from the event after `last_event_id`
+    async fn resume(&self, last_event_id: &str) -> Result<Response, Error> {
+        let mut request = self
+            .http_client
+            .get(&self.url)
+            .header(ACCEPT, "text/event-stream")
+            .header(LAST_EVENT_ID_HEADER, last_event_id);
+        if let Some(session_id) = self.session_id.read().await.as_deref() {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::StreamableHttp(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::HttpError {
+                status: response.status().as_u16(),
+                message: response.status().to_string(),
+            });
+        }
+        Ok(response)
+    }
+
+    /// Pass on the messages in the events of `response` until it ends, keeping the id of the
+    /// last event read in `last_event_id`
+    async fn read_events(&self, response: Response, last_event_id: &mut Option<String>) {
+        let mut parser = EventParser::default();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    warn!("Stream of {} failed: {}", self.url, e);
+                    return;
+                }
+            };
+            for event in parser.feed(&chunk) {
+                if event.id.is_some() {
+                    *last_event_id = event.id;
+                }
+                if event.data.is_empty() || !matches!(event.event.as_str(), "" | "message") {
+                    continue;
+                }
+                match serde_json::from_str::<JsonRpcMessage>(&event.data) {
+                    Ok(message) => self.dispatch(message).await,
+                    Err(e) => warn!("Failed to parse streamed message: {e}"),
+                }
+            }
+        }
+    }
+
+    /// Respond to the pending request a response or error is for, or answer a request from the
+    /// server
+    async fn dispatch(&self, message: JsonRpcMessage) {
+        let id = match &message {
+            JsonRpcMessage::Response(response) => response.id,
+            JsonRpcMessage::Error(error) => error.id,
+            JsonRpcMessage::Request(request) => {
+                // The server waits for the answer before it goes on with the response this
+                // request came in, so nothing is held up by answering it here
+                let response = answer_request(self.request_handler.clone(), request.clone()).await;
+                let session_id = self.session_id.read().await.clone();
+                match self.post(&response, session_id.as_deref()).await {
+                    Ok(sent) if sent.status().is_success() => {}
+                    Ok(sent) => warn!(
+                        "Server refused the answer to its request: {}",
+                        sent.status()
+                    ),
+                    Err(e) => warn!("Failed to answer the server's request: {e}"),
+                }
+                return;
+            }
+            JsonRpcMessage::Notification(notification) => {
+                forward_notification(self.request_handler.clone(), notification.clone()).await;
+                return;
+            }
+            _ => None,
+        };
+        if let Some(id) = id {
+            self.pending_requests
+                .respond(&id.to_string(), Ok(message))
+                .await;
+        }
+    }
+}
+
+fn is_initialize(message: &JsonRpcMessage) -> bool {
+    matches!(message, JsonRpcMessage::Request(request) if request.method == "initialize")
+}
+
+fn request_id(message: &JsonRpcMessage) -> Option<String> {
+    match message {
+        JsonRpcMessage::Request(JsonRpcRequest { id: Some(id), .. }) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// An event of a `text/event-stream` body
+#[derive(Debug, Default)]
+struct Event {
+    event: String,
+    data: String,
+    id: Option<String>,
+}
+
+/// Splits the chunks of a `text/event-stream` body into events, which can span chunks
+#[derive(Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    event: Event,
+}
+
+impl EventParser {
+    /// Add `chunk` to what has been read, returning the events it completes
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            // A blank line ends the event
+            if line.is_empty() {
+                let event = std::mem::take(&mut self.event);
+                if !event.data.is_empty() || event.id.is_some() {
+                    events.push(event);
+                }
+                continue;
+            }
+            // Lines starting with a colon are comments, which servers send to keep streams open
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "event" => self.event.event = value.to_string(),
+                "data" => {
+                    if !self.event.data.is_empty() {
+                        self.event.data.push('\n');
+                    }
+                    self.event.data.push_str(value);
+                }
+                "id" => self.event.id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// The actor that takes messages from the handle and POSTs each to the server's endpoint,
+/// in a task of its own so that a long streamed response doesn't hold up the others
+pub struct StreamableHttpActor {
+    /// Receives messages (requests/notifications) from the handle
+    receiver: mpsc::Receiver<TransportMessage>,
+    connection: Arc<Connection>,
+}
+
+impl StreamableHttpActor {
+    /// - If it's a request, store the oneshot in `pending_requests`.
+    /// - If it's part of the handshake, keep it to send again for a new session.
+    /// - POST the message, failing its request when no response comes back.
+    pub async fn run(mut self) {
+        while let Some(transport_msg) = self.receiver.recv().await {
+            let message = transport_msg.message;
+            let id = request_id(&message);
+            if let (Some(id), Some(response_tx)) = (&id, transport_msg.response_tx) {
+                self.connection
+                    .pending_requests
+                    .insert(id.clone(), response_tx)
+                    .await;
+            }
+
+            match &message {
+                JsonRpcMessage::Request(request) if request.method == "initialize" => {
+                    *self.connection.handshake.write().await = vec![message.clone()];
+                    // Initializing starts a new session
+                    *self.connection.session_id.write().await = None;
+                }
+                JsonRpcMessage::Notification(notification)
+                    if notification.method == "notifications/initialized" =>
+                {
+                    self.connection
+                        .handshake
+                        .write()
+                        .await
+                        .push(message.clone());
+                }
+                _ => {}
+            }
+
+            let connection = Arc::clone(&self.connection);
+            tokio::spawn(async move {
+                let result = connection.send(&message).await;
+                if let Some(id) = id {
+                    // Nothing is left to respond to when the response came back
+                    let result = result.and(Err(Error::StreamableHttp(
+                        "The server closed the response without answering".to_string(),
+                    )));
+                    connection.pending_requests.respond(&id, result).await;
+                } else if let Err(e) = result {
+                    warn!("Failed to send notification: {e}");
+                }
+            });
+        }
+
+        // mpsc channel closed => no more outgoing messages
+        tracing::error!(
+            "StreamableHttpActor: outgoing message loop ended. Clearing pending requests."
+        );
+        self.connection.pending_requests.clear().await;
+    }
+}
+
+#[derive(Clone)]
+pub struct StreamableHttpTransportHandle {
+    sender: mpsc::Sender<TransportMessage>,
+}
+
+#[async_trait::async_trait]
+impl TransportHandle for StreamableHttpTransportHandle {
+    async fn send(&self, message: JsonRpcMessage) -> Result<JsonRpcMessage, Error> {
+        send_message(&self.sender, message).await
+    }
+}
+
+/// A transport to a server's single streamable HTTP endpoint, which answers each POSTed
+/// message with JSON or a stream of events, and can keep a session across requests
+#[derive(Clone)]
+pub struct StreamableHttpTransport {
+    url: String,
+    env: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    /// The connection of the started transport, whose session is ended on `close()`
+    connection: Arc<Mutex<Option<Arc<Connection>>>>,
+    request_handler: Option<Arc<dyn RequestHandler>>,
+}
+
+/// The streamable HTTP transport spawns a `StreamableHttpActor` on `start()`.
+impl StreamableHttpTransport {
+    pub fn new<S: Into<String>>(url: S, env: HashMap<String, String>) -> Self {
+        Self {
+            url: url.into(),
+            env,
+            headers: HashMap::new(),
+            connection: Arc::new(Mutex::new(None)),
+            request_handler: None,
+        }
+    }
+
+    /// Send `headers`, such as `Authorization`, with each request
+    pub fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Answer the requests the server sends with `handler`
+    pub fn with_request_handler(mut self, handler: Arc<dyn RequestHandler>) -> Self {
+        self.request_handler = Some(handler);
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for StreamableHttpTransport {
+    type Handle = StreamableHttpTransportHandle;
+
+    async fn start(&self) -> Result<Self::Handle, Error> {
+        // Set environment variables
+        for (key, value) in &self.env {
+            std::env::set_var(key, value);
+        }
+
+        let http_client = super::http_client(&self.headers).map_err(Error::StreamableHttp)?;
+        let connection = Arc::new(Connection {
+            url: self.url.clone(),
+            http_client,
+            pending_requests: PendingRequests::new(),
+            session_id: RwLock::new(None),
+            handshake: RwLock::new(Vec::new()),
+            reinitializing: Mutex::new(()),
+            request_handler: self.request_handler.clone(),
+        });
+        *self.connection.lock().await = Some(Arc::clone(&connection));
+
+        // There is no stream to open first: the server is reached with the initialize request
+        let (tx, rx) = mpsc::channel(32);
+        let actor = StreamableHttpActor {
+            receiver: rx,
+            connection,
+        };
+        tokio::spawn(actor.run());
+
+        Ok(StreamableHttpTransportHandle { sender: tx })
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let Some(connection) = self.connection.lock().await.take() else {
+            return Ok(());
+        };
+        let Some(session_id) = connection.session_id.read().await.clone() else {
+            return Ok(());
+        };
+        // Tell the server the session is over; one that doesn't let clients end sessions
+        // answers 405, which leaves it to expire
+        let response = connection
+            .http_client
+            .delete(&connection.url)
+            .header(SESSION_ID_HEADER, session_id)
+            .send()
+            .await
+            .map_err(|e| Error::StreamableHttp(e.to_string()))?;
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+            warn!(
+                "Failed to end the session of {}: {}",
+                connection.url, status
+            );
+        }
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     prompt::{Prompt, PromptMessage},
     resource::Resource,
     resource::ResourceContents,
+    role::Role,
     tool::Tool,
 };
 use serde::{Deserialize, Serialize};
@@ -237,6 +238,91 @@ pub struct GetPromptResult {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EmptyResult {}
 
+/// The method a server asks the client's model for a completion with
+pub const CREATE_MESSAGE_METHOD: &str = "sampling/createMessage";
+
+/// A server's request for the client's model to answer a conversation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<ModelPreferences>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Which conversations the server would like included: `none`, `thisServer` or `allServers`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    pub max_tokens: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: Content,
+}
+
+/// What the server would like from the model, which the client is free to weigh as it likes
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPreferences {
+    /// Models to prefer, in order, each named by a part of its name like `claude` or `sonnet`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<ModelHint>,
+    /// From 0 to 1, how much a cheap model is preferred
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_priority: Option<f32>,
+    /// From 0 to 1, how much a fast model is preferred
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_priority: Option<f32>,
+    /// From 0 to 1, how much a capable model is preferred
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intelligence_priority: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ModelHint {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// The model's answer to a [`CreateMessageParams`]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: Content,
+    /// The model that answered
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+pub const PROGRESS_NOTIFICATION: &str = "notifications/progress";
+
+/// How far a request that asked for progress with a `_meta.progressToken` has got
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressNotificationParams {
+    /// The token of the request, a string or a number
+    pub progress_token: Value,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    /// What the request is doing, such as the last line a command printed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Sent by a server whose tools changed, for the client to list them again
+pub const TOOLS_LIST_CHANGED_NOTIFICATION: &str = "notifications/tools/list_changed";
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,4 +372,46 @@ mod tests {
             _ => panic!("Expected Request"),
         }
     }
+
+    #[test]
+    fn test_create_message_params() {
+        let params: CreateMessageParams = serde_json::from_value(json!({
+            "messages": [
+                {"role": "user", "content": {"type": "text", "text": "Summarize the issue"}}
+            ],
+            "modelPreferences": {
+                "hints": [{"name": "claude-3-haiku"}],
+                "speedPriority": 0.8
+            },
+            "systemPrompt": "You are a helpful assistant.",
+            "maxTokens": 100
+        }))
+        .unwrap();
+        assert_eq!(params.messages[0].role, Role::User);
+        assert_eq!(
+            params.messages[0].content,
+            Content::text("Summarize the issue")
+        );
+        let preferences = params.model_preferences.unwrap();
+        assert_eq!(preferences.hints[0].name.as_deref(), Some("claude-3-haiku"));
+        assert_eq!(preferences.speed_priority, Some(0.8));
+        assert_eq!(preferences.cost_priority, None);
+        assert_eq!(params.max_tokens, 100);
+
+        let result = CreateMessageResult {
+            role: Role::Assistant,
+            content: Content::text("The build fails on Windows"),
+            model: "gpt-4o".to_string(),
+            stop_reason: Some("endTurn".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(result).unwrap(),
+            json!({
+                "role": "assistant",
+                "content": {"type": "text", "text": "The build fails on Windows"},
+                "model": "gpt-4o",
+                "stopReason": "endTurn"
+            })
+        );
+    }
 }
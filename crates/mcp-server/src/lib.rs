@@ -1,17 +1,22 @@
 use std::{
+    collections::VecDeque,
     pin::Pin,
     task::{Context, Poll},
 };
 
 use futures::{Future, Stream};
-use mcp_core::protocol::{JsonRpcError, JsonRpcMessage, JsonRpcRequest, JsonRpcResponse};
+use mcp_core::protocol::{
+    JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+};
 use pin_project::pin_project;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
 use tower_service::Service;
 
 mod errors;
 pub use errors::{BoxError, RouterError, ServerError, TransportError};
 
+pub mod progress;
 pub mod router;
 pub use router::Router;
 
@@ -139,9 +144,20 @@ where
     {
         use futures::StreamExt;
         let mut service = self.service;
+        // Messages that arrived while a request was running, handled once it's done
+        let mut queued = VecDeque::new();
+        // What the running tool reports, sent while it runs
+        let (notifications, mut progress) = mpsc::channel(progress::PROGRESS_BUFFER);
 
         tracing::info!("Server started");
-        while let Some(msg_result) = transport.next().await {
+        loop {
+            let msg_result = match queued.pop_front() {
+                Some(msg_result) => msg_result,
+                None => match transport.next().await {
+                    Some(msg_result) => msg_result,
+                    None => break,
+                },
+            };
             let _span = tracing::span!(tracing::Level::INFO, "message_processing").entered();
             match msg_result {
                 Ok(msg) => {
@@ -159,8 +175,60 @@ where
                                 "Received request"
                             );
 
-                            // Process the request using our service
-                            let response = match service.call(request).await {
+                            // Process the request using our service, while watching for the
+                            // client cancelling it, in which case it is dropped without a response,
+                            // and sending the progress the tool reports
+                            let token = progress::progress_token(&request);
+                            let call = progress::with_reporter(
+                                token,
+                                notifications.clone(),
+                                service.call(request),
+                            );
+                            tokio::pin!(call);
+                            let result = loop {
+                                tokio::select! {
+                                    result = &mut call => break Some(result),
+                                    Some(notification) = progress.recv() => {
+                                        if let Err(e) = transport
+                                            .write_message(JsonRpcMessage::Notification(notification))
+                                            .await
+                                        {
+                                            return Err(ServerError::Transport(TransportError::Io(e)));
+                                        }
+                                    }
+                                    msg_result = transport.next() => match msg_result {
+                                        Some(Ok(JsonRpcMessage::Notification(notification)))
+                                            if cancelled_request(&notification).is_some() =>
+                                        {
+                                            let cancelled = cancelled_request(&notification);
+                                            if cancelled == id {
+                                                break None;
+                                            }
+                                            queued.retain(|msg_result| !matches!(
+                                                msg_result,
+                                                Ok(JsonRpcMessage::Request(request)) if request.id == cancelled
+                                            ));
+                                        }
+                                        Some(msg_result) => queued.push_back(msg_result),
+                                        // The client is gone, so is whoever wanted the result
+                                        None => return Ok(()),
+                                    },
+                                }
+                            };
+                            // Reports that came in with the end of the call go before its response
+                            while let Ok(notification) = progress.try_recv() {
+                                if let Err(e) = transport
+                                    .write_message(JsonRpcMessage::Notification(notification))
+                                    .await
+                                {
+                                    return Err(ServerError::Transport(TransportError::Io(e)));
+                                }
+                            }
+                            let Some(result) = result else {
+                                tracing::info!(request_id = ?id, "Request cancelled");
+                                continue;
+                            };
+                            let response = match result {
                                 Ok(resp) => resp,
                                 Err(e) => {
                                     let error_msg = e.into().to_string();
@@ -243,6 +311,14 @@ where
     }
 }
 
+/// The id of the request a `notifications/cancelled` notification cancels
+fn cancelled_request(notification: &JsonRpcNotification) -> Option<u64> {
+    if notification.method != "notifications/cancelled" {
+        return None;
+    }
+    notification.params.as_ref()?.get("requestId")?.as_u64()
+}
+
 // Define a specific service implementation that we need for any
 // Any router implements this
 pub trait BoundedService:
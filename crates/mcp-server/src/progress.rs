@@ -0,0 +1,79 @@
+//! Progress notifications from the tool that is running
+//!
+//! A client that wants to hear how a long request is getting on sends a `_meta.progressToken`
+//! with it. While the server handles such a request, [`report`] sends the client a
+//! `notifications/progress` with that token, so a tool can pass on each line a command prints
+//! rather than only its whole output at the end. Outside such a request it does nothing. Reports
+//! are dropped while [`PROGRESS_BUFFER`] of them wait to be sent, so a tool that reports faster
+//! than the client reads can't hold up the server or use up its memory.
+use mcp_core::protocol::{
+    JsonRpcNotification, JsonRpcRequest, ProgressNotificationParams, PROGRESS_NOTIFICATION,
+};
+use serde_json::Value;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// How many reports can wait to be sent
+pub(crate) const PROGRESS_BUFFER: usize = 64;
+
+tokio::task_local! {
+    static REPORTER: Reporter;
+}
+
+#[derive(Clone)]
+struct Reporter {
+    token: Value,
+    /// How many reports were sent, which is the progress when a tool doesn't count its own
+    sent: Arc<AtomicU64>,
+    notifications: mpsc::Sender<JsonRpcNotification>,
+}
+
+/// The progress token the client sent with `request`, if any
+pub(crate) fn progress_token(request: &JsonRpcRequest) -> Option<Value> {
+    request
+        .params
+        .as_ref()?
+        .get("_meta")?
+        .get("progressToken")
+        .cloned()
+}
+
+/// Handle a request with `handling`, sending what the tool reports to `notifications` with
+/// `token`, when the client sent one
+pub(crate) async fn with_reporter<F: Future>(
+    token: Option<Value>,
+    notifications: mpsc::Sender<JsonRpcNotification>,
+    handling: F,
+) -> F::Output {
+    match token {
+        Some(token) => {
+            let reporter = Reporter {
+                token,
+                sent: Arc::new(AtomicU64::new(0)),
+                notifications,
+            };
+            REPORTER.scope(reporter, handling).await
+        }
+        None => handling.await,
+    }
+}
+
+/// Tell the client what the running request is doing, such as the line a command just printed
+pub fn report(message: impl Into<String>) {
+    let _ = REPORTER.try_with(|reporter| {
+        let progress = reporter.sent.fetch_add(1, Ordering::Relaxed) + 1;
+        let params = ProgressNotificationParams {
+            progress_token: reporter.token.clone(),
+            progress: progress as f64,
+            total: None,
+            message: Some(message.into()),
+        };
+        let _ = reporter.notifications.try_send(JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: PROGRESS_NOTIFICATION.to_string(),
+            params: serde_json::to_value(params).ok(),
+        });
+    });
+}
@@ -418,6 +418,11 @@ where
                 "resources/read" => this.handle_resources_read(req).await,
                 "prompts/list" => this.handle_prompts_list(req).await,
                 "prompts/get" => this.handle_prompts_get(req).await,
+                "ping" => {
+                    let mut response = this.create_response(req.id);
+                    response.result = Some(serde_json::json!({}));
+                    Ok(response)
+                }
                 _ => {
                     let mut response = this.create_response(req.id);
                     response.error = Some(RouterError::MethodNotFound(req.method).into());
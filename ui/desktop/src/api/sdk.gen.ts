@@ -1,7 +1,7 @@
 // This file is auto-generated by @hey-api/openapi-ts
 
 import type { Options as ClientOptions, TDataShape, Client } from '@hey-api/client-fetch';
-import type { ReadAllConfigData, ReadAllConfigResponse, GetExtensionsData, GetExtensionsResponse, AddExtensionData, AddExtensionResponse, RemoveExtensionData, RemoveExtensionResponse, ProvidersData, ProvidersResponse2, ReadConfigData, RemoveConfigData, RemoveConfigResponse, UpsertConfigData, UpsertConfigResponse } from './types.gen';
+import type { ReadAllConfigData, ReadAllConfigResponse, GetExtensionsData, GetExtensionsResponse, AddExtensionData, AddExtensionResponse, RemoveExtensionData, RemoveExtensionResponse, ProvidersData, ProvidersResponse2, ProviderHealthData, ProviderHealthResponse, ProviderModelsData, ProviderModelsResponse, ReadConfigData, RemoveConfigData, RemoveConfigResponse, UpsertConfigData, UpsertConfigResponse } from './types.gen';
 import { client as _heyApiClient } from './client.gen';
 
 export type Options<TData extends TDataShape = TDataShape, ThrowOnError extends boolean = boolean> = ClientOptions<TData, ThrowOnError> & {
@@ -57,6 +57,20 @@ export const providers = <ThrowOnError extends boolean = false>(options?: Option
     });
 };
 
+export const providerHealth = <ThrowOnError extends boolean = false>(options: Options<ProviderHealthData, ThrowOnError>) => {
+    return (options.client ?? _heyApiClient).get<ProviderHealthResponse, unknown, ThrowOnError>({
+        url: '/config/providers/{name}/health',
+        ...options
+    });
+};
+
+export const providerModels = <ThrowOnError extends boolean = false>(options: Options<ProviderModelsData, ThrowOnError>) => {
+    return (options.client ?? _heyApiClient).get<ProviderModelsResponse, unknown, ThrowOnError>({
+        url: '/config/providers/{name}/models',
+        ...options
+    });
+};
+
 export const readConfig = <ThrowOnError extends boolean = false>(options: Options<ReadConfigData, ThrowOnError>) => {
     return (options.client ?? _heyApiClient).post<unknown, unknown, ThrowOnError>({
         url: '/config/read',
@@ -26,6 +26,12 @@ export type Envs = {
 export type ExtensionConfig = {
     description?: string | null;
     envs?: Envs;
+    /**
+     * Headers sent with each request to the server, such as `Authorization`
+     */
+    headers?: {
+        [key: string]: string;
+    };
     /**
      * The name used to identify this extension
      */
@@ -33,6 +39,22 @@ export type ExtensionConfig = {
     timeout?: number | null;
     type: 'sse';
     uri: string;
+} | {
+    description?: string | null;
+    envs?: Envs;
+    /**
+     * Headers sent with each request to the server, such as `Authorization`
+     */
+    headers?: {
+        [key: string]: string;
+    };
+    /**
+     * The name used to identify this extension
+     */
+    name: string;
+    timeout?: number | null;
+    type: 'streamable_http';
+    uri: string;
 } | {
     args: Array<string>;
     cmd: string;
@@ -70,6 +92,37 @@ export type ExtensionResponse = {
     extensions: Array<ExtensionEntry>;
 };
 
+/**
+ * The outcome of a provider health check
+ */
+export type HealthStatus = {
+    status: 'healthy';
+} | {
+    /**
+     * The provider rejected the API key or other credentials
+     */
+    message: string;
+    status: 'invalid_credentials';
+} | {
+    /**
+     * The provider's host couldn't be reached
+     */
+    message: string;
+    status: 'unreachable';
+} | {
+    /**
+     * The provider doesn't serve the configured model
+     */
+    message: string;
+    status: 'model_not_found';
+} | {
+    /**
+     * The check failed for another reason
+     */
+    message: string;
+    status: 'unhealthy';
+};
+
 export type ProviderDetails = {
     /**
      * Indicates whether the provider is fully configured
@@ -238,6 +291,67 @@ export type ProvidersResponses = {
 
 export type ProvidersResponse2 = ProvidersResponses[keyof ProvidersResponses];
 
+export type ProviderHealthData = {
+    body?: never;
+    path: {
+        /**
+         * Provider name
+         */
+        name: string;
+    };
+    query?: {
+        /**
+         * Model to check
+         */
+        model?: string | null;
+    };
+    url: '/config/providers/{name}/health';
+};
+
+export type ProviderHealthErrors = {
+    /**
+     * Provider not found
+     */
+    404: unknown;
+};
+
+export type ProviderHealthResponses = {
+    /**
+     * Result of the provider health check
+     */
+    200: HealthStatus;
+};
+
+export type ProviderHealthResponse = ProviderHealthResponses[keyof ProviderHealthResponses];
+
+export type ProviderModelsData = {
+    body?: never;
+    path: {
+        /**
+         * Provider name
+         */
+        name: string;
+    };
+    query?: never;
+    url: '/config/providers/{name}/models';
+};
+
+export type ProviderModelsErrors = {
+    /**
+     * Provider not found
+     */
+    404: unknown;
+};
+
+export type ProviderModelsResponses = {
+    /**
+     * Models offered by the provider
+     */
+    200: Array<string>;
+};
+
+export type ProviderModelsResponse = ProviderModelsResponses[keyof ProviderModelsResponses];
+
 export type ReadConfigData = {
     body: ConfigKeyQuery;
     path?: never;
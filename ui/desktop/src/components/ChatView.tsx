@@ -65,6 +65,7 @@ export default function ChatView({
     append,
     stop,
     isLoading,
+    toolProgress,
     error,
     setMessages,
     input: _input,
@@ -435,7 +436,7 @@ export default function ChatView({
         )}
 
         <div className="relative">
-          {isLoading && <LoadingGoose />}
+          {isLoading && <LoadingGoose message={toolProgress} />}
           <Input
             handleSubmit={handleSubmit}
             isLoading={isLoading}
@@ -1,12 +1,17 @@
 import React from 'react';
 import GooseLogo from './GooseLogo';
 
-const LoadingGoose = () => {
+interface LoadingGooseProps {
+  // What the tool that is running last reported, shown in place of the usual message
+  message?: string;
+}
+
+const LoadingGoose = ({ message }: LoadingGooseProps) => {
   return (
     <div className="w-full pb-[2px]">
       <div className="flex items-center text-xs text-textStandard mb-2 mt-2 pl-4 animate-[appear_250ms_ease-in_forwards]">
         <GooseLogo className="mr-2" size="small" hover={false} />
-        goose is working on it…
+        <span className="truncate">{message || 'goose is working on it…'}</span>
       </div>
     </div>
   );
@@ -9,6 +9,13 @@ const TextDecoder = globalThis.TextDecoder;
 // Event types for SSE stream
 type MessageEvent =
   | { type: 'Message'; message: Message }
+  | {
+      type: 'Progress';
+      tool: string;
+      progress: number;
+      total: number | null;
+      message: string | null;
+    }
   | { type: 'Error'; error: string }
   | { type: 'Finish'; reason: string };
 
@@ -119,6 +126,9 @@ export interface UseMessageStreamHelpers {
   /** Whether the API request is in progress */
   isLoading: boolean;
 
+  /** What the tool that is running last reported, such as the lines a command printed */
+  toolProgress: string | undefined;
+
   /** Add a tool result to a tool call */
   addToolResult: ({ toolCallId, result }: { toolCallId: string; result: unknown }) => void;
 }
@@ -165,6 +175,9 @@ export function useMessageStream({
     null
   );
 
+  // What the tool that is running last reported, cleared once it is done
+  const [toolProgress, setToolProgress] = useState<string | undefined>(undefined);
+
   // Abort controller to cancel the current API call
   const abortControllerRef = useRef<AbortController | null>(null);
 
@@ -219,12 +232,18 @@ export function useMessageStream({
                     // Update messages with the new message
                     currentMessages = [...currentMessages, parsedEvent.message];
                     mutate(currentMessages, false);
+                    setToolProgress(undefined);
+                    break;
+
+                  case 'Progress':
+                    setToolProgress(parsedEvent.message ?? undefined);
                     break;
 
                   case 'Error':
                     throw new Error(parsedEvent.error);
 
                   case 'Finish':
+                    setToolProgress(undefined);
                     // Call onFinish with the last message if available
                     if (onFinish && currentMessages.length > 0) {
                       const lastMessage = currentMessages[currentMessages.length - 1];
@@ -335,6 +354,7 @@ export function useMessageStream({
 
         setError(err as Error);
       } finally {
+        setToolProgress(undefined);
         mutateLoading(false);
       }
     },
@@ -485,6 +505,7 @@ export function useMessageStream({
     handleInputChange,
     handleSubmit,
     isLoading: isLoading || false,
+    toolProgress,
     addToolResult,
   };
 }
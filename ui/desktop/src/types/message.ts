@@ -80,6 +80,8 @@ export interface Message {
   role: Role;
   created: number;
   content: MessageContent[];
+  // Values integrations attach to correlate the message with other systems
+  metadata?: Record<string, unknown>;
 }
 
 // Helper functions to create messages